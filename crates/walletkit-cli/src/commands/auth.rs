@@ -77,13 +77,17 @@ pub async fn register_and_poll(
                 eyre::bail!("registration failed: {error} (code: {error_code:?})");
             }
             _ => {
+                let wait = match status {
+                    RegistrationStatus::SponsorshipExhausted {
+                        retry_after: Some(retry_after),
+                    } => retry_after.max(poll_interval),
+                    _ => poll_interval,
+                };
                 let status_str = format!("{status:?}");
                 if !cli.json {
-                    eprintln!(
-                        "Status: {status_str} — polling again in {poll_interval}s..."
-                    );
+                    eprintln!("Status: {status_str} — polling again in {wait}s...");
                 }
-                tokio::time::sleep(std::time::Duration::from_secs(poll_interval)).await;
+                tokio::time::sleep(std::time::Duration::from_secs(wait)).await;
             }
         }
     }
@@ -0,0 +1,201 @@
+#ifndef WALLETKIT_H
+#define WALLETKIT_H
+
+/* Generated by cbindgen from crates/walletkit-core/src/c_ffi.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Status codes returned across the C boundary.
+ */
+typedef enum WalletKitStatus {
+  /**
+   * The call succeeded.
+   */
+  WALLET_KIT_STATUS_OK = 0,
+  /**
+   * A required pointer was `NULL` or a string was not valid UTF-8.
+   */
+  WALLET_KIT_STATUS_INVALID_ARGUMENT = 1,
+  /**
+   * The input was rejected by `WalletKit` (e.g. malformed config or request).
+   */
+  WALLET_KIT_STATUS_INVALID_INPUT = 2,
+  /**
+   * Credential storage failed.
+   */
+  WALLET_KIT_STATUS_STORAGE = 3,
+  /**
+   * A network request failed.
+   */
+  WALLET_KIT_STATUS_NETWORK = 4,
+  /**
+   * Proof generation failed.
+   */
+  WALLET_KIT_STATUS_PROOF = 5,
+  /**
+   * Any other `WalletKit` error.
+   */
+  WALLET_KIT_STATUS_OTHER = 6,
+  /**
+   * The library panicked. The handle should be considered unusable.
+   *
+   * Only reported by builds with `panic = "unwind"`; see the module docs.
+   */
+  WALLET_KIT_STATUS_PANIC = 7,
+} WalletKitStatus;
+
+/**
+ * Opaque authenticator handle.
+ */
+typedef struct WalletKitAuthenticator WalletKitAuthenticator;
+
+/**
+ * Signature of the [`WalletKitKeystore`] `seal` / `open` callbacks.
+ */
+typedef int32_t (*WalletKitKeystoreFn)(void *user_data,
+                                       const uint8_t *ad,
+                                       size_t ad_len,
+                                       const uint8_t *input,
+                                       size_t input_len,
+                                       uint8_t **out,
+                                       size_t *out_len);
+
+/**
+ * Device keystore callbacks supplied by the C caller.
+ *
+ * `seal` and `open` write a buffer allocated by the caller to `out` / `out_len`
+ * and return `0` on success. The library releases that buffer through
+ * `free_buffer`. The callbacks may be invoked from any thread.
+ */
+typedef struct WalletKitKeystore {
+  /**
+   * Opaque pointer passed back to every callback.
+   */
+  void *user_data;
+  /**
+   * Seals `input` under the device-bound key, authenticating `ad`.
+   */
+  WalletKitKeystoreFn seal;
+  /**
+   * Opens `input` under the device-bound key, verifying `ad`.
+   */
+  WalletKitKeystoreFn open;
+  /**
+   * Releases a buffer returned by `seal` or `open`.
+   */
+  void (*free_buffer)(void *user_data, uint8_t *buf, size_t len);
+} WalletKitKeystore;
+
+/**
+ * Callback invoked when an asynchronous call completes.
+ *
+ * On success `status` is [`WalletKitStatus::Ok`] and `result` holds the JSON
+ * response; otherwise `result` holds the error message. Either way `result` is
+ * owned by the caller and must be released with [`walletkit_free_string`].
+ */
+typedef void (*WalletKitCallback)(void *user_data, enum WalletKitStatus status, char *result);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Initializes an authenticator for an already-registered World ID.
+ *
+ * `config_json` is the same configuration accepted by `Authenticator::init`.
+ * Credentials and caches are stored under `storage_root`; Groth16 material is
+ * loaded from the embedded zkeys when built with `embed-zkeys`, otherwise from
+ * the cache under `storage_root`.
+ *
+ * On success writes a new handle to `out` and returns [`WalletKitStatus::Ok`].
+ *
+ * # Safety
+ *
+ * `seed` must point to `seed_len` readable bytes; `config_json` and
+ * `storage_root` must be NUL-terminated strings; `keystore` and `out` must be
+ * valid pointers.
+ */
+enum WalletKitStatus walletkit_authenticator_init(const uint8_t *seed,
+                                                  size_t seed_len,
+                                                  const char *config_json,
+                                                  const char *storage_root,
+                                                  const struct WalletKitKeystore *keystore,
+                                                  struct WalletKitAuthenticator **out);
+
+/**
+ * Releases an authenticator handle. `NULL` is a no-op.
+ *
+ * # Safety
+ *
+ * `handle` must be `NULL` or a handle returned by [`walletkit_authenticator_init`]
+ * that has not been freed yet.
+ */
+void walletkit_authenticator_free(struct WalletKitAuthenticator *handle);
+
+/**
+ * Generates a proof for a JSON proof request, blocking until it completes.
+ *
+ * `now` is the current time in seconds since the UNIX epoch; pass `0` to use the
+ * system clock. Returns the JSON proof response, or `NULL` on error.
+ *
+ * # Safety
+ *
+ * `handle` must be a live handle and `request_json` a NUL-terminated string.
+ */
+char *walletkit_generate_proof_json(const struct WalletKitAuthenticator *handle,
+                                    const char *request_json,
+                                    uint64_t now);
+
+/**
+ * Generates a proof for a JSON proof request without blocking.
+ *
+ * Unless `callback` is `NULL`, it is invoked exactly once with `user_data` and
+ * the outcome. Returns [`WalletKitStatus::Ok`] if the request was scheduled,
+ * in which case `callback` runs later on a library-owned thread. If the
+ * request is rejected up front, the error is recorded as the last error,
+ * returned, and also delivered to `callback` on the calling thread before
+ * this function returns.
+ *
+ * # Safety
+ *
+ * `handle` must be a live handle, `request_json` a NUL-terminated string, and
+ * `user_data` must be safe to use from another thread.
+ */
+enum WalletKitStatus walletkit_generate_proof_json_async(const struct WalletKitAuthenticator *handle,
+                                                         const char *request_json,
+                                                         uint64_t now,
+                                                         WalletKitCallback callback,
+                                                         void *user_data);
+
+/**
+ * Releases a string returned by this library. `NULL` is a no-op.
+ *
+ * # Safety
+ *
+ * `s` must be `NULL` or a string returned by this library that has not been
+ * freed yet.
+ */
+void walletkit_free_string(char *s);
+
+/**
+ * Returns the status of the last failed call on this thread, or
+ * [`WalletKitStatus::Ok`] if the last call succeeded.
+ */
+enum WalletKitStatus walletkit_last_error_code(void);
+
+/**
+ * Returns the message of the last failed call on this thread, or `NULL` if the
+ * last call succeeded. Release it with [`walletkit_free_string`].
+ */
+char *walletkit_last_error_message(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* WALLETKIT_H */
@@ -0,0 +1,107 @@
+//! Allocation accounting for [`ProofStats`](crate::ProofStats).
+//!
+//! [`CountingAllocator`] wraps the system allocator and tracks the bytes live
+//! on each thread together with their high-water mark. It only measures
+//! anything once installed as the global allocator, which this crate does for
+//! its own tests; other binaries opt in with the `alloc-stats` feature:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOC: walletkit_core::alloc_stats::CountingAllocator =
+//!     walletkit_core::alloc_stats::CountingAllocator;
+//! ```
+//!
+//! Counts are per thread so that concurrent work does not skew a measurement.
+//! Memory freed on a different thread than it was allocated on is not
+//! credited back, so figures are estimates.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static LIVE: Cell<usize> = const { Cell::new(0) };
+    static PEAK: Cell<usize> = const { Cell::new(0) };
+}
+
+fn record_alloc(size: usize) {
+    let _ = LIVE.try_with(|live| {
+        let now = live.get().saturating_add(size);
+        live.set(now);
+        let _ = PEAK.try_with(|peak| peak.set(peak.get().max(now)));
+    });
+}
+
+fn record_dealloc(size: usize) {
+    let _ = LIVE.try_with(|live| live.set(live.get().saturating_sub(size)));
+}
+
+/// Global allocator that counts live bytes per thread.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CountingAllocator;
+
+// SAFETY: every call is forwarded unchanged to `System`; the bookkeeping only
+// touches const-initialized thread locals, which never allocate.
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        INSTALLED.store(true, Ordering::Relaxed);
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        INSTALLED.store(true, Ordering::Relaxed);
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        record_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            record_dealloc(layout.size());
+            record_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Measures the peak bytes allocated on the current thread since the
+/// tracker was started.
+#[derive(Debug)]
+pub struct PeakTracker {
+    baseline: usize,
+}
+
+impl PeakTracker {
+    /// Starts a measurement, resetting the thread's high-water mark.
+    #[must_use]
+    pub fn start() -> Self {
+        let baseline = LIVE.try_with(Cell::get).unwrap_or(0);
+        let _ = PEAK.try_with(|peak| peak.set(baseline));
+        Self { baseline }
+    }
+
+    /// Returns the peak bytes allocated above the starting point, or `None`
+    /// if [`CountingAllocator`] is not the global allocator.
+    #[must_use]
+    pub fn peak_bytes(&self) -> Option<u64> {
+        if !INSTALLED.load(Ordering::Relaxed) {
+            return None;
+        }
+        let peak = PEAK.try_with(Cell::get).unwrap_or(self.baseline);
+        Some(peak.saturating_sub(self.baseline) as u64)
+    }
+}
@@ -0,0 +1,209 @@
+//! Privacy-preserving export of credential metadata for aggregate analytics.
+//!
+//! Only metadata is exported, never credential payloads or blinding factors.
+//! Credential IDs are pseudonymised as the hex encoding of
+//! `SHA256(credential_id_be_u64 || nonce)[..16]`: rows stay joinable across
+//! exports made with the same `nonce`, and unlinkable across nonces.
+//!
+//! Columns, in order:
+//!
+//! | column                | source                                   |
+//! |-----------------------|------------------------------------------|
+//! | `credential_id`       | pseudonymised credential ID              |
+//! | `issuer_schema_id`    | [`CredentialRecord::issuer_schema_id`]   |
+//! | `created_at`          | [`CredentialRecord::genesis_issued_at`]  |
+//! | `expires_at`          | [`CredentialRecord::expires_at`]         |
+//! | `status`              | `active`, `expired` or `invalidated`     |
+//! | `has_associated_data` | [`CredentialRecord::has_associated_data`] |
+
+use std::fmt::Write as _;
+
+use sha2::{Digest, Sha256};
+
+use crate::storage::{CredentialRecord, CredentialStatus};
+
+/// CSV header row, see the [module docs](self).
+const CSV_HEADER: &str =
+    "credential_id,issuer_schema_id,created_at,expires_at,status,has_associated_data";
+
+/// Length of a pseudonymised credential ID, in bytes.
+const PSEUDONYM_LEN: usize = 16;
+
+/// A single exported row.
+struct MetadataRow {
+    credential_id: String,
+    issuer_schema_id: u64,
+    created_at: u64,
+    expires_at: u64,
+    status: &'static str,
+    has_associated_data: bool,
+}
+
+impl MetadataRow {
+    fn new(record: &CredentialRecord, nonce: &[u8]) -> Self {
+        Self {
+            credential_id: pseudonymise(record.credential_id, nonce),
+            issuer_schema_id: record.issuer_schema_id,
+            created_at: record.genesis_issued_at,
+            expires_at: record.expires_at,
+            status: match record.status {
+                CredentialStatus::Active => "active",
+                CredentialStatus::Expired => "expired",
+                CredentialStatus::Invalidated => "invalidated",
+            },
+            has_associated_data: record.has_associated_data,
+        }
+    }
+}
+
+fn pseudonymise(credential_id: u64, nonce: &[u8]) -> String {
+    let digest = Sha256::new()
+        .chain_update(credential_id.to_be_bytes())
+        .chain_update(nonce)
+        .finalize();
+    hex::encode(&digest[..PSEUDONYM_LEN])
+}
+
+/// Exports credential metadata as RFC 4180 CSV (CRLF line endings, header
+/// row first), pseudonymising credential IDs with `nonce`.
+///
+/// No field can contain a comma, quote or line break, so none is quoted.
+#[must_use]
+pub fn export_credential_metadata_csv(
+    records: &[CredentialRecord],
+    nonce: &[u8],
+) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push_str("\r\n");
+    for record in records {
+        let row = MetadataRow::new(record, nonce);
+        // writing to a `String` cannot fail
+        let _ = write!(
+            csv,
+            "{},{},{},{},{},{}\r\n",
+            row.credential_id,
+            row.issuer_schema_id,
+            row.created_at,
+            row.expires_at,
+            row.status,
+            row.has_associated_data,
+        );
+    }
+    csv
+}
+
+/// Exports credential metadata as JSON Lines, one object per record with the
+/// CSV column names as keys, pseudonymising credential IDs with `nonce`.
+#[must_use]
+pub fn export_credential_metadata_json_lines(
+    records: &[CredentialRecord],
+    nonce: &[u8],
+) -> String {
+    let mut json_lines = String::new();
+    for record in records {
+        let row = MetadataRow::new(record, nonce);
+        let line = serde_json::json!({
+            "credential_id": row.credential_id,
+            "issuer_schema_id": row.issuer_schema_id,
+            "created_at": row.created_at,
+            "expires_at": row.expires_at,
+            "status": row.status,
+            "has_associated_data": row.has_associated_data,
+        });
+        // writing to a `String` cannot fail
+        let _ = writeln!(json_lines, "{line}");
+    }
+    json_lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW_ID: u64 = 9_876_543_210_123;
+
+    fn records() -> Vec<CredentialRecord> {
+        vec![
+            CredentialRecord {
+                credential_id: RAW_ID,
+                issuer_schema_id: 100,
+                genesis_issued_at: 1_700_000_000,
+                expires_at: 1_800_000_000,
+                is_expired: false,
+                recovery_counter: 0,
+                status: CredentialStatus::Active,
+                has_associated_data: true,
+                use_count: 0,
+                last_used_at: None,
+            },
+            CredentialRecord {
+                credential_id: RAW_ID + 1,
+                issuer_schema_id: 200,
+                genesis_issued_at: 1_600_000_000,
+                expires_at: 1_650_000_000,
+                is_expired: true,
+                recovery_counter: 1,
+                status: CredentialStatus::Expired,
+                has_associated_data: false,
+                use_count: 0,
+                last_used_at: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_csv_format() {
+        let csv = export_credential_metadata_csv(&records(), b"nonce");
+        let lines = csv.split_terminator("\r\n").collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(csv.ends_with("\r\n"));
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            format!(
+                "{},100,1700000000,1800000000,active,true",
+                pseudonymise(RAW_ID, b"nonce")
+            )
+        );
+        assert!(lines[2].ends_with(",200,1600000000,1650000000,expired,false"));
+        assert_eq!(lines[1].split(',').next().unwrap().len(), 2 * PSEUDONYM_LEN);
+    }
+
+    #[test]
+    fn test_json_lines_format() {
+        let json_lines = export_credential_metadata_json_lines(&records(), b"nonce");
+        let rows = json_lines
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0],
+            serde_json::json!({
+                "credential_id": pseudonymise(RAW_ID, b"nonce"),
+                "issuer_schema_id": 100,
+                "created_at": 1_700_000_000u64,
+                "expires_at": 1_800_000_000u64,
+                "status": "active",
+                "has_associated_data": true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_raw_credential_ids_are_not_exported() {
+        let csv = export_credential_metadata_csv(&records(), b"nonce");
+        let json_lines = export_credential_metadata_json_lines(&records(), b"nonce");
+        for raw in [RAW_ID, RAW_ID + 1] {
+            for encoded in [raw.to_string(), hex::encode(raw.to_be_bytes())] {
+                assert!(!csv.contains(&encoded));
+                assert!(!json_lines.contains(&encoded));
+            }
+        }
+        // the pseudonym depends on the nonce
+        assert_ne!(
+            csv,
+            export_credential_metadata_csv(&records(), b"other nonce")
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Developer Portal app IDs, validated when they are constructed.
+
+use std::fmt;
+
+use crate::error::WalletKitError;
+
+/// Number of hexadecimal characters (16 bytes) following the `app_id` prefix.
+const APP_ID_HEX_LENGTH: usize = 32;
+
+/// Number of characters of a rejected value echoed back in the error.
+const MAX_ECHOED_CHARS: usize = 64;
+
+/// A Developer Portal app ID: `app_` (or `app_staging_` for staging apps)
+/// followed by 32 lowercase hexadecimal characters.
+///
+/// Hosts can call [`AppId::parse`] to validate an ID as soon as it is entered,
+/// instead of waiting for the Developer Portal to reject the proof.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, uniffi::Object)]
+pub struct AppId(String);
+
+#[uniffi::export]
+impl AppId {
+    /// Parses an app ID, ignoring surrounding whitespace.
+    ///
+    /// Developer Portal app IDs carry no checksum, so only the format is
+    /// checked.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WalletKitError::InvalidInput`] for the `app_id` attribute if
+    /// the ID contains whitespace or control characters, or has the wrong
+    /// prefix, length or characters. The reason echoes the received value,
+    /// escaped and truncated.
+    #[uniffi::constructor]
+    pub fn parse(app_id: &str) -> Result<Self, WalletKitError> {
+        let invalid = |reason: &str| WalletKitError::InvalidInput {
+            attribute: "app_id".to_string(),
+            reason: format!("{reason} (received {})", echo(app_id)),
+        };
+
+        let app_id = app_id.trim();
+        if app_id.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(invalid("must not contain whitespace or control characters"));
+        }
+        let id = app_id
+            .strip_prefix("app_staging_")
+            .or_else(|| app_id.strip_prefix("app_"))
+            .ok_or_else(|| invalid("must start with `app_` or `app_staging_`"))?;
+        let len = id.chars().count();
+        if len != APP_ID_HEX_LENGTH {
+            return Err(invalid(&format!(
+                "must contain {APP_ID_HEX_LENGTH} hexadecimal characters after the prefix, found {len}"
+            )));
+        }
+        if !id
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+        {
+            return Err(invalid(
+                "must only contain lowercase hexadecimal characters after the prefix",
+            ));
+        }
+        Ok(Self(app_id.to_string()))
+    }
+
+    /// Returns the app ID as a string.
+    #[must_use]
+    pub fn value(&self) -> String {
+        self.0.clone()
+    }
+
+    /// Whether this is the ID of a staging app.
+    #[must_use]
+    pub fn is_staging(&self) -> bool {
+        self.0.starts_with("app_staging_")
+    }
+}
+
+impl AppId {
+    /// Wraps `app_id` without validating it, for tests and legacy identifiers.
+    #[must_use]
+    pub fn new_unchecked(app_id: impl Into<String>) -> Self {
+        Self(app_id.into())
+    }
+
+    /// Returns the app ID as a string slice.
+    #[must_use]
+    pub const fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl fmt::Display for AppId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Quotes `value` with control characters escaped, truncated to
+/// [`MAX_ECHOED_CHARS`] characters.
+fn echo(value: &str) -> String {
+    let truncated = value.chars().take(MAX_ECHOED_CHARS).collect::<String>();
+    if truncated.len() < value.len() {
+        format!("{truncated:?}...")
+    } else {
+        format!("{truncated:?}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_invalid(app_id: &str, expected_reason: &str) {
+        match AppId::parse(app_id) {
+            Err(WalletKitError::InvalidInput { attribute, reason }) => {
+                assert_eq!(attribute, "app_id");
+                assert!(
+                    reason.contains(expected_reason),
+                    "unexpected reason: {reason}"
+                );
+            }
+            other => panic!("expected InvalidInput, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_valid_app_ids() {
+        let production = AppId::parse("app_369183bd38f1641b6964ab51d7a20434").unwrap();
+        assert_eq!(production.as_str(), "app_369183bd38f1641b6964ab51d7a20434");
+        assert!(!production.is_staging());
+
+        let staging =
+            AppId::parse("app_staging_45068dca85829d2fd90e2dd6f0bff997").unwrap();
+        assert!(staging.is_staging());
+    }
+
+    #[test]
+    fn test_parse_trims_surrounding_whitespace() {
+        let app_id = AppId::parse(" app_369183bd38f1641b6964ab51d7a20434\n").unwrap();
+        assert_eq!(app_id.value(), "app_369183bd38f1641b6964ab51d7a20434");
+    }
+
+    #[test]
+    fn test_parse_rejects_embedded_whitespace() {
+        assert_invalid(
+            "app_369183bd38f1641b 6964ab51d7a20434",
+            "whitespace or control characters",
+        );
+        // the received value is echoed with control characters escaped
+        assert_invalid(
+            "app_369183bd38f1641b\t6964ab51d7a20434",
+            r#"(received "app_369183bd38f1641b\t6964ab51d7a20434")"#,
+        );
+        assert_invalid("app_369183bd\u{0}38f1641b6964ab51d7a20434", "control");
+    }
+
+    #[test]
+    fn test_parse_rejects_wrong_prefix() {
+        for app_id in [
+            "",
+            "369183bd38f1641b6964ab51d7a20434",
+            "App_369183bd38f1641b6964ab51d7a20434",
+        ] {
+            assert_invalid(app_id, "must start with");
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_wrong_length() {
+        assert_invalid("app_369183bd38f1641b6964ab51d7a2043", "found 31");
+        assert_invalid("app_staging_45068dca85829d2fd90e2dd6f0bff9970", "found 33");
+        assert_invalid("app_", "found 0");
+    }
+
+    #[test]
+    fn test_parse_rejects_non_hex_characters() {
+        assert_invalid(
+            "app_369183bd38f1641b6964ab51d7a2043g",
+            "lowercase hexadecimal",
+        );
+        assert_invalid(
+            "app_CE4CB73CB75FC3B73B71FFB4DE178410",
+            "lowercase hexadecimal",
+        );
+    }
+
+    #[test]
+    fn test_parse_truncates_echoed_value() {
+        let app_id = format!("app_{}", "a".repeat(200));
+        match AppId::parse(&app_id) {
+            Err(WalletKitError::InvalidInput { reason, .. }) => {
+                assert!(reason.len() < 200, "reason not truncated: {reason}");
+                assert!(reason.ends_with("...)"));
+            }
+            other => panic!("expected InvalidInput, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! Client for the per-action configuration RPs set in the Developer Portal.
+
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+use std::sync::{Mutex, PoisonError};
+
+use serde::Deserialize;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::shared_cache::SharedCaches;
+use crate::{error::WalletKitError, http_request::Request, AppId, Environment};
+
+/// An action's configuration in the Developer Portal.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct ActionConfig {
+    /// How many times a World ID may be verified for the action, or `None`
+    /// if unlimited.
+    pub max_verifications: Option<u32>,
+    /// Display name of the action.
+    pub name: String,
+    /// Description of the action shown to users.
+    pub description: String,
+}
+
+#[derive(Deserialize)]
+struct PrecheckResponse {
+    action: PrecheckAction,
+}
+
+#[derive(Deserialize)]
+struct PrecheckAction {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    description: String,
+    /// `0` means unlimited.
+    max_verifications: u32,
+}
+
+impl From<PrecheckAction> for ActionConfig {
+    fn from(action: PrecheckAction) -> Self {
+        Self {
+            max_verifications: (action.max_verifications != 0)
+                .then_some(action.max_verifications),
+            name: action.name,
+            description: action.description,
+        }
+    }
+}
+
+/// Developer Portal API client for app and action metadata.
+///
+/// Action configs are cached for the lifetime of the client; create a new
+/// client or call [`clear_cache`](Self::clear_cache) to refetch them. Clients
+/// created with [`with_shared_caches`](Self::with_shared_caches) use the
+/// shared cache instead.
+#[derive(uniffi::Object)]
+pub struct AppRegistryClient {
+    base_url: String,
+    request: Request,
+    actions: Mutex<HashMap<(String, String), ActionConfig>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    shared_caches: Option<Arc<SharedCaches>>,
+}
+
+#[uniffi::export]
+impl AppRegistryClient {
+    /// Create a new Developer Portal client for the specified environment
+    #[uniffi::constructor]
+    #[must_use]
+    pub fn new(environment: &Environment, user_agent: String) -> Self {
+        Self::with_base_url(base_url_for(environment), user_agent)
+    }
+
+    /// Drops all cached action configs, except those in shared caches.
+    pub fn clear_cache(&self) {
+        self.lock_actions().clear();
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl AppRegistryClient {
+    /// Returns the configuration of `action` of the app `app_id`, from the
+    /// cache if it was fetched before.
+    ///
+    /// Calls the `/api/v1/precheck/{app_id}` endpoint.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WalletKitError::InvalidInput`] if `app_id` is malformed (see
+    /// [`AppId::parse`]), and an error on network failure (including unknown
+    /// apps or actions) or an invalid response.
+    pub async fn fetch_action(
+        &self,
+        app_id: &str,
+        action: &str,
+    ) -> Result<ActionConfig, WalletKitError> {
+        // a well-formed app ID is also safe to use as a path segment
+        let app_id = AppId::parse(app_id)?;
+        let key = (app_id.value(), action.to_string());
+        if let Some(config) = self.cached_action(&key) {
+            return Ok(config);
+        }
+
+        let url = format!("{}/api/v1/precheck/{app_id}", self.base_url);
+        let request_builder = self
+            .request
+            .post(&url)
+            .json(&serde_json::json!({ "action": action }));
+        let response = self.request.handle(request_builder).await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_body = response.text().await.unwrap_or_default();
+            return Err(WalletKitError::NetworkError {
+                url,
+                status: Some(status.as_u16()),
+                error: format!("action precheck failed: {error_body}"),
+            });
+        }
+
+        let precheck: PrecheckResponse =
+            response
+                .json()
+                .await
+                .map_err(|e| WalletKitError::SerializationError {
+                    error: format!("Failed to parse action precheck response: {e}"),
+                })?;
+        let config = ActionConfig::from(precheck.action);
+        self.cache_action(key, config.clone());
+        Ok(config)
+    }
+}
+
+impl AppRegistryClient {
+    fn with_base_url(base_url: &str, user_agent: String) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            request: Request::new(user_agent),
+            actions: Mutex::new(HashMap::new()),
+            #[cfg(not(target_arch = "wasm32"))]
+            shared_caches: None,
+        }
+    }
+
+    fn cached_action(&self, key: &(String, String)) -> Option<ActionConfig> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(shared_caches) = &self.shared_caches {
+            return shared_caches.action_configs.get(key);
+        }
+        self.lock_actions().get(key).cloned()
+    }
+
+    fn cache_action(&self, key: (String, String), config: ActionConfig) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(shared_caches) = &self.shared_caches {
+            shared_caches.action_configs.insert(key, config);
+            return;
+        }
+        self.lock_actions().insert(key, config);
+    }
+
+    fn lock_actions(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<(String, String), ActionConfig>> {
+        self.actions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[uniffi::export]
+impl AppRegistryClient {
+    /// Create a new Developer Portal client for the specified environment
+    /// that caches action configs in `shared_caches`, so clients sharing
+    /// them fetch each action once per TTL.
+    #[uniffi::constructor]
+    #[must_use]
+    pub fn with_shared_caches(
+        environment: &Environment,
+        user_agent: String,
+        shared_caches: Arc<SharedCaches>,
+    ) -> Self {
+        Self {
+            shared_caches: Some(shared_caches),
+            ..Self::with_base_url(base_url_for(environment), user_agent)
+        }
+    }
+}
+
+pub(crate) const fn base_url_for(environment: &Environment) -> &'static str {
+    match environment {
+        Environment::Staging => "https://staging-developer.worldcoin.org",
+        Environment::Production => "https://developer.world.org",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APP_ID: &str = "app_ce4cb73cb75fc3b73b71ffb4de178410";
+
+    fn client(server: &mockito::ServerGuard) -> AppRegistryClient {
+        AppRegistryClient::with_base_url(&server.url(), "test/1.0.0".to_string())
+    }
+
+    #[test]
+    fn test_production_url() {
+        let client = AppRegistryClient::new(
+            &Environment::Production,
+            "WorldApp/1.0.0 test/1.0.0".to_string(),
+        );
+        assert_eq!(client.base_url, "https://developer.world.org");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_action_is_cached() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", format!("/api/v1/precheck/{APP_ID}").as_str())
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({ "action": "vote" }),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "id": APP_ID,
+                    "name": "Polls",
+                    "action": {
+                        "action": "vote",
+                        "name": "Vote",
+                        "description": "Vote in the poll",
+                        "max_verifications": 1,
+                        "max_accounts_per_user": 1,
+                        "status": "active",
+                    },
+                })
+                .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let client = client(&server);
+
+        let expected = ActionConfig {
+            max_verifications: Some(1),
+            name: "Vote".to_string(),
+            description: "Vote in the poll".to_string(),
+        };
+        assert_eq!(client.fetch_action(APP_ID, "vote").await.unwrap(), expected);
+        assert_eq!(client.fetch_action(APP_ID, "vote").await.unwrap(), expected);
+        mock.assert_async().await;
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_action_uses_shared_caches() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", format!("/api/v1/precheck/{APP_ID}").as_str())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "id": APP_ID,
+                    "action": { "action": "vote", "max_verifications": 0 },
+                })
+                .to_string(),
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        let shared_caches = Arc::new(SharedCaches::new(60, 60));
+        let shared_client = || AppRegistryClient {
+            shared_caches: Some(Arc::clone(&shared_caches)),
+            ..client(&server)
+        };
+
+        // the second client is served from the first one's fetch
+        shared_client().fetch_action(APP_ID, "vote").await.unwrap();
+        shared_client().fetch_action(APP_ID, "vote").await.unwrap();
+
+        // clearing one client's cache leaves the shared one
+        let client = shared_client();
+        client.clear_cache();
+        client.fetch_action(APP_ID, "vote").await.unwrap();
+
+        shared_caches.clear();
+        client.fetch_action(APP_ID, "vote").await.unwrap();
+        mock.assert_async().await;
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_action_unlimited() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", format!("/api/v1/precheck/{APP_ID}").as_str())
+            .with_status(200)
+            .with_body(
+                r#"{"action":{"name":"Claim","description":"","max_verifications":0}}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        let client = client(&server);
+
+        let config = client.fetch_action(APP_ID, "claim-daily").await.unwrap();
+        assert_eq!(config.max_verifications, None);
+        client.clear_cache();
+        client.fetch_action(APP_ID, "claim-daily").await.unwrap();
+        mock.assert_async().await;
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_action_unknown() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", format!("/api/v1/precheck/{APP_ID}").as_str())
+            .with_status(404)
+            .with_body(r#"{"code":"not_found"}"#)
+            .create_async()
+            .await;
+        let client = client(&server);
+
+        let error = client.fetch_action(APP_ID, "missing").await.unwrap_err();
+        assert!(matches!(
+            error,
+            WalletKitError::NetworkError {
+                status: Some(404),
+                ..
+            }
+        ));
+        mock.assert_async().await;
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_action_rejects_malformed_app_id() {
+        let client = AppRegistryClient::new(&Environment::Staging, "test".to_string());
+        for app_id in ["", "app_", "ce4cb73c", "app_../admin", "app_vote"] {
+            assert!(matches!(
+                client.fetch_action(app_id, "vote").await,
+                Err(WalletKitError::InvalidInput { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,799 @@
+//! Resumable downloads of the Groth16 proving artifacts.
+//!
+//! The proving keys and witness graphs are tens of megabytes, too large to
+//! download in one go on a mobile network. [`ArtifactManager`] downloads them
+//! into the Groth16 directory of [`StoragePaths`] with HTTP range requests,
+//! continuing a partial download where it stopped, also after an app restart.
+//! A download only replaces the artifact, with an atomic rename, once its
+//! SHA-256 digest matches the one pinned in the embedded manifest, so a
+//! corrupted download fails here rather than at proof time.
+//!
+//! Verified artifacts get a marker file recording their digest, size and
+//! modification time. The marker only saves work: [`ArtifactManager::status`]
+//! reports an artifact with a matching marker as ready without hashing it,
+//! and once a process has hashed an artifact, later checks (here and in
+//! `cache_embedded_groth16_material`) trust its marker instead of hashing it
+//! again. A marker catches an artifact that was replaced, truncated or
+//! rewritten, as that changes its size or modification time, but not one
+//! changed in place with both preserved, nor a forged marker. So a process
+//! always hashes an artifact before it first trusts the marker, and the
+//! proving keys are checked against their pinned digests again when loaded.
+//!
+//! Once all artifacts are ensured, load them with
+//! [`Groth16Materials::from_cache`](crate::Groth16Materials::from_cache).
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, UNIX_EPOCH};
+
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+
+use crate::error::WalletKitError;
+use crate::http_request::Request;
+use crate::runtime::{compat, spawn_blocking};
+use crate::storage::StoragePaths;
+
+/// Release the embedded manifest's artifacts are downloaded from.
+const ARTIFACT_BASE_URL: &str =
+    "https://github.com/worldcoin/world-id-protocol/releases/download/circuit-artifacts-v0.1.0";
+
+/// Timeout of one download request, including its body. An interrupted
+/// download resumes where it stopped.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Suffix of a partial download, next to the artifact.
+const PARTIAL_SUFFIX: &str = "part";
+
+/// Suffix of the marker recording that the artifact was verified.
+const MARKER_SUFFIX: &str = "verified";
+
+/// Artifacts this process hashed, whose markers it trusts from then on.
+static HASHED_ARTIFACTS: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+/// Receives the progress of artifact downloads.
+#[uniffi::export(with_foreign)]
+pub trait ArtifactProgressListener: Send + Sync {
+    /// Called after each chunk of `artifact_id` is written. `total_bytes` is
+    /// `None` if the server did not report the size.
+    fn on_progress(
+        &self,
+        artifact_id: String,
+        downloaded_bytes: u64,
+        total_bytes: Option<u64>,
+    );
+}
+
+/// The state of an artifact on device, see [`ArtifactManager::status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum ArtifactStatus {
+    /// Nothing was downloaded yet.
+    Missing,
+    /// A download was interrupted or cancelled, and is resumed by the next
+    /// [`ArtifactManager::ensure`].
+    Partial {
+        /// Bytes downloaded so far.
+        downloaded_bytes: u64,
+    },
+    /// The artifact is being downloaded.
+    Downloading {
+        /// Bytes downloaded so far.
+        downloaded_bytes: u64,
+        /// Size of the artifact, if the server reported it.
+        total_bytes: Option<u64>,
+    },
+    /// The artifact is present but was not verified since it was written,
+    /// e.g. by an older version. [`ArtifactManager::ensure`] verifies it
+    /// without downloading it again if it is intact.
+    Unverified,
+    /// The artifact is present and verified.
+    Ready,
+}
+
+/// An artifact of the manifest.
+#[derive(Debug, Clone)]
+struct Artifact {
+    id: String,
+    url: String,
+    path: PathBuf,
+    /// Hex-encoded SHA-256 digest.
+    sha256: String,
+}
+
+/// Per-artifact state of the downloads in progress.
+#[derive(Debug, Default)]
+struct Slot {
+    /// Held while the artifact is ensured, so concurrent calls download it
+    /// once.
+    ensuring: tokio::sync::Mutex<()>,
+    cancelled: AtomicBool,
+    /// Bytes downloaded and the total, while downloading.
+    progress: Mutex<Option<(u64, Option<u64>)>>,
+}
+
+impl Slot {
+    fn set_progress(&self, progress: Option<(u64, Option<u64>)>) {
+        // the progress is only ever replaced whole, so a panic cannot corrupt it
+        *self.progress.lock().unwrap_or_else(PoisonError::into_inner) = progress;
+    }
+
+    fn progress(&self) -> Option<(u64, Option<u64>)> {
+        *self.progress.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Downloads and verifies the Groth16 proving artifacts, see the
+/// [module documentation](self).
+#[derive(uniffi::Object)]
+pub struct ArtifactManager {
+    paths: Arc<StoragePaths>,
+    request: Request,
+    artifacts: Vec<Artifact>,
+    slots: HashMap<String, Slot>,
+}
+
+impl std::fmt::Debug for ArtifactManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArtifactManager")
+            .field("artifacts", &self.artifacts)
+            .finish_non_exhaustive()
+    }
+}
+
+#[uniffi::export]
+impl ArtifactManager {
+    /// Creates a manager for the artifacts of the embedded manifest, kept in
+    /// the Groth16 directory of `paths`.
+    #[uniffi::constructor]
+    #[must_use]
+    pub fn new(paths: Arc<StoragePaths>, user_agent: String) -> Self {
+        let manifest = [
+            (
+                "query_zkey",
+                paths.query_zkey_path(),
+                world_id_core::proof::QUERY_ZKEY_FINGERPRINT,
+            ),
+            (
+                "nullifier_zkey",
+                paths.nullifier_zkey_path(),
+                world_id_core::proof::NULLIFIER_ZKEY_FINGERPRINT,
+            ),
+            (
+                "query_graph",
+                paths.query_graph_path(),
+                world_id_core::proof::QUERY_GRAPH_FINGERPRINT,
+            ),
+            (
+                "nullifier_graph",
+                paths.nullifier_graph_path(),
+                world_id_core::proof::NULLIFIER_GRAPH_FINGERPRINT,
+            ),
+        ]
+        .into_iter()
+        .map(|(id, path, sha256)| Artifact {
+            id: id.to_string(),
+            url: format!("{ARTIFACT_BASE_URL}/{}", file_name(&path)),
+            path,
+            sha256: sha256.to_string(),
+        })
+        .collect();
+        Self::with_manifest(paths, Request::new(user_agent), manifest)
+    }
+
+    /// Returns the IDs of the artifacts in the manifest.
+    #[must_use]
+    pub fn artifact_ids(&self) -> Vec<String> {
+        self.artifacts
+            .iter()
+            .map(|artifact| artifact.id.clone())
+            .collect()
+    }
+
+    /// Returns the state of `artifact_id` on device. Does not hash the
+    /// artifact.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] if the manifest has no
+    /// artifact `artifact_id`.
+    pub fn status(&self, artifact_id: &str) -> Result<ArtifactStatus, WalletKitError> {
+        let (artifact, slot) = self.artifact(artifact_id)?;
+        if let Some((downloaded_bytes, total_bytes)) = slot.progress() {
+            return Ok(ArtifactStatus::Downloading {
+                downloaded_bytes,
+                total_bytes,
+            });
+        }
+        if has_marker(&artifact.path, &artifact.sha256) {
+            return Ok(ArtifactStatus::Ready);
+        }
+        if artifact.path.is_file() {
+            return Ok(ArtifactStatus::Unverified);
+        }
+        Ok(fs::metadata(partial_path(&artifact.path)).map_or(
+            ArtifactStatus::Missing,
+            |metadata| ArtifactStatus::Partial {
+                downloaded_bytes: metadata.len(),
+            },
+        ))
+    }
+
+    /// Cancels the download of `artifact_id` in progress, if any. The
+    /// download stops after the chunk it is receiving and its
+    /// [`ensure`](Self::ensure) fails with
+    /// [`WalletKitError::ArtifactDownloadCancelled`].
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] if the manifest has no
+    /// artifact `artifact_id`.
+    pub fn cancel(&self, artifact_id: &str) -> Result<(), WalletKitError> {
+        let (_, slot) = self.artifact(artifact_id)?;
+        slot.cancelled.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Deletes the files in the Groth16 directory that are not artifacts of
+    /// the manifest, their markers or their partial downloads, e.g. artifacts
+    /// of an earlier circuit version. Returns the names of the deleted files.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::Groth16MaterialCacheInvalid`] if the
+    /// directory cannot be read or a file cannot be deleted.
+    pub fn purge_unused(&self) -> Result<Vec<String>, WalletKitError> {
+        let dir = self.paths.groth16_dir();
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Ok(Vec::new())
+            }
+            Err(error) => return Err(io_error(&dir, &error)),
+        };
+        let keep: Vec<PathBuf> = self
+            .artifacts
+            .iter()
+            .flat_map(|artifact| {
+                [
+                    artifact.path.clone(),
+                    marker_path(&artifact.path),
+                    partial_path(&artifact.path),
+                ]
+            })
+            .collect();
+        let mut purged = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|error| io_error(&dir, &error))?.path();
+            if !path.is_file() || keep.contains(&path) {
+                continue;
+            }
+            fs::remove_file(&path).map_err(|error| io_error(&path, &error))?;
+            purged.push(file_name(&path));
+        }
+        Ok(purged)
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl ArtifactManager {
+    /// Makes sure `artifact_id` is present and verified, downloading it if
+    /// needed. Resumes an earlier partial download.
+    ///
+    /// Concurrent calls for the same artifact download it once; the later
+    /// calls wait for the first and return its result.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] if the manifest has no
+    /// artifact `artifact_id`,
+    /// [`WalletKitError::ArtifactChecksumMismatch`] if the download is
+    /// corrupted, in which case it is discarded,
+    /// [`WalletKitError::ArtifactDownloadCancelled`] if the download was
+    /// cancelled, or an error if the download fails.
+    pub async fn ensure(
+        &self,
+        artifact_id: &str,
+        progress_listener: Option<Arc<dyn ArtifactProgressListener>>,
+    ) -> Result<(), WalletKitError> {
+        let (artifact, slot) = self.artifact(artifact_id)?;
+        let _ensuring = slot.ensuring.lock().await;
+
+        // an earlier call may have ensured it while this one waited
+        let present = artifact.clone();
+        if spawn_blocking(move || verify_file(&present.path, &present.sha256))
+            .await
+            .map_err(|error| WalletKitError::Generic {
+                error: error.to_string(),
+            })?
+            .map_err(|error| io_error(&artifact.path, &error))?
+        {
+            return Ok(());
+        }
+
+        slot.cancelled.store(false, Ordering::SeqCst);
+        let downloaded = self
+            .download(artifact, slot, progress_listener.as_deref())
+            .await;
+        slot.set_progress(None);
+        downloaded?;
+
+        let partial = partial_path(&artifact.path);
+        let digest = {
+            let path = partial.clone();
+            spawn_blocking(move || file_sha256_hex(&path))
+                .await
+                .map_err(|error| WalletKitError::Generic {
+                    error: error.to_string(),
+                })?
+                .map_err(|error| io_error(&partial, &error))?
+        };
+        if digest != artifact.sha256 {
+            // a corrupted download cannot be resumed, start over next time
+            fs::remove_file(&partial).map_err(|error| io_error(&partial, &error))?;
+            return Err(WalletKitError::ArtifactChecksumMismatch {
+                artifact_id: artifact.id.clone(),
+                expected: artifact.sha256.clone(),
+                actual: digest,
+            });
+        }
+        fs::rename(&partial, &artifact.path)
+            .map_err(|error| io_error(&artifact.path, &error))?;
+        write_marker(&artifact.path, &artifact.sha256)
+            .map_err(|error| io_error(&artifact.path, &error))?;
+        record_hashed(&artifact.path);
+        Ok(())
+    }
+}
+
+impl ArtifactManager {
+    fn with_manifest(
+        paths: Arc<StoragePaths>,
+        request: Request,
+        artifacts: Vec<Artifact>,
+    ) -> Self {
+        let slots = artifacts
+            .iter()
+            .map(|artifact| (artifact.id.clone(), Slot::default()))
+            .collect();
+        Self {
+            paths,
+            request,
+            artifacts,
+            slots,
+        }
+    }
+
+    fn artifact(
+        &self,
+        artifact_id: &str,
+    ) -> Result<(&Artifact, &Slot), WalletKitError> {
+        self.artifacts
+            .iter()
+            .find(|artifact| artifact.id == artifact_id)
+            .zip(self.slots.get(artifact_id))
+            .ok_or_else(|| WalletKitError::InvalidInput {
+                attribute: "artifact_id".to_string(),
+                reason: format!("unknown artifact {artifact_id}"),
+            })
+    }
+
+    /// Downloads `artifact` into its partial download, continuing where an
+    /// earlier download stopped.
+    async fn download(
+        &self,
+        artifact: &Artifact,
+        slot: &Slot,
+        listener: Option<&dyn ArtifactProgressListener>,
+    ) -> Result<(), WalletKitError> {
+        let dir = self.paths.groth16_dir();
+        fs::create_dir_all(&dir).map_err(|error| io_error(&dir, &error))?;
+        let partial = partial_path(&artifact.path);
+        let mut downloaded =
+            fs::metadata(&partial).map_or(0, |metadata| metadata.len());
+
+        let mut request_builder =
+            self.request.get(&artifact.url).timeout(DOWNLOAD_TIMEOUT);
+        if downloaded > 0 {
+            request_builder =
+                request_builder.header(RANGE, format!("bytes={downloaded}-"));
+        }
+        let mut response = self.request.handle(request_builder).await?;
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT => {}
+            // the server ignored the range and sends the whole artifact
+            StatusCode::OK => downloaded = 0,
+            // the partial download is complete, verification decides
+            StatusCode::RANGE_NOT_SATISFIABLE if downloaded > 0 => return Ok(()),
+            status => {
+                return Err(WalletKitError::NetworkError {
+                    url: artifact.url.clone(),
+                    status: Some(status.as_u16()),
+                    error: "artifact download failed".to_string(),
+                })
+            }
+        }
+        let total = response.content_length().map(|len| downloaded + len);
+
+        let mut file = if downloaded == 0 {
+            fs::File::create(&partial)
+        } else {
+            fs::OpenOptions::new().append(true).open(&partial)
+        }
+        .map_err(|error| io_error(&partial, &error))?;
+        slot.set_progress(Some((downloaded, total)));
+        while let Some(chunk) = compat(response.chunk()).await? {
+            if slot.cancelled.load(Ordering::SeqCst) {
+                file.sync_all()
+                    .map_err(|error| io_error(&partial, &error))?;
+                return Err(WalletKitError::ArtifactDownloadCancelled {
+                    artifact_id: artifact.id.clone(),
+                });
+            }
+            file.write_all(&chunk)
+                .map_err(|error| io_error(&partial, &error))?;
+            downloaded += chunk.len() as u64;
+            slot.set_progress(Some((downloaded, total)));
+            if let Some(listener) = listener {
+                listener.on_progress(artifact.id.clone(), downloaded, total);
+            }
+        }
+        file.sync_all().map_err(|error| io_error(&partial, &error))
+    }
+}
+
+/// Returns whether `path` holds the file with SHA-256 digest
+/// `expected_sha256`. Trusts the marker if this process already hashed the
+/// file and it did not change since; hashes it and leaves a marker otherwise.
+pub(crate) fn verify_file(path: &Path, expected_sha256: &str) -> io::Result<bool> {
+    if was_hashed(path) && has_marker(path, expected_sha256) {
+        return Ok(true);
+    }
+    if !path.is_file() {
+        return Ok(false);
+    }
+    if file_sha256_hex(path)? != expected_sha256 {
+        return Ok(false);
+    }
+    write_marker(path, expected_sha256)?;
+    record_hashed(path);
+    Ok(true)
+}
+
+fn was_hashed(path: &Path) -> bool {
+    HASHED_ARTIFACTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .contains(path)
+}
+
+fn record_hashed(path: &Path) {
+    HASHED_ARTIFACTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(path.to_path_buf());
+}
+
+/// Returns the hex-encoded SHA-256 digest of the file at `path`.
+fn file_sha256_hex(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 16 * 1024];
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// The marker contents for the file at `path` with digest `sha256`: the
+/// digest, size and modification time, one per line.
+fn marker_contents(path: &Path, sha256: &str) -> io::Result<String> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_nanos());
+    Ok(format!("{sha256}\n{}\n{modified}\n", metadata.len()))
+}
+
+fn has_marker(path: &Path, expected_sha256: &str) -> bool {
+    let Ok(marker) = fs::read_to_string(marker_path(path)) else {
+        return false;
+    };
+    marker_contents(path, expected_sha256).is_ok_and(|expected| marker == expected)
+}
+
+fn write_marker(path: &Path, sha256: &str) -> io::Result<()> {
+    fs::write(marker_path(path), marker_contents(path, sha256)?)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn marker_path(path: &Path) -> PathBuf {
+    with_suffix(path, MARKER_SUFFIX)
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    with_suffix(path, PARTIAL_SUFFIX)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn io_error(path: &Path, error: &io::Error) -> WalletKitError {
+    WalletKitError::Groth16MaterialCacheInvalid {
+        path: path.to_string_lossy().to_string(),
+        error: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+
+    use super::*;
+    use crate::storage::tests_utils::{cleanup_test_storage, temp_root_path};
+
+    const ARTIFACT: &str = "query_zkey";
+
+    struct Artifacts {
+        root: PathBuf,
+        manager: ArtifactManager,
+        body: Vec<u8>,
+    }
+
+    impl Drop for Artifacts {
+        fn drop(&mut self) {
+            cleanup_test_storage(&self.root);
+        }
+    }
+
+    fn artifacts(
+        server: &mockito::ServerGuard,
+        body: Vec<u8>,
+        sha256: String,
+    ) -> Artifacts {
+        let root = temp_root_path();
+        let paths = Arc::new(StoragePaths::new(&root));
+        let manifest = vec![Artifact {
+            id: ARTIFACT.to_string(),
+            url: format!("{}/OPRFQuery.arks.zkey", server.url()),
+            path: paths.query_zkey_path(),
+            sha256,
+        }];
+        let manager = ArtifactManager::with_manifest(
+            paths,
+            Request::new("test/1.0.0".to_string()),
+            manifest,
+        );
+        Artifacts {
+            root,
+            manager,
+            body,
+        }
+    }
+
+    fn body() -> (Vec<u8>, String) {
+        let body: Vec<u8> = (0..=u8::MAX).cycle().take(64 * 1024).collect();
+        let sha256 = hex::encode(Sha256::digest(&body));
+        (body, sha256)
+    }
+
+    struct CountingListener(AtomicU64);
+
+    impl ArtifactProgressListener for CountingListener {
+        fn on_progress(
+            &self,
+            artifact_id: String,
+            downloaded_bytes: u64,
+            _: Option<u64>,
+        ) {
+            assert_eq!(artifact_id, ARTIFACT);
+            self.0.store(downloaded_bytes, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ensure_resumes_partial_download() {
+        let mut server = mockito::Server::new_async().await;
+        let (body, sha256) = body();
+        let artifacts = artifacts(&server, body, sha256);
+        let path = artifacts.manager.paths.query_zkey_path();
+        let half = artifacts.body.len() / 2;
+
+        // an earlier download was interrupted halfway
+        fs::create_dir_all(artifacts.manager.paths.groth16_dir()).unwrap();
+        fs::write(partial_path(&path), &artifacts.body[..half]).unwrap();
+        assert_eq!(
+            artifacts.manager.status(ARTIFACT).unwrap(),
+            ArtifactStatus::Partial {
+                downloaded_bytes: half as u64
+            }
+        );
+
+        let mock = server
+            .mock("GET", "/OPRFQuery.arks.zkey")
+            .match_header("range", format!("bytes={half}-").as_str())
+            .with_status(206)
+            .with_body(&artifacts.body[half..])
+            .expect(1)
+            .create_async()
+            .await;
+        let listener = Arc::new(CountingListener(AtomicU64::new(0)));
+        artifacts
+            .manager
+            .ensure(ARTIFACT, Some(listener.clone()))
+            .await
+            .expect("ensure");
+
+        mock.assert_async().await;
+        assert_eq!(
+            listener.0.load(Ordering::SeqCst),
+            artifacts.body.len() as u64
+        );
+        assert_eq!(fs::read(&path).unwrap(), artifacts.body);
+        assert!(!partial_path(&path).exists());
+        assert_eq!(
+            artifacts.manager.status(ARTIFACT).unwrap(),
+            ArtifactStatus::Ready
+        );
+
+        // verified artifacts are not downloaded again
+        artifacts
+            .manager
+            .ensure(ARTIFACT, None)
+            .await
+            .expect("ensure");
+        mock.assert_async().await;
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_ensure_rejects_checksum_mismatch() {
+        let mut server = mockito::Server::new_async().await;
+        let (body, sha256) = body();
+        let artifacts = artifacts(&server, body, sha256.clone());
+        let path = artifacts.manager.paths.query_zkey_path();
+
+        let mut corrupted = artifacts.body.clone();
+        corrupted[100] ^= 1;
+        server
+            .mock("GET", "/OPRFQuery.arks.zkey")
+            .with_status(200)
+            .with_body(&corrupted)
+            .create_async()
+            .await;
+
+        let result = artifacts.manager.ensure(ARTIFACT, None).await;
+        assert!(matches!(
+            result,
+            Err(WalletKitError::ArtifactChecksumMismatch { ref expected, .. })
+                if *expected == sha256
+        ));
+        assert!(!path.exists());
+        assert!(!partial_path(&path).exists());
+        assert_eq!(
+            artifacts.manager.status(ARTIFACT).unwrap(),
+            ArtifactStatus::Missing
+        );
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_ensure_downloads_once() {
+        let mut server = mockito::Server::new_async().await;
+        let (body, sha256) = body();
+        let artifacts = Arc::new(artifacts(&server, body, sha256));
+        let mock = server
+            .mock("GET", "/OPRFQuery.arks.zkey")
+            .with_status(200)
+            .with_body(&artifacts.body)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..4 {
+            let artifacts = Arc::clone(&artifacts);
+            tasks.spawn(async move { artifacts.manager.ensure(ARTIFACT, None).await });
+        }
+        for result in tasks.join_all().await {
+            result.expect("ensure");
+        }
+
+        mock.assert_async().await;
+        assert_eq!(
+            fs::read(artifacts.manager.paths.query_zkey_path()).unwrap(),
+            artifacts.body
+        );
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_artifact_and_purge() {
+        // nothing is downloaded, the server only provides the URL
+        let (body, sha256) = body();
+        let artifacts = artifacts(&mockito::Server::new_async().await, body, sha256);
+        assert!(matches!(
+            artifacts.manager.status("unknown"),
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+        assert!(artifacts.manager.purge_unused().unwrap().is_empty());
+
+        let dir = artifacts.manager.paths.groth16_dir();
+        fs::create_dir_all(&dir).unwrap();
+        let path = artifacts.manager.paths.query_zkey_path();
+        fs::write(&path, &artifacts.body).unwrap();
+        fs::write(partial_path(&path), b"partial").unwrap();
+        fs::write(dir.join("OPRFQuery.v0.arks.zkey"), b"stale").unwrap();
+        assert_eq!(
+            artifacts.manager.status(ARTIFACT).unwrap(),
+            ArtifactStatus::Unverified
+        );
+
+        assert_eq!(
+            artifacts.manager.purge_unused().unwrap(),
+            vec!["OPRFQuery.v0.arks.zkey".to_string()]
+        );
+        assert!(path.exists());
+        assert!(partial_path(&path).exists());
+    }
+
+    #[test]
+    fn test_marker_is_invalidated_by_changes() {
+        let root = temp_root_path();
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join("artifact.bin");
+        let (body, sha256) = body();
+        fs::write(&path, &body).unwrap();
+
+        assert!(verify_file(&path, &sha256).unwrap());
+        assert!(has_marker(&path, &sha256));
+        assert!(!has_marker(&path, &"0".repeat(64)));
+
+        fs::write(&path, &body[1..]).unwrap();
+        assert!(!has_marker(&path, &sha256));
+        assert!(!verify_file(&path, &sha256).unwrap());
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_marker_of_earlier_process_is_not_trusted() {
+        let root = temp_root_path();
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join("artifact.bin");
+        let (mut body, sha256) = body();
+        fs::write(&path, &body).unwrap();
+        write_marker(&path, &sha256).unwrap();
+
+        // changed in place, keeping its size and modification time
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        body[0] ^= 0xff;
+        fs::write(&path, &body).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert!(has_marker(&path, &sha256));
+
+        assert!(!verify_file(&path, &sha256).unwrap());
+
+        cleanup_test_storage(&root);
+    }
+}
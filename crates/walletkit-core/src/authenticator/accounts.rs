@@ -0,0 +1,244 @@
+//! Switching between several World ID accounts on one device.
+//!
+//! Each account is an [`Authenticator`] with its own seed and
+//! [`CredentialStore`]. A [`MultiAccountAuthenticator`] keeps the accounts the
+//! host opened, keyed by leaf index, so switching accounts does not mean
+//! initializing from the seed again. Its accounts are minted from one
+//! [`AuthenticatorPool`] and share its materials and caches.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::{Authenticator, AuthenticatorPool};
+use crate::error::WalletKitError;
+use crate::requests::{ProofRequest, ProofResponse};
+use crate::storage::CredentialStore;
+
+/// The open accounts and which one is selected.
+#[derive(Default)]
+struct Accounts {
+    open: BTreeMap<u64, Arc<Authenticator>>,
+    selected: Option<u64>,
+}
+
+/// Several open accounts, one of them selected, see the
+/// [module documentation](self).
+#[derive(uniffi::Object)]
+pub struct MultiAccountAuthenticator {
+    pool: Arc<AuthenticatorPool>,
+    accounts: Mutex<Accounts>,
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl MultiAccountAuthenticator {
+    /// Creates an empty set of accounts minted from `pool`.
+    #[uniffi::constructor]
+    #[must_use]
+    pub fn new(pool: Arc<AuthenticatorPool>) -> Self {
+        Self {
+            pool,
+            accounts: Mutex::new(Accounts::default()),
+        }
+    }
+
+    /// Opens the account of `seed`, storing its credentials in `store`, and
+    /// returns its leaf index. The first account opened is selected.
+    ///
+    /// Opening an account that is already open replaces it.
+    ///
+    /// # Errors
+    /// Returns an error if the seed is invalid or the account cannot be
+    /// loaded from the registry.
+    pub async fn open_account(
+        &self,
+        seed: &[u8],
+        store: Arc<CredentialStore>,
+    ) -> Result<u64, WalletKitError> {
+        let authenticator = self.pool.authenticator(seed, store).await?;
+        let leaf_index = authenticator.leaf_index();
+        let mut accounts = self.lock();
+        accounts.open.insert(leaf_index, authenticator);
+        accounts.selected.get_or_insert(leaf_index);
+        drop(accounts);
+        Ok(leaf_index)
+    }
+
+    /// Generates a proof with the selected account, as
+    /// [`Authenticator::generate_proof`] does.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] if no account is selected, or
+    /// an error if proof generation fails.
+    pub async fn generate_proof(
+        &self,
+        proof_request: &ProofRequest,
+        now: Option<u64>,
+    ) -> Result<ProofResponse, WalletKitError> {
+        self.selected_account()?
+            .generate_proof(proof_request, now)
+            .await
+    }
+}
+
+#[uniffi::export]
+impl MultiAccountAuthenticator {
+    /// Returns the leaf indexes of the open accounts, in ascending order.
+    #[must_use]
+    pub fn account_ids(&self) -> Vec<u64> {
+        self.lock().open.keys().copied().collect()
+    }
+
+    /// Returns the open account with `leaf_index`.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] if the account is not open.
+    pub fn account(
+        &self,
+        leaf_index: u64,
+    ) -> Result<Arc<Authenticator>, WalletKitError> {
+        self.lock()
+            .open
+            .get(&leaf_index)
+            .cloned()
+            .ok_or_else(|| not_open(leaf_index))
+    }
+
+    /// Selects the open account with `leaf_index` for
+    /// [`generate_proof`](Self::generate_proof).
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] if the account is not open.
+    pub fn select_account(&self, leaf_index: u64) -> Result<(), WalletKitError> {
+        let mut accounts = self.lock();
+        if !accounts.open.contains_key(&leaf_index) {
+            return Err(not_open(leaf_index));
+        }
+        accounts.selected = Some(leaf_index);
+        drop(accounts);
+        Ok(())
+    }
+
+    /// Returns the leaf index of the selected account, if any.
+    #[must_use]
+    pub fn selected_account_id(&self) -> Option<u64> {
+        self.lock().selected
+    }
+
+    /// Returns the selected account.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] if no account is selected.
+    pub fn selected_account(&self) -> Result<Arc<Authenticator>, WalletKitError> {
+        let accounts = self.lock();
+        accounts
+            .selected
+            .and_then(|leaf_index| accounts.open.get(&leaf_index).cloned())
+            .ok_or_else(|| WalletKitError::InvalidInput {
+                attribute: "account".to_string(),
+                reason: "no account selected".to_string(),
+            })
+    }
+
+    /// Forgets the open account with `leaf_index`, deselecting it if it was
+    /// selected. Returns whether the account was open.
+    ///
+    /// The account's [`CredentialStore`] stays open while the host holds it.
+    pub fn close_account(&self, leaf_index: u64) -> bool {
+        let mut accounts = self.lock();
+        if accounts.selected == Some(leaf_index) {
+            accounts.selected = None;
+        }
+        accounts.open.remove(&leaf_index).is_some()
+    }
+}
+
+impl MultiAccountAuthenticator {
+    /// Locks the accounts; a panic while holding the lock leaves them intact.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Accounts> {
+        self.accounts.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The error for an account that is not open.
+fn not_open(leaf_index: u64) -> WalletKitError {
+    WalletKitError::InvalidInput {
+        attribute: "leaf_index".to_string(),
+        reason: format!("account {leaf_index} is not open"),
+    }
+}
+
+#[cfg(all(test, feature = "embed-zkeys"))]
+mod tests {
+    use ruint::aliases::U256;
+    use world_id_core::primitives::{Config, ServiceEndpoint};
+
+    use super::*;
+    use crate::authenticator::{AuthenticatorPoolOptions, Groth16Materials};
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+
+    #[tokio::test]
+    async fn test_open_select_and_close_accounts() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let mut server = mockito::Server::new_async().await;
+        let packed_account_data = (U256::from(1) << 192) | U256::from(5);
+        server
+            .mock("POST", "/packed-account")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "packed_account_data": format!("{packed_account_data:#x}"),
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let config = Config::new(
+            None,
+            480,
+            alloy::primitives::address!("0x969947cFED008bFb5e3F32a25A1A2CDdf64d46fe"),
+            ServiceEndpoint::direct(server.url()),
+            ServiceEndpoint::direct(server.url()),
+            vec![],
+            2,
+        )
+        .unwrap();
+        let pool = AuthenticatorPool::new(
+            &serde_json::to_string(&config).unwrap(),
+            Arc::new(Groth16Materials::from_embedded().expect("load materials")),
+            AuthenticatorPoolOptions::default(),
+        )
+        .expect("pool");
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = Arc::new(CredentialStore::from_provider(&provider).expect("store"));
+        store.init(5, 100).expect("init storage");
+
+        let accounts = MultiAccountAuthenticator::new(Arc::new(pool));
+        assert!(accounts.selected_account().is_err());
+        let leaf_index = accounts
+            .open_account(&[1u8; 32], store)
+            .await
+            .expect("open account");
+        assert_eq!(leaf_index, 5);
+        assert_eq!(accounts.account_ids(), vec![5]);
+        assert_eq!(accounts.selected_account_id(), Some(5));
+        assert!(matches!(
+            accounts.select_account(6),
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+        assert_eq!(accounts.account(5).expect("open").leaf_index(), 5);
+
+        assert!(accounts.close_account(5));
+        assert!(!accounts.close_account(5));
+        assert_eq!(accounts.selected_account_id(), None);
+        assert!(accounts.account_ids().is_empty());
+
+        drop(server);
+        cleanup_test_storage(&root);
+    }
+}
@@ -0,0 +1,200 @@
+//! Local estimate of how often an action was used, before proving.
+
+use world_id_core::FieldElement as CoreFieldElement;
+
+use super::{resolve_now, Authenticator};
+use crate::app_registry::ActionConfig;
+use crate::error::WalletKitError;
+use crate::requests::ProofRequest;
+use crate::runtime::compat;
+use crate::storage::CredentialStore;
+
+/// How much of an action this World ID has used, as far as this device
+/// knows.
+///
+/// This is an estimate for the UI. The RP's server is authoritative: proofs
+/// generated on other devices or before a reinstall are not known locally,
+/// and the RP may count verifications differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Record)]
+pub struct ActionUsage {
+    /// Whether a proof for the action was already disclosed from this device.
+    pub used: bool,
+    /// Verifications left, or `None` if the action is unlimited.
+    pub remaining: Option<u32>,
+}
+
+impl ActionUsage {
+    fn new(used: bool, config: &ActionConfig) -> Self {
+        Self {
+            used,
+            remaining: config
+                .max_verifications
+                .map(|max| max.saturating_sub(u32::from(used))),
+        }
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl Authenticator {
+    /// Estimates the usage of the action `proof_request` is for, so the user
+    /// can be told they already used it before anything is generated.
+    ///
+    /// Combines the local replay guard with `action_config`, see
+    /// [`AppRegistryClient::fetch_action`](crate::AppRegistryClient::fetch_action).
+    /// Deriving the action's nullifier queries the OPRF nodes, but no proof is
+    /// generated and nothing is recorded. A proof still within the replay
+    /// guard's retry grace period does not count as used.
+    ///
+    /// # Errors
+    /// Returns an error if the nullifier cannot be derived or the replay
+    /// guard cannot be read.
+    pub async fn action_usage(
+        &self,
+        proof_request: &ProofRequest,
+        action_config: &ActionConfig,
+        now: Option<u64>,
+    ) -> Result<ActionUsage, WalletKitError> {
+        let now = resolve_now(now)?;
+        let account_inclusion_proof =
+            self.fetch_inclusion_proof_with_cache(now).await?;
+        // Box::pin to keep this future below clippy::large_futures threshold
+        let nullifier = compat(Box::pin(
+            self.inner
+                .generate_nullifier(&proof_request.0, Some(account_inclusion_proof)),
+        ))
+        .await?;
+        let store = self.store()?;
+        action_usage_for_nullifier(
+            &store,
+            self.environment_scope(),
+            nullifier.verifiable_oprf_output.output.into(),
+            action_config,
+            now,
+        )
+    }
+}
+
+fn action_usage_for_nullifier(
+    store: &CredentialStore,
+    environment: [u8; 32],
+    nullifier: CoreFieldElement,
+    action_config: &ActionConfig,
+    now: u64,
+) -> Result<ActionUsage, WalletKitError> {
+    let used = store.is_nullifier_replay(environment, nullifier, now)?;
+    Ok(ActionUsage::new(used, action_config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider, TEST_ENVIRONMENT,
+    };
+
+    fn config(max_verifications: Option<u32>) -> ActionConfig {
+        ActionConfig {
+            max_verifications,
+            name: "Vote".to_string(),
+            description: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_action_usage_from_replay_records() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("store");
+        store.init(42, 100).expect("init storage");
+
+        let used_nullifier = CoreFieldElement::from(7u64);
+        let fresh_nullifier = CoreFieldElement::from(8u64);
+        store
+            .replay_guard_set(TEST_ENVIRONMENT, used_nullifier, 1000)
+            .expect("seed replay record");
+        // past the retry grace period
+        let now = 1000 + 3600;
+
+        let usage = action_usage_for_nullifier(
+            &store,
+            TEST_ENVIRONMENT,
+            used_nullifier,
+            &config(Some(1)),
+            now,
+        )
+        .unwrap();
+        assert_eq!(
+            usage,
+            ActionUsage {
+                used: true,
+                remaining: Some(0)
+            }
+        );
+        let usage = action_usage_for_nullifier(
+            &store,
+            TEST_ENVIRONMENT,
+            used_nullifier,
+            &config(Some(3)),
+            now,
+        )
+        .unwrap();
+        assert_eq!(usage.remaining, Some(2));
+        let usage = action_usage_for_nullifier(
+            &store,
+            TEST_ENVIRONMENT,
+            used_nullifier,
+            &config(None),
+            now,
+        )
+        .unwrap();
+        assert_eq!(
+            usage,
+            ActionUsage {
+                used: true,
+                remaining: None
+            }
+        );
+        let usage = action_usage_for_nullifier(
+            &store,
+            TEST_ENVIRONMENT,
+            fresh_nullifier,
+            &config(Some(1)),
+            now,
+        )
+        .unwrap();
+        assert_eq!(
+            usage,
+            ActionUsage {
+                used: false,
+                remaining: Some(1)
+            }
+        );
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_action_usage_within_grace_period() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("store");
+        store.init(42, 100).expect("init storage");
+        let nullifier = CoreFieldElement::from(7u64);
+        store
+            .replay_guard_set(TEST_ENVIRONMENT, nullifier, 1000)
+            .expect("seed");
+
+        // a proof that may not have reached the RP can still be retried
+        let usage = action_usage_for_nullifier(
+            &store,
+            TEST_ENVIRONMENT,
+            nullifier,
+            &config(Some(1)),
+            1001,
+        )
+        .unwrap();
+        assert!(!usage.used);
+
+        cleanup_test_storage(&root);
+    }
+}
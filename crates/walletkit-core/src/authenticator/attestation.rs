@@ -0,0 +1,337 @@
+//! Signed statements that the device holds a World ID account, for RPs that
+//! allow-list accounts rather than verify proofs.
+//!
+//! An attestation is `<payload>.<signature>`, both base64url-encoded without
+//! padding. The payload is a JSON object naming the account's on-chain
+//! authenticator address and packed account data, the audience it is for, and
+//! when it was issued and expires. The signature is an EIP-191 signature of
+//! the encoded payload with the authenticator's on-chain key.
+//!
+//! Unlike a proof, an attestation is **not** privacy-preserving: it reveals
+//! the authenticator address and leaf index, which link every attestation and
+//! on-chain action of the account. Replay is only bounded by the audience and
+//! the expiry.
+
+use alloy_core::primitives::{Address, Signature};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use ruint::aliases::U256;
+use ruint_uniffi::Uint256;
+use serde::{Deserialize, Serialize};
+
+use super::Authenticator;
+use crate::error::WalletKitError;
+use crate::limits::InputLimit;
+
+/// How long an attestation is valid after it is issued, in seconds.
+pub const ACCOUNT_ATTESTATION_TTL_SECONDS: u64 = 10 * 60;
+
+/// How far in the future an attestation's issue time may be, to allow for
+/// clock skew between the device and the RP, in seconds.
+const MAX_CLOCK_SKEW_SECONDS: u64 = 60;
+
+/// Type of the attestation payload, so other EIP-191 signatures of the
+/// authenticator key are not mistaken for attestations.
+const ATTESTATION_TYPE: &str = "world-id-account-attestation";
+
+/// Version of the attestation payload.
+const ATTESTATION_VERSION: u8 = 1;
+
+/// The signed part of an attestation.
+#[derive(Debug, Serialize, Deserialize)]
+struct AttestationPayload {
+    typ: String,
+    version: u8,
+    /// Checksummed hex address.
+    onchain_address: String,
+    /// `0x`-prefixed hex.
+    packed_account_data: String,
+    audience: String,
+    issued_at: u64,
+    expires_at: u64,
+}
+
+/// An account attestation, see
+/// [`Authenticator::export_account_attestation`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct SignedAttestation {
+    /// The encoded attestation, to send to the RP as is.
+    pub bytes: Vec<u8>,
+    /// Seconds since the UNIX epoch after which the attestation is rejected.
+    pub expires_at: u64,
+}
+
+/// The contents of an attestation that passed
+/// [`verify_account_attestation`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct VerifiedAccountAttestation {
+    /// Checksummed hex address of the authenticator that signed the
+    /// attestation.
+    pub onchain_address: String,
+    /// The account's packed account data when the attestation was issued.
+    pub packed_account_data: Uint256,
+    /// The account's leaf index in the `WorldIDRegistry`, from
+    /// `packed_account_data`.
+    pub leaf_index: u64,
+    /// Seconds since the UNIX epoch when the attestation was issued.
+    pub issued_at: u64,
+    /// Seconds since the UNIX epoch after which the attestation is rejected.
+    pub expires_at: u64,
+}
+
+#[uniffi::export]
+impl Authenticator {
+    /// Exports a signed statement that this device holds the account, for an
+    /// RP that allow-lists accounts. The attestation is valid for
+    /// [`ACCOUNT_ATTESTATION_TTL_SECONDS`] after `now` and only for
+    /// `audience`, e.g. the RP's domain.
+    ///
+    /// # Warning
+    /// The attestation reveals the authenticator address and leaf index and
+    /// links everything the account does. Only use it where the RP is meant to
+    /// identify the account, never in place of a proof.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] if `audience` is empty, or an
+    /// error if signing fails.
+    pub fn export_account_attestation(
+        &self,
+        audience: &str,
+        now: u64,
+    ) -> Result<SignedAttestation, WalletKitError> {
+        if audience.is_empty() {
+            return Err(WalletKitError::InvalidInput {
+                attribute: "audience".to_string(),
+                reason: "must not be empty".to_string(),
+            });
+        }
+        let payload = AttestationPayload {
+            typ: ATTESTATION_TYPE.to_string(),
+            version: ATTESTATION_VERSION,
+            onchain_address: self.inner.onchain_address().to_checksum(None),
+            packed_account_data: format!("{:#x}", self.inner.packed_account_data),
+            audience: audience.to_string(),
+            issued_at: now,
+            expires_at: now.saturating_add(ACCOUNT_ATTESTATION_TTL_SECONDS),
+        };
+        let payload_json = serde_json::to_vec(&payload).map_err(|error| {
+            WalletKitError::SerializationError {
+                error: error.to_string(),
+            }
+        })?;
+        let encoded_payload = BASE64_URL_SAFE_NO_PAD.encode(payload_json);
+        let signature = self
+            .inner
+            .danger_sign_challenge(encoded_payload.as_bytes())?;
+        let encoded = format!(
+            "{encoded_payload}.{}",
+            BASE64_URL_SAFE_NO_PAD.encode(signature.as_bytes())
+        );
+        Ok(SignedAttestation {
+            bytes: encoded.into_bytes(),
+            expires_at: payload.expires_at,
+        })
+    }
+}
+
+/// Verifies an attestation from
+/// [`Authenticator::export_account_attestation`] for an RP whose audience is
+/// `expected_audience`, at `now`.
+///
+/// This only checks that the authenticator named in the attestation signed
+/// it. To know the address still belongs to the account, check it against
+/// the `WorldIDRegistry`.
+///
+/// # Errors
+/// Returns [`WalletKitError::AccountAttestationInvalid`] if the attestation
+/// is malformed, its signature does not match the authenticator address, it
+/// is for another audience, or it is expired or issued in the future.
+#[uniffi::export]
+pub fn verify_account_attestation(
+    bytes: &[u8],
+    expected_audience: &str,
+    now: u64,
+) -> Result<VerifiedAccountAttestation, WalletKitError> {
+    InputLimit::AccountAttestation.check(bytes.len())?;
+    let invalid = |reason: &str| WalletKitError::AccountAttestationInvalid {
+        reason: reason.to_string(),
+    };
+
+    let (encoded_payload, encoded_signature) = std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|encoded| encoded.split_once('.'))
+        .ok_or_else(|| invalid("malformed"))?;
+    let payload: AttestationPayload = BASE64_URL_SAFE_NO_PAD
+        .decode(encoded_payload)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or_else(|| invalid("malformed"))?;
+    if payload.typ != ATTESTATION_TYPE || payload.version != ATTESTATION_VERSION {
+        return Err(invalid("unsupported"));
+    }
+    let onchain_address: Address = payload
+        .onchain_address
+        .parse()
+        .map_err(|_| invalid("malformed"))?;
+    let packed_account_data: U256 = payload
+        .packed_account_data
+        .parse()
+        .map_err(|_| invalid("malformed"))?;
+
+    let signer = BASE64_URL_SAFE_NO_PAD
+        .decode(encoded_signature)
+        .ok()
+        .and_then(|signature| Signature::from_raw(&signature).ok())
+        .and_then(|signature| {
+            signature
+                .recover_address_from_msg(encoded_payload.as_bytes())
+                .ok()
+        });
+    if signer != Some(onchain_address) {
+        return Err(invalid("signature"));
+    }
+    if payload.audience != expected_audience {
+        return Err(invalid("audience"));
+    }
+    if now >= payload.expires_at {
+        return Err(invalid("expired"));
+    }
+    if payload.issued_at > now.saturating_add(MAX_CLOCK_SKEW_SECONDS) {
+        return Err(invalid("issued_in_future"));
+    }
+
+    Ok(VerifiedAccountAttestation {
+        onchain_address: onchain_address.to_checksum(None),
+        packed_account_data: packed_account_data.into(),
+        leaf_index: packed_account_data.as_limbs()[0],
+        issued_at: payload.issued_at,
+        expires_at: payload.expires_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use world_id_core::primitives::{Config, ServiceEndpoint};
+    use world_id_core::Authenticator as CoreAuthenticator;
+
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+    use crate::storage::CredentialStore;
+
+    const AUDIENCE: &str = "acme.example";
+
+    fn assert_invalid(
+        result: Result<VerifiedAccountAttestation, WalletKitError>,
+        expected: &str,
+    ) {
+        match result {
+            Err(WalletKitError::AccountAttestationInvalid { reason }) => {
+                assert_eq!(reason, expected);
+            }
+            other => panic!("expected {expected}, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_account_attestation() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let mut mock_server = mockito::Server::new_async().await;
+        mock_server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "result": "0x0000000000000000000000000000000000000000000000000000000000000001"
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let config = Config::new(
+            Some(mock_server.url()),
+            480,
+            crate::defaults::STAGING_WORLD_ID_REGISTRY,
+            ServiceEndpoint::direct(mock_server.url()),
+            ServiceEndpoint::direct(mock_server.url()),
+            vec![],
+            2,
+        )
+        .unwrap();
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = Arc::new(CredentialStore::from_provider(&provider).expect("store"));
+        store.init(1, 100).expect("init storage");
+        let inner = CoreAuthenticator::init(&[2u8; 32], config)
+            .await
+            .expect("init authenticator");
+        let authenticator = Authenticator::from_parts(inner, store);
+
+        let attestation = authenticator
+            .export_account_attestation(AUDIENCE, 1000)
+            .expect("export");
+        assert_eq!(
+            attestation.expires_at,
+            1000 + ACCOUNT_ATTESTATION_TTL_SECONDS
+        );
+
+        let verified = verify_account_attestation(&attestation.bytes, AUDIENCE, 1100)
+            .expect("verify");
+        assert_eq!(
+            verified.onchain_address,
+            authenticator.inner.onchain_address().to_checksum(None)
+        );
+        assert_eq!(verified.leaf_index, 1);
+        assert_eq!(verified.issued_at, 1000);
+
+        assert_invalid(
+            verify_account_attestation(&attestation.bytes, "other.example", 1100),
+            "audience",
+        );
+        assert_invalid(
+            verify_account_attestation(
+                &attestation.bytes,
+                AUDIENCE,
+                attestation.expires_at,
+            ),
+            "expired",
+        );
+        assert_invalid(
+            verify_account_attestation(&attestation.bytes, AUDIENCE, 900),
+            "issued_in_future",
+        );
+
+        // a payload for another audience under the original signature
+        let encoded = String::from_utf8(attestation.bytes).unwrap();
+        let (payload, signature) = encoded.split_once('.').unwrap();
+        let mut payload: serde_json::Value =
+            serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(payload).unwrap())
+                .unwrap();
+        payload["audience"] = "other.example".into();
+        let forged = format!(
+            "{}.{signature}",
+            BASE64_URL_SAFE_NO_PAD.encode(payload.to_string())
+        );
+        assert_invalid(
+            verify_account_attestation(forged.as_bytes(), "other.example", 1100),
+            "signature",
+        );
+        assert_invalid(
+            verify_account_attestation(b"not an attestation", AUDIENCE, 1100),
+            "malformed",
+        );
+
+        assert!(matches!(
+            authenticator.export_account_attestation("", 1000),
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+
+        cleanup_test_storage(&root);
+        drop(mock_server);
+    }
+}
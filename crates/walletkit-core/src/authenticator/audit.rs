@@ -0,0 +1,408 @@
+//! Canonical pre-images of what proof generation hashes, for debugging proofs
+//! that fail verification.
+//!
+//! With the `proof-audit` feature, `Authenticator::set_proof_audit_enabled`
+//! makes [`generate_proof_with_options`](super::Authenticator::generate_proof_with_options)
+//! return a [`ProofAuditTrail`] in [`ProofOutcome::audit_trail`](super::ProofOutcome::audit_trail).
+//! The trail is only ever returned: `WalletKit` neither persists nor logs it.
+//!
+//! # Sensitive material
+//!
+//! The trail holds the signals, the request nonce and, for session proofs,
+//! the session seed `r` together with the holder's leaf index, which links
+//! the session to the on-chain account. Handle it like a credential: keep it
+//! out of logs and crash reports, and never forward it to the RP.
+
+use sha2::{Digest, Sha256};
+use world_id_core::primitives::rp::compute_rp_signature_msg;
+use world_id_core::requests::{
+    ProofRequest as CoreProofRequest, ProofResponse as CoreProofResponse,
+};
+use world_id_core::FieldElement;
+
+#[cfg(feature = "proof-audit")]
+use super::Authenticator;
+use crate::error::WalletKitError;
+
+/// Domain separator of the session commitment, mirrors `SessionId::from_r_seed`.
+const SESSION_COMMITMENT_DS: &[u8] = b"H(id, r)";
+
+/// A canonical pre-image and the hash computed from it.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct AuditEntry {
+    /// What was hashed, e.g. the request item identifier for signals.
+    pub label: String,
+    /// The exact bytes that were hashed, hex encoded.
+    pub preimage_hex: String,
+    /// The resulting hash, hex encoded big-endian.
+    pub hash_hex: String,
+}
+
+impl AuditEntry {
+    fn new(label: impl Into<String>, preimage: &[u8], hash: &[u8]) -> Self {
+        Self {
+            label: label.into(),
+            preimage_hex: hex::encode(preimage),
+            hash_hex: hex::encode(hash),
+        }
+    }
+}
+
+/// The pre-images hashed while generating a proof, and their hashes.
+///
+/// Contains sensitive material, see the module docs of
+/// `authenticator::audit`. It is never persisted or logged by `WalletKit`.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct ProofAuditTrail {
+    /// The message signed by the RP,
+    /// `version || nonce || created_at || expires_at || action`, and its
+    /// SHA-256 digest. The action is the external nullifier of uniqueness
+    /// proofs.
+    pub rp_signature: AuditEntry,
+    /// For each request item, the signal bytes and the signal hash, a public
+    /// input of the item's proof. An absent signal hashes to zero.
+    pub signals: Vec<AuditEntry>,
+    /// The UTF-8 request ID and its SHA-256 digest, the key of the replay
+    /// guard and consent ledger entries.
+    pub request_id: AuditEntry,
+    /// `DS_C || leaf_index || r`, the Poseidon2 input of the session
+    /// commitment, and the commitment, a public input of session proofs.
+    /// `None` for uniqueness proofs.
+    pub session_commitment: Option<AuditEntry>,
+}
+
+impl ProofAuditTrail {
+    /// Collects the trail of `response`, generated for `request` by the
+    /// account at `leaf_index`. `session_id_r_seed` is the session seed `r`
+    /// the proof was generated with, if any.
+    ///
+    /// # Errors
+    /// Returns an error if the RP signature digest cannot be computed.
+    pub(super) fn collect(
+        request: &CoreProofRequest,
+        response: &CoreProofResponse,
+        leaf_index: u64,
+        session_id_r_seed: Option<FieldElement>,
+    ) -> Result<Self, WalletKitError> {
+        let rp_signature_msg = compute_rp_signature_msg(
+            *request.nonce,
+            request.created_at,
+            request.expires_at,
+            request.action.map(|action| *action),
+        );
+        let signals = request
+            .requests
+            .iter()
+            .map(|item| {
+                AuditEntry::new(
+                    &item.identifier,
+                    item.signal.as_deref().unwrap_or_default(),
+                    &item.signal_hash().to_be_bytes(),
+                )
+            })
+            .collect();
+        let session_commitment =
+            response
+                .session_id
+                .zip(session_id_r_seed)
+                .map(|(session_id, r_seed)| {
+                    let preimage = [
+                        FieldElement::from_be_bytes_mod_order(SESSION_COMMITMENT_DS),
+                        FieldElement::from(leaf_index),
+                        r_seed,
+                    ]
+                    .iter()
+                    .flat_map(FieldElement::to_be_bytes)
+                    .collect::<Vec<_>>();
+                    AuditEntry::new(
+                        "session_commitment",
+                        &preimage,
+                        &session_id.commitment.to_be_bytes(),
+                    )
+                });
+        Ok(Self {
+            rp_signature: AuditEntry::new(
+                "rp_signature",
+                &rp_signature_msg,
+                &request.digest_hash()?,
+            ),
+            signals,
+            request_id: AuditEntry::new(
+                "request_id",
+                request.id.as_bytes(),
+                &Sha256::digest(request.id.as_bytes()),
+            ),
+            session_commitment,
+        })
+    }
+}
+
+#[cfg(feature = "proof-audit")]
+impl Authenticator {
+    /// Whether proofs are generated with a [`ProofAuditTrail`].
+    pub(super) fn proof_audit_enabled(&self) -> bool {
+        self.proof_audit.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "proof-audit")]
+#[uniffi::export]
+impl Authenticator {
+    /// Enables or disables the [`ProofAuditTrail`] returned by
+    /// [`generate_proof_with_options`](Self::generate_proof_with_options).
+    /// Off by default.
+    ///
+    /// The trail contains sensitive material, see the module docs of
+    /// `authenticator::audit`. It is for debugging against staging only.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] when enabling it on an
+    /// authenticator configured for the production `WorldIDRegistry`.
+    pub fn set_proof_audit_enabled(&self, enabled: bool) -> Result<(), WalletKitError> {
+        if enabled
+            && *self.inner.config.registry_address()
+                == crate::defaults::WORLD_ID_REGISTRY
+        {
+            return Err(WalletKitError::InvalidInput {
+                attribute: "proof_audit".to_string(),
+                reason: "proof audit cannot be enabled in production".to_string(),
+            });
+        }
+        self.proof_audit
+            .store(enabled, std::sync::atomic::Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::signers::Signature;
+    use alloy_core::primitives::U160;
+    use ruint::aliases::U256;
+    use taceo_oprf::types::OprfKeyId;
+    use world_id_core::primitives::rp::RpId;
+    use world_id_core::primitives::{Nullifier, SessionId, ZeroKnowledgeProof};
+    use world_id_core::requests::{
+        ProofType, RequestItem, RequestVersion, ResponseItem,
+    };
+
+    use super::*;
+
+    fn request_item(identifier: &str, signal: Option<&[u8]>) -> RequestItem {
+        RequestItem {
+            identifier: identifier.to_string(),
+            issuer_schema_id: 1,
+            signal: signal.map(<[u8]>::to_vec),
+            genesis_issued_at_min: None,
+            expires_at_min: None,
+        }
+    }
+
+    fn decode(hex_str: &str) -> Vec<u8> {
+        hex::decode(hex_str).expect("valid hex")
+    }
+
+    #[test]
+    fn test_recomputed_hashes_match_public_inputs() {
+        let leaf_index = 42;
+        let r_seed = FieldElement::from(5u64);
+        let session_id = SessionId::from_r_seed(
+            leaf_index,
+            r_seed,
+            SessionId::generate_oprf_seed(&mut rand::thread_rng()),
+        )
+        .expect("session id");
+        let request = CoreProofRequest {
+            id: "request-1".to_string(),
+            version: RequestVersion::V1,
+            proof_type: ProofType::Session,
+            created_at: 100,
+            expires_at: 400,
+            rp_id: RpId::new(1),
+            oprf_key_id: OprfKeyId::new(U160::from(1u64)),
+            session_id: Some(session_id),
+            action: None,
+            signature: Signature::test_signature(),
+            nonce: FieldElement::from(9u64),
+            requests: vec![
+                request_item("orb", Some(b"0xdeadbeef")),
+                request_item("document", None),
+            ],
+            constraints: None,
+        };
+        let response = CoreProofResponse {
+            id: request.id.clone(),
+            version: RequestVersion::V1,
+            session_id: Some(session_id),
+            error: None,
+            responses: vec![ResponseItem::new_uniqueness(
+                "orb".to_string(),
+                1,
+                ZeroKnowledgeProof::from_ethereum_representation([U256::ZERO; 5]),
+                Nullifier::from(FieldElement::from(1u64)),
+                0,
+            )],
+        };
+
+        let trail =
+            ProofAuditTrail::collect(&request, &response, leaf_index, Some(r_seed))
+                .expect("collect");
+
+        // the RP signature covers the nonce and action
+        let digest = Sha256::digest(decode(&trail.rp_signature.preimage_hex));
+        assert_eq!(digest.as_slice(), request.digest_hash().unwrap());
+        assert_eq!(decode(&trail.rp_signature.hash_hex), digest.as_slice());
+
+        // signal hashes are the `signal_hash` public input of each proof
+        assert_eq!(trail.signals.len(), 2);
+        for (entry, item) in trail.signals.iter().zip(&request.requests) {
+            assert_eq!(entry.label, item.identifier);
+            let public_input = item.signal_hash().to_be_bytes();
+            assert_eq!(decode(&entry.hash_hex), public_input);
+            let preimage = decode(&entry.preimage_hex);
+            if item.signal.is_some() {
+                assert_eq!(
+                    FieldElement::from_arbitrary_raw_bytes(&preimage).to_be_bytes(),
+                    public_input
+                );
+            } else {
+                assert!(preimage.is_empty());
+                assert_eq!(public_input, FieldElement::ZERO.to_be_bytes());
+            }
+        }
+
+        assert_eq!(decode(&trail.request_id.preimage_hex), b"request-1");
+        assert_eq!(
+            decode(&trail.request_id.hash_hex),
+            Sha256::digest(b"request-1").as_slice()
+        );
+
+        // the session commitment is the `id_commitment` public input
+        let entry = trail.session_commitment.expect("session commitment");
+        let preimage = decode(&entry.preimage_hex);
+        assert_eq!(preimage.len(), 96);
+        assert_eq!(
+            preimage[..32],
+            FieldElement::from_be_bytes_mod_order(SESSION_COMMITMENT_DS).to_be_bytes()
+        );
+        let recomputed = SessionId::from_r_seed(
+            u64::from_be_bytes(preimage[56..64].try_into().unwrap()),
+            FieldElement::from_be_bytes(&preimage[64..].try_into().unwrap())
+                .expect("field element"),
+            session_id.oprf_seed,
+        )
+        .expect("session id");
+        assert_eq!(recomputed.commitment, session_id.commitment);
+        assert_eq!(decode(&entry.hash_hex), session_id.commitment.to_be_bytes());
+    }
+
+    #[test]
+    fn test_uniqueness_proof_has_no_session_commitment() {
+        let request = CoreProofRequest {
+            id: "request-1".to_string(),
+            version: RequestVersion::V1,
+            proof_type: ProofType::Uniqueness,
+            created_at: 100,
+            expires_at: 400,
+            rp_id: RpId::new(1),
+            oprf_key_id: OprfKeyId::new(U160::from(1u64)),
+            session_id: None,
+            action: Some(FieldElement::from(3u64)),
+            signature: Signature::test_signature(),
+            nonce: FieldElement::from(9u64),
+            requests: vec![request_item("orb", None)],
+            constraints: None,
+        };
+        let response = CoreProofResponse {
+            id: request.id.clone(),
+            version: RequestVersion::V1,
+            session_id: None,
+            error: None,
+            responses: vec![],
+        };
+
+        let trail =
+            ProofAuditTrail::collect(&request, &response, 42, None).expect("collect");
+        assert!(trail.session_commitment.is_none());
+        // the action is the last 32 bytes of the signed message
+        let preimage = decode(&trail.rp_signature.preimage_hex);
+        assert_eq!(
+            preimage[preimage.len() - 32..],
+            FieldElement::from(3u64).to_be_bytes()
+        );
+    }
+
+    #[cfg(feature = "proof-audit")]
+    #[tokio::test]
+    async fn test_audit_refuses_production() {
+        use alloy::primitives::Address;
+        use std::sync::Arc;
+        use world_id_core::primitives::{Config, ServiceEndpoint};
+        use world_id_core::Authenticator as CoreAuthenticator;
+
+        use crate::defaults::{STAGING_WORLD_ID_REGISTRY, WORLD_ID_REGISTRY};
+        use crate::storage::tests_utils::{
+            cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+        };
+        use crate::storage::CredentialStore;
+
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let mut mock_server = mockito::Server::new_async().await;
+        mock_server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "result": "0x0000000000000000000000000000000000000000000000000000000000000001"
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = Arc::new(CredentialStore::from_provider(&provider).expect("store"));
+        store.init(1, 100).expect("init storage");
+
+        let authenticator_for = |registry: Address| {
+            let config = Config::new(
+                Some(mock_server.url()),
+                480,
+                registry,
+                ServiceEndpoint::direct(mock_server.url()),
+                ServiceEndpoint::direct(mock_server.url()),
+                vec![],
+                2,
+            )
+            .unwrap();
+            let store = Arc::clone(&store);
+            async move {
+                let inner = CoreAuthenticator::init(&[2u8; 32], config)
+                    .await
+                    .expect("init authenticator");
+                Authenticator::from_parts(inner, store)
+            }
+        };
+
+        let production = authenticator_for(WORLD_ID_REGISTRY).await;
+        assert!(!production.proof_audit_enabled());
+        assert!(matches!(
+            production.set_proof_audit_enabled(true),
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+        assert!(!production.proof_audit_enabled());
+        production
+            .set_proof_audit_enabled(false)
+            .expect("disabling is always allowed");
+
+        let staging = authenticator_for(STAGING_WORLD_ID_REGISTRY).await;
+        staging.set_proof_audit_enabled(true).expect("staging");
+        assert!(staging.proof_audit_enabled());
+
+        drop(mock_server);
+        cleanup_test_storage(&root);
+    }
+}
@@ -0,0 +1,80 @@
+//! Proofs for several requests of one RP interaction at once.
+
+use std::sync::Arc;
+
+use super::{resolve_now, traced, Authenticator, DisclosureNotes, ProvingProfile};
+use crate::error::WalletKitError;
+use crate::requests::{ProofRequest, ProofResponse};
+
+/// The outcome of one request of [`Authenticator::generate_proof_batch`].
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct BatchProofResult {
+    /// The `id` of the request.
+    pub request_id: String,
+    /// The proof, if it was generated.
+    pub response: Option<Arc<ProofResponse>>,
+    /// Why no proof was generated, if it was not. The message starts with
+    /// the error code of the [`WalletKitError`].
+    pub error: Option<String>,
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl Authenticator {
+    /// Generates a proof for each of `proof_requests`, e.g. for an RP that
+    /// asks for several actions at once.
+    ///
+    /// The Merkle inclusion proof is fetched once for the whole batch. Each
+    /// request is then proven on its own, in order, with its own replay
+    /// check, so a request that fails (e.g. because it was already answered
+    /// or has expired) is reported in its [`BatchProofResult`] and does not
+    /// stop the others.
+    ///
+    /// # Errors
+    /// Returns an error if the Merkle inclusion proof cannot be fetched, in
+    /// which case no request could be proven.
+    pub async fn generate_proof_batch(
+        &self,
+        proof_requests: Vec<Arc<ProofRequest>>,
+        now: Option<u64>,
+    ) -> Result<Vec<BatchProofResult>, WalletKitError> {
+        let now = resolve_now(now)?;
+        if proof_requests.is_empty() {
+            return Ok(Vec::new());
+        }
+        let account_inclusion_proof = traced(
+            "indexer.fetch_merkle_proof",
+            self.fetch_inclusion_proof_with_cache(now),
+        )
+        .await?;
+
+        let mut results = Vec::with_capacity(proof_requests.len());
+        for proof_request in proof_requests {
+            let result = self
+                .generate_proof_with_inclusion_proof(
+                    &proof_request,
+                    account_inclusion_proof.clone(),
+                    DisclosureNotes::default(),
+                    ProvingProfile::default(),
+                    now,
+                )
+                .await;
+            let request_id = proof_request.0.id.clone();
+            results.push(match result {
+                Ok(response) => BatchProofResult {
+                    request_id,
+                    response: Some(Arc::new(response)),
+                    error: None,
+                },
+                Err(error) => {
+                    tracing::warn!(%request_id, %error, "batch proof failed");
+                    BatchProofResult {
+                        request_id,
+                        response: None,
+                        error: Some(error.to_string()),
+                    }
+                }
+            });
+        }
+        Ok(results)
+    }
+}
@@ -0,0 +1,204 @@
+//! Combined initialization for app cold start.
+
+use std::sync::Arc;
+
+use world_id_core::Authenticator as CoreAuthenticator;
+
+use super::with_storage::MERKLE_PROOF_VALIDITY_SECONDS;
+use super::{environment_scope, parse_config, Authenticator, Groth16Materials};
+use crate::error::WalletKitError;
+use crate::limits::InputLimit;
+use crate::runtime::compat;
+use crate::storage::{CredentialRecord, CredentialStore};
+
+/// Options for [`cold_start`].
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ColdStartOptions {
+    /// Current time (seconds since the Unix epoch).
+    pub now: u64,
+    /// Whether to fetch a fresh Merkle inclusion proof from the indexer.
+    #[uniffi(default = true)]
+    pub prefetch_merkle_proof: bool,
+}
+
+/// Everything the app needs after a cold start, gathered in one FFI call.
+#[derive(Debug, uniffi::Record)]
+pub struct ColdStartResult {
+    /// The initialized authenticator.
+    pub authenticator: Arc<Authenticator>,
+    /// All stored credentials, as returned by
+    /// [`CredentialStore::list_credentials`].
+    pub credentials: Vec<CredentialRecord>,
+    /// Credentials invalidated because they were issued before the account's
+    /// latest recovery.
+    pub invalidated_credential_ids: Vec<u64>,
+    /// Whether no valid Merkle inclusion proof is cached, so the next proof
+    /// generation has to query the indexer.
+    pub merkle_proof_stale: bool,
+    /// Why the Merkle inclusion proof prefetch failed, if it did. A failed
+    /// prefetch does not fail the cold start.
+    pub prefetch_error: Option<String>,
+}
+
+/// Initializes the authenticator and its storage in a single call.
+///
+/// Replaces the usual sequence of opening the store, `CredentialStore::init`,
+/// `Authenticator::init`, prefetching the Merkle inclusion proof, and listing
+/// credentials, acquiring the storage mutex only once.
+///
+/// The registry read is required and its failure fails the call. The Merkle
+/// inclusion proof prefetch is best effort: its failure is reported in
+/// [`ColdStartResult::prefetch_error`].
+///
+/// # Errors
+///
+/// Returns an error if the config is invalid, the account cannot be loaded
+/// from the registry, or the storage cannot be initialized.
+#[uniffi::export(async_runtime = "tokio")]
+#[tracing::instrument(target = "walletkit_latency", name = "cold_start", skip_all)]
+pub async fn cold_start(
+    seed: &[u8],
+    config: &str,
+    materials: Arc<Groth16Materials>,
+    store: Arc<CredentialStore>,
+    options: ColdStartOptions,
+) -> Result<ColdStartResult, WalletKitError> {
+    let config = parse_config(config)?;
+    InputLimit::Seed.check(seed.len())?;
+    let inner = compat(CoreAuthenticator::init(seed, config))
+        .await?
+        .with_proof_materials(
+            Arc::clone(&materials.query),
+            Arc::clone(&materials.nullifier),
+        );
+    Authenticator::cold_start(inner, store, &options).await
+}
+
+impl Authenticator {
+    /// Completes a cold start for an already-initialized core authenticator.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage cannot be initialized.
+    async fn cold_start(
+        inner: CoreAuthenticator,
+        store: Arc<CredentialStore>,
+        options: &ColdStartOptions,
+    ) -> Result<ColdStartResult, WalletKitError> {
+        let (inclusion_proof, prefetch_error) = if options.prefetch_merkle_proof {
+            match compat(inner.fetch_inclusion_proof()).await {
+                Ok(proof) => (Some(proof), None),
+                Err(e) => {
+                    tracing::warn!("Merkle inclusion proof prefetch failed: {e}");
+                    (None, Some(e.to_string()))
+                }
+            }
+        } else {
+            (None, None)
+        };
+
+        let snapshot = store.cold_start(
+            inner.leaf_index(),
+            inner.recovery_counter().saturating_to::<u64>(),
+            environment_scope(&inner.config),
+            inclusion_proof.as_ref(),
+            MERKLE_PROOF_VALIDITY_SECONDS,
+            options.now,
+        )?;
+
+        Ok(ColdStartResult {
+            authenticator: Arc::new(Self::from_parts(inner, store)),
+            credentials: snapshot.credentials,
+            invalidated_credential_ids: snapshot.invalidated_credential_ids,
+            merkle_proof_stale: !snapshot.merkle_proof_cached,
+            prefetch_error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+    use crate::{Credential, FieldElement};
+    use alloy::primitives::address;
+    use world_id_core::primitives::{Config, ServiceEndpoint};
+
+    #[tokio::test]
+    async fn test_cold_start_reports_failed_prefetch() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let mut mock_server = mockito::Server::new_async().await;
+        mock_server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "result": "0x0000000000000000000000000000000000000000000000000000000000000001"
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let indexer_mock = mock_server
+            .mock("POST", "/inclusion-proof")
+            .with_status(500)
+            .create_async()
+            .await;
+
+        let config = Config::new(
+            Some(mock_server.url()),
+            480,
+            address!("0x969947cFED008bFb5e3F32a25A1A2CDdf64d46fe"),
+            ServiceEndpoint::direct(mock_server.url()),
+            ServiceEndpoint::direct(mock_server.url()),
+            vec![],
+            2,
+        )
+        .unwrap();
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = Arc::new(CredentialStore::from_provider(&provider).expect("store"));
+        store.init(1, 100).expect("init storage");
+        let credential: Credential = world_id_core::Credential::new()
+            .issuer_schema_id(7)
+            .genesis_issued_at(100)
+            .into();
+        store
+            .store_credential(&credential, &FieldElement::from(3u64), 1000, None, 100)
+            .expect("store credential");
+
+        let inner = CoreAuthenticator::init(&[2u8; 32], config)
+            .await
+            .expect("init authenticator");
+        let acquisitions = store.lock_acquisitions();
+        let result = Authenticator::cold_start(
+            inner,
+            Arc::clone(&store),
+            &ColdStartOptions {
+                now: 200,
+                prefetch_merkle_proof: true,
+            },
+        )
+        .await
+        .expect("cold start");
+
+        assert_eq!(store.lock_acquisitions() - acquisitions, 1);
+        indexer_mock.assert_async().await;
+        assert!(result.prefetch_error.is_some());
+        assert!(result.merkle_proof_stale);
+        assert!(result.invalidated_credential_ids.is_empty());
+        assert_eq!(result.credentials.len(), 1);
+        assert_eq!(result.credentials[0].issuer_schema_id, 7);
+        assert_eq!(result.authenticator.leaf_index(), 1);
+
+        drop(mock_server);
+        cleanup_test_storage(&root);
+    }
+}
@@ -0,0 +1,268 @@
+//! Fault injection for testing how apps handle proofs that RPs reject.
+//!
+//! With the `testing` feature, [`Authenticator::inject_fault`] arms a
+//! [`FaultKind`] for the next proof generation. Faults are applied to the
+//! proof itself rather than surfaced as errors, so the RP's verifier fails
+//! the way it would in the field.
+
+use ruint::aliases::U256;
+use world_id_core::primitives::ZeroKnowledgeProof;
+use world_id_core::requests::ProofResponse as CoreProofResponse;
+
+use super::Authenticator;
+use crate::error::WalletKitError;
+
+/// Index of the Merkle root in a proof's Ethereum representation, after the
+/// four words of the compressed Groth16 proof.
+const MERKLE_ROOT_INDEX: usize = 4;
+
+/// A fault to apply to the next proof generation, see
+/// [`Authenticator::inject_fault`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum FaultKind {
+    /// Reports each proof against a Merkle root the `WorldIDRegistry` has no
+    /// record of, as a verifier sees a root that has left its root history.
+    ///
+    /// [`ProofOptions::include_inclusion_proof`](super::ProofOptions::include_inclusion_proof)
+    /// makes proof generation fail instead, since the proofs no longer match
+    /// the inclusion proof.
+    StaleRoot,
+    /// Flips a bit of each compressed Groth16 proof, so it no longer verifies
+    /// against its public inputs.
+    CorruptProofBytes,
+    /// Waits `delay_ms` milliseconds before proving, e.g. to test timeouts.
+    /// Not available on wasm32.
+    DelayMs {
+        /// The delay, in milliseconds.
+        delay_ms: u64,
+    },
+    /// Skips the replay guard, so a request whose nullifier was already
+    /// disclosed is answered with a proof carrying that nullifier, which the
+    /// RP rejects as consumed, instead of failing with
+    /// [`WalletKitError::NullifierReplay`] or
+    /// [`WalletKitError::NullifierConflict`].
+    NullifierReplayOnNext,
+}
+
+impl FaultKind {
+    /// Applies the fault to the generated `response`. Faults that act before
+    /// proving leave it as it is.
+    pub(super) fn apply(self, response: &mut CoreProofResponse) {
+        for item in &mut response.responses {
+            let mut words = item.proof.as_ethereum_representation();
+            match self {
+                Self::StaleRoot => {
+                    // stays a field element: a root is below the modulus
+                    let root = words[MERKLE_ROOT_INDEX];
+                    words[MERKLE_ROOT_INDEX] = if root == U256::ZERO {
+                        U256::from(1)
+                    } else {
+                        root - U256::from(1)
+                    };
+                }
+                Self::CorruptProofBytes => words[0] ^= U256::from(1),
+                Self::DelayMs { .. } | Self::NullifierReplayOnNext => return,
+            }
+            item.proof = ZeroKnowledgeProof::from_ethereum_representation(words);
+        }
+    }
+
+    /// Waits out a [`FaultKind::DelayMs`].
+    pub(super) async fn delay(self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Self::DelayMs { delay_ms } = self {
+            crate::runtime::sleep(std::time::Duration::from_millis(delay_ms)).await;
+        }
+    }
+}
+
+impl Authenticator {
+    /// Takes the fault armed for this proof generation, if any.
+    pub(super) fn take_fault(&self) -> Option<FaultKind> {
+        // the slot is only ever replaced whole, so a panic cannot corrupt it
+        self.pending_fault
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take()
+    }
+}
+
+#[uniffi::export]
+impl Authenticator {
+    /// Arms `fault` for the next proof generation, replacing any fault armed
+    /// before. It is applied to exactly one proof generation and cleared when
+    /// that starts, whether or not it succeeds.
+    ///
+    /// For testing the app's handling of proofs that RPs reject, against
+    /// staging only.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] on an authenticator configured
+    /// for the production `WorldIDRegistry`, or for
+    /// [`FaultKind::DelayMs`] on wasm32.
+    pub fn inject_fault(&self, fault: FaultKind) -> Result<(), WalletKitError> {
+        if *self.inner.config.registry_address() == crate::defaults::WORLD_ID_REGISTRY {
+            return Err(WalletKitError::InvalidInput {
+                attribute: "fault".to_string(),
+                reason: "faults cannot be injected in production".to_string(),
+            });
+        }
+        if cfg!(target_arch = "wasm32") && matches!(fault, FaultKind::DelayMs { .. }) {
+            return Err(WalletKitError::InvalidInput {
+                attribute: "fault".to_string(),
+                reason: "delays are not supported on wasm32".to_string(),
+            });
+        }
+        *self
+            .pending_fault
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(fault);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use alloy::primitives::Address;
+    use world_id_core::primitives::{Config, Nullifier, ServiceEndpoint};
+    use world_id_core::requests::{RequestVersion, ResponseItem};
+    use world_id_core::{Authenticator as CoreAuthenticator, FieldElement};
+
+    use super::*;
+    use crate::defaults::{STAGING_WORLD_ID_REGISTRY, WORLD_ID_REGISTRY};
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+    use crate::storage::CredentialStore;
+
+    fn response(words: [U256; 5]) -> CoreProofResponse {
+        CoreProofResponse {
+            id: "request-1".to_string(),
+            version: RequestVersion::V1,
+            session_id: None,
+            error: None,
+            responses: vec![ResponseItem::new_uniqueness(
+                "orb".to_string(),
+                1,
+                ZeroKnowledgeProof::from_ethereum_representation(words),
+                Nullifier::from(FieldElement::from(1u64)),
+                0,
+            )],
+        }
+    }
+
+    fn words(response: &CoreProofResponse) -> [U256; 5] {
+        response.responses[0].proof.as_ethereum_representation()
+    }
+
+    #[test]
+    fn test_faults_corrupt_the_proof() {
+        let original = [
+            U256::from(11),
+            U256::from(12),
+            U256::from(13),
+            U256::from(14),
+            U256::from(123),
+        ];
+
+        let mut corrupted = response(original);
+        FaultKind::CorruptProofBytes.apply(&mut corrupted);
+        let corrupted = words(&corrupted);
+        assert_eq!(
+            corrupted
+                .iter()
+                .zip(&original)
+                .map(|(a, b)| (*a ^ *b).count_ones())
+                .sum::<usize>(),
+            1
+        );
+        assert_ne!(corrupted[..4], original[..4]);
+        assert_eq!(corrupted[MERKLE_ROOT_INDEX], original[MERKLE_ROOT_INDEX]);
+
+        let mut stale = response(original);
+        FaultKind::StaleRoot.apply(&mut stale);
+        let stale = words(&stale);
+        assert_eq!(stale[..4], original[..4]);
+        assert_ne!(stale[MERKLE_ROOT_INDEX], original[MERKLE_ROOT_INDEX]);
+        assert!(FieldElement::try_from(stale[MERKLE_ROOT_INDEX]).is_ok());
+
+        for fault in [
+            FaultKind::DelayMs { delay_ms: 1 },
+            FaultKind::NullifierReplayOnNext,
+        ] {
+            let mut untouched = response(original);
+            fault.apply(&mut untouched);
+            assert_eq!(words(&untouched), original);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_faults_clear_after_one_use_and_refuse_production() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let mut mock_server = mockito::Server::new_async().await;
+        mock_server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "result": "0x0000000000000000000000000000000000000000000000000000000000000001"
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = Arc::new(CredentialStore::from_provider(&provider).expect("store"));
+        store.init(1, 100).expect("init storage");
+
+        let authenticator_for = |registry: Address| {
+            let config = Config::new(
+                Some(mock_server.url()),
+                480,
+                registry,
+                ServiceEndpoint::direct(mock_server.url()),
+                ServiceEndpoint::direct(mock_server.url()),
+                vec![],
+                2,
+            )
+            .unwrap();
+            let store = Arc::clone(&store);
+            async move {
+                let inner = CoreAuthenticator::init(&[2u8; 32], config)
+                    .await
+                    .expect("init authenticator");
+                Authenticator::from_parts(inner, store)
+            }
+        };
+
+        let production = authenticator_for(WORLD_ID_REGISTRY).await;
+        assert!(matches!(
+            production.inject_fault(FaultKind::CorruptProofBytes),
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+        assert_eq!(production.take_fault(), None);
+
+        let staging = authenticator_for(STAGING_WORLD_ID_REGISTRY).await;
+        assert_eq!(staging.take_fault(), None);
+        staging.inject_fault(FaultKind::StaleRoot).expect("staging");
+        staging
+            .inject_fault(FaultKind::DelayMs { delay_ms: 5 })
+            .expect("staging");
+        let fault = staging.take_fault();
+        assert_eq!(fault, Some(FaultKind::DelayMs { delay_ms: 5 }));
+        assert_eq!(staging.take_fault(), None);
+
+        let started = std::time::Instant::now();
+        fault.expect("fault").delay().await;
+        assert!(started.elapsed() >= std::time::Duration::from_millis(5));
+
+        drop(mock_server);
+        cleanup_test_storage(&root);
+    }
+}
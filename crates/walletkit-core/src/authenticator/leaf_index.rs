@@ -0,0 +1,182 @@
+//! Leaf index discovery, for initializing storage without a cached leaf index.
+
+use super::Authenticator;
+use crate::error::WalletKitError;
+use crate::storage::{CredentialStore, StorageError};
+
+#[uniffi::export(async_runtime = "tokio")]
+impl Authenticator {
+    /// Resolves the account's leaf index from the registry (or the indexer,
+    /// if no RPC URL is configured) using the authenticator's
+    /// `onchain_address`.
+    ///
+    /// Unlike [`leaf_index`](Self::leaf_index), which returns the value read
+    /// when the authenticator was initialized, this queries the network, or
+    /// the shared caches if the authenticator was initialized with them.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::AccountDoesNotExist`] if no account is
+    /// registered for this authenticator, or an error if the lookup fails.
+    pub async fn discover_leaf_index(&self) -> Result<u64, WalletKitError> {
+        let packed_account_data = self.fetch_packed_account_data().await?;
+        // the low 64 bits, as in `CoreAuthenticator::leaf_index`; bits 192 to
+        // 223 hold the pubkey ID and the bits above the recovery counter
+        Ok(packed_account_data.as_limbs()[0])
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl CredentialStore {
+    /// Discovers the account's leaf index through `authenticator` and
+    /// initializes the store with it, in one call.
+    ///
+    /// Use this instead of [`init`](Self::init) when the leaf index is not
+    /// cached, e.g. after a reinstall. Returns the leaf index.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::LeafIndexMismatch`] if the store was already
+    /// initialized with a different leaf index, or an error if discovery or
+    /// initialization fails.
+    pub async fn init_auto(
+        &self,
+        authenticator: &Authenticator,
+        now: u64,
+    ) -> Result<u64, WalletKitError> {
+        let leaf_index = authenticator.discover_leaf_index().await?;
+        self.init(leaf_index, now).map_err(|error| match error {
+            StorageError::InvalidLeafIndex { expected, provided } => {
+                WalletKitError::LeafIndexMismatch {
+                    stored: expected,
+                    discovered: provided,
+                }
+            }
+            other => other.into(),
+        })?;
+        Ok(leaf_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use alloy::primitives::address;
+    use ruint::aliases::U256;
+    use world_id_core::primitives::{Config, ServiceEndpoint};
+    use world_id_core::Authenticator as CoreAuthenticator;
+
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+
+    const LEAF_INDEX: u64 = 5;
+
+    /// Serves `/packed-account` for an account at [`LEAF_INDEX`] and returns
+    /// an authenticator using it as its indexer, with the indexer mock.
+    async fn authenticator_with_indexer(
+        server: &mut mockito::ServerGuard,
+        store: &Arc<CredentialStore>,
+    ) -> (Authenticator, mockito::Mock) {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        // recovery counter 1 and pubkey ID 1 above the leaf index
+        let packed_account_data =
+            (U256::from(1) << 224) | (U256::from(1) << 192) | U256::from(LEAF_INDEX);
+        let indexer_mock = server
+            .mock("POST", "/packed-account")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "packed_account_data": format!("{packed_account_data:#x}"),
+                })
+                .to_string(),
+            )
+            .expect(2)
+            .create_async()
+            .await;
+
+        let config = Config::new(
+            None,
+            480,
+            address!("0x969947cFED008bFb5e3F32a25A1A2CDdf64d46fe"),
+            ServiceEndpoint::direct(server.url()),
+            ServiceEndpoint::direct(server.url()),
+            vec![],
+            2,
+        )
+        .unwrap();
+        let inner = CoreAuthenticator::init(&[2u8; 32], config)
+            .await
+            .expect("init authenticator");
+        let authenticator = Authenticator::from_parts(inner, Arc::clone(store));
+        (authenticator, indexer_mock)
+    }
+
+    #[tokio::test]
+    async fn test_init_auto_fresh_store() {
+        let mut server = mockito::Server::new_async().await;
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = Arc::new(CredentialStore::from_provider(&provider).expect("store"));
+        let (authenticator, indexer_mock) =
+            authenticator_with_indexer(&mut server, &store).await;
+
+        let leaf_index = store.init_auto(&authenticator, 100).await.unwrap();
+        // once by `CoreAuthenticator::init`, once by the discovery
+        indexer_mock.assert_async().await;
+        drop(server);
+        assert_eq!(leaf_index, LEAF_INDEX);
+        assert_eq!(store.leaf_index().unwrap(), LEAF_INDEX);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[tokio::test]
+    async fn test_init_auto_matching_leaf_index() {
+        let mut server = mockito::Server::new_async().await;
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = Arc::new(CredentialStore::from_provider(&provider).expect("store"));
+        store.init(LEAF_INDEX, 50).expect("init storage");
+        let (authenticator, indexer_mock) =
+            authenticator_with_indexer(&mut server, &store).await;
+
+        let leaf_index = store.init_auto(&authenticator, 100).await.unwrap();
+        // once by `CoreAuthenticator::init`, once by the discovery
+        indexer_mock.assert_async().await;
+        drop(server);
+        assert_eq!(leaf_index, LEAF_INDEX);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[tokio::test]
+    async fn test_init_auto_mismatch() {
+        let mut server = mockito::Server::new_async().await;
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = Arc::new(CredentialStore::from_provider(&provider).expect("store"));
+        store.init(9, 50).expect("init storage");
+        let (authenticator, indexer_mock) =
+            authenticator_with_indexer(&mut server, &store).await;
+
+        let error = store.init_auto(&authenticator, 100).await.unwrap_err();
+        indexer_mock.assert_async().await;
+        drop(server);
+        assert!(
+            matches!(
+                error,
+                WalletKitError::LeafIndexMismatch {
+                    stored: 9,
+                    discovered: LEAF_INDEX,
+                }
+            ),
+            "unexpected error: {error:?}"
+        );
+        assert_eq!(store.leaf_index().unwrap(), 9);
+
+        cleanup_test_storage(&root);
+    }
+}
@@ -0,0 +1,378 @@
+//! OTLP export of proof generation telemetry.
+//!
+//! Once enabled with [`Authenticator::enable_proof_metrics`], each
+//! [`Authenticator::generate_proof`] call is traced as a `proof.generate`
+//! span with the attributes `proof.credential_type` (the requested
+//! credential identifiers, comma separated) and `proof.environment`
+//! (`production`, `staging` or `custom`), and the child spans:
+//!
+//! | span                         | stage                                         |
+//! |------------------------------|-----------------------------------------------|
+//! | `indexer.fetch_merkle_proof` | loading or fetching the Merkle inclusion proof |
+//! | `zkp.compute`                | computing the ZK proofs                       |
+//! | `cache.write`                | recording the nullifier and consent entry     |
+//!
+//! Each call also increments the `proof.success` or `proof.failure` counter.
+//! Failures carry an `error.type` attribute, the snake case name of the
+//! [`WalletKitError`] variant. No request contents (signals, nonces, RP IDs)
+//! are exported.
+
+use std::future::Future;
+use std::sync::{Arc, PoisonError};
+
+use opentelemetry::metrics::{Counter, MeterProvider as _};
+use opentelemetry::trace::{
+    FutureExt as _, Status, TraceContextExt as _, Tracer as _, TracerProvider as _,
+};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig as _};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use world_id_core::primitives::Config;
+
+use super::Authenticator;
+use crate::defaults::{STAGING_WORLD_ID_REGISTRY, WORLD_ID_REGISTRY};
+use crate::error::WalletKitError;
+use crate::requests::ProofRequest;
+
+/// `service.name` of the exported resource, and the instrumentation scope.
+const SERVICE_NAME: &str = "walletkit";
+
+/// Where proof generation telemetry is exported to.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ProofMetricsConfig {
+    /// Base URL of an OTLP/HTTP collector, e.g. `http://localhost:4318`.
+    /// Spans are sent to `/v1/traces` and counters to `/v1/metrics`.
+    pub otlp_endpoint: String,
+}
+
+/// Exporters and instruments for proof generation telemetry.
+pub(super) struct ProofMetrics {
+    tracer_provider: SdkTracerProvider,
+    meter_provider: SdkMeterProvider,
+    tracer: SdkTracer,
+    success: Counter<u64>,
+    failure: Counter<u64>,
+}
+
+impl std::fmt::Debug for ProofMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProofMetrics").finish_non_exhaustive()
+    }
+}
+
+/// Carries the tracer to the stages of a traced proof generation.
+#[derive(Clone)]
+struct StageTracer(SdkTracer);
+
+impl ProofMetrics {
+    fn new(config: &ProofMetricsConfig) -> Result<Self, WalletKitError> {
+        let endpoint = config.otlp_endpoint.trim_end_matches('/');
+        if endpoint.is_empty() {
+            return Err(invalid_endpoint("must not be empty"));
+        }
+
+        let span_exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{endpoint}/v1/traces"))
+            .build()
+            .map_err(|err| invalid_endpoint(&err.to_string()))?;
+        let metric_exporter = MetricExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{endpoint}/v1/metrics"))
+            .build()
+            .map_err(|err| invalid_endpoint(&err.to_string()))?;
+
+        let resource = Resource::builder().with_service_name(SERVICE_NAME).build();
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_resource(resource.clone())
+            .with_batch_exporter(span_exporter)
+            .build();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_resource(resource)
+            .with_periodic_exporter(metric_exporter)
+            .build();
+
+        let meter = meter_provider.meter(SERVICE_NAME);
+        Ok(Self {
+            tracer: tracer_provider.tracer(SERVICE_NAME),
+            success: meter
+                .u64_counter("proof.success")
+                .with_description("Proofs generated")
+                .build(),
+            failure: meter
+                .u64_counter("proof.failure")
+                .with_description("Failed proof generations")
+                .build(),
+            tracer_provider,
+            meter_provider,
+        })
+    }
+
+    /// Runs `pipeline` under a `proof.generate` span and counts its outcome.
+    pub(super) async fn record<T>(
+        &self,
+        environment: &'static str,
+        credential_types: String,
+        pipeline: impl Future<Output = Result<T, WalletKitError>>,
+    ) -> Result<T, WalletKitError> {
+        let span = self
+            .tracer
+            .span_builder("proof.generate")
+            .with_attributes([
+                KeyValue::new("proof.credential_type", credential_types),
+                KeyValue::new("proof.environment", environment),
+            ])
+            .start(&self.tracer);
+        let cx = Context::current_with_span(span)
+            .with_value(StageTracer(self.tracer.clone()));
+
+        let result = pipeline.with_context(cx.clone()).await;
+
+        let span = cx.span();
+        match &result {
+            Ok(_) => {
+                self.success
+                    .add(1, &[KeyValue::new("proof.environment", environment)]);
+            }
+            Err(error) => {
+                let error_type: &'static str = error.into();
+                span.set_attribute(KeyValue::new("error.type", error_type));
+                span.set_status(Status::error(error.to_string()));
+                self.failure.add(
+                    1,
+                    &[
+                        KeyValue::new("proof.environment", environment),
+                        KeyValue::new("error.type", error_type),
+                    ],
+                );
+            }
+        }
+        span.end();
+        result
+    }
+
+    /// Exports all finished spans and the current counter values.
+    fn flush(&self) {
+        if let Err(err) = self.tracer_provider.force_flush() {
+            tracing::warn!(error = %err, "failed to flush proof spans");
+        }
+        if let Err(err) = self.meter_provider.force_flush() {
+            tracing::warn!(error = %err, "failed to flush proof counters");
+        }
+    }
+}
+
+fn invalid_endpoint(reason: &str) -> WalletKitError {
+    WalletKitError::InvalidInput {
+        attribute: "otlp_endpoint".to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// Starts a child span `name` of the current `proof.generate` span, if the
+/// proof generation is traced.
+fn stage_context(name: &'static str) -> Option<Context> {
+    let cx = Context::current();
+    let tracer = cx.get::<StageTracer>()?.0.clone();
+    let span = tracer.start_with_context(name, &cx);
+    Some(cx.with_span(span))
+}
+
+/// Runs the proof generation stage `future` under a child span `name`.
+pub(super) async fn traced<F: Future>(name: &'static str, future: F) -> F::Output {
+    let Some(cx) = stage_context(name) else {
+        return future.await;
+    };
+    let output = future.with_context(cx.clone()).await;
+    cx.span().end();
+    output
+}
+
+/// Synchronous counterpart of [`traced`].
+pub(super) fn traced_sync<T>(name: &'static str, stage: impl FnOnce() -> T) -> T {
+    let Some(cx) = stage_context(name) else {
+        return stage();
+    };
+    let output = {
+        let _guard = cx.clone().attach();
+        stage()
+    };
+    cx.span().end();
+    output
+}
+
+/// The `proof.environment` attribute for an authenticator using `config`.
+pub(super) fn environment(config: &Config) -> &'static str {
+    match *config.registry_address() {
+        address if address == WORLD_ID_REGISTRY => "production",
+        address if address == STAGING_WORLD_ID_REGISTRY => "staging",
+        _ => "custom",
+    }
+}
+
+/// The `proof.credential_type` attribute for `proof_request`.
+pub(super) fn credential_types(proof_request: &ProofRequest) -> String {
+    proof_request
+        .0
+        .requests
+        .iter()
+        .map(|item| item.identifier.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl Authenticator {
+    pub(super) fn proof_metrics(&self) -> Option<Arc<ProofMetrics>> {
+        self.proof_metrics
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+#[uniffi::export]
+impl Authenticator {
+    /// Exports spans and counters for [`generate_proof`](Self::generate_proof)
+    /// calls to the OTLP/HTTP collector in `config`. See the module docs of
+    /// `authenticator::metrics` for what is exported.
+    ///
+    /// Replaces the exporter of a previous call, flushing what it still
+    /// holds. Export failures are logged and never fail proof generation.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] if `otlp_endpoint` is not a
+    /// valid URL.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn enable_proof_metrics(
+        &self,
+        config: ProofMetricsConfig,
+    ) -> Result<(), WalletKitError> {
+        let metrics = Arc::new(ProofMetrics::new(&config)?);
+        let previous = self
+            .proof_metrics
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .replace(metrics);
+        if let Some(previous) = previous {
+            previous.flush();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Collects the bodies of OTLP export requests to `path`.
+    async fn collector(
+        server: &mut mockito::ServerGuard,
+        path: &str,
+    ) -> (mockito::Mock, Arc<Mutex<Vec<Vec<u8>>>>) {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let captured = Arc::clone(&bodies);
+        let mock = server
+            .mock("POST", path)
+            .with_status(200)
+            .with_header("content-type", "application/x-protobuf")
+            .with_body_from_request(move |request| {
+                captured
+                    .lock()
+                    .unwrap()
+                    .push(request.body().unwrap().clone());
+                Vec::new()
+            })
+            .expect_at_least(1)
+            .create_async()
+            .await;
+        (mock, bodies)
+    }
+
+    /// Whether any of `bodies` contains `needle`. OTLP protobuf encodes span
+    /// names, attribute keys and string values as raw UTF-8.
+    fn contains(bodies: &Mutex<Vec<Vec<u8>>>, needle: &str) -> bool {
+        bodies.lock().unwrap().iter().any(|body| {
+            body.windows(needle.len())
+                .any(|window| window == needle.as_bytes())
+        })
+    }
+
+    #[tokio::test]
+    async fn test_exports_proof_spans_and_counters() {
+        let mut server = mockito::Server::new_async().await;
+        let (traces_mock, traces) = collector(&mut server, "/v1/traces").await;
+        let (metrics_mock, metrics) = collector(&mut server, "/v1/metrics").await;
+        let proof_metrics = ProofMetrics::new(&ProofMetricsConfig {
+            otlp_endpoint: format!("{}/", server.url()),
+        })
+        .expect("metrics");
+
+        let succeeded = proof_metrics
+            .record("staging", "orb,document".to_string(), async {
+                traced("indexer.fetch_merkle_proof", async {}).await;
+                traced("zkp.compute", async {}).await;
+                traced_sync("cache.write", || ());
+                Ok(())
+            })
+            .await;
+        assert!(succeeded.is_ok());
+        let failed = proof_metrics
+            .record("staging", "orb".to_string(), async {
+                traced("indexer.fetch_merkle_proof", async {}).await;
+                Err::<(), _>(WalletKitError::OfflineProofUnavailable {
+                    cache_age_seconds: 0,
+                })
+            })
+            .await;
+        assert!(failed.is_err());
+        proof_metrics.flush();
+
+        traces_mock.assert_async().await;
+        metrics_mock.assert_async().await;
+        drop(server);
+        for needle in [
+            "proof.generate",
+            "indexer.fetch_merkle_proof",
+            "zkp.compute",
+            "cache.write",
+            "proof.credential_type",
+            "orb,document",
+            "proof.environment",
+            "staging",
+            "error.type",
+            "offline_proof_unavailable",
+        ] {
+            assert!(contains(&traces, needle), "no {needle:?} in exported spans");
+        }
+        for needle in [
+            "proof.success",
+            "proof.failure",
+            "error.type",
+            "offline_proof_unavailable",
+        ] {
+            assert!(
+                contains(&metrics, needle),
+                "no {needle:?} in exported metrics"
+            );
+        }
+    }
+
+    #[test]
+    fn test_stages_are_not_traced_without_a_root_span() {
+        assert!(stage_context("zkp.compute").is_none());
+        assert_eq!(traced_sync("cache.write", || 7), 7);
+    }
+
+    #[test]
+    fn test_rejects_empty_endpoint() {
+        let error = ProofMetrics::new(&ProofMetricsConfig {
+            otlp_endpoint: String::new(),
+        })
+        .unwrap_err();
+        assert!(matches!(error, WalletKitError::InvalidInput { .. }));
+    }
+}
@@ -7,23 +7,79 @@ use crate::{
 use alloy_core::primitives::Address;
 use ruint::aliases::U256;
 use ruint_uniffi::Uint256;
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
 use world_id_core::{
     api_types::{GatewayErrorCode, GatewayRequestState},
-    primitives::{AuthenticatorPublicKeySet, Config},
-    Authenticator as CoreAuthenticator, Credential as CoreCredential, CredentialInput,
+    primitives::{
+        merkle::AccountInclusionProof, AuthenticatorPublicKeySet, Config, TREE_DEPTH,
+    },
+    Authenticator as CoreAuthenticator, Credential as CoreCredential,
     InitializingAuthenticator as CoreInitializingAuthenticator,
     OnchainKeyRepresentable, Signer,
 };
 
+use crate::cancellation::CancellationHandle;
+use crate::limits::InputLimit;
+use crate::metrics_observer::{timed, timed_sync, ProofStage};
 use crate::requests::{ProofRequest, ProofResponse};
-use crate::storage::CredentialStore;
+use crate::runtime::compat;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::storage::StoragePaths;
+use crate::storage::{CredentialStore, NewConsent, StorageError};
 use crate::OwnershipProof;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod accounts;
+mod action_usage;
+mod attestation;
+mod audit;
+mod batch;
+mod cold_start;
+#[cfg(feature = "testing")]
+mod faults;
+mod leaf_index;
+#[cfg(all(feature = "opentelemetry", not(target_arch = "wasm32")))]
+mod metrics;
+mod passkey;
+#[cfg(not(target_arch = "wasm32"))]
+mod pool;
+mod proving;
+#[cfg(not(target_arch = "wasm32"))]
+mod refresher;
+mod registration;
+#[cfg(not(target_arch = "wasm32"))]
+mod shared_caches;
 mod with_storage;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use accounts::MultiAccountAuthenticator;
+pub use action_usage::ActionUsage;
+pub use attestation::{
+    verify_account_attestation, SignedAttestation, VerifiedAccountAttestation,
+    ACCOUNT_ATTESTATION_TTL_SECONDS,
+};
+pub use audit::{AuditEntry, ProofAuditTrail};
+pub use batch::BatchProofResult;
+pub use cold_start::{cold_start, ColdStartOptions, ColdStartResult};
+#[cfg(feature = "testing")]
+pub use faults::FaultKind;
+#[cfg(all(feature = "opentelemetry", not(target_arch = "wasm32")))]
+pub use metrics::ProofMetricsConfig;
+#[cfg(all(feature = "opentelemetry", not(target_arch = "wasm32")))]
+use metrics::{traced, traced_sync};
+pub use passkey::{
+    derive_seed_from_passkey_prf, passkey_prf_salt, PASSKEY_SEED_VERSION,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use pool::{AuthenticatorPool, AuthenticatorPoolOptions};
+pub use proving::{
+    InclusionProof, ProofOptions, ProofOutcome, ProofStats, ProvingProfile,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use shared_caches::AuthenticatorOptions;
+pub use with_storage::MERKLE_PROOF_VALIDITY_SECONDS;
+
 /// ZK Proof material for both Groth16 proofs (query & nullifier proofs)
 #[derive(Clone, uniffi::Object)]
 pub struct Groth16Materials {
@@ -138,10 +194,87 @@ impl Groth16Materials {
 #[derive(Debug, uniffi::Object)]
 pub struct Authenticator {
     inner: CoreAuthenticator,
-    store: Arc<CredentialStore>,
+    /// The credential store, `None` once the authenticator is closed.
+    store: std::sync::RwLock<Option<Arc<CredentialStore>>>,
+    #[cfg(all(feature = "opentelemetry", not(target_arch = "wasm32")))]
+    proof_metrics: std::sync::RwLock<Option<Arc<metrics::ProofMetrics>>>,
+    #[cfg(feature = "proof-audit")]
+    proof_audit: std::sync::atomic::AtomicBool,
+    /// The fault armed for the next proof generation.
+    #[cfg(feature = "testing")]
+    pending_fault: std::sync::Mutex<Option<FaultKind>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    shared_caches: Option<Arc<crate::SharedCaches>>,
+    /// Bounds the proofs generated at once by the authenticators of an
+    /// [`AuthenticatorPool`], `None` outside a pool.
+    #[cfg(not(target_arch = "wasm32"))]
+    proving_permits: Option<Arc<tokio::sync::Semaphore>>,
+    /// Background refresh of the cached Merkle inclusion proof.
+    #[cfg(not(target_arch = "wasm32"))]
+    merkle_refresher: refresher::MerkleProofRefresher,
+}
+
+/// What the holder's app records alongside a disclosure, see
+/// [`NewConsent::consent_text_hash`] and [`Authenticator::generate_proof_with_memo`].
+#[derive(Debug, Clone, Copy, Default)]
+struct DisclosureNotes<'a> {
+    consent_text_hash: Option<&'a [u8]>,
+    memo: Option<&'a [u8]>,
 }
 
 impl Authenticator {
+    pub(crate) fn from_parts(
+        inner: CoreAuthenticator,
+        store: Arc<CredentialStore>,
+    ) -> Self {
+        Self {
+            inner,
+            store: std::sync::RwLock::new(Some(store)),
+            #[cfg(all(feature = "opentelemetry", not(target_arch = "wasm32")))]
+            proof_metrics: std::sync::RwLock::new(None),
+            #[cfg(feature = "proof-audit")]
+            proof_audit: std::sync::atomic::AtomicBool::new(false),
+            #[cfg(feature = "testing")]
+            pending_fault: std::sync::Mutex::new(None),
+            #[cfg(not(target_arch = "wasm32"))]
+            shared_caches: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            proving_permits: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            merkle_refresher: refresher::MerkleProofRefresher::default(),
+        }
+    }
+
+    /// Returns the scope of this authenticator's replay guard entries and
+    /// session seeds in the credential store, e.g. for
+    /// [`CredentialStore::get_session_seed`]. Authenticators share a scope
+    /// exactly if they share the chain and `WorldIDRegistry`.
+    #[must_use]
+    pub fn environment_scope(&self) -> [u8; 32] {
+        environment_scope(&self.inner.config)
+    }
+
+    /// Returns the credential store, or [`StorageError::Closed`] once the
+    /// authenticator is closed.
+    fn store(&self) -> Result<Arc<CredentialStore>, StorageError> {
+        // the slot is only ever replaced whole, so a panic cannot corrupt it
+        self.store
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+            .ok_or(StorageError::Closed)
+    }
+
+    /// Fetches the packed account data, through the shared caches if the
+    /// authenticator was initialized with them.
+    async fn fetch_packed_account_data(&self) -> Result<U256, WalletKitError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(shared_caches) = &self.shared_caches {
+            return self.fetch_packed_account_data_shared(shared_caches).await;
+        }
+        Ok(compat(self.inner.fetch_packed_account_data()).await?)
+    }
+
     /// Initializes a new Authenticator from a seed and an already-parsed
     /// [`Config`].
     ///
@@ -153,16 +286,176 @@ impl Authenticator {
         materials: Arc<Groth16Materials>,
         store: Arc<CredentialStore>,
     ) -> Result<Self, WalletKitError> {
-        let authenticator = CoreAuthenticator::init(seed, config)
+        InputLimit::Seed.check(seed.len())?;
+        let authenticator = compat(CoreAuthenticator::init(seed, config))
             .await?
             .with_proof_materials(
                 Arc::clone(&materials.query),
                 Arc::clone(&materials.nullifier),
             );
-        Ok(Self {
-            inner: authenticator,
-            store,
-        })
+        Ok(Self::from_parts(authenticator, store))
+    }
+
+    /// Generates a proof for `proof_request` against the given Merkle
+    /// inclusion proof.
+    ///
+    /// `notes` go into the consent ledger entry, if the ledger is enabled, and
+    /// the replay guard entry. `profile` decides which credentials are loaded, see
+    /// [`ProvingProfile`].
+    async fn generate_proof_with_inclusion_proof(
+        &self,
+        proof_request: &ProofRequest,
+        account_inclusion_proof: AccountInclusionProof<TREE_DEPTH>,
+        notes: DisclosureNotes<'_>,
+        profile: ProvingProfile,
+        now: u64,
+    ) -> Result<ProofResponse, WalletKitError> {
+        let (response, _) = self
+            .generate_proof_audited(
+                proof_request,
+                account_inclusion_proof,
+                notes,
+                profile,
+                false,
+                now,
+            )
+            .await?;
+        Ok(response)
+    }
+
+    /// Like [`generate_proof_with_inclusion_proof`](Self::generate_proof_with_inclusion_proof),
+    /// additionally collecting a [`ProofAuditTrail`] if `audit` is set.
+    #[cfg_attr(
+        feature = "tracing-spans",
+        tracing::instrument(
+            target = "walletkit",
+            name = "generate_proof",
+            skip_all,
+            fields(request_id = %proof_request.0.id),
+            err(Display)
+        )
+    )]
+    async fn generate_proof_audited(
+        &self,
+        proof_request: &ProofRequest,
+        account_inclusion_proof: AccountInclusionProof<TREE_DEPTH>,
+        notes: DisclosureNotes<'_>,
+        profile: ProvingProfile,
+        audit: bool,
+        now: u64,
+    ) -> Result<(ProofResponse, Option<ProofAuditTrail>), WalletKitError> {
+        #[cfg(feature = "testing")]
+        let fault = self.take_fault();
+        proof_request.check_valid_until(now)?;
+        proof_request.check_chain_id(self.inner.config.chain_id())?;
+        #[cfg(not(target_arch = "wasm32"))]
+        let _proving_permit = self.proving_permit().await;
+        self.invalidate_stale_credentials(now)?;
+
+        let store = self.store()?;
+        let credentials =
+            proving::load_credential_inputs(&store, proof_request, profile, now)?;
+
+        // Generate the nullifier and check the replay guard
+        // Box::pin to heap-allocate the large upstream futures and keep this future below clippy::large_futures threshold
+        let nullifier = timed(
+            ProofStage::NullifierGeneration,
+            compat(Box::pin(self.inner.generate_nullifier(
+                &proof_request.0,
+                Some(account_inclusion_proof.clone()),
+            ))),
+        )
+        .await?;
+
+        let environment = self.environment_scope();
+        #[cfg(feature = "testing")]
+        let guard_replays = fault != Some(FaultKind::NullifierReplayOnNext);
+        #[cfg(not(feature = "testing"))]
+        let guard_replays = true;
+        if guard_replays {
+            begin_replay_guard(
+                &store,
+                environment,
+                nullifier.verifiable_oprf_output.output.into(),
+                &proof_request.0.id,
+                now,
+            )?;
+        }
+
+        // Get cached `session_id_r_seed` if session ID is provided in the proof request
+        let session_id_r_seed =
+            cached_session_seed(&store, environment, &proof_request.0, now);
+
+        #[cfg(feature = "testing")]
+        if let Some(fault) = fault {
+            fault.delay().await;
+        }
+
+        // Handles credential selection, session resolution, per-credential proofs, response assembly, and validation
+        let result = traced(
+            "zkp.compute",
+            timed(
+                ProofStage::ProofComputation,
+                compat(Box::pin(self.inner.generate_proof(
+                    &proof_request.0,
+                    nullifier.clone(),
+                    &credentials,
+                    Some(account_inclusion_proof),
+                    session_id_r_seed,
+                ))),
+            ),
+        )
+        .await?;
+        #[cfg(feature = "testing")]
+        let result = {
+            let mut result = result;
+            if let Some(fault) = fault {
+                fault.apply(&mut result.proof_response);
+            }
+            result
+        };
+
+        let audit_trail = audit
+            .then(|| {
+                ProofAuditTrail::collect(
+                    &proof_request.0,
+                    &result.proof_response,
+                    self.inner.leaf_index(),
+                    result.session_id_r_seed.or(session_id_r_seed),
+                )
+            })
+            .transpose()?;
+
+        // Cache session seed if returned. Create-session requests do not carry a
+        // session_id, so use the session_id generated in the proof response.
+        if let (Some(seed), Some(session_id)) =
+            (result.session_id_r_seed, result.proof_response.session_id)
+        {
+            if let Err(err) =
+                store.store_session_seed(environment, session_id.oprf_seed, seed, now)
+            {
+                tracing::error!("error caching session_id_r_seed: {}", err);
+            }
+        }
+
+        traced_sync("cache.write", || {
+            timed_sync(ProofStage::CacheWrite, || {
+                finalize_proof(
+                    &store,
+                    environment,
+                    nullifier.verifiable_oprf_output.output.into(),
+                    &proof_request.0,
+                    &result.proof_response,
+                    notes,
+                    now,
+                )
+            })
+        })?;
+
+        Ok((
+            ProofResponse(result.proof_response, proof_request.valid_until()),
+            audit_trail,
+        ))
     }
 }
 
@@ -196,6 +489,10 @@ impl Authenticator {
 
     /// Returns the packed account data for the holder's World ID fetching it from the on-chain registry.
     ///
+    /// If the authenticator shares caches with other instances, see
+    /// [`AuthenticatorOptions`], a value fetched within the cache's TTL is
+    /// returned instead.
+    ///
     /// # Errors
     /// Will error if the provided RPC URL is not valid or if there are RPC call failures.
     #[tracing::instrument(
@@ -206,7 +503,7 @@ impl Authenticator {
     pub async fn get_packed_account_data_remote(
         &self,
     ) -> Result<Uint256, WalletKitError> {
-        let packed_account_data = self.inner.fetch_packed_account_data().await?;
+        let packed_account_data = self.fetch_packed_account_data().await?;
         Ok(packed_account_data.into())
     }
 
@@ -227,11 +524,12 @@ impl Authenticator {
         &self,
         issuer_schema_id: u64,
     ) -> Result<FieldElement, WalletKitError> {
-        Ok(self
-            .inner
-            .generate_credential_blinding_factor(issuer_schema_id)
-            .await
-            .map(Into::into)?)
+        Ok(compat(
+            self.inner
+                .generate_credential_blinding_factor(issuer_schema_id),
+        )
+        .await
+        .map(Into::into)?)
     }
 
     /// Compute the `sub` for a credential from the authenticator's leaf index and a `blinding_factor`.
@@ -291,10 +589,11 @@ impl Authenticator {
     ) -> Result<RecoveryUpdateSignature, WalletKitError> {
         let new_recovery_agent =
             Address::parse_from_ffi(&new_recovery_agent, "new_recovery_agent")?;
-        let (sig, nonce) = self
-            .inner
-            .danger_sign_initiate_recovery_agent_update(new_recovery_agent)
-            .await?;
+        let (sig, nonce) = compat(
+            self.inner
+                .danger_sign_initiate_recovery_agent_update(new_recovery_agent),
+        )
+        .await?;
         Ok(RecoveryUpdateSignature {
             signature: sig.as_bytes().to_vec(),
             nonce: nonce.into(),
@@ -323,10 +622,11 @@ impl Authenticator {
         let new_recovery_agent =
             Address::parse_from_ffi(&new_recovery_agent, "new_recovery_agent")?;
 
-        let request_id = self
-            .inner
-            .initiate_recovery_agent_update(new_recovery_agent)
-            .await?;
+        let request_id = compat(
+            self.inner
+                .initiate_recovery_agent_update(new_recovery_agent),
+        )
+        .await?;
 
         Ok(request_id.to_string())
     }
@@ -346,7 +646,7 @@ impl Authenticator {
     pub async fn execute_recovery_agent_update(
         &self,
     ) -> Result<String, WalletKitError> {
-        let request_id = self.inner.execute_recovery_agent_update().await?;
+        let request_id = compat(self.inner.execute_recovery_agent_update()).await?;
 
         Ok(request_id.to_string())
     }
@@ -362,7 +662,7 @@ impl Authenticator {
     /// Returns a network error if the gateway request fails.
     #[allow(deprecated)]
     pub async fn cancel_recovery_agent_update(&self) -> Result<String, WalletKitError> {
-        let request_id = self.inner.cancel_recovery_agent_update().await?;
+        let request_id = compat(self.inner.cancel_recovery_agent_update()).await?;
 
         Ok(request_id.to_string())
     }
@@ -429,11 +729,7 @@ impl Authenticator {
         materials: Arc<Groth16Materials>,
         store: Arc<CredentialStore>,
     ) -> Result<Self, WalletKitError> {
-        let config =
-            Config::from_json(config).map_err(|_| WalletKitError::InvalidInput {
-                attribute: "config".to_string(),
-                reason: "Invalid config".to_string(),
-            })?;
+        let config = parse_config(config)?;
         Self::init_with_config(seed, config, materials, store).await
     }
 
@@ -446,117 +742,151 @@ impl Authenticator {
         proof_request: &ProofRequest,
         now: Option<u64>,
     ) -> Result<ProofResponse, WalletKitError> {
-        let now = if let Some(n) = now {
-            n
-        } else {
-            #[cfg(target_arch = "wasm32")]
-            {
-                return Err(WalletKitError::InvalidInput {
-                    attribute: "now".to_string(),
-                    reason: "`now` must be provided on wasm32 targets".to_string(),
-                });
-            }
-
-            #[cfg(not(target_arch = "wasm32"))]
-            {
-                let start = std::time::SystemTime::now();
-                start
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .map_err(|e| WalletKitError::Generic {
-                        error: format!("Critical. Unable to determine SystemTime: {e}"),
-                    })?
-                    .as_secs()
-            }
-        };
+        let now = resolve_now(now)?;
+        // Box::pin to keep this future below clippy::large_futures threshold
+        let pipeline = Box::pin(async {
+            let account_inclusion_proof = traced(
+                "indexer.fetch_merkle_proof",
+                self.fetch_inclusion_proof_with_cache(now),
+            )
+            .await?;
+            self.generate_proof_with_inclusion_proof(
+                proof_request,
+                account_inclusion_proof,
+                DisclosureNotes::default(),
+                ProvingProfile::default(),
+                now,
+            )
+            .await
+        });
+        #[cfg(all(feature = "opentelemetry", not(target_arch = "wasm32")))]
+        if let Some(metrics) = self.proof_metrics() {
+            return metrics
+                .record(
+                    metrics::environment(&self.inner.config),
+                    metrics::credential_types(proof_request),
+                    pipeline,
+                )
+                .await;
+        }
+        pipeline.await
+    }
 
-        // Build CredentialInput list from storage
-        // Note: We simply load all non-expired credentials. Filtering for the requested schema IDs is done in `generate_proof`.
-        // We could avoid unnecessary loading by filtering via `world_id_primitives::ProofRequest::credentials_to_prove`. We consider this an
-        // unnecessary optimization for now.
-        let credentials: Vec<_> = self
-            .store
-            .list_credentials(None, now)?
-            .iter()
-            .filter(|c| !c.is_expired)
-            .filter_map(|cred| {
-                if let Ok(Some((credential, blinding_factor))) =
-                    self.store.get_credential(cred.issuer_schema_id, now)
-                {
-                    Some(CredentialInput {
-                        credential: credential.into(),
-                        blinding_factor: blinding_factor.into(),
-                    })
-                } else {
-                    tracing::warn!(
-                        issuer_schema_id = %cred.issuer_schema_id,
-                        credential_id = %cred.credential_id,
-                        "credential listed but not loadable, skipping"
-                    );
-                    None
-                }
-            })
-            .collect();
+    /// Like [`generate_proof`](Self::generate_proof), but abandoned as soon
+    /// as `cancellation` is cancelled, e.g. when the user dismisses the proof
+    /// dialog. See [`CancellationHandle`] for when cancellation takes effect.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::Cancelled`] if cancelled, or an error if
+    /// proof generation fails.
+    pub async fn generate_proof_with_cancellation(
+        &self,
+        proof_request: &ProofRequest,
+        cancellation: Arc<CancellationHandle>,
+        now: Option<u64>,
+    ) -> Result<ProofResponse, WalletKitError> {
+        cancellation
+            .run(self.generate_proof(proof_request, now))
+            .await
+    }
 
+    /// Like [`generate_proof`](Self::generate_proof), additionally recording
+    /// `consent_text_hash` (the hash of the consent text shown to the user) in
+    /// the consent ledger entry.
+    ///
+    /// See [`CredentialStore::set_consent_ledger_enabled`].
+    ///
+    /// # Errors
+    /// Returns an error if proof generation fails.
+    pub async fn generate_proof_with_consent(
+        &self,
+        proof_request: &ProofRequest,
+        consent_text_hash: Vec<u8>,
+        now: Option<u64>,
+    ) -> Result<ProofResponse, WalletKitError> {
+        let now = resolve_now(now)?;
         let account_inclusion_proof =
             self.fetch_inclusion_proof_with_cache(now).await?;
+        self.generate_proof_with_inclusion_proof(
+            proof_request,
+            account_inclusion_proof,
+            DisclosureNotes {
+                consent_text_hash: Some(&consent_text_hash),
+                memo: None,
+            },
+            ProvingProfile::default(),
+            now,
+        )
+        .await
+    }
 
-        // Generate the nullifier and check the replay guard
-        // Box::pin to heap-allocate the large upstream futures and keep this future below clippy::large_futures threshold
-        let nullifier = Box::pin(self.inner.generate_nullifier(
-            &proof_request.0,
-            Some(account_inclusion_proof.clone()),
-        ))
-        .await?;
-
-        if self
-            .store
-            .is_nullifier_replay(nullifier.verifiable_oprf_output.output.into(), now)?
-        {
-            return Err(WalletKitError::NullifierReplay);
-        }
-
-        // Get cached `session_id_r_seed` if session ID is provided in the proof request
-        let session_id_r_seed =
-            proof_request
-                .0
-                .session_id
-                .and_then(|session_id| {
-                    match self.store.get_session_seed(session_id.oprf_seed, now) {
-                        Ok(seed) => seed,
-                        Err(err) => {
-                            tracing::warn!(error = %err, "failed to load cached session seed, continuing without");
-                            None
-                        }
-                    }
-                });
-
-        // Handles credential selection, session resolution, per-credential proofs, response assembly, and validation
-        let result = Box::pin(self.inner.generate_proof(
-            &proof_request.0,
-            nullifier.clone(),
-            &credentials,
-            Some(account_inclusion_proof),
-            session_id_r_seed,
-        ))
-        .await?;
-
-        // Cache session seed if returned. Create-session requests do not carry a
-        // session_id, so use the session_id generated in the proof response.
-        if let Some(seed) = result.session_id_r_seed {
-            if let Some(session_id) = result.proof_response.session_id {
-                if let Err(err) =
-                    self.store
-                        .store_session_seed(session_id.oprf_seed, seed, now)
-                {
-                    tracing::error!("error caching session_id_r_seed: {}", err);
-                }
-            }
-        }
-
-        self.store
-            .replay_guard_set(nullifier.verifiable_oprf_output.output.into(), now)?;
+    /// Like [`generate_proof`](Self::generate_proof), additionally attaching
+    /// `memo` to the disclosure, e.g. a note the user types or an order
+    /// number, to help them recognize it later in a dispute with the RP.
+    ///
+    /// The memo is kept with the replay guard entry of the request's nullifier
+    /// and expires with it. It is only listed by
+    /// [`CredentialStore::list_disclosures`] if requested there, and is never
+    /// part of a vault backup. If the nullifier was already disclosed for the
+    /// same request, the first memo is kept.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] if `memo` exceeds
+    /// [`MAX_DISCLOSURE_MEMO_BYTES`](crate::limits::MAX_DISCLOSURE_MEMO_BYTES),
+    /// or an error if proof generation fails.
+    pub async fn generate_proof_with_memo(
+        &self,
+        proof_request: &ProofRequest,
+        memo: Vec<u8>,
+        now: Option<u64>,
+    ) -> Result<ProofResponse, WalletKitError> {
+        InputLimit::DisclosureMemo.check(memo.len())?;
+        let now = resolve_now(now)?;
+        let account_inclusion_proof =
+            self.fetch_inclusion_proof_with_cache(now).await?;
+        self.generate_proof_with_inclusion_proof(
+            proof_request,
+            account_inclusion_proof,
+            DisclosureNotes {
+                consent_text_hash: None,
+                memo: Some(&memo),
+            },
+            ProvingProfile::default(),
+            now,
+        )
+        .await
+    }
 
-        Ok(result.proof_response.into())
+    /// Generates a proof using only the cached Merkle inclusion proof, for
+    /// when the indexer is unreachable.
+    ///
+    /// The cached proof is used as long as it was fetched at most
+    /// `max_proof_age` seconds before `now`, even past its cache TTL. An older
+    /// root may have been evicted from the registry's root history, in which
+    /// case verifiers reject the proof, so keep `max_proof_age` well below
+    /// the registry's root validity window.
+    ///
+    /// Nullifier generation still queries the OPRF nodes.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::OfflineProofUnavailable`] if no cached proof
+    /// is recent enough, or an error if proof generation fails.
+    pub async fn prove_offline(
+        &self,
+        proof_request: &ProofRequest,
+        max_proof_age: u64,
+        now: u64,
+    ) -> Result<ProofResponse, WalletKitError> {
+        let account_inclusion_proof =
+            self.cached_inclusion_proof_with_max_age(max_proof_age, now)?;
+        self.generate_proof_with_inclusion_proof(
+            proof_request,
+            account_inclusion_proof,
+            DisclosureNotes::default(),
+            ProvingProfile::default(),
+            now,
+        )
+        .await
     }
 
     /// Generates a WIP-103 Ownership Proof for Issuers.
@@ -608,15 +938,13 @@ impl Authenticator {
                 .as_secs();
 
             let inclusion_proof = self.fetch_inclusion_proof_with_cache(now).await?;
-            let proof = self
-                .inner
-                .prove_credential_sub(
-                    nonce.0,
-                    blinding_factor.0,
-                    sub.0,
-                    Some(inclusion_proof),
-                )
-                .await?;
+            let proof = compat(self.inner.prove_credential_sub(
+                nonce.0,
+                blinding_factor.0,
+                sub.0,
+                Some(inclusion_proof),
+            ))
+            .await?;
 
             Ok(OwnershipProof(proof))
         }
@@ -641,6 +969,12 @@ pub enum RegistrationStatus {
         /// Specific error code, if available.
         error_code: Option<String>,
     },
+    /// The gateway's gas sponsorship is exhausted. Not a failure: the request
+    /// is kept and proceeds once the sponsorship quota resets, so poll again.
+    SponsorshipExhausted {
+        /// Seconds to wait before polling again, if the gateway provided it.
+        retry_after: Option<u64>,
+    },
 }
 
 impl From<GatewayRequestState> for RegistrationStatus {
@@ -692,8 +1026,9 @@ impl InitializingAuthenticator {
 
         let config = defaults::default_config(environment, rpc_url, region)?;
 
+        InputLimit::Seed.check(seed.len())?;
         let initializing_authenticator =
-            CoreAuthenticator::register(seed, config, recovery_address).await?;
+            compat(CoreAuthenticator::register(seed, config, recovery_address)).await?;
 
         Ok(Self(initializing_authenticator))
     }
@@ -725,8 +1060,9 @@ impl InitializingAuthenticator {
 
         let config = defaults::default_config_with_ohttp(environment, rpc_url, region)?;
 
+        InputLimit::Seed.check(seed.len())?;
         let initializing_authenticator =
-            CoreAuthenticator::register(seed, config, recovery_address).await?;
+            compat(CoreAuthenticator::register(seed, config, recovery_address)).await?;
 
         Ok(Self(initializing_authenticator))
     }
@@ -752,14 +1088,11 @@ impl InitializingAuthenticator {
         let recovery_address =
             Address::parse_from_ffi_optional(recovery_address, "recovery_address")?;
 
-        let config =
-            Config::from_json(config).map_err(|_| WalletKitError::InvalidInput {
-                attribute: "config".to_string(),
-                reason: "Invalid config".to_string(),
-            })?;
+        let config = parse_config(config)?;
 
+        InputLimit::Seed.check(seed.len())?;
         let initializing_authenticator =
-            CoreAuthenticator::register(seed, config, recovery_address).await?;
+            compat(CoreAuthenticator::register(seed, config, recovery_address)).await?;
 
         Ok(Self(initializing_authenticator))
     }
@@ -767,15 +1100,68 @@ impl InitializingAuthenticator {
     /// Polls the registration status from the gateway.
     ///
     /// # Errors
-    /// Will error if the network request fails or the gateway returns an error.
+    /// Will error if the network request fails or the gateway returns an
+    /// error, other than exhausted sponsorship, which is reported as
+    /// [`RegistrationStatus::SponsorshipExhausted`].
     #[tracing::instrument(
         target = "walletkit_latency",
         name = "gateway_poll",
         skip_all
     )]
+    #[cfg_attr(
+        feature = "tracing-spans",
+        tracing::instrument(
+            target = "walletkit",
+            name = "poll_status",
+            skip_all,
+            err(Display)
+        )
+    )]
     pub async fn poll_status(&self) -> Result<RegistrationStatus, WalletKitError> {
-        let status = self.0.poll_status().await?;
-        Ok(status.into())
+        match compat(self.0.poll_status()).await {
+            Ok(status) => Ok(status.into()),
+            Err(error) => registration::sponsorship_exhausted(&error)
+                .map_or_else(|| Err(error.into()), Ok),
+        }
+    }
+
+    /// Like [`poll_status`](Self::poll_status), but abandoned as soon as
+    /// `cancellation` is cancelled, e.g. when the user leaves the onboarding
+    /// screen.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::Cancelled`] if cancelled, or an error as
+    /// [`poll_status`](Self::poll_status) does.
+    pub async fn poll_status_with_cancellation(
+        &self,
+        cancellation: Arc<CancellationHandle>,
+    ) -> Result<RegistrationStatus, WalletKitError> {
+        cancellation.run(self.poll_status()).await
+    }
+}
+
+impl InitializingAuthenticator {
+    /// Registers a new World ID with an already parsed config.
+    ///
+    /// # Errors
+    /// See `CoreAuthenticator::register` for potential errors.
+    #[cfg(feature = "v3")]
+    pub(crate) async fn register_with_config(
+        seed: &[u8],
+        config: Config,
+        recovery_address: Option<Address>,
+    ) -> Result<Self, WalletKitError> {
+        InputLimit::Seed.check(seed.len())?;
+        let initializing_authenticator =
+            compat(CoreAuthenticator::register(seed, config, recovery_address)).await?;
+
+        Ok(Self(initializing_authenticator))
+    }
+
+    /// The gateway request ID of the pending registration.
+    #[cfg(feature = "v3")]
+    pub(crate) fn request_id(&self) -> &str {
+        self.0.request_id().as_str()
     }
 }
 
@@ -821,6 +1207,7 @@ impl RecoveryData {
     /// # Errors
     /// Returns [`WalletKitError`] if the seed is invalid or serialization fails.
     pub fn from_seed(seed: &[u8]) -> Result<Self, WalletKitError> {
+        InputLimit::Seed.check(seed.len())?;
         let signer = Signer::from_seed_bytes(seed)?;
         let authenticator_address = signer.onchain_signer_address().to_checksum(None);
         let authenticator_pubkey: U256 = signer
@@ -849,6 +1236,143 @@ pub fn recovery_data_from_seed(seed: &[u8]) -> Result<RecoveryData, WalletKitErr
     RecoveryData::from_seed(seed)
 }
 
+/// Parses an authenticator config JSON, rejecting oversized input unparsed.
+///
+/// # Errors
+/// Returns [`WalletKitError::InvalidInput`] if the config is too large or
+/// invalid.
+pub fn parse_config(config: &str) -> Result<Config, WalletKitError> {
+    InputLimit::Config.check(config.len())?;
+    Config::from_json(config).map_err(|_| WalletKitError::InvalidInput {
+        attribute: "config".to_string(),
+        reason: "Invalid config".to_string(),
+    })
+}
+
+/// The scope in the credential store of replay guard entries and session
+/// seeds for proofs generated with `config`: the SHA-256 digest of the chain
+/// ID (big-endian) and the `WorldIDRegistry` address.
+///
+/// Nullifiers and sessions of one environment (e.g. staging) are never
+/// checked against those of another, even if they share a store.
+pub fn environment_scope(config: &Config) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(config.chain_id().to_be_bytes());
+    hasher.update(config.registry_address());
+    hasher.finalize().into()
+}
+
+/// Marks the replay guard entry of `nullifier` as used and records the
+/// disclosure of `proof_response` to the RP that sent `proof_request`.
+fn finalize_proof(
+    store: &CredentialStore,
+    environment: [u8; 32],
+    nullifier: world_id_core::FieldElement,
+    proof_request: &world_id_core::requests::ProofRequest,
+    proof_response: &world_id_core::requests::ProofResponse,
+    notes: DisclosureNotes<'_>,
+    now: u64,
+) -> Result<(), StorageError> {
+    let rp_id_hash = Sha256::digest(proof_request.rp_id.into_inner().to_be_bytes());
+    let disclosed_schema_ids = proof_response
+        .responses
+        .iter()
+        .map(|response| response.issuer_schema_id)
+        .collect::<Vec<_>>();
+    store.finalize_proof(
+        environment,
+        nullifier,
+        NewConsent {
+            request_id: &proof_request.id,
+            rp_id_hash: rp_id_hash.as_slice(),
+            disclosed_schema_ids: &disclosed_schema_ids,
+            consent_text_hash: notes.consent_text_hash,
+        },
+        notes.memo,
+        now,
+    )
+}
+
+/// Returns the cached `session_id_r_seed` for the session of `proof_request`,
+/// if it has one. A failed lookup is logged and treated as a miss.
+fn cached_session_seed(
+    store: &CredentialStore,
+    environment: [u8; 32],
+    proof_request: &world_id_core::requests::ProofRequest,
+    now: u64,
+) -> Option<world_id_core::FieldElement> {
+    let session_id = proof_request.session_id?;
+    match store.get_session_seed(environment, session_id.oprf_seed, now) {
+        Ok(seed) => seed,
+        Err(err) => {
+            tracing::warn!(error = %err, "failed to load cached session seed, continuing without");
+            None
+        }
+    }
+}
+
+/// Checks the replay guard before a proof for `request_id` is generated.
+///
+/// # Errors
+/// Returns [`WalletKitError::NullifierReplay`] if the nullifier was already
+/// disclosed for the same request, and [`WalletKitError::NullifierConflict`]
+/// if it was disclosed for a different one.
+fn begin_replay_guard(
+    store: &CredentialStore,
+    environment: [u8; 32],
+    nullifier: world_id_core::FieldElement,
+    request_id: &str,
+    now: u64,
+) -> Result<(), WalletKitError> {
+    if store.begin_replay_guard(environment, nullifier, request_id, now)? {
+        return Err(WalletKitError::NullifierReplay);
+    }
+    Ok(())
+}
+
+/// Runs the proof generation stage `name`. Only traced with the
+/// `opentelemetry` feature.
+#[cfg(not(all(feature = "opentelemetry", not(target_arch = "wasm32"))))]
+async fn traced<F: std::future::Future>(_name: &'static str, future: F) -> F::Output {
+    future.await
+}
+
+/// Synchronous counterpart of [`traced`].
+#[cfg(not(all(feature = "opentelemetry", not(target_arch = "wasm32"))))]
+fn traced_sync<T>(_name: &'static str, stage: impl FnOnce() -> T) -> T {
+    stage()
+}
+
+/// Returns `now`, or the current system time if it is not provided.
+///
+/// # Errors
+/// Returns an error on wasm32 targets if `now` is not provided, or if the system
+/// time cannot be determined.
+fn resolve_now(now: Option<u64>) -> Result<u64, WalletKitError> {
+    if let Some(n) = now {
+        return Ok(n);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        Err(WalletKitError::InvalidInput {
+            attribute: "now".to_string(),
+            reason: "`now` must be provided on wasm32 targets".to_string(),
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let start = std::time::SystemTime::now();
+        Ok(start
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| WalletKitError::Generic {
+                error: format!("Critical. Unable to determine SystemTime: {e}"),
+            })?
+            .as_secs())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -875,6 +1399,84 @@ mod tests {
         assert!(RecoveryData::from_seed(&[]).is_err());
     }
 
+    #[test]
+    fn test_replay_guard_distinguishes_replay_from_conflict() {
+        use crate::storage::tests_utils::{
+            cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+            TEST_ENVIRONMENT,
+        };
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("store");
+        store.init(1, 100).expect("init storage");
+        let nullifier = world_id_core::FieldElement::from(42u64);
+        let consent = |request_id| NewConsent {
+            request_id,
+            rp_id_hash: &[0; 32],
+            disclosed_schema_ids: &[],
+            consent_text_hash: None,
+        };
+
+        begin_replay_guard(&store, TEST_ENVIRONMENT, nullifier, "request-1", 1000)
+            .expect("not disclosed");
+        store
+            .finalize_proof(
+                TEST_ENVIRONMENT,
+                nullifier,
+                consent("request-1"),
+                None,
+                1000,
+            )
+            .expect("finalize proof");
+
+        // both are allowed within the grace period
+        begin_replay_guard(&store, TEST_ENVIRONMENT, nullifier, "request-1", 1060)
+            .expect("grace period");
+        begin_replay_guard(&store, TEST_ENVIRONMENT, nullifier, "request-2", 1060)
+            .expect("grace period");
+
+        let now = 1000 + 3600;
+        assert!(matches!(
+            begin_replay_guard(&store, TEST_ENVIRONMENT, nullifier, "request-1", now),
+            Err(WalletKitError::NullifierReplay)
+        ));
+        match begin_replay_guard(&store, TEST_ENVIRONMENT, nullifier, "request-2", now)
+        {
+            Err(WalletKitError::NullifierConflict {
+                conflicting_request,
+            }) => assert_eq!(
+                conflicting_request,
+                hex::encode(Sha256::digest(b"request-1"))
+            ),
+            other => panic!("expected a nullifier conflict, got {other:?}"),
+        }
+
+        // entries written without a request ID are treated as replays
+        let other_nullifier = world_id_core::FieldElement::from(43u64);
+        store
+            .replay_guard_set(TEST_ENVIRONMENT, other_nullifier, 1000)
+            .expect("set replay guard");
+        assert!(matches!(
+            begin_replay_guard(
+                &store,
+                TEST_ENVIRONMENT,
+                other_nullifier,
+                "request-2",
+                now
+            ),
+            Err(WalletKitError::NullifierReplay)
+        ));
+
+        // entries never block proofs against another environment
+        begin_replay_guard(&store, [0xDD; 32], nullifier, "request-2", now)
+            .expect("other environment");
+        begin_replay_guard(&store, [0xDD; 32], other_nullifier, "request-2", now)
+            .expect("other environment");
+
+        cleanup_test_storage(&root);
+    }
+
     #[cfg(feature = "embed-zkeys")]
     #[tokio::test]
     async fn test_init_with_config_and_materials() {
@@ -0,0 +1,255 @@
+//! Passkey-based unlock: derives the authenticator seed from the output of the
+//! `WebAuthn` PRF extension (CTAP2 `hmac-secret`).
+//!
+//! The host evaluates the passkey's PRF on [`passkey_prf_salt`] and hands the
+//! 32-byte output to [`Authenticator::init_with_passkey_prf`], which derives
+//! the seed with [`derive_seed_from_passkey_prf`] so the seed itself never
+//! crosses the FFI boundary. With the `passkey` feature, wasm32 builds can
+//! instead run the assertion themselves through
+//! `Authenticator::init_with_passkey`.
+//!
+//! The derivation is versioned: a passkey always unlocks the same identity on
+//! every platform as long as [`PASSKEY_SEED_VERSION`] is unchanged.
+
+use std::sync::Arc;
+
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use super::{Authenticator, Groth16Materials};
+use crate::storage::CredentialStore;
+use crate::{defaults, error::WalletKitError, Environment, Region};
+
+/// Version of the passkey seed derivation.
+pub const PASSKEY_SEED_VERSION: u8 = 1;
+
+/// Length of a PRF extension output, and of the derived seed.
+const PRF_OUTPUT_LEN: usize = 32;
+
+/// Domain separator hashed into the PRF evaluation input.
+const PRF_SALT_DOMAIN: &[u8] = b"worldid:passkey-prf:v1";
+
+/// HKDF salt and info for the seed derivation.
+const SEED_HKDF_SALT: &[u8] = b"worldid:passkey-seed";
+const SEED_HKDF_INFO: &[u8] = b"worldid:passkey-seed:v1";
+
+/// Returns the input to evaluate the passkey's PRF on (`prf.eval.first`).
+///
+/// Every platform must use this exact value to derive the same seed.
+#[uniffi::export]
+#[must_use]
+pub fn passkey_prf_salt() -> Vec<u8> {
+    Sha256::digest(PRF_SALT_DOMAIN).to_vec()
+}
+
+/// Derives the authenticator seed from a passkey PRF output.
+///
+/// # Errors
+/// Returns [`WalletKitError::InvalidInput`] if `prf_output` is not 32 bytes.
+pub fn derive_seed_from_passkey_prf(
+    prf_output: &[u8],
+) -> Result<Zeroizing<[u8; 32]>, WalletKitError> {
+    if prf_output.len() != PRF_OUTPUT_LEN {
+        return Err(WalletKitError::InvalidInput {
+            attribute: "prf_output".to_string(),
+            reason: format!(
+                "expected {PRF_OUTPUT_LEN} bytes, got {}",
+                prf_output.len()
+            ),
+        });
+    }
+    let mut seed = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(SEED_HKDF_SALT), prf_output)
+        .expand(SEED_HKDF_INFO, seed.as_mut())
+        .map_err(|err| WalletKitError::Generic {
+            error: format!("passkey seed derivation failed: {err}"),
+        })?;
+    Ok(seed)
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl Authenticator {
+    /// Initializes a new Authenticator with SDK defaults from the output of a
+    /// passkey's PRF extension evaluated on [`passkey_prf_salt`].
+    ///
+    /// # Errors
+    /// Returns an error if `prf_output` is not 32 bytes; see
+    /// [`Authenticator::init_with_defaults`] for the remaining errors.
+    #[uniffi::constructor]
+    pub async fn init_with_passkey_prf(
+        prf_output: &[u8],
+        rpc_url: Option<String>,
+        environment: &Environment,
+        region: Option<Region>,
+        materials: Arc<Groth16Materials>,
+        store: Arc<CredentialStore>,
+    ) -> Result<Self, WalletKitError> {
+        let seed = derive_seed_from_passkey_prf(prf_output)?;
+        let config = defaults::default_config(environment, rpc_url, region)?;
+        Self::init_with_config(seed.as_slice(), config, materials, store).await
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "passkey"))]
+#[uniffi::export(async_runtime = "tokio")]
+impl Authenticator {
+    /// Initializes a new Authenticator with SDK defaults by asserting the
+    /// passkey `credential_id` for `rp_id` through `navigator.credentials`.
+    ///
+    /// The PRF output and the derived seed stay inside the wasm module.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::PasskeyPrfUnavailable`] if the browser or
+    /// authenticator does not support the PRF extension, so the app can offer
+    /// another unlock path. See [`Authenticator::init_with_defaults`] for the
+    /// remaining errors.
+    #[uniffi::constructor]
+    pub async fn init_with_passkey(
+        credential_id: &[u8],
+        rp_id: String,
+        rpc_url: Option<String>,
+        environment: &Environment,
+        region: Option<Region>,
+        materials: Arc<Groth16Materials>,
+        store: Arc<CredentialStore>,
+    ) -> Result<Self, WalletKitError> {
+        let prf_output = web::assert_prf(credential_id, &rp_id).await?;
+        Self::init_with_passkey_prf(
+            &prf_output,
+            rpc_url,
+            environment,
+            region,
+            materials,
+            store,
+        )
+        .await
+    }
+}
+
+/// `navigator.credentials.get` plumbing for the PRF extension.
+#[cfg(all(target_arch = "wasm32", feature = "passkey"))]
+mod web {
+    use js_sys::{Array, Object, Reflect, Uint8Array};
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_futures::JsFuture;
+    use zeroize::Zeroizing;
+
+    use super::passkey_prf_salt;
+    use crate::error::WalletKitError;
+
+    fn js_error(context: &str, err: &JsValue) -> WalletKitError {
+        WalletKitError::Generic {
+            error: format!("{context}: {err:?}"),
+        }
+    }
+
+    fn set(target: &Object, key: &str, value: &JsValue) -> Result<(), WalletKitError> {
+        Reflect::set(target, &JsValue::from_str(key), value)
+            .map(|_| ())
+            .map_err(|err| js_error("failed to build passkey request", &err))
+    }
+
+    fn get(target: &JsValue, key: &str) -> Option<JsValue> {
+        Reflect::get(target, &JsValue::from_str(key))
+            .ok()
+            .filter(|value| !value.is_undefined() && !value.is_null())
+    }
+
+    /// Asserts `credential_id` with the PRF extension and returns the output.
+    pub(super) async fn assert_prf(
+        credential_id: &[u8],
+        rp_id: &str,
+    ) -> Result<Zeroizing<Vec<u8>>, WalletKitError> {
+        let window = web_sys::window().ok_or_else(|| WalletKitError::Generic {
+            error: "passkeys require a browser window".to_string(),
+        })?;
+
+        let mut challenge = [0u8; 32];
+        getrandom::fill(&mut challenge).map_err(|err| WalletKitError::Generic {
+            error: format!("failed to generate passkey challenge: {err}"),
+        })?;
+
+        let allowed = Object::new();
+        set(&allowed, "type", &JsValue::from_str("public-key"))?;
+        set(&allowed, "id", &Uint8Array::from(credential_id).into())?;
+
+        let eval = Object::new();
+        set(
+            &eval,
+            "first",
+            &Uint8Array::from(passkey_prf_salt().as_slice()).into(),
+        )?;
+        let prf = Object::new();
+        set(&prf, "eval", &eval.into())?;
+        let extensions = Object::new();
+        set(&extensions, "prf", &prf.into())?;
+
+        let public_key = Object::new();
+        set(
+            &public_key,
+            "challenge",
+            &Uint8Array::from(&challenge[..]).into(),
+        )?;
+        set(&public_key, "rpId", &JsValue::from_str(rp_id))?;
+        set(
+            &public_key,
+            "allowCredentials",
+            &Array::of1(&allowed).into(),
+        )?;
+        set(
+            &public_key,
+            "userVerification",
+            &JsValue::from_str("required"),
+        )?;
+        set(&public_key, "extensions", &extensions.into())?;
+        let options = Object::new();
+        set(&options, "publicKey", &public_key.into())?;
+
+        let promise = window
+            .navigator()
+            .credentials()
+            .get_with_options(options.unchecked_ref())
+            .map_err(|err| js_error("passkey assertion failed", &err))?;
+        let credential = JsFuture::from(promise)
+            .await
+            .map_err(|err| js_error("passkey assertion failed", &err))?;
+
+        let results = get(&credential, "getClientExtensionResults")
+            .and_then(|f| f.dyn_into::<js_sys::Function>().ok())
+            .and_then(|f| f.call0(&credential).ok())
+            .ok_or(WalletKitError::PasskeyPrfUnavailable)?;
+        let first = get(&results, "prf")
+            .and_then(|prf| get(&prf, "results"))
+            .and_then(|results| get(&results, "first"))
+            .ok_or(WalletKitError::PasskeyPrfUnavailable)?;
+        Ok(Zeroizing::new(Uint8Array::new(&first).to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passkey_seed_derivation_vector() {
+        assert_eq!(
+            hex::encode(passkey_prf_salt()),
+            "d57dfbbcde40fdfd88db0ade098c9f7df07a875300092e60ff0c2a9fa3434a6d"
+        );
+        let prf_output = (0u8..32).collect::<Vec<_>>();
+        let seed = derive_seed_from_passkey_prf(&prf_output).expect("derive seed");
+        assert_eq!(
+            hex::encode(seed.as_slice()),
+            "3e6e26fc2cc104a0e1cbe2fad5ff8ef040d7b138c1ba79dab101cebeb227617f"
+        );
+    }
+
+    #[test]
+    fn test_passkey_seed_rejects_truncated_prf_output() {
+        assert!(matches!(
+            derive_seed_from_passkey_prf(&[0u8; 16]),
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+    }
+}
@@ -0,0 +1,270 @@
+//! Authenticators for many accounts in one process, sharing what does not
+//! depend on the account.
+//!
+//! Hosts holding many World IDs (e.g. a custodial backend) create one
+//! [`AuthenticatorPool`] per configuration and mint an [`Authenticator`] per
+//! seed from it. The authenticators share the pool's Groth16 materials and
+//! [`SharedCaches`], and generate at most
+//! [`AuthenticatorPoolOptions::max_concurrent_proofs`] proofs at once, so
+//! memory for proving witnesses stays bounded however many accounts prove
+//! concurrently.
+//!
+//! Nothing derived from a seed is shared: the shared caches only hold public
+//! data, keyed by on-chain address, and each authenticator keeps its keys
+//! and its [`CredentialStore`] to itself.
+
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use world_id_core::primitives::Config;
+
+use super::{parse_config, Authenticator, Groth16Materials};
+use crate::error::WalletKitError;
+use crate::shared_cache::{
+    SharedCaches, DEFAULT_ACTION_CONFIG_TTL_SECS, DEFAULT_PACKED_ACCOUNT_DATA_TTL_SECS,
+};
+use crate::storage::CredentialStore;
+
+/// Default of [`AuthenticatorPoolOptions::max_concurrent_proofs`].
+const DEFAULT_MAX_CONCURRENT_PROOFS: u32 = 4;
+
+/// Options for [`AuthenticatorPool::new`].
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct AuthenticatorPoolOptions {
+    /// Maximum number of proofs the pool's authenticators generate at once.
+    /// Further proof generations wait for one of them to finish.
+    #[uniffi(default = 4)]
+    pub max_concurrent_proofs: u32,
+    /// Caches to share, usually [`SharedCaches::global`]. The pool creates
+    /// its own, with the default TTLs, if `None`.
+    #[uniffi(default = None)]
+    pub shared_caches: Option<Arc<SharedCaches>>,
+}
+
+impl Default for AuthenticatorPoolOptions {
+    fn default() -> Self {
+        Self {
+            max_concurrent_proofs: DEFAULT_MAX_CONCURRENT_PROOFS,
+            shared_caches: None,
+        }
+    }
+}
+
+/// Mints [`Authenticator`]s for one configuration that share resources, see
+/// the [module documentation](self).
+#[derive(Debug, uniffi::Object)]
+pub struct AuthenticatorPool {
+    config: Config,
+    materials: Arc<Groth16Materials>,
+    shared_caches: Arc<SharedCaches>,
+    proving_permits: Arc<Semaphore>,
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl AuthenticatorPool {
+    /// Creates a pool for authenticators with the given JSON `config`.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] if the config is invalid or
+    /// `max_concurrent_proofs` is zero.
+    #[uniffi::constructor]
+    pub fn new(
+        config: &str,
+        materials: Arc<Groth16Materials>,
+        options: AuthenticatorPoolOptions,
+    ) -> Result<Self, WalletKitError> {
+        let config = parse_config(config)?;
+        if options.max_concurrent_proofs == 0 {
+            return Err(WalletKitError::InvalidInput {
+                attribute: "max_concurrent_proofs".to_string(),
+                reason: "must be at least 1".to_string(),
+            });
+        }
+        Ok(Self {
+            config,
+            materials,
+            shared_caches: options.shared_caches.unwrap_or_else(|| {
+                Arc::new(SharedCaches::new(
+                    DEFAULT_ACTION_CONFIG_TTL_SECS,
+                    DEFAULT_PACKED_ACCOUNT_DATA_TTL_SECS,
+                ))
+            }),
+            proving_permits: Arc::new(Semaphore::new(
+                options.max_concurrent_proofs as usize,
+            )),
+        })
+    }
+
+    /// Initializes an authenticator for the account of `seed`, storing its
+    /// credentials in `store`, as [`Authenticator::init`] does.
+    ///
+    /// # Errors
+    /// Returns an error if the seed is invalid or the account cannot be
+    /// loaded from the registry.
+    pub async fn authenticator(
+        &self,
+        seed: &[u8],
+        store: Arc<CredentialStore>,
+    ) -> Result<Arc<Authenticator>, WalletKitError> {
+        let mut authenticator = Authenticator::init_with_config(
+            seed,
+            self.config.clone(),
+            Arc::clone(&self.materials),
+            store,
+        )
+        .await?;
+        authenticator.share_caches(Arc::clone(&self.shared_caches));
+        authenticator.proving_permits = Some(Arc::clone(&self.proving_permits));
+        Ok(Arc::new(authenticator))
+    }
+
+    /// Returns the caches the pool's authenticators share, e.g. for
+    /// [`AppRegistryClient::with_shared_caches`](crate::AppRegistryClient::with_shared_caches).
+    #[must_use]
+    pub fn shared_caches(&self) -> Arc<SharedCaches> {
+        Arc::clone(&self.shared_caches)
+    }
+}
+
+impl Authenticator {
+    /// Waits until this authenticator may generate a proof. Authenticators
+    /// outside a pool never wait.
+    pub(super) async fn proving_permit(&self) -> Option<OwnedSemaphorePermit> {
+        let permits = Arc::clone(self.proving_permits.as_ref()?);
+        // the pool never closes its semaphore
+        permits.acquire_owned().await.ok()
+    }
+}
+
+#[cfg(all(test, feature = "embed-zkeys"))]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use ruint::aliases::U256;
+    use tokio::task::JoinSet;
+    use world_id_core::primitives::ServiceEndpoint;
+
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+
+    const ACCOUNTS: u8 = 50;
+
+    fn config(url: String) -> String {
+        let config = Config::new(
+            None,
+            480,
+            alloy::primitives::address!("0x969947cFED008bFb5e3F32a25A1A2CDdf64d46fe"),
+            ServiceEndpoint::direct(url.clone()),
+            ServiceEndpoint::direct(url),
+            vec![],
+            2,
+        )
+        .unwrap();
+        serde_json::to_string(&config).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_pool_shares_resources_and_bounds_proving() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let mut server = mockito::Server::new_async().await;
+        let packed_account_data = (U256::from(1) << 192) | U256::from(5);
+        server
+            .mock("POST", "/packed-account")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "packed_account_data": format!("{packed_account_data:#x}"),
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = Arc::new(CredentialStore::from_provider(&provider).expect("store"));
+        store.init(5, 100).expect("init storage");
+        let materials =
+            Arc::new(Groth16Materials::from_embedded().expect("load materials"));
+        let pool = Arc::new(
+            AuthenticatorPool::new(
+                &config(server.url()),
+                Arc::clone(&materials),
+                AuthenticatorPoolOptions {
+                    max_concurrent_proofs: 3,
+                    shared_caches: None,
+                },
+            )
+            .expect("pool"),
+        );
+
+        let mut tasks = JoinSet::new();
+        for i in 1..=ACCOUNTS {
+            let pool = Arc::clone(&pool);
+            let store = Arc::clone(&store);
+            tasks.spawn(async move { pool.authenticator(&[i; 32], store).await });
+        }
+        let authenticators = tasks
+            .join_all()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .expect("init authenticators");
+
+        // one copy of the materials and caches, however many accounts
+        assert_eq!(
+            Arc::strong_count(&materials.query),
+            usize::from(ACCOUNTS) + 1
+        );
+        assert_eq!(
+            Arc::strong_count(&pool.shared_caches),
+            usize::from(ACCOUNTS) + 1
+        );
+        let onchain_addresses: std::collections::HashSet<_> = authenticators
+            .iter()
+            .map(|authenticator| authenticator.inner.onchain_address())
+            .collect();
+        assert_eq!(onchain_addresses.len(), usize::from(ACCOUNTS));
+
+        let proving = Arc::new(AtomicUsize::new(0));
+        let max_proving = Arc::new(AtomicUsize::new(0));
+        let mut tasks = JoinSet::new();
+        for authenticator in authenticators {
+            let proving = Arc::clone(&proving);
+            let max_proving = Arc::clone(&max_proving);
+            tasks.spawn(async move {
+                let _permit = authenticator.proving_permit().await.expect("pooled");
+                let now_proving = proving.fetch_add(1, Ordering::SeqCst) + 1;
+                max_proving.fetch_max(now_proving, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                proving.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        tasks.join_all().await;
+        assert_eq!(max_proving.load(Ordering::SeqCst), 3);
+
+        drop(server);
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_pool_rejects_zero_concurrency() {
+        let materials =
+            Arc::new(Groth16Materials::from_embedded().expect("load materials"));
+        assert!(matches!(
+            AuthenticatorPool::new(
+                &config("https://indexer.example".to_string()),
+                materials,
+                AuthenticatorPoolOptions {
+                    max_concurrent_proofs: 0,
+                    shared_caches: None,
+                },
+            ),
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+    }
+}
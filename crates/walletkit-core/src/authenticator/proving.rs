@@ -0,0 +1,438 @@
+//! Memory/speed trade-offs for proof generation.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use ruint::aliases::U256;
+use ruint_uniffi::Uint256;
+use world_id_core::primitives::merkle::AccountInclusionProof;
+use world_id_core::primitives::TREE_DEPTH;
+use world_id_core::CredentialInput;
+
+use super::{resolve_now, Authenticator, DisclosureNotes, ProofAuditTrail};
+use crate::error::WalletKitError;
+use crate::requests::{ProofRequest, ProofResponse};
+use crate::storage::{CredentialStatus, CredentialStore};
+
+/// How proof generation trades speed for memory.
+///
+/// The Groth16 witness generation and proving inside `world-id-core` have
+/// the same peak for every profile, and dominate it. The profiles bound what
+/// `WalletKit` itself holds in memory while proving:
+///
+/// - [`Speed`](Self::Speed): every active credential is decrypted up front,
+///   and the most recently used ones stay in the session credential cache.
+///   Peak ≈ prover peak + the size of all stored credentials.
+/// - [`Balanced`](Self::Balanced): only credentials of requested issuer
+///   schemas are decrypted; the session cache is kept.
+///   Peak ≈ prover peak + the size of the requested credentials.
+/// - [`LowMemory`](Self::LowMemory): like `Balanced`, but credentials are
+///   read straight from the vault and the session cache is emptied before
+///   proving, so repeated proofs re-read the vault.
+///   Peak ≈ prover peak + the size of the requested credentials, with no
+///   cached copies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, uniffi::Enum)]
+pub enum ProvingProfile {
+    /// Favor latency; hold all credentials in memory.
+    Speed,
+    /// Load only what the request needs.
+    #[default]
+    Balanced,
+    /// Release decrypted credentials as early as possible, at the cost of
+    /// re-reading the vault.
+    LowMemory,
+}
+
+/// Options for [`Authenticator::generate_proof_with_options`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, uniffi::Record)]
+pub struct ProofOptions {
+    /// Memory/speed trade-off.
+    pub profile: ProvingProfile,
+    /// Whether to measure the proof generation, see [`ProofStats`].
+    #[uniffi(default = false)]
+    pub collect_stats: bool,
+    /// Whether to return the Merkle inclusion proof the proof was generated
+    /// against, see [`ProofOutcome::inclusion_proof`].
+    #[uniffi(default = false)]
+    pub include_inclusion_proof: bool,
+}
+
+/// Measurements of a single proof generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Record)]
+pub struct ProofStats {
+    /// Peak bytes allocated while generating the proof, on the thread that
+    /// drove it. `None` unless the `alloc-stats` allocator is installed, see
+    /// [`crate::alloc_stats`].
+    pub peak_alloc_bytes_estimate: Option<u64>,
+    /// Wall-clock duration of the proof generation in milliseconds.
+    pub duration_ms: u64,
+}
+
+/// A proof together with its measurements.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ProofOutcome {
+    /// The generated proof.
+    pub response: Arc<ProofResponse>,
+    /// Measurements, if [`ProofOptions::collect_stats`] was set. Never
+    /// collected on wasm32.
+    pub stats: Option<ProofStats>,
+    /// The account's inclusion proof the proof was generated against, if
+    /// [`ProofOptions::include_inclusion_proof`] was set. It is not part of
+    /// the response JSON; hosts forward it to the RP themselves if needed.
+    pub inclusion_proof: Option<InclusionProof>,
+    /// The pre-images hashed while proving, if the authenticator has proof
+    /// auditing enabled, see `Authenticator::set_proof_audit_enabled`.
+    /// Always `None` without the `proof-audit` feature. Contains sensitive
+    /// material; never log or persist it.
+    pub audit_trail: Option<ProofAuditTrail>,
+}
+
+/// Merkle inclusion proof of an account in the `WorldIDRegistry`, for RPs
+/// verifying proofs on-chain or auditing them.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct InclusionProof {
+    /// Root of the Merkle tree, the one returned by [`ProofResponse::root`].
+    pub root: Uint256,
+    /// Position of the account's leaf in the tree.
+    pub leaf_index: u64,
+    /// Sibling hashes from the leaf up to the root.
+    pub siblings: Vec<Uint256>,
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl Authenticator {
+    /// Like [`generate_proof`](Self::generate_proof), with a choice of
+    /// [`ProvingProfile`] and optional [`ProofStats`].
+    ///
+    /// # Errors
+    /// Returns an error if proof generation fails.
+    pub async fn generate_proof_with_options(
+        &self,
+        proof_request: &ProofRequest,
+        options: ProofOptions,
+        now: Option<u64>,
+    ) -> Result<ProofOutcome, WalletKitError> {
+        let now = resolve_now(now)?;
+        let stats = options.collect_stats.then(StatsCollector::start);
+        let account_inclusion_proof =
+            self.fetch_inclusion_proof_with_cache(now).await?;
+        let proven = options
+            .include_inclusion_proof
+            .then(|| account_inclusion_proof.clone());
+        #[cfg(feature = "proof-audit")]
+        let audit = self.proof_audit_enabled();
+        #[cfg(not(feature = "proof-audit"))]
+        let audit = false;
+        let (response, audit_trail) = self
+            .generate_proof_audited(
+                proof_request,
+                account_inclusion_proof,
+                DisclosureNotes::default(),
+                options.profile,
+                audit,
+                now,
+            )
+            .await?;
+        let inclusion_proof = proven
+            .map(|proven| inclusion_proof_of(&response, &proven))
+            .transpose()?;
+        Ok(ProofOutcome {
+            response: Arc::new(response),
+            stats: stats.and_then(StatsCollector::finish),
+            inclusion_proof,
+            audit_trail,
+        })
+    }
+}
+
+/// Converts `account_inclusion_proof` for the host, after checking that every
+/// proof in `response` was generated against its root.
+///
+/// # Errors
+/// Returns [`WalletKitError::Generic`] if a proof carries a different root.
+fn inclusion_proof_of(
+    response: &ProofResponse,
+    account_inclusion_proof: &AccountInclusionProof<TREE_DEPTH>,
+) -> Result<InclusionProof, WalletKitError> {
+    let proof = &account_inclusion_proof.inclusion_proof;
+    let root: U256 = proof.root.into();
+    if let Some(item) = response
+        .0
+        .responses
+        .iter()
+        .find(|item| item.proof.as_ethereum_representation()[4] != root)
+    {
+        return Err(WalletKitError::Generic {
+            error: format!(
+                "proof for `{}` was generated against a different Merkle root than the inclusion proof",
+                item.identifier
+            ),
+        });
+    }
+    Ok(InclusionProof {
+        root: root.into(),
+        leaf_index: proof.leaf_index,
+        siblings: proof
+            .siblings
+            .iter()
+            .map(|&sibling| {
+                let sibling: U256 = sibling.into();
+                sibling.into()
+            })
+            .collect(),
+    })
+}
+
+/// Loads the credentials to prove with from `store`, according to `profile`.
+///
+/// Credentials that are listed but cannot be loaded are skipped.
+///
+/// # Errors
+/// Returns an error if the credentials cannot be listed.
+pub(super) fn load_credential_inputs(
+    store: &CredentialStore,
+    proof_request: &ProofRequest,
+    profile: ProvingProfile,
+    now: u64,
+) -> Result<Vec<CredentialInput>, WalletKitError> {
+    let requested = match profile {
+        ProvingProfile::Speed => None,
+        ProvingProfile::Balanced | ProvingProfile::LowMemory => Some(
+            proof_request
+                .0
+                .requests
+                .iter()
+                .map(|item| item.issuer_schema_id)
+                .collect::<HashSet<_>>(),
+        ),
+    };
+    if profile == ProvingProfile::LowMemory {
+        store.clear_credential_cache()?;
+    }
+
+    Ok(store
+        .list_credentials(None, now)?
+        .iter()
+        .filter(|c| c.status == CredentialStatus::Active)
+        .filter(|c| {
+            requested
+                .as_ref()
+                .is_none_or(|ids| ids.contains(&c.issuer_schema_id))
+        })
+        .filter_map(|cred| {
+            let loaded = if profile == ProvingProfile::LowMemory {
+                store.get_credential_uncached(cred.issuer_schema_id, now)
+            } else {
+                store.get_credential(cred.issuer_schema_id, now)
+            };
+            if let Ok(Some((credential, blinding_factor))) = loaded {
+                Some(CredentialInput {
+                    credential: credential.into(),
+                    blinding_factor: blinding_factor.into(),
+                })
+            } else {
+                tracing::warn!(
+                    issuer_schema_id = %cred.issuer_schema_id,
+                    credential_id = %cred.credential_id,
+                    "credential listed but not loadable, skipping"
+                );
+                None
+            }
+        })
+        .collect())
+}
+
+/// Collects [`ProofStats`] over a proof generation.
+struct StatsCollector {
+    #[cfg(not(target_arch = "wasm32"))]
+    started: std::time::Instant,
+    #[cfg(any(test, feature = "alloc-stats"))]
+    peak: crate::alloc_stats::PeakTracker,
+}
+
+impl StatsCollector {
+    fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            started: std::time::Instant::now(),
+            #[cfg(any(test, feature = "alloc-stats"))]
+            peak: crate::alloc_stats::PeakTracker::start(),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[expect(
+        clippy::unnecessary_wraps,
+        clippy::needless_pass_by_value,
+        reason = "wasm32 has no monotonic clock, so no stats are collected"
+    )]
+    fn finish(self) -> Option<ProofStats> {
+        None
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[expect(clippy::unnecessary_wraps, reason = "matches the wasm32 signature")]
+    fn finish(self) -> Option<ProofStats> {
+        #[cfg(any(test, feature = "alloc-stats"))]
+        let peak_alloc_bytes_estimate = self.peak.peak_bytes();
+        #[cfg(not(any(test, feature = "alloc-stats")))]
+        let peak_alloc_bytes_estimate = None;
+        Some(ProofStats {
+            peak_alloc_bytes_estimate,
+            duration_ms: u64::try_from(self.started.elapsed().as_millis())
+                .unwrap_or(u64::MAX),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::signers::Signature;
+    use alloy_core::primitives::U160;
+    use taceo_oprf::types::OprfKeyId;
+    use world_id_core::primitives::rp::RpId;
+    use world_id_core::requests::{
+        ProofRequest as CoreProofRequest, ProofType, RequestItem, RequestVersion,
+    };
+    use world_id_core::Credential as CoreCredential;
+
+    use super::*;
+    use crate::alloc_stats::PeakTracker;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+    use crate::{Credential, FieldElement};
+
+    fn request_for(issuer_schema_id: u64) -> ProofRequest {
+        ProofRequest::from(CoreProofRequest {
+            id: "test".to_string(),
+            version: RequestVersion::V1,
+            proof_type: ProofType::Uniqueness,
+            created_at: 0,
+            expires_at: u64::MAX,
+            rp_id: RpId::new(1),
+            oprf_key_id: OprfKeyId::new(U160::from(1u64)),
+            session_id: None,
+            action: None,
+            signature: Signature::test_signature(),
+            nonce: world_id_core::FieldElement::ZERO,
+            requests: vec![RequestItem {
+                identifier: "credential".to_string(),
+                issuer_schema_id,
+                signal: None,
+                genesis_issued_at_min: None,
+                expires_at_min: None,
+            }],
+            constraints: None,
+        })
+    }
+
+    fn peak_while_loading(
+        store: &CredentialStore,
+        request: &ProofRequest,
+        profile: ProvingProfile,
+    ) -> u64 {
+        let tracker = PeakTracker::start();
+        let inputs =
+            load_credential_inputs(store, request, profile, 1000).expect("load inputs");
+        assert!(!inputs.is_empty());
+        drop(inputs);
+        tracker
+            .peak_bytes()
+            .expect("counting allocator is installed")
+    }
+
+    #[test]
+    fn test_low_memory_profile_peaks_below_speed() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init");
+        for issuer_schema_id in 1..=20u64 {
+            let credential: Credential = CoreCredential::new()
+                .issuer_schema_id(issuer_schema_id)
+                .genesis_issued_at(1000)
+                .into();
+            store
+                .store_credential(
+                    &credential,
+                    &FieldElement::from(7u64),
+                    9999,
+                    None,
+                    1000,
+                )
+                .expect("store credential");
+        }
+        let request = request_for(1);
+        // warm up lazily initialized state so it is not attributed to either run
+        load_credential_inputs(&store, &request, ProvingProfile::Balanced, 1000)
+            .expect("warm up");
+
+        let speed = peak_while_loading(&store, &request, ProvingProfile::Speed);
+        let low_memory =
+            peak_while_loading(&store, &request, ProvingProfile::LowMemory);
+        assert!(
+            low_memory * 2 < speed,
+            "low memory peak {low_memory} not measurably below speed peak {speed}"
+        );
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_inclusion_proof_root_matches_proven_root() {
+        use world_id_core::primitives::merkle::MerkleInclusionProof;
+        use world_id_core::primitives::{
+            AuthenticatorPublicKeySet, Nullifier, ZeroKnowledgeProof,
+        };
+        use world_id_core::requests::{
+            ProofResponse as CoreProofResponse, ResponseItem,
+        };
+
+        let root = world_id_core::FieldElement::from(123u64);
+        let account_inclusion_proof = AccountInclusionProof {
+            inclusion_proof: MerkleInclusionProof::new(
+                root,
+                7,
+                [world_id_core::FieldElement::from(5u64); TREE_DEPTH],
+            ),
+            authenticator_pubkeys: AuthenticatorPublicKeySet::new(vec![])
+                .expect("key set"),
+        };
+        let response_with_root = |proven_root: U256| {
+            ProofResponse::from(CoreProofResponse {
+                id: "test".to_string(),
+                version: RequestVersion::V1,
+                session_id: None,
+                error: None,
+                responses: vec![ResponseItem::new_uniqueness(
+                    "credential".to_string(),
+                    1,
+                    ZeroKnowledgeProof::from_ethereum_representation([
+                        U256::ZERO,
+                        U256::ZERO,
+                        U256::ZERO,
+                        U256::ZERO,
+                        proven_root,
+                    ]),
+                    Nullifier::from(world_id_core::FieldElement::from(1u64)),
+                    0,
+                )],
+            })
+        };
+
+        let response = response_with_root(U256::from(123u64));
+        let inclusion_proof =
+            inclusion_proof_of(&response, &account_inclusion_proof).expect("same root");
+        assert_eq!(Some(inclusion_proof.root), response.root());
+        assert_eq!(inclusion_proof.leaf_index, 7);
+        assert_eq!(inclusion_proof.siblings.len(), TREE_DEPTH);
+        assert_eq!(inclusion_proof.siblings[0], Uint256::from(U256::from(5u64)));
+
+        let response = response_with_root(U256::from(124u64));
+        assert!(matches!(
+            inclusion_proof_of(&response, &account_inclusion_proof),
+            Err(WalletKitError::Generic { .. })
+        ));
+    }
+}
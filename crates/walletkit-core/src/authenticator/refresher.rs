@@ -0,0 +1,135 @@
+//! Keeping the cached Merkle inclusion proof fresh in the background.
+
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::time::Duration;
+
+use tokio::task::AbortHandle;
+
+use super::{resolve_now, Authenticator};
+use crate::error::WalletKitError;
+use crate::runtime::compat;
+
+/// The background task of [`Authenticator::start_merkle_proof_refresh`].
+#[derive(Debug, Default)]
+pub(super) struct MerkleProofRefresher {
+    task: Mutex<Option<AbortHandle>>,
+}
+
+impl MerkleProofRefresher {
+    /// Replaces the running task, if any, with `task`.
+    fn replace(&self, task: Option<AbortHandle>) {
+        let previous = std::mem::replace(
+            &mut *self.task.lock().unwrap_or_else(PoisonError::into_inner),
+            task,
+        );
+        if let Some(previous) = previous {
+            previous.abort();
+        }
+    }
+
+    /// Whether a task is running.
+    fn is_running(&self) -> bool {
+        self.task
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .is_some_and(|task| !task.is_finished())
+    }
+}
+
+impl Drop for MerkleProofRefresher {
+    fn drop(&mut self) {
+        self.replace(None);
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl Authenticator {
+    /// Starts refreshing the cached Merkle inclusion proof in the background,
+    /// so the first proof after the app opens does not wait for the indexer.
+    ///
+    /// Runs [`refresh_merkle_proof_if_due`](Self::refresh_merkle_proof_if_due)
+    /// right away and then every `interval_seconds` while the app runs. Stops
+    /// with [`stop_merkle_proof_refresh`](Self::stop_merkle_proof_refresh),
+    /// when the authenticator is dropped or closed, or when started again,
+    /// which replaces the previous schedule. Failed refreshes are logged and
+    /// retried on the next tick.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] if `interval_seconds` is 0.
+    pub async fn start_merkle_proof_refresh(
+        self: Arc<Self>,
+        interval_seconds: u64,
+    ) -> Result<(), WalletKitError> {
+        if interval_seconds == 0 {
+            return Err(WalletKitError::InvalidInput {
+                attribute: "interval_seconds".to_string(),
+                reason: "must be positive".to_string(),
+            });
+        }
+        let authenticator = Arc::downgrade(&self);
+        let interval = Duration::from_secs(interval_seconds);
+        // spawned under `compat` so the task has a tokio runtime to run on
+        // whichever executor polls this call
+        let task = compat(async move {
+            tokio::spawn(run_refresh(authenticator, interval)).abort_handle()
+        })
+        .await;
+        self.merkle_refresher.replace(Some(task));
+        Ok(())
+    }
+
+    /// Fetches a fresh Merkle inclusion proof and caches it, whether or not
+    /// the cached one is due, e.g. after the app learns the account changed.
+    ///
+    /// # Errors
+    /// Returns an error if the indexer request fails or the authenticator is
+    /// closed.
+    pub async fn refresh_merkle_proof_now(
+        &self,
+        now: Option<u64>,
+    ) -> Result<(), WalletKitError> {
+        let now = resolve_now(now)?;
+        self.refresh_inclusion_proof_cache(now).await?;
+        Ok(())
+    }
+}
+
+#[uniffi::export]
+impl Authenticator {
+    /// Stops the background refresh started by
+    /// [`start_merkle_proof_refresh`](Self::start_merkle_proof_refresh). Does
+    /// nothing if it is not running.
+    pub fn stop_merkle_proof_refresh(&self) {
+        self.merkle_refresher.replace(None);
+    }
+
+    /// Whether the background refresh is running.
+    #[must_use]
+    pub fn is_merkle_proof_refresh_running(&self) -> bool {
+        self.merkle_refresher.is_running()
+    }
+}
+
+/// Refreshes the proof of `authenticator` every `interval` while it is alive
+/// and open.
+async fn run_refresh(authenticator: Weak<Authenticator>, interval: Duration) {
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        let Some(authenticator) = authenticator.upgrade() else {
+            break;
+        };
+        // closed authenticators have nothing to refresh into
+        if authenticator.store().is_err() {
+            break;
+        }
+        let Ok(now) = resolve_now(None) else {
+            break;
+        };
+        if let Err(err) = authenticator.refresh_merkle_proof_if_due(now).await {
+            tracing::warn!("background Merkle proof refresh failed: {err}");
+        }
+    }
+}
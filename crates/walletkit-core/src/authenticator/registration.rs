@@ -0,0 +1,274 @@
+//! Waiting for a gateway registration, including while gas sponsorship is
+//! exhausted.
+//!
+//! The gateway sponsors the gas of registrations. When its sponsorship quota
+//! is used up it answers status polls with `402 Payment Required`, optionally
+//! with a `retry_after` (seconds) in the JSON error body, until the quota
+//! resets. This is reported as [`RegistrationStatus::SponsorshipExhausted`]
+//! rather than as a failure.
+
+use serde::Deserialize;
+use world_id_core::AuthenticatorError;
+
+use super::RegistrationStatus;
+#[cfg(not(target_arch = "wasm32"))]
+use super::{InitializingAuthenticator, WalletKitError};
+
+/// Interval between polls of a pending registration, and the minimum wait
+/// while sponsorship is exhausted.
+#[cfg(not(target_arch = "wasm32"))]
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// The part of the gateway's `402` error body this crate reads.
+#[derive(Deserialize)]
+struct SponsorshipExhaustedBody {
+    retry_after: Option<u64>,
+}
+
+/// Returns [`RegistrationStatus::SponsorshipExhausted`] if `error` is the
+/// gateway reporting that gas sponsorship is exhausted.
+pub(super) fn sponsorship_exhausted(
+    error: &AuthenticatorError,
+) -> Option<RegistrationStatus> {
+    let AuthenticatorError::GatewayError { status, body } = error else {
+        return None;
+    };
+    (*status == reqwest::StatusCode::PAYMENT_REQUIRED).then(|| {
+        RegistrationStatus::SponsorshipExhausted {
+            retry_after: serde_json::from_str::<SponsorshipExhaustedBody>(body)
+                .ok()
+                .and_then(|body| body.retry_after),
+        }
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[uniffi::export(async_runtime = "tokio")]
+impl InitializingAuthenticator {
+    /// Polls the registration status until the registration is
+    /// [`Finalized`](RegistrationStatus::Finalized) or
+    /// [`Failed`](RegistrationStatus::Failed), or `timeout_seconds` have
+    /// passed.
+    ///
+    /// While sponsorship is exhausted, waits for the gateway's `retry_after`
+    /// before polling again. Returns the last status polled, which is not
+    /// final if the timeout passed first.
+    ///
+    /// # Errors
+    /// Will error if a poll fails, see [`poll_status`](Self::poll_status).
+    pub async fn wait_until_finalized(
+        &self,
+        timeout_seconds: u64,
+    ) -> Result<RegistrationStatus, WalletKitError> {
+        let deadline =
+            std::time::Instant::now() + std::time::Duration::from_secs(timeout_seconds);
+        loop {
+            let status = self.poll_status().await?;
+            let delay = match &status {
+                RegistrationStatus::Finalized | RegistrationStatus::Failed { .. } => {
+                    return Ok(status);
+                }
+                RegistrationStatus::SponsorshipExhausted {
+                    retry_after: Some(retry_after),
+                } => std::time::Duration::from_secs(*retry_after).max(POLL_INTERVAL),
+                _ => POLL_INTERVAL,
+            };
+            let remaining =
+                deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() {
+                return Ok(status);
+            }
+            crate::runtime::sleep(delay.min(remaining)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+    use world_id_core::primitives::{Config, ServiceEndpoint};
+
+    use super::*;
+
+    fn config(server: &mockito::ServerGuard) -> String {
+        let config = Config::new(
+            None,
+            480,
+            address!("0x969947cFED008bFb5e3F32a25A1A2CDdf64d46fe"),
+            ServiceEndpoint::direct(server.url()),
+            ServiceEndpoint::direct(server.url()),
+            vec![],
+            2,
+        )
+        .unwrap();
+        serde_json::to_string(&config).unwrap()
+    }
+
+    async fn register(server: &mut mockito::ServerGuard) -> InitializingAuthenticator {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        server
+            .mock("POST", "/create-account")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "request_id": "gw_1",
+                    "kind": "create_account",
+                    "status": { "state": "queued" },
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        InitializingAuthenticator::register(&[7u8; 32], &config(server), None)
+            .await
+            .unwrap()
+    }
+
+    fn state(state: &serde_json::Value) -> String {
+        serde_json::json!({
+            "request_id": "gw_1",
+            "kind": "create_account",
+            "status": state,
+        })
+        .to_string()
+    }
+
+    fn sponsorship_exhausted_body(retry_after: Option<u64>) -> String {
+        let mut body = serde_json::json!({
+            "code": "sponsorship_exhausted",
+            "message": "gas sponsorship quota exhausted",
+        });
+        if let Some(retry_after) = retry_after {
+            body["retry_after"] = retry_after.into();
+        }
+        body.to_string()
+    }
+
+    #[test]
+    fn test_only_payment_required_is_sponsorship_exhausted() {
+        let error =
+            |status, body: String| AuthenticatorError::GatewayError { status, body };
+
+        assert!(matches!(
+            sponsorship_exhausted(&error(
+                reqwest::StatusCode::PAYMENT_REQUIRED,
+                sponsorship_exhausted_body(Some(30))
+            )),
+            Some(RegistrationStatus::SponsorshipExhausted {
+                retry_after: Some(30)
+            })
+        ));
+        assert!(matches!(
+            sponsorship_exhausted(&error(
+                reqwest::StatusCode::PAYMENT_REQUIRED,
+                "quota exhausted".to_string()
+            )),
+            Some(RegistrationStatus::SponsorshipExhausted { retry_after: None })
+        ));
+        assert!(sponsorship_exhausted(&error(
+            reqwest::StatusCode::TOO_MANY_REQUESTS,
+            sponsorship_exhausted_body(Some(30))
+        ))
+        .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_poll_reports_sponsorship_exhausted() {
+        let mut server = mockito::Server::new_async().await;
+        let authenticator = register(&mut server).await;
+        let _status_mock = server
+            .mock("GET", "/status/gw_1")
+            .with_status(402)
+            .with_header("content-type", "application/json")
+            .with_body(sponsorship_exhausted_body(Some(60)))
+            .create_async()
+            .await;
+
+        let status = authenticator.poll_status().await.unwrap();
+        assert!(
+            matches!(
+                status,
+                RegistrationStatus::SponsorshipExhausted {
+                    retry_after: Some(60)
+                }
+            ),
+            "unexpected status: {status:?}"
+        );
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_wait_retries_sponsorship_exhausted_until_finalized() {
+        let mut server = mockito::Server::new_async().await;
+        let authenticator = register(&mut server).await;
+        let exhausted_mock = server
+            .mock("GET", "/status/gw_1")
+            .with_status(402)
+            .with_body(sponsorship_exhausted_body(Some(1)))
+            .expect(2)
+            .create_async()
+            .await;
+        let finalized_mock = server
+            .mock("GET", "/status/gw_1")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(state(
+                &serde_json::json!({ "state": "finalized", "tx_hash": "0x01" }),
+            ))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let status = authenticator.wait_until_finalized(30).await.unwrap();
+        assert!(matches!(status, RegistrationStatus::Finalized));
+        exhausted_mock.assert_async().await;
+        finalized_mock.assert_async().await;
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_wait_stops_at_deadline_and_on_failure() {
+        let mut server = mockito::Server::new_async().await;
+        let authenticator = register(&mut server).await;
+
+        // a retry_after past the deadline is cut short for a last poll
+        let exhausted_mock = server
+            .mock("GET", "/status/gw_1")
+            .with_status(402)
+            .with_body(sponsorship_exhausted_body(Some(3600)))
+            .expect(2)
+            .create_async()
+            .await;
+        let status = authenticator.wait_until_finalized(1).await.unwrap();
+        assert!(matches!(
+            status,
+            RegistrationStatus::SponsorshipExhausted {
+                retry_after: Some(3600)
+            }
+        ));
+        exhausted_mock.assert_async().await;
+
+        let _failed_mock = server
+            .mock("GET", "/status/gw_1")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(state(&serde_json::json!({
+                "state": "failed",
+                "error": "reverted",
+                "error_code": "transaction_reverted",
+            })))
+            .create_async()
+            .await;
+        let status = authenticator.wait_until_finalized(30).await.unwrap();
+        assert!(
+            matches!(
+                status,
+                RegistrationStatus::Failed { ref error_code, .. }
+                    if error_code.as_deref() == Some("transaction_reverted")
+            ),
+            "unexpected status: {status:?}"
+        );
+        drop(server);
+    }
+}
@@ -0,0 +1,166 @@
+//! Authenticator initialization with caches shared between instances.
+
+use std::sync::Arc;
+
+use alloy_core::primitives::Address;
+use ruint::aliases::U256;
+
+use super::{parse_config, Authenticator, Groth16Materials};
+use crate::error::WalletKitError;
+use crate::runtime::compat;
+use crate::storage::CredentialStore;
+use crate::SharedCaches;
+
+/// Options for [`Authenticator::init_with_options`].
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct AuthenticatorOptions {
+    /// Caches to share with other instances, usually
+    /// [`SharedCaches::global`]. Instances without them only cache for their
+    /// own lifetime.
+    #[uniffi(default = None)]
+    pub shared_caches: Option<Arc<SharedCaches>>,
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl Authenticator {
+    /// Like [`init`](Self::init), with [`AuthenticatorOptions`].
+    ///
+    /// With shared caches, the packed account data read during
+    /// initialization is cached for later instances of the same account,
+    /// which then skip fetching it in
+    /// [`get_packed_account_data_remote`](Self::get_packed_account_data_remote)
+    /// and [`discover_leaf_index`](Self::discover_leaf_index).
+    ///
+    /// # Errors
+    /// Will error if the provided seed is not valid or if the config is not valid.
+    #[uniffi::constructor]
+    #[tracing::instrument(target = "walletkit_latency", name = "rpc_init", skip_all)]
+    pub async fn init_with_options(
+        seed: &[u8],
+        config: &str,
+        materials: Arc<Groth16Materials>,
+        store: Arc<CredentialStore>,
+        options: AuthenticatorOptions,
+    ) -> Result<Self, WalletKitError> {
+        let config = parse_config(config)?;
+        let mut authenticator =
+            Self::init_with_config(seed, config, materials, store).await?;
+        if let Some(shared_caches) = options.shared_caches {
+            authenticator.share_caches(shared_caches);
+        }
+        Ok(authenticator)
+    }
+}
+
+impl Authenticator {
+    /// Makes this authenticator use `shared_caches`, seeding them with the
+    /// packed account data read during initialization.
+    pub(super) fn share_caches(&mut self, shared_caches: Arc<SharedCaches>) {
+        shared_caches.packed_account_data.insert(
+            self.packed_account_data_key(),
+            self.inner.packed_account_data,
+        );
+        self.shared_caches = Some(shared_caches);
+    }
+
+    pub(super) async fn fetch_packed_account_data_shared(
+        &self,
+        shared_caches: &SharedCaches,
+    ) -> Result<U256, WalletKitError> {
+        let key = self.packed_account_data_key();
+        if let Some(packed_account_data) = shared_caches.packed_account_data.get(&key) {
+            return Ok(packed_account_data);
+        }
+        let packed_account_data =
+            compat(self.inner.fetch_packed_account_data()).await?;
+        shared_caches
+            .packed_account_data
+            .insert(key, packed_account_data);
+        Ok(packed_account_data)
+    }
+
+    const fn packed_account_data_key(&self) -> (Address, Address) {
+        (
+            *self.inner.config.registry_address(),
+            self.inner.onchain_address(),
+        )
+    }
+}
+
+#[cfg(all(test, feature = "embed-zkeys"))]
+mod tests {
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+    use world_id_core::primitives::{Config, ServiceEndpoint};
+
+    #[tokio::test]
+    async fn test_authenticators_share_cached_data() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let mut server = mockito::Server::new_async().await;
+        let packed_account_data = (U256::from(1) << 192) | U256::from(5);
+        // once per initialization, which always reads the account
+        let indexer_mock = server
+            .mock("POST", "/packed-account")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "packed_account_data": format!("{packed_account_data:#x}"),
+                })
+                .to_string(),
+            )
+            .expect(2)
+            .create_async()
+            .await;
+
+        let config = Config::new(
+            None,
+            480,
+            alloy::primitives::address!("0x969947cFED008bFb5e3F32a25A1A2CDdf64d46fe"),
+            ServiceEndpoint::direct(server.url()),
+            ServiceEndpoint::direct(server.url()),
+            vec![],
+            2,
+        )
+        .unwrap();
+        let config = serde_json::to_string(&config).unwrap();
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = Arc::new(CredentialStore::from_provider(&provider).expect("store"));
+        store.init(5, 100).expect("init storage");
+        let materials =
+            Arc::new(Groth16Materials::from_embedded().expect("load materials"));
+        let shared_caches = Arc::new(SharedCaches::new(60, 60));
+        let options = AuthenticatorOptions {
+            shared_caches: Some(Arc::clone(&shared_caches)),
+        };
+
+        for _ in 0..2 {
+            let authenticator = Authenticator::init_with_options(
+                &[2u8; 32],
+                &config,
+                Arc::clone(&materials),
+                Arc::clone(&store),
+                options.clone(),
+            )
+            .await
+            .expect("init authenticator");
+            assert_eq!(authenticator.discover_leaf_index().await.unwrap(), 5);
+            assert_eq!(
+                authenticator
+                    .get_packed_account_data_remote()
+                    .await
+                    .unwrap(),
+                authenticator.packed_account_data()
+            );
+        }
+        indexer_mock.assert_async().await;
+
+        drop(server);
+        cleanup_test_storage(&root);
+    }
+}
@@ -1,4 +1,7 @@
 use crate::error::WalletKitError;
+use crate::metrics_observer::{timed, ProofStage};
+use crate::runtime::compat;
+use crate::storage::{RegistryKind, StorageError};
 
 use super::Authenticator;
 
@@ -6,7 +9,7 @@ use world_id_core::primitives::merkle::AccountInclusionProof;
 use world_id_core::primitives::TREE_DEPTH;
 
 /// The amount of time a Merkle inclusion proof remains valid in the cache.
-const MERKLE_PROOF_VALIDITY_SECONDS: u64 = 60 * 15;
+pub const MERKLE_PROOF_VALIDITY_SECONDS: u64 = 60 * 15;
 
 #[uniffi::export]
 impl Authenticator {
@@ -16,7 +19,7 @@ impl Authenticator {
     ///
     /// Returns an error if the leaf index is invalid or storage initialization fails.
     pub fn init_storage(&self, now: u64) -> Result<(), WalletKitError> {
-        self.store.init(self.leaf_index(), now)?;
+        self.store()?.init(self.leaf_index(), now)?;
         Ok(())
     }
 
@@ -30,9 +33,67 @@ impl Authenticator {
     ///
     /// Returns an error if the storage destruction fails.
     pub fn destroy_storage(&self) -> Result<(), WalletKitError> {
-        self.store.destroy_storage()?;
+        self.store()?.destroy_storage()?;
         Ok(())
     }
+
+    /// Closes the authenticator: drops the decrypted credentials cached by
+    /// its store and releases its reference to the store.
+    ///
+    /// The store stays open for its other holders; close it with
+    /// [`CredentialStore::close`](crate::storage::CredentialStore::close)
+    /// once nothing uses it. Afterwards every method that needs the store
+    /// fails with [`StorageError::Closed`]. Closing a closed authenticator
+    /// does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store's caches cannot be cleared. The
+    /// authenticator is closed either way.
+    pub fn close(&self) -> Result<(), WalletKitError> {
+        let store = self
+            .store
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take();
+        match store.map(|store| store.clear_sensitive_caches()) {
+            // a closed store holds nothing in memory
+            None | Some(Ok(()) | Err(StorageError::Closed)) => Ok(()),
+            Some(Err(err)) => Err(err.into()),
+        }
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl Authenticator {
+    /// Refreshes the cached Merkle inclusion proof if it is due, so that proof
+    /// generation keeps hitting the cache.
+    ///
+    /// Intended to be called periodically from a background task (e.g.
+    /// `WorkManager` or `BackgroundTasks`). The proof is due once `now` reaches
+    /// [`CredentialStore::next_merkle_refresh_at`](crate::storage::CredentialStore::next_merkle_refresh_at)
+    /// or when no valid proof is cached; otherwise this is a no-op.
+    ///
+    /// Returns `true` if a fresh proof was fetched.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache lookup or the indexer request fails.
+    pub async fn refresh_merkle_proof_if_due(
+        &self,
+        now: u64,
+    ) -> Result<bool, WalletKitError> {
+        if let Some(refresh_at) = self
+            .store()?
+            .next_merkle_refresh_at(RegistryKind::AccountRegistry, now)?
+        {
+            if now < refresh_at {
+                return Ok(false);
+            }
+        }
+        self.refresh_inclusion_proof_cache(now).await?;
+        Ok(true)
+    }
 }
 
 impl Authenticator {
@@ -50,17 +111,94 @@ impl Authenticator {
         &self,
         now: u64,
     ) -> Result<AccountInclusionProof<TREE_DEPTH>, WalletKitError> {
-        // If there is a cached inclusion proof, return it
-        if let Some(account_inclusion_proof) = self.store.merkle_cache_get(now)? {
-            if account_inclusion_proof.inclusion_proof.leaf_index == self.leaf_index() {
-                return Ok(account_inclusion_proof);
+        timed(ProofStage::MerkleFetch, async {
+            // If there is a cached inclusion proof, return it
+            if let Some(account_inclusion_proof) = self
+                .store()?
+                .merkle_cache_get(RegistryKind::AccountRegistry, now)?
+            {
+                if account_inclusion_proof.inclusion_proof.leaf_index
+                    == self.leaf_index()
+                {
+                    return Ok(account_inclusion_proof);
+                }
             }
+
+            // Otherwise, fetch from the indexer and cache it
+            self.refresh_inclusion_proof_cache(now).await
+        })
+        .await
+    }
+
+    /// Returns the cached Merkle inclusion proof if it was fetched at most
+    /// `max_age` seconds before `now`, without contacting the indexer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WalletKitError::OfflineProofUnavailable`] if no proof for
+    /// this account is cached or the cached one is too old.
+    pub(crate) fn cached_inclusion_proof_with_max_age(
+        &self,
+        max_age: u64,
+        now: u64,
+    ) -> Result<AccountInclusionProof<TREE_DEPTH>, WalletKitError> {
+        let cached = self
+            .store()?
+            .merkle_cache_get_with_age(RegistryKind::AccountRegistry, now)?
+            .filter(|(proof, _)| proof.inclusion_proof.leaf_index == self.leaf_index());
+        match cached {
+            Some((proof, age)) if age <= max_age => Ok(proof),
+            Some((_, age)) => Err(WalletKitError::OfflineProofUnavailable {
+                cache_age_seconds: age,
+            }),
+            None => Err(WalletKitError::OfflineProofUnavailable {
+                cache_age_seconds: u64::MAX,
+            }),
         }
+    }
 
-        // Otherwise, fetch from the indexer and cache it
-        let account_inclusion_proof = self.inner.fetch_inclusion_proof().await?;
+    /// Invalidates stored credentials issued before the account's current
+    /// recovery counter.
+    ///
+    /// Such credentials were blinded for a stale pubkey id and would only fail
+    /// inside the circuits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage update fails.
+    pub(crate) fn invalidate_stale_credentials(
+        &self,
+        now: u64,
+    ) -> Result<(), WalletKitError> {
+        let recovery_counter = self.inner.recovery_counter().saturating_to::<u64>();
+        let invalidated = self
+            .store()?
+            .invalidate_for_recovery(recovery_counter, now)?;
+        if !invalidated.is_empty() {
+            tracing::info!(
+                recovery_counter,
+                credential_ids = ?invalidated,
+                "invalidated credentials issued before account recovery"
+            );
+        }
+        Ok(())
+    }
 
-        if let Err(e) = self.store.merkle_cache_put(
+    /// Fetches a [`MerkleInclusionProof`] from the indexer and caches it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if fetching the proof fails. Caching failures are only
+    /// logged.
+    pub(crate) async fn refresh_inclusion_proof_cache(
+        &self,
+        now: u64,
+    ) -> Result<AccountInclusionProof<TREE_DEPTH>, WalletKitError> {
+        let account_inclusion_proof =
+            compat(self.inner.fetch_inclusion_proof()).await?;
+
+        if let Err(e) = self.store()?.merkle_cache_put(
+            RegistryKind::AccountRegistry,
             &account_inclusion_proof,
             now,
             MERKLE_PROOF_VALIDITY_SECONDS,
@@ -75,6 +213,7 @@ impl Authenticator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::requests::ProofRequest;
     use crate::storage::tests_utils::{
         cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
     };
@@ -101,16 +240,407 @@ mod tests {
         };
 
         store
-            .merkle_cache_put(&account_inclusion_proof, 100, 60)
+            .merkle_cache_put(
+                RegistryKind::AccountRegistry,
+                &account_inclusion_proof,
+                100,
+                60,
+            )
             .expect("cache put");
         let now = 110;
         let decoded = store
-            .merkle_cache_get(now)
+            .merkle_cache_get(RegistryKind::AccountRegistry, now)
             .expect("cache get")
             .expect("cache hit");
         assert_eq!(decoded.inclusion_proof.leaf_index, 42);
         assert_eq!(decoded.inclusion_proof.root, root_fe);
         assert_eq!(decoded.authenticator_pubkeys.len(), 0);
+
+        let refresh_at = store
+            .next_merkle_refresh_at(RegistryKind::AccountRegistry, now)
+            .expect("refresh at")
+            .expect("proof cached");
+        assert!((130..=154).contains(&refresh_at), "{refresh_at}");
+        assert_eq!(
+            store
+                .next_merkle_refresh_at(RegistryKind::AccountRegistry, refresh_at)
+                .expect("refresh at"),
+            Some(refresh_at)
+        );
+        assert_eq!(
+            store
+                .next_merkle_refresh_at(RegistryKind::AccountRegistry, 161)
+                .expect("refresh at"),
+            None
+        );
+        cleanup_test_storage(&root);
+    }
+
+    /// Returns an authenticator whose indexer and RPC are unreachable, so
+    /// only cached inclusion proofs can be used.
+    async fn offline_authenticator(root: &std::path::Path) -> Authenticator {
+        use alloy::primitives::address;
+        use world_id_core::primitives::{Config, ServiceEndpoint};
+        use world_id_core::Authenticator as CoreAuthenticator;
+
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let mut mock_server = mockito::Server::new_async().await;
+        mock_server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "result": "0x0000000000000000000000000000000000000000000000000000000000000001"
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let config = Config::new(
+            Some(mock_server.url()),
+            480,
+            address!("0x969947cFED008bFb5e3F32a25A1A2CDdf64d46fe"),
+            ServiceEndpoint::direct(mock_server.url()),
+            ServiceEndpoint::direct(mock_server.url()),
+            vec![],
+            2,
+        )
+        .unwrap();
+        let inner = CoreAuthenticator::init(&[2u8; 32], config)
+            .await
+            .expect("init authenticator");
+        // from here on, any indexer call would fail
+        drop(mock_server);
+
+        let provider = InMemoryStorageProvider::new(root);
+        let store = CredentialStore::from_provider(&provider).expect("store");
+        store.init(1, 100).expect("init storage");
+        Authenticator::from_parts(inner, std::sync::Arc::new(store))
+    }
+
+    #[tokio::test]
+    async fn test_offline_inclusion_proof_respects_max_age() {
+        let root = temp_root_path();
+        let authenticator = offline_authenticator(&root).await;
+
+        assert!(matches!(
+            authenticator.cached_inclusion_proof_with_max_age(600, 100),
+            Err(WalletKitError::OfflineProofUnavailable {
+                cache_age_seconds: u64::MAX
+            })
+        ));
+
+        let root_fe = FieldElement::from(123u64);
+        let account_inclusion_proof = AccountInclusionProof {
+            inclusion_proof: MerkleInclusionProof::new(
+                root_fe,
+                1,
+                [FieldElement::from(0u64); TREE_DEPTH],
+            ),
+            authenticator_pubkeys: AuthenticatorPublicKeySet::new(vec![])
+                .expect("key set"),
+        };
+        authenticator
+            .store()
+            .expect("store")
+            .merkle_cache_put(
+                RegistryKind::AccountRegistry,
+                &account_inclusion_proof,
+                100,
+                MERKLE_PROOF_VALIDITY_SECONDS,
+            )
+            .expect("cache put");
+
+        // usable offline past the cache TTL, as long as it is within max age
+        let now = 100 + MERKLE_PROOF_VALIDITY_SECONDS + 60;
+        let proof = authenticator
+            .cached_inclusion_proof_with_max_age(MERKLE_PROOF_VALIDITY_SECONDS * 2, now)
+            .expect("cached proof");
+        assert_eq!(proof.inclusion_proof.root, root_fe);
+        assert!(matches!(
+            authenticator.cached_inclusion_proof_with_max_age(60, now),
+            Err(WalletKitError::OfflineProofUnavailable { cache_age_seconds })
+                if cache_age_seconds == MERKLE_PROOF_VALIDITY_SECONDS + 60
+        ));
+
+        cleanup_test_storage(&root);
+    }
+
+    #[tokio::test]
+    async fn test_expired_request_is_refused_before_proving() {
+        use alloy::signers::Signature;
+        use alloy_core::primitives::U160;
+        use taceo_oprf::types::OprfKeyId;
+        use world_id_core::primitives::rp::RpId;
+        use world_id_core::requests::{
+            ProofRequest as CoreProofRequest, ProofType, RequestItem, RequestVersion,
+        };
+
+        let root = temp_root_path();
+        let authenticator = offline_authenticator(&root).await;
+        let account_inclusion_proof = AccountInclusionProof {
+            inclusion_proof: MerkleInclusionProof::new(
+                FieldElement::from(123u64),
+                1,
+                [FieldElement::from(0u64); TREE_DEPTH],
+            ),
+            authenticator_pubkeys: AuthenticatorPublicKeySet::new(vec![])
+                .expect("key set"),
+        };
+        authenticator
+            .store()
+            .expect("store")
+            .merkle_cache_put(
+                RegistryKind::AccountRegistry,
+                &account_inclusion_proof,
+                100,
+                MERKLE_PROOF_VALIDITY_SECONDS,
+            )
+            .expect("cache put");
+        let core_request = CoreProofRequest {
+            id: "kiosk".to_string(),
+            version: RequestVersion::V1,
+            proof_type: ProofType::Uniqueness,
+            created_at: 100,
+            expires_at: 1000,
+            rp_id: RpId::new(1),
+            oprf_key_id: OprfKeyId::new(U160::from(1u64)),
+            session_id: None,
+            action: Some(FieldElement::from(1u64)),
+            signature: Signature::test_signature(),
+            nonce: FieldElement::from(2u64),
+            requests: vec![RequestItem {
+                identifier: "credential".to_string(),
+                issuer_schema_id: 1,
+                signal: None,
+                genesis_issued_at_min: None,
+                expires_at_min: None,
+            }],
+            constraints: None,
+        };
+        let request = ProofRequest(core_request, Some(130), None, None);
+
+        // the OPRF nodes are unreachable, so getting past the check would
+        // fail with a network error instead
+        assert!(matches!(
+            authenticator.prove_offline(&request, 600, 131).await,
+            Err(WalletKitError::ProofRequestExpired { expired_at: 130 })
+        ));
+
+        cleanup_test_storage(&root);
+    }
+
+    #[tokio::test]
+    async fn test_staging_request_is_refused_by_production_authenticator() {
+        use alloy::signers::Signature;
+        use alloy_core::primitives::U160;
+        use taceo_oprf::types::OprfKeyId;
+        use world_id_core::primitives::rp::RpId;
+        use world_id_core::requests::{
+            ProofRequest as CoreProofRequest, ProofType, RequestItem, RequestVersion,
+        };
+
+        let root = temp_root_path();
+        // configured for World Chain mainnet (480)
+        let authenticator = offline_authenticator(&root).await;
+        let account_inclusion_proof = AccountInclusionProof {
+            inclusion_proof: MerkleInclusionProof::new(
+                FieldElement::from(123u64),
+                1,
+                [FieldElement::from(0u64); TREE_DEPTH],
+            ),
+            authenticator_pubkeys: AuthenticatorPublicKeySet::new(vec![])
+                .expect("key set"),
+        };
+        let store = authenticator.store().expect("store");
+        store
+            .merkle_cache_put(
+                RegistryKind::AccountRegistry,
+                &account_inclusion_proof,
+                100,
+                MERKLE_PROOF_VALIDITY_SECONDS,
+            )
+            .expect("cache put");
+        let core_request = CoreProofRequest {
+            id: "staging".to_string(),
+            version: RequestVersion::V1,
+            proof_type: ProofType::Uniqueness,
+            created_at: 100,
+            expires_at: 1000,
+            rp_id: RpId::new(1),
+            oprf_key_id: OprfKeyId::new(U160::from(1u64)),
+            session_id: None,
+            action: Some(FieldElement::from(1u64)),
+            signature: Signature::test_signature(),
+            nonce: FieldElement::from(2u64),
+            requests: vec![RequestItem {
+                identifier: "credential".to_string(),
+                issuer_schema_id: 1,
+                signal: None,
+                genesis_issued_at_min: None,
+                expires_at_min: None,
+            }],
+            constraints: None,
+        };
+        // a World Chain Sepolia (staging) request
+        let request = ProofRequest(core_request, None, None, Some(4801));
+
+        // the OPRF nodes are unreachable, so getting past the check would
+        // fail with a network error instead
+        assert!(matches!(
+            authenticator.prove_offline(&request, 600, 131).await,
+            Err(WalletKitError::EnvironmentMismatch {
+                request_chain: 4801,
+                configured_chain: 480,
+            })
+        ));
+        assert!(store
+            .list_disclosures(false, 131)
+            .expect("disclosures")
+            .is_empty());
+
+        cleanup_test_storage(&root);
+    }
+
+    #[tokio::test]
+    async fn test_proof_batch_reports_failures_per_request() {
+        use alloy::signers::Signature;
+        use alloy_core::primitives::U160;
+        use taceo_oprf::types::OprfKeyId;
+        use world_id_core::primitives::rp::RpId;
+        use world_id_core::requests::{
+            ProofRequest as CoreProofRequest, ProofType, RequestItem, RequestVersion,
+        };
+
+        let root = temp_root_path();
+        let authenticator = offline_authenticator(&root).await;
+        assert!(authenticator
+            .generate_proof_batch(vec![], Some(131))
+            .await
+            .expect("empty batch")
+            .is_empty());
+
+        let account_inclusion_proof = AccountInclusionProof {
+            inclusion_proof: MerkleInclusionProof::new(
+                FieldElement::from(123u64),
+                1,
+                [FieldElement::from(0u64); TREE_DEPTH],
+            ),
+            authenticator_pubkeys: AuthenticatorPublicKeySet::new(vec![])
+                .expect("key set"),
+        };
+        authenticator
+            .store()
+            .expect("store")
+            .merkle_cache_put(
+                RegistryKind::AccountRegistry,
+                &account_inclusion_proof,
+                100,
+                MERKLE_PROOF_VALIDITY_SECONDS,
+            )
+            .expect("cache put");
+        let request = |id: &str, valid_until: Option<u64>| {
+            let core_request = CoreProofRequest {
+                id: id.to_string(),
+                version: RequestVersion::V1,
+                proof_type: ProofType::Uniqueness,
+                created_at: 100,
+                expires_at: 1000,
+                rp_id: RpId::new(1),
+                oprf_key_id: OprfKeyId::new(U160::from(1u64)),
+                session_id: None,
+                action: Some(FieldElement::from(1u64)),
+                signature: Signature::test_signature(),
+                nonce: FieldElement::from(2u64),
+                requests: vec![RequestItem {
+                    identifier: "credential".to_string(),
+                    issuer_schema_id: 1,
+                    signal: None,
+                    genesis_issued_at_min: None,
+                    expires_at_min: None,
+                }],
+                constraints: None,
+            };
+            std::sync::Arc::new(ProofRequest(core_request, valid_until, None, None))
+        };
+
+        // the first request has expired; the second gets past that check but
+        // cannot reach the OPRF nodes
+        let results = authenticator
+            .generate_proof_batch(
+                vec![request("expired", Some(130)), request("live", None)],
+                Some(131),
+            )
+            .await
+            .expect("batch");
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].request_id, "expired");
+        assert!(results[0].response.is_none());
+        assert!(results[0]
+            .error
+            .as_deref()
+            .is_some_and(|error| error.starts_with("proof_request_expired")));
+        assert_eq!(results[1].request_id, "live");
+        assert!(results[1].response.is_none());
+        assert!(results[1]
+            .error
+            .as_deref()
+            .is_some_and(|error| !error.starts_with("proof_request_expired")));
+
+        cleanup_test_storage(&root);
+    }
+
+    #[tokio::test]
+    async fn test_close_releases_store() {
+        let root = temp_root_path();
+        let authenticator = offline_authenticator(&root).await;
+        let store = authenticator.store().expect("store");
+
+        authenticator.close().expect("close");
+        authenticator.close().expect("closing twice is a no-op");
+        assert!(matches!(authenticator.store(), Err(StorageError::Closed)));
+        assert!(matches!(
+            authenticator.init_storage(100),
+            Err(WalletKitError::Storage { .. })
+        ));
+
+        // the store stays open for its other holders
+        assert_eq!(std::sync::Arc::strong_count(&store), 1);
+        store.list_credentials(None, 100).expect("list credentials");
+
+        cleanup_test_storage(&root);
+    }
+
+    #[tokio::test]
+    async fn test_merkle_proof_refresh_start_and_stop() {
+        let root = temp_root_path();
+        let authenticator = std::sync::Arc::new(offline_authenticator(&root).await);
+
+        assert!(matches!(
+            authenticator.clone().start_merkle_proof_refresh(0).await,
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+        assert!(!authenticator.is_merkle_proof_refresh_running());
+
+        authenticator
+            .clone()
+            .start_merkle_proof_refresh(3600)
+            .await
+            .expect("start refresh");
+        assert!(authenticator.is_merkle_proof_refresh_running());
+        authenticator.stop_merkle_proof_refresh();
+        assert!(!authenticator.is_merkle_proof_refresh_running());
+
+        // the indexer is unreachable, so a forced refresh fails
+        assert!(authenticator
+            .refresh_merkle_proof_now(Some(100))
+            .await
+            .is_err());
+
         cleanup_test_storage(&root);
     }
 }
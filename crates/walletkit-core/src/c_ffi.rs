@@ -0,0 +1,852 @@
+//! Stable `extern "C"` surface for consumers that cannot use `UniFFI` bindings.
+//!
+//! All functions operate on opaque handles and NUL-terminated UTF-8 strings.
+//! The header is generated with `cbindgen` and committed at
+//! `crates/walletkit-core/include/walletkit.h`:
+//!
+//! ```sh
+//! cbindgen --config crates/walletkit-core/cbindgen.toml \
+//!   --output crates/walletkit-core/include/walletkit.h crates/walletkit-core
+//! ```
+//!
+//! # Ownership rules
+//!
+//! - Strings passed **into** the library are borrowed for the duration of the call.
+//! - Strings returned **from** the library are owned by the caller and must be
+//!   released with [`walletkit_free_string`].
+//! - Handles returned by [`walletkit_authenticator_init`] must be released with
+//!   [`walletkit_authenticator_free`]. Pending asynchronous calls keep their own
+//!   reference, so a handle may be freed while a callback is still outstanding.
+//!
+//! # Errors
+//!
+//! Fallible functions return a [`WalletKitStatus`] (or `NULL`) and record the
+//! error for the calling thread. Read it with [`walletkit_last_error_code`] and
+//! [`walletkit_last_error_message`]. Callbacks receive the status and message
+//! directly, since they run on a library-owned thread.
+//!
+//! # Panics
+//!
+//! Panics are reported as [`WalletKitStatus::Panic`] only when the library is
+//! built with `panic = "unwind"`. The workspace release profile sets
+//! `panic = "abort"`, so a panic in a release build terminates the process.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use crate::authenticator::parse_config;
+use crate::error::WalletKitError;
+use crate::limits::InputLimit;
+use crate::requests::ProofRequest;
+use crate::storage::{
+    AtomicBlobStore, CredentialStore, DeviceKeystore, StorageError, StoragePaths,
+    StorageResult,
+};
+use crate::{Authenticator, Groth16Materials};
+
+/// Status codes returned across the C boundary.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletKitStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A required pointer was `NULL` or a string was not valid UTF-8.
+    InvalidArgument = 1,
+    /// The input was rejected by `WalletKit` (e.g. malformed config or request).
+    InvalidInput = 2,
+    /// Credential storage failed.
+    Storage = 3,
+    /// A network request failed.
+    Network = 4,
+    /// Proof generation failed.
+    Proof = 5,
+    /// Any other `WalletKit` error.
+    Other = 6,
+    /// The library panicked. The handle should be considered unusable.
+    ///
+    /// Only reported by builds with `panic = "unwind"`; see the module docs.
+    Panic = 7,
+}
+
+impl From<&WalletKitError> for WalletKitStatus {
+    fn from(error: &WalletKitError) -> Self {
+        match error {
+            WalletKitError::InvalidInput { .. }
+            | WalletKitError::InvalidNumber
+            | WalletKitError::SerializationError { .. } => Self::InvalidInput,
+            WalletKitError::NetworkError { .. }
+            | WalletKitError::Reqwest { .. }
+            | WalletKitError::OhttpError { .. } => Self::Network,
+            WalletKitError::ProofGeneration { .. }
+            | WalletKitError::NullifierReplay
+            | WalletKitError::NullifierConflict { .. }
+            | WalletKitError::OfflineProofUnavailable { .. }
+            | WalletKitError::ProofRequestExpired { .. }
+            | WalletKitError::EnvironmentMismatch { .. }
+            | WalletKitError::UnfulfillableRequest => Self::Proof,
+            WalletKitError::Storage { .. }
+            | WalletKitError::LeafIndexMismatch { .. } => Self::Storage,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Device keystore callbacks supplied by the C caller.
+///
+/// `seal` and `open` write a buffer allocated by the caller to `out` / `out_len`
+/// and return `0` on success. The library releases that buffer through
+/// `free_buffer`. The callbacks may be invoked from any thread.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct WalletKitKeystore {
+    /// Opaque pointer passed back to every callback.
+    pub user_data: *mut c_void,
+    /// Seals `input` under the device-bound key, authenticating `ad`.
+    pub seal: WalletKitKeystoreFn,
+    /// Opens `input` under the device-bound key, verifying `ad`.
+    pub open: WalletKitKeystoreFn,
+    /// Releases a buffer returned by `seal` or `open`.
+    pub free_buffer:
+        Option<extern "C" fn(user_data: *mut c_void, buf: *mut u8, len: usize)>,
+}
+
+/// Signature of the [`WalletKitKeystore`] `seal` / `open` callbacks.
+pub type WalletKitKeystoreFn = Option<
+    extern "C" fn(
+        user_data: *mut c_void,
+        ad: *const u8,
+        ad_len: usize,
+        input: *const u8,
+        input_len: usize,
+        out: *mut *mut u8,
+        out_len: *mut usize,
+    ) -> i32,
+>;
+
+/// Callback invoked when an asynchronous call completes.
+///
+/// On success `status` is [`WalletKitStatus::Ok`] and `result` holds the JSON
+/// response; otherwise `result` holds the error message. Either way `result` is
+/// owned by the caller and must be released with [`walletkit_free_string`].
+pub type WalletKitCallback = Option<
+    extern "C" fn(user_data: *mut c_void, status: WalletKitStatus, result: *mut c_char),
+>;
+
+/// Opaque authenticator handle.
+pub struct WalletKitAuthenticator {
+    inner: Arc<Authenticator>,
+}
+
+/// Keystore adapter over the C callbacks.
+struct CKeystore(WalletKitKeystore);
+
+// SAFETY: the caller guarantees (see `WalletKitKeystore`) that the callbacks and
+// `user_data` may be used from any thread.
+unsafe impl Send for CKeystore {}
+// SAFETY: see above.
+unsafe impl Sync for CKeystore {}
+
+impl CKeystore {
+    fn call(
+        &self,
+        f: WalletKitKeystoreFn,
+        associated_data: &[u8],
+        input: &[u8],
+    ) -> StorageResult<Vec<u8>> {
+        let (Some(f), Some(free_buffer)) = (f, self.0.free_buffer) else {
+            return Err(StorageError::Keystore(
+                "keystore callback not provided".to_string(),
+            ));
+        };
+        let mut out: *mut u8 = std::ptr::null_mut();
+        let mut out_len = 0usize;
+        let rc = f(
+            self.0.user_data,
+            associated_data.as_ptr(),
+            associated_data.len(),
+            input.as_ptr(),
+            input.len(),
+            &raw mut out,
+            &raw mut out_len,
+        );
+        if rc != 0 || out.is_null() {
+            return Err(StorageError::Keystore(format!(
+                "keystore callback failed with code {rc}"
+            )));
+        }
+        // SAFETY: on success the callback hands over a buffer of `out_len` bytes,
+        // which stays valid until released through `free_buffer` below.
+        let bytes = unsafe { std::slice::from_raw_parts(out, out_len) }.to_vec();
+        free_buffer(self.0.user_data, out, out_len);
+        Ok(bytes)
+    }
+}
+
+impl DeviceKeystore for CKeystore {
+    fn seal(
+        &self,
+        associated_data: Vec<u8>,
+        plaintext: Vec<u8>,
+    ) -> StorageResult<Vec<u8>> {
+        self.call(self.0.seal, &associated_data, &plaintext)
+    }
+
+    fn open_sealed(
+        &self,
+        associated_data: Vec<u8>,
+        ciphertext: Vec<u8>,
+    ) -> StorageResult<Vec<u8>> {
+        self.call(self.0.open, &associated_data, &ciphertext)
+    }
+}
+
+/// Filesystem blob store rooted at the storage root, with write-then-rename
+/// atomic replacement.
+struct FsBlobStore {
+    base: PathBuf,
+}
+
+impl AtomicBlobStore for FsBlobStore {
+    fn read(&self, path: String) -> StorageResult<Option<Vec<u8>>> {
+        match std::fs::read(self.base.join(path)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(StorageError::BlobStore(e.to_string())),
+        }
+    }
+
+    fn write_atomic(&self, path: String, bytes: Vec<u8>) -> StorageResult<()> {
+        let full = self.base.join(path);
+        if let Some(parent) = full.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| StorageError::BlobStore(e.to_string()))?;
+        }
+        let tmp = full.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        std::fs::write(&tmp, bytes)
+            .map_err(|e| StorageError::BlobStore(e.to_string()))?;
+        std::fs::rename(&tmp, &full).map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            StorageError::BlobStore(e.to_string())
+        })
+    }
+
+    fn delete(&self, path: String) -> StorageResult<()> {
+        match std::fs::remove_file(self.base.join(path)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(StorageError::BlobStore(e.to_string())),
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<(WalletKitStatus, String)>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(status: WalletKitStatus, message: String) {
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some((status, message)));
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+}
+
+/// Shared runtime for blocking and callback-based calls.
+fn runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("walletkit-c-ffi")
+            .build()
+            .expect("failed to build the WalletKit C FFI runtime")
+    })
+}
+
+/// Hands a result string to the caller.
+fn into_c_string(s: String) -> Result<*mut c_char, (WalletKitStatus, String)> {
+    CString::new(s).map(CString::into_raw).map_err(|_| {
+        (
+            WalletKitStatus::Other,
+            "result contains an interior NUL byte".to_string(),
+        )
+    })
+}
+
+/// Hands an error message to the caller, dropping any interior NUL bytes so the
+/// message is never lost.
+fn message_c_string(message: String) -> *mut c_char {
+    let message = if message.contains('\0') {
+        message.replace('\0', "")
+    } else {
+        message
+    };
+    CString::new(message).map_or(std::ptr::null_mut(), CString::into_raw)
+}
+
+/// Borrows a C string argument.
+///
+/// # Safety
+///
+/// `ptr` must be `NULL` or point to a NUL-terminated string valid for `'a`.
+unsafe fn borrow_str<'a>(
+    ptr: *const c_char,
+    name: &str,
+) -> Result<&'a str, (WalletKitStatus, String)> {
+    if ptr.is_null() {
+        return Err((
+            WalletKitStatus::InvalidArgument,
+            format!("`{name}` is NULL"),
+        ));
+    }
+    // SAFETY: guaranteed by the caller.
+    unsafe { CStr::from_ptr(ptr) }.to_str().map_err(|_| {
+        (
+            WalletKitStatus::InvalidArgument,
+            format!("`{name}` is not valid UTF-8"),
+        )
+    })
+}
+
+/// Runs `f`, converting a panic into [`WalletKitStatus::Panic`].
+///
+/// This only has an effect under `panic = "unwind"`; with `panic = "abort"` the
+/// process terminates before the panic can be caught.
+fn catch_panics<T>(
+    f: impl FnOnce() -> Result<T, (WalletKitStatus, String)>,
+) -> Result<T, (WalletKitStatus, String)> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| {
+        Err((WalletKitStatus::Panic, "walletkit panicked".to_string()))
+    })
+}
+
+/// Runs `f`, recording any error (or caught panic) as the last error.
+fn guarded<T>(
+    f: impl FnOnce() -> Result<T, (WalletKitStatus, String)>,
+) -> Result<T, WalletKitStatus> {
+    clear_last_error();
+    catch_panics(f).map_err(|(status, message)| {
+        set_last_error(status, message);
+        status
+    })
+}
+
+/// Delivers the outcome of an asynchronous call to its [`WalletKitCallback`].
+///
+/// The callback fires exactly once: through [`CallbackGuard::complete`], or,
+/// if the guard is dropped without completing (for example while unwinding out
+/// of the spawned task), with [`WalletKitStatus::Panic`].
+struct CallbackGuard {
+    callback: extern "C" fn(*mut c_void, WalletKitStatus, *mut c_char),
+    user_data: *mut c_void,
+    completed: bool,
+}
+
+// SAFETY: the caller of `walletkit_generate_proof_json_async` guarantees that
+// `user_data` may be used from another thread.
+unsafe impl Send for CallbackGuard {}
+
+impl CallbackGuard {
+    const fn new(
+        callback: extern "C" fn(*mut c_void, WalletKitStatus, *mut c_char),
+        user_data: *mut c_void,
+    ) -> Self {
+        Self {
+            callback,
+            user_data,
+            completed: false,
+        }
+    }
+
+    fn complete(mut self, result: Result<String, (WalletKitStatus, String)>) {
+        self.completed = true;
+        let (status, result) = match result.and_then(into_c_string) {
+            Ok(json) => (WalletKitStatus::Ok, json),
+            Err((status, message)) => (status, message_c_string(message)),
+        };
+        (self.callback)(self.user_data, status, result);
+    }
+}
+
+impl Drop for CallbackGuard {
+    fn drop(&mut self) {
+        if !self.completed {
+            (self.callback)(
+                self.user_data,
+                WalletKitStatus::Panic,
+                message_c_string("walletkit panicked".to_string()),
+            );
+        }
+    }
+}
+
+fn walletkit_error(error: &WalletKitError) -> (WalletKitStatus, String) {
+    (error.into(), error.to_string())
+}
+
+fn current_time() -> Result<u64, (WalletKitStatus, String)> {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .map_err(|e| {
+            (
+                WalletKitStatus::Other,
+                format!("Unable to determine SystemTime: {e}"),
+            )
+        })
+}
+
+async fn init_authenticator(
+    seed: Vec<u8>,
+    config_json: String,
+    storage_root: PathBuf,
+    keystore: CKeystore,
+) -> Result<Authenticator, WalletKitError> {
+    // reject a malformed config before touching storage
+    let config = parse_config(&config_json)?;
+    let paths = StoragePaths::new(&storage_root);
+    let store = Arc::new(CredentialStore::new(
+        paths.clone(),
+        Arc::new(keystore),
+        Arc::new(FsBlobStore { base: storage_root }),
+    )?);
+
+    #[cfg(feature = "embed-zkeys")]
+    let materials = {
+        let _ = paths;
+        Arc::new(Groth16Materials::from_embedded()?)
+    };
+    #[cfg(not(feature = "embed-zkeys"))]
+    let materials = Arc::new(Groth16Materials::from_cache(Arc::new(paths))?);
+
+    let authenticator =
+        Authenticator::init_with_config(&seed, config, materials, store).await?;
+    let now = current_time().map_err(|(_, error)| WalletKitError::Generic { error })?;
+    authenticator.init_storage(now)?;
+    Ok(authenticator)
+}
+
+/// Borrows the proof request argument and checks it against
+/// [`InputLimit::ProofRequest`] before anything parses or copies it.
+///
+/// # Safety
+///
+/// `request_json` must be `NULL` or point to a NUL-terminated string valid for
+/// `'a`.
+unsafe fn borrow_proof_request<'a>(
+    request_json: *const c_char,
+) -> Result<&'a str, (WalletKitStatus, String)> {
+    // SAFETY: guaranteed by the caller.
+    let request_json = unsafe { borrow_str(request_json, "request_json") }?;
+    InputLimit::ProofRequest
+        .check(request_json.len())
+        .map_err(|e| walletkit_error(&e))?;
+    Ok(request_json)
+}
+
+async fn generate_proof_json(
+    authenticator: &Authenticator,
+    request_json: &str,
+    now: u64,
+) -> Result<String, WalletKitError> {
+    let request = ProofRequest::from_json(request_json)?;
+    let now = (now != 0).then_some(now);
+    let response = authenticator.generate_proof(&request, now).await?;
+    response.to_json()
+}
+
+/// Initializes an authenticator for an already-registered World ID.
+///
+/// `config_json` is the same configuration accepted by `Authenticator::init`.
+/// Credentials and caches are stored under `storage_root`; Groth16 material is
+/// loaded from the embedded zkeys when built with `embed-zkeys`, otherwise from
+/// the cache under `storage_root`.
+///
+/// On success writes a new handle to `out` and returns [`WalletKitStatus::Ok`].
+///
+/// # Safety
+///
+/// `seed` must point to `seed_len` readable bytes; `config_json` and
+/// `storage_root` must be NUL-terminated strings; `keystore` and `out` must be
+/// valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn walletkit_authenticator_init(
+    seed: *const u8,
+    seed_len: usize,
+    config_json: *const c_char,
+    storage_root: *const c_char,
+    keystore: *const WalletKitKeystore,
+    out: *mut *mut WalletKitAuthenticator,
+) -> WalletKitStatus {
+    let result = guarded(|| {
+        if seed.is_null() || keystore.is_null() || out.is_null() {
+            return Err((
+                WalletKitStatus::InvalidArgument,
+                "`seed`, `keystore` and `out` must not be NULL".to_string(),
+            ));
+        }
+        InputLimit::Seed
+            .check(seed_len)
+            .map_err(|e| walletkit_error(&e))?;
+        // SAFETY: guaranteed by the caller.
+        let seed = unsafe { std::slice::from_raw_parts(seed, seed_len) }.to_vec();
+        // SAFETY: guaranteed by the caller.
+        let config_json = unsafe { borrow_str(config_json, "config_json") }?;
+        InputLimit::Config
+            .check(config_json.len())
+            .map_err(|e| walletkit_error(&e))?;
+        let config_json = config_json.to_string();
+        // SAFETY: guaranteed by the caller.
+        let storage_root =
+            Path::new(unsafe { borrow_str(storage_root, "storage_root") }?)
+                .to_path_buf();
+        // SAFETY: guaranteed by the caller.
+        let keystore = CKeystore(unsafe { *keystore });
+
+        let authenticator = runtime()
+            .block_on(init_authenticator(
+                seed,
+                config_json,
+                storage_root,
+                keystore,
+            ))
+            .map_err(|e| walletkit_error(&e))?;
+        let handle = Box::new(WalletKitAuthenticator {
+            inner: Arc::new(authenticator),
+        });
+        // SAFETY: `out` is valid per the caller contract.
+        unsafe { *out = Box::into_raw(handle) };
+        Ok(())
+    });
+    result.err().unwrap_or(WalletKitStatus::Ok)
+}
+
+/// Releases an authenticator handle. `NULL` is a no-op.
+///
+/// # Safety
+///
+/// `handle` must be `NULL` or a handle returned by [`walletkit_authenticator_init`]
+/// that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn walletkit_authenticator_free(
+    handle: *mut WalletKitAuthenticator,
+) {
+    if !handle.is_null() {
+        // SAFETY: guaranteed by the caller.
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/// Generates a proof for a JSON proof request, blocking until it completes.
+///
+/// `now` is the current time in seconds since the UNIX epoch; pass `0` to use the
+/// system clock. Returns the JSON proof response, or `NULL` on error.
+///
+/// # Safety
+///
+/// `handle` must be a live handle and `request_json` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn walletkit_generate_proof_json(
+    handle: *const WalletKitAuthenticator,
+    request_json: *const c_char,
+    now: u64,
+) -> *mut c_char {
+    guarded(|| {
+        // SAFETY: guaranteed by the caller.
+        let authenticator = unsafe { handle.as_ref() }.ok_or_else(|| {
+            (
+                WalletKitStatus::InvalidArgument,
+                "`handle` is NULL".to_string(),
+            )
+        })?;
+        // SAFETY: guaranteed by the caller.
+        let request_json = unsafe { borrow_proof_request(request_json) }?;
+        let json = runtime()
+            .block_on(generate_proof_json(&authenticator.inner, request_json, now))
+            .map_err(|e| walletkit_error(&e))?;
+        into_c_string(json)
+    })
+    .unwrap_or(std::ptr::null_mut())
+}
+
+/// Generates a proof for a JSON proof request without blocking.
+///
+/// Unless `callback` is `NULL`, it is invoked exactly once with `user_data` and
+/// the outcome. Returns [`WalletKitStatus::Ok`] if the request was scheduled,
+/// in which case `callback` runs later on a library-owned thread. If the
+/// request is rejected up front, the error is recorded as the last error,
+/// returned, and also delivered to `callback` on the calling thread before
+/// this function returns.
+///
+/// # Safety
+///
+/// `handle` must be a live handle, `request_json` a NUL-terminated string, and
+/// `user_data` must be safe to use from another thread.
+#[no_mangle]
+pub unsafe extern "C" fn walletkit_generate_proof_json_async(
+    handle: *const WalletKitAuthenticator,
+    request_json: *const c_char,
+    now: u64,
+    callback: WalletKitCallback,
+    user_data: *mut c_void,
+) -> WalletKitStatus {
+    clear_last_error();
+    let Some(callback) = callback else {
+        set_last_error(
+            WalletKitStatus::InvalidArgument,
+            "`callback` is NULL".to_string(),
+        );
+        return WalletKitStatus::InvalidArgument;
+    };
+    let mut guard = Some(CallbackGuard::new(callback, user_data));
+
+    let result = catch_panics(|| {
+        // SAFETY: guaranteed by the caller.
+        let authenticator = unsafe { handle.as_ref() }.ok_or_else(|| {
+            (
+                WalletKitStatus::InvalidArgument,
+                "`handle` is NULL".to_string(),
+            )
+        })?;
+        // SAFETY: guaranteed by the caller.
+        let request_json = unsafe { borrow_proof_request(request_json) }?.to_string();
+
+        let authenticator = Arc::clone(&authenticator.inner);
+        let guard = guard.take();
+        runtime().spawn(async move {
+            let result = generate_proof_json(&authenticator, &request_json, now)
+                .await
+                .map_err(|e| walletkit_error(&e));
+            if let Some(guard) = guard {
+                guard.complete(result);
+            }
+        });
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => WalletKitStatus::Ok,
+        Err((status, message)) => {
+            set_last_error(status, message.clone());
+            if let Some(guard) = guard {
+                guard.complete(Err((status, message)));
+            }
+            status
+        }
+    }
+}
+
+/// Releases a string returned by this library. `NULL` is a no-op.
+///
+/// # Safety
+///
+/// `s` must be `NULL` or a string returned by this library that has not been
+/// freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn walletkit_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        // SAFETY: guaranteed by the caller.
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+/// Returns the status of the last failed call on this thread, or
+/// [`WalletKitStatus::Ok`] if the last call succeeded.
+#[no_mangle]
+pub extern "C" fn walletkit_last_error_code() -> WalletKitStatus {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(WalletKitStatus::Ok, |(s, _)| *s))
+}
+
+/// Returns the message of the last failed call on this thread, or `NULL` if the
+/// last call succeeded. Release it with [`walletkit_free_string`].
+#[no_mangle]
+pub extern "C" fn walletkit_last_error_message() -> *mut c_char {
+    LAST_ERROR.with(|e| {
+        e.borrow()
+            .as_ref()
+            .map_or(std::ptr::null_mut(), |(_, message)| {
+                message_c_string(message.clone())
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::MAX_PROOF_REQUEST_BYTES;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+
+    /// Returns a handle for an authenticator whose RPC is a local mock.
+    fn test_handle(root: &Path) -> WalletKitAuthenticator {
+        use alloy::primitives::address;
+        use world_id_core::primitives::{Config, ServiceEndpoint};
+        use world_id_core::Authenticator as CoreAuthenticator;
+
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let inner = runtime().block_on(async {
+            let mut mock_server = mockito::Server::new_async().await;
+            mock_server
+                .mock("POST", "/")
+                .with_status(200)
+                .with_header("content-type", "application/json")
+                .with_body(
+                    serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": "0x0000000000000000000000000000000000000000000000000000000000000001"
+                    })
+                    .to_string(),
+                )
+                .create_async()
+                .await;
+            let config = Config::new(
+                Some(mock_server.url()),
+                480,
+                address!("0x969947cFED008bFb5e3F32a25A1A2CDdf64d46fe"),
+                ServiceEndpoint::direct(mock_server.url()),
+                ServiceEndpoint::direct(mock_server.url()),
+                vec![],
+                2,
+            )
+            .unwrap();
+            let inner = CoreAuthenticator::init(&[2u8; 32], config)
+                .await
+                .expect("init authenticator");
+            drop(mock_server);
+            inner
+        });
+        let provider = InMemoryStorageProvider::new(root);
+        let store = CredentialStore::from_provider(&provider).expect("store");
+        store.init(1, 100).expect("init storage");
+        WalletKitAuthenticator {
+            inner: Arc::new(Authenticator::from_parts(inner, Arc::new(store))),
+        }
+    }
+
+    fn last_error_message() -> String {
+        let message = walletkit_last_error_message();
+        assert!(!message.is_null());
+        // SAFETY: returned by this library and released right after.
+        let text = unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned();
+        // SAFETY: returned by this library and not yet freed.
+        unsafe { walletkit_free_string(message) };
+        text
+    }
+
+    type Outcomes = Vec<(WalletKitStatus, String)>;
+
+    /// Callback that appends each outcome to the `Outcomes` behind `user_data`.
+    extern "C" fn record(
+        user_data: *mut c_void,
+        status: WalletKitStatus,
+        result: *mut c_char,
+    ) {
+        // SAFETY: returned by this library and released right after.
+        let text = unsafe { CStr::from_ptr(result) }
+            .to_string_lossy()
+            .into_owned();
+        // SAFETY: returned by this library and not yet freed.
+        unsafe { walletkit_free_string(result) };
+        // SAFETY: the tests pass a live `Outcomes` as `user_data`.
+        unsafe { &mut *user_data.cast::<Outcomes>() }.push((status, text));
+    }
+
+    #[test]
+    fn test_oversized_proof_request_is_rejected_by_both_paths() {
+        let root = temp_root_path();
+        let handle = test_handle(&root);
+        let oversized = CString::new(" ".repeat(MAX_PROOF_REQUEST_BYTES + 1)).unwrap();
+
+        // SAFETY: live handle and NUL-terminated request.
+        let response = unsafe {
+            walletkit_generate_proof_json(&raw const handle, oversized.as_ptr(), 0)
+        };
+        assert!(response.is_null());
+        assert_eq!(walletkit_last_error_code(), WalletKitStatus::InvalidInput);
+        assert!(last_error_message().contains("proof_request"));
+
+        let mut outcomes = Outcomes::new();
+        // SAFETY: live handle and NUL-terminated request; `outcomes` outlives
+        // the call, and the callback runs before it returns.
+        let status = unsafe {
+            walletkit_generate_proof_json_async(
+                &raw const handle,
+                oversized.as_ptr(),
+                0,
+                Some(record),
+                (&raw mut outcomes).cast(),
+            )
+        };
+        assert_eq!(status, WalletKitStatus::InvalidInput);
+        assert!(last_error_message().contains("proof_request"));
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].0, WalletKitStatus::InvalidInput);
+        assert!(outcomes[0].1.contains("proof_request"));
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_async_argument_errors_fire_the_callback_once() {
+        let mut outcomes = Outcomes::new();
+        let request = CString::new("{}").unwrap();
+        // SAFETY: a NULL handle is rejected before it is dereferenced.
+        let status = unsafe {
+            walletkit_generate_proof_json_async(
+                std::ptr::null(),
+                request.as_ptr(),
+                0,
+                Some(record),
+                (&raw mut outcomes).cast(),
+            )
+        };
+        assert_eq!(status, WalletKitStatus::InvalidArgument);
+        assert_eq!(
+            walletkit_last_error_code(),
+            WalletKitStatus::InvalidArgument
+        );
+        assert_eq!(
+            outcomes,
+            vec![(
+                WalletKitStatus::InvalidArgument,
+                "`handle` is NULL".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_dropped_callback_guard_reports_a_panic() {
+        let mut outcomes = Outcomes::new();
+        drop(CallbackGuard::new(record, (&raw mut outcomes).cast()));
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].0, WalletKitStatus::Panic);
+
+        let mut outcomes = Outcomes::new();
+        CallbackGuard::new(record, (&raw mut outcomes).cast())
+            .complete(Ok("{}".to_string()));
+        assert_eq!(outcomes, vec![(WalletKitStatus::Ok, "{}".to_string())]);
+    }
+
+    #[test]
+    fn test_interior_nul_is_reported() {
+        let err = into_c_string("a\0b".to_string()).unwrap_err();
+        assert_eq!(err.0, WalletKitStatus::Other);
+
+        let message = message_c_string("a\0b".to_string());
+        // SAFETY: returned by this library and released right after.
+        let text = unsafe { CStr::from_ptr(message) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        // SAFETY: returned by this library and not yet freed.
+        unsafe { walletkit_free_string(message) };
+        assert_eq!(text, "ab");
+    }
+}
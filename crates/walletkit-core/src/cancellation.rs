@@ -0,0 +1,123 @@
+//! Cancellation of long-running calls by the host.
+//!
+//! A [`CancellationHandle`] is passed to a cancellable call, e.g.
+//! `Authenticator::generate_proof_with_cancellation`, and cancelled from any
+//! thread, typically when the user dismisses the dialog waiting for it. The
+//! call then returns [`WalletKitError::Cancelled`] and drops its work in
+//! progress, closing its network requests and releasing its proving permit.
+//!
+//! Work is dropped at the call's next await point. The Groth16 proofs of a
+//! request are computed by `world-id-core` without yielding, so once they are
+//! being computed, cancellation takes effect when they are done.
+
+use std::future::{poll_fn, Future};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::Poll;
+
+use tokio::sync::Notify;
+
+use crate::error::WalletKitError;
+
+/// Cancels the calls it is passed to, see the
+/// [module documentation](self).
+///
+/// A handle stays cancelled; use a new one for each call.
+#[derive(Debug, Default, uniffi::Object)]
+pub struct CancellationHandle {
+    cancelled: AtomicBool,
+    /// Wakes the calls waiting on this handle.
+    notify: Notify,
+}
+
+#[uniffi::export]
+impl CancellationHandle {
+    /// Creates a handle that is not cancelled.
+    #[uniffi::constructor]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the calls this handle was passed to, and those it is passed
+    /// to from now on.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    /// Whether [`cancel`](Self::cancel) was called.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl CancellationHandle {
+    /// Runs `future` until it completes or this handle is cancelled,
+    /// whichever comes first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WalletKitError::Cancelled`] if the handle is cancelled
+    /// first, or the error of `future`.
+    pub(crate) async fn run<T>(
+        &self,
+        future: impl Future<Output = Result<T, WalletKitError>>,
+    ) -> Result<T, WalletKitError> {
+        let mut future = Box::pin(future);
+        // created before the first check, so a `cancel` racing it still wakes us
+        let mut cancelled = Box::pin(self.notify.notified());
+        poll_fn(|cx| {
+            if self.is_cancelled() {
+                return Poll::Ready(Err(WalletKitError::Cancelled));
+            }
+            if let Poll::Ready(output) = future.as_mut().poll(cx) {
+                return Poll::Ready(output);
+            }
+            if cancelled.as_mut().poll(cx).is_ready() || self.is_cancelled() {
+                return Poll::Ready(Err(WalletKitError::Cancelled));
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cancel_stops_pending_call() {
+        let handle = Arc::new(CancellationHandle::new());
+        let canceller = {
+            let handle = Arc::clone(&handle);
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                handle.cancel();
+            })
+        };
+        let result = handle
+            .run(async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .await;
+        assert!(matches!(result, Err(WalletKitError::Cancelled)));
+        canceller.await.unwrap();
+
+        // a cancelled handle stays cancelled
+        assert!(handle.is_cancelled());
+        assert!(matches!(
+            handle.run(async { Ok(1) }).await,
+            Err(WalletKitError::Cancelled)
+        ));
+        assert_eq!(
+            CancellationHandle::new().run(async { Ok(1) }).await.ok(),
+            Some(1)
+        );
+    }
+}
@@ -5,6 +5,7 @@ use std::ops::Deref;
 use world_id_core::Credential as CoreCredential;
 
 use crate::error::WalletKitError;
+use crate::limits::InputLimit;
 use crate::FieldElement;
 
 /// A wrapper around [`CoreCredential`] to enable FFI interoperability.
@@ -20,18 +21,12 @@ impl Credential {
     ///
     /// # Errors
     ///
-    /// Returns an error if the bytes cannot be deserialized.
+    /// Returns an error if the bytes exceed [`MAX_CREDENTIAL_BYTES`](crate::limits::MAX_CREDENTIAL_BYTES) or cannot
+    /// be deserialized.
     #[uniffi::constructor]
     #[allow(clippy::needless_pass_by_value)]
     pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, WalletKitError> {
-        let credential: CoreCredential =
-            serde_json::from_slice(&bytes).map_err(|e| {
-                WalletKitError::InvalidInput {
-                    attribute: "credential_bytes".to_string(),
-                    reason: format!("Failed to deserialize credential: {e}"),
-                }
-            })?;
-        Ok(Self(credential))
+        Self::from_slice(&bytes)
     }
 
     /// Returns the credential's `sub` field element.
@@ -62,6 +57,25 @@ impl Credential {
 }
 
 impl Credential {
+    /// Deserializes a `Credential` from a borrowed JSON byte blob, see
+    /// [`Self::from_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes exceed the size limit or cannot be
+    /// deserialized.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, WalletKitError> {
+        InputLimit::Credential.check(bytes.len())?;
+        let credential: CoreCredential =
+            serde_json::from_slice(bytes).map_err(|e| {
+                WalletKitError::InvalidInput {
+                    attribute: "credential_bytes".to_string(),
+                    reason: format!("Failed to deserialize credential: {e}"),
+                }
+            })?;
+        Ok(Self(credential))
+    }
+
     /// Serializes the credential to a JSON byte blob for storage.
     ///
     /// # Errors
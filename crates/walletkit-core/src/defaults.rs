@@ -34,6 +34,15 @@ pub fn world_id_verifier_address(environment: &Environment) -> Address {
     }
 }
 
+/// Returns the World ID Registry contract address for the given environment.
+#[must_use]
+pub fn world_id_registry_address(environment: &Environment) -> Address {
+    match environment {
+        Environment::Staging => STAGING_WORLD_ID_REGISTRY,
+        Environment::Production => WORLD_ID_REGISTRY,
+    }
+}
+
 /// The `PoH` Recovery Agent contract address on the staging environment.
 pub static POH_RECOVERY_AGENT_ADDRESS_STAGING: Address =
     address!("0x8df366ed8ef894f0d1d25dc21b7e36e2d97a7140");
@@ -135,16 +144,13 @@ fn build_config(
     indexer: ServiceEndpoint,
     gateway: ServiceEndpoint,
 ) -> Result<Config, WalletKitError> {
-    let (chain_id, registry_address) = match environment {
-        // Staging also runs on World Chain Mainnet.
-        Environment::Staging => (480, STAGING_WORLD_ID_REGISTRY),
-        Environment::Production => (480, WORLD_ID_REGISTRY),
-    };
+    // Staging also runs on World Chain Mainnet.
+    let chain_id = 480;
 
     Config::new(
         rpc_url,
         chain_id,
-        registry_address,
+        world_id_registry_address(environment),
         indexer,
         gateway,
         oprf_node_urls(region, environment),
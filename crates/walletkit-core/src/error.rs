@@ -5,10 +5,11 @@ use world_id_core::{
 };
 use world_id_proof::ProofError;
 
-use crate::storage::StorageError;
+use crate::storage::{ErrorSeverity, StorageError};
 
 /// Error outputs from `WalletKit`
-#[derive(Debug, Error, uniffi::Error)]
+#[derive(Debug, Error, uniffi::Error, strum::IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum WalletKitError {
     /// Invalid input provided (e.g., incorrect length, format, etc.)
     #[error("invalid_input_{attribute}")]
@@ -98,6 +99,43 @@ pub enum WalletKitError {
     #[error("nullifier_replay")]
     NullifierReplay,
 
+    /// The generated nullifier was already disclosed for a different proof
+    /// request.
+    #[error("nullifier_conflict: {conflicting_request}")]
+    NullifierConflict {
+        /// Hex-encoded SHA-256 digest of the ID of the request the nullifier
+        /// was disclosed for.
+        conflicting_request: String,
+    },
+
+    /// No cached Merkle inclusion proof is recent enough to generate a proof
+    /// without network access.
+    #[error("offline_proof_unavailable")]
+    OfflineProofUnavailable {
+        /// Age of the cached proof in seconds, or `u64::MAX` if none is cached.
+        cache_age_seconds: u64,
+    },
+
+    /// The proof request's `valid_until` or `expires_at` has passed, so no
+    /// proof was generated. See [`ProofRequest::valid_until`](crate::requests::ProofRequest::valid_until).
+    #[error("proof_request_expired: expired at {expired_at}")]
+    ProofRequestExpired {
+        /// The request's `valid_until` or `expires_at` that passed (unix
+        /// seconds).
+        expired_at: u64,
+    },
+
+    /// The proof request is for another environment than the authenticator's,
+    /// e.g. a staging request sent to a production authenticator. Rejected
+    /// before any replay guard entry is written.
+    #[error("environment_mismatch: request chain {request_chain}, configured chain {configured_chain}")]
+    EnvironmentMismatch {
+        /// Chain ID of the World ID registry the request is for.
+        request_chain: u64,
+        /// Chain ID of the authenticator's configuration.
+        configured_chain: u64,
+    },
+
     /// The RP's signature on the proof request could not be verified.
     #[error("invalid_rp_signature")]
     InvalidRpSignature,
@@ -172,6 +210,63 @@ pub enum WalletKitError {
         error_code: String,
     },
 
+    /// The attestation token is too old and must be regenerated by the platform.
+    #[error("attestation_expired")]
+    AttestationExpired,
+
+    /// The attestation token is not in a recognized platform format (App Attest or Play Integrity).
+    #[error("attestation_unknown_format")]
+    AttestationUnknownFormat,
+
+    /// The attestation token is bound to a different challenge than the one
+    /// expected.
+    #[error("attestation_nonce_mismatch")]
+    AttestationNonceMismatch,
+
+    /// The signature of a JWS-signed proof request does not verify against
+    /// the RP's key.
+    #[error("jws_invalid_signature")]
+    JwsInvalidSignature,
+
+    /// The `alg` of a JWS-signed proof request is not `ES256` or `EdDSA`, or
+    /// does not match the RP's key.
+    #[error("jws_algorithm_not_allowed: {alg}")]
+    JwsAlgorithmNotAllowed {
+        /// The rejected algorithm
+        alg: String,
+    },
+
+    /// A JWS-signed proof request is past its `exp` claim.
+    #[error("jws_expired: {expires_at}")]
+    JwsExpired {
+        /// The `exp` claim, in seconds since the UNIX epoch
+        expires_at: u64,
+    },
+
+    /// A JWS-signed proof request has an `iat` claim in the future.
+    #[error("jws_issued_in_future: {issued_at}")]
+    JwsIssuedInFuture {
+        /// The `iat` claim, in seconds since the UNIX epoch
+        issued_at: u64,
+    },
+
+    /// The RP has no signing key with the `kid` of a JWS-signed proof request.
+    #[error("jws_key_not_found: {kid} for {rp_id}")]
+    JwsKeyNotFound {
+        /// The RP the request claims to be from
+        rp_id: String,
+        /// The key ID from the JWS header
+        kid: String,
+    },
+
+    /// The server's certificate chain matched none of the TLS pins of its
+    /// host. See `set_tls_pins`.
+    #[error("tls_pin_mismatch: {host}")]
+    TlsPinMismatch {
+        /// The host whose pins did not match
+        host: String,
+    },
+
     /// The debug report was not found
     #[error("debug_report_not_found")]
     DebugReportNotFound,
@@ -186,19 +281,99 @@ pub enum WalletKitError {
         error: String,
     },
 
+    /// The browser or passkey does not support the `WebAuthn` PRF extension.
+    /// The app should offer a different unlock path.
+    #[error("passkey_prf_unavailable")]
+    PasskeyPrfUnavailable,
+
     /// The session proof action or session OPRF seed is not valid for the session OPRF module.
     #[error("invalid_action_for_session")]
     InvalidActionSession,
+
+    /// The credential store was initialized for a different account than the
+    /// one the registry reports for this authenticator.
+    #[error("leaf_index_mismatch: stored {stored}, discovered {discovered}")]
+    LeafIndexMismatch {
+        /// Leaf index the store was initialized with.
+        stored: u64,
+        /// Leaf index reported by the registry.
+        discovered: u64,
+    },
+
+    /// A legacy identity was already upgraded with this seed, so the 4.0
+    /// account is not registered again. See `migration::upgrade`.
+    #[error("migration_already_submitted: {request_id}")]
+    MigrationAlreadySubmitted {
+        /// Gateway request ID of the earlier registration.
+        request_id: String,
+    },
+
+    /// An account attestation was rejected. `reason` is one of `malformed`,
+    /// `unsupported`, `signature`, `audience`, `expired` or
+    /// `issued_in_future`. See `verify_account_attestation`.
+    #[error("account_attestation_invalid: {reason}")]
+    AccountAttestationInvalid {
+        /// Why the attestation was rejected.
+        reason: String,
+    },
+
+    /// A downloaded proving artifact does not match the checksum pinned in
+    /// the artifact manifest. The download is discarded. See
+    /// `ArtifactManager::ensure`.
+    #[error("artifact_checksum_mismatch: {artifact_id}")]
+    ArtifactChecksumMismatch {
+        /// The artifact that failed verification.
+        artifact_id: String,
+        /// SHA-256 digest pinned in the manifest, hex-encoded.
+        expected: String,
+        /// SHA-256 digest of the download, hex-encoded.
+        actual: String,
+    },
+
+    /// The download of a proving artifact was cancelled. What was downloaded
+    /// so far is kept and resumed by the next `ArtifactManager::ensure`.
+    #[error("artifact_download_cancelled: {artifact_id}")]
+    ArtifactDownloadCancelled {
+        /// The artifact whose download was cancelled.
+        artifact_id: String,
+    },
+
+    /// The call was cancelled through its `CancellationHandle`.
+    #[error("cancelled")]
+    Cancelled,
+
+    /// A credential storage operation failed.
+    #[error("storage_error: {error}")]
+    Storage {
+        /// The storage error message
+        error: String,
+        /// How the app should react (see [`ErrorSeverity`])
+        severity: ErrorSeverity,
+        /// Whether persisted data is corrupted
+        is_corruption: bool,
+    },
 }
 
 impl From<reqwest::Error> for WalletKitError {
     fn from(error: reqwest::Error) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(host) = crate::tls_pinning::pin_mismatch_host(&error) {
+            return Self::TlsPinMismatch { host };
+        }
         Self::Reqwest {
             error: error.to_string(),
         }
     }
 }
 
+impl From<uniffi::UnexpectedUniFFICallbackError> for WalletKitError {
+    fn from(error: uniffi::UnexpectedUniFFICallbackError) -> Self {
+        Self::Generic {
+            error: error.reason,
+        }
+    }
+}
+
 impl From<PrimitiveError> for WalletKitError {
     fn from(error: PrimitiveError) -> Self {
         match error {
@@ -265,8 +440,17 @@ impl From<semaphore_rs::protocol::ProofError> for WalletKitError {
 
 impl From<StorageError> for WalletKitError {
     fn from(error: StorageError) -> Self {
-        Self::Generic {
-            error: error.to_string(),
+        match error {
+            StorageError::NullifierConflict {
+                conflicting_request_id,
+            } => Self::NullifierConflict {
+                conflicting_request: hex::encode(conflicting_request_id),
+            },
+            error => Self::Storage {
+                severity: error.severity(),
+                is_corruption: error.is_corruption(),
+                error: error.to_string(),
+            },
         }
     }
 }
@@ -0,0 +1,358 @@
+//! Localized, user-facing messages for [`WalletKitError`].
+//!
+//! Errors keep their machine-readable codes; [`WalletKitError::user_message`]
+//! looks up display text for the error's code in a message catalog. English
+//! is built in, and hosts add or override locales with
+//! [`register_error_messages`].
+//!
+//! Templates reference the error's fields by name in braces, e.g.
+//! `"{host} could not be verified"`. Placeholders without a matching field
+//! are left as they are.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{PoisonError, RwLock};
+
+use crate::error::WalletKitError;
+
+/// Locale every lookup falls back to.
+const DEFAULT_LOCALE: &str = "en";
+
+/// Built-in English messages, by error code. Codes without an entry have no
+/// user-facing text, so hosts show their own copy.
+const EN: &[(&str, &str)] = &[
+    ("invalid_input", "The provided {attribute} is not valid."),
+    (
+        "network_error",
+        "The server could not be reached. Check your connection and try again.",
+    ),
+    (
+        "reqwest",
+        "The server could not be reached. Check your connection and try again.",
+    ),
+    (
+        "credential_not_issued",
+        "This credential has not been issued to you yet.",
+    ),
+    (
+        "account_does_not_exist",
+        "No World ID is registered on this device yet.",
+    ),
+    (
+        "unfulfillable_request",
+        "You don't have the credentials this request asks for.",
+    ),
+    (
+        "nullifier_replay",
+        "You have already responded to this request.",
+    ),
+    (
+        "offline_proof_unavailable",
+        "Connect to the internet to respond to this request.",
+    ),
+    (
+        "proof_request_expired",
+        "This request has expired. Ask for a new one.",
+    ),
+    (
+        "environment_mismatch",
+        "This request is for a different World ID environment.",
+    ),
+    (
+        "jws_expired",
+        "This request has expired. Ask for a new one.",
+    ),
+    (
+        "invalid_rp_signature",
+        "This request could not be verified.",
+    ),
+    (
+        "nfc_non_retryable",
+        "Your document could not be verified ({error_code}).",
+    ),
+    (
+        "attestation_expired",
+        "Verification took too long. Please try again.",
+    ),
+    (
+        "attestation_nonce_mismatch",
+        "This device could not be verified. Please try again.",
+    ),
+    (
+        "tls_pin_mismatch",
+        "A secure connection to {host} could not be established.",
+    ),
+    (
+        "not_eligible_for_recovery",
+        "This World ID is not eligible for recovery.",
+    ),
+    (
+        "passkey_prf_unavailable",
+        "This passkey cannot unlock your World ID. Use another unlock method.",
+    ),
+    (
+        "storage",
+        "Your credentials could not be accessed on this device.",
+    ),
+];
+
+/// Catalogs registered by the host, by normalized locale.
+static HOST_CATALOGS: RwLock<BTreeMap<String, HashMap<String, String>>> =
+    RwLock::new(BTreeMap::new());
+
+/// Registers user-facing messages for `locale` (e.g. `de` or `pt-BR`), as a
+/// map from error code to message template.
+///
+/// Registering again for the same locale adds to the earlier messages, and
+/// replaces those with the same code. Messages registered for `en` replace
+/// the built-in ones.
+///
+/// Error codes are the `snake_case` names of the [`WalletKitError`]
+/// variants, e.g. `invalid_input` or `tls_pin_mismatch`; templates reference
+/// the variant's fields in braces, e.g. `{attribute}`.
+#[uniffi::export]
+#[expect(clippy::implicit_hasher, reason = "UniFFI exports cannot be generic")]
+pub fn register_error_messages(locale: &str, messages: HashMap<String, String>) {
+    // catalogs are only ever extended, a panic cannot leave one partial
+    HOST_CATALOGS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(normalize_locale(locale))
+        .or_default()
+        .extend(messages);
+}
+
+#[uniffi::export]
+impl WalletKitError {
+    /// Returns a message describing this error to the user in `locale`.
+    ///
+    /// `locale` is a BCP 47 tag such as `pt-BR`. Lookup tries the full tag,
+    /// then its language (`pt`), then English. Returns `None` if no catalog
+    /// has a message for this error, so the host shows its own copy.
+    #[must_use]
+    pub fn user_message(&self, locale: &str) -> Option<String> {
+        let code: &'static str = self.into();
+        let template = template(code, locale)?;
+        Some(fill(&template, &self.message_params()))
+    }
+}
+
+impl WalletKitError {
+    /// The fields of this error that templates can reference.
+    fn message_params(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Self::InvalidInput { attribute, reason } => {
+                vec![("attribute", attribute.clone()), ("reason", reason.clone())]
+            }
+            Self::NetworkError { url, error, status } => {
+                let mut params = vec![("url", url.clone()), ("error", error.clone())];
+                if let Some(status) = status {
+                    params.push(("status", status.to_string()));
+                }
+                params
+            }
+            Self::SerializationError { error }
+            | Self::Reqwest { error }
+            | Self::ProofGeneration { error }
+            | Self::AuthenticatorError { error }
+            | Self::ResponseValidation(error)
+            | Self::Groth16MaterialEmbeddedLoad { error }
+            | Self::Generic { error }
+            | Self::OhttpError { error }
+            | Self::Storage { error, .. } => vec![("error", error.clone())],
+            Self::Groth16MaterialCacheInvalid { path, error } => {
+                vec![("path", path.clone()), ("error", error.clone())]
+            }
+            Self::NullifierConflict {
+                conflicting_request,
+            } => vec![("conflicting_request", conflicting_request.clone())],
+            Self::OfflineProofUnavailable { cache_age_seconds } => {
+                vec![("cache_age_seconds", cache_age_seconds.to_string())]
+            }
+            Self::ProofRequestExpired { expired_at } => {
+                vec![("expired_at", expired_at.to_string())]
+            }
+            Self::EnvironmentMismatch {
+                request_chain,
+                configured_chain,
+            } => vec![
+                ("request_chain", request_chain.to_string()),
+                ("configured_chain", configured_chain.to_string()),
+            ],
+            Self::NfcNonRetryable { error_code } => {
+                vec![("error_code", error_code.clone())]
+            }
+            Self::JwsAlgorithmNotAllowed { alg } => vec![("alg", alg.clone())],
+            Self::JwsExpired { expires_at } => {
+                vec![("expires_at", expires_at.to_string())]
+            }
+            Self::JwsIssuedInFuture { issued_at } => {
+                vec![("issued_at", issued_at.to_string())]
+            }
+            Self::JwsKeyNotFound { rp_id, kid } => {
+                vec![("rp_id", rp_id.clone()), ("kid", kid.clone())]
+            }
+            Self::TlsPinMismatch { host } => vec![("host", host.clone())],
+            Self::LeafIndexMismatch { stored, discovered } => vec![
+                ("stored", stored.to_string()),
+                ("discovered", discovered.to_string()),
+            ],
+            Self::MigrationAlreadySubmitted { request_id } => {
+                vec![("request_id", request_id.clone())]
+            }
+            Self::ArtifactChecksumMismatch {
+                artifact_id,
+                expected,
+                actual,
+            } => vec![
+                ("artifact_id", artifact_id.clone()),
+                ("expected", expected.clone()),
+                ("actual", actual.clone()),
+            ],
+            Self::AccountAttestationInvalid { reason } => {
+                vec![("reason", reason.clone())]
+            }
+            Self::ArtifactDownloadCancelled { artifact_id } => {
+                vec![("artifact_id", artifact_id.clone())]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Lowercases `locale` and uses `-` as the subtag separator, so `pt_BR` and
+/// `pt-br` find the catalog registered as `pt-BR`.
+fn normalize_locale(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_ascii_lowercase()
+}
+
+/// Returns the template for `code`, trying `locale`, its language, and the
+/// default locale in turn. Host catalogs take precedence over built-in ones.
+fn template(code: &str, locale: &str) -> Option<String> {
+    let locale = normalize_locale(locale);
+    let language = locale.split('-').next().unwrap_or_default();
+    let catalogs = HOST_CATALOGS.read().unwrap_or_else(PoisonError::into_inner);
+    let template = [locale.as_str(), language, DEFAULT_LOCALE]
+        .into_iter()
+        .find_map(|candidate| {
+            catalogs
+                .get(candidate)
+                .and_then(|catalog| catalog.get(code))
+                .cloned()
+                .or_else(|| {
+                    if candidate == DEFAULT_LOCALE {
+                        built_in_template(code).map(str::to_string)
+                    } else {
+                        None
+                    }
+                })
+        });
+    template
+}
+
+/// Returns the built-in English template for `code`.
+fn built_in_template(code: &str) -> Option<&'static str> {
+    EN.iter()
+        .find(|(entry, _)| *entry == code)
+        .map(|(_, template)| *template)
+}
+
+/// Replaces each `{name}` in `template` with the value of parameter `name`,
+/// in a single pass so values are never substituted into.
+fn fill(template: &str, params: &[(&'static str, String)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start..];
+        let value = after.find('}').and_then(|end| {
+            let name = &after[1..end];
+            params
+                .iter()
+                .find(|(param, _)| *param == name)
+                .map(|(_, value)| (value, end))
+        });
+        let Some((value, end)) = value else {
+            filled.push('{');
+            rest = &after[1..];
+            continue;
+        };
+        filled.push_str(value);
+        rest = &after[end + 1..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_message_fills_parameters() {
+        let error = WalletKitError::InvalidInput {
+            attribute: "proof_request".to_string(),
+            reason: "too large".to_string(),
+        };
+        assert_eq!(
+            error.user_message("en").as_deref(),
+            Some("The provided proof_request is not valid.")
+        );
+
+        assert_eq!(
+            fill(
+                "{host} {missing} {host}{ {",
+                &[("host", "a {host}".to_string())]
+            ),
+            "a {host} {missing} a {host}{ {"
+        );
+    }
+
+    #[test]
+    fn test_user_message_falls_back_to_english() {
+        let error = WalletKitError::TlsPinMismatch {
+            host: "example.org".to_string(),
+        };
+        let english = Some(
+            "A secure connection to example.org could not be established.".to_string(),
+        );
+        assert_eq!(error.user_message("fr-FR"), english);
+        assert_eq!(error.user_message(""), english);
+        // codes without a message are left to the host
+        assert_eq!(WalletKitError::InvalidNumber.user_message("en"), None);
+    }
+
+    #[test]
+    fn test_host_catalogs_extend_and_override_built_ins() {
+        register_error_messages(
+            "de",
+            HashMap::from([(
+                "nfc_non_retryable".to_string(),
+                "Dokument abgelehnt: {error_code}".to_string(),
+            )]),
+        );
+        register_error_messages(
+            "en",
+            HashMap::from([(
+                "attestation_expired".to_string(),
+                "Please try again.".to_string(),
+            )]),
+        );
+
+        let error = WalletKitError::NfcNonRetryable {
+            error_code: "document_expired".to_string(),
+        };
+        for locale in ["de", "de-AT", "DE_at"] {
+            assert_eq!(
+                error.user_message(locale).as_deref(),
+                Some("Dokument abgelehnt: document_expired")
+            );
+        }
+        // codes missing from a host catalog fall back to English
+        assert_eq!(
+            WalletKitError::AttestationExpired
+                .user_message("de")
+                .as_deref(),
+            Some("Please try again.")
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! The HTTP client shared by `WalletKit`'s own requests.
+//!
+//! Every request `WalletKit` sends itself (app registry, JWS key fetches,
+//! issuers, proving artifacts, v3 Merkle proofs) goes through one
+//! `reqwest::Client`, so connections are pooled and reused across calls and
+//! instances. [`set_http_client_config`] sets its timeouts, retries and proxy;
+//! `set_tls_pins` its certificate pins. Both rebuild the shared client right
+//! away and leave it untouched if the new one cannot be built, so a rejected
+//! setting never drops the pins. Call them at startup.
+//!
+//! Hosts whose outbound traffic must go through their own HTTP stack install
+//! an `HttpTransport` with `set_http_transport` (native targets only). The
+//! requests are then handed to the transport instead of the shared client;
+//! timeouts, retries and the user agent still apply, TLS and proxying are up
+//! to the transport.
+//!
+//! The registry, gateway, indexer and OPRF requests of an
+//! [`Authenticator`](crate::Authenticator) are sent by `world-id-core` with its
+//! own clients and are not covered.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+use std::sync::{Mutex, PoisonError, RwLock};
+use std::time::Duration;
+
+use crate::error::WalletKitError;
+
+/// Settings of the shared HTTP client, see [`set_http_client_config`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct HttpClientConfig {
+    /// Timeout of each attempt of a request, in milliseconds.
+    #[uniffi(default = 5000)]
+    pub timeout_ms: u64,
+    /// How often a request that failed transiently (timeout, connection error,
+    /// 429 or 5xx) is retried.
+    #[uniffi(default = 3)]
+    pub max_retries: u32,
+    /// Proxy to send all requests through, e.g. `http://proxy.internal:3128`.
+    /// Not supported on wasm32, where the browser decides.
+    #[uniffi(default = None)]
+    pub proxy_url: Option<String>,
+    /// Idle connections kept open per host for reuse.
+    #[uniffi(default = 8)]
+    pub max_idle_connections_per_host: u32,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        DEFAULT_CONFIG
+    }
+}
+
+const DEFAULT_CONFIG: HttpClientConfig = HttpClientConfig {
+    timeout_ms: 5000,
+    max_retries: 3,
+    proxy_url: None,
+    max_idle_connections_per_host: 8,
+};
+
+/// The current settings.
+static CONFIG: RwLock<HttpClientConfig> = RwLock::new(DEFAULT_CONFIG);
+
+/// The shared client, rebuilt by the setters and built on first use otherwise.
+static CLIENT: RwLock<Option<reqwest::Client>> = RwLock::new(None);
+
+/// Serializes rebuilding the shared client, so it always matches the current
+/// settings and pins.
+static REBUILD: Mutex<()> = Mutex::new(());
+
+/// Configures the HTTP client shared by `WalletKit`'s own requests, replacing
+/// the settings of a previous call. Clients created before the call keep the
+/// previous settings.
+///
+/// # Errors
+/// Returns [`WalletKitError::InvalidInput`] if the timeout is 0 or the proxy
+/// URL is invalid or, on wasm32, set at all.
+#[uniffi::export]
+pub fn set_http_client_config(config: HttpClientConfig) -> Result<(), WalletKitError> {
+    let invalid = |reason: String| WalletKitError::InvalidInput {
+        attribute: "http_client_config".to_string(),
+        reason,
+    };
+    if config.timeout_ms == 0 {
+        return Err(invalid("timeout must be positive".to_string()));
+    }
+    let _rebuild = REBUILD.lock().unwrap_or_else(PoisonError::into_inner);
+    let client = build_client(&config).map_err(invalid)?;
+    *CONFIG.write().unwrap_or_else(PoisonError::into_inner) = config;
+    *CLIENT.write().unwrap_or_else(PoisonError::into_inner) = Some(client);
+    Ok(())
+}
+
+/// Returns the shared client, building it if needed.
+pub(crate) fn shared_client() -> reqwest::Client {
+    // the slots are only ever replaced whole, so a panic cannot corrupt them
+    if let Some(client) = CLIENT
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+    {
+        return client.clone();
+    }
+    let _rebuild = REBUILD.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut slot = CLIENT.write().unwrap_or_else(PoisonError::into_inner);
+    slot.get_or_insert_with(|| {
+        // the setters install a client, so this is the default configuration,
+        // which only fails to build where `reqwest::Client::new` would panic
+        build_client(&config()).expect("default HTTP client builds")
+    })
+    .clone()
+}
+
+/// Rebuilds the shared client with the current settings after `update`
+/// changed the pins, installing it only if it builds.
+///
+/// # Errors
+/// Returns the build error, after passing what `update` returned to `undo`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn rebuild_shared_client<T>(
+    update: impl FnOnce() -> T,
+    undo: impl FnOnce(T),
+) -> Result<(), String> {
+    let _rebuild = REBUILD.lock().unwrap_or_else(PoisonError::into_inner);
+    let previous = update();
+    match build_client(&config()) {
+        Ok(client) => {
+            *CLIENT.write().unwrap_or_else(PoisonError::into_inner) = Some(client);
+            Ok(())
+        }
+        Err(err) => {
+            undo(previous);
+            Err(err)
+        }
+    }
+}
+
+/// Returns the current settings.
+pub(crate) fn config() -> HttpClientConfig {
+    CONFIG
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+impl HttpClientConfig {
+    /// Timeout of each attempt.
+    pub(crate) const fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+/// An HTTP header.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct HttpHeader {
+    /// The header name, lowercase.
+    pub name: String,
+    /// The header value.
+    pub value: String,
+}
+
+/// A request for an [`HttpTransport`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct HttpRequest {
+    /// The method, e.g. `GET`.
+    pub method: String,
+    /// The absolute URL.
+    pub url: String,
+    /// The headers, including `User-Agent`.
+    pub headers: Vec<HttpHeader>,
+    /// The body, if any.
+    pub body: Option<Vec<u8>>,
+    /// How long the transport may take, in milliseconds.
+    pub timeout_ms: Option<u64>,
+}
+
+/// A response from an [`HttpTransport`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct HttpResponse {
+    /// The status code.
+    pub status: u16,
+    /// The headers.
+    pub headers: Vec<HttpHeader>,
+    /// The whole body.
+    pub body: Vec<u8>,
+}
+
+/// Sends `WalletKit`'s own HTTP requests through the host's HTTP stack, see
+/// [`set_http_transport`].
+#[cfg(not(target_arch = "wasm32"))]
+#[uniffi::export(with_foreign)]
+#[async_trait::async_trait]
+pub trait HttpTransport: Send + Sync {
+    /// Sends `request` and returns the response, whatever its status.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WalletKitError::NetworkError`] if no response was received,
+    /// e.g. on a timeout or connection failure; the request is then retried.
+    /// Any other error fails the request without retrying.
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, WalletKitError>;
+}
+
+/// The installed transport, `None` for the shared client.
+#[cfg(not(target_arch = "wasm32"))]
+static TRANSPORT: RwLock<Option<Arc<dyn HttpTransport>>> = RwLock::new(None);
+
+/// Sends `WalletKit`'s own HTTP requests through `transport` from now on, or
+/// through the shared client again if `None`.
+///
+/// The transport is responsible for TLS, including any certificate pinning;
+/// the pins of `set_tls_pins` and the proxy of [`set_http_client_config`] do
+/// not apply to it.
+#[cfg(not(target_arch = "wasm32"))]
+#[uniffi::export]
+pub fn set_http_transport(transport: Option<Arc<dyn HttpTransport>>) {
+    *TRANSPORT.write().unwrap_or_else(PoisonError::into_inner) = transport;
+}
+
+/// Returns the installed transport, if any.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn transport() -> Option<Arc<dyn HttpTransport>> {
+    TRANSPORT
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Sends `request` through `transport`.
+///
+/// # Errors
+///
+/// Returns the transport's error, or [`WalletKitError::Generic`] if the
+/// request has a streaming body or the response is malformed.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn send_via(
+    transport: &dyn HttpTransport,
+    request: reqwest::Request,
+) -> Result<reqwest::Response, WalletKitError> {
+    let to_header =
+        |(name, value): (&http::HeaderName, &http::HeaderValue)| HttpHeader {
+            name: name.as_str().to_string(),
+            value: String::from_utf8_lossy(value.as_bytes()).into_owned(),
+        };
+    let body = match request.body() {
+        None => None,
+        Some(body) => Some(
+            body.as_bytes()
+                .ok_or_else(|| WalletKitError::Generic {
+                    error:
+                        "streaming request bodies cannot go through an HTTP transport"
+                            .to_string(),
+                })?
+                .to_vec(),
+        ),
+    };
+    let response = transport
+        .send(HttpRequest {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers: request.headers().iter().map(to_header).collect(),
+            body,
+            timeout_ms: request
+                .timeout()
+                .map(|timeout| u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX)),
+        })
+        .await?;
+
+    let malformed = |error: http::Error| WalletKitError::Generic {
+        error: format!("malformed response from HTTP transport: {error}"),
+    };
+    let mut builder = http::Response::builder().status(response.status);
+    for header in response.headers {
+        builder = builder.header(header.name, header.value);
+    }
+    let response = builder.body(response.body).map_err(malformed)?;
+    Ok(reqwest::Response::from(response))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn build_client(config: &HttpClientConfig) -> Result<reqwest::Client, String> {
+    let mut builder = crate::tls_pinning::client_builder()
+        .pool_max_idle_per_host(config.max_idle_connections_per_host as usize);
+    if let Some(proxy_url) = &config.proxy_url {
+        let proxy = reqwest::Proxy::all(proxy_url)
+            .map_err(|err| format!("invalid proxy URL: {err}"))?;
+        builder = builder.proxy(proxy);
+    }
+    builder.build().map_err(|err| err.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn build_client(config: &HttpClientConfig) -> Result<reqwest::Client, String> {
+    if config.proxy_url.is_some() {
+        return Err("proxies are not supported on wasm32".to_string());
+    }
+    Ok(reqwest::Client::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_client_config() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        assert!(matches!(
+            set_http_client_config(HttpClientConfig {
+                timeout_ms: 0,
+                ..HttpClientConfig::default()
+            }),
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            set_http_client_config(HttpClientConfig {
+                proxy_url: Some("not a url".to_string()),
+                ..HttpClientConfig::default()
+            }),
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+
+        let config = HttpClientConfig {
+            timeout_ms: 7000,
+            max_retries: 1,
+            proxy_url: Some("http://127.0.0.1:3128".to_string()),
+            max_idle_connections_per_host: 2,
+        };
+        assert!(build_client(&config).is_ok());
+        assert_eq!(config.timeout(), Duration::from_secs(7));
+    }
+    struct EchoTransport(std::sync::Mutex<Vec<HttpRequest>>);
+
+    #[async_trait::async_trait]
+    impl HttpTransport for EchoTransport {
+        async fn send(
+            &self,
+            request: HttpRequest,
+        ) -> Result<HttpResponse, WalletKitError> {
+            let body = request.body.clone().unwrap_or_default();
+            self.0.lock().unwrap().push(request);
+            Ok(HttpResponse {
+                status: 201,
+                headers: vec![HttpHeader {
+                    name: "x-echo".to_string(),
+                    value: "1".to_string(),
+                }],
+                body,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_via_transport() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let transport = EchoTransport(std::sync::Mutex::new(Vec::new()));
+        let request = reqwest::Client::new()
+            .post("https://example.com/echo")
+            .header("User-Agent", "walletkit-test")
+            .timeout(Duration::from_secs(2))
+            .body("hello")
+            .build()
+            .unwrap();
+
+        let response = send_via(&transport, request).await.expect("send");
+        assert_eq!(response.status().as_u16(), 201);
+        assert_eq!(response.headers()["x-echo"], "1");
+        assert_eq!(response.text().await.unwrap(), "hello");
+
+        let sent = transport.0.lock().unwrap().pop().expect("request sent");
+        assert_eq!(sent.method, "POST");
+        assert_eq!(sent.url, "https://example.com/echo");
+        assert_eq!(sent.timeout_ms, Some(2000));
+        assert!(sent
+            .headers
+            .iter()
+            .any(|header| header.name == "user-agent"
+                && header.value == "walletkit-test"));
+    }
+}
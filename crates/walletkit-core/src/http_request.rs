@@ -4,6 +4,7 @@ use backon::{ExponentialBuilder, Retryable};
 use reqwest::{Method, RequestBuilder, Response};
 
 use crate::error::WalletKitError;
+use crate::runtime::compat;
 
 /// A simple wrapper on an HTTP client for making requests. Sets sensible defaults such as timeouts,
 /// user-agent & ensuring HTTPS, and applies retry middleware for transient failures.
@@ -15,9 +16,22 @@ pub struct Request {
 }
 
 impl Request {
-    /// Initializes a new `Request` instance.
+    /// Initializes a new `Request` instance on the shared client, with the
+    /// timeout and retries of [`crate::http_client::set_http_client_config`].
     pub(crate) fn new(user_agent: String) -> Self {
-        let client = reqwest::Client::new();
+        let config = crate::http_client::config();
+        Self {
+            timeout: config.timeout(),
+            max_retries: config.max_retries,
+            ..Self::with_client(crate::http_client::shared_client(), user_agent)
+        }
+    }
+
+    /// Initializes a new `Request` instance sending through `client`.
+    pub(crate) const fn with_client(
+        client: reqwest::Client,
+        user_agent: String,
+    ) -> Self {
         let timeout = Duration::from_secs(5);
         let max_retries = 3; // total attempts = 4
         Self {
@@ -51,12 +65,22 @@ impl Request {
     }
 
     /// Handles sending a request built by `req`/`get`/`post` with retries for transient failures.
+    #[cfg_attr(
+        feature = "tracing-spans",
+        tracing::instrument(
+            target = "walletkit",
+            name = "http_request",
+            skip_all,
+            fields(url = tracing::field::Empty),
+            err(Display)
+        )
+    )]
     pub(crate) async fn handle(
         &self,
         request_builder: RequestBuilder,
     ) -> Result<Response, WalletKitError> {
         if request_builder.try_clone().is_none() {
-            return execute_request_builder(request_builder)
+            return compat(execute_request_builder(request_builder))
                 .await
                 .map_err(Into::into);
         }
@@ -68,14 +92,17 @@ impl Request {
 
         let template = request_builder;
 
-        (|| async {
-            let request_builder = template.try_clone().expect(
-                "request_builder must be cloneable after initial handle() guard",
-            );
-            execute_request_builder(request_builder).await
-        })
-        .retry(backoff)
-        .when(|err: &RequestHandleError| err.is_retryable())
+        // backon sleeps between attempts on tokio's timer
+        compat(
+            (move || {
+                let request_builder = template.try_clone().expect(
+                    "request_builder must be cloneable after initial handle() guard",
+                );
+                execute_request_builder(request_builder)
+            })
+            .retry(backoff)
+            .when(|err: &RequestHandleError| err.is_retryable()),
+        )
         .await
         .map_err(Into::into)
     }
@@ -87,6 +114,8 @@ struct RequestHandleError {
     status: Option<u16>,
     error: String,
     retryable: bool,
+    /// The host whose TLS pins the server's certificates did not match.
+    pin_mismatch: Option<String>,
 }
 
 impl RequestHandleError {
@@ -96,6 +125,7 @@ impl RequestHandleError {
             status,
             error,
             retryable: true,
+            pin_mismatch: None,
         }
     }
 
@@ -105,6 +135,7 @@ impl RequestHandleError {
             status,
             error,
             retryable: false,
+            pin_mismatch: None,
         }
     }
 
@@ -115,6 +146,9 @@ impl RequestHandleError {
 
 impl From<RequestHandleError> for WalletKitError {
     fn from(value: RequestHandleError) -> Self {
+        if let Some(host) = value.pin_mismatch {
+            return Self::TlsPinMismatch { host };
+        }
         Self::NetworkError {
             url: value.url,
             status: value.status,
@@ -138,20 +172,44 @@ async fn execute_request_builder(
         )
     })?;
     let url = request.url().to_string();
-
-    match client.execute(request).await {
-        Ok(resp) => {
-            let status = resp.status().as_u16();
-            if status == 429 || (500..600).contains(&status) {
-                return Err(RequestHandleError::retryable(
+    #[cfg(feature = "tracing-spans")]
+    tracing::Span::current().record("url", url.as_str());
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(transport) = crate::http_client::transport() {
+        return match crate::http_client::send_via(transport.as_ref(), request).await {
+            Ok(resp) => check_status(url, resp),
+            Err(WalletKitError::NetworkError { error, .. }) => {
+                Err(RequestHandleError::retryable(
                     url,
-                    Some(status),
-                    format!("request error with bad status code {status}"),
-                ));
+                    None,
+                    format!("transport error: {error}"),
+                ))
             }
-            Ok(resp)
-        }
+            Err(err) => Err(RequestHandleError::permanent(
+                url,
+                None,
+                format!("transport failed: {err}"),
+            )),
+        };
+    }
+
+    match client.execute(request).await {
+        Ok(resp) => check_status(url, resp),
         Err(err) => {
+            // a pin mismatch surfaces as a connect error but will not go away on retry
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(host) = crate::tls_pinning::pin_mismatch_host(&err) {
+                return Err(RequestHandleError {
+                    pin_mismatch: Some(host),
+                    ..RequestHandleError::permanent(
+                        url,
+                        None,
+                        format!("request failed: {err}"),
+                    )
+                });
+            }
+
             // NOTE: WASM reqwest uses a fetch-like API, which doesn't expose enough detail
             //       to classify failures as connection errors.
             #[cfg(not(target_arch = "wasm32"))]
@@ -174,3 +232,16 @@ async fn execute_request_builder(
         }
     }
 }
+
+/// Marks rate-limited and server-error responses as retryable.
+fn check_status(url: String, resp: Response) -> Result<Response, RequestHandleError> {
+    let status = resp.status().as_u16();
+    if status == 429 || (500..600).contains(&status) {
+        return Err(RequestHandleError::retryable(
+            url,
+            Some(status),
+            format!("request error with bad status code {status}"),
+        ));
+    }
+    Ok(resp)
+}
@@ -0,0 +1,527 @@
+//! Client-side pre-validation of platform attestation tokens.
+//!
+//! Issuers require an attestation token proving that a request originates from
+//! a genuine app build (Apple App Attest on iOS, Google Play Integrity on Android).
+//! The token is bound to the issuer's challenge through a value that walletkit
+//! computes ([`app_attest_client_data_hash`], [`play_integrity_request_hash`])
+//! and the host hands to the platform API. [`AttestationToken::parse`] checks
+//! the token's structure, its age and that the value embedded in it matches
+//! the challenge, so that obviously-invalid tokens are rejected before the
+//! user goes through a potentially long issuance flow. The signatures are
+//! verified by the issuer, which holds the keys.
+
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE, URL_SAFE_NO_PAD},
+    Engine,
+};
+use ciborium::Value as CborValue;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use crate::error::WalletKitError;
+
+/// HTTP header used to forward the attestation token to the issuer.
+pub const ATTESTATION_HEADER: &str = "X-Attestation-Token";
+
+/// Maximum age of a Play Integrity verdict, counted from its
+/// `requestDetails.timestampMillis`.
+const PLAY_INTEGRITY_MAX_AGE_SECONDS: u64 = 600;
+
+/// Minimum length of `WebAuthn`-style authenticator data
+/// (`rpIdHash` (32) || `flags` (1) || `counter` (4)).
+const APP_ATTEST_MIN_AUTHENTICATOR_DATA_LEN: usize = 37;
+
+/// Returns the `clientDataHash` to pass to App Attest's `generateAssertion`
+/// for the issuer's `challenge`: `SHA-256(challenge)`.
+#[must_use]
+#[uniffi::export]
+pub fn app_attest_client_data_hash(challenge: &[u8]) -> Vec<u8> {
+    Sha256::digest(challenge).to_vec()
+}
+
+/// Returns the value to request a Play Integrity token with for the issuer's
+/// `challenge`.
+///
+/// This is the web-safe base64 encoding of `SHA-256(challenge)`. Pass it as
+/// `requestHash` to a standard request or as `nonce` to a classic one.
+#[must_use]
+#[uniffi::export]
+pub fn play_integrity_request_hash(challenge: &[u8]) -> String {
+    URL_SAFE.encode(Sha256::digest(challenge))
+}
+
+/// Platform format of an [`AttestationToken`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum AttestationFormat {
+    /// Apple App Attest assertion (CBOR).
+    AppAttestAssertion,
+    /// Google Play Integrity verdict (compact JWS).
+    PlayIntegrity,
+}
+
+/// A structurally validated platform attestation token.
+///
+/// Constructed through [`AttestationToken::parse`], which rejects tokens that are
+/// malformed, expired or bound to another challenge.
+#[derive(Debug, Clone, uniffi::Object)]
+pub struct AttestationToken {
+    format: AttestationFormat,
+    raw: Vec<u8>,
+    /// When the platform issued the token, in seconds since the UNIX epoch,
+    /// if the format carries it.
+    issued_at: Option<u64>,
+}
+
+#[uniffi::export]
+impl AttestationToken {
+    /// Parses and pre-validates an attestation token for the issuer's
+    /// `challenge`.
+    ///
+    /// Supported formats:
+    /// - **App Attest**: a CBOR map with the `signature` and
+    ///   `authenticatorData` of the assertion returned by `generateAssertion`,
+    ///   and the `clientDataHash` it was called with. That must be
+    ///   [`app_attest_client_data_hash`] of `challenge`.
+    /// - **Play Integrity**: the verdict as a compact JWS. Its
+    ///   `requestDetails.requestHash` (standard requests) or
+    ///   `requestDetails.nonce` (classic requests) must be
+    ///   [`play_integrity_request_hash`] of `challenge`, and its
+    ///   `requestDetails.timestampMillis` no more than 10 minutes before `now`.
+    ///   The encrypted integrity token cannot be checked on-device and is
+    ///   rejected.
+    ///
+    /// # Arguments
+    /// * `bytes` - The token.
+    /// * `challenge` - The issuer's challenge the token must be bound to.
+    /// * `now` - Current time in seconds since the UNIX epoch.
+    ///
+    /// # Errors
+    /// - [`WalletKitError::AttestationUnknownFormat`] if the token is in neither format.
+    /// - [`WalletKitError::AttestationExpired`] if the verdict is too old.
+    /// - [`WalletKitError::AttestationNonceMismatch`] if the token is bound to
+    ///   another challenge.
+    /// - [`WalletKitError::InvalidInput`] if the token is in a known format but
+    ///   malformed, or an encrypted Play Integrity token.
+    #[uniffi::constructor]
+    pub fn parse(
+        bytes: Vec<u8>,
+        challenge: &[u8],
+        now: u64,
+    ) -> Result<Self, WalletKitError> {
+        if let Some(jws) = as_compact_jws(&bytes) {
+            let issued_at = validate_play_integrity(jws, challenge, now)?;
+            return Ok(Self {
+                format: AttestationFormat::PlayIntegrity,
+                raw: bytes,
+                issued_at: Some(issued_at),
+            });
+        }
+        if is_compact_jwe(&bytes) {
+            return Err(invalid_token(
+                "encrypted Play Integrity tokens cannot be checked, pass the verdict",
+            ));
+        }
+
+        if let Ok(CborValue::Map(entries)) =
+            ciborium::from_reader::<CborValue, _>(bytes.as_slice())
+        {
+            validate_app_attest(&entries, challenge)?;
+            return Ok(Self {
+                format: AttestationFormat::AppAttestAssertion,
+                raw: bytes,
+                issued_at: None,
+            });
+        }
+
+        Err(WalletKitError::AttestationUnknownFormat)
+    }
+
+    /// Returns the platform format of the token.
+    #[must_use]
+    pub const fn format(&self) -> AttestationFormat {
+        self.format
+    }
+
+    /// Returns when the platform issued the token (seconds since the UNIX
+    /// epoch), if the format carries it. App Attest assertions do not.
+    #[must_use]
+    pub const fn issued_at(&self) -> Option<u64> {
+        self.issued_at
+    }
+}
+
+impl AttestationToken {
+    /// Re-checks freshness right before the token is sent to an issuer.
+    pub(crate) const fn ensure_fresh(&self, now: u64) -> Result<(), WalletKitError> {
+        match self.issued_at {
+            Some(issued_at) => ensure_not_expired(issued_at, now),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the value sent in the [`ATTESTATION_HEADER`] header.
+    pub(crate) fn header_value(&self) -> String {
+        match self.format {
+            // `as_compact_jws` guarantees the raw bytes are ASCII
+            AttestationFormat::PlayIntegrity => {
+                String::from_utf8_lossy(&self.raw).trim().to_string()
+            }
+            AttestationFormat::AppAttestAssertion => STANDARD.encode(&self.raw),
+        }
+    }
+}
+
+/// Splits `bytes` into its base64url segments if it is an ASCII token of
+/// dot-separated, non-empty segments.
+fn compact_segments(bytes: &[u8]) -> Option<(&str, usize)> {
+    let token = std::str::from_utf8(bytes).ok()?.trim();
+    let is_base64url = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    let segments = token.split('.');
+    let count = segments.clone().count();
+    segments
+        .into_iter()
+        .all(|s| !s.is_empty() && s.chars().all(is_base64url))
+        .then_some((token, count))
+}
+
+/// Returns the token as a string if it looks like a compact JWS
+/// (`header.payload.signature`).
+fn as_compact_jws(bytes: &[u8]) -> Option<&str> {
+    compact_segments(bytes)
+        .filter(|(_, count)| *count == 3)
+        .map(|(token, _)| token)
+}
+
+/// Whether the token looks like a compact JWE
+/// (`header.encrypted_key.iv.ciphertext.tag`).
+fn is_compact_jwe(bytes: &[u8]) -> bool {
+    compact_segments(bytes).is_some_and(|(_, count)| count == 5)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlayIntegrityPayload {
+    request_details: PlayIntegrityRequestDetails,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlayIntegrityRequestDetails {
+    /// Set by standard requests.
+    request_hash: Option<String>,
+    /// Set by classic requests.
+    nonce: Option<String>,
+    /// Google encodes this as a string, but numbers are accepted as well.
+    timestamp_millis: serde_json::Value,
+}
+
+/// Validates a Play Integrity verdict and returns its issuance time in
+/// seconds.
+fn validate_play_integrity(
+    jws: &str,
+    challenge: &[u8],
+    now: u64,
+) -> Result<u64, WalletKitError> {
+    let segments: Vec<&str> = jws.split('.').collect();
+    let [header, payload, signature] = segments.as_slice() else {
+        return Err(WalletKitError::AttestationUnknownFormat);
+    };
+
+    let header: serde_json::Value = serde_json::from_slice(&decode_b64url(header)?)
+        .map_err(|e| invalid_token(format!("invalid JWS header: {e}")))?;
+    if header
+        .get("alg")
+        .and_then(serde_json::Value::as_str)
+        .is_none()
+    {
+        return Err(invalid_token("JWS header is missing `alg`"));
+    }
+    if decode_b64url(signature)?.is_empty() {
+        return Err(invalid_token("JWS signature is empty"));
+    }
+
+    let payload: PlayIntegrityPayload =
+        serde_json::from_slice(&decode_b64url(payload)?)
+            .map_err(|e| invalid_token(format!("invalid verdict payload: {e}")))?;
+    let details = payload.request_details;
+
+    let timestamp_millis = match &details.timestamp_millis {
+        serde_json::Value::String(s) => s.parse::<u64>().ok(),
+        serde_json::Value::Number(n) => n.as_u64(),
+        _ => None,
+    }
+    .ok_or_else(|| invalid_token("invalid `timestampMillis`"))?;
+    let issued_at = timestamp_millis / 1000;
+    ensure_not_expired(issued_at, now)?;
+
+    let bound_to = details
+        .request_hash
+        .or(details.nonce)
+        .ok_or_else(|| invalid_token("missing `requestHash` and `nonce`"))?;
+    let expected = Sha256::digest(challenge);
+    if !bool::from(decode_b64url(&bound_to)?.ct_eq(expected.as_slice())) {
+        return Err(WalletKitError::AttestationNonceMismatch);
+    }
+
+    Ok(issued_at)
+}
+
+/// Validates an App Attest assertion (CBOR map).
+fn validate_app_attest(
+    entries: &[(CborValue, CborValue)],
+    challenge: &[u8],
+) -> Result<(), WalletKitError> {
+    let field = |name: &str| {
+        entries
+            .iter()
+            .find(|(key, _)| key.as_text() == Some(name))
+            .and_then(|(_, value)| value.as_bytes())
+            .ok_or_else(|| invalid_token(format!("missing `{name}`")))
+    };
+
+    if field("signature")?.is_empty() {
+        return Err(invalid_token("`signature` is empty"));
+    }
+    if field("authenticatorData")?.len() < APP_ATTEST_MIN_AUTHENTICATOR_DATA_LEN {
+        return Err(invalid_token("`authenticatorData` is too short"));
+    }
+
+    let client_data_hash = field("clientDataHash")?;
+    let expected = app_attest_client_data_hash(challenge);
+    if !bool::from(client_data_hash.ct_eq(&expected)) {
+        return Err(WalletKitError::AttestationNonceMismatch);
+    }
+    Ok(())
+}
+
+const fn ensure_not_expired(issued_at: u64, now: u64) -> Result<(), WalletKitError> {
+    if now > issued_at.saturating_add(PLAY_INTEGRITY_MAX_AGE_SECONDS) {
+        return Err(WalletKitError::AttestationExpired);
+    }
+    Ok(())
+}
+
+/// Decodes web-safe base64, tolerating trailing padding.
+fn decode_b64url(input: &str) -> Result<Vec<u8>, WalletKitError> {
+    URL_SAFE_NO_PAD
+        .decode(input.trim_end_matches('='))
+        .map_err(|e| invalid_token(format!("invalid base64: {e}")))
+}
+
+fn invalid_token(reason: impl Into<String>) -> WalletKitError {
+    WalletKitError::InvalidInput {
+        attribute: "attestation_token".to_string(),
+        reason: reason.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+    const CHALLENGE: &[u8] = b"issuer challenge";
+
+    fn play_integrity_fixture(
+        header: &serde_json::Value,
+        request_details: &serde_json::Value,
+    ) -> Vec<u8> {
+        let payload = serde_json::json!({ "requestDetails": request_details });
+        let segments = [
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(payload.to_string()),
+            URL_SAFE_NO_PAD.encode([0xAB; 64]),
+        ];
+        segments.join(".").into_bytes()
+    }
+
+    fn play_integrity_verdict(challenge: &[u8], issued_at: u64) -> Vec<u8> {
+        play_integrity_fixture(
+            &serde_json::json!({ "alg": "ES256" }),
+            &serde_json::json!({
+                "requestHash": play_integrity_request_hash(challenge),
+                "timestampMillis": (issued_at * 1000).to_string(),
+            }),
+        )
+    }
+
+    fn app_attest_fixture(authenticator_data_len: usize, challenge: &[u8]) -> Vec<u8> {
+        let assertion = CborValue::Map(vec![
+            (
+                CborValue::Text("signature".to_string()),
+                CborValue::Bytes(vec![0x30; 70]),
+            ),
+            (
+                CborValue::Text("authenticatorData".to_string()),
+                CborValue::Bytes(vec![0x01; authenticator_data_len]),
+            ),
+            (
+                CborValue::Text("clientDataHash".to_string()),
+                CborValue::Bytes(app_attest_client_data_hash(challenge)),
+            ),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&assertion, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_request_values_are_computed_from_the_challenge() {
+        // SHA-256("abc") from FIPS 180-2, appendix B.1
+        assert_eq!(
+            hex::encode(app_attest_client_data_hash(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            play_integrity_request_hash(b"abc"),
+            "ungWv48Bz-pBQUDeXa4iI7ADYaOWF3qctBD_YfIAFa0="
+        );
+    }
+
+    #[test]
+    fn test_parse_play_integrity() {
+        let verdict = play_integrity_verdict(CHALLENGE, NOW - 60);
+        let token = AttestationToken::parse(verdict.clone(), CHALLENGE, NOW).unwrap();
+        assert_eq!(token.format(), AttestationFormat::PlayIntegrity);
+        assert_eq!(token.issued_at(), Some(NOW - 60));
+        assert_eq!(token.header_value().into_bytes(), verdict);
+
+        // classic requests carry the value as `nonce`, with a numeric timestamp
+        let classic = play_integrity_fixture(
+            &serde_json::json!({ "alg": "ES256" }),
+            &serde_json::json!({
+                "nonce": play_integrity_request_hash(CHALLENGE),
+                "timestampMillis": NOW * 1000,
+            }),
+        );
+        let token = AttestationToken::parse(classic, CHALLENGE, NOW).unwrap();
+        assert_eq!(token.issued_at(), Some(NOW));
+    }
+
+    #[test]
+    fn test_play_integrity_expiry_is_based_on_the_verdict_timestamp() {
+        // a verdict issued long ago is rejected even if it was never parsed
+        let stale =
+            play_integrity_verdict(CHALLENGE, NOW - PLAY_INTEGRITY_MAX_AGE_SECONDS - 1);
+        let err = AttestationToken::parse(stale, CHALLENGE, NOW).unwrap_err();
+        assert!(matches!(err, WalletKitError::AttestationExpired));
+
+        // the clock starts when the verdict was issued, not when it was parsed
+        let verdict = play_integrity_verdict(CHALLENGE, NOW - 300);
+        let token = AttestationToken::parse(verdict, CHALLENGE, NOW).unwrap();
+        assert!(token
+            .ensure_fresh(NOW - 300 + PLAY_INTEGRITY_MAX_AGE_SECONDS)
+            .is_ok());
+        assert!(matches!(
+            token.ensure_fresh(NOW - 300 + PLAY_INTEGRITY_MAX_AGE_SECONDS + 1),
+            Err(WalletKitError::AttestationExpired)
+        ));
+    }
+
+    #[test]
+    fn test_parse_play_integrity_rejects_tampered_nonce() {
+        let verdict = play_integrity_verdict(b"another challenge", NOW);
+        let err = AttestationToken::parse(verdict, CHALLENGE, NOW).unwrap_err();
+        assert!(matches!(err, WalletKitError::AttestationNonceMismatch));
+    }
+
+    #[test]
+    fn test_parse_play_integrity_malformed() {
+        let details = serde_json::json!({
+            "requestHash": play_integrity_request_hash(CHALLENGE),
+            "timestampMillis": (NOW * 1000).to_string(),
+        });
+        let missing_alg = play_integrity_fixture(&serde_json::json!({}), &details);
+        let missing_timestamp = play_integrity_fixture(
+            &serde_json::json!({ "alg": "ES256" }),
+            &serde_json::json!({ "requestHash": play_integrity_request_hash(CHALLENGE) }),
+        );
+        let missing_nonce = play_integrity_fixture(
+            &serde_json::json!({ "alg": "ES256" }),
+            &serde_json::json!({ "timestampMillis": (NOW * 1000).to_string() }),
+        );
+        for token in [missing_alg, missing_timestamp, missing_nonce] {
+            let err = AttestationToken::parse(token, CHALLENGE, NOW).unwrap_err();
+            assert!(matches!(err, WalletKitError::InvalidInput { .. }));
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_encrypted_play_integrity_token() {
+        // the integrity token is encrypted for the issuer, its nonce cannot be read
+        let segments = [
+            URL_SAFE_NO_PAD.encode(br#"{"alg":"A256KW","enc":"A256GCM"}"#),
+            URL_SAFE_NO_PAD.encode([0x11; 40]),
+            URL_SAFE_NO_PAD.encode([0x22; 12]),
+            URL_SAFE_NO_PAD.encode([0x33; 512]),
+            URL_SAFE_NO_PAD.encode([0x44; 16]),
+        ];
+        let token = segments.join(".").into_bytes();
+        let err = AttestationToken::parse(token, CHALLENGE, NOW).unwrap_err();
+        assert!(matches!(err, WalletKitError::InvalidInput { .. }));
+    }
+
+    #[test]
+    fn test_parse_app_attest() {
+        let bytes = app_attest_fixture(37, CHALLENGE);
+        let token = AttestationToken::parse(bytes.clone(), CHALLENGE, NOW).unwrap();
+        assert_eq!(token.format(), AttestationFormat::AppAttestAssertion);
+        assert_eq!(token.issued_at(), None);
+        assert_eq!(STANDARD.decode(token.header_value()).unwrap(), bytes);
+        assert!(token
+            .ensure_fresh(NOW + PLAY_INTEGRITY_MAX_AGE_SECONDS + 1)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_parse_app_attest_rejects_tampered_nonce() {
+        let bytes = app_attest_fixture(37, b"another challenge");
+        let err = AttestationToken::parse(bytes, CHALLENGE, NOW).unwrap_err();
+        assert!(matches!(err, WalletKitError::AttestationNonceMismatch));
+    }
+
+    #[test]
+    fn test_parse_app_attest_malformed() {
+        let err =
+            AttestationToken::parse(app_attest_fixture(36, CHALLENGE), CHALLENGE, NOW)
+                .unwrap_err();
+        assert!(matches!(err, WalletKitError::InvalidInput { .. }));
+
+        let assertion = CborValue::Map(vec![(
+            CborValue::Text("signature".to_string()),
+            CborValue::Bytes(vec![0x30; 70]),
+        )]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&assertion, &mut bytes).unwrap();
+        let err = AttestationToken::parse(bytes, CHALLENGE, NOW).unwrap_err();
+        assert!(matches!(err, WalletKitError::InvalidInput { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_issuer_refuses_expired_token() {
+        // parsed while fresh, sent long after the verdict was issued
+        let verdict = play_integrity_verdict(CHALLENGE, NOW);
+        let token = AttestationToken::parse(verdict, CHALLENGE, NOW).unwrap();
+        let issuer = crate::issuers::TfhNfcIssuer::with_base_url(
+            "http://127.0.0.1:1",
+            "WorldApp/1.0.0 test/1.0.0".to_string(),
+        );
+
+        let err = issuer
+            .refresh_nfc_credential_with_attestation("{}", &token, HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, WalletKitError::AttestationExpired));
+    }
+
+    #[test]
+    fn test_parse_unknown_format() {
+        for bytes in [b"not a token".to_vec(), vec![0xFF, 0x00, 0x13], Vec::new()] {
+            let err = AttestationToken::parse(bytes, CHALLENGE, NOW).unwrap_err();
+            assert!(matches!(err, WalletKitError::AttestationUnknownFormat));
+        }
+    }
+}
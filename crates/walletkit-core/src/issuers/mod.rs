@@ -1,9 +1,15 @@
 //! Logic for different specific issuers of Credentials in World ID.
 
+mod attestation;
 mod pop_backend_client;
 mod recovery_bindings_manager;
 mod tfh_nfc;
-pub use tfh_nfc::TfhNfcIssuer;
+pub use tfh_nfc::{NfcRefreshConfig, NfcRefreshError, TfhNfcIssuer};
+
+pub use attestation::{
+    app_attest_client_data_hash, play_integrity_request_hash, AttestationFormat,
+    AttestationToken,
+};
 
 pub use pop_backend_client::PopBackendClient;
 pub use recovery_bindings_manager::RecoveryBinding;
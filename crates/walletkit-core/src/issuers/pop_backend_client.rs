@@ -1,5 +1,6 @@
 use crate::error::WalletKitError;
 use crate::http_request::Request;
+use crate::runtime::compat;
 use reqwest::Method;
 use serde::{Deserialize, Serialize};
 
@@ -76,14 +77,15 @@ impl PopBackendClient {
         challenge: String,
     ) -> Result<(), WalletKitError> {
         let url: String = format!("{}/api/v1/recovery-binding", self.base_url);
-        let response = self
-            .request
-            .post(&url)
-            .json(&request)
-            .header("X-Auth-Signature", security_token)
-            .header("X-Auth-Challenge", challenge)
-            .send()
-            .await?;
+        let response = compat(
+            self.request
+                .post(&url)
+                .json(&request)
+                .header("X-Auth-Signature", security_token)
+                .header("X-Auth-Challenge", challenge)
+                .send(),
+        )
+        .await?;
 
         let response_status = response.status();
         match response_status {
@@ -119,14 +121,15 @@ impl PopBackendClient {
         challenge: String,
     ) -> Result<(), WalletKitError> {
         let url: String = format!("{}/api/v1/recovery-binding", self.base_url);
-        let response = self
-            .request
-            .req(Method::DELETE, url.as_str())
-            .json(&request)
-            .header("X-Auth-Signature", security_token)
-            .header("X-Auth-Challenge", challenge)
-            .send()
-            .await?;
+        let response = compat(
+            self.request
+                .req(Method::DELETE, url.as_str())
+                .json(&request)
+                .header("X-Auth-Signature", security_token)
+                .header("X-Auth-Challenge", challenge)
+                .send(),
+        )
+        .await?;
         let response_status = response.status();
         match response_status {
             reqwest::StatusCode::OK => Ok(()),
@@ -158,7 +161,7 @@ impl PopBackendClient {
     /// * [`WalletKitError::SerializationError`] — response body is not valid JSON.
     pub async fn get_challenge(&self) -> Result<String, WalletKitError> {
         let url = format!("{}/api/v1/challenge", self.base_url);
-        let response = self.request.get(url.as_str()).send().await?;
+        let response = compat(self.request.get(url.as_str()).send()).await?;
 
         let response_status = response.status();
         if !response_status.is_success() {
@@ -199,7 +202,7 @@ impl PopBackendClient {
             "{}/api/v1/recovery-binding?leafIndex={leaf_index}",
             self.base_url
         );
-        let response = self.request.get(url.as_str()).send().await?;
+        let response = compat(self.request.get(url.as_str()).send()).await?;
 
         let status = response.status();
         if status.is_success() {
@@ -1,10 +1,15 @@
 //! TFH NFC credential issuer (passport, eID, MNC).
+use super::attestation::{AttestationToken, ATTESTATION_HEADER};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::runtime::sleep;
 use crate::Credential;
 use crate::{error::WalletKitError, http_request::Request, Environment};
 
 use base64::{engine::general_purpose::STANDARD, Engine};
 use serde::Deserialize;
 use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
 
 /// Response from NFC refresh endpoint
 #[derive(Debug, Clone, Deserialize)]
@@ -26,6 +31,187 @@ struct NfcErrorBody {
 
 const NFC_NON_RETRYABLE_ERRORS: &[&str] = &["document_expired"];
 
+/// Error codes meaning the service cannot process this document type; the
+/// request may succeed with another document type. Only
+/// [`TfhNfcIssuer::refresh_with_retry`] tells them apart;
+/// [`TfhNfcIssuer::refresh_nfc_credential`] reports them as a
+/// [`WalletKitError::NetworkError`] with the response status.
+const NFC_DOCUMENT_NOT_SUPPORTED_ERRORS: &[&str] =
+    &["document_not_supported", "unsupported_document_version"];
+
+/// Field of the refresh request body that selects the document type.
+const NFC_DOCUMENT_TYPE_FIELD: &str = "document_type";
+
+/// Retry policy for [`TfhNfcIssuer::refresh_with_retry`].
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct NfcRefreshConfig {
+    /// Maximum number of retries after a transient failure. Ignored on
+    /// wasm32, which has no timer to wait between retries.
+    pub max_retries: u32,
+    /// Delay between retries after a transient failure, in milliseconds.
+    pub retry_delay_ms: u64,
+    /// Document types to try, in order, when the service does not support the
+    /// one in the request body.
+    pub fallback_doc_types: Vec<String>,
+}
+
+impl Default for NfcRefreshConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            retry_delay_ms: 1_000,
+            fallback_doc_types: Vec::new(),
+        }
+    }
+}
+
+/// Outcome of a failed NFC credential refresh, classified by whether retrying
+/// can help.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum NfcRefreshError {
+    /// The request timed out or the service was temporarily unavailable.
+    /// Transient.
+    #[error("nfc_network_timeout: {error}")]
+    NetworkTimeout {
+        /// Details of the underlying failure
+        error: String,
+    },
+    /// The service does not support the requested document type. Another
+    /// document type may succeed.
+    #[error("nfc_document_not_supported: {error_code}")]
+    DocumentNotSupported {
+        /// The error code from the NFC service
+        error_code: String,
+    },
+    /// The service rejected the document permanently (e.g. expired document).
+    #[error("nfc_rejected: {error_code}")]
+    Rejected {
+        /// The error code from the NFC service (e.g. `document_expired`)
+        error_code: String,
+    },
+    /// The attestation token expired and must be regenerated by the platform.
+    #[error("attestation_expired")]
+    AttestationExpired,
+    /// Any other failure that will not resolve on retry (invalid request,
+    /// unexpected status or malformed response).
+    #[error("nfc_refresh_failed: {error}")]
+    Failed {
+        /// Details of the failure
+        error: String,
+    },
+}
+
+impl NfcRefreshError {
+    /// Whether the same request may succeed if retried.
+    #[must_use]
+    pub const fn is_transient(&self) -> bool {
+        matches!(self, Self::NetworkTimeout { .. })
+    }
+}
+
+impl From<WalletKitError> for NfcRefreshError {
+    fn from(error: WalletKitError) -> Self {
+        match error {
+            WalletKitError::NfcNonRetryable { error_code } => {
+                Self::Rejected { error_code }
+            }
+            WalletKitError::AttestationExpired => Self::AttestationExpired,
+            WalletKitError::NetworkError {
+                status: None | Some(408 | 429 | 500..=599),
+                error,
+                ..
+            }
+            | WalletKitError::Reqwest { error } => Self::NetworkTimeout { error },
+            error => Self::Failed {
+                error: error.to_string(),
+            },
+        }
+    }
+}
+
+/// Returns `request_body` with its document type replaced by `doc_type`.
+fn with_document_type(
+    request_body: &str,
+    doc_type: &str,
+) -> Result<String, NfcRefreshError> {
+    let mut body: serde_json::Value =
+        serde_json::from_str(request_body).map_err(|e| NfcRefreshError::Failed {
+            error: format!("NFC request body is not valid JSON: {e}"),
+        })?;
+    let object = body
+        .as_object_mut()
+        .ok_or_else(|| NfcRefreshError::Failed {
+            error: "NFC request body is not a JSON object".to_string(),
+        })?;
+    object.insert(
+        NFC_DOCUMENT_TYPE_FIELD.to_string(),
+        serde_json::Value::String(doc_type.to_string()),
+    );
+    Ok(body.to_string())
+}
+
+/// A non-success response of the refresh endpoint.
+struct NfcRefreshFailure {
+    url: String,
+    status: u16,
+    body: String,
+}
+
+impl NfcRefreshFailure {
+    /// The service's error code, if the body is an [`NfcErrorBody`].
+    fn error_code(&self) -> Option<String> {
+        serde_json::from_str::<NfcErrorBody>(&self.body)
+            .ok()
+            .map(|parsed| parsed.error)
+    }
+
+    /// The error [`TfhNfcIssuer::refresh_nfc_credential`] reports.
+    fn into_wallet_kit_error(self) -> WalletKitError {
+        match self.error_code() {
+            Some(error_code)
+                if NFC_NON_RETRYABLE_ERRORS.contains(&error_code.as_str()) =>
+            {
+                WalletKitError::NfcNonRetryable { error_code }
+            }
+            _ => WalletKitError::NetworkError {
+                url: self.url,
+                status: Some(self.status),
+                error: format!("NFC refresh failed: {}", self.body),
+            },
+        }
+    }
+}
+
+impl From<NfcRefreshFailure> for NfcRefreshError {
+    fn from(failure: NfcRefreshFailure) -> Self {
+        match failure.error_code() {
+            Some(error_code)
+                if NFC_DOCUMENT_NOT_SUPPORTED_ERRORS.contains(&error_code.as_str()) =>
+            {
+                Self::DocumentNotSupported { error_code }
+            }
+            _ => failure.into_wallet_kit_error().into(),
+        }
+    }
+}
+
+/// Returns `headers` with the attestation header of `attestation`, after
+/// checking that the token is still fresh.
+fn with_attestation_header(
+    mut headers: HashMap<String, String>,
+    attestation: &AttestationToken,
+) -> Result<HashMap<String, String>, WalletKitError> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| WalletKitError::Generic {
+            error: format!("Critical. Unable to determine SystemTime: {e}"),
+        })?
+        .as_secs();
+    attestation.ensure_fresh(now)?;
+    headers.insert(ATTESTATION_HEADER.to_string(), attestation.header_value());
+    Ok(headers)
+}
+
 impl NfcRefreshResultRaw {
     fn parse(&self) -> Result<Credential, WalletKitError> {
         let credential_bytes = STANDARD.decode(&self.credential).map_err(|e| {
@@ -81,8 +267,104 @@ impl TfhNfcIssuer {
         request_body: &str,
         headers: HashMap<String, String>,
     ) -> Result<Credential, WalletKitError> {
-        let url = format!("{}/v2/migrate", self.base_url);
+        self.send_refresh(request_body, headers)
+            .await?
+            .map_err(NfcRefreshFailure::into_wallet_kit_error)
+    }
+
+    /// Refresh an NFC credential, forwarding a pre-validated platform attestation token.
+    ///
+    /// The token's freshness is re-checked right before sending so that a token which
+    /// expired after parsing is never sent to the issuer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WalletKitError::AttestationExpired`] if the token is no longer fresh,
+    /// otherwise the same errors as [`Self::refresh_nfc_credential`].
+    pub async fn refresh_nfc_credential_with_attestation(
+        &self,
+        request_body: &str,
+        attestation: &AttestationToken,
+        headers: HashMap<String, String>,
+    ) -> Result<Credential, WalletKitError> {
+        let headers = with_attestation_header(headers, attestation)?;
+        self.refresh_nfc_credential(request_body, headers).await
+    }
+
+    /// Refresh an NFC credential, retrying transient failures and falling back
+    /// to other document types.
+    ///
+    /// - On [`NfcRefreshError::NetworkTimeout`] the request is retried up to
+    ///   `config.max_retries` times, waiting `config.retry_delay_ms` in between.
+    ///   Not on wasm32, which has no timer to back off with; there the timeout
+    ///   is returned.
+    /// - On [`NfcRefreshError::DocumentNotSupported`] the request is re-sent with
+    ///   the `document_type` field of the body set to the next entry of
+    ///   `config.fallback_doc_types`.
+    /// - Any other error is returned immediately.
+    ///
+    /// # Errors
+    ///
+    /// Returns the last [`NfcRefreshError`] once retries and fallbacks are
+    /// exhausted, or the first permanent error.
+    pub async fn refresh_with_retry(
+        &self,
+        request_body: &str,
+        headers: HashMap<String, String>,
+        attestation: &AttestationToken,
+        config: NfcRefreshConfig,
+    ) -> Result<Credential, NfcRefreshError> {
+        let mut fallback_doc_types = config.fallback_doc_types.iter();
+        let mut body = request_body.to_string();
+        #[cfg_attr(
+            target_arch = "wasm32",
+            expect(unused_mut, reason = "timeouts are not retried on wasm")
+        )]
+        let mut retries = 0;
+
+        loop {
+            let headers = with_attestation_header(headers.clone(), attestation)?;
+            let error = match self.send_refresh(&body, headers).await? {
+                Ok(credential) => return Ok(credential),
+                Err(failure) => NfcRefreshError::from(failure),
+            };
+
+            match &error {
+                #[cfg(not(target_arch = "wasm32"))]
+                NfcRefreshError::NetworkTimeout { .. }
+                    if retries < config.max_retries =>
+                {
+                    retries += 1;
+                    tracing::info!(retry = retries, reason = %error, "retrying NFC refresh");
+                    sleep(Duration::from_millis(config.retry_delay_ms)).await;
+                }
+                NfcRefreshError::DocumentNotSupported { .. } => {
+                    let Some(doc_type) = fallback_doc_types.next() else {
+                        return Err(error);
+                    };
+                    tracing::info!(
+                        retry = retries,
+                        reason = %error,
+                        doc_type,
+                        "retrying NFC refresh with fallback document type"
+                    );
+                    body = with_document_type(request_body, doc_type)?;
+                }
+                _ => return Err(error),
+            }
+        }
+    }
+}
 
+impl TfhNfcIssuer {
+    /// Posts `request_body` to the refresh endpoint. A non-success response
+    /// is returned as the inner error, for the caller to classify.
+    async fn send_refresh(
+        &self,
+        request_body: &str,
+        headers: HashMap<String, String>,
+    ) -> Result<Result<Credential, NfcRefreshFailure>, WalletKitError> {
+        let url = format!("{}/v2/migrate", self.base_url);
         let mut request_builder = self
             .request
             .post(&url)
@@ -95,21 +377,11 @@ impl TfhNfcIssuer {
 
         let status = response.status();
         if !status.is_success() {
-            let error_body = response.text().await.unwrap_or_default();
-
-            if let Ok(parsed) = serde_json::from_str::<NfcErrorBody>(&error_body) {
-                if NFC_NON_RETRYABLE_ERRORS.contains(&parsed.error.as_str()) {
-                    return Err(WalletKitError::NfcNonRetryable {
-                        error_code: parsed.error,
-                    });
-                }
-            }
-
-            return Err(WalletKitError::NetworkError {
+            return Ok(Err(NfcRefreshFailure {
                 url,
-                status: Some(status.as_u16()),
-                error: format!("NFC refresh failed: {error_body}"),
-            });
+                status: status.as_u16(),
+                body: response.text().await.unwrap_or_default(),
+            }));
         }
 
         let refresh_response: NfcRefreshResponse =
@@ -120,7 +392,7 @@ impl TfhNfcIssuer {
                     error: format!("Failed to parse NFC refresh response: {e}"),
                 })?;
 
-        refresh_response.result.parse()
+        refresh_response.result.parse().map(Ok)
     }
 }
 
@@ -192,4 +464,213 @@ mod tests {
         let err = raw.parse().unwrap_err();
         assert!(matches!(err, WalletKitError::SerializationError { .. }));
     }
+
+    fn app_attest_token() -> AttestationToken {
+        let assertion = ciborium::Value::Map(vec![
+            (
+                ciborium::Value::Text("signature".to_string()),
+                ciborium::Value::Bytes(vec![0x30; 70]),
+            ),
+            (
+                ciborium::Value::Text("authenticatorData".to_string()),
+                ciborium::Value::Bytes(vec![0x01; 37]),
+            ),
+            (
+                ciborium::Value::Text("clientDataHash".to_string()),
+                ciborium::Value::Bytes(crate::issuers::app_attest_client_data_hash(
+                    b"challenge",
+                )),
+            ),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&assertion, &mut bytes).unwrap();
+        AttestationToken::parse(bytes, b"challenge", 1_700_000_000).unwrap()
+    }
+
+    fn credential_response() -> String {
+        let credential = serde_json::to_vec(&world_id_core::Credential::new()).unwrap();
+        serde_json::json!({ "result": { "credential": STANDARD.encode(credential) } })
+            .to_string()
+    }
+
+    fn config(max_retries: u32, fallback_doc_types: &[&str]) -> NfcRefreshConfig {
+        NfcRefreshConfig {
+            max_retries,
+            retry_delay_ms: 1,
+            fallback_doc_types: fallback_doc_types
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        }
+    }
+
+    async fn refresh(
+        server: &mockito::Server,
+        config: NfcRefreshConfig,
+    ) -> Result<Credential, NfcRefreshError> {
+        let issuer = TfhNfcIssuer::with_base_url(
+            &server.url(),
+            "WorldApp/1.0.0 test/1.0.0".to_string(),
+        );
+        issuer
+            .refresh_with_retry(
+                r#"{"document_type":"passport","data":"abc"}"#,
+                HashMap::new(),
+                &app_attest_token(),
+                config,
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_refresh_with_retry_recovers_from_timeouts() {
+        let mut server = mockito::Server::new_async().await;
+        let timeouts = server
+            .mock("POST", "/v2/migrate")
+            .match_header(ATTESTATION_HEADER, mockito::Matcher::Any)
+            .with_status(408)
+            .expect(2)
+            .create_async()
+            .await;
+        let success = server
+            .mock("POST", "/v2/migrate")
+            .with_status(200)
+            .with_body(credential_response())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let credential = refresh(&server, config(2, &[])).await.unwrap();
+        assert_eq!(credential.version, world_id_core::Credential::new().version);
+        timeouts.assert_async().await;
+        success.assert_async().await;
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_with_retry_gives_up_after_max_retries() {
+        let mut server = mockito::Server::new_async().await;
+        let timeouts = server
+            .mock("POST", "/v2/migrate")
+            .with_status(408)
+            .expect(3)
+            .create_async()
+            .await;
+
+        let err = refresh(&server, config(2, &[])).await.unwrap_err();
+        assert!(matches!(err, NfcRefreshError::NetworkTimeout { .. }));
+        assert!(err.is_transient());
+        timeouts.assert_async().await;
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_with_retry_falls_back_to_other_doc_types() {
+        let mut server = mockito::Server::new_async().await;
+        let unsupported = server
+            .mock("POST", "/v2/migrate")
+            .match_body(mockito::Matcher::AnyOf(vec![
+                mockito::Matcher::PartialJson(
+                    serde_json::json!({ "document_type": "passport" }),
+                ),
+                mockito::Matcher::PartialJson(
+                    serde_json::json!({ "document_type": "eid" }),
+                ),
+            ]))
+            .with_status(400)
+            .with_body(r#"{"error":"document_not_supported"}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let success = server
+            .mock("POST", "/v2/migrate")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "document_type": "mnc",
+                "data": "abc",
+            })))
+            .with_status(200)
+            .with_body(credential_response())
+            .expect(1)
+            .create_async()
+            .await;
+
+        refresh(&server, config(0, &["eid", "mnc"])).await.unwrap();
+        unsupported.assert_async().await;
+        success.assert_async().await;
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_with_retry_fallbacks_exhausted() {
+        let mut server = mockito::Server::new_async().await;
+        let unsupported = server
+            .mock("POST", "/v2/migrate")
+            .with_status(400)
+            .with_body(r#"{"error":"unsupported_document_version"}"#)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let err = refresh(&server, config(2, &["eid"])).await.unwrap_err();
+        assert!(matches!(
+            err,
+            NfcRefreshError::DocumentNotSupported { ref error_code }
+                if error_code == "unsupported_document_version"
+        ));
+        assert!(!err.is_transient());
+        unsupported.assert_async().await;
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_nfc_credential_keeps_document_type_errors_as_network_errors()
+    {
+        let mut server = mockito::Server::new_async().await;
+        let unsupported = server
+            .mock("POST", "/v2/migrate")
+            .with_status(400)
+            .with_body(r#"{"error":"document_not_supported"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let issuer = TfhNfcIssuer::with_base_url(
+            &server.url(),
+            "WorldApp/1.0.0 test/1.0.0".to_string(),
+        );
+
+        // only refresh_with_retry classifies the document type codes
+        let err = issuer
+            .refresh_nfc_credential("{}", HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            WalletKitError::NetworkError {
+                status: Some(400),
+                ..
+            }
+        ));
+        unsupported.assert_async().await;
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_with_retry_does_not_retry_permanent_errors() {
+        let mut server = mockito::Server::new_async().await;
+        let expired = server
+            .mock("POST", "/v2/migrate")
+            .with_status(400)
+            .with_body(r#"{"error":"document_expired"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let err = refresh(&server, config(3, &["eid"])).await.unwrap_err();
+        assert!(matches!(
+            err,
+            NfcRefreshError::Rejected { ref error_code } if error_code == "document_expired"
+        ));
+        expired.assert_async().await;
+        drop(server);
+    }
 }
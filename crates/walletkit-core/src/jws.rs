@@ -0,0 +1,798 @@
+//! Verification of JWS-signed proof requests.
+//!
+//! Some RPs deliver a [`ProofRequest`](crate::requests::ProofRequest) as a
+//! compact JWS whose payload is the request JSON plus the `iat` and `exp`
+//! claims. The verifying key is looked up by the request's `rp_id` and the
+//! header `kid` through an [`RpKeyResolver`]. Only `ES256` and `EdDSA`
+//! (Ed25519) are accepted; every other `alg`, including `none` and the HMAC
+//! algorithms, is rejected before a key is resolved.
+
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+use std::sync::{Mutex, PoisonError};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::error::WalletKitError;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{http_request::Request, storage::CredentialStore, Environment};
+
+/// Tolerated clock skew when checking the `iat` claim, in seconds.
+const MAX_CLOCK_SKEW_SECONDS: u64 = 60;
+
+/// Minimum time between two fetches of an RP's key set, in seconds. Within it
+/// an unknown `kid` is answered from the last fetched set, so forged headers
+/// cannot make every verification a network round-trip.
+#[cfg(not(target_arch = "wasm32"))]
+const MIN_REFETCH_INTERVAL_SECONDS: u64 = 300;
+
+/// A JWS algorithm accepted for signed proof requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum JwsAlgorithm {
+    /// ECDSA over P-256 with SHA-256 (`ES256`).
+    Es256,
+    /// Ed25519 (`EdDSA`).
+    EdDsa,
+}
+
+impl JwsAlgorithm {
+    /// Parses a header `alg`, rejecting anything outside the allow-list.
+    fn from_header(alg: &str) -> Result<Self, WalletKitError> {
+        match alg {
+            "ES256" => Ok(Self::Es256),
+            "EdDSA" => Ok(Self::EdDsa),
+            _ => Err(WalletKitError::JwsAlgorithmNotAllowed {
+                alg: alg.to_string(),
+            }),
+        }
+    }
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Es256 => "ES256",
+            Self::EdDsa => "EdDSA",
+        }
+    }
+}
+
+/// A public key an RP signs proof requests with.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct RpSigningKey {
+    /// The algorithm the key verifies.
+    pub algorithm: JwsAlgorithm,
+    /// The SEC1-encoded point for [`JwsAlgorithm::Es256`], or the 32-byte
+    /// public key for [`JwsAlgorithm::EdDsa`].
+    pub public_key: Vec<u8>,
+}
+
+/// Resolves the keys RPs sign proof requests with.
+///
+/// Use [`http_rp_key_resolver`] to fetch them from the Developer Portal, or
+/// implement it to pin keys on the host.
+#[uniffi::export(with_foreign)]
+#[async_trait::async_trait]
+pub trait RpKeyResolver: Send + Sync {
+    /// Returns the key `rp_id` signs with under `kid`.
+    ///
+    /// `now` is the current time in seconds since the UNIX epoch.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WalletKitError::JwsKeyNotFound`] if the RP has no such key,
+    /// or an error if the keys cannot be fetched.
+    async fn resolve_key(
+        &self,
+        rp_id: String,
+        kid: String,
+        now: u64,
+    ) -> Result<RpSigningKey, WalletKitError>;
+}
+
+/// A compact JWS whose signature has not been checked yet.
+pub(crate) struct CompactJws<'a> {
+    algorithm: JwsAlgorithm,
+    kid: String,
+    signing_input: &'a str,
+    payload: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct JwsHeader {
+    alg: String,
+    kid: Option<String>,
+    crit: Option<Value>,
+}
+
+impl<'a> CompactJws<'a> {
+    /// Splits and decodes `token`, checking the header `alg` against the
+    /// allow-list.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WalletKitError::JwsAlgorithmNotAllowed`] if `alg` is not
+    /// allowed, or [`WalletKitError::InvalidInput`] if the token is malformed.
+    pub(crate) fn parse(token: &'a str) -> Result<Self, WalletKitError> {
+        let mut segments = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) = (
+            segments.next(),
+            segments.next(),
+            segments.next(),
+            segments.next(),
+        ) else {
+            return Err(invalid_token("expected three `.`-separated segments"));
+        };
+
+        let header: JwsHeader = serde_json::from_slice(&decode_b64url(header)?)
+            .map_err(|e| invalid_token(format!("invalid JWS header: {e}")))?;
+        let algorithm = JwsAlgorithm::from_header(&header.alg)?;
+        if header.crit.is_some() {
+            return Err(invalid_token(
+                "critical header extensions are not supported",
+            ));
+        }
+        let kid = header
+            .kid
+            .ok_or_else(|| invalid_token("JWS header is missing `kid`"))?;
+
+        Ok(Self {
+            algorithm,
+            kid,
+            signing_input: &token[..signing_input_len(token)],
+            payload: decode_b64url(payload)?,
+            signature: decode_b64url(signature)?,
+        })
+    }
+
+    /// The header `kid`.
+    pub(crate) fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    /// The payload as a JSON object, before the signature is checked.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WalletKitError::InvalidInput`] if it is not a JSON object.
+    pub(crate) fn payload(&self) -> Result<Map<String, Value>, WalletKitError> {
+        serde_json::from_slice(&self.payload)
+            .map_err(|e| invalid_token(format!("invalid JWS payload: {e}")))
+    }
+
+    /// Checks the signature against `key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WalletKitError::JwsAlgorithmNotAllowed`] if `key` is for a
+    /// different algorithm than the header `alg`,
+    /// [`WalletKitError::JwsInvalidSignature`] if the signature does not
+    /// verify, or [`WalletKitError::InvalidInput`] if `key` is malformed.
+    pub(crate) fn verify(&self, key: &RpSigningKey) -> Result<(), WalletKitError> {
+        if key.algorithm != self.algorithm {
+            return Err(WalletKitError::JwsAlgorithmNotAllowed {
+                alg: format!(
+                    "{} with a {} key",
+                    self.algorithm.as_str(),
+                    key.algorithm.as_str()
+                ),
+            });
+        }
+        let message = self.signing_input.as_bytes();
+        let verified = match self.algorithm {
+            JwsAlgorithm::Es256 => {
+                use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+
+                let key = VerifyingKey::from_sec1_bytes(&key.public_key)
+                    .map_err(|_| invalid_key("not a valid P-256 point"))?;
+                Signature::from_slice(&self.signature)
+                    .is_ok_and(|signature| key.verify(message, &signature).is_ok())
+            }
+            JwsAlgorithm::EdDsa => {
+                use ed25519_dalek::{Signature, VerifyingKey};
+
+                let bytes = <[u8; 32]>::try_from(key.public_key.as_slice())
+                    .map_err(|_| invalid_key("Ed25519 keys are 32 bytes"))?;
+                let key = VerifyingKey::from_bytes(&bytes)
+                    .map_err(|_| invalid_key("not a valid Ed25519 key"))?;
+                Signature::from_slice(&self.signature).is_ok_and(|signature| {
+                    key.verify_strict(message, &signature).is_ok()
+                })
+            }
+        };
+        if verified {
+            Ok(())
+        } else {
+            Err(WalletKitError::JwsInvalidSignature)
+        }
+    }
+}
+
+/// Length of the `header.payload` signing input of `token`.
+fn signing_input_len(token: &str) -> usize {
+    token.rfind('.').unwrap_or(token.len())
+}
+
+/// The registered claims of a signed proof request.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct JwsClaims {
+    issued_at: u64,
+    expires_at: u64,
+}
+
+impl JwsClaims {
+    /// Removes the `iat` and `exp` claims from `payload`, leaving the request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WalletKitError::InvalidInput`] if either is missing or not a
+    /// unix timestamp.
+    pub(crate) fn take(
+        payload: &mut Map<String, Value>,
+    ) -> Result<Self, WalletKitError> {
+        let mut claim = |name: &str| {
+            payload
+                .remove(name)
+                .and_then(|value| value.as_u64())
+                .ok_or_else(|| {
+                    invalid_token(format!("`{name}` claim must be a unix timestamp"))
+                })
+        };
+        Ok(Self {
+            issued_at: claim("iat")?,
+            expires_at: claim("exp")?,
+        })
+    }
+
+    /// Checks the claims at `now`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WalletKitError::JwsExpired`] if `exp` has passed, or
+    /// [`WalletKitError::JwsIssuedInFuture`] if `iat` is more than a minute
+    /// ahead of `now`.
+    pub(crate) const fn check(self, now: u64) -> Result<(), WalletKitError> {
+        if now >= self.expires_at {
+            return Err(WalletKitError::JwsExpired {
+                expires_at: self.expires_at,
+            });
+        }
+        if self.issued_at > now.saturating_add(MAX_CLOCK_SKEW_SECONDS) {
+            return Err(WalletKitError::JwsIssuedInFuture {
+                issued_at: self.issued_at,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A JSON Web Key Set, as served by RPs.
+#[derive(Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    crv: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+}
+
+impl Jwks {
+    fn parse(bytes: &[u8]) -> Result<Self, WalletKitError> {
+        serde_json::from_slice(bytes).map_err(|e| WalletKitError::SerializationError {
+            error: format!("Failed to parse RP key set: {e}"),
+        })
+    }
+
+    fn find(&self, kid: &str) -> Option<&Jwk> {
+        self.keys.iter().find(|key| key.kid.as_deref() == Some(kid))
+    }
+}
+
+impl Jwk {
+    /// Converts the key, rejecting key types outside the allow-list.
+    fn to_signing_key(&self) -> Result<RpSigningKey, WalletKitError> {
+        let coordinate = |value: Option<&str>, name: &str| {
+            let value =
+                value.ok_or_else(|| invalid_key(format!("missing `{name}`")))?;
+            let bytes = decode_b64url(value)?;
+            if bytes.len() == 32 {
+                Ok(bytes)
+            } else {
+                Err(invalid_key(format!("`{name}` must be 32 bytes")))
+            }
+        };
+        match (self.kty.as_str(), self.crv.as_deref()) {
+            ("EC", Some("P-256")) => {
+                let mut public_key = vec![0x04];
+                public_key.extend(coordinate(self.x.as_deref(), "x")?);
+                public_key.extend(coordinate(self.y.as_deref(), "y")?);
+                Ok(RpSigningKey {
+                    algorithm: JwsAlgorithm::Es256,
+                    public_key,
+                })
+            }
+            ("OKP", Some("Ed25519")) => Ok(RpSigningKey {
+                algorithm: JwsAlgorithm::EdDsa,
+                public_key: coordinate(self.x.as_deref(), "x")?,
+            }),
+            (kty, crv) => Err(WalletKitError::JwsAlgorithmNotAllowed {
+                alg: format!("{kty} key on {}", crv.unwrap_or("no curve")),
+            }),
+        }
+    }
+}
+
+/// An [`RpKeyResolver`] over a fixed set of keys, for tests.
+#[derive(Debug, Default)]
+pub struct InMemoryRpKeyResolver {
+    keys: Mutex<HashMap<(String, String), RpSigningKey>>,
+}
+
+impl InMemoryRpKeyResolver {
+    /// Creates a resolver without keys.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the key `rp_id` signs with under `kid`.
+    pub fn insert(&self, rp_id: &str, kid: &str, key: RpSigningKey) {
+        self.keys
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert((rp_id.to_string(), kid.to_string()), key);
+    }
+
+    /// Adds every key with a `kid` of the JWKS document `jwks`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document or one of its keys is invalid.
+    pub fn insert_jwks(&self, rp_id: &str, jwks: &[u8]) -> Result<(), WalletKitError> {
+        for key in Jwks::parse(jwks)?.keys {
+            if let Some(kid) = &key.kid {
+                self.insert(rp_id, kid, key.to_signing_key()?);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl RpKeyResolver for InMemoryRpKeyResolver {
+    async fn resolve_key(
+        &self,
+        rp_id: String,
+        kid: String,
+        _now: u64,
+    ) -> Result<RpSigningKey, WalletKitError> {
+        let key = self
+            .keys
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&(rp_id.clone(), kid.clone()))
+            .cloned();
+        key.ok_or(WalletKitError::JwsKeyNotFound { rp_id, kid })
+    }
+}
+
+/// Fetches RP key sets from the Developer Portal, caching them for an hour
+/// in a [`CredentialStore`]'s cache if one is given.
+#[cfg(not(target_arch = "wasm32"))]
+struct HttpRpKeyResolver {
+    base_url: String,
+    request: Request,
+    store: Option<Arc<CredentialStore>>,
+    /// The last fetched key set of each RP, with the time it was fetched.
+    fetched: Mutex<HashMap<String, (u64, Arc<Jwks>)>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl HttpRpKeyResolver {
+    fn with_base_url(
+        base_url: &str,
+        user_agent: String,
+        store: Option<Arc<CredentialStore>>,
+    ) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            request: Request::new(user_agent),
+            store,
+            fetched: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the key set of `rp_id` if it was fetched less than
+    /// [`MIN_REFETCH_INTERVAL_SECONDS`] before `now`.
+    fn recently_fetched_jwks(&self, rp_id: &str, now: u64) -> Option<Arc<Jwks>> {
+        let (fetched_at, jwks) = self
+            .fetched
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(rp_id)
+            .cloned()?;
+        (now < fetched_at.saturating_add(MIN_REFETCH_INTERVAL_SECONDS)).then_some(jwks)
+    }
+
+    fn cached_jwks(&self, rp_id: &str, now: u64) -> Option<Jwks> {
+        let bytes = match self.store.as_ref()?.rp_keys_get(rp_id, now) {
+            Ok(bytes) => bytes?,
+            Err(e) => {
+                tracing::warn!("failed to read cached RP keys: {e}");
+                return None;
+            }
+        };
+        Jwks::parse(&bytes).ok()
+    }
+
+    fn cache_jwks(&self, rp_id: &str, bytes: &[u8], now: u64) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.rp_keys_put(rp_id, bytes, now) {
+                tracing::warn!("failed to cache RP keys: {e}");
+            }
+        }
+    }
+
+    /// Calls the `/api/v1/rp/{rp_id}/.well-known/jwks.json` endpoint.
+    async fn fetch_jwks(
+        &self,
+        rp_id: &str,
+        kid: &str,
+    ) -> Result<Vec<u8>, WalletKitError> {
+        let url = format!("{}/api/v1/rp/{rp_id}/.well-known/jwks.json", self.base_url);
+        let response = self.request.handle(self.request.get(&url)).await?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Err(WalletKitError::JwsKeyNotFound {
+                rp_id: rp_id.to_string(),
+                kid: kid.to_string(),
+            });
+        }
+        if !status.is_success() {
+            let error_body = response.text().await.unwrap_or_default();
+            return Err(WalletKitError::NetworkError {
+                url,
+                status: Some(status.as_u16()),
+                error: format!("RP key set fetch failed: {error_body}"),
+            });
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait::async_trait]
+impl RpKeyResolver for HttpRpKeyResolver {
+    async fn resolve_key(
+        &self,
+        rp_id: String,
+        kid: String,
+        now: u64,
+    ) -> Result<RpSigningKey, WalletKitError> {
+        validate_rp_id(&rp_id)?;
+        if let Some(key) = self
+            .cached_jwks(&rp_id, now)
+            .as_ref()
+            .and_then(|jwks| jwks.find(&kid))
+        {
+            return key.to_signing_key();
+        }
+
+        // not cached, or a `kid` the RP rotated in since: refetch, unless the
+        // set was fetched just now
+        let jwks = if let Some(jwks) = self.recently_fetched_jwks(&rp_id, now) {
+            jwks
+        } else {
+            let bytes = self.fetch_jwks(&rp_id, &kid).await?;
+            let jwks = Arc::new(Jwks::parse(&bytes)?);
+            self.cache_jwks(&rp_id, &bytes, now);
+            self.fetched
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(rp_id.clone(), (now, Arc::clone(&jwks)));
+            jwks
+        };
+        jwks.find(&kid)
+            .ok_or(WalletKitError::JwsKeyNotFound { rp_id, kid })?
+            .to_signing_key()
+    }
+}
+
+/// Creates a resolver that fetches RP key sets from the Developer Portal of
+/// `environment`.
+///
+/// Key sets are cached for an hour in `store`'s cache if one is given; an
+/// unknown `kid` triggers a refetch, so rotated keys are picked up, but at
+/// most once every five minutes per RP.
+#[cfg(not(target_arch = "wasm32"))]
+#[uniffi::export]
+#[must_use]
+pub fn http_rp_key_resolver(
+    environment: &Environment,
+    user_agent: String,
+    store: Option<Arc<CredentialStore>>,
+) -> Arc<dyn RpKeyResolver> {
+    Arc::new(HttpRpKeyResolver::with_base_url(
+        crate::app_registry::base_url_for(environment),
+        user_agent,
+        store,
+    ))
+}
+
+/// Checks that `rp_id` is safe to use as a path segment.
+#[cfg(not(target_arch = "wasm32"))]
+fn validate_rp_id(rp_id: &str) -> Result<(), WalletKitError> {
+    let valid = rp_id.strip_prefix("rp_").is_some_and(|hex| {
+        !hex.is_empty() && hex.bytes().all(|b| b.is_ascii_hexdigit())
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(WalletKitError::InvalidInput {
+            attribute: "rp_id".to_string(),
+            reason: "expected `rp_` followed by hex digits".to_string(),
+        })
+    }
+}
+
+fn decode_b64url(segment: &str) -> Result<Vec<u8>, WalletKitError> {
+    URL_SAFE_NO_PAD
+        .decode(segment)
+        .map_err(|e| invalid_token(format!("invalid base64url: {e}")))
+}
+
+fn invalid_token(reason: impl Into<String>) -> WalletKitError {
+    WalletKitError::InvalidInput {
+        attribute: "proof_request_jws".to_string(),
+        reason: reason.into(),
+    }
+}
+
+fn invalid_key(reason: impl Into<String>) -> WalletKitError {
+    WalletKitError::InvalidInput {
+        attribute: "rp_signing_key".to_string(),
+        reason: reason.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::requests::ProofRequest;
+
+    const FIXTURES: &str = include_str!("../tests/fixtures/jws/fixtures.json");
+
+    struct Fixtures {
+        now: u64,
+        rp_id: String,
+        jwks: Vec<u8>,
+        tokens: Value,
+    }
+
+    impl Fixtures {
+        fn load() -> Self {
+            let fixtures: Value = serde_json::from_str(FIXTURES).unwrap();
+            Self {
+                now: fixtures["now"].as_u64().unwrap(),
+                rp_id: fixtures["rp_id"].as_str().unwrap().to_string(),
+                jwks: fixtures["jwks"].to_string().into_bytes(),
+                tokens: fixtures["tokens"].clone(),
+            }
+        }
+
+        fn token(&self, name: &str) -> &str {
+            self.tokens[name].as_str().unwrap()
+        }
+
+        fn resolver(&self) -> Arc<dyn RpKeyResolver> {
+            let resolver = InMemoryRpKeyResolver::new();
+            resolver.insert_jwks(&self.rp_id, &self.jwks).unwrap();
+            Arc::new(resolver)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_valid_tokens() {
+        let fixtures = Fixtures::load();
+        for name in ["valid_es256", "valid_eddsa"] {
+            let token = fixtures.token(name);
+            let request =
+                ProofRequest::from_jws(token, fixtures.resolver(), fixtures.now)
+                    .await
+                    .unwrap_or_else(|e| panic!("{name}: {e:?}"));
+            assert_eq!(request.id(), "req_abc123");
+            assert_eq!(request.jws().as_deref(), Some(token));
+            // the registered claims are not part of the request
+            assert!(!request.to_json().unwrap().contains("\"exp\""));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_expired_token() {
+        let fixtures = Fixtures::load();
+        let result = ProofRequest::from_jws(
+            fixtures.token("expired"),
+            fixtures.resolver(),
+            fixtures.now,
+        )
+        .await;
+        assert!(matches!(result, Err(WalletKitError::JwsExpired { .. })));
+
+        // `iat` more than a minute ahead of the clock
+        let result = ProofRequest::from_jws(
+            fixtures.token("valid_es256"),
+            fixtures.resolver(),
+            fixtures.now - 8 - MAX_CLOCK_SKEW_SECONDS - 1,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(WalletKitError::JwsIssuedInFuture { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_wrong_key() {
+        let fixtures = Fixtures::load();
+        let result = ProofRequest::from_jws(
+            fixtures.token("wrong_key"),
+            fixtures.resolver(),
+            fixtures.now,
+        )
+        .await;
+        assert!(matches!(result, Err(WalletKitError::JwsInvalidSignature)));
+    }
+
+    #[tokio::test]
+    async fn test_alg_confusion() {
+        let fixtures = Fixtures::load();
+        let result = ProofRequest::from_jws(
+            fixtures.token("alg_confusion"),
+            fixtures.resolver(),
+            fixtures.now,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(WalletKitError::JwsAlgorithmNotAllowed { ref alg }) if alg == "HS256"
+        ));
+
+        // unsigned
+        let token = fixtures.token("valid_es256");
+        let payload = token.split('.').nth(1).unwrap();
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"none","kid":"es256-1"}"#);
+        let result = ProofRequest::from_jws(
+            &format!("{header}.{payload}."),
+            fixtures.resolver(),
+            fixtures.now,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(WalletKitError::JwsAlgorithmNotAllowed { .. })
+        ));
+
+        // an ES256 token must not be checked against an Ed25519 key
+        let resolver = InMemoryRpKeyResolver::new();
+        resolver.insert(
+            &fixtures.rp_id,
+            "es256-1",
+            RpSigningKey {
+                algorithm: JwsAlgorithm::EdDsa,
+                public_key: vec![0; 32],
+            },
+        );
+        let result =
+            ProofRequest::from_jws(token, Arc::new(resolver), fixtures.now).await;
+        assert!(matches!(
+            result,
+            Err(WalletKitError::JwsAlgorithmNotAllowed { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_kid() {
+        let fixtures = Fixtures::load();
+        let result = ProofRequest::from_jws(
+            fixtures.token("valid_es256"),
+            Arc::new(InMemoryRpKeyResolver::new()),
+            fixtures.now,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(WalletKitError::JwsKeyNotFound { ref kid, .. }) if kid == "es256-1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_token() {
+        let fixtures = Fixtures::load();
+        for token in ["", "a.b", "a.b.c.d", "!!.!!.!!"] {
+            let result =
+                ProofRequest::from_jws(token, fixtures.resolver(), fixtures.now).await;
+            assert!(
+                matches!(result, Err(WalletKitError::InvalidInput { ref attribute, .. }) if attribute == "proof_request_jws"),
+                "{token:?}"
+            );
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn test_http_resolver_caches_key_sets() {
+        use crate::storage::tests_utils::{
+            cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+        };
+
+        let fixtures = Fixtures::load();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock(
+                "GET",
+                format!("/api/v1/rp/{}/.well-known/jwks.json", fixtures.rp_id).as_str(),
+            )
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(&fixtures.jwks)
+            .expect(2)
+            .create_async()
+            .await;
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = Arc::new(CredentialStore::from_provider(&provider).expect("store"));
+        store.init(42, 100).expect("init storage");
+        let resolver: Arc<dyn RpKeyResolver> =
+            Arc::new(HttpRpKeyResolver::with_base_url(
+                &server.url(),
+                "test/1.0.0".to_string(),
+                Some(Arc::clone(&store)),
+            ));
+
+        for name in ["valid_es256", "valid_eddsa"] {
+            ProofRequest::from_jws(
+                fixtures.token(name),
+                Arc::clone(&resolver),
+                fixtures.now,
+            )
+            .await
+            .unwrap();
+        }
+        // an unknown kid refetches the key set, once per interval
+        for now in [
+            fixtures.now,
+            fixtures.now + 1,
+            fixtures.now + MIN_REFETCH_INTERVAL_SECONDS,
+            fixtures.now + MIN_REFETCH_INTERVAL_SECONDS + 1,
+        ] {
+            let result = resolver
+                .resolve_key(fixtures.rp_id.clone(), "rotated".to_string(), now)
+                .await;
+            assert!(matches!(result, Err(WalletKitError::JwsKeyNotFound { .. })));
+        }
+        mock.assert_async().await;
+
+        assert!(matches!(
+            resolver
+                .resolve_key(
+                    "../admin".to_string(),
+                    "es256-1".to_string(),
+                    fixtures.now
+                )
+                .await,
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+        drop(server);
+        cleanup_test_storage(&root);
+    }
+}
@@ -39,6 +39,22 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! # Async runtimes
+//!
+//! The async API does not require the caller to run tokio and can be driven
+//! by any executor (e.g. `async-std` or `smol`). HTTP and timers are
+//! tokio-based internally; outside a tokio runtime they are driven by a
+//! single-threaded tokio runtime on one background thread, started on first
+//! use. Inside a tokio runtime, the caller's runtime is used.
+//!
+//! Which parts pull in tokio:
+//! - Always: tokio's `sync` primitives, and on native targets the `rt` and
+//!   `time` drivers behind the background runtime above.
+//! - `UniFFI` bindings: exported async functions are polled by the foreign
+//!   language's executor and run on tokio (`async_runtime = "tokio"`).
+//! - `c-ffi`: blocking `extern "C"` calls are run on an internal
+//!   multi-threaded tokio runtime.
 
 use strum::{Display, EnumString};
 
@@ -105,6 +121,9 @@ pub enum Region {
 /// Contains error outputs from `WalletKit`
 pub mod error;
 
+mod error_messages;
+pub use error_messages::register_error_messages;
+
 /// Contains logging functionality that can be integrated with foreign language bindings.
 pub mod logger;
 
@@ -114,18 +133,61 @@ pub use field_element::FieldElement;
 mod credential;
 pub use credential::Credential;
 
+mod app_id;
+pub use app_id::AppId;
+
 /// Credential storage primitives for World ID v4.
 pub mod storage;
+pub use storage::{data_inventory, entropy_selfcheck};
+#[cfg(not(target_arch = "wasm32"))]
+pub use storage::{inspect_file, FileInspection, StorageFileKind};
+
+/// Releasing cached memory on mobile memory-pressure events.
+pub mod memory_pressure;
+pub use memory_pressure::{on_memory_pressure, MemoryPressureLevel};
 
 mod authenticator;
+#[cfg(feature = "testing")]
+pub use authenticator::FaultKind;
+#[cfg(all(feature = "opentelemetry", not(target_arch = "wasm32")))]
+pub use authenticator::ProofMetricsConfig;
+pub use authenticator::{
+    cold_start, derive_seed_from_passkey_prf, passkey_prf_salt,
+    verify_account_attestation, ActionUsage, AuditEntry, Authenticator,
+    BatchProofResult, ColdStartOptions, ColdStartResult, Groth16Materials,
+    InclusionProof, InitializingAuthenticator, ProofAuditTrail, ProofOptions,
+    ProofOutcome, ProofStats, ProvingProfile, RecoveryData, RecoveryUpdateSignature,
+    RegistrationStatus, SignedAttestation, VerifiedAccountAttestation,
+    ACCOUNT_ATTESTATION_TTL_SECONDS, PASSKEY_SEED_VERSION,
+};
+#[cfg(not(target_arch = "wasm32"))]
 pub use authenticator::{
-    Authenticator, Groth16Materials, InitializingAuthenticator, RecoveryData,
-    RecoveryUpdateSignature, RegistrationStatus,
+    AuthenticatorOptions, AuthenticatorPool, AuthenticatorPoolOptions,
+    MultiAccountAuthenticator,
 };
 
+/// Allocation accounting behind [`ProofStats::peak_alloc_bytes_estimate`].
+#[cfg(any(test, feature = "alloc-stats"))]
+pub mod alloc_stats;
+
+#[cfg(test)]
+#[global_allocator]
+static ALLOC: alloc_stats::CountingAllocator = alloc_stats::CountingAllocator;
+
 /// Default configuration values for each [`Environment`].
 pub mod defaults;
 
+/// Size limits for untrusted input crossing the FFI.
+pub mod limits;
+
+/// Protocol parameters (tree depth, field, circuits) for configuring verifiers.
+pub mod protocol;
+pub use protocol::{protocol_parameters, ProtocolParameters};
+
+/// Pseudonymised export of credential metadata for analytics.
+#[cfg(any(test, feature = "analytics"))]
+pub mod analytics;
+
 /// User agent for HTTP requests.
 pub mod user_agent;
 pub use user_agent::{UserAgent, UserAgentBuilder};
@@ -133,12 +195,63 @@ pub use user_agent::{UserAgent, UserAgentBuilder};
 /// Proof requests and responses in World ID v4.
 pub mod requests;
 
+/// Verification of JWS-signed proof requests.
+pub mod jws;
+
+/// Developer Portal app and action metadata.
+pub mod app_registry;
+pub use app_registry::{ActionConfig, AppRegistryClient};
+
+/// Caches shared between instances, for hosts that create many short-lived ones.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod shared_cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub use shared_cache::SharedCaches;
+
+/// Resumable, verified downloads of the Groth16 proving artifacts.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod artifacts;
+#[cfg(not(target_arch = "wasm32"))]
+pub use artifacts::{ArtifactManager, ArtifactProgressListener, ArtifactStatus};
+
+/// The HTTP client shared by `WalletKit`'s own requests.
+pub mod http_client;
+pub use http_client::{set_http_client_config, HttpClientConfig};
+#[cfg(not(target_arch = "wasm32"))]
+pub use http_client::{
+    set_http_transport, HttpHeader, HttpRequest, HttpResponse, HttpTransport,
+};
+
+/// Certificate pinning for `WalletKit`'s HTTPS clients.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tls_pinning;
+#[cfg(not(target_arch = "wasm32"))]
+pub use tls_pinning::{set_tls_pins, TlsPin, TlsPinConfig};
+
 /// Pre-flight check of whether stored credentials can satisfy a [`requests::ProofRequest`].
 pub mod proof_request_credential_constraints_check;
 
+/// Push-style proof generation timings and cache hit rates.
+pub mod metrics_observer;
+pub use metrics_observer::{
+    set_metrics_observer, CacheKind, MetricsObserver, ProofStage,
+};
+
+/// Cancellation of long-running calls, e.g. proof generation.
+pub mod cancellation;
+pub use cancellation::CancellationHandle;
+
+/// Verification of World ID proofs by relying parties.
+pub mod verify;
+pub use verify::{verify_proof, ProofItemVerification, ProofVerification, RootSource};
+
 mod proof;
 pub use proof::OwnershipProof;
 
+/// Stable `extern "C"` surface for non-`UniFFI` consumers.
+#[cfg(all(feature = "c-ffi", not(target_arch = "wasm32")))]
+pub mod c_ffi;
+
 /// Credential issuers for World ID (NFC, etc.)
 #[cfg(feature = "issuers")]
 pub mod issuers;
@@ -151,20 +264,25 @@ pub mod issuers;
 /// use walletkit_core::Environment;
 /// async fn example() {
 ///     let world_id = WorldId::new(b"not_a_real_secret", &Environment::Staging);
-///     let context = ProofContext::new("app_ce4cb73cb75fc3b73b71ffb4de178410", Some("my_action".to_string()), None, CredentialType::Orb);
+///     let context = ProofContext::new("app_ce4cb73cb75fc3b73b71ffb4de178410", Some("my_action".to_string()), None, CredentialType::Orb).unwrap();
 ///     let proof = world_id.generate_proof(&context).await.unwrap();
-///     println!("{}", proof.to_json().unwrap()); // the JSON output can be passed to the Developer Portal, World ID contracts, etc. for verification
+///     println!("{}", proof.to_portal_json().unwrap()); // for the Developer Portal; use `to_onchain_calldata()` for the World ID contracts
 /// }
 #[cfg(feature = "v3")]
 pub mod v3;
 
+/// Detection and migration of legacy World ID 3.0 identities to 4.0 accounts.
+#[cfg(feature = "v3")]
+pub mod migration;
+
 ////////////////////////////////////////////////////////////////////////////////
 // Private modules
 ////////////////////////////////////////////////////////////////////////////////
 
-#[cfg(any(feature = "issuers", feature = "v3"))]
 mod http_request;
 pub(crate) mod primitives;
+mod rpc;
+mod runtime;
 
 uniffi::setup_scaffolding!("walletkit_core");
 
@@ -0,0 +1,202 @@
+//! Size limits for untrusted input crossing the FFI.
+//!
+//! Every entry point that accepts a string or byte payload from the host checks
+//! it against these limits before parsing it, so an oversized payload (e.g.
+//! from a malicious deep link) is rejected with [`WalletKitError::InvalidInput`]
+//! instead of being deserialized. Foreign bindings have already copied the
+//! payload into Rust by the time the check runs; the C FFI checks before its
+//! own copy.
+//!
+//! The limits are far above any legitimate payload.
+
+use crate::error::WalletKitError;
+
+/// Maximum size of a proof request JSON, in bytes.
+pub const MAX_PROOF_REQUEST_BYTES: usize = 256 * 1024;
+
+/// Maximum size of a proof request deep link or QR payload, in bytes. Leaves
+/// room for a percent-encoded request of [`MAX_PROOF_REQUEST_BYTES`].
+pub const MAX_PROOF_REQUEST_URI_BYTES: usize = 1024 * 1024;
+
+/// Maximum size of an authenticator config JSON, in bytes.
+pub const MAX_CONFIG_BYTES: usize = 64 * 1024;
+
+/// Maximum size of an authenticator seed, in bytes.
+pub const MAX_SEED_BYTES: usize = 32;
+
+/// Maximum size of a serialized credential, in bytes.
+pub const MAX_CREDENTIAL_BYTES: usize = 64 * 1024;
+
+/// Maximum size of a disclosure memo, in bytes.
+pub const MAX_DISCLOSURE_MEMO_BYTES: usize = 128;
+
+/// Maximum size of an account attestation, in bytes.
+pub const MAX_ACCOUNT_ATTESTATION_BYTES: usize = 4 * 1024;
+
+/// A size-limited input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InputLimit {
+    /// See [`MAX_PROOF_REQUEST_BYTES`].
+    ProofRequest,
+    /// See [`MAX_PROOF_REQUEST_URI_BYTES`].
+    ProofRequestUri,
+    /// See [`MAX_CONFIG_BYTES`].
+    Config,
+    /// See [`MAX_SEED_BYTES`].
+    Seed,
+    /// See [`MAX_CREDENTIAL_BYTES`].
+    Credential,
+    /// See [`MAX_DISCLOSURE_MEMO_BYTES`].
+    DisclosureMemo,
+    /// See [`MAX_ACCOUNT_ATTESTATION_BYTES`].
+    AccountAttestation,
+}
+
+impl InputLimit {
+    /// The attribute reported in [`WalletKitError::InvalidInput`].
+    const fn attribute(self) -> &'static str {
+        match self {
+            Self::ProofRequest => "proof_request",
+            Self::ProofRequestUri => "uri",
+            Self::Config => "config",
+            Self::Seed => "seed",
+            Self::Credential => "credential_bytes",
+            Self::DisclosureMemo => "disclosure_memo",
+            Self::AccountAttestation => "account_attestation",
+        }
+    }
+
+    /// The limit in bytes.
+    pub(crate) const fn max_bytes(self) -> usize {
+        match self {
+            Self::ProofRequest => MAX_PROOF_REQUEST_BYTES,
+            Self::ProofRequestUri => MAX_PROOF_REQUEST_URI_BYTES,
+            Self::Config => MAX_CONFIG_BYTES,
+            Self::Seed => MAX_SEED_BYTES,
+            Self::Credential => MAX_CREDENTIAL_BYTES,
+            Self::DisclosureMemo => MAX_DISCLOSURE_MEMO_BYTES,
+            Self::AccountAttestation => MAX_ACCOUNT_ATTESTATION_BYTES,
+        }
+    }
+
+    /// Checks an input of `len` bytes against the limit.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::InvalidInput`] naming the limit if `len`
+    /// exceeds it.
+    pub(crate) fn check(self, len: usize) -> Result<(), WalletKitError> {
+        let max = self.max_bytes();
+        if len > max {
+            return Err(WalletKitError::InvalidInput {
+                attribute: self.attribute().to_string(),
+                reason: format!("{len} bytes exceeds the limit of {max} bytes"),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::ProofRequest;
+    use crate::{Credential, InitializingAuthenticator, RecoveryData};
+
+    /// A JSON object of exactly `len` bytes.
+    fn json_of_len(len: usize) -> String {
+        format!("{{{}}}", " ".repeat(len - 2))
+    }
+
+    fn assert_over_limit<T>(
+        result: Result<T, WalletKitError>,
+        attribute: &str,
+        max: usize,
+    ) {
+        let Err(error) = result else {
+            panic!("oversized input must be rejected");
+        };
+        match error {
+            WalletKitError::InvalidInput {
+                attribute: actual,
+                reason,
+            } => {
+                assert_eq!(actual, attribute);
+                assert!(
+                    reason.contains(&format!("limit of {max} bytes")),
+                    "reason must name the limit: {reason}"
+                );
+            }
+            other => panic!("expected invalid input error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_check_boundary() {
+        assert!(InputLimit::Config.check(MAX_CONFIG_BYTES).is_ok());
+        assert_over_limit(
+            InputLimit::Config.check(MAX_CONFIG_BYTES + 1),
+            "config",
+            MAX_CONFIG_BYTES,
+        );
+    }
+
+    #[test]
+    fn test_proof_request_over_limit() {
+        let json = json_of_len(MAX_PROOF_REQUEST_BYTES + 1);
+        assert_over_limit(
+            ProofRequest::from_json(&json),
+            "proof_request",
+            MAX_PROOF_REQUEST_BYTES,
+        );
+    }
+
+    #[test]
+    fn test_credential_over_limit() {
+        let bytes = json_of_len(MAX_CREDENTIAL_BYTES + 1).into_bytes();
+        assert_over_limit(
+            Credential::from_bytes(bytes),
+            "credential_bytes",
+            MAX_CREDENTIAL_BYTES,
+        );
+    }
+
+    #[test]
+    fn test_config_over_limit() {
+        let json = json_of_len(MAX_CONFIG_BYTES + 1);
+        assert_over_limit(
+            crate::authenticator::parse_config(&json),
+            "config",
+            MAX_CONFIG_BYTES,
+        );
+    }
+
+    #[test]
+    fn test_seed_over_limit() {
+        let seed = [1u8; MAX_SEED_BYTES + 1];
+        assert_over_limit(RecoveryData::from_seed(&seed), "seed", MAX_SEED_BYTES);
+    }
+
+    #[tokio::test]
+    async fn test_register_rejects_oversized_input_before_network() {
+        // no server is listening; the inputs must be rejected before any request
+        let json = json_of_len(MAX_CONFIG_BYTES + 1);
+        assert_over_limit(
+            InitializingAuthenticator::register(&[1u8; 32], &json, None).await,
+            "config",
+            MAX_CONFIG_BYTES,
+        );
+        let seed = [1u8; MAX_SEED_BYTES + 1];
+        assert_over_limit(
+            InitializingAuthenticator::register_with_defaults(
+                &seed,
+                Some("http://127.0.0.1:1".to_string()),
+                &crate::Environment::Staging,
+                None,
+                None,
+            )
+            .await,
+            "seed",
+            MAX_SEED_BYTES,
+        );
+    }
+}
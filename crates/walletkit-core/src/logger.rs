@@ -121,6 +121,49 @@ impl tracing::field::Visit for EventFieldVisitor {
 /// Forwards walletkit tracing events to the foreign logger.
 struct ForeignLoggerLayer;
 
+/// The fields of a span, formatted as `name=value` pairs and kept in its
+/// extensions to name the span in messages.
+#[cfg(feature = "tracing-spans")]
+struct SpanFields(String);
+
+#[cfg(feature = "tracing-spans")]
+impl EventFieldVisitor {
+    /// Formats the visited fields as space-separated `name=value` pairs.
+    fn into_pairs(self) -> String {
+        self.message
+            .map(|message| ("message".to_string(), message))
+            .into_iter()
+            .chain(self.fields)
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Returns the spans `event` occurs in, outermost first, e.g.
+/// `generate_proof{request_id=req_1}:http_request{url=https://…}`.
+#[cfg(feature = "tracing-spans")]
+fn span_path<S>(ctx: &Context<'_, S>, event: &Event<'_>) -> Option<String>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let path = ctx
+        .event_scope(event)?
+        .from_root()
+        .map(|span| {
+            let extensions = span.extensions();
+            match extensions.get::<SpanFields>() {
+                Some(SpanFields(fields)) if !fields.is_empty() => {
+                    format!("{}{{{fields}}}", span.name())
+                }
+                _ => span.name().to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(":");
+    (!path.is_empty()).then_some(path)
+}
+
 // On native targets, log events flow through a channel to avoid making FFI
 // calls from within a UniFFI future-poll context.  On WASM the Logger is
 // called directly because there is no background thread support.
@@ -150,7 +193,48 @@ impl<S> Layer<S> for ForeignLoggerLayer
 where
     S: Subscriber + for<'span> LookupSpan<'span>,
 {
-    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+    #[cfg(feature = "tracing-spans")]
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        id: &tracing::span::Id,
+        ctx: Context<'_, S>,
+    ) {
+        let mut visitor = EventFieldVisitor::default();
+        attrs.record(&mut visitor);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut()
+                .insert(SpanFields(visitor.into_pairs()));
+        }
+    }
+
+    #[cfg(feature = "tracing-spans")]
+    fn on_record(
+        &self,
+        id: &tracing::span::Id,
+        values: &tracing::span::Record<'_>,
+        ctx: Context<'_, S>,
+    ) {
+        let mut visitor = EventFieldVisitor::default();
+        values.record(&mut visitor);
+        let recorded = visitor.into_pairs();
+        if let Some(span) = ctx.span(id) {
+            if let Some(SpanFields(fields)) =
+                span.extensions_mut().get_mut::<SpanFields>()
+            {
+                if !fields.is_empty() {
+                    fields.push(' ');
+                }
+                fields.push_str(&recorded);
+            }
+        }
+    }
+
+    #[cfg_attr(
+        not(feature = "tracing-spans"),
+        expect(unused_variables, reason = "spans are only named with `tracing-spans`")
+    )]
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
         #[cfg(not(target_arch = "wasm32"))]
         let Some(sender) = LOG_CHANNEL.get() else {
             return;
@@ -182,6 +266,10 @@ where
         if message.is_empty() {
             message = metadata.name().to_string();
         }
+        #[cfg(feature = "tracing-spans")]
+        if let Some(path) = span_path(&ctx, event) {
+            message = format!("{path}: {message}");
+        }
 
         let formatted =
             sanitize_hex_secrets(format!("{} {message}", metadata.target()));
@@ -415,6 +503,47 @@ fn has_long_hex_run(bytes: &[u8]) -> bool {
 mod tests {
     use super::*;
 
+    #[cfg(feature = "tracing-spans")]
+    #[test]
+    fn span_path_names_spans_with_fields() {
+        struct PathCapture(Arc<std::sync::Mutex<Vec<Option<String>>>>);
+
+        impl<S> Layer<S> for PathCapture
+        where
+            S: Subscriber + for<'span> LookupSpan<'span>,
+        {
+            fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+                self.0.lock().unwrap().push(span_path(&ctx, event));
+            }
+        }
+
+        let paths = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let subscriber = Registry::default()
+            .with(ForeignLoggerLayer)
+            .with(PathCapture(Arc::clone(&paths)));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("outside");
+            let outer = tracing::info_span!("generate_proof", request_id = "req_1");
+            let _outer = outer.enter();
+            let inner =
+                tracing::info_span!("http_request", url = tracing::field::Empty);
+            inner.record("url", "https://example.com");
+            let _inner = inner.enter();
+            tracing::info!("sent");
+        });
+
+        assert_eq!(
+            *paths.lock().unwrap(),
+            vec![
+                None,
+                Some(
+                    "generate_proof{request_id=req_1}:http_request{url=https://example.com}"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn short_hex_passes_through() {
         let input = "tx hash is abcdef1";
@@ -0,0 +1,36 @@
+//! Shedding memory on the host OS's memory-pressure callbacks.
+
+use crate::storage::credential_storage::release_memory_of_live_stores;
+
+/// Severity of a memory-pressure event reported by the OS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum MemoryPressureLevel {
+    /// Memory is getting low, e.g. Android's `TRIM_MEMORY_RUNNING_LOW` or
+    /// `TRIM_MEMORY_UI_HIDDEN`.
+    Moderate,
+    /// The process is about to be killed unless it frees memory, e.g. iOS's
+    /// `didReceiveMemoryWarning` or Android's `TRIM_MEMORY_COMPLETE`.
+    Critical,
+}
+
+/// Releases memory `WalletKit` holds for reuse. Hosts call it from their
+/// memory-pressure callbacks.
+///
+/// At every level, each live `CredentialStore` drops its decrypted
+/// credential cache and frees the page caches of its databases, running a
+/// `PASSIVE` WAL checkpoint that never waits for readers or writers. On
+/// [`MemoryPressureLevel::Critical`], the process-wide
+/// `SharedCaches::global` are emptied as well.
+///
+/// Nothing is lost: everything released is rebuilt on next use. Safe to call
+/// from any thread, including the UI thread: stores busy with another
+/// operation are skipped rather than waited for, and the call is cheap when
+/// nothing is cached.
+#[uniffi::export]
+pub fn on_memory_pressure(level: MemoryPressureLevel) {
+    release_memory_of_live_stores();
+    if level == MemoryPressureLevel::Critical {
+        #[cfg(not(target_arch = "wasm32"))]
+        crate::SharedCaches::global().clear();
+    }
+}
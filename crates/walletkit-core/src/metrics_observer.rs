@@ -0,0 +1,283 @@
+//! Push-style metrics for the host's production dashboards.
+//!
+//! A [`MetricsObserver`] installed with [`set_metrics_observer`] is told how
+//! long each stage of a proof generation took and whether cache lookups hit,
+//! as it happens, for every authenticator and store of the process. The
+//! counters of `CredentialStore::metrics` are the pull-style counterpart.
+//!
+//! On native targets observations are delivered on a dedicated thread, never
+//! from inside a `WalletKit` call, for the same reason log messages are (see
+//! [`init_logging`](crate::logger::init_logging)). Stage timings need a
+//! monotonic clock and are not reported on wasm32.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// A stage of proof generation, see [`MetricsObserver::on_stage_timing`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum ProofStage {
+    /// Loading the Merkle inclusion proof from the cache or the indexer.
+    MerkleFetch,
+    /// Deriving the nullifier with the OPRF nodes, including the query proof.
+    NullifierGeneration,
+    /// Generating the witnesses and Groth16 proofs of the credentials.
+    ProofComputation,
+    /// Recording the nullifier and consent entry in the store.
+    CacheWrite,
+}
+
+/// A cache of the `CacheDb`, see [`MetricsObserver::on_cache_lookup`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum CacheKind {
+    /// The account's Merkle inclusion proof.
+    MerkleProof,
+    /// Session `r` seeds of session proofs.
+    SessionSeed,
+    /// JWKS documents of RPs.
+    RpKeys,
+}
+
+/// Receives metrics as they are recorded, see [`set_metrics_observer`].
+#[uniffi::export(with_foreign)]
+pub trait MetricsObserver: Send + Sync {
+    /// A proof generation `stage` finished after `duration_ms`, successfully
+    /// or not.
+    fn on_stage_timing(&self, stage: ProofStage, duration_ms: u64, success: bool);
+
+    /// A lookup in `cache` found a usable entry (`hit`) or not.
+    fn on_cache_lookup(&self, cache: CacheKind, hit: bool);
+}
+
+/// One call to make on the observer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Observation {
+    StageTiming {
+        stage: ProofStage,
+        duration_ms: u64,
+        success: bool,
+    },
+    CacheLookup {
+        cache: CacheKind,
+        hit: bool,
+    },
+}
+
+impl Observation {
+    fn deliver(self, observer: &dyn MetricsObserver) {
+        match self {
+            Self::StageTiming {
+                stage,
+                duration_ms,
+                success,
+            } => observer.on_stage_timing(stage, duration_ms, success),
+            Self::CacheLookup { cache, hit } => observer.on_cache_lookup(cache, hit),
+        }
+    }
+}
+
+/// Feeds the delivery thread of the installed observer, `None` if there is
+/// none. Dropping the sender stops the thread.
+#[cfg(not(target_arch = "wasm32"))]
+static OBSERVER: Mutex<Option<mpsc::Sender<Observation>>> = Mutex::new(None);
+
+/// The installed observer, called directly on wasm32.
+#[cfg(target_arch = "wasm32")]
+static OBSERVER: Mutex<Option<Arc<dyn MetricsObserver>>> = Mutex::new(None);
+
+/// Sends metrics to `observer` from now on, replacing the previous one, or
+/// stops sending them if `None`.
+///
+/// # Panics
+///
+/// Panics if the delivery thread cannot be spawned (native only).
+#[uniffi::export]
+pub fn set_metrics_observer(observer: Option<Arc<dyn MetricsObserver>>) {
+    #[cfg(not(target_arch = "wasm32"))]
+    let observer = observer.map(|observer| {
+        let (sender, receiver) = mpsc::channel::<Observation>();
+        std::thread::Builder::new()
+            .name("walletkit-metrics".into())
+            .spawn(move || {
+                for observation in receiver {
+                    observation.deliver(observer.as_ref());
+                }
+            })
+            .expect("failed to spawn walletkit metrics thread");
+        sender
+    });
+    *OBSERVER.lock().unwrap_or_else(PoisonError::into_inner) = observer;
+}
+
+/// Whether an observer is installed, so measuring can be skipped otherwise.
+#[cfg(not(target_arch = "wasm32"))]
+fn is_observed() -> bool {
+    OBSERVER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn observe(observation: Observation) {
+    if let Some(sender) = OBSERVER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+    {
+        let _ = sender.send(observation);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn observe(observation: Observation) {
+    // cloned so the observer is called without holding the lock
+    let observer = OBSERVER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    if let Some(observer) = observer {
+        observation.deliver(observer.as_ref());
+    }
+}
+
+/// Reports a lookup in `cache`.
+pub(crate) fn record_cache_lookup(cache: CacheKind, hit: bool) {
+    observe(Observation::CacheLookup { cache, hit });
+}
+
+/// Runs the proof generation `stage` and reports how long it took.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn timed<T, E>(
+    stage: ProofStage,
+    future: impl std::future::Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    if !is_observed() {
+        return future.await;
+    }
+    let started = std::time::Instant::now();
+    let output = future.await;
+    record_stage(stage, started, output.is_ok());
+    output
+}
+
+/// Runs the proof generation `stage`; stages are not timed on wasm32.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn timed<T, E>(
+    _stage: ProofStage,
+    future: impl std::future::Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    future.await
+}
+
+/// Synchronous counterpart of [`timed`].
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn timed_sync<T, E>(
+    stage: ProofStage,
+    f: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    if !is_observed() {
+        return f();
+    }
+    let started = std::time::Instant::now();
+    let output = f();
+    record_stage(stage, started, output.is_ok());
+    output
+}
+
+/// Synchronous counterpart of [`timed`].
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn timed_sync<T, E>(
+    _stage: ProofStage,
+    f: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    f()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn record_stage(stage: ProofStage, started: std::time::Instant, success: bool) {
+    observe(Observation::StageTiming {
+        stage,
+        duration_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+        success,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<Observation>>);
+
+    impl MetricsObserver for Recorder {
+        fn on_stage_timing(&self, stage: ProofStage, duration_ms: u64, success: bool) {
+            self.0.lock().unwrap().push(Observation::StageTiming {
+                stage,
+                duration_ms,
+                success,
+            });
+        }
+
+        fn on_cache_lookup(&self, cache: CacheKind, hit: bool) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(Observation::CacheLookup { cache, hit });
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observer_receives_stage_timings_and_cache_lookups() {
+        let recorder = Arc::new(Recorder::default());
+        set_metrics_observer(Some(recorder.clone()));
+
+        let output = timed(ProofStage::NullifierGeneration, async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok::<_, ()>(7)
+        })
+        .await;
+        assert_eq!(output, Ok(7));
+        assert_eq!(
+            timed_sync(ProofStage::CacheWrite, || Err::<(), _>("full")),
+            Err("full")
+        );
+        record_cache_lookup(CacheKind::RpKeys, true);
+
+        // other tests may record concurrently, so look for ours only
+        let delivered = |recorder: &Recorder| {
+            let observations = recorder.0.lock().unwrap();
+            let nullifier = observations.iter().any(|observation| {
+                matches!(
+                    observation,
+                    Observation::StageTiming {
+                        stage: ProofStage::NullifierGeneration,
+                        duration_ms,
+                        success: true,
+                    } if *duration_ms >= 20
+                )
+            });
+            let cache_write = observations.contains(&Observation::StageTiming {
+                stage: ProofStage::CacheWrite,
+                duration_ms: 0,
+                success: false,
+            });
+            let lookup = observations.contains(&Observation::CacheLookup {
+                cache: CacheKind::RpKeys,
+                hit: true,
+            });
+            drop(observations);
+            nullifier && cache_write && lookup
+        };
+        for _ in 0..100 {
+            if delivered(&recorder) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        set_metrics_observer(None);
+        assert!(delivered(&recorder));
+    }
+}
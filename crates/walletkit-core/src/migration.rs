@@ -0,0 +1,402 @@
+//! A seed may back a legacy World ID 3.0 identity (a Semaphore identity
+//! commitment inserted by the sign up sequencer), a 4.0 account (an
+//! authenticator registered in the `WorldIDRegistry`), both, or neither.
+//! [`assess`] tells these apart and [`upgrade`] registers the 4.0 account.
+//!
+//! The 4.0 authenticator keys are derived from the same seed as the v3
+//! identity, but the registry cannot attest to a v3 inclusion: the Orb
+//! credential is not carried over by [`upgrade`] and must be issued to the new
+//! account.
+
+use std::sync::Arc;
+
+use alloy_core::primitives::Address;
+use serde::{Deserialize, Serialize};
+use world_id_core::{
+    primitives::Config, Authenticator as CoreAuthenticator, AuthenticatorError, Signer,
+};
+
+use crate::{
+    authenticator::parse_config,
+    defaults,
+    error::WalletKitError,
+    limits::InputLimit,
+    primitives::ParseFromForeignBinding,
+    runtime::compat,
+    storage::inventory::{InventoryEntry, RetentionPolicy},
+    storage::AtomicBlobStore,
+    v3::{merkle_tree::MerkleTreeProof, world_id::WorldId, CredentialType},
+    Environment, InitializingAuthenticator,
+};
+
+/// Prefix of the blob recording an [`upgrade`] for an authenticator address.
+const UPGRADE_MARKER_PREFIX: &str = "v4_migration_";
+
+/// What upgrades store, see [`data_inventory`](crate::storage::inventory::data_inventory).
+pub(crate) const INVENTORY: &[InventoryEntry] = &[InventoryEntry {
+    name: "upgrade_marker",
+    description: "The gateway request ID of a 4.0 account registration \
+        submitted by `upgrade`, named after the authenticator's address, so \
+        the registration can be polled after a restart.",
+    locations: &["v4_migration_<authenticator address>"],
+    contains_personal_data: true,
+    retention: RetentionPolicy::UntilDeleted,
+}];
+
+/// Inclusion status of a v3 Orb identity commitment in the sign up sequencer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum V3InclusionStatus {
+    /// The identity commitment was never inserted.
+    NotIssued,
+    /// The identity commitment was inserted but is not yet mined.
+    Pending,
+    /// The identity commitment is mined.
+    Mined,
+}
+
+/// Which identities a seed backs, as reported by [`assess`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Record)]
+pub struct MigrationAssessment {
+    /// Whether the seed has a v3 Orb identity, mined or pending.
+    pub has_v3_identity: bool,
+    /// Inclusion status of the v3 Orb identity commitment.
+    pub v3_inclusion_status: V3InclusionStatus,
+    /// Whether a 4.0 account is registered for the seed's authenticator.
+    pub has_v4_account: bool,
+}
+
+/// Contents of the blob recording an [`upgrade`].
+#[derive(Debug, Serialize, Deserialize)]
+struct UpgradeMarker {
+    request_id: String,
+}
+
+/// Checks whether `seed` has a v3 identity and a 4.0 account in `environment`.
+///
+/// Makes one request to the Orb sign up sequencer and one to the indexer.
+///
+/// # Errors
+/// Returns an error if either request fails.
+#[uniffi::export(async_runtime = "tokio")]
+pub async fn assess(
+    seed: &[u8],
+    environment: &Environment,
+) -> Result<MigrationAssessment, WalletKitError> {
+    let config = defaults::default_config(environment, None, None)?;
+    let sequencer_host = CredentialType::Orb.get_sign_up_sequencer_host(environment);
+    assess_with(seed, environment, config, sequencer_host).await
+}
+
+/// Checks whether `seed` has a v3 identity and a 4.0 account, against a custom
+/// authenticator config and sign up sequencer.
+///
+/// # Errors
+/// Returns an error if the config is invalid or either request fails.
+#[uniffi::export(async_runtime = "tokio")]
+pub async fn assess_with_config(
+    seed: &[u8],
+    environment: &Environment,
+    config: &str,
+    sequencer_host: &str,
+) -> Result<MigrationAssessment, WalletKitError> {
+    let config = parse_config(config)?;
+    assess_with(seed, environment, config, sequencer_host).await
+}
+
+async fn assess_with(
+    seed: &[u8],
+    environment: &Environment,
+    config: Config,
+    sequencer_host: &str,
+) -> Result<MigrationAssessment, WalletKitError> {
+    InputLimit::Seed.check(seed.len())?;
+
+    let identity_commitment =
+        WorldId::new(seed, environment).get_identity_commitment(&CredentialType::Orb);
+    let v3_inclusion_status = match MerkleTreeProof::from_identity_commitment(
+        &identity_commitment,
+        sequencer_host,
+        true,
+    )
+    .await
+    {
+        Ok(_) => V3InclusionStatus::Mined,
+        Err(WalletKitError::CredentialNotMined) => V3InclusionStatus::Pending,
+        Err(WalletKitError::CredentialNotIssued) => V3InclusionStatus::NotIssued,
+        Err(error) => return Err(error),
+    };
+
+    let has_v4_account = match compat(CoreAuthenticator::init(seed, config)).await {
+        Ok(_) => true,
+        Err(AuthenticatorError::AccountDoesNotExist) => false,
+        Err(error) => return Err(error.into()),
+    };
+
+    Ok(MigrationAssessment {
+        has_v3_identity: v3_inclusion_status != V3InclusionStatus::NotIssued,
+        v3_inclusion_status,
+        has_v4_account,
+    })
+}
+
+/// Registers a 4.0 account for the seed of a v3 identity.
+///
+/// The registration is recorded in `marker_store`, and later calls with the
+/// same seed fail with [`WalletKitError::MigrationAlreadySubmitted`] instead
+/// of registering again. Once the registration is finalized, initialize an
+/// `Authenticator` with the seed; if it failed, call [`reset_upgrade`] before
+/// retrying.
+///
+/// # Errors
+/// Returns [`WalletKitError::MigrationAlreadySubmitted`] if the seed was
+/// already upgraded, or an error if the input is invalid or the gateway
+/// rejects the registration.
+#[uniffi::export(async_runtime = "tokio")]
+pub async fn upgrade(
+    seed: &[u8],
+    config: &str,
+    recovery_address: Option<String>,
+    marker_store: Arc<dyn AtomicBlobStore>,
+) -> Result<Arc<InitializingAuthenticator>, WalletKitError> {
+    let recovery_address =
+        Address::parse_from_ffi_optional(recovery_address, "recovery_address")?;
+    let config = parse_config(config)?;
+    let marker_path = upgrade_marker_path(seed)?;
+
+    if let Some(bytes) = marker_store.read(marker_path.clone())? {
+        let marker: UpgradeMarker =
+            serde_json::from_slice(&bytes).map_err(|error| {
+                WalletKitError::SerializationError {
+                    error: format!("invalid upgrade marker: {error}"),
+                }
+            })?;
+        return Err(WalletKitError::MigrationAlreadySubmitted {
+            request_id: marker.request_id,
+        });
+    }
+
+    let initializing_authenticator =
+        InitializingAuthenticator::register_with_config(seed, config, recovery_address)
+            .await?;
+
+    let marker = UpgradeMarker {
+        request_id: initializing_authenticator.request_id().to_string(),
+    };
+    let bytes = serde_json::to_vec(&marker).map_err(|error| {
+        WalletKitError::SerializationError {
+            error: error.to_string(),
+        }
+    })?;
+    // the registration is already submitted, so a failed write must not lose
+    // the handle to poll it
+    if let Err(error) = marker_store.write_atomic(marker_path, bytes) {
+        tracing::warn!("failed to record upgrade marker: {error}");
+    }
+
+    Ok(Arc::new(initializing_authenticator))
+}
+
+/// Forgets an [`upgrade`] of `seed`, so it can be retried after the
+/// registration failed.
+///
+/// # Errors
+/// Returns an error if the seed is invalid or the marker cannot be deleted.
+#[uniffi::export]
+pub fn reset_upgrade(
+    seed: &[u8],
+    marker_store: Arc<dyn AtomicBlobStore>,
+) -> Result<(), WalletKitError> {
+    marker_store.delete(upgrade_marker_path(seed)?)?;
+    Ok(())
+}
+
+/// Path of the upgrade marker, keyed by the seed's authenticator address.
+fn upgrade_marker_path(seed: &[u8]) -> Result<String, WalletKitError> {
+    InputLimit::Seed.check(seed.len())?;
+    let signer = Signer::from_seed_bytes(seed)?;
+    Ok(format!(
+        "{UPGRADE_MARKER_PREFIX}{}",
+        signer.onchain_signer_address().to_checksum(None)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+    use ruint::aliases::U256;
+    use world_id_core::primitives::ServiceEndpoint;
+
+    use super::*;
+    use crate::storage::tests_utils::InMemoryBlobStore;
+
+    const SEED: [u8; 32] = [7u8; 32];
+
+    fn config(server: &mockito::ServerGuard) -> Config {
+        Config::new(
+            None,
+            480,
+            address!("0x969947cFED008bFb5e3F32a25A1A2CDdf64d46fe"),
+            ServiceEndpoint::direct(server.url()),
+            ServiceEndpoint::direct(server.url()),
+            vec![],
+            2,
+        )
+        .unwrap()
+    }
+
+    /// Serves `/inclusionProof` as the sequencer does for `status`.
+    async fn mock_sequencer(
+        server: &mut mockito::ServerGuard,
+        status: V3InclusionStatus,
+    ) -> mockito::Mock {
+        let mock = server.mock("POST", "/inclusionProof");
+        let mock = match status {
+            V3InclusionStatus::NotIssued => mock
+                .with_status(400)
+                .with_body("provided identity commitment not found"),
+            V3InclusionStatus::Pending => mock.with_status(200).with_body(
+                include_str!("../tests/v3/fixtures/inclusion_proof.json")
+                    .replace("\"mined\"", "\"pending\""),
+            ),
+            V3InclusionStatus::Mined => mock
+                .with_status(200)
+                .with_body(include_str!("../tests/v3/fixtures/inclusion_proof.json")),
+        };
+        mock.create_async().await
+    }
+
+    /// Serves `/packed-account` for a registered or unregistered account.
+    async fn mock_indexer(
+        server: &mut mockito::ServerGuard,
+        has_account: bool,
+    ) -> mockito::Mock {
+        // pubkey ID 1 above the leaf index 5; all zero when unregistered
+        let packed_account_data = if has_account {
+            (U256::from(1) << 192) | U256::from(5)
+        } else {
+            U256::ZERO
+        };
+        server
+            .mock("POST", "/packed-account")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "packed_account_data": format!("{packed_account_data:#x}"),
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await
+    }
+
+    async fn assess_against(
+        v3_status: V3InclusionStatus,
+        has_v4_account: bool,
+    ) -> MigrationAssessment {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let mut server = mockito::Server::new_async().await;
+        let sequencer_mock = mock_sequencer(&mut server, v3_status).await;
+        let indexer_mock = mock_indexer(&mut server, has_v4_account).await;
+
+        let assessment =
+            assess_with(&SEED, &Environment::Staging, config(&server), &server.url())
+                .await
+                .unwrap();
+        sequencer_mock.assert_async().await;
+        indexer_mock.assert_async().await;
+        assessment
+    }
+
+    #[tokio::test]
+    async fn test_assess_each_combination() {
+        for v3_status in [
+            V3InclusionStatus::NotIssued,
+            V3InclusionStatus::Pending,
+            V3InclusionStatus::Mined,
+        ] {
+            for has_v4_account in [false, true] {
+                let assessment = assess_against(v3_status, has_v4_account).await;
+                assert_eq!(
+                    assessment,
+                    MigrationAssessment {
+                        has_v3_identity: v3_status != V3InclusionStatus::NotIssued,
+                        v3_inclusion_status: v3_status,
+                        has_v4_account,
+                    }
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_assess_propagates_sequencer_errors() {
+        let mut server = mockito::Server::new_async().await;
+        let _sequencer_mock = server
+            .mock("POST", "/inclusionProof")
+            .with_status(500)
+            .with_body("internal error")
+            .create_async()
+            .await;
+
+        let error =
+            assess_with(&SEED, &Environment::Staging, config(&server), &server.url())
+                .await
+                .unwrap_err();
+        assert!(
+            matches!(error, WalletKitError::SerializationError { .. }),
+            "unexpected error: {error:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_registers_once() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let mut server = mockito::Server::new_async().await;
+        let gateway_mock = server
+            .mock("POST", "/create-account")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "request_id": "gw_upgrade",
+                    "kind": "create_account",
+                    "status": { "state": "queued" },
+                })
+                .to_string(),
+            )
+            .expect(3)
+            .create_async()
+            .await;
+        let config = serde_json::to_string(&config(&server)).unwrap();
+        let marker_store: Arc<dyn AtomicBlobStore> = Arc::new(InMemoryBlobStore::new());
+
+        let initializing = upgrade(&SEED, &config, None, Arc::clone(&marker_store))
+            .await
+            .unwrap();
+        assert_eq!(initializing.request_id(), "gw_upgrade");
+
+        let Err(error) = upgrade(&SEED, &config, None, Arc::clone(&marker_store)).await
+        else {
+            panic!("upgraded the same seed twice");
+        };
+        assert!(
+            matches!(
+                &error,
+                WalletKitError::MigrationAlreadySubmitted { request_id }
+                    if request_id == "gw_upgrade"
+            ),
+            "unexpected error: {error:?}"
+        );
+
+        // another seed has its own marker
+        upgrade(&[8u8; 32], &config, None, Arc::clone(&marker_store))
+            .await
+            .unwrap();
+
+        // after a reset the seed registers again
+        reset_upgrade(&SEED, Arc::clone(&marker_store)).unwrap();
+        upgrade(&SEED, &config, None, marker_store).await.unwrap();
+        gateway_mock.assert_async().await;
+    }
+}
@@ -223,7 +223,7 @@ mod tests {
             requests: items,
             constraints,
         };
-        ProofRequest(core)
+        ProofRequest::from(core)
     }
 
     fn store_with_credentials(
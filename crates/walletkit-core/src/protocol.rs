@@ -0,0 +1,146 @@
+//! Protocol parameters for configuring World ID verifiers.
+//!
+//! Every value is read from the constants the proving code uses, so what is
+//! reported here cannot drift from what proofs are generated against.
+
+use ruint::aliases::U256;
+use world_id_core::primitives::TREE_DEPTH;
+use world_id_core::proof::{
+    NULLIFIER_GRAPH_FINGERPRINT, NULLIFIER_ZKEY_FINGERPRINT, QUERY_GRAPH_FINGERPRINT,
+    QUERY_ZKEY_FINGERPRINT,
+};
+use world_id_core::requests::RequestVersion;
+use world_id_core::FieldElement as CoreFieldElement;
+
+use crate::{defaults, Environment};
+
+/// Hash function of the `WorldIDRegistry` Merkle tree: the Poseidon2
+/// permutation over BN254 with state width 2, in compression mode.
+pub const MERKLE_HASH_FUNCTION: &str = "poseidon2-bn254-t2-compress";
+
+/// [`TREE_DEPTH`] as exported.
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "asserted to fit at compile time"
+)]
+const TREE_DEPTH_U32: u32 = {
+    assert!(TREE_DEPTH <= u32::MAX as usize);
+    TREE_DEPTH as u32
+};
+
+// the circuit identifiers are hex-encoded SHA-256 digests
+const _: () = assert!(
+    QUERY_ZKEY_FINGERPRINT.len() == 64
+        && QUERY_GRAPH_FINGERPRINT.len() == 64
+        && NULLIFIER_ZKEY_FINGERPRINT.len() == 64
+        && NULLIFIER_GRAPH_FINGERPRINT.len() == 64
+);
+
+/// The World ID 4.0 protocol parameters proofs are generated against, see
+/// [`protocol_parameters`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct ProtocolParameters {
+    /// Depth of the `WorldIDRegistry` Merkle tree.
+    pub tree_depth: u32,
+    /// Hash function of the Merkle tree, see [`MERKLE_HASH_FUNCTION`].
+    pub merkle_hash_function: String,
+    /// Modulus of the field of all circuit inputs and outputs (the BN254
+    /// scalar field), `0x`-prefixed lowercase hex.
+    pub field_modulus: String,
+    /// Checksummed address of the `WorldIDRegistry` contract.
+    pub registry_address: String,
+    /// SHA-256 of the query circuit (`OPRFQuery`) proving key.
+    pub query_zkey_fingerprint: String,
+    /// SHA-256 of the query circuit witness graph.
+    pub query_graph_fingerprint: String,
+    /// SHA-256 of the nullifier circuit (`OPRFNullifier`) proving key.
+    pub nullifier_zkey_fingerprint: String,
+    /// SHA-256 of the nullifier circuit witness graph.
+    pub nullifier_graph_fingerprint: String,
+    /// Proof request versions accepted by
+    /// [`ProofRequest::from_json`](crate::requests::ProofRequest::from_json).
+    pub supported_request_versions: Vec<u8>,
+}
+
+/// Returns the protocol parameters used for `environment`.
+///
+/// Only the registry address differs between environments; the circuits and
+/// the tree are the same.
+#[uniffi::export]
+#[must_use]
+pub fn protocol_parameters(environment: &Environment) -> ProtocolParameters {
+    ProtocolParameters {
+        tree_depth: TREE_DEPTH_U32,
+        merkle_hash_function: MERKLE_HASH_FUNCTION.to_string(),
+        field_modulus: format!("{:#x}", field_modulus()),
+        registry_address: defaults::world_id_registry_address(environment).to_string(),
+        query_zkey_fingerprint: QUERY_ZKEY_FINGERPRINT.to_string(),
+        query_graph_fingerprint: QUERY_GRAPH_FINGERPRINT.to_string(),
+        nullifier_zkey_fingerprint: NULLIFIER_ZKEY_FINGERPRINT.to_string(),
+        nullifier_graph_fingerprint: NULLIFIER_GRAPH_FINGERPRINT.to_string(),
+        supported_request_versions: supported_request_versions(),
+    }
+}
+
+/// The modulus of the protocol's field, as one more than its largest element.
+fn field_modulus() -> U256 {
+    let largest = CoreFieldElement::from(-*CoreFieldElement::ONE);
+    largest.to_u256() + U256::from(1)
+}
+
+/// The versions the protocol's request parser accepts.
+fn supported_request_versions() -> Vec<u8> {
+    (0..=u8::MAX)
+        .filter(|version| {
+            serde_json::from_value::<RequestVersion>((*version).into()).is_ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expected(registry_address: &str) -> ProtocolParameters {
+        ProtocolParameters {
+            tree_depth: 30,
+            merkle_hash_function: "poseidon2-bn254-t2-compress".to_string(),
+            field_modulus:
+                "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001"
+                    .to_string(),
+            registry_address: registry_address.to_string(),
+            query_zkey_fingerprint:
+                "616c98c6ba024b5a4015d3ebfd20f6cab12e1e33486080c5167a4bcfac111798"
+                    .to_string(),
+            query_graph_fingerprint:
+                "6b0cb90304c510f9142a555fe2b7cf31b9f68f6f37286f4471fd5d03e91da311"
+                    .to_string(),
+            nullifier_zkey_fingerprint:
+                "4247e6bfe1af211e72d3657346802e1af00e6071fb32429a200f9fc0a25a36f9"
+                    .to_string(),
+            nullifier_graph_fingerprint:
+                "c1d951716e3b74b72e4ea0429986849cadc43cccc630a7ee44a56a6199a66b9a"
+                    .to_string(),
+            supported_request_versions: vec![1],
+        }
+    }
+
+    #[test]
+    fn test_protocol_parameters_snapshot() {
+        assert_eq!(
+            protocol_parameters(&Environment::Staging),
+            expected("0x8556d07D75025f286fe757C7EeEceC40D54FA16D")
+        );
+        assert_eq!(
+            protocol_parameters(&Environment::Production),
+            expected("0x0000000000aE079eB8a274cD51c0f44a9E4d67d4")
+        );
+    }
+
+    #[test]
+    fn test_field_modulus_bounds_field_elements() {
+        let modulus = field_modulus();
+        assert!(CoreFieldElement::try_from(modulus - U256::from(1)).is_ok());
+        assert!(CoreFieldElement::try_from(modulus).is_err());
+    }
+}
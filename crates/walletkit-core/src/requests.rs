@@ -1,29 +1,63 @@
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ruint::aliases::U256;
+use ruint_uniffi::Uint256;
+use serde::Serialize;
+use serde_json::Value;
 use world_id_core::requests::{
     ProofRequest as CoreProofRequest, ProofResponse as CoreProofResponse,
 };
 
 use crate::error::WalletKitError;
+use crate::jws::{CompactJws, JwsClaims, RpKeyResolver};
+use crate::limits::InputLimit;
+
+/// JSON field carrying the wrapper-level validity window, see
+/// [`ProofRequest::valid_until`].
+const VALID_UNTIL_FIELD: &str = "valid_until";
+
+/// JSON field carrying the wrapper-level registry chain, see
+/// [`ProofRequest::chain_id`].
+const CHAIN_ID_FIELD: &str = "chain_id";
+
+/// Custom URI scheme of World App deep links.
+const DEEP_LINK_SCHEME: &str = "worldcoin";
+
+/// Host of the `https` universal links to World App.
+const DEEP_LINK_HOST: &str = "id.worldcoin.org";
+
+/// Query parameter of a deep link that carries the request.
+const DEEP_LINK_REQUEST_PARAM: &str = "request";
 
 /// A request from the RP to the Authenticator. See [`CoreProofRequest`] for more details.
 /// This is a wrapper type to expose to foreign language bindings.
+///
+/// The second field is the optional `valid_until` timestamp, see
+/// [`valid_until`](Self::valid_until). The third is the compact JWS the request
+/// was imported from, see [`jws`](Self::jws). The fourth is the optional chain
+/// ID, see [`chain_id`](Self::chain_id).
 #[derive(Debug, Clone, uniffi::Object)]
-pub struct ProofRequest(pub(crate) CoreProofRequest);
+pub struct ProofRequest(
+    pub(crate) CoreProofRequest,
+    pub(crate) Option<u64>,
+    pub(crate) Option<String>,
+    pub(crate) Option<u64>,
+);
 
 #[uniffi::export]
 impl ProofRequest {
     /// Deserializes a `ProofRequest` from a JSON string.
     ///
     /// # Errors
-    /// Returns an error if the JSON is invalid or cannot be parsed.
+    /// Returns an error if the JSON exceeds [`MAX_PROOF_REQUEST_BYTES`](crate::limits::MAX_PROOF_REQUEST_BYTES), is
+    /// invalid or cannot be parsed.
     #[uniffi::constructor]
     pub fn from_json(json: &str) -> Result<Self, WalletKitError> {
-        let core_request = CoreProofRequest::from_json(json).map_err(|e| {
-            WalletKitError::InvalidInput {
-                attribute: "proof_request".to_string(),
-                reason: format!("invalid proof request json: {e}"),
-            }
-        })?;
-        Ok(Self(core_request))
+        InputLimit::ProofRequest.check(json.len())?;
+        let value: Value = serde_json::from_str(json)
+            .map_err(|e| invalid_request(format!("invalid proof request json: {e}")))?;
+        Self::from_value(value)
     }
 
     /// Serializes the proof request to a JSON string.
@@ -31,9 +65,18 @@ impl ProofRequest {
     /// # Errors
     /// Returns an error if serialization fails.
     pub fn to_json(&self) -> Result<String, WalletKitError> {
-        serde_json::to_string(&self.0).map_err(|e| WalletKitError::Generic {
-            error: format!("critical unexpected error serializing to json: {e}"),
-        })
+        let json = to_json_with_valid_until(&self.0, self.1)?;
+        let Some(chain_id) = self.3 else {
+            return Ok(json);
+        };
+        let mut value: Value =
+            serde_json::from_str(&json).map_err(|e| WalletKitError::Generic {
+                error: format!("critical unexpected error serializing to json: {e}"),
+            })?;
+        if let Some(object) = value.as_object_mut() {
+            object.insert(CHAIN_ID_FIELD.to_string(), chain_id.into());
+        }
+        Ok(value.to_string())
     }
 
     /// Returns the unique identifier for this request.
@@ -47,24 +90,176 @@ impl ProofRequest {
     pub const fn version(&self) -> u8 {
         self.0.version as u8
     }
+
+    /// Returns the time (unix seconds) after which the RP no longer accepts a
+    /// response to this request, if it set one.
+    ///
+    /// Proofs are not refused for requests already past it. This is enforced
+    /// by the wrapper only, not by the circuit: the proof itself stays valid,
+    /// and the RP must check `valid_until` (echoed in the response) on its
+    /// side too.
+    #[must_use]
+    pub const fn valid_until(&self) -> Option<u64> {
+        self.1
+    }
+
+    /// Returns the chain ID of the World ID registry the RP expects the proof
+    /// against (e.g. `480` for World Chain), if it set one.
+    ///
+    /// Proof generation refuses a request whose chain differs from the
+    /// authenticator's with [`WalletKitError::EnvironmentMismatch`], so a
+    /// staging request never leaves replay guard entries in production.
+    /// Requests without it are not checked.
+    #[must_use]
+    pub const fn chain_id(&self) -> Option<u64> {
+        self.3
+    }
+
+    /// Returns the compact JWS the request was imported from with
+    /// [`from_jws`](Self::from_jws), kept so the RP's signature can be
+    /// audited later. `None` for requests parsed from plain JSON.
+    #[must_use]
+    pub fn jws(&self) -> Option<String> {
+        self.2.clone()
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl ProofRequest {
+    /// Imports a proof request from a compact JWS signed by the RP.
+    ///
+    /// The payload is the request JSON, as accepted by
+    /// [`from_json`](Self::from_json), plus the `iat` and `exp` claims. The
+    /// verifying key is resolved from the request's `rp_id` and the header
+    /// `kid`. Only `ES256` and `EdDSA` are accepted. The token is kept, see
+    /// [`jws`](Self::jws).
+    ///
+    /// # Arguments
+    /// * `token` - The compact JWS.
+    /// * `resolver` - Resolves the RP's signing key.
+    /// * `now` - Current time in seconds since the UNIX epoch.
+    ///
+    /// # Errors
+    /// - [`WalletKitError::JwsAlgorithmNotAllowed`] if `alg` is not allowed or
+    ///   does not match the resolved key.
+    /// - [`WalletKitError::JwsKeyNotFound`] if the RP has no key for the `kid`.
+    /// - [`WalletKitError::JwsInvalidSignature`] if the signature does not verify.
+    /// - [`WalletKitError::JwsExpired`] if `exp` has passed.
+    /// - [`WalletKitError::JwsIssuedInFuture`] if `iat` is in the future.
+    /// - [`WalletKitError::InvalidInput`] if the token or the request is malformed.
+    #[uniffi::constructor]
+    pub async fn from_jws(
+        token: &str,
+        resolver: Arc<dyn RpKeyResolver>,
+        now: u64,
+    ) -> Result<Self, WalletKitError> {
+        InputLimit::ProofRequest.check(token.len())?;
+        let jws = CompactJws::parse(token)?;
+        let mut payload = jws.payload()?;
+        let claims = JwsClaims::take(&mut payload)?;
+        let mut request = Self::from_value(Value::Object(payload))?;
+
+        let key = resolver
+            .resolve_key(request.0.rp_id.to_string(), jws.kid().to_string(), now)
+            .await?;
+        jws.verify(&key)?;
+        claims.check(now)?;
+
+        request.2 = Some(token.to_string());
+        Ok(request)
+    }
+}
+
+impl ProofRequest {
+    /// Parses a request from its JSON value, taking out the wrapper-level
+    /// `valid_until` and `chain_id` fields.
+    fn from_value(mut value: Value) -> Result<Self, WalletKitError> {
+        let valid_until = take_valid_until(&mut value).map_err(invalid_request)?;
+        let chain_id = take_chain_id(&mut value).map_err(invalid_request)?;
+        let core_request = CoreProofRequest::from_json(&value.to_string())
+            .map_err(|e| invalid_request(format!("invalid proof request json: {e}")))?;
+        Ok(Self(core_request, valid_until, None, chain_id))
+    }
+
+    /// Checks that the request is for the registry on `configured_chain`, if
+    /// it names a chain.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::EnvironmentMismatch`] if it is not.
+    pub(crate) const fn check_chain_id(
+        &self,
+        configured_chain: u64,
+    ) -> Result<(), WalletKitError> {
+        match self.3 {
+            Some(request_chain) if request_chain != configured_chain => {
+                Err(WalletKitError::EnvironmentMismatch {
+                    request_chain,
+                    configured_chain,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the request's `valid_until` has not passed at `now`.
+    ///
+    /// # Errors
+    /// Returns [`WalletKitError::ProofRequestExpired`] if it has.
+    pub(crate) const fn check_valid_until(
+        &self,
+        now: u64,
+    ) -> Result<(), WalletKitError> {
+        match self.1 {
+            Some(valid_until) if now > valid_until => {
+                Err(WalletKitError::ProofRequestExpired {
+                    expired_at: valid_until,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 /// A response from the Authenticator to the RP. See [`CoreProofResponse`] for more details.
 ///
 /// This is a wrapper type to expose to foreign language bindings.
+///
+/// The second field echoes the request's
+/// [`valid_until`](ProofRequest::valid_until).
 #[derive(Debug, Clone, uniffi::Object)]
-pub struct ProofResponse(pub CoreProofResponse);
+pub struct ProofResponse(pub CoreProofResponse, pub(crate) Option<u64>);
 
 #[uniffi::export]
 impl ProofResponse {
+    /// Deserializes a `ProofResponse` from a JSON string, as produced by
+    /// [`to_json`](Self::to_json).
+    ///
+    /// # Errors
+    /// Returns an error if the JSON exceeds [`MAX_PROOF_REQUEST_BYTES`](crate::limits::MAX_PROOF_REQUEST_BYTES), is
+    /// invalid or cannot be parsed.
+    #[uniffi::constructor]
+    pub fn from_json(json: &str) -> Result<Self, WalletKitError> {
+        InputLimit::ProofRequest.check(json.len())?;
+        let invalid_response = |reason: String| WalletKitError::InvalidInput {
+            attribute: "proof_response".to_string(),
+            reason,
+        };
+        let mut value: Value = serde_json::from_str(json).map_err(|e| {
+            invalid_response(format!("invalid proof response json: {e}"))
+        })?;
+        let valid_until = take_valid_until(&mut value).map_err(invalid_response)?;
+        let core_response = serde_json::from_value(value).map_err(|e| {
+            invalid_response(format!("invalid proof response json: {e}"))
+        })?;
+        Ok(Self(core_response, valid_until))
+    }
+
     /// Serializes the proof response to a JSON string.
     ///
     /// # Errors
     /// Returns an error if serialization fails.
     pub fn to_json(&self) -> Result<String, WalletKitError> {
-        serde_json::to_string(&self.0).map_err(|e| WalletKitError::Generic {
-            error: format!("critical unexpected error serializing to json: {e}"),
-        })
+        to_json_with_valid_until(&self.0, self.1)
     }
 
     /// Returns the unique identifier for this response.
@@ -84,9 +279,41 @@ impl ProofResponse {
     pub fn error(&self) -> Option<String> {
         self.0.error.clone()
     }
+
+    /// Returns the request's `valid_until`, see
+    /// [`ProofRequest::valid_until`].
+    #[must_use]
+    pub const fn valid_until(&self) -> Option<u64> {
+        self.1
+    }
+
+    /// Returns whether the response is past its request's `valid_until` at
+    /// `now`, and should no longer be sent. Always `false` if the request
+    /// set none.
+    #[must_use]
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.1.is_some_and(|valid_until| now > valid_until)
+    }
+
+    /// Returns the root of the `WorldIDRegistry` Merkle tree the proofs were
+    /// generated against, which on-chain verifiers check them with. `None`
+    /// if the request failed.
+    #[must_use]
+    pub fn root(&self) -> Option<Uint256> {
+        self.merkle_root().map(Into::into)
+    }
 }
 
 impl ProofResponse {
+    /// Returns the Merkle root encoded in the first proof. All proofs of a
+    /// response are generated against the same root.
+    pub(crate) fn merkle_root(&self) -> Option<U256> {
+        self.0
+            .responses
+            .first()
+            .map(|item| item.proof.as_ethereum_representation()[4])
+    }
+
     /// Consumes the wrapper and returns the inner `CoreProofResponse`.
     #[must_use]
     pub fn into_inner(self) -> CoreProofResponse {
@@ -96,16 +323,166 @@ impl ProofResponse {
 
 impl From<CoreProofRequest> for ProofRequest {
     fn from(core_request: CoreProofRequest) -> Self {
-        Self(core_request)
+        Self(core_request, None, None, None)
     }
 }
 
 impl From<CoreProofResponse> for ProofResponse {
     fn from(core_response: CoreProofResponse) -> Self {
-        Self(core_response)
+        Self(core_response, None)
     }
 }
 
+/// Parses a proof request from a deep link or the text of a QR code, and
+/// checks that it is still valid at `now`.
+///
+/// Accepted are `worldcoin://…` and `https://id.worldcoin.org/…` links whose
+/// `request` query parameter holds the request, as well as a bare request
+/// (e.g. a QR code without a link). The request is either a compact JWS
+/// signed by the RP, verified as in [`ProofRequest::from_jws`], or the
+/// request JSON, plain or base64url-encoded.
+///
+/// # Errors
+/// - [`WalletKitError::InvalidInput`] if `uri` exceeds
+///   [`MAX_PROOF_REQUEST_URI_BYTES`](crate::limits::MAX_PROOF_REQUEST_URI_BYTES),
+///   the link or request is malformed, or the request is signed and no
+///   `resolver` is given.
+/// - [`WalletKitError::ProofRequestExpired`] if the request's `expires_at`
+///   or `valid_until` has passed.
+/// - The JWS errors of [`ProofRequest::from_jws`] for signed requests.
+#[uniffi::export(async_runtime = "tokio")]
+pub async fn parse_proof_request_uri(
+    uri: &str,
+    resolver: Option<Arc<dyn RpKeyResolver>>,
+    now: u64,
+) -> Result<Arc<ProofRequest>, WalletKitError> {
+    InputLimit::ProofRequestUri.check(uri.len())?;
+    let payload = deep_link_payload(uri.trim())?;
+    let request = if is_compact_jws(&payload) {
+        let resolver = resolver.ok_or_else(|| {
+            invalid_uri("signed proof requests need an RP key resolver".to_string())
+        })?;
+        ProofRequest::from_jws(&payload, resolver, now).await?
+    } else if payload.starts_with('{') {
+        ProofRequest::from_json(&payload)?
+    } else {
+        InputLimit::ProofRequest.check(payload.len() / 4 * 3)?;
+        let json = URL_SAFE_NO_PAD
+            .decode(payload.trim_end_matches('='))
+            .ok()
+            .and_then(|json| String::from_utf8(json).ok())
+            .ok_or_else(|| {
+                invalid_uri(
+                    "request is neither JSON, base64url JSON nor a JWS".to_string(),
+                )
+            })?;
+        ProofRequest::from_json(&json)?
+    };
+
+    if now > request.0.expires_at {
+        return Err(WalletKitError::ProofRequestExpired {
+            expired_at: request.0.expires_at,
+        });
+    }
+    request.check_valid_until(now)?;
+    Ok(Arc::new(request))
+}
+
+/// Returns the request carried by the deep link `uri`, or `uri` itself if it
+/// is a bare request.
+fn deep_link_payload(uri: &str) -> Result<String, WalletKitError> {
+    if !uri.contains("://") {
+        return Ok(uri.to_string());
+    }
+    let url = reqwest::Url::parse(uri)
+        .map_err(|e| invalid_uri(format!("not a proof request link: {e}")))?;
+    let recognized = match url.scheme() {
+        DEEP_LINK_SCHEME => true,
+        "https" => url.host_str() == Some(DEEP_LINK_HOST),
+        _ => false,
+    };
+    if !recognized {
+        return Err(invalid_uri(format!(
+            "unsupported link {}://{}",
+            url.scheme(),
+            url.host_str().unwrap_or_default()
+        )));
+    }
+    url.query_pairs()
+        .find(|(name, _)| name == DEEP_LINK_REQUEST_PARAM)
+        .map(|(_, value)| value.into_owned())
+        .ok_or_else(|| {
+            invalid_uri(format!("link has no `{DEEP_LINK_REQUEST_PARAM}` parameter"))
+        })
+}
+
+/// Whether `payload` has the shape of a compact JWS: three base64url
+/// segments separated by dots.
+fn is_compact_jws(payload: &str) -> bool {
+    payload.split('.').count() == 3
+        && payload
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
+fn invalid_uri(reason: String) -> WalletKitError {
+    WalletKitError::InvalidInput {
+        attribute: "uri".to_string(),
+        reason,
+    }
+}
+
+fn invalid_request(reason: String) -> WalletKitError {
+    WalletKitError::InvalidInput {
+        attribute: "proof_request".to_string(),
+        reason,
+    }
+}
+
+/// Takes the wrapper-level `valid_until` field out of `value`.
+fn take_valid_until(value: &mut Value) -> Result<Option<u64>, String> {
+    value
+        .as_object_mut()
+        .and_then(|object| object.remove(VALID_UNTIL_FIELD))
+        .map(|valid_until| {
+            valid_until.as_u64().ok_or_else(|| {
+                format!("`{VALID_UNTIL_FIELD}` must be a unix timestamp")
+            })
+        })
+        .transpose()
+}
+
+/// Takes the wrapper-level `chain_id` field out of `value`.
+fn take_chain_id(value: &mut Value) -> Result<Option<u64>, String> {
+    value
+        .as_object_mut()
+        .and_then(|object| object.remove(CHAIN_ID_FIELD))
+        .map(|chain_id| {
+            chain_id
+                .as_u64()
+                .ok_or_else(|| format!("`{CHAIN_ID_FIELD}` must be a chain ID"))
+        })
+        .transpose()
+}
+
+/// Serializes `value`, adding the `valid_until` field if set.
+fn to_json_with_valid_until(
+    value: &impl Serialize,
+    valid_until: Option<u64>,
+) -> Result<String, WalletKitError> {
+    let unexpected = |e: serde_json::Error| WalletKitError::Generic {
+        error: format!("critical unexpected error serializing to json: {e}"),
+    };
+    let Some(valid_until) = valid_until else {
+        return serde_json::to_string(value).map_err(unexpected);
+    };
+    let mut value = serde_json::to_value(value).map_err(unexpected)?;
+    if let Some(object) = value.as_object_mut() {
+        object.insert(VALID_UNTIL_FIELD.to_string(), valid_until.into());
+    }
+    Ok(value.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use alloy::signers::{local::PrivateKeySigner, SignerSync};
@@ -192,4 +569,195 @@ mod tests {
             other => panic!("expected invalid input error, got {other:?}"),
         }
     }
+
+    #[test]
+    fn valid_until_round_trips_through_json() {
+        let core_request = base_core_request(ProofType::Uniqueness);
+        let mut value =
+            serde_json::to_value(&core_request).expect("request should serialize");
+        value["valid_until"] = Value::from(1_700_000_060u64);
+
+        let request =
+            ProofRequest::from_json(&value.to_string()).expect("request should parse");
+        assert_eq!(request.valid_until(), Some(1_700_000_060));
+        assert_eq!(request.0, core_request);
+        let json: Value = serde_json::from_str(&request.to_json().unwrap()).unwrap();
+        assert_eq!(json, value);
+
+        assert!(request.check_valid_until(1_700_000_060).is_ok());
+        assert!(matches!(
+            request.check_valid_until(1_700_000_061),
+            Err(WalletKitError::ProofRequestExpired {
+                expired_at: 1_700_000_060
+            })
+        ));
+
+        value["valid_until"] = Value::from("soon");
+        assert!(matches!(
+            ProofRequest::from_json(&value.to_string()),
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+    }
+
+    #[test]
+    fn chain_id_round_trips_through_json() {
+        let core_request = base_core_request(ProofType::Uniqueness);
+        let mut value =
+            serde_json::to_value(&core_request).expect("request should serialize");
+        value["chain_id"] = Value::from(4801u64);
+
+        let request =
+            ProofRequest::from_json(&value.to_string()).expect("request should parse");
+        assert_eq!(request.chain_id(), Some(4801));
+        assert_eq!(request.0, core_request);
+        let json: Value = serde_json::from_str(&request.to_json().unwrap()).unwrap();
+        assert_eq!(json, value);
+
+        assert!(request.check_chain_id(4801).is_ok());
+        assert!(matches!(
+            request.check_chain_id(480),
+            Err(WalletKitError::EnvironmentMismatch {
+                request_chain: 4801,
+                configured_chain: 480,
+            })
+        ));
+        // requests without a chain are not bound to one
+        assert!(ProofRequest::from(core_request).check_chain_id(480).is_ok());
+
+        value["chain_id"] = Value::from("staging");
+        assert!(matches!(
+            ProofRequest::from_json(&value.to_string()),
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+    }
+
+    #[test]
+    fn missing_valid_until_keeps_json_unchanged() {
+        let core_request = base_core_request(ProofType::Uniqueness);
+        let json = serde_json::to_string(&core_request).unwrap();
+
+        let request = ProofRequest::from_json(&json).expect("request should parse");
+        assert_eq!(request.valid_until(), None);
+        assert_eq!(request.to_json().unwrap(), json);
+        assert!(request.check_valid_until(u64::MAX).is_ok());
+
+        let response = ProofResponse::from(CoreProofResponse {
+            id: core_request.id,
+            version: core_request.version,
+            session_id: None,
+            error: None,
+            responses: vec![],
+        });
+        assert!(!response.is_expired(u64::MAX));
+        assert!(!response.to_json().unwrap().contains("valid_until"));
+    }
+
+    #[test]
+    fn response_echoes_valid_until() {
+        let response = ProofResponse(
+            CoreProofResponse {
+                id: "test_request".to_string(),
+                version: RequestVersion::V1,
+                session_id: None,
+                error: None,
+                responses: vec![],
+            },
+            Some(1_700_000_060),
+        );
+
+        assert_eq!(response.valid_until(), Some(1_700_000_060));
+        assert!(!response.is_expired(1_700_000_060));
+        assert!(response.is_expired(1_700_000_061));
+        let json: Value = serde_json::from_str(&response.to_json().unwrap()).unwrap();
+        assert_eq!(json["valid_until"], 1_700_000_060);
+
+        let parsed = ProofResponse::from_json(&response.to_json().unwrap())
+            .expect("response should parse");
+        assert_eq!(parsed.to_json().unwrap(), response.to_json().unwrap());
+        assert_eq!(parsed.valid_until(), Some(1_700_000_060));
+    }
+
+    #[test]
+    fn response_root_is_the_proven_merkle_root() {
+        use world_id_core::primitives::{Nullifier, ZeroKnowledgeProof};
+        use world_id_core::requests::ResponseItem;
+
+        let mut response = ProofResponse::from(CoreProofResponse {
+            id: "test_request".to_string(),
+            version: RequestVersion::V1,
+            session_id: None,
+            error: Some("failed".to_string()),
+            responses: vec![],
+        });
+        assert_eq!(response.root(), None);
+
+        let root = U256::from(42u64);
+        response.0.error = None;
+        response.0.responses.push(ResponseItem::new_uniqueness(
+            "credential".to_string(),
+            1,
+            ZeroKnowledgeProof::from_ethereum_representation([
+                U256::from(1u64),
+                U256::from(2u64),
+                U256::from(3u64),
+                U256::from(4u64),
+                root,
+            ]),
+            Nullifier::from(FieldElement::from(1u64)),
+            1_700_000_000,
+        ));
+        assert_eq!(response.root(), Some(Uint256::from(root)));
+    }
+
+    #[tokio::test]
+    async fn parse_proof_request_uri_accepts_links_and_bare_requests() {
+        let core_request = base_core_request(ProofType::Uniqueness);
+        let json = serde_json::to_string(&core_request).unwrap();
+        let encoded = URL_SAFE_NO_PAD.encode(&json);
+        let now = 1_700_000_100;
+
+        let mut https_link =
+            reqwest::Url::parse("https://id.worldcoin.org/verify").unwrap();
+        https_link.query_pairs_mut().append_pair("request", &json);
+        for uri in [
+            format!("worldcoin://verify?request={encoded}"),
+            https_link.to_string(),
+            json.clone(),
+            format!("  {encoded}\n"),
+        ] {
+            let request = parse_proof_request_uri(&uri, None, now)
+                .await
+                .unwrap_or_else(|e| panic!("{uri}: {e:?}"));
+            assert_eq!(request.0, core_request);
+        }
+
+        assert!(matches!(
+            parse_proof_request_uri(&json, None, core_request.expires_at + 1).await,
+            Err(WalletKitError::ProofRequestExpired {
+                expired_at: 1_700_000_300
+            })
+        ));
+        let oversized = format!(
+            "worldcoin://verify?request={}",
+            "A".repeat(crate::limits::MAX_PROOF_REQUEST_URI_BYTES)
+        );
+        assert!(matches!(
+            parse_proof_request_uri(&oversized, None, now).await,
+            Err(WalletKitError::InvalidInput { attribute, reason })
+                if attribute == "uri" && reason.contains("exceeds the limit")
+        ));
+        for uri in [
+            format!("https://example.com/verify?request={encoded}"),
+            "worldcoin://verify?other=1".to_string(),
+            "aGVsbG8.d29ybGQ.c2ln".to_string(),
+            "not a request".to_string(),
+        ] {
+            match parse_proof_request_uri(&uri, None, now).await {
+                Err(WalletKitError::InvalidInput { attribute, .. }) => {
+                    assert!(attribute == "uri" || attribute == "proof_request");
+                }
+                other => panic!("{uri}: expected invalid input, got {other:?}"),
+            }
+        }
+    }
 }
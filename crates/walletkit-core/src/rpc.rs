@@ -0,0 +1,128 @@
+//! Read-only calls to World Chain contracts over JSON-RPC.
+
+use alloy_core::primitives::{Address, U256};
+use alloy_core::sol_types::SolCall;
+use serde::Deserialize;
+
+use crate::defaults::world_id_registry_address;
+use crate::error::WalletKitError;
+use crate::http_request::Request;
+use crate::{Environment, UserAgentBuilder};
+
+alloy_core::sol! {
+    function isValidRoot(uint256 root) external view returns (bool);
+}
+
+/// JSON-RPC error code of a reverted call, see EIP-1474.
+const EXECUTION_REVERTED: i64 = 3;
+
+/// Outcome of an [`eth_call`] that reached the node.
+pub enum CallOutcome {
+    /// The call returned this data.
+    Returned(Vec<u8>),
+    /// The call reverted with this message.
+    Reverted(String),
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    result: Option<String>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    #[serde(default)]
+    code: i64,
+    message: String,
+}
+
+/// Calls `to` with `calldata` at the latest block through the JSON-RPC
+/// endpoint at `rpc_url`.
+///
+/// # Errors
+///
+/// Returns [`WalletKitError::NetworkError`] if the request fails or the node
+/// returns an error other than a revert, and
+/// [`WalletKitError::SerializationError`] if the response is malformed.
+pub async fn eth_call(
+    rpc_url: &str,
+    to: Address,
+    calldata: Vec<u8>,
+) -> Result<CallOutcome, WalletKitError> {
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_call",
+        "params": [
+            {
+                "to": to.to_string(),
+                "data": format!("0x{}", hex::encode(calldata)),
+            },
+            "latest",
+        ],
+    });
+    let user_agent = UserAgentBuilder::new().with_walletkit_segment().build();
+    let request = Request::new(user_agent.to_string());
+    let response = request.handle(request.post(rpc_url).json(&body)).await?;
+
+    let status = response.status();
+    let response: RpcResponse =
+        response
+            .json()
+            .await
+            .map_err(|e| WalletKitError::SerializationError {
+                error: format!(
+                    "Failed to parse eth_call response with status {status}: {e}"
+                ),
+            })?;
+    if let Some(error) = response.error {
+        if error.code == EXECUTION_REVERTED
+            || error.message.starts_with("execution reverted")
+        {
+            return Ok(CallOutcome::Reverted(error.message));
+        }
+        return Err(WalletKitError::NetworkError {
+            url: rpc_url.to_string(),
+            error: error.message,
+            status: Some(status.as_u16()),
+        });
+    }
+    let result = response.result.unwrap_or_default();
+    let bytes = hex::decode(result.trim_start_matches("0x")).map_err(|e| {
+        WalletKitError::SerializationError {
+            error: format!("Invalid eth_call result: {e}"),
+        }
+    })?;
+    Ok(CallOutcome::Returned(bytes))
+}
+
+/// Asks the `environment`'s World ID registry whether it accepts proofs
+/// against `root`.
+///
+/// # Errors
+///
+/// Returns an error if the call fails or reverts, or its result is malformed.
+pub async fn is_valid_root(
+    rpc_url: &str,
+    environment: &Environment,
+    root: U256,
+) -> Result<bool, WalletKitError> {
+    let calldata = isValidRootCall { root }.abi_encode();
+    let to = world_id_registry_address(environment);
+    let bytes = match eth_call(rpc_url, to, calldata).await? {
+        CallOutcome::Returned(bytes) => bytes,
+        CallOutcome::Reverted(message) => {
+            return Err(WalletKitError::NetworkError {
+                url: rpc_url.to_string(),
+                error: message,
+                status: None,
+            });
+        }
+    };
+    isValidRootCall::abi_decode_returns(&bytes).map_err(|e| {
+        WalletKitError::SerializationError {
+            error: format!("Invalid isValidRoot result: {e}"),
+        }
+    })
+}
@@ -0,0 +1,49 @@
+//! Executor independence for the crate's async functions.
+//!
+//! HTTP requests (through `reqwest`, in this crate and in `world-id-core`),
+//! timers and blocking tasks are tokio-based and panic when polled outside a
+//! tokio runtime. Every await on such a future goes through [`compat`], so
+//! the public async API can be driven by any executor: under a tokio runtime
+//! the caller's runtime is used, otherwise a single-threaded tokio runtime is
+//! started on one background thread on first use to drive I/O and timers.
+//!
+//! On wasm32 there is no tokio runtime and these helpers are pass-throughs.
+
+use std::future::Future;
+
+/// Runs `future` with a tokio runtime available, see the
+/// [module documentation](self).
+///
+/// Upstream `async fn`s do no work until polled, so they may be passed
+/// directly; wrap anything that touches tokio when constructed (e.g.
+/// `tokio::time::sleep`) in an `async` block.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn compat<F: Future>(future: F) -> F::Output {
+    async_compat::Compat::new(future).await
+}
+
+/// Runs `future`; there is no tokio runtime to provide on wasm32.
+#[cfg(target_arch = "wasm32")]
+pub async fn compat<F: Future>(future: F) -> F::Output {
+    future.await
+}
+
+/// Runs the blocking `task` on tokio's blocking thread pool.
+///
+/// # Errors
+///
+/// Returns the task's panic as a [`tokio::task::JoinError`].
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn spawn_blocking<T, F>(task: F) -> Result<T, tokio::task::JoinError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    compat(async move { tokio::task::spawn_blocking(task).await }).await
+}
+
+/// Waits for `duration`.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn sleep(duration: std::time::Duration) {
+    compat(async move { tokio::time::sleep(duration).await }).await;
+}
@@ -0,0 +1,190 @@
+//! Used by [`Authenticator`](crate::Authenticator) and
+//! [`AppRegistryClient`](crate::AppRegistryClient).
+//!
+//! Each instance otherwise keeps its caches to itself, so hosts creating a
+//! short-lived instance per request refetch the same data every time. Passing
+//! one [`SharedCaches`], usually [`SharedCaches::global`], to every instance
+//! lets them share hits. Entries expire after the TTL of their cache.
+//!
+//! Lookups and inserts hold a shard's lock only for the map operation, never
+//! across an await.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+use alloy_core::primitives::{Address, U256};
+
+use crate::app_registry::ActionConfig;
+
+/// Default TTL of cached action configs, in seconds.
+pub const DEFAULT_ACTION_CONFIG_TTL_SECS: u64 = 10 * 60;
+
+/// Default TTL of cached packed account data, in seconds.
+pub const DEFAULT_PACKED_ACCOUNT_DATA_TTL_SECS: u64 = 60;
+
+const SHARDS: usize = 16;
+
+/// One shard of a [`TtlCache`]: values with their expiry, `None` if the TTL
+/// overflows `Instant`.
+type Shard<K, V> = Mutex<HashMap<K, (V, Option<Instant>)>>;
+
+/// A map whose entries expire `ttl` after they were inserted, sharded by key
+/// hash to keep lock contention low.
+pub(crate) struct TtlCache<K, V> {
+    ttl: Duration,
+    shards: [Shard<K, V>; SHARDS],
+}
+
+impl<K: Hash + Eq, V: Clone> TtlCache<K, V> {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            shards: std::array::from_fn(|_| Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Returns the value for `key` unless it is missing or expired.
+    pub(crate) fn get(&self, key: &K) -> Option<V> {
+        let mut shard = self.shard(key);
+        match shard.get(key) {
+            Some((value, expires_at))
+                if expires_at.is_none_or(|expires_at| Instant::now() < expires_at) =>
+            {
+                Some(value.clone())
+            }
+            Some(_) => {
+                shard.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub(crate) fn insert(&self, key: K, value: V) {
+        let expires_at = Instant::now().checked_add(self.ttl);
+        self.shard(&key).insert(key, (value, expires_at));
+    }
+
+    fn clear(&self) {
+        for shard in &self.shards {
+            shard.lock().unwrap_or_else(PoisonError::into_inner).clear();
+        }
+    }
+
+    fn shard(
+        &self,
+        key: &K,
+    ) -> std::sync::MutexGuard<'_, HashMap<K, (V, Option<Instant>)>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        #[allow(clippy::cast_possible_truncation)]
+        let index = hasher.finish() as usize % SHARDS;
+        // a poisoned shard only means a panic mid-insert; the map is intact
+        self.shards[index]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Process-wide caches for data that does not depend on the instance that
+/// fetched it. See the [module documentation](self).
+#[derive(uniffi::Object)]
+pub struct SharedCaches {
+    /// Action configs by `(app_id, action)`.
+    pub(crate) action_configs: TtlCache<(String, String), ActionConfig>,
+    /// Packed account data by `(registry address, authenticator address)`.
+    pub(crate) packed_account_data: TtlCache<(Address, Address), U256>,
+}
+
+impl std::fmt::Debug for SharedCaches {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedCaches").finish_non_exhaustive()
+    }
+}
+
+#[uniffi::export]
+impl SharedCaches {
+    /// Creates empty caches with the given TTLs, in seconds.
+    #[uniffi::constructor]
+    #[must_use]
+    pub fn new(action_config_ttl_secs: u64, packed_account_data_ttl_secs: u64) -> Self {
+        Self {
+            action_configs: TtlCache::new(Duration::from_secs(action_config_ttl_secs)),
+            packed_account_data: TtlCache::new(Duration::from_secs(
+                packed_account_data_ttl_secs,
+            )),
+        }
+    }
+
+    /// Returns the process-wide caches, created with the default TTLs on
+    /// first use.
+    #[uniffi::constructor]
+    #[must_use]
+    pub fn global() -> Arc<Self> {
+        static GLOBAL: OnceLock<Arc<SharedCaches>> = OnceLock::new();
+        Arc::clone(GLOBAL.get_or_init(|| {
+            Arc::new(Self::new(
+                DEFAULT_ACTION_CONFIG_TTL_SECS,
+                DEFAULT_PACKED_ACCOUNT_DATA_TTL_SECS,
+            ))
+        }))
+    }
+
+    /// Drops all cached entries.
+    pub fn clear(&self) {
+        self.action_configs.clear();
+        self.packed_account_data.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_expire_after_ttl() {
+        let cache = TtlCache::new(Duration::from_millis(50));
+        cache.insert("key", 1);
+        assert_eq!(cache.get(&"key"), Some(1));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(cache.get(&"key"), None);
+    }
+
+    #[test]
+    fn test_clear_drops_all_entries() {
+        let caches = SharedCaches::new(60, 60);
+        caches
+            .packed_account_data
+            .insert((Address::ZERO, Address::ZERO), U256::from(1));
+        caches.action_configs.insert(
+            ("app".to_string(), "action".to_string()),
+            ActionConfig {
+                max_verifications: None,
+                name: String::new(),
+                description: String::new(),
+            },
+        );
+
+        caches.clear();
+        assert!(caches
+            .packed_account_data
+            .get(&(Address::ZERO, Address::ZERO))
+            .is_none());
+        assert!(caches
+            .action_configs
+            .get(&("app".to_string(), "action".to_string()))
+            .is_none());
+    }
+
+    #[test]
+    fn test_global_is_shared() {
+        assert!(Arc::ptr_eq(
+            &SharedCaches::global(),
+            &SharedCaches::global()
+        ));
+    }
+}
@@ -0,0 +1,187 @@
+//! Async variants of [`CredentialStore`] reads.
+//!
+//! Vault reads are blocking `SQLite` I/O, which shows up as UI hitches when a
+//! large vault is read from the main thread. These variants run the read on
+//! tokio's blocking thread pool instead; the sync methods remain for callers
+//! already on a background thread.
+
+use std::sync::Arc;
+
+use super::error::{StorageError, StorageResult};
+use super::types::CredentialRecord;
+use super::CredentialStore;
+use crate::{Credential, FieldElement};
+
+#[uniffi::export(async_runtime = "tokio")]
+impl CredentialStore {
+    /// Like [`list_credentials`](Self::list_credentials), but reads the vault
+    /// on a blocking thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the credential query fails.
+    pub async fn list_credentials_async(
+        self: Arc<Self>,
+        issuer_schema_id: Option<u64>,
+        now: u64,
+    ) -> StorageResult<Vec<CredentialRecord>> {
+        run_blocking(move || self.list_credentials(issuer_schema_id, now)).await
+    }
+}
+
+impl CredentialStore {
+    /// Like [`get_credential`](Self::get_credential), but reads the vault on a
+    /// blocking thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the credential query fails.
+    pub async fn get_credential_async(
+        self: Arc<Self>,
+        issuer_schema_id: u64,
+        now: u64,
+    ) -> StorageResult<Option<(Credential, FieldElement)>> {
+        run_blocking(move || self.get_credential(issuer_schema_id, now)).await
+    }
+}
+
+async fn run_blocking<T, F>(read: F) -> StorageResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> StorageResult<T> + Send + 'static,
+{
+    crate::runtime::spawn_blocking(read).await.map_err(|err| {
+        StorageError::VaultDb(format!("blocking read task failed: {err}"))
+    })?
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::{Duration, Instant};
+
+    use world_id_core::Credential as CoreCredential;
+
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+
+    fn store_with_credentials(
+        provider: &InMemoryStorageProvider,
+        count: u64,
+        associated_data_len: usize,
+    ) -> Arc<CredentialStore> {
+        let store = Arc::new(CredentialStore::from_provider(provider).expect("store"));
+        store.init(42, 1000).expect("init storage");
+        for issuer_schema_id in 0..count {
+            let credential: Credential = CoreCredential::new()
+                .issuer_schema_id(issuer_schema_id)
+                .genesis_issued_at(1000)
+                .into();
+            store
+                .store_credential(
+                    &credential,
+                    &FieldElement::from(7u64),
+                    2000,
+                    Some(vec![0xab; associated_data_len]),
+                    1000,
+                )
+                .expect("store credential");
+        }
+        store
+    }
+
+    #[tokio::test]
+    async fn test_async_reads_match_sync_reads() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = store_with_credentials(&provider, 3, 16);
+
+        let records = Arc::clone(&store)
+            .list_credentials_async(None, 1001)
+            .await
+            .expect("list credentials");
+        assert_eq!(records, store.list_credentials(None, 1001).unwrap());
+        assert_eq!(records.len(), 3);
+
+        let (credential, blinding_factor) = Arc::clone(&store)
+            .get_credential_async(1, 1001)
+            .await
+            .expect("get credential")
+            .expect("credential exists");
+        assert_eq!(credential.issuer_schema_id(), 1);
+        assert_eq!(blinding_factor.0, FieldElement::from(7u64).0);
+        assert!(Arc::clone(&store)
+            .get_credential_async(99, 1001)
+            .await
+            .expect("get credential")
+            .is_none());
+
+        cleanup_test_storage(&root);
+    }
+
+    /// Compares sync and async reads of a ~500 kB vault on a single-threaded
+    /// runtime, and the longest each stalls a task ticking every millisecond.
+    ///
+    /// `cargo test -p walletkit-core --release bench_async_reads -- --ignored
+    /// --nocapture`
+    #[tokio::test(flavor = "current_thread")]
+    #[ignore = "benchmark"]
+    async fn bench_async_reads() {
+        const CREDENTIALS: u64 = 50;
+        const ROUNDS: u32 = 20;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = store_with_credentials(&provider, CREDENTIALS, 10 * 1024);
+        store
+            .set_credential_cache_capacity(0)
+            .expect("disable cache");
+
+        for asynchronous in [false, true] {
+            let longest_stall = Arc::new(AtomicU64::new(0));
+            let ticker = tokio::spawn({
+                let longest_stall = Arc::clone(&longest_stall);
+                async move {
+                    let mut last = Instant::now();
+                    loop {
+                        tokio::time::sleep(Duration::from_millis(1)).await;
+                        let stall = u64::try_from(last.elapsed().as_micros())
+                            .unwrap_or(u64::MAX);
+                        longest_stall.fetch_max(stall, Ordering::Relaxed);
+                        last = Instant::now();
+                    }
+                }
+            });
+            tokio::task::yield_now().await;
+
+            let started = Instant::now();
+            for _ in 0..ROUNDS {
+                for issuer_schema_id in 0..CREDENTIALS {
+                    if asynchronous {
+                        Arc::clone(&store)
+                            .get_credential_async(issuer_schema_id, 1001)
+                            .await
+                            .expect("get credential");
+                    } else {
+                        store
+                            .get_credential(issuer_schema_id, 1001)
+                            .expect("get credential");
+                    }
+                }
+                tokio::task::yield_now().await;
+            }
+            let per_round = started.elapsed() / ROUNDS;
+            ticker.abort();
+            println!(
+                "{} reads of {CREDENTIALS} credentials: {per_round:?} per round, \
+                 runtime stalled up to {}µs",
+                if asynchronous { "async" } else { "sync" },
+                longest_stall.load(Ordering::Relaxed),
+            );
+        }
+
+        cleanup_test_storage(&root);
+    }
+}
@@ -9,7 +9,7 @@ use crate::storage::error::StorageResult;
 use walletkit_db::Vault;
 
 use super::schema;
-use super::util::map_io_err;
+use super::util::{map_io_err, map_store_err};
 
 /// Opens the cache DB through `Vault`, rebuilding on any open / key /
 /// integrity failure.
@@ -29,7 +29,7 @@ pub(super) fn open_or_rebuild(
         return Ok(vault);
     }
     delete_cache_files(path)?;
-    Vault::open(path, k_intermediate, schema::ensure_schema).map_err(Into::into)
+    Vault::open(path, k_intermediate, schema::ensure_schema).map_err(map_store_err)
 }
 
 /// Deletes the cache DB and its WAL/SHM sidecar files if present.
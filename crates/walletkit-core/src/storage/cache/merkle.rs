@@ -1,36 +1,174 @@
 //! Merkle proof cache helpers.
 
-use crate::storage::{cache::schema::CACHE_KEY_PREFIX_MERKLE, error::StorageResult};
-use walletkit_db::Connection;
+use crate::storage::{
+    cache::schema::CACHE_KEY_PREFIX_MERKLE,
+    error::{StorageError, StorageResult},
+    types::RegistryKind,
+};
+use sha2::{Digest, Sha256};
+use walletkit_db::{params, Connection, StepResult};
 
 use super::util::{
-    cache_entry_times, get_cache_entry, prune_expired_entries, upsert_cache_entry,
+    cache_entry_times, map_db_err, prune_expired_entries, to_i64, upsert_cache_entry,
 };
 
-/// Fetches a cached Merkle proof if it is still valid.
+/// Start of the refresh window, as a percentage of the proof's TTL.
+const REFRESH_WINDOW_START_PERCENT: u64 = 50;
+
+/// End of the refresh window, as a percentage of the proof's TTL.
+const REFRESH_WINDOW_END_PERCENT: u64 = 90;
+
+/// Returns the cache key of the Merkle proof for `kind`.
+const fn merkle_key(kind: RegistryKind) -> [u8; 2] {
+    [CACHE_KEY_PREFIX_MERKLE, kind.as_u8()]
+}
+
+/// Fetches a cached Merkle proof for `kind` if it is still valid.
+///
+/// Every valid Merkle entry is checked, so a proof stored under a registry kind
+/// this version does not know surfaces as an error instead of a cache miss.
 ///
 /// # Errors
 ///
-/// Returns an error if the query or conversion fails.
+/// Returns [`StorageError::UnknownRegistryKind`] if a stored entry has an
+/// unknown kind, or an error if the query fails.
 pub(super) fn get(
     conn: &Connection,
+    kind: RegistryKind,
     valid_until: u64,
 ) -> StorageResult<Option<Vec<u8>>> {
-    get_cache_entry(conn, &[CACHE_KEY_PREFIX_MERKLE], valid_until, None)
+    let valid_until = to_i64(valid_until, "valid_until")?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT key_bytes, value_bytes FROM cache_entries
+             WHERE substr(key_bytes, 1, 1) = ?1 AND expires_at >= ?2",
+        )
+        .map_err(|err| map_db_err(&err))?;
+    stmt.bind_values(params![[CACHE_KEY_PREFIX_MERKLE].as_slice(), valid_until])
+        .map_err(|err| map_db_err(&err))?;
+
+    let mut found = None;
+    while let StepResult::Row(row) = stmt.step().map_err(|err| map_db_err(&err))? {
+        let key = row.column_blob(0);
+        let stored_kind = match key.as_slice() {
+            [_, kind] => RegistryKind::try_from(*kind)?,
+            _ => {
+                return Err(StorageError::CorruptedCacheEntry {
+                    key_prefix: CACHE_KEY_PREFIX_MERKLE,
+                })
+            }
+        };
+        if stored_kind == kind {
+            found = Some(row.column_blob(1));
+        }
+    }
+    Ok(found)
 }
 
-/// Inserts or replaces a cached Merkle proof with a TTL.
+/// Fetches the cached Merkle proof for `kind` and the time it was cached,
+/// regardless of its TTL.
+///
+/// # Errors
+///
+/// Returns an error if the query fails or the stored timestamp is invalid.
+pub(super) fn get_with_inserted_at(
+    conn: &Connection,
+    kind: RegistryKind,
+) -> StorageResult<Option<(Vec<u8>, u64)>> {
+    let entry = conn
+        .query_row_optional(
+            "SELECT value_bytes, inserted_at FROM cache_entries WHERE key_bytes = ?1",
+            params![merkle_key(kind).as_slice()],
+            |stmt| Ok((stmt.column_blob(0), stmt.column_i64(1))),
+        )
+        .map_err(|err| map_db_err(&err))?;
+    entry
+        .map(|(bytes, inserted_at)| {
+            u64::try_from(inserted_at)
+                .map(|inserted_at| (bytes, inserted_at))
+                .map_err(|_| {
+                    StorageError::CacheDb(format!("invalid inserted_at {inserted_at}"))
+                })
+        })
+        .transpose()
+}
+
+/// Inserts or replaces the cached Merkle proof for `kind` with a TTL.
 ///
 /// # Errors
 ///
 /// Returns an error if pruning or insert fails.
 pub(super) fn put(
     conn: &Connection,
+    kind: RegistryKind,
     proof_bytes: &[u8],
     now: u64,
     ttl_seconds: u64,
 ) -> StorageResult<()> {
     prune_expired_entries(conn, now)?;
     let times = cache_entry_times(now, ttl_seconds)?;
-    upsert_cache_entry(conn, &[CACHE_KEY_PREFIX_MERKLE], proof_bytes, times)
+    upsert_cache_entry(conn, &merkle_key(kind), proof_bytes, times)
+}
+
+/// Returns when the cached Merkle proof for `kind` should be refreshed, or
+/// `None` if no proof valid at `now` is cached.
+///
+/// The refresh time falls uniformly within
+/// [`REFRESH_WINDOW_START_PERCENT`, `REFRESH_WINDOW_END_PERCENT`] of the
+/// proof's TTL, derived from the per-device `seed` so that devices spread
+/// their refreshes instead of all hitting the indexer at expiry.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub(super) fn refresh_at(
+    conn: &Connection,
+    kind: RegistryKind,
+    now: u64,
+    seed: &[u8; 32],
+) -> StorageResult<Option<u64>> {
+    let now_i64 = to_i64(now, "now")?;
+    let times = conn
+        .query_row_optional(
+            "SELECT inserted_at, expires_at FROM cache_entries WHERE key_bytes = ?1 AND expires_at >= ?2",
+            params![merkle_key(kind).as_slice(), now_i64],
+            |stmt| Ok((stmt.column_i64(0), stmt.column_i64(1))),
+        )
+        .map_err(|err| map_db_err(&err))?;
+    let Some((inserted_at, expires_at)) = times else {
+        return Ok(None);
+    };
+    Ok(Some(jittered_refresh_at(
+        seed,
+        to_u64(inserted_at, "inserted_at")?,
+        to_u64(expires_at, "expires_at")?,
+    )))
+}
+
+/// Picks a refresh time for a proof cached between `inserted_at` and
+/// `expires_at`. Deterministic for a given seed and proof.
+pub(super) fn jittered_refresh_at(
+    seed: &[u8; 32],
+    inserted_at: u64,
+    expires_at: u64,
+) -> u64 {
+    let ttl = expires_at.saturating_sub(inserted_at);
+    let window_start = ttl.saturating_mul(REFRESH_WINDOW_START_PERCENT) / 100;
+    let window_end = ttl.saturating_mul(REFRESH_WINDOW_END_PERCENT) / 100;
+
+    let digest = Sha256::new()
+        .chain_update(seed)
+        .chain_update(inserted_at.to_be_bytes())
+        .finalize();
+    let mut sample = [0u8; 8];
+    sample.copy_from_slice(&digest[..8]);
+    let offset = u64::from_be_bytes(sample) % (window_end - window_start + 1);
+
+    inserted_at.saturating_add(window_start + offset)
+}
+
+fn to_u64(value: i64, label: &str) -> StorageResult<u64> {
+    u64::try_from(value).map_err(|_| {
+        StorageError::CacheDb(format!("{label} out of range for u64: {value}"))
+    })
 }
@@ -1,18 +1,85 @@
 //! Encrypted cache database for credential storage.
 
 use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
-use crate::storage::error::StorageResult;
+use crate::storage::{
+    error::StorageResult,
+    inventory::{InventoryEntry, RetentionPolicy},
+    types::{BloomStats, CacheConfig, DisclosureRecord, RegistryKind},
+    StorageLockGuard,
+};
 use secrecy::SecretBox;
 use walletkit_db::Vault;
 
+mod bloom;
 mod maintenance;
 mod merkle;
 mod nullifiers;
+mod rp_keys;
 mod schema;
 mod session;
 mod util;
 
+/// What the cache database stores, see
+/// [`data_inventory`](crate::storage::inventory::data_inventory).
+pub(crate) const INVENTORY: &[InventoryEntry] = &[
+    InventoryEntry {
+        name: "merkle_inclusion_proofs",
+        description: "Merkle inclusion proofs of the holder's account in the \
+            World ID registry, so proofs can be generated without fetching \
+            them again.",
+        locations: &["worldid/account.cache.sqlite:cache_entries"],
+        contains_personal_data: true,
+        retention: RetentionPolicy::Expiring {
+            max_age_seconds: crate::authenticator::MERKLE_PROOF_VALIDITY_SECONDS,
+        },
+    },
+    InventoryEntry {
+        name: "session_seeds",
+        description: "Seeds of the sessions the holder created with relying \
+            parties, so later session proofs can be linked to them.",
+        locations: &["worldid/account.cache.sqlite:cache_entries"],
+        contains_personal_data: true,
+        retention: RetentionPolicy::Expiring {
+            max_age_seconds: super::credential_storage::SESSION_SEED_TTL_SECONDS,
+        },
+    },
+    InventoryEntry {
+        name: "replay_guard",
+        description: "Nullifiers disclosed in proofs, a hash of the ID of \
+            the request each was disclosed for, so a nullifier is not \
+            disclosed twice, and the memo the app attached, if any.",
+        locations: &["worldid/account.cache.sqlite:cache_entries"],
+        contains_personal_data: true,
+        retention: RetentionPolicy::Expiring {
+            max_age_seconds: nullifiers::REPLAY_REQUEST_TTL_SECONDS,
+        },
+    },
+    InventoryEntry {
+        name: "rp_signing_keys",
+        description: "Public keys relying parties sign proof requests with.",
+        locations: &["worldid/account.cache.sqlite:cache_entries"],
+        contains_personal_data: false,
+        retention: RetentionPolicy::Expiring {
+            max_age_seconds: super::credential_storage::RP_KEYS_TTL_SECONDS,
+        },
+    },
+    InventoryEntry {
+        name: "cache_metadata",
+        description: "The cache's schema version, the World ID environment \
+            the store was provisioned for, and a random seed that spreads out \
+            background refreshes.",
+        locations: &[
+            "worldid/account.cache.sqlite:cache_meta",
+            "worldid/account.cache.sqlite:cache_environment",
+            "worldid/account.cache.sqlite:cache_entries",
+        ],
+        contains_personal_data: false,
+        retention: RetentionPolicy::UntilDeleted,
+    },
+];
+
 /// Encrypted cache database wrapper.
 ///
 /// Stores non-authoritative, regenerable data (proof cache, session keys,
@@ -24,6 +91,9 @@ mod util;
 #[derive(Debug)]
 pub struct CacheDb {
     vault: Vault,
+    config: CacheConfig,
+    /// Answers most replay guard checks without a database read.
+    replay_filter: Mutex<bloom::ReplayGuardBloomFilter>,
 }
 
 impl CacheDb {
@@ -38,39 +108,130 @@ impl CacheDb {
     pub fn new(
         path: &Path,
         k_intermediate: &SecretBox<[u8; 32]>,
+    ) -> StorageResult<Self> {
+        Self::new_with_config(path, k_intermediate, CacheConfig::default())
+    }
+
+    /// Opens or rebuilds the encrypted cache database at `path`, bounding it
+    /// according to `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened or rebuilt.
+    pub fn new_with_config(
+        path: &Path,
+        k_intermediate: &SecretBox<[u8; 32]>,
+        config: CacheConfig,
     ) -> StorageResult<Self> {
         let vault = maintenance::open_or_rebuild(path, k_intermediate)?;
-        Ok(Self { vault })
+        let replay_filter =
+            Mutex::new(bloom::ReplayGuardBloomFilter::load(vault.connection())?);
+        Ok(Self {
+            vault,
+            config,
+            replay_filter,
+        })
     }
 
-    /// Fetches a cached Merkle proof if it remains valid beyond `valid_until`.
+    /// Fetches the cached Merkle proof for `kind` if it remains valid beyond
+    /// `valid_until`.
     ///
     /// Returns `None` when missing or expired so callers can refetch from the
     /// indexer without relying on stale proofs.
     ///
     /// # Errors
     ///
+    /// Returns [`StorageError::UnknownRegistryKind`](crate::storage::StorageError::UnknownRegistryKind)
+    /// if a cached proof carries an unknown registry kind, or an error if the
+    /// query fails.
+    pub fn merkle_cache_get(
+        &self,
+        kind: RegistryKind,
+        valid_until: u64,
+    ) -> StorageResult<Option<Vec<u8>>> {
+        merkle::get(self.vault.connection(), kind, valid_until)
+    }
+
+    /// Fetches the cached Merkle proof for `kind` together with the time it
+    /// was cached, even if its TTL has passed.
+    ///
+    /// Expired proofs are only kept until the next cache insert prunes them.
+    ///
+    /// # Errors
+    ///
     /// Returns an error if the query fails.
-    pub fn merkle_cache_get(&self, valid_until: u64) -> StorageResult<Option<Vec<u8>>> {
-        merkle::get(self.vault.connection(), valid_until)
+    pub fn merkle_cache_get_with_inserted_at(
+        &self,
+        kind: RegistryKind,
+    ) -> StorageResult<Option<(Vec<u8>, u64)>> {
+        merkle::get_with_inserted_at(self.vault.connection(), kind)
     }
 
-    /// Inserts a cached Merkle proof with a TTL. Existing entries for the
-    /// same key are replaced.
+    /// Inserts a cached Merkle proof for `kind` with a TTL. Existing entries
+    /// for the same kind are replaced.
+    ///
+    /// Evicts the oldest Merkle proofs afterwards if there are more than
+    /// [`CacheConfig::max_merkle_entries`].
     ///
     /// # Errors
     ///
-    /// Returns an error if the insert fails.
+    /// Returns an error if the insert or eviction fails.
     pub fn merkle_cache_put(
         &self,
+        kind: RegistryKind,
         proof_bytes: &[u8],
         now: u64,
         ttl_seconds: u64,
     ) -> StorageResult<()> {
-        merkle::put(self.vault.connection(), proof_bytes, now, ttl_seconds)
+        let conn = self.vault.connection();
+        merkle::put(conn, kind, proof_bytes, now, ttl_seconds)?;
+        if let Some(max_entries) = self.config.max_merkle_entries {
+            util::evict_lru(conn, schema::CACHE_KEY_PREFIX_MERKLE, max_entries)?;
+        }
+        Ok(())
     }
 
-    /// Fetches a cached `session_id_r_seed` for the given `oprf_seed`.
+    /// Deletes the least recently inserted Merkle proofs until at most
+    /// `max_entries` remain.
+    ///
+    /// Returns the number of deleted proofs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the deletion fails.
+    pub fn merkle_cache_evict_lru(
+        &self,
+        max_entries: usize,
+        _lock: &StorageLockGuard,
+    ) -> StorageResult<u64> {
+        util::evict_lru(
+            self.vault.connection(),
+            schema::CACHE_KEY_PREFIX_MERKLE,
+            u64::try_from(max_entries).unwrap_or(u64::MAX),
+        )
+    }
+
+    /// Returns when the cached Merkle proof for `kind` should be proactively
+    /// refreshed,
+    /// or `None` if no proof valid at `now` is cached.
+    ///
+    /// The time is jittered by the per-device `seed` within the proof's TTL
+    /// and is stable across calls for the same cached proof.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn merkle_refresh_at(
+        &self,
+        kind: RegistryKind,
+        now: u64,
+        seed: &[u8; 32],
+    ) -> StorageResult<Option<u64>> {
+        merkle::refresh_at(self.vault.connection(), kind, now, seed)
+    }
+
+    /// Fetches a cached `session_id_r_seed` for the given `oprf_seed` in
+    /// `environment`.
     ///
     /// Returns `None` when missing or expired.
     ///
@@ -79,34 +240,74 @@ impl CacheDb {
     /// Returns an error if the query fails.
     pub fn session_seed_get(
         &self,
+        environment: [u8; 32],
         oprf_seed: [u8; 32],
         now: u64,
     ) -> StorageResult<Option<[u8; 32]>> {
-        session::get(self.vault.connection(), oprf_seed, now)
+        session::get(self.vault.connection(), environment, oprf_seed, now)
     }
 
-    /// Stores a `session_id_r_seed` keyed by `oprf_seed` with a TTL.
+    /// Stores a `session_id_r_seed` keyed by `oprf_seed` in `environment` with
+    /// a TTL.
+    ///
+    /// Evicts the oldest session seeds afterwards if there are more than
+    /// [`CacheConfig::max_session_keys`].
     ///
     /// # Errors
     ///
-    /// Returns an error if the insert fails.
+    /// Returns an error if the insert or eviction fails.
     pub fn session_seed_put(
         &self,
+        environment: [u8; 32],
         oprf_seed: [u8; 32],
         session_id_r_seed: [u8; 32],
         now: u64,
         ttl_seconds: u64,
     ) -> StorageResult<()> {
+        let conn = self.vault.connection();
         session::put(
-            self.vault.connection(),
+            conn,
+            environment,
             oprf_seed,
             session_id_r_seed,
             now,
             ttl_seconds,
-        )
+        )?;
+        if let Some(max_entries) = self.config.max_session_keys {
+            util::evict_lru(conn, schema::CACHE_KEY_PREFIX_SESSION, max_entries)?;
+        }
+        Ok(())
+    }
+
+    /// Fetches the cached JWKS document of `rp_id`.
+    ///
+    /// Returns `None` when missing or expired.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn rp_keys_get(&self, rp_id: &str, now: u64) -> StorageResult<Option<Vec<u8>>> {
+        rp_keys::get(self.vault.connection(), rp_id, now)
+    }
+
+    /// Stores the JWKS document of `rp_id` with a TTL, replacing any earlier
+    /// one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the insert fails.
+    pub fn rp_keys_put(
+        &self,
+        rp_id: &str,
+        jwks: &[u8],
+        now: u64,
+        ttl_seconds: u64,
+    ) -> StorageResult<()> {
+        rp_keys::put(self.vault.connection(), rp_id, jwks, now, ttl_seconds)
     }
 
-    /// Checks whether a replay guard entry exists for the given nullifier.
+    /// Checks whether a replay guard entry exists for the given nullifier in
+    /// `environment`.
     ///
     /// # Returns
     ///
@@ -118,30 +319,196 @@ impl CacheDb {
     /// Returns an error if the query to the cache unexpectedly fails.
     pub fn is_nullifier_replay(
         &self,
+        environment: [u8; 32],
         nullifier: [u8; 32],
         now: u64,
     ) -> StorageResult<bool> {
-        nullifiers::is_nullifier_replay(self.vault.connection(), nullifier, now)
+        if !self.replay_filter_might_contain(nullifier)? {
+            return Ok(false);
+        }
+        nullifiers::is_nullifier_replay(
+            self.vault.connection(),
+            environment,
+            nullifier,
+            now,
+        )
+    }
+
+    /// Checks the replay guard of `environment` before generating a proof for
+    /// `request_id` (the SHA-256 digest of the request's ID).
+    ///
+    /// # Returns
+    ///
+    /// - `true` if the nullifier was already disclosed for this request
+    ///   (nullifier replay).
+    /// - `false` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::NullifierConflict`](crate::storage::StorageError::NullifierConflict)
+    /// if the nullifier was disclosed for a different request, or an error if
+    /// the query to the cache unexpectedly fails.
+    pub fn begin_replay_guard(
+        &self,
+        environment: [u8; 32],
+        nullifier: [u8; 32],
+        request_id: [u8; 32],
+        now: u64,
+    ) -> StorageResult<bool> {
+        if !self.replay_filter_might_contain(nullifier)? {
+            return Ok(false);
+        }
+        nullifiers::begin_replay_guard(
+            self.vault.connection(),
+            environment,
+            nullifier,
+            request_id,
+            now,
+        )
     }
 
     /// After a proof has been successfully generated, creates a replay guard
-    /// entry locally to avoid future replays of the same nullifier.
+    /// entry in `environment` to avoid future replays of the same nullifier.
+    ///
+    /// `request_id` is the SHA-256 digest of the disclosing request's ID, if
+    /// known. `memo` is kept with the entry, but only if `request_id` is set.
     ///
     /// # Errors
     ///
     /// Returns an error if the query to the cache unexpectedly fails.
-    pub fn replay_guard_set(&self, nullifier: [u8; 32], now: u64) -> StorageResult<()> {
-        nullifiers::replay_guard_set(self.vault.connection(), nullifier, now)
+    pub fn replay_guard_set(
+        &self,
+        environment: [u8; 32],
+        nullifier: [u8; 32],
+        request_id: Option<[u8; 32]>,
+        memo: Option<&[u8]>,
+        now: u64,
+    ) -> StorageResult<()> {
+        nullifiers::replay_guard_set(
+            self.vault.connection(),
+            environment,
+            nullifier,
+            request_id,
+            memo,
+            now,
+        )?;
+        self.lock_replay_filter().insert(&nullifier);
+        Ok(())
+    }
+
+    /// Records `environment` as the environment the store was provisioned
+    /// for, unless one is recorded already, and returns the recorded one.
+    ///
+    /// Recording it moves the session seed and replay guard entries written
+    /// before entries were scoped to an environment into `environment`. This
+    /// happens once per cache; later calls, from any environment, leave the
+    /// entries where they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the environment cannot be recorded or the entries
+    /// cannot be moved.
+    pub fn provision_environment(
+        &self,
+        environment: [u8; 32],
+    ) -> StorageResult<[u8; 32]> {
+        util::provision_environment(self.vault.connection(), environment)
+    }
+
+    /// Lists the unexpired replay guard entries across environments, most
+    /// recent first, with their memos if `include_memos` is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query to the cache unexpectedly fails.
+    pub fn list_disclosures(
+        &self,
+        include_memos: bool,
+        now: u64,
+    ) -> StorageResult<Vec<DisclosureRecord>> {
+        nullifiers::list_disclosures(self.vault.connection(), include_memos, now)
+    }
+
+    /// Counts the replay guard entries recorded in `[since, now)`, across
+    /// environments.
+    ///
+    /// Repeated proofs for the same nullifier share one entry, and entries
+    /// pruned after their TTL are no longer counted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query to the cache unexpectedly fails.
+    pub fn count_replay_guard_entries(
+        &self,
+        since: u64,
+        now: u64,
+    ) -> StorageResult<u64> {
+        nullifiers::count_replay_guard_entries(self.vault.connection(), since, now)
+    }
+
+    /// Returns fill statistics of the replay guard's Bloom filter.
+    pub fn replay_guard_bloom_stats(&self) -> BloomStats {
+        self.lock_replay_filter().stats()
+    }
+
+    /// Re-encrypts the cache under `new_key`, see
+    /// [`walletkit_db::Vault::rekey`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the rekey fails; the cache then keeps its key.
+    pub fn rekey(&self, new_key: &SecretBox<[u8; 32]>) -> StorageResult<()> {
+        self.vault.rekey(new_key).map_err(util::map_store_err)
+    }
+
+    /// Checkpoints the cache's WAL into the database file, see
+    /// [`walletkit_db::Vault::checkpoint`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the checkpoint cannot run.
+    pub fn checkpoint(&self) -> StorageResult<()> {
+        self.vault.checkpoint().map_err(util::map_store_err)
+    }
+
+    /// Releases the connection's page cache and checkpoints the WAL without
+    /// waiting, see [`walletkit_db::Vault::release_memory`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection cannot release its memory.
+    pub fn release_memory(&self) -> StorageResult<()> {
+        self.vault.release_memory().map_err(util::map_store_err)
+    }
+
+    /// Returns `false` if `nullifier` definitely has no replay guard entry.
+    fn replay_filter_might_contain(&self, nullifier: [u8; 32]) -> StorageResult<bool> {
+        let mut filter = self.lock_replay_filter();
+        filter.sync(self.vault.connection())?;
+        Ok(filter.might_contain(&nullifier))
+    }
+
+    fn lock_replay_filter(&self) -> MutexGuard<'_, bloom::ReplayGuardBloomFilter> {
+        // the filter is only replaced whole, so a panic cannot leave it partial
+        self.replay_filter
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::error::StorageError;
+    use crate::storage::StorageLock;
     use secrecy::SecretBox;
     use std::fs;
     use std::path::PathBuf;
     use uuid::Uuid;
+    use walletkit_db::params;
+
+    const ENV: [u8; 32] = [0xEE; 32];
+    const OTHER_ENV: [u8; 32] = [0xDD; 32];
 
     fn temp_cache_path() -> PathBuf {
         let mut path = std::env::temp_dir();
@@ -186,7 +553,7 @@ mod tests {
         let oprf_seed = [0x01u8; 32];
         let r_seed = [0x02u8; 32];
         let now = 1_000;
-        db.session_seed_put(oprf_seed, r_seed, now, 1000)
+        db.session_seed_put(ENV, oprf_seed, r_seed, now, 1000)
             .expect("put session seed");
         drop(db);
 
@@ -194,7 +561,7 @@ mod tests {
 
         let db = CacheDb::new(&path, &key).expect("rebuild cache");
         let value = db
-            .session_seed_get(oprf_seed, now)
+            .session_seed_get(ENV, oprf_seed, now)
             .expect("get session seed");
         assert!(value.is_none());
         cleanup_cache_files(&path);
@@ -207,16 +574,398 @@ mod tests {
         let key = SecretBox::init_with(|| [0x33u8; 32]);
         let lock_path = temp_lock_path();
         let db = CacheDb::new(&path, &key).expect("create cache");
-        db.merkle_cache_put(&[1, 2, 3], 100, 10)
+        db.merkle_cache_put(RegistryKind::AccountRegistry, &[1, 2, 3], 100, 10)
             .expect("put merkle proof");
-        let hit = db.merkle_cache_get(105).expect("get merkle proof");
+        let hit = db
+            .merkle_cache_get(RegistryKind::AccountRegistry, 105)
+            .expect("get merkle proof");
         assert!(hit.is_some());
-        let miss = db.merkle_cache_get(111).expect("get merkle proof");
+        let miss = db
+            .merkle_cache_get(RegistryKind::AccountRegistry, 111)
+            .expect("get merkle proof");
         assert!(miss.is_none());
         cleanup_cache_files(&path);
         cleanup_lock_file(&lock_path);
     }
 
+    #[test]
+    fn test_rp_keys_cache_ttl() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x35u8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        db.rp_keys_put("rp_0000000000000001", b"{\"keys\":[]}", 100, 10)
+            .expect("put rp keys");
+        assert_eq!(
+            db.rp_keys_get("rp_0000000000000001", 105)
+                .expect("get rp keys")
+                .as_deref(),
+            Some(b"{\"keys\":[]}".as_slice())
+        );
+        assert!(db
+            .rp_keys_get("rp_0000000000000002", 105)
+            .expect("get rp keys")
+            .is_none());
+        assert!(db
+            .rp_keys_get("rp_0000000000000001", 111)
+            .expect("get rp keys")
+            .is_none());
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_merkle_cache_rejects_unknown_registry_kind() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x34u8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        db.merkle_cache_put(RegistryKind::AccountRegistry, &[1, 2, 3], 100, 10)
+            .expect("put merkle proof");
+
+        let times = util::cache_entry_times(100, 10).expect("times");
+        util::upsert_cache_entry(
+            db.vault.connection(),
+            &[schema::CACHE_KEY_PREFIX_MERKLE, 0x09],
+            &[4, 5, 6],
+            times,
+        )
+        .expect("insert unknown kind");
+
+        let err = db
+            .merkle_cache_get(RegistryKind::AccountRegistry, 105)
+            .expect_err("unknown kind must be rejected");
+        assert!(
+            matches!(err, StorageError::UnknownRegistryKind(0x09)),
+            "{err}"
+        );
+        assert!(matches!(
+            RegistryKind::try_from(0x09),
+            Err(StorageError::UnknownRegistryKind(0x09))
+        ));
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_migration_deletes_unknown_registry_kinds() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x35u8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        db.merkle_cache_put(RegistryKind::AccountRegistry, &[1, 2, 3], 100, 10)
+            .expect("put merkle proof");
+        let times = util::cache_entry_times(100, 10).expect("times");
+        for bad_key in [
+            vec![schema::CACHE_KEY_PREFIX_MERKLE],
+            vec![schema::CACHE_KEY_PREFIX_MERKLE, 0x09],
+            vec![schema::CACHE_KEY_PREFIX_MERKLE, 0x01, 0x00],
+        ] {
+            util::upsert_cache_entry(db.vault.connection(), &bad_key, &[4], times)
+                .expect("insert bad row");
+        }
+        db.session_seed_put(ENV, [0x01; 32], [0x02; 32], 100, 10)
+            .expect("put session seed");
+        // a cache from before Merkle proofs were keyed by registry kind
+        db.vault
+            .connection()
+            .execute_batch("PRAGMA user_version = 1;")
+            .expect("reset user_version");
+        drop(db);
+
+        let db = CacheDb::new(&path, &key).expect("open cache");
+        let merkle_rows = db
+            .vault
+            .connection()
+            .query_row(
+                "SELECT COUNT(*) FROM cache_entries WHERE substr(key_bytes, 1, 1) = ?1",
+                params![[schema::CACHE_KEY_PREFIX_MERKLE].as_slice()],
+                |stmt| Ok(stmt.column_i64(0)),
+            )
+            .expect("count merkle rows");
+        assert_eq!(merkle_rows, 1);
+        assert_eq!(
+            db.merkle_cache_get(RegistryKind::AccountRegistry, 105)
+                .expect("get merkle proof"),
+            Some(vec![1, 2, 3])
+        );
+        assert!(db
+            .session_seed_get(ENV, [0x01; 32], 105)
+            .expect("get session seed")
+            .is_some());
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_reopen_keeps_newer_registry_kinds() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x3Au8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        // a proof written by a release that knows more registry kinds
+        let times = util::cache_entry_times(100, 10).expect("times");
+        util::upsert_cache_entry(
+            db.vault.connection(),
+            &[schema::CACHE_KEY_PREFIX_MERKLE, 0x09],
+            &[4],
+            times,
+        )
+        .expect("insert newer kind");
+        drop(db);
+
+        let db = CacheDb::new(&path, &key).expect("reopen cache");
+        assert_eq!(count_entries(&db, schema::CACHE_KEY_PREFIX_MERKLE), 1);
+        assert!(matches!(
+            db.merkle_cache_get(RegistryKind::AccountRegistry, 105),
+            Err(StorageError::UnknownRegistryKind(0x09))
+        ));
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_migration_drops_the_refresh_seed() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x37u8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        let times = util::CacheEntryTimes {
+            inserted_at: 100,
+            expires_at: i64::MAX,
+        };
+        util::upsert_cache_entry(db.vault.connection(), &[0x04], &[0x05; 32], times)
+            .expect("insert refresh seed");
+        // a cache from before the seed moved to the vault
+        db.vault
+            .connection()
+            .execute_batch("PRAGMA user_version = 2;")
+            .expect("reset user_version");
+        drop(db);
+
+        let db = CacheDb::new(&path, &key).expect("open cache");
+        let seed_rows = db
+            .vault
+            .connection()
+            .query_row(
+                "SELECT COUNT(*) FROM cache_entries WHERE key_bytes = X'04'",
+                &[],
+                |stmt| Ok(stmt.column_i64(0)),
+            )
+            .expect("count seed rows");
+        assert_eq!(seed_rows, 0);
+        cleanup_cache_files(&path);
+    }
+
+    fn unix_now() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("clock after epoch")
+            .as_secs()
+    }
+
+    #[test]
+    fn test_upgrade_migrates_old_cache_in_place() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x36u8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        // a cache from before proofs were keyed by registry kind and before
+        // migrations were tracked
+        let times = util::cache_entry_times(100, 10).expect("times");
+        util::upsert_cache_entry(
+            db.vault.connection(),
+            &[schema::CACHE_KEY_PREFIX_MERKLE],
+            &[7, 8, 9],
+            times,
+        )
+        .expect("insert legacy proof");
+        db.session_seed_put(ENV, [0x01; 32], [0x02; 32], 100, 10)
+            .expect("put session seed");
+        db.vault
+            .connection()
+            .execute_batch("PRAGMA user_version = 0;")
+            .expect("reset user_version");
+        drop(db);
+
+        let db = CacheDb::new(&path, &key).expect("upgrade cache");
+        assert_eq!(
+            db.merkle_cache_get(RegistryKind::AccountRegistry, 105)
+                .expect("get merkle proof"),
+            Some(vec![7, 8, 9])
+        );
+        assert_eq!(count_entries(&db, schema::CACHE_KEY_PREFIX_MERKLE), 1);
+        assert_eq!(
+            db.session_seed_get(ENV, [0x01; 32], 105)
+                .expect("get session seed"),
+            Some([0x02; 32])
+        );
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_rebuild_carries_over_valid_merkle_proofs() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x37u8; 32]);
+        let now = unix_now();
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        db.merkle_cache_put(RegistryKind::AccountRegistry, &[1, 2, 3], now, 3600)
+            .expect("put merkle proof");
+        let expired = util::cache_entry_times(now - 100, 10).expect("times");
+        util::upsert_cache_entry(
+            db.vault.connection(),
+            &[schema::CACHE_KEY_PREFIX_MERKLE],
+            &[4],
+            expired,
+        )
+        .expect("insert expired legacy proof");
+        db.session_seed_put(ENV, [0x01; 32], [0x02; 32], now, 3600)
+            .expect("put session seed");
+        // a schema version this release cannot migrate, e.g. after a downgrade
+        db.vault
+            .connection()
+            .execute("UPDATE cache_meta SET schema_version = 99", &[])
+            .expect("bump schema version");
+        drop(db);
+
+        let db = CacheDb::new(&path, &key).expect("rebuild cache");
+        assert_eq!(
+            db.merkle_cache_get(RegistryKind::AccountRegistry, now)
+                .expect("get merkle proof"),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(count_entries(&db, schema::CACHE_KEY_PREFIX_MERKLE), 1);
+        assert_eq!(
+            db.session_seed_get(ENV, [0x01; 32], now)
+                .expect("get session seed"),
+            None
+        );
+        let schema_version = db
+            .vault
+            .connection()
+            .query_row("SELECT schema_version FROM cache_meta", &[], |stmt| {
+                Ok(stmt.column_i64(0))
+            })
+            .expect("read schema version");
+        assert_eq!(schema_version, 2);
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_merkle_refresh_at_is_jittered_and_stable() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x77u8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        let seed = [0x42u8; 32];
+        assert_eq!(
+            db.merkle_refresh_at(RegistryKind::AccountRegistry, 1_000, &seed)
+                .expect("refresh at"),
+            None
+        );
+
+        db.merkle_cache_put(RegistryKind::AccountRegistry, &[1, 2, 3], 1_000, 900)
+            .expect("put merkle proof");
+        let refresh_at = db
+            .merkle_refresh_at(RegistryKind::AccountRegistry, 1_000, &seed)
+            .expect("refresh at")
+            .expect("proof cached");
+        assert!((1_450..=1_810).contains(&refresh_at), "{refresh_at}");
+        assert_eq!(
+            db.merkle_refresh_at(RegistryKind::AccountRegistry, 1_200, &seed)
+                .expect("refresh at"),
+            Some(refresh_at)
+        );
+
+        // the time depends only on the seed and the cached proof
+        drop(db);
+        let db = CacheDb::new(&path, &key).expect("open cache");
+        assert_eq!(
+            db.merkle_refresh_at(RegistryKind::AccountRegistry, 1_000, &seed)
+                .expect("refresh at"),
+            Some(refresh_at)
+        );
+        assert_eq!(
+            db.merkle_refresh_at(RegistryKind::AccountRegistry, 1_901, &seed)
+                .expect("refresh at"),
+            None
+        );
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_jittered_refresh_differs_between_seeds() {
+        let first = merkle::jittered_refresh_at(&[0x01; 32], 1_000, 1_900);
+        let second = merkle::jittered_refresh_at(&[0x02; 32], 1_000, 1_900);
+        assert!((1_450..=1_810).contains(&first), "{first}");
+        assert!((1_450..=1_810).contains(&second), "{second}");
+        assert_ne!(first, second);
+    }
+
+    fn count_entries(db: &CacheDb, prefix: u8) -> i64 {
+        db.vault
+            .connection()
+            .query_row(
+                "SELECT COUNT(*) FROM cache_entries WHERE substr(key_bytes, 1, 1) = ?1",
+                params![[prefix].as_slice()],
+                |stmt| Ok(stmt.column_i64(0)),
+            )
+            .expect("count entries")
+    }
+
+    #[test]
+    fn test_session_seed_put_evicts_oldest_beyond_limit() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x45u8; 32]);
+        let config = CacheConfig {
+            max_session_keys: Some(10),
+            ..CacheConfig::default()
+        };
+        let db = CacheDb::new_with_config(&path, &key, config).expect("create cache");
+        for index in 0u8..20 {
+            db.session_seed_put(
+                ENV,
+                [index; 32],
+                [0xAA; 32],
+                100 + u64::from(index),
+                1000,
+            )
+            .expect("put session seed");
+        }
+
+        assert_eq!(count_entries(&db, schema::CACHE_KEY_PREFIX_SESSION), 10);
+        assert!(db
+            .session_seed_get(ENV, [9; 32], 200)
+            .expect("get evicted seed")
+            .is_none());
+        assert!(db
+            .session_seed_get(ENV, [10; 32], 200)
+            .expect("get kept seed")
+            .is_some());
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_merkle_cache_evict_lru() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x36u8; 32]);
+        let lock_path = temp_lock_path();
+        let lock = StorageLock::open(&lock_path).expect("open lock");
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        for index in 0u8..20 {
+            let times =
+                util::cache_entry_times(100 + u64::from(index), 1000).expect("times");
+            util::upsert_cache_entry(
+                db.vault.connection(),
+                &[schema::CACHE_KEY_PREFIX_MERKLE, 0x80 + index],
+                &[index],
+                times,
+            )
+            .expect("insert merkle row");
+        }
+        db.session_seed_put(ENV, [0x01; 32], [0x02; 32], 100, 1000)
+            .expect("put session seed");
+
+        let guard = lock.lock().expect("lock");
+        assert_eq!(db.merkle_cache_evict_lru(10, &guard).expect("evict"), 10);
+        assert_eq!(db.merkle_cache_evict_lru(10, &guard).expect("evict"), 0);
+        assert_eq!(count_entries(&db, schema::CACHE_KEY_PREFIX_MERKLE), 10);
+        // other entry types are untouched
+        assert_eq!(count_entries(&db, schema::CACHE_KEY_PREFIX_SESSION), 1);
+        drop(guard);
+        cleanup_cache_files(&path);
+        cleanup_lock_file(&lock_path);
+    }
+
     #[test]
     fn test_session_seed_cache_ttl() {
         let path = temp_cache_path();
@@ -226,13 +975,331 @@ mod tests {
         let oprf_seed = [0x55u8; 32];
         let r_seed = [0x66u8; 32];
         let now = 100;
-        db.session_seed_put(oprf_seed, r_seed, now, 10)
+        db.session_seed_put(ENV, oprf_seed, r_seed, now, 10)
             .expect("put session seed");
-        let hit = db.session_seed_get(oprf_seed, now).expect("get");
+        let hit = db.session_seed_get(ENV, oprf_seed, now).expect("get");
         assert_eq!(hit, Some(r_seed));
-        let miss = db.session_seed_get(oprf_seed, now + 11).expect("get");
+        let miss = db.session_seed_get(ENV, oprf_seed, now + 11).expect("get");
         assert!(miss.is_none());
         cleanup_cache_files(&path);
         cleanup_lock_file(&lock_path);
     }
+
+    fn nullifier(i: u32) -> [u8; 32] {
+        let mut nullifier = [0u8; 32];
+        nullifier[28..].copy_from_slice(&i.to_be_bytes());
+        nullifier
+    }
+
+    #[test]
+    fn test_replay_filter_rebuilt_on_reopen() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x11u8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        for i in 0..10 {
+            db.replay_guard_set(ENV, nullifier(i), None, None, 1000)
+                .expect("set replay guard");
+        }
+        drop(db);
+
+        let db = CacheDb::new(&path, &key).expect("open cache");
+        assert_eq!(db.replay_guard_bloom_stats().estimated_entries, 10);
+        for i in 0..10 {
+            assert!(db
+                .is_nullifier_replay(ENV, nullifier(i), 2000)
+                .expect("check replay"));
+        }
+        assert!(!db
+            .is_nullifier_replay(ENV, nullifier(10), 2000)
+            .expect("check replay"));
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_disclosure_memos() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x11u8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        db.replay_guard_set(
+            ENV,
+            nullifier(1),
+            Some([0x02; 32]),
+            Some(b"event-1"),
+            1000,
+        )
+        .expect("set replay guard");
+        db.replay_guard_set(ENV, nullifier(2), Some([0x03; 32]), None, 1100)
+            .expect("set replay guard");
+        // the memo of the first disclosure is kept
+        db.replay_guard_set(
+            ENV,
+            nullifier(1),
+            Some([0x02; 32]),
+            Some(b"event-2"),
+            1200,
+        )
+        .expect("set replay guard");
+
+        let memos = |include_memos, now| {
+            db.list_disclosures(include_memos, now)
+                .expect("list disclosures")
+                .into_iter()
+                .map(|disclosure| (disclosure.disclosed_at, disclosure.memo))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            memos(true, 2000),
+            vec![(1100, None), (1000, Some(b"event-1".to_vec()))]
+        );
+        assert_eq!(memos(false, 2000), vec![(1100, None), (1000, None)]);
+
+        // the memo does not change how the entry guards replays
+        assert!(db
+            .begin_replay_guard(ENV, nullifier(1), [0x02; 32], 2000)
+            .expect("same request"));
+        assert!(matches!(
+            db.begin_replay_guard(ENV, nullifier(1), [0x04; 32], 2000),
+            Err(StorageError::NullifierConflict { conflicting_request_id })
+                if conflicting_request_id == [0x02; 32]
+        ));
+
+        // memos expire with their entry, which the next write prunes
+        let expired = 1000 + nullifiers::REPLAY_REQUEST_TTL_SECONDS;
+        assert_eq!(memos(true, expired), vec![(1100, None)]);
+        db.replay_guard_set(ENV, nullifier(3), None, None, expired)
+            .expect("set replay guard");
+        assert_eq!(
+            count_entries(&db, schema::CACHE_KEY_PREFIX_REPLAY_NULLIFIER),
+            2
+        );
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_replay_filter_sees_writes_from_other_connections() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x11u8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        let other = CacheDb::new(&path, &key).expect("open second connection");
+        assert!(!db
+            .is_nullifier_replay(ENV, nullifier(1), 2000)
+            .expect("check replay"));
+
+        other
+            .replay_guard_set(ENV, nullifier(1), Some([0x02; 32]), None, 1000)
+            .expect("set replay guard");
+        assert!(db
+            .is_nullifier_replay(ENV, nullifier(1), 2000)
+            .expect("check replay"));
+        assert!(matches!(
+            db.begin_replay_guard(ENV, nullifier(1), [0x03; 32], 2000),
+            Err(StorageError::NullifierConflict { .. })
+        ));
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_replay_guard_and_sessions_are_scoped_to_environment() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x11u8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+
+        db.replay_guard_set(OTHER_ENV, nullifier(1), Some([0x02; 32]), None, 1000)
+            .expect("set replay guard");
+        db.session_seed_put(OTHER_ENV, [0x01; 32], [0x02; 32], 1000, 1000)
+            .expect("put session seed");
+        assert!(!db
+            .is_nullifier_replay(ENV, nullifier(1), 2000)
+            .expect("check replay"));
+        assert!(!db
+            .begin_replay_guard(ENV, nullifier(1), [0x03; 32], 2000)
+            .expect("no conflict across environments"));
+        assert!(db
+            .session_seed_get(ENV, [0x01; 32], 1000)
+            .expect("get session seed")
+            .is_none());
+        assert!(db
+            .is_nullifier_replay(OTHER_ENV, nullifier(1), 2000)
+            .expect("check replay"));
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_begin_replay_guard_reads_stored_request_ids() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x11u8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        let times = util::cache_entry_times(1000, 10_000).expect("times");
+        for (n, value) in [(1, [0x1].as_slice()), (2, [0x02; 16].as_slice())] {
+            let key = util::replay_nullifier_key(ENV, nullifier(n));
+            util::upsert_cache_entry(db.vault.connection(), &key, value, times)
+                .expect("insert replay guard entry");
+        }
+        // reopen, so the replay filter picks up the rows
+        drop(db);
+        let db = CacheDb::new(&path, &key).expect("open cache");
+
+        // entries recorded without a request are replays of any request
+        assert!(db
+            .begin_replay_guard(ENV, nullifier(1), [0x03; 32], 2000)
+            .expect("check replay"));
+        assert!(matches!(
+            db.begin_replay_guard(ENV, nullifier(2), [0x03; 32], 2000),
+            Err(StorageError::CorruptedCacheEntry {
+                key_prefix: schema::CACHE_KEY_PREFIX_REPLAY_NULLIFIER
+            })
+        ));
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_unscoped_entries_move_into_provisioned_environment() {
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x11u8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        let times = util::cache_entry_times(1000, 1000).expect("times");
+        let unscoped = |prefix: u8, payload: [u8; 32]| {
+            let mut key = vec![prefix];
+            key.extend_from_slice(&payload);
+            key
+        };
+        for (key, value) in [
+            (
+                unscoped(schema::CACHE_KEY_PREFIX_REPLAY_NULLIFIER, nullifier(1)),
+                [0x02; 32],
+            ),
+            (
+                unscoped(schema::CACHE_KEY_PREFIX_REPLAY_NULLIFIER, nullifier(2)),
+                [0x04; 32],
+            ),
+            (
+                unscoped(schema::CACHE_KEY_PREFIX_SESSION, [0x01; 32]),
+                [0x05; 32],
+            ),
+        ] {
+            util::upsert_cache_entry(db.vault.connection(), &key, &value, times)
+                .expect("insert unscoped row");
+        }
+        // reopen, so the replay filter picks up the rows
+        drop(db);
+        let db = CacheDb::new(&path, &key).expect("open cache");
+        // an entry already in the environment wins over an unscoped one
+        db.replay_guard_set(ENV, nullifier(2), Some([0x03; 32]), None, 1000)
+            .expect("set replay guard");
+
+        assert_eq!(
+            db.provision_environment(ENV)
+                .expect("provision environment"),
+            ENV
+        );
+        assert_eq!(
+            count_entries(&db, schema::CACHE_KEY_PREFIX_REPLAY_NULLIFIER),
+            2
+        );
+        match db.begin_replay_guard(ENV, nullifier(1), [0x03; 32], 2000) {
+            Err(StorageError::NullifierConflict {
+                conflicting_request_id,
+            }) => assert_eq!(conflicting_request_id, [0x02; 32]),
+            other => panic!("expected a conflict, got {other:?}"),
+        }
+        assert!(db
+            .begin_replay_guard(ENV, nullifier(2), [0x03; 32], 2000)
+            .expect("check replay"));
+        assert_eq!(
+            db.session_seed_get(ENV, [0x01; 32], 1000)
+                .expect("get session seed"),
+            Some([0x05; 32])
+        );
+
+        // another environment opening the store later cannot claim them
+        drop(db);
+        let db = CacheDb::new(&path, &key).expect("reopen cache");
+        assert_eq!(
+            db.provision_environment(OTHER_ENV)
+                .expect("provision environment"),
+            ENV
+        );
+        assert!(!db
+            .is_nullifier_replay(OTHER_ENV, nullifier(1), 2000)
+            .expect("check replay"));
+        assert!(db
+            .session_seed_get(OTHER_ENV, [0x01; 32], 1000)
+            .expect("get session seed")
+            .is_none());
+        assert!(db
+            .is_nullifier_replay(ENV, nullifier(1), 2000)
+            .expect("check replay"));
+        cleanup_cache_files(&path);
+    }
+
+    #[test]
+    fn test_replay_filter_stats() {
+        let mut filter = bloom::ReplayGuardBloomFilter::load(
+            CacheDb::new(&temp_cache_path(), &SecretBox::init_with(|| [0x11u8; 32]))
+                .expect("create cache")
+                .vault
+                .connection(),
+        )
+        .expect("load filter");
+        assert_eq!(filter.stats().estimated_entries, 0);
+        for i in 0..20_000 {
+            filter.insert(&nullifier(i));
+        }
+        let stats = filter.stats();
+        assert!(stats.estimated_entries.abs_diff(20_000) < 400, "{stats:?}");
+        assert!(stats.bit_saturation > 0.15 && stats.bit_saturation < 0.2);
+
+        let false_positives = (20_000..120_000)
+            .filter(|&i| filter.might_contain(&nullifier(i)))
+            .count();
+        // expected rate at 20k entries is about 0.02%
+        assert!(false_positives < 100, "{false_positives} false positives");
+    }
+
+    /// Compares replay checks for fresh nullifiers answered by the Bloom
+    /// filter against the `SQLite` lookup they replace, with 10 000 disclosed
+    /// nullifiers.
+    ///
+    /// Run with:
+    /// `cargo test -p walletkit-core --release bench_replay_filter -- --ignored --nocapture`
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_replay_filter() {
+        const DISCLOSED: u32 = 10_000;
+        const CHECKS: u32 = 10_000;
+        let path = temp_cache_path();
+        let key = SecretBox::init_with(|| [0x11u8; 32]);
+        let db = CacheDb::new(&path, &key).expect("create cache");
+        for i in 0..DISCLOSED {
+            db.replay_guard_set(ENV, nullifier(i), None, None, 1000)
+                .expect("set replay guard");
+        }
+
+        let start = std::time::Instant::now();
+        for i in DISCLOSED..DISCLOSED + CHECKS {
+            assert!(!db
+                .is_nullifier_replay(ENV, nullifier(i), 2000)
+                .expect("check"));
+        }
+        let filtered = start.elapsed();
+
+        let start = std::time::Instant::now();
+        for i in DISCLOSED..DISCLOSED + CHECKS {
+            assert!(!nullifiers::is_nullifier_replay(
+                db.vault.connection(),
+                ENV,
+                nullifier(i),
+                2000
+            )
+            .expect("check"));
+        }
+        let direct = start.elapsed();
+
+        println!(
+            "{CHECKS} fresh nullifier checks: {filtered:?} with the filter, \
+             {direct:?} against SQLite ({:?})",
+            db.replay_guard_bloom_stats()
+        );
+        cleanup_cache_files(&path);
+    }
 }
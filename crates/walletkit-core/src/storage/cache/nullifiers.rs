@@ -5,13 +5,22 @@
 //! delays enforcement so a proof that failed to reach the RP can be retried;
 //! entries expire after [`REPLAY_REQUEST_TTL_SECONDS`] and may be pruned.
 //!
+//! Entries are scoped to the environment (World ID registry) the nullifier
+//! was generated against, so a proof generated against one environment never
+//! blocks a request in another.
+//!
+//! An entry may carry a memo supplied by the host (e.g. its own event ID), so
+//! support can tell when the device disclosed a proof without learning to
+//! whom. Memos live and expire with their entry.
 
-use crate::storage::error::StorageResult;
-use walletkit_db::Connection;
+use crate::storage::error::{StorageError, StorageResult};
+use crate::storage::types::DisclosureRecord;
+use walletkit_db::{params, Connection, StepResult};
 
+use super::schema::CACHE_KEY_PREFIX_REPLAY_NULLIFIER;
 use super::util::{
     cache_entry_times, get_cache_entry, get_cache_entry_tx, insert_cache_entry_tx,
-    map_db_err, prune_expired_entries_tx, replay_nullifier_key,
+    map_db_err, prune_expired_entries_tx, replay_nullifier_key, to_i64,
 };
 
 /// The time to wait before a replayed request starts being enforced.
@@ -24,9 +33,13 @@ use super::util::{
 const REPLAY_REQUEST_NBF_SECONDS: u64 = 600; // 10 minutes
 
 /// Retention window for a replay guard entry. Bounded to avoid indefinite growth.
-static REPLAY_REQUEST_TTL_SECONDS: u64 = 60 * 60 * 24 * 365; // 1 year
+pub(super) const REPLAY_REQUEST_TTL_SECONDS: u64 = 60 * 60 * 24 * 365; // 1 year
+
+/// Value of a replay guard entry whose disclosing request is unknown.
+const UNKNOWN_REQUEST_MARKER: &[u8] = &[0x1];
 
-/// Checks whether a replay guard entry exists for the given nullifier.
+/// Checks whether a replay guard entry exists for the given nullifier in
+/// `environment`.
 ///
 /// # Returns
 /// - bool: true if a replay guard entry exists (hence signalling a nullifier replay), false otherwise.
@@ -36,23 +49,84 @@ static REPLAY_REQUEST_TTL_SECONDS: u64 = 60 * 60 * 24 * 365; // 1 year
 /// Returns an error if the query to the cache unexpectedly fails.
 pub(super) fn is_nullifier_replay(
     conn: &Connection,
+    environment: [u8; 32],
     nullifier: [u8; 32],
     now: u64,
 ) -> StorageResult<bool> {
-    let key = replay_nullifier_key(nullifier);
+    let key = replay_nullifier_key(environment, nullifier);
     let nbf = now.saturating_sub(REPLAY_REQUEST_NBF_SECONDS);
     let result = get_cache_entry(conn, key.as_slice(), now, Some(nbf))?;
     Ok(result.is_some())
 }
 
+/// Checks the replay guard before generating a proof for `request_id`.
+///
+/// Enforced entries (past the grace period) recorded for a different request
+/// are a cross-request conflict; entries recorded for the same request, or
+/// for an unknown one, are a replay.
+///
+/// # Returns
+/// - bool: true if the nullifier was already disclosed for this request (replay), false if it was not disclosed.
+///
+/// # Errors
+///
+/// Returns [`StorageError::NullifierConflict`] if the nullifier was disclosed
+/// for a different request, or an error if the query unexpectedly fails.
+pub(super) fn begin_replay_guard(
+    conn: &Connection,
+    environment: [u8; 32],
+    nullifier: [u8; 32],
+    request_id: [u8; 32],
+    now: u64,
+) -> StorageResult<bool> {
+    let key = replay_nullifier_key(environment, nullifier);
+    let nbf = now.saturating_sub(REPLAY_REQUEST_NBF_SECONDS);
+    let Some(entry) = get_cache_entry(conn, key.as_slice(), now, Some(nbf))? else {
+        return Ok(false);
+    };
+    match disclosed_request_id(&entry)? {
+        Some(disclosed) if disclosed != request_id => {
+            Err(StorageError::NullifierConflict {
+                conflicting_request_id: disclosed,
+            })
+        }
+        _ => Ok(true),
+    }
+}
+
+/// Returns the request a replay guard entry was recorded for, or `None` if
+/// it was recorded without one ([`UNKNOWN_REQUEST_MARKER`]).
+///
+/// # Errors
+///
+/// Returns [`StorageError::CorruptedCacheEntry`] if the entry is neither the
+/// marker nor starts with a request ID digest.
+fn disclosed_request_id(entry: &[u8]) -> StorageResult<Option<[u8; 32]>> {
+    if entry == UNKNOWN_REQUEST_MARKER {
+        return Ok(None);
+    }
+    entry
+        .get(..32)
+        .and_then(|request_id| request_id.try_into().ok())
+        .map(Some)
+        .ok_or(StorageError::CorruptedCacheEntry {
+            key_prefix: CACHE_KEY_PREFIX_REPLAY_NULLIFIER,
+        })
+}
+
 /// After a proof has been successfully generated, creates a replay guard entry
-/// locally to avoid future replays of the same nullifier.
+/// locally to avoid future replays of the same nullifier, recording the
+/// request it was disclosed for and the host's `memo`, if known. A memo is
+/// only kept together with a request.
 ///
 /// This operation is idempotent - if an entry already exists and hasn't expired,
-/// it will not be re-inserted (maintains the original insertion time).
+/// it will not be re-inserted (maintains the original insertion time and memo).
 pub(super) fn replay_guard_set(
     conn: &Connection,
+    environment: [u8; 32],
     nullifier: [u8; 32],
+    request_id: Option<[u8; 32]>,
+    memo: Option<&[u8]>,
     now: u64,
 ) -> StorageResult<()> {
     let tx = conn
@@ -60,7 +134,7 @@ pub(super) fn replay_guard_set(
         .map_err(|err| map_db_err(&err))?;
     prune_expired_entries_tx(&tx, now)?;
 
-    let key = replay_nullifier_key(nullifier);
+    let key = replay_nullifier_key(environment, nullifier);
 
     // Check if entry already exists (idempotency check)
     let existing = get_cache_entry_tx(&tx, key.as_slice(), now, None)?;
@@ -72,7 +146,78 @@ pub(super) fn replay_guard_set(
 
     // Insert new entry
     let times = cache_entry_times(now, REPLAY_REQUEST_TTL_SECONDS)?;
-    insert_cache_entry_tx(&tx, key.as_slice(), &[0x1], times)?;
+    let value = request_id.map_or_else(
+        || UNKNOWN_REQUEST_MARKER.to_vec(),
+        |request_id| [request_id.as_slice(), memo.unwrap_or_default()].concat(),
+    );
+    insert_cache_entry_tx(&tx, key.as_slice(), &value, times)?;
     tx.commit().map_err(|err| map_db_err(&err))?;
     Ok(())
 }
+
+/// Lists the unexpired replay guard entries across environments, most recent
+/// first. Memos are only returned if `include_memos` is set.
+///
+/// # Errors
+///
+/// Returns an error if the query to the cache unexpectedly fails.
+pub(super) fn list_disclosures(
+    conn: &Connection,
+    include_memos: bool,
+    now: u64,
+) -> StorageResult<Vec<DisclosureRecord>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT value_bytes, inserted_at FROM cache_entries
+             WHERE substr(key_bytes, 1, 1) = ?1 AND expires_at > ?2
+             ORDER BY inserted_at DESC",
+        )
+        .map_err(|err| map_db_err(&err))?;
+    stmt.bind_values(params![
+        [CACHE_KEY_PREFIX_REPLAY_NULLIFIER].as_slice(),
+        to_i64(now, "now")?
+    ])
+    .map_err(|err| map_db_err(&err))?;
+    let mut disclosures = Vec::new();
+    while let StepResult::Row(row) = stmt.step().map_err(|err| map_db_err(&err))? {
+        let value = row.column_blob(0);
+        let inserted_at = row.column_i64(1);
+        let disclosed_at = u64::try_from(inserted_at).map_err(|_| {
+            StorageError::CacheDb(format!("invalid inserted_at {inserted_at}"))
+        })?;
+        let memo = value
+            .get(32..)
+            .filter(|memo| include_memos && !memo.is_empty())
+            .map(<[u8]>::to_vec);
+        disclosures.push(DisclosureRecord { disclosed_at, memo });
+    }
+    Ok(disclosures)
+}
+
+/// Counts the replay guard entries recorded in `[since, now)`, i.e. the
+/// distinct nullifiers disclosed in that window whose entry has not been
+/// pruned since.
+///
+/// # Errors
+///
+/// Returns an error if the query to the cache unexpectedly fails.
+pub(super) fn count_replay_guard_entries(
+    conn: &Connection,
+    since: u64,
+    now: u64,
+) -> StorageResult<u64> {
+    let count = conn
+        .query_row(
+            "SELECT COUNT(*) FROM cache_entries
+             WHERE substr(key_bytes, 1, 1) = ?1
+               AND inserted_at >= ?2 AND inserted_at < ?3",
+            params![
+                [CACHE_KEY_PREFIX_REPLAY_NULLIFIER].as_slice(),
+                to_i64(since, "since")?,
+                to_i64(now, "now")?
+            ],
+            |row| Ok(row.column_i64(0)),
+        )
+        .map_err(|err| map_db_err(&err))?;
+    Ok(count.unsigned_abs())
+}
@@ -0,0 +1,39 @@
+//! RP signing key (JWKS) cache helpers.
+
+use crate::storage::error::StorageResult;
+use walletkit_db::Connection;
+
+use super::util::{
+    cache_entry_times, get_cache_entry, prune_expired_entries, rp_keys_cache_key,
+    upsert_cache_entry,
+};
+
+/// Fetches the cached JWKS document of `rp_id`, if still valid.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+pub(super) fn get(
+    conn: &Connection,
+    rp_id: &str,
+    now: u64,
+) -> StorageResult<Option<Vec<u8>>> {
+    get_cache_entry(conn, &rp_keys_cache_key(rp_id), now, None)
+}
+
+/// Stores the JWKS document of `rp_id` with a TTL, replacing any earlier one.
+///
+/// # Errors
+///
+/// Returns an error if pruning or insert fails.
+pub(super) fn put(
+    conn: &Connection,
+    rp_id: &str,
+    jwks: &[u8],
+    now: u64,
+    ttl_seconds: u64,
+) -> StorageResult<()> {
+    prune_expired_entries(conn, now)?;
+    let times = cache_entry_times(now, ttl_seconds)?;
+    upsert_cache_entry(conn, &rp_keys_cache_key(rp_id), jwks, times)
+}
@@ -5,24 +5,57 @@
 //!
 //! The keys adhere to the following schema:
 //!
-//! - `0x01` — Merkle inclusion proof; at most one entry; value is the proof bytes.
-//! - `0x02 || oprf_seed` — session seed; value is the `session_id_r_seed`.
-//! - `0x03 || nullifier` — replay guard; value is a presence marker.
+//! - `0x01 || registry_kind` — Merkle inclusion proof; at most one entry per
+//!   [`RegistryKind`]; value is the proof bytes.
+//! - `0x02 || environment || oprf_seed` — session seed; value is the
+//!   `session_id_r_seed`.
+//! - `0x03 || environment || nullifier` — replay guard; value is the SHA-256
+//!   digest of the disclosing request's ID followed by the host's memo, if
+//!   any, or `0x01` if the request is unknown.
+//! - `0x04` — retired; held the Merkle refresh seed, which now lives in the
+//!   vault.
+//! - `0x05 || rp_id` — RP signing keys; value is the JWKS document fetched
+//!   for the RP (`rp_id` as UTF-8).
+//!
+//! `environment` is the 32-byte scope of the World ID registry the entry
+//! belongs to. Session seed and replay guard entries written before entries
+//! were scoped have no `environment`. `cache_environment` records the
+//! environment the store was provisioned for; recording it moves those
+//! entries into it, once (see `provision_environment`).
+//!
+//! Upgrades migrate the entries in place. Only caches that cannot be
+//! migrated are rebuilt, and the rebuild keeps their still-valid Merkle
+//! proofs (see `carry_over`).
 
 pub(super) const CACHE_KEY_PREFIX_MERKLE: u8 = 0x01;
 pub(super) const CACHE_KEY_PREFIX_SESSION: u8 = 0x02;
 pub(super) const CACHE_KEY_PREFIX_REPLAY_NULLIFIER: u8 = 0x03;
+pub(super) const CACHE_KEY_PREFIX_RP_KEYS: u8 = 0x05;
+
+use walletkit_db::migration::{run_migrations, Migration, MigrationProgress};
+use walletkit_db::{params, Connection, DbResult, StepResult, Value};
 
-use walletkit_db::{params, Connection, DbResult};
+use crate::storage::types::RegistryKind;
+use crate::storage::CACHE_APPLICATION_ID;
 
 const CACHE_SCHEMA_VERSION: i64 = 2;
 
+/// Oldest `cache_meta.schema_version` that is migrated in place. Earlier
+/// caches kept Merkle proofs, session keys, and nullifiers in separate tables
+/// and are rebuilt, as are caches written by a newer version.
+///
+/// From this version on, changes to `cache_entries` go into
+/// [`ENTRIES_MIGRATIONS`] and leave the version alone, so upgrading keeps the
+/// cached proofs and session seeds.
+const MIN_MIGRATABLE_SCHEMA_VERSION: i64 = 2;
+
 /// Ensures the cache schema is present and at the expected version.
 ///
 /// # Errors
 ///
 /// Returns an error if schema creation or migration fails.
 pub(super) fn ensure_schema(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(&format!("PRAGMA application_id = {CACHE_APPLICATION_ID};"))?;
     conn.execute_batch(
         "CREATE TABLE IF NOT EXISTS cache_meta (
             schema_version  INTEGER NOT NULL,
@@ -38,11 +71,21 @@ pub(super) fn ensure_schema(conn: &Connection) -> DbResult<()> {
     )?;
 
     match existing {
-        Some(version) if version == CACHE_SCHEMA_VERSION => {
+        Some(version)
+            if (MIN_MIGRATABLE_SCHEMA_VERSION..=CACHE_SCHEMA_VERSION)
+                .contains(&version) =>
+        {
             ensure_entries_schema(conn)?;
+            if version != CACHE_SCHEMA_VERSION {
+                conn.execute(
+                    "UPDATE cache_meta
+                     SET schema_version = ?1, updated_at = strftime('%s','now')",
+                    params![CACHE_SCHEMA_VERSION],
+                )?;
+            }
         }
         Some(_) => {
-            reset_schema(conn)?;
+            rebuild_schema(conn)?;
         }
         None => {
             ensure_entries_schema(conn)?;
@@ -52,19 +95,195 @@ pub(super) fn ensure_schema(conn: &Connection) -> DbResult<()> {
     Ok(())
 }
 
+/// Migrations of the `cache_entries` table.
+///
+/// Version 1 is idempotent: caches created before migrations were tracked
+/// already have the table but report `user_version = 0`. Version 2 keys
+/// Merkle proofs by [`RegistryKind`], see [`migrate_merkle_entries`].
+/// Version 3 drops the Merkle refresh seed (prefix `0x04`), which never
+/// expired and is now kept in the vault. Version 4 adds `cache_environment`,
+/// which holds the environment the store was provisioned for once it is
+/// known.
+const ENTRIES_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        sql: "CREATE TABLE IF NOT EXISTS cache_entries (
+                key_bytes       BLOB    NOT NULL,
+                value_bytes     BLOB    NOT NULL,
+                inserted_at     INTEGER NOT NULL,
+                expires_at      INTEGER NOT NULL,
+                PRIMARY KEY (key_bytes)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_cache_entries_expiry
+            ON cache_entries (expires_at);",
+        description: "cache entries",
+        data_migration: None,
+    },
+    Migration {
+        version: 2,
+        sql: "",
+        description: "Merkle proofs keyed by registry kind",
+        data_migration: Some(migrate_merkle_entries),
+    },
+    Migration {
+        version: 3,
+        sql: "DELETE FROM cache_entries WHERE substr(key_bytes, 1, 1) = X'04';",
+        description: "drop the Merkle refresh seed",
+        data_migration: None,
+    },
+    Migration {
+        version: 4,
+        sql: "CREATE TABLE IF NOT EXISTS cache_environment (
+                id          INTEGER NOT NULL PRIMARY KEY CHECK (id = 0),
+                environment BLOB    NOT NULL
+            );",
+        description: "provisioned environment",
+        data_migration: None,
+    },
+];
+
 fn ensure_entries_schema(conn: &Connection) -> DbResult<()> {
-    conn.execute_batch(
-        "CREATE TABLE IF NOT EXISTS cache_entries (
-            key_bytes       BLOB    NOT NULL,
-            value_bytes     BLOB    NOT NULL,
-            inserted_at     INTEGER NOT NULL,
-            expires_at      INTEGER NOT NULL,
-            PRIMARY KEY (key_bytes)
+    run_migrations(conn, ENTRIES_MIGRATIONS, |progress: MigrationProgress| {
+        tracing::info!(
+            "cache migration {}/{} applied (schema version {})",
+            progress.migration_index + 1,
+            progress.total,
+            progress.version_applied
         );
+    })
+    .map(|_| ())
+}
+
+/// Returns the cache keys of the Merkle proofs of every known
+/// [`RegistryKind`].
+fn known_merkle_keys() -> Vec<Value> {
+    RegistryKind::ALL
+        .iter()
+        .map(|kind| Value::Blob(vec![CACHE_KEY_PREFIX_MERKLE, kind.as_u8()]))
+        .collect()
+}
+
+/// Moves the Merkle proof stored under the legacy single-byte key, from
+/// before proofs were keyed by [`RegistryKind`], to the account registry's
+/// key, unless that key already has a proof. Then deletes Merkle proof
+/// entries whose key does not name a known kind.
+///
+/// Runs once, as migration 2. Caches that are already at version 2 are left
+/// alone, so proofs under kinds added by a newer release survive a
+/// downgrade or a cache shared between app versions; an older release
+/// reports them as [`StorageError::UnknownRegistryKind`] instead.
+///
+/// [`StorageError::UnknownRegistryKind`]: crate::storage::StorageError::UnknownRegistryKind
+fn migrate_merkle_entries(conn: &Connection) -> DbResult<()> {
+    conn.execute(
+        "UPDATE OR IGNORE cache_entries SET key_bytes = ?1 WHERE key_bytes = ?2",
+        params![
+            [
+                CACHE_KEY_PREFIX_MERKLE,
+                RegistryKind::AccountRegistry.as_u8()
+            ]
+            .as_slice(),
+            [CACHE_KEY_PREFIX_MERKLE].as_slice()
+        ],
+    )?;
+    let mut params = vec![Value::Blob(vec![CACHE_KEY_PREFIX_MERKLE])];
+    params.extend(known_merkle_keys());
+    let placeholders = (2..=params.len())
+        .map(|index| format!("?{index}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    conn.execute(
+        &format!(
+            "DELETE FROM cache_entries
+             WHERE substr(key_bytes, 1, 1) = ?1 AND key_bytes NOT IN ({placeholders})"
+        ),
+        &params,
+    )?;
+    Ok(())
+}
 
-        CREATE INDEX IF NOT EXISTS idx_cache_entries_expiry
-        ON cache_entries (expires_at);",
-    )
+/// A Merkle proof row kept across a rebuild, see [`carry_over`].
+struct CarriedEntry {
+    key_bytes: Vec<u8>,
+    value_bytes: Vec<u8>,
+    inserted_at: i64,
+    expires_at: i64,
+}
+
+/// Reads the still-valid Merkle proofs of a cache that is about to be
+/// rebuilt, so the rebuild does not send every client to the indexer at
+/// once.
+///
+/// Only `cache_entries` rows under a known [`RegistryKind`] key (or the
+/// legacy single-byte key) are carried over. Proofs are fetched again if
+/// their bytes turn out not to decode. Caches without a `cache_entries` table
+/// carry nothing over.
+fn carry_over(conn: &Connection) -> DbResult<Vec<CarriedEntry>> {
+    let has_entries = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master
+         WHERE type = 'table' AND name = 'cache_entries'",
+        &[],
+        |row| Ok(row.column_i64(0) != 0),
+    )?;
+    if !has_entries {
+        return Ok(Vec::new());
+    }
+    let keys = known_merkle_keys();
+    let placeholders = (2..=keys.len() + 1)
+        .map(|index| format!("?{index}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut params = vec![Value::Blob(vec![CACHE_KEY_PREFIX_MERKLE])];
+    params.extend(keys);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT key_bytes, value_bytes, inserted_at, expires_at FROM cache_entries
+         WHERE (key_bytes = ?1 OR key_bytes IN ({placeholders}))
+           AND expires_at > CAST(strftime('%s','now') AS INTEGER)
+         ORDER BY length(key_bytes) ASC"
+    ))?;
+    stmt.bind_values(&params)?;
+    let mut carried = Vec::new();
+    while let StepResult::Row(row) = stmt.step()? {
+        let mut key_bytes = row.column_blob(0);
+        if key_bytes == [CACHE_KEY_PREFIX_MERKLE] {
+            key_bytes.push(RegistryKind::AccountRegistry.as_u8());
+        }
+        carried.push(CarriedEntry {
+            key_bytes,
+            value_bytes: row.column_blob(1),
+            inserted_at: row.column_i64(2),
+            expires_at: row.column_i64(3),
+        });
+    }
+    Ok(carried)
+}
+
+/// Drops and recreates the cache, keeping the entries from [`carry_over`].
+///
+/// A proof under the account registry's key replaces one carried over from
+/// the legacy key, which is read first.
+fn rebuild_schema(conn: &Connection) -> DbResult<()> {
+    let carried = carry_over(conn)?;
+    reset_schema(conn)?;
+    tracing::info!(
+        "cache rebuilt, carried over {} Merkle proof(s)",
+        carried.len()
+    );
+    for entry in carried {
+        conn.execute(
+            "INSERT OR REPLACE INTO cache_entries
+             (key_bytes, value_bytes, inserted_at, expires_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                entry.key_bytes.as_slice(),
+                entry.value_bytes.as_slice(),
+                entry.inserted_at,
+                entry.expires_at
+            ],
+        )?;
+    }
+    Ok(())
 }
 
 fn reset_schema(conn: &Connection) -> DbResult<()> {
@@ -72,7 +291,9 @@ fn reset_schema(conn: &Connection) -> DbResult<()> {
         "DROP TABLE IF EXISTS used_nullifiers;
          DROP TABLE IF EXISTS merkle_proof_cache;
          DROP TABLE IF EXISTS session_keys;
-         DROP TABLE IF EXISTS cache_entries;",
+         DROP TABLE IF EXISTS cache_entries;
+         DROP TABLE IF EXISTS cache_environment;
+         PRAGMA user_version = 0;",
     )?;
     ensure_entries_schema(conn)?;
     conn.execute("DELETE FROM cache_meta;", &[])?;
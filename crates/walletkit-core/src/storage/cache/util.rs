@@ -3,14 +3,29 @@
 use std::io;
 
 use crate::storage::{
-    cache::schema::{CACHE_KEY_PREFIX_REPLAY_NULLIFIER, CACHE_KEY_PREFIX_SESSION},
+    cache::schema::{
+        CACHE_KEY_PREFIX_REPLAY_NULLIFIER, CACHE_KEY_PREFIX_RP_KEYS,
+        CACHE_KEY_PREFIX_SESSION,
+    },
     error::{StorageError, StorageResult},
 };
 use walletkit_db::{params, Connection, DbError, Transaction};
 
 /// Maps a database error into a cache storage error.
 pub(super) fn map_db_err(err: &DbError) -> StorageError {
-    StorageError::CacheDb(err.to_string())
+    StorageError::CacheSqlite {
+        code: err.code.0,
+        message: err.message.clone(),
+    }
+}
+
+/// Maps an error from the underlying [`walletkit_db::Vault`] into a cache
+/// storage error, keeping the `SQLite` result code of database errors.
+pub(super) fn map_store_err(err: walletkit_db::StoreError) -> StorageError {
+    match err {
+        walletkit_db::StoreError::Db(err) => map_db_err(&err),
+        err => StorageError::CacheDb(err.to_string()),
+    }
 }
 
 /// Maps an IO error into a cache storage error.
@@ -90,6 +105,34 @@ pub(super) fn prune_expired_entries_tx(
     Ok(())
 }
 
+/// Deletes the least recently inserted entries whose key starts with `prefix`
+/// until at most `max_entries` remain.
+///
+/// Returns the number of deleted entries.
+///
+/// # Errors
+///
+/// Returns an error if the deletion fails.
+pub(super) fn evict_lru(
+    conn: &Connection,
+    prefix: u8,
+    max_entries: u64,
+) -> StorageResult<u64> {
+    let keep = i64::try_from(max_entries).unwrap_or(i64::MAX);
+    let deleted = conn
+        .execute(
+            "DELETE FROM cache_entries WHERE rowid IN (
+                SELECT rowid FROM cache_entries
+                WHERE substr(key_bytes, 1, 1) = ?1
+                ORDER BY inserted_at DESC, rowid DESC
+                LIMIT -1 OFFSET ?2
+            )",
+            params![[prefix].as_slice(), keep],
+        )
+        .map_err(|err| map_db_err(&err))?;
+    Ok(deleted as u64)
+}
+
 /// Inserts or replaces a cache entry row.
 ///
 /// # Errors
@@ -212,14 +255,111 @@ fn cache_key_with_prefix(prefix: u8, payload: &[u8]) -> Vec<u8> {
     key
 }
 
+/// Builds the key of an entry scoped to `environment`.
+fn scoped_cache_key(prefix: u8, environment: [u8; 32], payload: [u8; 32]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(1 + 2 * 32);
+    key.push(prefix);
+    key.extend_from_slice(&environment);
+    key.extend_from_slice(&payload);
+    key
+}
+
 /// Builds the cache key for a session key entry.
-pub(super) fn session_cache_key(rp_id: [u8; 32]) -> Vec<u8> {
-    cache_key_with_prefix(CACHE_KEY_PREFIX_SESSION, rp_id.as_ref())
+pub(super) fn session_cache_key(environment: [u8; 32], oprf_seed: [u8; 32]) -> Vec<u8> {
+    scoped_cache_key(CACHE_KEY_PREFIX_SESSION, environment, oprf_seed)
 }
 
 /// Builds the cache key for a replay-guard nullifier entry.
-pub(super) fn replay_nullifier_key(nullifier: [u8; 32]) -> Vec<u8> {
-    cache_key_with_prefix(CACHE_KEY_PREFIX_REPLAY_NULLIFIER, nullifier.as_ref())
+pub(super) fn replay_nullifier_key(
+    environment: [u8; 32],
+    nullifier: [u8; 32],
+) -> Vec<u8> {
+    scoped_cache_key(CACHE_KEY_PREFIX_REPLAY_NULLIFIER, environment, nullifier)
+}
+
+/// Length of the session seed and replay guard keys written before entries
+/// were scoped to an environment: the prefix and a 32-byte payload.
+const UNSCOPED_KEY_LEN: i64 = 1 + 32;
+
+/// Records `environment` as the environment the store was provisioned for,
+/// unless one is recorded already, and returns the recorded one.
+///
+/// Recording it moves the session seed and replay guard entries written
+/// before entries were scoped to an environment into `environment`, in the
+/// same transaction, so the move happens once per cache and no other
+/// environment can claim them later. Entries that collide with one already
+/// in `environment` are dropped, the existing entry is kept.
+///
+/// # Errors
+///
+/// Returns an error if the query or update fails, in which case nothing is
+/// recorded.
+pub(super) fn provision_environment(
+    conn: &Connection,
+    environment: [u8; 32],
+) -> StorageResult<[u8; 32]> {
+    let tx = conn
+        .transaction_immediate()
+        .map_err(|err| map_db_err(&err))?;
+    let recorded = {
+        let mut stmt = tx
+            .prepare("SELECT environment FROM cache_environment WHERE id = 0")
+            .map_err(|err| map_db_err(&err))?;
+        match stmt.step().map_err(|err| map_db_err(&err))? {
+            walletkit_db::StepResult::Row(row) => Some(row.column_blob(0)),
+            walletkit_db::StepResult::Done => None,
+        }
+    };
+    if let Some(recorded) = recorded {
+        return parse_fixed_bytes::<32>(&recorded, "provisioned environment");
+    }
+    tx.execute(
+        "INSERT INTO cache_environment (id, environment) VALUES (0, ?1)",
+        params![environment.as_slice()],
+    )
+    .map_err(|err| map_db_err(&err))?;
+    for prefix in [CACHE_KEY_PREFIX_SESSION, CACHE_KEY_PREFIX_REPLAY_NULLIFIER] {
+        let mut unscoped = Vec::new();
+        {
+            let mut stmt = tx
+                .prepare(
+                    "SELECT key_bytes FROM cache_entries
+                     WHERE substr(key_bytes, 1, 1) = ?1 AND length(key_bytes) = ?2",
+                )
+                .map_err(|err| map_db_err(&err))?;
+            stmt.bind_values(params![[prefix].as_slice(), UNSCOPED_KEY_LEN])
+                .map_err(|err| map_db_err(&err))?;
+            while let walletkit_db::StepResult::Row(row) =
+                stmt.step().map_err(|err| map_db_err(&err))?
+            {
+                unscoped.push(row.column_blob(0));
+            }
+        }
+        for key in unscoped {
+            let payload = parse_fixed_bytes::<32>(&key[1..], "unscoped cache key")?;
+            tx.execute(
+                "UPDATE OR IGNORE cache_entries SET key_bytes = ?1 WHERE key_bytes = ?2",
+                params![
+                    scoped_cache_key(prefix, environment, payload).as_slice(),
+                    key.as_slice()
+                ],
+            )
+            .map_err(|err| map_db_err(&err))?;
+        }
+        tx.execute(
+            "DELETE FROM cache_entries
+             WHERE substr(key_bytes, 1, 1) = ?1 AND length(key_bytes) = ?2",
+            params![[prefix].as_slice(), UNSCOPED_KEY_LEN],
+        )
+        .map_err(|err| map_db_err(&err))?;
+    }
+    tx.commit().map_err(|err| map_db_err(&err))?;
+    Ok(environment)
+}
+
+/// Builds the cache key for an RP's signing keys.
+pub(super) fn rp_keys_cache_key(rp_id: &str) -> Vec<u8> {
+    cache_key_with_prefix(CACHE_KEY_PREFIX_RP_KEYS, rp_id.as_bytes())
 }
 
 /// Computes an expiry timestamp using saturating addition.
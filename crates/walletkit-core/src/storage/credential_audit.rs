@@ -0,0 +1,312 @@
+//! Merkle proof freshness audit of the credentials held in a
+//! [`CredentialStore`].
+//!
+//! Credentials do not carry a Merkle proof of their own: every proof for an
+//! account is generated against the account's inclusion proof in the World ID
+//! registry, which is cached in the [`CacheDb`](super::CacheDb). A credential
+//! is therefore only as fresh as that cached proof, and the audit checks its
+//! root against the registry once for all credentials.
+
+use std::sync::Arc;
+
+use alloy_core::primitives::U256;
+use world_id_core::primitives::merkle::AccountInclusionProof;
+use world_id_core::primitives::TREE_DEPTH;
+
+use super::error::{StorageError, StorageResult};
+use super::types::{CredentialStatus, RegistryKind};
+use super::CredentialStore;
+use crate::error::WalletKitError;
+use crate::rpc::is_valid_root;
+use crate::Environment;
+
+/// Result of [`CredentialStore::verify_all_credentials`], listing credential
+/// IDs by health.
+///
+/// Invalidated credentials (see [`CredentialStatus::Invalidated`]) cannot be
+/// used in proofs regardless of their Merkle proof and are not listed.
+#[derive(Debug, Default)]
+pub struct CredentialAuditReport {
+    /// Active credentials whose Merkle proof is against a root the registry
+    /// still accepts.
+    pub valid: Vec<u64>,
+    /// Active credentials with no cached Merkle proof, or one against a root
+    /// the registry no longer accepts. The next proof generation fetches a
+    /// fresh one.
+    pub stale_proof: Vec<u64>,
+    /// Credentials past their `expires_at`.
+    pub expired: Vec<u64>,
+    /// Active credentials whose Merkle proof could not be checked.
+    pub errors: Vec<(u64, WalletKitError)>,
+}
+
+impl CredentialStore {
+    /// Checks every stored credential for expiry and for the freshness of the
+    /// Merkle proof it would be proven with at `now`.
+    ///
+    /// The vault is read on a blocking thread, then the cached account
+    /// inclusion proof's root is checked with the `environment`'s World ID
+    /// registry through the JSON-RPC endpoint at `rpc_url`. A failed RPC call
+    /// is reported per credential in [`CredentialAuditReport::errors`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or a vault or cache
+    /// query fails.
+    pub async fn verify_all_credentials(
+        self: Arc<Self>,
+        rpc_url: &str,
+        environment: &Environment,
+        now: u64,
+    ) -> StorageResult<CredentialAuditReport> {
+        let (records, cached_proof) = crate::runtime::spawn_blocking(move || {
+            self.with_databases(|vault, cache| {
+                let records = vault.list_credentials(None, now)?;
+                let cached_proof = cache
+                    .merkle_cache_get_with_inserted_at(RegistryKind::AccountRegistry)?
+                    .and_then(|(bytes, _)| {
+                        serde_json::from_slice::<AccountInclusionProof<TREE_DEPTH>>(
+                            &bytes,
+                        )
+                        .ok()
+                    });
+                Ok((records, cached_proof))
+            })
+        })
+        .await
+        .map_err(|err| {
+            StorageError::VaultDb(format!("blocking audit task failed: {err}"))
+        })??;
+
+        let mut report = CredentialAuditReport::default();
+        let mut active = Vec::new();
+        for record in records {
+            match record.status {
+                CredentialStatus::Active => active.push(record.credential_id),
+                CredentialStatus::Expired => report.expired.push(record.credential_id),
+                CredentialStatus::Invalidated => {}
+            }
+        }
+        if active.is_empty() {
+            return Ok(report);
+        }
+
+        let Some(cached_proof) = cached_proof else {
+            report.stale_proof = active;
+            return Ok(report);
+        };
+        let root: U256 = cached_proof.inclusion_proof.root.into();
+        match is_valid_root(rpc_url, environment, root).await {
+            Ok(true) => report.valid = active,
+            Ok(false) => report.stale_proof = active,
+            Err(err) => {
+                let error = err.to_string();
+                report.errors = active
+                    .into_iter()
+                    .map(|credential_id| {
+                        let err = WalletKitError::NetworkError {
+                            url: rpc_url.to_string(),
+                            error: error.clone(),
+                            status: None,
+                        };
+                        (credential_id, err)
+                    })
+                    .collect();
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use world_id_core::primitives::merkle::MerkleInclusionProof;
+    use world_id_core::primitives::AuthenticatorPublicKeySet;
+    use world_id_core::{
+        Credential as CoreCredential, FieldElement as CoreFieldElement,
+    };
+
+    use alloy_core::sol_types::SolCall;
+
+    use super::*;
+    use crate::rpc::isValidRootCall;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+    use crate::{Credential, FieldElement};
+
+    const VALID: &str =
+        "0x0000000000000000000000000000000000000000000000000000000000000001";
+    const INVALID: &str =
+        "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+    fn store_with_credentials(
+        provider: &InMemoryStorageProvider,
+    ) -> Arc<CredentialStore> {
+        let store = Arc::new(CredentialStore::from_provider(provider).expect("store"));
+        store.init(42, 1000).expect("init storage");
+        for (issuer_schema_id, expires_at) in [(1, 9999), (2, 9999), (3, 1500)] {
+            let credential: Credential = CoreCredential::new()
+                .issuer_schema_id(issuer_schema_id)
+                .genesis_issued_at(1000)
+                .into();
+            store
+                .store_credential(
+                    &credential,
+                    &FieldElement::from(7u64),
+                    expires_at,
+                    None,
+                    1000,
+                )
+                .expect("store credential");
+        }
+        store
+    }
+
+    fn cache_proof(store: &CredentialStore) {
+        let account_inclusion_proof = AccountInclusionProof {
+            inclusion_proof: MerkleInclusionProof::new(
+                CoreFieldElement::from(123u64),
+                42,
+                [CoreFieldElement::from(0u64); TREE_DEPTH],
+            ),
+            authenticator_pubkeys: AuthenticatorPublicKeySet::new(vec![])
+                .expect("key set"),
+        };
+        store
+            .merkle_cache_put(
+                RegistryKind::AccountRegistry,
+                &account_inclusion_proof,
+                1000,
+                600,
+            )
+            .expect("cache put");
+    }
+
+    async fn mock_rpc(
+        server: &mut mockito::ServerGuard,
+        result: &str,
+    ) -> mockito::Mock {
+        let calldata = isValidRootCall {
+            root: U256::from(123u64),
+        }
+        .abi_encode();
+        server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "method": "eth_call",
+                "params": [{ "data": format!("0x{}", hex::encode(calldata)) }],
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result })
+                    .to_string(),
+            )
+            .create_async()
+            .await
+    }
+
+    fn sorted(mut ids: Vec<u64>) -> Vec<u64> {
+        ids.sort_unstable();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_verify_all_credentials_against_registry() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = store_with_credentials(&provider);
+        cache_proof(&store);
+        let records = store.list_credentials(None, 2000).unwrap();
+        let id_of = |issuer_schema_id: u64| {
+            records
+                .iter()
+                .find(|record| record.issuer_schema_id == issuer_schema_id)
+                .unwrap()
+                .credential_id
+        };
+
+        let mut server = mockito::Server::new_async().await;
+        let mock = mock_rpc(&mut server, VALID).await;
+        let report = Arc::clone(&store)
+            .verify_all_credentials(&server.url(), &Environment::Staging, 2000)
+            .await
+            .expect("audit");
+        mock.assert_async().await;
+        assert_eq!(sorted(report.valid), sorted(vec![id_of(1), id_of(2)]));
+        assert!(report.stale_proof.is_empty());
+        assert_eq!(report.expired, vec![id_of(3)]);
+        assert!(report.errors.is_empty());
+
+        mock.remove_async().await;
+        let mock = mock_rpc(&mut server, INVALID).await;
+        let report = Arc::clone(&store)
+            .verify_all_credentials(&server.url(), &Environment::Staging, 2000)
+            .await
+            .expect("audit");
+        mock.assert_async().await;
+        assert!(report.valid.is_empty());
+        assert_eq!(sorted(report.stale_proof), sorted(vec![id_of(1), id_of(2)]));
+
+        cleanup_test_storage(&root);
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_verify_all_credentials_reports_rpc_errors() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = store_with_credentials(&provider);
+        cache_proof(&store);
+
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "error": { "code": -32000, "message": "execution reverted" },
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let report = Arc::clone(&store)
+            .verify_all_credentials(&server.url(), &Environment::Staging, 2000)
+            .await
+            .expect("audit");
+        mock.assert_async().await;
+        assert!(report.valid.is_empty());
+        assert_eq!(report.expired.len(), 1);
+        assert_eq!(report.errors.len(), 2);
+        assert!(report.errors.iter().all(|(_, err)| matches!(
+            err,
+            WalletKitError::NetworkError { error, .. } if error.contains("execution reverted")
+        )));
+
+        cleanup_test_storage(&root);
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_verify_all_credentials_without_cached_proof() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = store_with_credentials(&provider);
+
+        // no RPC call is needed without a cached proof
+        let report = Arc::clone(&store)
+            .verify_all_credentials("http://127.0.0.1:1", &Environment::Staging, 2000)
+            .await
+            .expect("audit");
+        assert!(report.valid.is_empty());
+        assert_eq!(report.stale_proof.len(), 2);
+        assert_eq!(report.expired.len(), 1);
+
+        cleanup_test_storage(&root);
+    }
+}
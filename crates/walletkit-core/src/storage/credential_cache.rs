@@ -0,0 +1,227 @@
+//! Session-scoped in-memory cache of decrypted credentials.
+//!
+//! Proof bursts (one proof per action in a session) read the same credential
+//! repeatedly. This small LRU, keyed by issuer schema ID, avoids re-reading and
+//! re-decrypting the vault row each time. Entries are zeroized when evicted,
+//! invalidated, or cleared.
+//!
+//! Other processes holding the same vault can write it at any time, so the
+//! cache is tied to the vault connection's `PRAGMA data_version` (see
+//! [`CredentialCache::sync_vault_version`]) and dropped when it changes.
+
+use zeroize::{Zeroize, Zeroizing};
+
+/// Default number of credentials kept in memory.
+pub const DEFAULT_CREDENTIAL_CACHE_CAPACITY: usize = 4;
+
+/// Raw credential and blinding factor bytes as read from the vault.
+#[derive(Zeroize)]
+pub struct CachedCredential {
+    /// Serialized credential.
+    pub credential: Vec<u8>,
+    /// Serialized subject blinding factor.
+    pub blinding_factor: Vec<u8>,
+}
+
+struct Entry<V: Zeroize> {
+    issuer_schema_id: u64,
+    /// Time of the vault read that produced the value. The cached result is
+    /// only valid for queries at or after this time.
+    fetched_at: u64,
+    /// Expiry of the cached credential; hits at or after it are misses.
+    expires_at: u64,
+    value: Zeroizing<V>,
+}
+
+/// Least-recently-used cache; the most recently used entry is last.
+pub struct CredentialCache<V: Zeroize = CachedCredential> {
+    capacity: usize,
+    entries: Vec<Entry<V>>,
+    /// Vault `data_version` the entries were read at.
+    vault_version: Option<i64>,
+}
+
+impl<V: Zeroize> CredentialCache<V> {
+    /// Creates a cache holding at most `capacity` entries. A capacity of `0`
+    /// disables caching.
+    #[must_use]
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Vec::new(),
+            vault_version: None,
+        }
+    }
+
+    /// Drops every entry if the vault changed since they were read.
+    ///
+    /// `version` is the vault connection's `PRAGMA data_version`, which
+    /// changes whenever another connection, including one in another process,
+    /// commits to the vault. Writes through this store clear the cache
+    /// themselves.
+    pub fn sync_vault_version(&mut self, version: i64) {
+        if self.vault_version != Some(version) {
+            self.entries.clear();
+            self.vault_version = Some(version);
+        }
+    }
+
+    /// Returns the cached value for `issuer_schema_id` if it is still valid at
+    /// `now`, marking it as most recently used.
+    pub fn get(&mut self, issuer_schema_id: u64, now: u64) -> Option<&V> {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.issuer_schema_id == issuer_schema_id)?;
+        let entry = &self.entries[index];
+        if now < entry.fetched_at || now >= entry.expires_at {
+            // dropping the entry zeroizes it
+            self.entries.remove(index);
+            return None;
+        }
+        let entry = self.entries.remove(index);
+        self.entries.push(entry);
+        self.entries.last().map(|entry| &*entry.value)
+    }
+
+    /// Caches `value`, evicting the least recently used entry when full.
+    pub fn insert(
+        &mut self,
+        issuer_schema_id: u64,
+        fetched_at: u64,
+        expires_at: u64,
+        value: V,
+    ) {
+        let value = Zeroizing::new(value);
+        if self.capacity == 0 {
+            return;
+        }
+        self.entries
+            .retain(|entry| entry.issuer_schema_id != issuer_schema_id);
+        while self.entries.len() >= self.capacity {
+            self.entries.remove(0);
+        }
+        self.entries.push(Entry {
+            issuer_schema_id,
+            fetched_at,
+            expires_at,
+            value,
+        });
+    }
+
+    /// Changes the capacity, evicting the least recently used entries that no
+    /// longer fit.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        let excess = self.entries.len().saturating_sub(capacity);
+        self.entries.drain(..excess);
+    }
+
+    /// Drops and zeroizes every entry.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.vault_version = None;
+    }
+
+    /// Returns the number of cached entries.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if nothing is cached.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<V: Zeroize> Default for CredentialCache<V> {
+    fn default() -> Self {
+        Self::new(DEFAULT_CREDENTIAL_CACHE_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Tracked {
+        bytes: Vec<u8>,
+        zeroized: Arc<AtomicUsize>,
+    }
+
+    impl Zeroize for Tracked {
+        fn zeroize(&mut self) {
+            self.bytes.zeroize();
+            self.zeroized.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn tracked(byte: u8, zeroized: &Arc<AtomicUsize>) -> Tracked {
+        Tracked {
+            bytes: vec![byte; 8],
+            zeroized: Arc::clone(zeroized),
+        }
+    }
+
+    #[test]
+    fn test_lru_eviction_zeroizes() {
+        let zeroized = Arc::new(AtomicUsize::new(0));
+        let mut cache = CredentialCache::new(2);
+        cache.insert(1, 100, 1000, tracked(1, &zeroized));
+        cache.insert(2, 100, 1000, tracked(2, &zeroized));
+
+        // touch 1 so that 2 becomes the least recently used entry
+        assert_eq!(cache.get(1, 100).expect("hit").bytes, vec![1; 8]);
+        cache.insert(3, 100, 1000, tracked(3, &zeroized));
+
+        assert_eq!(zeroized.load(Ordering::SeqCst), 1);
+        assert!(cache.get(2, 100).is_none());
+        assert!(cache.get(1, 100).is_some());
+        assert!(cache.get(3, 100).is_some());
+
+        cache.clear();
+        assert_eq!(zeroized.load(Ordering::SeqCst), 3);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_expired_or_earlier_reads_miss() {
+        let zeroized = Arc::new(AtomicUsize::new(0));
+        let mut cache = CredentialCache::new(4);
+        cache.insert(1, 100, 200, tracked(1, &zeroized));
+        assert!(cache.get(1, 99).is_none(), "read before the vault fetch");
+
+        cache.insert(1, 100, 200, tracked(1, &zeroized));
+        assert!(cache.get(1, 150).is_some());
+        assert!(cache.get(1, 200).is_none(), "expired");
+        assert_eq!(zeroized.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_vault_version_change_drops_entries() {
+        let zeroized = Arc::new(AtomicUsize::new(0));
+        let mut cache = CredentialCache::new(4);
+        cache.sync_vault_version(1);
+        cache.insert(1, 100, 200, tracked(1, &zeroized));
+        cache.sync_vault_version(1);
+        assert!(cache.get(1, 100).is_some());
+
+        cache.sync_vault_version(2);
+        assert!(cache.is_empty());
+        assert_eq!(zeroized.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_cache() {
+        let zeroized = Arc::new(AtomicUsize::new(0));
+        let mut cache = CredentialCache::new(0);
+        cache.insert(1, 100, 200, tracked(1, &zeroized));
+        assert!(cache.get(1, 100).is_none());
+        assert_eq!(zeroized.load(Ordering::SeqCst), 1);
+    }
+}
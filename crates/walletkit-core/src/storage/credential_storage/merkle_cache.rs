@@ -0,0 +1,169 @@
+//! Merkle inclusion proof cache of the credential store.
+//!
+//! Proofs are cached as JSON per registry. Every lookup is counted, both in
+//! the store's in-memory metrics and in the vault's persisted usage counters.
+
+use world_id_core::primitives::merkle::AccountInclusionProof;
+use world_id_core::primitives::TREE_DEPTH;
+
+use super::{CredentialStore, CredentialStoreInner};
+use crate::metrics_observer::{record_cache_lookup, CacheKind};
+use crate::storage::credential_vault::UsageCounter;
+use crate::storage::error::{StorageError, StorageResult};
+use crate::storage::metrics::CredentialStoreMetrics;
+use crate::storage::types::RegistryKind;
+
+impl CredentialStore {
+    /// Fetches the cached Merkle proof for `kind` if it remains valid beyond
+    /// `valid_until`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache lookup fails.
+    pub fn merkle_cache_get(
+        &self,
+        kind: RegistryKind,
+        valid_until: u64,
+    ) -> StorageResult<Option<AccountInclusionProof<TREE_DEPTH>>> {
+        let proof = self.lock_inner().and_then(|inner| {
+            let proof = inner.merkle_cache_get(kind, valid_until)?;
+            inner.persist_cache_lookup(proof.is_some());
+            Ok(proof)
+        })?;
+        self.count_cache_lookup(proof.is_some());
+        Ok(proof)
+    }
+
+    /// Retrieves the cached Merkle proof for `kind` and its age in seconds at
+    /// `now`, even if its TTL has passed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache lookup fails.
+    pub fn merkle_cache_get_with_age(
+        &self,
+        kind: RegistryKind,
+        now: u64,
+    ) -> StorageResult<Option<(AccountInclusionProof<TREE_DEPTH>, u64)>> {
+        let proof = self.lock_inner().and_then(|inner| {
+            let proof = inner.merkle_cache_get_with_age(kind, now)?;
+            inner.persist_cache_lookup(proof.is_some());
+            Ok(proof)
+        })?;
+        self.count_cache_lookup(proof.is_some());
+        Ok(proof)
+    }
+
+    fn count_cache_lookup(&self, hit: bool) {
+        CredentialStoreMetrics::increment(if hit {
+            &self.metrics.cache_hits
+        } else {
+            &self.metrics.cache_misses
+        });
+        record_cache_lookup(CacheKind::MerkleProof, hit);
+    }
+
+    /// Inserts a cached Merkle proof for `kind` with a TTL.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache insert fails.
+    pub fn merkle_cache_put(
+        &self,
+        kind: RegistryKind,
+        account_inclusion_proof: &AccountInclusionProof<TREE_DEPTH>,
+        now: u64,
+        ttl_seconds: u64,
+    ) -> StorageResult<()> {
+        self.lock_inner()?.merkle_cache_put(
+            kind,
+            account_inclusion_proof,
+            now,
+            ttl_seconds,
+        )
+    }
+}
+
+impl CredentialStoreInner {
+    pub(super) fn merkle_cache_get(
+        &self,
+        kind: RegistryKind,
+        valid_until: u64,
+    ) -> StorageResult<Option<AccountInclusionProof<TREE_DEPTH>>> {
+        let state = self.state()?;
+        let bytes = state.cache.merkle_cache_get(kind, valid_until)?;
+
+        if let Some(bytes) = bytes {
+            let result =
+                serde_json::from_slice::<AccountInclusionProof<TREE_DEPTH>>(&bytes)
+                    .ok();
+            return Ok(result);
+        }
+        Ok(None)
+    }
+
+    fn merkle_cache_get_with_age(
+        &self,
+        kind: RegistryKind,
+        now: u64,
+    ) -> StorageResult<Option<(AccountInclusionProof<TREE_DEPTH>, u64)>> {
+        let state = self.state()?;
+        let Some((bytes, inserted_at)) =
+            state.cache.merkle_cache_get_with_inserted_at(kind)?
+        else {
+            return Ok(None);
+        };
+        Ok(
+            serde_json::from_slice::<AccountInclusionProof<TREE_DEPTH>>(&bytes)
+                .ok()
+                .map(|proof| (proof, now.saturating_sub(inserted_at))),
+        )
+    }
+
+    /// Counts a Merkle cache lookup in the vault's usage counters. Failing to
+    /// persist the count is only logged.
+    fn persist_cache_lookup(&self, hit: bool) {
+        let counter = if hit {
+            UsageCounter::MerkleCacheHit
+        } else {
+            UsageCounter::MerkleCacheMiss
+        };
+        if let Err(e) = self
+            .state()
+            .and_then(|state| state.vault.increment_usage_counter(counter))
+        {
+            tracing::warn!("failed to persist Merkle cache usage counter: {e}");
+        }
+    }
+
+    pub(super) fn next_merkle_refresh_at(
+        &self,
+        kind: RegistryKind,
+        now: u64,
+    ) -> StorageResult<Option<u64>> {
+        let state = self.state()?;
+        // a vault not opened for writing since the seed was introduced has
+        // none yet; refreshes are then only spread per proof until `init`
+        let seed = state.vault.merkle_refresh_seed()?.unwrap_or_default();
+        state.cache.merkle_refresh_at(kind, now, &seed)
+    }
+
+    pub(super) fn merkle_cache_put(
+        &mut self,
+        kind: RegistryKind,
+        account_inclusion_proof: &AccountInclusionProof<TREE_DEPTH>,
+        now: u64,
+        ttl_seconds: u64,
+    ) -> StorageResult<()> {
+        let state = self.state_mut()?;
+        // Use JSON for storage instead of CBOR because the `#[serde(flatten)]` property
+        // causes the deserialization of `FieldElement`s to appear as human-readable
+        let bytes = serde_json::to_vec(account_inclusion_proof).map_err(|_| {
+            StorageError::Serialization(
+                "unexpected. unable to serialize `account_inclusion_proof`".to_string(),
+            )
+        })?;
+
+        state.cache.merkle_cache_put(kind, &bytes, now, ttl_seconds)
+    }
+}
@@ -0,0 +1,3822 @@
+//! Storage facade implementing the credential storage API.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::AtomicBool;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+
+#[cfg(not(target_arch = "wasm32"))]
+use walletkit_db::inspect::FileFormat;
+use world_id_core::FieldElement as CoreFieldElement;
+use zeroize::Zeroizing;
+
+use super::credential_cache::{CachedCredential, CredentialCache};
+use super::error::{StorageError, StorageResult};
+#[cfg(not(target_arch = "wasm32"))]
+use super::inventory::{InventoryEntry, RetentionPolicy};
+use super::keys::StorageKeys;
+use super::metrics::CredentialStoreMetrics;
+use super::paths::StoragePaths;
+use super::quota::QuotaGuard;
+#[cfg(not(target_arch = "wasm32"))]
+use super::traits::VaultChangedListener;
+use super::traits::{AtomicBlobStore, DeviceKeystore};
+use super::traits::{StorageProvider, StorageQuotaProvider};
+use super::types::{
+    BloomStats, BulkDeleteReport, CacheConfig, ConsentRecord, CredentialRecord,
+    CredentialStatus, DisclosureRecord, EnvelopeHealth, RegistryKind,
+    StorageQuotaPolicy,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use super::VAULT_APPLICATION_ID;
+use super::{CacheDb, CredentialVault, NewConsent};
+use super::{StorageLock, StorageLockGuard};
+use super::{ACCOUNT_KEYS_FILENAME, ACCOUNT_PENDING_KEYS_FILENAME};
+use crate::metrics_observer::{record_cache_lookup, CacheKind};
+use crate::{Credential, FieldElement};
+use world_id_core::primitives::merkle::AccountInclusionProof;
+use world_id_core::primitives::TREE_DEPTH;
+
+mod merkle_cache;
+mod replay_guard;
+
+/// Session seed TTL: ~6 months (182 days).
+pub(super) const SESSION_SEED_TTL_SECONDS: u64 = 182 * 86_400;
+
+/// RP signing key (JWKS) TTL: 1 hour, so rotated keys are picked up quickly.
+pub(super) const RP_KEYS_TTL_SECONDS: u64 = 3_600;
+
+/// Filename prefix for temporary plaintext vault exports used during
+/// backup export and import. A UUID is appended to avoid collisions.
+#[cfg(not(target_arch = "wasm32"))]
+const VAULT_BACKUP_TEMP_PREFIX: &str = "vault_backup_plaintext_";
+
+/// Files the store writes outside the databases, see
+/// [`data_inventory`](super::inventory::data_inventory).
+#[cfg(not(target_arch = "wasm32"))]
+pub(super) const INVENTORY: &[InventoryEntry] = &[InventoryEntry {
+    name: "vault_backup_export",
+    description: "An unencrypted copy of the vault's backed-up tables, and \
+        of disclosure memos if the app opted in, written while a backup is \
+        exported or imported.",
+    locations: &["worldid/vault_backup_plaintext_<uuid>.sqlite"],
+    contains_personal_data: true,
+    retention: RetentionPolicy::Transient,
+}];
+
+/// Checks that `bytes` look like a plaintext vault backup: a plaintext
+/// `SQLite` database with the vault's application ID, or none for backups
+/// taken before it was set.
+#[cfg(not(target_arch = "wasm32"))]
+fn check_vault_backup(bytes: &[u8]) -> StorageResult<()> {
+    match walletkit_db::inspect::inspect_bytes(bytes) {
+        FileFormat::PlaintextDatabase {
+            application_id: VAULT_APPLICATION_ID | 0,
+            ..
+        } => Ok(()),
+        _ => Err(StorageError::VaultDb(
+            "backup is not a plaintext vault database".to_string(),
+        )),
+    }
+}
+
+/// Adds the memos of `disclosures` to the plaintext vault backup at `dest`, in
+/// a `disclosure_memos` table that imports ignore.
+#[cfg(not(target_arch = "wasm32"))]
+fn export_disclosure_memos(
+    dest: &std::path::Path,
+    disclosures: &[DisclosureRecord],
+) -> StorageResult<()> {
+    let map_err = |e: walletkit_db::DbError| {
+        StorageError::VaultDb(format!("failed to export disclosure memos: {e}"))
+    };
+    let conn = walletkit_db::Connection::open(dest, false).map_err(map_err)?;
+    let tx = conn.transaction().map_err(map_err)?;
+    tx.execute_batch(
+        "CREATE TABLE disclosure_memos (
+            disclosed_at INTEGER NOT NULL,
+            memo BLOB NOT NULL
+        );",
+    )
+    .map_err(map_err)?;
+    for disclosure in disclosures {
+        let Some(memo) = &disclosure.memo else {
+            continue;
+        };
+        let disclosed_at = i64::try_from(disclosure.disclosed_at).map_err(|_| {
+            StorageError::VaultDb(format!(
+                "invalid disclosed_at {}",
+                disclosure.disclosed_at
+            ))
+        })?;
+        tx.execute(
+            "INSERT INTO disclosure_memos (disclosed_at, memo) VALUES (?1, ?2)",
+            walletkit_db::params![disclosed_at, memo.as_slice()],
+        )
+        .map_err(map_err)?;
+    }
+    tx.commit().map_err(map_err)
+}
+
+/// RAII guard that deletes a sensitive plaintext file on drop — regardless
+/// of whether we exit normally, return early, or panic.
+#[cfg(not(target_arch = "wasm32"))]
+struct CleanupFile(String);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for CleanupFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            tracing::error!(
+                "Failed to delete plaintext vault temp file {}: {e}",
+                self.0
+            );
+        }
+    }
+}
+
+/// Removes a `SQLite` database file and its WAL/SHM sidecar files.
+/// Best-effort: missing files are silently ignored, other errors are logged.
+#[cfg(not(target_arch = "wasm32"))]
+fn remove_db_files(db_path: &std::path::Path) {
+    for ext in &["sqlite", "sqlite-wal", "sqlite-shm"] {
+        let path = db_path.with_extension(ext);
+        if let Err(e) = std::fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::error!("Failed to delete {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+/// Storage state gathered by [`CredentialStore::cold_start`].
+#[derive(Debug)]
+pub(crate) struct ColdStartSnapshot {
+    /// All stored credentials.
+    pub credentials: Vec<CredentialRecord>,
+    /// Credentials invalidated because they predate the latest recovery.
+    pub invalidated_credential_ids: Vec<u64>,
+    /// Whether a valid Merkle inclusion proof for the account is cached.
+    pub merkle_proof_cached: bool,
+}
+
+/// Concrete storage implementation backed by `SQLCipher` databases.
+#[derive(uniffi::Object)]
+pub struct CredentialStore {
+    /// Shared with [`LIVE_STORES`] so memory pressure can reach it.
+    inner: Arc<Mutex<CredentialStoreInner>>,
+    /// Channel sender for the vault-changed notification thread.
+    /// Kept outside `inner` so we can notify after releasing the storage mutex.
+    #[cfg(not(target_arch = "wasm32"))]
+    vault_changed_tx: Mutex<Option<mpsc::SyncSender<()>>>,
+    /// Read-only vault connection for snapshot reads, opened by `init`.
+    /// Kept outside `inner` so metadata reads don't wait behind a writer
+    /// holding the storage mutex.
+    #[cfg(not(target_arch = "wasm32"))]
+    vault_reader: Mutex<Option<CredentialVault>>,
+    /// Stop flag of the active credential expiry polling thread, if any.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(super) expiry_listener_stop: Mutex<Option<Arc<AtomicBool>>>,
+    /// Stop flag of the thread committing batched writes whose window has
+    /// elapsed, if batching is enabled.
+    #[cfg(not(target_arch = "wasm32"))]
+    batch_flusher_stop: Mutex<Option<Arc<AtomicBool>>>,
+    /// Operational counters, see [`CredentialStore::metrics`].
+    pub(super) metrics: Arc<CredentialStoreMetrics>,
+    /// Number of times the storage mutex was acquired.
+    #[cfg(test)]
+    lock_acquisitions: std::sync::atomic::AtomicUsize,
+}
+
+type LiveStores = Vec<Weak<Mutex<CredentialStoreInner>>>;
+
+/// The storage state of every live [`CredentialStore`], see
+/// [`release_memory_of_live_stores`].
+#[cfg(not(test))]
+static LIVE_STORES: Mutex<LiveStores> = Mutex::new(Vec::new());
+
+// per thread in tests, so releasing memory in one test cannot empty the
+// caches another test is asserting on
+#[cfg(test)]
+thread_local! {
+    static LIVE_STORES: Mutex<LiveStores> = const { Mutex::new(Vec::new()) };
+}
+
+/// Runs `f` on the live stores, after pruning the dropped ones.
+#[expect(
+    clippy::significant_drop_tightening,
+    reason = "the list stays locked while `f` runs on it"
+)]
+fn with_live_stores<T>(f: impl FnOnce(&mut LiveStores) -> T) -> T {
+    let prune_and_run = |live: &Mutex<LiveStores>| {
+        // the list is only ever pushed to and pruned, a panic cannot corrupt it
+        let mut live = live.lock().unwrap_or_else(PoisonError::into_inner);
+        live.retain(|store| store.strong_count() > 0);
+        f(&mut live)
+    };
+    #[cfg(not(test))]
+    {
+        prune_and_run(&LIVE_STORES)
+    }
+    #[cfg(test)]
+    {
+        LIVE_STORES.with(prune_and_run)
+    }
+}
+
+/// Drops the decrypted credential caches and releases the database memory
+/// of every live [`CredentialStore`], see [`crate::on_memory_pressure`].
+///
+/// Stores busy with another operation are skipped rather than waited for,
+/// so this never blocks the calling thread behind a proof or a write.
+pub(crate) fn release_memory_of_live_stores() {
+    let stores = with_live_stores(|live| {
+        live.iter().filter_map(Weak::upgrade).collect::<Vec<_>>()
+    });
+    for store in stores {
+        let Ok(mut inner) = store.try_lock() else {
+            continue;
+        };
+        if let Err(err) = inner.release_memory() {
+            tracing::warn!(error = %err, "failed to release storage memory");
+        }
+    }
+}
+
+impl std::fmt::Debug for CredentialStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CredentialStore").finish()
+    }
+}
+
+impl Drop for CredentialStore {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            tracing::warn!("failed to close credential store on drop: {e}");
+        }
+    }
+}
+
+struct CredentialStoreInner {
+    /// Cross-process storage lock, `None` once the store is closed.
+    lock: Option<StorageLock>,
+    keystore: Arc<dyn DeviceKeystore>,
+    blob_store: Arc<dyn AtomicBlobStore>,
+    paths: StoragePaths,
+    state: Option<StorageState>,
+    /// Decrypted credentials read during the current session.
+    credential_cache: CredentialCache,
+    /// Quota checks, if the host registered a quota provider.
+    quota_guard: Option<QuotaGuard>,
+    /// Whether proof generation records consent ledger entries.
+    consent_ledger_enabled: bool,
+    /// Whether vault backups carry disclosure memos.
+    disclosure_memos_in_backups: bool,
+    /// Size bounds applied to the cache database when it is opened.
+    cache_config: CacheConfig,
+    /// Write batch window applied to the vault when it is opened.
+    write_batch_window: std::time::Duration,
+    /// Number of credential reads that went to the vault.
+    #[cfg(test)]
+    vault_credential_reads: usize,
+}
+
+struct StorageState {
+    keys: StorageKeys,
+    vault: CredentialVault,
+    cache: CacheDb,
+    leaf_index: u64,
+}
+
+impl CredentialStoreInner {
+    /// Creates a new storage handle from a platform provider.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage lock cannot be opened.
+    pub fn from_provider(provider: &dyn StorageProvider) -> StorageResult<Self> {
+        let paths = provider.paths();
+        Self::new(
+            paths.as_ref().clone(),
+            provider.keystore(),
+            provider.blob_store(),
+        )
+    }
+
+    /// Creates a new storage handle from explicit components.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage lock cannot be opened.
+    pub fn new(
+        paths: StoragePaths,
+        keystore: Arc<dyn DeviceKeystore>,
+        blob_store: Arc<dyn AtomicBlobStore>,
+    ) -> StorageResult<Self> {
+        let lock = StorageLock::open(&paths.lock_path())?;
+        Ok(Self {
+            lock: Some(lock),
+            keystore,
+            blob_store,
+            paths,
+            state: None,
+            credential_cache: CredentialCache::default(),
+            quota_guard: None,
+            consent_ledger_enabled: false,
+            disclosure_memos_in_backups: false,
+            cache_config: CacheConfig::default(),
+            write_batch_window: std::time::Duration::ZERO,
+            #[cfg(test)]
+            vault_credential_reads: 0,
+        })
+    }
+
+    fn lock(&self) -> StorageResult<&StorageLock> {
+        self.lock.as_ref().ok_or(StorageError::Closed)
+    }
+
+    fn guard(&self) -> StorageResult<StorageLockGuard> {
+        self.lock()?.lock().map_err(Into::into)
+    }
+
+    fn state(&self) -> StorageResult<&StorageState> {
+        self.state.as_ref().ok_or(StorageError::NotInitialized)
+    }
+
+    fn state_mut(&mut self) -> StorageResult<&mut StorageState> {
+        self.state.as_mut().ok_or(StorageError::NotInitialized)
+    }
+}
+
+#[uniffi::export]
+impl CredentialStore {
+    /// Creates a new storage handle from explicit components.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage lock cannot be opened.
+    #[uniffi::constructor]
+    pub fn new_with_components(
+        paths: Arc<StoragePaths>,
+        keystore: Arc<dyn DeviceKeystore>,
+        blob_store: Arc<dyn AtomicBlobStore>,
+    ) -> StorageResult<Self> {
+        let paths = Arc::try_unwrap(paths).unwrap_or_else(|arc| (*arc).clone());
+        let inner = CredentialStoreInner::new(paths, keystore, blob_store)?;
+        Ok(Self::from_inner(inner))
+    }
+
+    /// Creates a new storage handle from a platform provider.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage lock cannot be opened.
+    #[uniffi::constructor]
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn from_provider_arc(
+        provider: Arc<dyn StorageProvider>,
+    ) -> StorageResult<Self> {
+        let inner = CredentialStoreInner::from_provider(provider.as_ref())?;
+        Ok(Self::from_inner(inner))
+    }
+
+    /// Creates a new storage handle from a platform provider, bounding the
+    /// cache database according to `cache_config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage lock cannot be opened.
+    #[uniffi::constructor]
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn from_provider_with_cache_config(
+        provider: Arc<dyn StorageProvider>,
+        cache_config: CacheConfig,
+    ) -> StorageResult<Self> {
+        let mut inner = CredentialStoreInner::from_provider(provider.as_ref())?;
+        inner.cache_config = cache_config;
+        Ok(Self::from_inner(inner))
+    }
+
+    /// Returns the storage paths used by this handle.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage mutex is poisoned.
+    pub fn storage_paths(&self) -> StorageResult<StoragePaths> {
+        self.lock_inner().map(|inner| inner.paths.clone())
+    }
+
+    /// Initializes storage and validates the account leaf index.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if initialization fails or the leaf index mismatches.
+    #[expect(
+        clippy::significant_drop_tightening,
+        reason = "the vault reader is opened under the storage lock"
+    )]
+    pub fn init(&self, leaf_index: u64, now: u64) -> StorageResult<()> {
+        let mut inner = self.lock_inner()?;
+        inner.init(leaf_index, now)?;
+        #[cfg(not(target_arch = "wasm32"))]
+        self.ensure_vault_reader(&inner)?;
+        Ok(())
+    }
+
+    /// Registers a quota provider and the policy enforced against it.
+    ///
+    /// Once registered, [`init`](Self::init) refuses to open the store with
+    /// [`StorageError::InsufficientStorageQuota`] when less than
+    /// [`StorageQuotaPolicy::min_available_bytes`] is available (requesting
+    /// persistent storage first if the policy asks for it), and large
+    /// credential writes check the quota before starting the vault
+    /// transaction. Intended for browsers; replaces any previous provider.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage mutex is poisoned.
+    pub fn set_storage_quota_provider(
+        &self,
+        provider: Arc<dyn StorageQuotaProvider>,
+        policy: StorageQuotaPolicy,
+    ) -> StorageResult<()> {
+        self.lock_inner()?.quota_guard = Some(QuotaGuard::new(provider, policy));
+        Ok(())
+    }
+
+    /// Lists credential metadata, optionally filtered by issuer schema ID.
+    ///
+    /// Results include both active and expired credentials. Expiry status is
+    /// reported via [`CredentialRecord::is_expired`]. Reads the last committed
+    /// state without waiting for an in-progress write.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the credential query fails.
+    pub fn list_credentials(
+        &self,
+        issuer_schema_id: Option<u64>,
+        now: u64,
+    ) -> StorageResult<Vec<CredentialRecord>> {
+        self.read_snapshot(|vault| vault.list_credentials(issuer_schema_id, now))
+    }
+
+    /// Lists credentials not used in a proof since `since`, as candidates for
+    /// removal, e.g. with `since` twelve months before `now`.
+    ///
+    /// A credential never used counts from when it was stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the credential query fails.
+    pub fn unused_credentials(
+        &self,
+        since: u64,
+        now: u64,
+    ) -> StorageResult<Vec<CredentialRecord>> {
+        self.read_snapshot(|vault| vault.list_unused_credentials(since, now))
+    }
+
+    /// Lists active credentials that expire within `within_seconds` of `now`,
+    /// soonest first, so the host app can schedule renewals before they lapse.
+    ///
+    /// Expired and invalidated credentials are not included; see
+    /// `set_credential_expiry_listener` to be told when a credential expires.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the credential query fails.
+    pub fn credentials_expiring_within(
+        &self,
+        within_seconds: u64,
+        now: u64,
+    ) -> StorageResult<Vec<CredentialRecord>> {
+        let deadline = now.saturating_add(within_seconds);
+        let mut records: Vec<_> = self
+            .list_credentials(None, now)?
+            .into_iter()
+            .filter(|record| {
+                record.status == CredentialStatus::Active
+                    && record.expires_at <= deadline
+            })
+            .collect();
+        records.sort_by_key(|record| record.expires_at);
+        Ok(records)
+    }
+
+    /// Deletes a credential by ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delete operation fails or the credential ID does
+    /// not exist.
+    pub fn delete_credential(&self, credential_id: u64) -> StorageResult<()> {
+        let result = self.lock_inner()?.delete_credential(credential_id);
+        if result.is_ok() {
+            self.notify_vault_changed();
+        }
+        result
+    }
+
+    /// Deletes several credentials by ID in a single vault transaction.
+    ///
+    /// IDs that do not exist are listed in the report instead of failing the
+    /// call, so either all existing credentials are deleted or none are.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the delete fails.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn delete_credentials(
+        &self,
+        credential_ids: Vec<u64>,
+    ) -> StorageResult<BulkDeleteReport> {
+        let report = self.lock_inner()?.delete_credentials(&credential_ids)?;
+        if report.deleted_count > 0 {
+            self.notify_vault_changed();
+        }
+        Ok(report)
+    }
+
+    /// Invalidates every credential issued before the account's latest
+    /// recovery.
+    ///
+    /// Call after an on-chain recovery with the account's new recovery
+    /// counter. Credentials stored under an older counter are no longer
+    /// returned for proving and are listed with
+    /// [`CredentialStatus::Invalidated`](super::CredentialStatus::Invalidated);
+    /// credentials stored afterwards are stamped with `new_counter`.
+    ///
+    /// Returns the IDs of the newly invalidated credentials.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the update fails.
+    pub fn invalidate_for_recovery(
+        &self,
+        new_counter: u64,
+        now: u64,
+    ) -> StorageResult<Vec<u64>> {
+        let invalidated = self
+            .lock_inner()?
+            .invalidate_for_recovery(new_counter, now)?;
+        if !invalidated.is_empty() {
+            self.notify_vault_changed();
+        }
+        Ok(invalidated)
+    }
+
+    /// Enables or disables the consent ledger.
+    ///
+    /// While enabled, every proof generated by an
+    /// [`Authenticator`](crate::Authenticator) using this store appends a
+    /// [`ConsentRecord`] together with the replay-guard update. Off by
+    /// default; the setting is not persisted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage mutex is poisoned.
+    pub fn set_consent_ledger_enabled(&self, enabled: bool) -> StorageResult<()> {
+        self.lock_inner()?.consent_ledger_enabled = enabled;
+        Ok(())
+    }
+
+    /// Appends an entry to the consent ledger, regardless of whether the
+    /// ledger is enabled for proof generation.
+    ///
+    /// Returns the entry's ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the insert fails.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn record_consent(
+        &self,
+        request_id: String,
+        rp_id_hash: Vec<u8>,
+        disclosed_schema_ids: Vec<u64>,
+        consent_text_hash: Option<Vec<u8>>,
+        now: u64,
+    ) -> StorageResult<u64> {
+        let consent = NewConsent {
+            request_id: &request_id,
+            rp_id_hash: &rp_id_hash,
+            disclosed_schema_ids: &disclosed_schema_ids,
+            consent_text_hash: consent_text_hash.as_deref(),
+        };
+        self.lock_inner()?
+            .state()?
+            .vault
+            .record_consent(consent, now)
+    }
+
+    /// Lists consent ledger entries, most recent first.
+    ///
+    /// Returns at most `limit` entries recorded strictly before `before`, if
+    /// set. Entries carry no nullifier material.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the query fails.
+    pub fn list_consents(
+        &self,
+        limit: u32,
+        before: Option<u64>,
+    ) -> StorageResult<Vec<ConsentRecord>> {
+        self.read_snapshot(|vault| vault.list_consents(limit, before))
+    }
+
+    /// Lists the proofs disclosed from this device whose replay guard entry
+    /// has not expired, most recent first, e.g. as evidence when the user
+    /// disputes a verification.
+    ///
+    /// Memos attached with
+    /// [`Authenticator::generate_proof_with_memo`](crate::Authenticator::generate_proof_with_memo)
+    /// are only returned if `include_memos` is set. Memos are kept in the
+    /// cache, so they expire with their entry and are deleted by
+    /// [`destroy_storage`](Self::destroy_storage). They are left out of vault
+    /// backups unless enabled with
+    /// [`set_disclosure_memos_in_backups`](Self::set_disclosure_memos_in_backups).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the query fails.
+    pub fn list_disclosures(
+        &self,
+        include_memos: bool,
+        now: u64,
+    ) -> StorageResult<Vec<DisclosureRecord>> {
+        self.lock_inner()?
+            .state()?
+            .cache
+            .list_disclosures(include_memos, now)
+    }
+
+    /// Sets whether [`export_vault_for_backup`](Self::export_vault_for_backup)
+    /// includes the memos of the unexpired disclosures, see
+    /// [`list_disclosures`](Self::list_disclosures).
+    ///
+    /// Memos are exported in a `disclosure_memos` table with the time of
+    /// their disclosure, and are not restored by an import. Off by default;
+    /// the setting is not persisted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage mutex is poisoned.
+    pub fn set_disclosure_memos_in_backups(&self, enabled: bool) -> StorageResult<()> {
+        self.lock_inner()?.disclosure_memos_in_backups = enabled;
+        Ok(())
+    }
+
+    /// Deletes consent ledger entries recorded before `older_than`, for
+    /// retention policies.
+    ///
+    /// Returns the number of deleted entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the delete fails.
+    pub fn purge_consents(&self, older_than: u64) -> StorageResult<u64> {
+        self.lock_inner()?.state()?.vault.purge_consents(older_than)
+    }
+
+    /// Stores a credential and optional associated data.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the credential cannot be stored.
+    pub fn store_credential(
+        &self,
+        credential: &Credential,
+        blinding_factor: &FieldElement,
+        expires_at: u64,
+        associated_data: Option<Vec<u8>>,
+        now: u64,
+    ) -> StorageResult<u64> {
+        let result = self.lock_inner()?.store_credential(
+            credential,
+            blinding_factor,
+            expires_at,
+            associated_data,
+            now,
+        );
+        if result.is_ok() {
+            self.notify_vault_changed();
+        }
+        result
+    }
+
+    /// **Development only.** Permanently deletes all stored credentials, their
+    /// associated blob data and the [`SecurePrefs`](super::SecurePrefs) from
+    /// the vault.
+    ///
+    /// This is a destructive, unrecoverable operation intended for use in
+    /// development and testing environments only. Do not call this in production.
+    ///
+    /// Preserves storage metadata (leaf index, schema version), so the store
+    /// remains initialized and ready to accept new credentials after the call.
+    ///
+    /// # Returns
+    ///
+    /// The number of credentials deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delete operation fails.
+    pub fn danger_delete_all_credentials(&self) -> StorageResult<u64> {
+        self.lock_inner()?.danger_delete_all_credentials()
+    }
+
+    /// Drops and zeroizes all decrypted credentials held in memory.
+    ///
+    /// Hosts should call this when the app is backgrounded. Subsequent reads
+    /// go back to the vault.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage mutex is poisoned.
+    pub fn clear_sensitive_caches(&self) -> StorageResult<()> {
+        self.lock_inner()?.credential_cache.clear();
+        Ok(())
+    }
+
+    /// Sets how many decrypted credentials are kept in memory between proof
+    /// requests (default 4). `0` disables the cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage mutex is poisoned.
+    pub fn set_credential_cache_capacity(&self, capacity: u32) -> StorageResult<()> {
+        self.lock_inner()?
+            .credential_cache
+            .set_capacity(capacity as usize);
+        Ok(())
+    }
+
+    /// Sets how long small metadata writes (the Merkle cache hit and miss
+    /// counters) may be queued so that they are committed together, saving
+    /// one transaction and fsync each. `0`, the default, disables batching;
+    /// around 50 ms suits devices with slow flash.
+    ///
+    /// Queued writes are visible to reads on this store before they are
+    /// committed. They are committed by a background thread at most a window
+    /// after the window elapsed, or earlier at a later queued write or counter
+    /// read, before every credential or replay guard write, on
+    /// [`flush`](Self::flush), on [`close`](Self::close), and when the store
+    /// is dropped; a crash loses at most the counter increments of the last
+    /// two windows. The setting is not persisted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage mutex is poisoned, or if `window_ms`
+    /// is `0` and committing the queued writes fails.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_write_batch_window_ms(&self, window_ms: u32) -> StorageResult<()> {
+        let window = std::time::Duration::from_millis(u64::from(window_ms));
+        let mut inner = self.lock_inner()?;
+        inner.write_batch_window = window;
+        let result = match inner.state.as_mut() {
+            Some(state) => state.vault.set_write_batch_window(window),
+            None => Ok(()),
+        };
+        drop(inner);
+        #[cfg(not(target_arch = "wasm32"))]
+        self.restart_batch_flusher(window);
+        result
+    }
+
+    /// Stops the batch flushing thread and, for a non-zero `window`, starts
+    /// one committing the writes queued for longer than `window`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn restart_batch_flusher(&self, window: std::time::Duration) {
+        let Ok(mut slot) = self.batch_flusher_stop.lock() else {
+            return;
+        };
+        if let Some(flag) = slot.take() {
+            flag.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+        if window.is_zero() {
+            return;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let inner = Arc::downgrade(&self.inner);
+        let spawned = std::thread::Builder::new()
+            .name("walletkit-batch-flush".into())
+            .spawn(move || loop {
+                std::thread::sleep(window);
+                if thread_stop.load(std::sync::atomic::Ordering::SeqCst) {
+                    return;
+                }
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+                let Ok(inner) = inner.lock() else {
+                    return;
+                };
+                if let Some(state) = inner.state.as_ref() {
+                    if let Err(e) = state.vault.flush_due_writes() {
+                        tracing::warn!("failed to flush queued usage counters: {e}");
+                    }
+                }
+            });
+        match spawned {
+            Ok(_) => *slot = Some(stop),
+            Err(e) => tracing::error!("failed to spawn batch flush thread: {e}"),
+        }
+    }
+
+    /// Commits the metadata writes queued under
+    /// [`set_write_batch_window_ms`](Self::set_write_batch_window_ms).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage mutex is poisoned or the write fails.
+    pub fn flush(&self) -> StorageResult<()> {
+        self.lock_inner()?
+            .state
+            .as_ref()
+            .map_or(Ok(()), |state| state.vault.flush_pending_writes())
+    }
+
+    /// Returns when the cached Merkle inclusion proof for `kind` should be
+    /// refreshed in the background, or `None` if no proof valid at `now` is cached.
+    ///
+    /// The time is spread between 50% and 90% of the proof's TTL using a seed
+    /// that [`init`](Self::init) stores in the vault, so it is stable across
+    /// calls but differs between devices.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the cache lookup
+    /// fails.
+    pub fn next_merkle_refresh_at(
+        &self,
+        kind: RegistryKind,
+        now: u64,
+    ) -> StorageResult<Option<u64>> {
+        self.lock_inner()?.next_merkle_refresh_at(kind, now)
+    }
+
+    /// Returns fill statistics of the in-memory Bloom filter that answers
+    /// most replay guard checks without reading the cache database.
+    ///
+    /// A `bit_saturation` approaching 0.25 means about 0.1% of checks for
+    /// fresh nullifiers fall through to the database.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized.
+    pub fn bloom_filter_stats(&self) -> StorageResult<BloomStats> {
+        Ok(self.lock_inner()?.state()?.cache.replay_guard_bloom_stats())
+    }
+}
+
+#[uniffi::export]
+impl CredentialStore {
+    /// Permanently destroys all credential storage data.
+    ///
+    /// This removes the encryption key envelope, the vault database, and the
+    /// cache database. After this call the store is left in an uninitialized
+    /// state — any subsequent operation (other than re-initialization) will
+    /// return [`StorageError::NotInitialized`].
+    ///
+    /// Intended for use when the user logs out or deletes their account.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage lock cannot be acquired or the key
+    /// envelope cannot be deleted from the blob store.
+    pub fn destroy_storage(&self) -> StorageResult<()> {
+        let mut inner = self.lock_inner()?;
+        #[cfg(not(target_arch = "wasm32"))]
+        self.lock_vault_reader()?.take();
+        inner.destroy_storage()
+    }
+
+    /// Closes the store: commits queued writes, checkpoints the vault and
+    /// cache WAL files, closes their connections and releases the storage
+    /// lock file, and stops the vault-changed and expiry listeners.
+    ///
+    /// Foreign garbage collectors may release this object much later than
+    /// its last use, keeping the databases open in the meantime. Hosts
+    /// should close the store explicitly when they are done with it, e.g.
+    /// before the app process is backgrounded or another instance opens the
+    /// same storage. Dropping an open store closes it as a fallback.
+    ///
+    /// Afterwards every other method fails with [`StorageError::Closed`].
+    /// Closing a closed store does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage mutex is poisoned, or if committing
+    /// queued writes or checkpointing fails. The store is closed either way.
+    pub fn close(&self) -> StorageResult<()> {
+        let mut inner = self
+            .inner
+            .lock()
+            .map_err(|_| StorageError::Lock("storage mutex poisoned".to_string()))?;
+        if inner.lock.is_none() {
+            return Ok(());
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            for stop in [&self.expiry_listener_stop, &self.batch_flusher_stop] {
+                if let Ok(mut stop) = stop.lock() {
+                    if let Some(flag) = stop.take() {
+                        flag.store(true, std::sync::atomic::Ordering::SeqCst);
+                    }
+                }
+            }
+            if let Ok(mut tx) = self.vault_changed_tx.lock() {
+                tx.take();
+            }
+            // the reader must be gone for the checkpoint to reach the end of
+            // the WAL
+            if let Ok(mut reader) = self.vault_reader.lock() {
+                reader.take();
+            }
+        }
+        inner.close()
+    }
+
+    /// Re-seals the account key envelope under `new_keystore` and uses it from
+    /// now on, e.g. after a passcode change or an OS keystore migration made
+    /// the current keystore unable to open the envelope.
+    ///
+    /// Uses `K_intermediate` from the open session if the store is
+    /// initialized; otherwise the envelope must still open under the current
+    /// keystore. The new envelope is verified before it atomically replaces
+    /// the old one, so a failure leaves the old envelope in place. The vault
+    /// and cache are not re-encrypted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::NotInitialized`] if there is no envelope, or an
+    /// error if the envelope cannot be opened, sealed, or written.
+    pub fn rewrap_key_envelope(
+        &self,
+        new_keystore: Arc<dyn DeviceKeystore>,
+        now: u64,
+    ) -> StorageResult<()> {
+        self.lock_inner()?.rewrap_key_envelope(new_keystore, now)
+    }
+
+    /// Replaces `K_intermediate` with a new random key and re-encrypts the
+    /// vault and cache under it, e.g. after a suspected device compromise.
+    ///
+    /// The new key is sealed into a pending envelope first. Each database is
+    /// re-encrypted in one transaction, then the account key envelope is
+    /// replaced and the pending envelope deleted. If the process dies in
+    /// between, the next [`init`](Self::init) finishes the rekey if the vault
+    /// was re-encrypted and discards it otherwise; a cache left under the old
+    /// key is rebuilt.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::NotInitialized`] if the store is not open, or
+    /// an error if the new key cannot be sealed or the vault cannot be
+    /// re-encrypted, in which case the old key stays in use.
+    #[expect(
+        clippy::significant_drop_tightening,
+        reason = "the vault reader is reopened under the storage lock"
+    )]
+    pub fn rekey_vault(&self, now: u64) -> StorageResult<()> {
+        let mut inner = self.lock_inner()?;
+        // the vault can only be re-encrypted without other connections
+        #[cfg(not(target_arch = "wasm32"))]
+        self.lock_vault_reader()?.take();
+        let result = inner.rekey_vault(now);
+        #[cfg(not(target_arch = "wasm32"))]
+        self.ensure_vault_reader(&inner)?;
+        result
+    }
+
+    /// Reports whether the account key envelope opens with the device
+    /// keystore and the vault it unlocks is intact, to tell a keystore problem
+    /// (recoverable with [`rewrap_key_envelope`](Self::rewrap_key_envelope)
+    /// while a session is open) from vault corruption (restore from backup).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage mutex is poisoned or the storage lock
+    /// cannot be acquired.
+    pub fn key_envelope_health(&self) -> StorageResult<EnvelopeHealth> {
+        self.lock_inner()?.key_envelope_health()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[uniffi::export]
+impl CredentialStore {
+    /// Exports the current vault as an in-memory plaintext (unencrypted)
+    /// `SQLite` database for backup.
+    ///
+    /// The host app is responsible for persisting or uploading the returned
+    /// bytes. Disclosure memos are only included if enabled with
+    /// [`set_disclosure_memos_in_backups`](Self::set_disclosure_memos_in_backups).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the export fails.
+    #[expect(
+        clippy::significant_drop_tightening,
+        reason = "lock held intentionally for the full operation to prevent concurrent cleanup from deleting in-use temp files"
+    )]
+    pub fn export_vault_for_backup(&self) -> StorageResult<Vec<u8>> {
+        let inner = self.lock_inner()?;
+        inner.cleanup_stale_backup_files();
+        let path = inner.export_vault_for_backup_to_file()?;
+        let _cleanup = CleanupFile(path.clone());
+
+        std::fs::read(&path).map_err(|e| {
+            StorageError::VaultDb(format!("failed to read exported vault: {e}"))
+        })
+    }
+
+    /// Imports credentials from an in-memory plaintext vault backup.
+    ///
+    /// The store must already be initialized via [`init`](Self::init).
+    /// Intended for restore on a fresh install where the vault is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the import fails.
+    pub fn import_vault_from_backup(&self, backup_bytes: &[u8]) -> StorageResult<()> {
+        let mut inner = self.lock_inner()?;
+        inner.cleanup_stale_backup_files();
+        let path = inner.write_temp_backup_file(backup_bytes)?;
+        let _cleanup = CleanupFile(path.clone());
+
+        inner.import_vault_from_file(&path)
+    }
+
+    /// Registers a listener that is called after every successful vault
+    /// mutation (store, delete, purge).
+    ///
+    /// Only one listener can be active at a time — calling this replaces any
+    /// previously registered listener. The previous delivery thread shuts down
+    /// automatically when the old sender is dropped.
+    ///
+    /// Delivery happens on a dedicated background thread to avoid re-entering
+    /// the `UniFFI` call stack (see `logger.rs` for rationale).
+    ///
+    /// **Warning:** the listener **must not** call back into this
+    /// `CredentialStore` — doing so will deadlock.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_vault_changed_listener(&self, listener: Arc<dyn VaultChangedListener>) {
+        let (tx, rx) = mpsc::sync_channel(1);
+
+        match std::thread::Builder::new()
+            .name("walletkit-vault-notify".into())
+            .spawn(move || {
+                for () in rx {
+                    listener.on_vault_changed();
+                }
+            }) {
+            Ok(_) => {
+                if let Ok(mut guard) = self.vault_changed_tx.lock() {
+                    *guard = Some(tx);
+                }
+            }
+            Err(e) => {
+                tracing::error!("failed to spawn vault notification thread: {e}");
+            }
+        }
+    }
+}
+
+/// Implementation not exposed to foreign bindings
+impl CredentialStore {
+    /// Stores a `session_id_r_seed` into the cache, scoped to `environment`.
+    ///
+    /// `environment` identifies the World ID registry the session belongs to;
+    /// the authenticator derives it from its configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the insert fails.
+    pub fn store_session_seed(
+        &self,
+        environment: [u8; 32],
+        oprf_seed: CoreFieldElement,
+        session_id_r_seed: CoreFieldElement,
+        now: u64,
+    ) -> StorageResult<()> {
+        self.lock_inner()?.store_session_seed(
+            environment,
+            oprf_seed,
+            session_id_r_seed,
+            now,
+        )
+    }
+
+    /// Retrieves the `session_id_r_seed` for a given `oprf_seed` in
+    /// `environment`, if not expired.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the query fails.
+    pub fn get_session_seed(
+        &self,
+        environment: [u8; 32],
+        oprf_seed: CoreFieldElement,
+        now: u64,
+    ) -> StorageResult<Option<CoreFieldElement>> {
+        let seed = self
+            .lock_inner()?
+            .get_session_seed(environment, oprf_seed, now)?;
+        record_cache_lookup(CacheKind::SessionSeed, seed.is_some());
+        Ok(seed)
+    }
+
+    /// Retrieves the cached JWKS document of `rp_id`, if not expired.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the query fails.
+    pub fn rp_keys_get(&self, rp_id: &str, now: u64) -> StorageResult<Option<Vec<u8>>> {
+        let jwks = self.lock_inner()?.state()?.cache.rp_keys_get(rp_id, now)?;
+        record_cache_lookup(CacheKind::RpKeys, jwks.is_some());
+        Ok(jwks)
+    }
+
+    /// Caches the JWKS document of `rp_id` for an hour.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the insert fails.
+    pub fn rp_keys_put(&self, rp_id: &str, jwks: &[u8], now: u64) -> StorageResult<()> {
+        self.lock_inner()?.state()?.cache.rp_keys_put(
+            rp_id,
+            jwks,
+            now,
+            RP_KEYS_TTL_SECONDS,
+        )
+    }
+
+    /// Returns the leaf index the store was initialized with.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized.
+    pub fn leaf_index(&self) -> StorageResult<u64> {
+        Ok(self.lock_inner()?.state()?.leaf_index)
+    }
+
+    /// Best-effort notification to the registered vault-changed listener.
+    /// No-op on wasm32 where the listener cannot be registered.
+    ///
+    /// Only call this when vault contents change in a way that warrants a new
+    /// backup (i.e. credential or secure pref added or removed). Do **not**
+    /// call it for destructive operations like vault deletion or purge.
+    pub(super) fn notify_vault_changed(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Ok(guard) = self.vault_changed_tx.lock() {
+            if let Some(tx) = guard.as_ref() {
+                match tx.try_send(()) {
+                    Ok(()) | Err(mpsc::TrySendError::Full(())) => {}
+                    Err(mpsc::TrySendError::Disconnected(())) => {
+                        tracing::warn!("vault-changed listener disconnected");
+                    }
+                }
+            }
+        }
+    }
+
+    fn lock_inner(
+        &self,
+    ) -> StorageResult<std::sync::MutexGuard<'_, CredentialStoreInner>> {
+        #[cfg(test)]
+        self.lock_acquisitions
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let inner = self
+            .inner
+            .lock()
+            .map_err(|_| StorageError::Lock("storage mutex poisoned".to_string()))?;
+        if inner.lock.is_none() {
+            return Err(StorageError::Closed);
+        }
+        Ok(inner)
+    }
+
+    /// Runs `f` on the vault under the storage mutex.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or `f` fails.
+    #[cfg_attr(
+        feature = "tracing-spans",
+        tracing::instrument(
+            target = "walletkit",
+            name = "storage",
+            level = "debug",
+            skip_all,
+            err(Display)
+        )
+    )]
+    pub(super) fn with_vault<T>(
+        &self,
+        f: impl FnOnce(&CredentialVault) -> StorageResult<T>,
+    ) -> StorageResult<T> {
+        f(&self.lock_inner()?.state()?.vault)
+    }
+
+    /// Runs `f` on the vault and the cache under the storage mutex.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or `f` fails.
+    #[cfg_attr(
+        feature = "tracing-spans",
+        tracing::instrument(
+            target = "walletkit",
+            name = "storage",
+            level = "debug",
+            skip_all,
+            err(Display)
+        )
+    )]
+    pub(super) fn with_databases<T>(
+        &self,
+        f: impl FnOnce(&CredentialVault, &CacheDb) -> StorageResult<T>,
+    ) -> StorageResult<T> {
+        self.lock_inner()?
+            .state()
+            .and_then(|state| f(&state.vault, &state.cache))
+    }
+
+    /// Runs the read-only `f` on the vault reader, or on the writer's
+    /// connection under the storage mutex if there is no reader (before
+    /// `init`, or on wasm32, where only one connection can be open).
+    fn read_snapshot<T>(
+        &self,
+        f: impl FnOnce(&CredentialVault) -> StorageResult<T>,
+    ) -> StorageResult<T> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let vault_reader = self.lock_vault_reader()?;
+            if let Some(reader) = vault_reader.as_ref() {
+                return f(reader);
+            }
+        }
+        self.with_vault(f)
+    }
+
+    /// Opens the vault reader if there is none. Failing to open it is only
+    /// logged: reads fall back to the writer's connection.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn ensure_vault_reader(&self, inner: &CredentialStoreInner) -> StorageResult<()> {
+        let mut vault_reader = self.lock_vault_reader()?;
+        if vault_reader.is_none() {
+            match inner.open_vault_reader() {
+                Ok(reader) => *vault_reader = Some(reader),
+                Err(err) => {
+                    tracing::warn!(error = %err, "failed to open vault reader");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Initializes the store for `leaf_index` and imports a plaintext vault
+    /// backup into it, as restoring an account received from another device
+    /// does.
+    ///
+    /// The backup is checked to be a plaintext vault before anything is
+    /// written. If initialization or the import fails on a store that was not
+    /// initialized before, the store is destroyed again, so a bad backup does
+    /// not leave a half-initialized store behind and the restore can be
+    /// retried.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::VaultDb`] if `backup_bytes` is not a plaintext
+    /// vault, or an error if initialization or the import fails.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn init_from_backup(
+        &self,
+        leaf_index: u64,
+        now: u64,
+        backup_bytes: &[u8],
+    ) -> StorageResult<()> {
+        check_vault_backup(backup_bytes)?;
+        let mut inner = self.lock_inner()?;
+        let fresh = inner.state.is_none()
+            && inner
+                .blob_store
+                .read(ACCOUNT_KEYS_FILENAME.to_string())?
+                .is_none();
+        match inner.init_from_backup(leaf_index, now, backup_bytes) {
+            Ok(()) => self.ensure_vault_reader(&inner),
+            Err(err) => {
+                if fresh {
+                    self.lock_vault_reader()?.take();
+                    if let Err(undo) = inner.destroy_storage() {
+                        tracing::error!(
+                            error = %undo,
+                            "failed to undo init after a failed restore"
+                        );
+                    }
+                }
+                Err(err)
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn lock_vault_reader(
+        &self,
+    ) -> StorageResult<std::sync::MutexGuard<'_, Option<CredentialVault>>> {
+        self.vault_reader
+            .lock()
+            .map_err(|_| StorageError::Lock("vault reader mutex poisoned".to_string()))
+    }
+
+    /// Number of times the storage mutex was acquired so far.
+    #[cfg(test)]
+    pub(crate) fn lock_acquisitions(&self) -> usize {
+        self.lock_acquisitions
+            .load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Runs the storage part of an app cold start under a single acquisition
+    /// of the storage mutex.
+    ///
+    /// Initializes the store for `leaf_index`, records `environment` as the
+    /// environment the store was provisioned for if none is recorded yet,
+    /// invalidates credentials issued before `recovery_counter`, caches
+    /// `inclusion_proof` (if any) for `ttl_seconds`, and lists all
+    /// credentials. Failing to cache the proof is only logged and reported as
+    /// a stale proof.
+    ///
+    /// The environment is recorded after the vault has checked `leaf_index`,
+    /// so only the account the store belongs to provisions it. Recording it
+    /// moves the replay guard entries and session seeds written before they
+    /// were scoped to an environment into it, see
+    /// [`CacheDb::provision_environment`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if initialization, recording the environment,
+    /// invalidation, or listing fails.
+    pub(crate) fn cold_start(
+        &self,
+        leaf_index: u64,
+        recovery_counter: u64,
+        environment: [u8; 32],
+        inclusion_proof: Option<&AccountInclusionProof<TREE_DEPTH>>,
+        ttl_seconds: u64,
+        now: u64,
+    ) -> StorageResult<ColdStartSnapshot> {
+        let snapshot = {
+            let mut inner = self.lock_inner()?;
+            inner.init(leaf_index, now)?;
+            inner.provision_environment(environment)?;
+            let invalidated_credential_ids =
+                inner.invalidate_for_recovery(recovery_counter, now)?;
+            if let Some(proof) = inclusion_proof {
+                if let Err(e) = inner.merkle_cache_put(
+                    RegistryKind::AccountRegistry,
+                    proof,
+                    now,
+                    ttl_seconds,
+                ) {
+                    tracing::error!("Failed to cache Merkle inclusion proof: {e}");
+                }
+            }
+            let merkle_proof_cached = inner
+                .merkle_cache_get(RegistryKind::AccountRegistry, now)?
+                .is_some_and(|proof| proof.inclusion_proof.leaf_index == leaf_index);
+            ColdStartSnapshot {
+                credentials: inner.list_credentials(None, now)?,
+                invalidated_credential_ids,
+                merkle_proof_cached,
+            }
+        };
+        if !snapshot.invalidated_credential_ids.is_empty() {
+            self.notify_vault_changed();
+        }
+        Ok(snapshot)
+    }
+
+    /// Retrieves a full credential including raw bytes by issuer schema ID.
+    ///
+    /// Returns the most recent non-expired credential matching the issuer schema ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the credential query fails.
+    pub fn get_credential(
+        &self,
+        issuer_schema_id: u64,
+        now: u64,
+    ) -> StorageResult<Option<(Credential, FieldElement)>> {
+        self.lock_inner()?.get_credential(issuer_schema_id, now)
+    }
+
+    /// Like [`get_credential`](Self::get_credential), but always reads the
+    /// vault and leaves the decrypted credential out of the session cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the credential query fails.
+    pub fn get_credential_uncached(
+        &self,
+        issuer_schema_id: u64,
+        now: u64,
+    ) -> StorageResult<Option<(Credential, FieldElement)>> {
+        self.lock_inner()?
+            .read_credential(issuer_schema_id, now, false)
+    }
+
+    /// Drops (and zeroizes) every decrypted credential held in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage mutex is poisoned.
+    pub fn clear_credential_cache(&self) -> StorageResult<()> {
+        self.lock_inner()?.credential_cache.clear();
+        Ok(())
+    }
+}
+
+impl CredentialStoreInner {
+    fn init(&mut self, leaf_index: u64, now: u64) -> StorageResult<()> {
+        self.credential_cache.clear();
+        if let Some(state) = &mut self.state {
+            state.vault.init_leaf_index(leaf_index, now)?;
+            state.vault.init_merkle_refresh_seed()?;
+            state.leaf_index = leaf_index;
+            return Ok(());
+        }
+
+        if let Some(guard) = &self.quota_guard {
+            guard.preflight()?;
+        }
+        self.finish_interrupted_rekey(now)?;
+        let keys = StorageKeys::init(
+            self.keystore.as_ref(),
+            self.blob_store.as_ref(),
+            self.lock()?,
+            now,
+        )?;
+        let (vault, cache) = self.open_databases(&keys)?;
+        let state = StorageState {
+            keys,
+            vault,
+            cache,
+            leaf_index,
+        };
+        state.vault.init_leaf_index(leaf_index, now)?;
+        state.vault.init_merkle_refresh_seed()?;
+        self.state = Some(state);
+        Ok(())
+    }
+
+    fn provision_environment(&self, environment: [u8; 32]) -> StorageResult<()> {
+        self.state()?.cache.provision_environment(environment)?;
+        Ok(())
+    }
+
+    /// Opens (or creates) the vault and cache for writing.
+    fn open_databases(
+        &self,
+        keys: &StorageKeys,
+    ) -> StorageResult<(CredentialVault, CacheDb)> {
+        let k_intermediate = keys.intermediate_key();
+        let mut vault =
+            CredentialVault::new(&self.paths.vault_db_path(), k_intermediate)?;
+        vault.set_write_batch_window(self.write_batch_window)?;
+        let cache = CacheDb::new_with_config(
+            &self.paths.cache_db_path(),
+            k_intermediate,
+            self.cache_config,
+        )?;
+        Ok((vault, cache))
+    }
+
+    fn list_credentials(
+        &self,
+        issuer_schema_id: Option<u64>,
+        now: u64,
+    ) -> StorageResult<Vec<CredentialRecord>> {
+        let state = self.state()?;
+        state.vault.list_credentials(issuer_schema_id, now)
+    }
+
+    /// Opens a read-only connection to the vault, see
+    /// [`CredentialVault::open_read_only`].
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_vault_reader(&self) -> StorageResult<CredentialVault> {
+        let state = self.state()?;
+        CredentialVault::open_read_only(
+            &self.paths.vault_db_path(),
+            state.keys.intermediate_key(),
+        )
+    }
+
+    fn delete_credential(&mut self, credential_id: u64) -> StorageResult<()> {
+        self.credential_cache.clear();
+        let state = self.state_mut()?;
+        state.vault.delete_credential(credential_id)
+    }
+
+    fn delete_credentials(
+        &mut self,
+        credential_ids: &[u64],
+    ) -> StorageResult<BulkDeleteReport> {
+        self.credential_cache.clear();
+        let state = self.state_mut()?;
+        state.vault.delete_credentials(credential_ids)
+    }
+
+    fn invalidate_for_recovery(
+        &mut self,
+        new_counter: u64,
+        now: u64,
+    ) -> StorageResult<Vec<u64>> {
+        let state = self.state()?;
+        let invalidated = state.vault.invalidate_for_recovery(new_counter, now)?;
+        if !invalidated.is_empty() {
+            self.credential_cache.clear();
+        }
+        Ok(invalidated)
+    }
+
+    fn get_credential(
+        &mut self,
+        issuer_schema_id: u64,
+        now: u64,
+    ) -> StorageResult<Option<(Credential, FieldElement)>> {
+        self.read_credential(issuer_schema_id, now, true)
+    }
+
+    /// Reads a credential, going through the session cache if `use_cache`.
+    fn read_credential(
+        &mut self,
+        issuer_schema_id: u64,
+        now: u64,
+        use_cache: bool,
+    ) -> StorageResult<Option<(Credential, FieldElement)>> {
+        let state = self.state.as_ref().ok_or(StorageError::NotInitialized)?;
+        let cached = if use_cache {
+            // another process may have written the vault since the last read
+            self.credential_cache
+                .sync_vault_version(state.vault.data_version()?);
+            self.credential_cache.get(issuer_schema_id, now)
+        } else {
+            None
+        };
+        let (credential_bytes, blinding_factor_bytes) = if let Some(cached) = cached {
+            (
+                Zeroizing::new(cached.credential.clone()),
+                Zeroizing::new(cached.blinding_factor.clone()),
+            )
+        } else {
+            #[cfg(test)]
+            {
+                self.vault_credential_reads += 1;
+            }
+            let Some((credential, blinding_factor, expires_at)) = state
+                .vault
+                .fetch_credential_with_expiry(issuer_schema_id, now)?
+            else {
+                return Ok(None);
+            };
+            let (credential, blinding_factor) =
+                (Zeroizing::new(credential), Zeroizing::new(blinding_factor));
+            if use_cache {
+                self.credential_cache.insert(
+                    issuer_schema_id,
+                    now,
+                    expires_at,
+                    CachedCredential {
+                        credential: credential.to_vec(),
+                        blinding_factor: blinding_factor.to_vec(),
+                    },
+                );
+            }
+            (credential, blinding_factor)
+        };
+
+        let credential = Credential::from_slice(&credential_bytes).map_err(|e| {
+            StorageError::Serialization(format!(
+                "Critical. Failed to deserialize credential: {e}"
+            ))
+        })?;
+
+        let blinding_factor_bytes = Zeroizing::new(
+            <[u8; 32]>::try_from(blinding_factor_bytes.as_slice()).map_err(|_| {
+                StorageError::Serialization(
+                    "Critical. Blinding factor has invalid length".to_string(),
+                )
+            })?,
+        );
+        let blinding_factor = CoreFieldElement::from_be_bytes(&blinding_factor_bytes)
+            .map_err(|e| {
+            StorageError::Serialization(format!(
+                "Critical. Failed to deserialize blinding factor: {e}"
+            ))
+        })?;
+        Ok(Some((credential, blinding_factor.into())))
+    }
+
+    fn store_credential(
+        &mut self,
+        credential: &Credential,
+        blinding_factor: &FieldElement,
+        expires_at: u64,
+        associated_data: Option<Vec<u8>>,
+        now: u64,
+    ) -> StorageResult<u64> {
+        let issuer_schema_id = credential.issuer_schema_id();
+        let genesis_issued_at = credential.genesis_issued_at();
+        let credential_blob = credential
+            .to_bytes()
+            .map_err(|e| StorageError::Serialization(e.to_string()))?;
+        let subject_blinding_factor = blinding_factor.to_bytes();
+        if let Some(guard) = &self.quota_guard {
+            let bytes = credential_blob.len()
+                + subject_blinding_factor.len()
+                + associated_data.as_ref().map_or(0, Vec::len);
+            guard.check_write(bytes as u64)?;
+        }
+
+        self.credential_cache.clear();
+        let state = self.state_mut()?;
+        state.vault.flush_pending_writes()?;
+        state.vault.store_credential(
+            issuer_schema_id,
+            subject_blinding_factor,
+            genesis_issued_at,
+            expires_at,
+            credential_blob,
+            associated_data,
+            now,
+        )
+    }
+
+    fn store_session_seed(
+        &mut self,
+        environment: [u8; 32],
+        oprf_seed: CoreFieldElement,
+        session_id_r_seed: CoreFieldElement,
+        now: u64,
+    ) -> StorageResult<()> {
+        let state = self.state_mut()?;
+        state.cache.session_seed_put(
+            environment,
+            oprf_seed.to_be_bytes(),
+            session_id_r_seed.to_be_bytes(),
+            now,
+            SESSION_SEED_TTL_SECONDS,
+        )
+    }
+
+    fn get_session_seed(
+        &self,
+        environment: [u8; 32],
+        oprf_seed: CoreFieldElement,
+        now: u64,
+    ) -> StorageResult<Option<CoreFieldElement>> {
+        let state = self.state()?;
+        let Some(bytes) =
+            state
+                .cache
+                .session_seed_get(environment, oprf_seed.to_be_bytes(), now)?
+        else {
+            return Ok(None);
+        };
+        let seed = CoreFieldElement::from_be_bytes(&bytes).map_err(|e| {
+            StorageError::Serialization(format!(
+                "failed to deserialize session_id_r_seed: {e}"
+            ))
+        })?;
+        Ok(Some(seed))
+    }
+
+    /// Exports the vault to a temporary plaintext file in the worldid directory.
+    /// Returns the path to the file. The caller is responsible for cleanup.
+    ///
+    /// Holds the cross-process lock for the duration of the export so a
+    /// concurrent writer can't interleave between the stale-file cleanup
+    /// and the ATTACH-based plaintext copy.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_vault_for_backup_to_file(&self) -> StorageResult<String> {
+        let _guard = self.guard()?;
+        let state = self.state()?;
+        let dest = self.temp_backup_path();
+        state.vault.export_plaintext(&dest)?;
+        if self.disclosure_memos_in_backups {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            let disclosures = state.cache.list_disclosures(true, now)?;
+            export_disclosure_memos(&dest, &disclosures)?;
+        }
+        Ok(dest.to_string_lossy().to_string())
+    }
+
+    /// Writes raw bytes to a temporary file in the worldid directory.
+    /// Returns the path. The caller is responsible for cleanup.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn write_temp_backup_file(&self, bytes: &[u8]) -> StorageResult<String> {
+        let dest = self.temp_backup_path();
+        if let Err(e) = std::fs::write(&dest, bytes) {
+            // Best-effort cleanup of any partial write to avoid leaking
+            // plaintext data on disk (e.g. after ENOSPC).
+            let _ = std::fs::remove_file(&dest);
+            return Err(StorageError::VaultDb(format!(
+                "failed to write temp backup file: {e}"
+            )));
+        }
+        Ok(dest.to_string_lossy().to_string())
+    }
+
+    /// Imports from a plaintext vault file on disk.
+    ///
+    /// Holds the cross-process lock for the duration of the import so a
+    /// concurrent writer can't interleave with the ATTACH-based copy.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn import_vault_from_file(&mut self, backup_path: &str) -> StorageResult<()> {
+        self.credential_cache.clear();
+        let _guard = self.guard()?;
+        let state = self.state()?;
+        let source = std::path::Path::new(backup_path);
+        state.vault.import_plaintext(source)
+    }
+
+    /// Initializes storage and imports a plaintext vault backup.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn init_from_backup(
+        &mut self,
+        leaf_index: u64,
+        now: u64,
+        backup_bytes: &[u8],
+    ) -> StorageResult<()> {
+        self.init(leaf_index, now)?;
+        self.cleanup_stale_backup_files();
+        let path = self.write_temp_backup_file(backup_bytes)?;
+        let _cleanup = CleanupFile(path.clone());
+        self.import_vault_from_file(&path)
+    }
+
+    /// Removes any stale plaintext backup temp files left behind by a
+    /// previous crash or hard kill. Best-effort — errors are logged.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn cleanup_stale_backup_files(&self) {
+        let dir = self.paths.worldid_dir();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if let Some(name) = entry.file_name().to_str() {
+                if name.starts_with(VAULT_BACKUP_TEMP_PREFIX) {
+                    if let Err(e) = std::fs::remove_file(entry.path()) {
+                        tracing::error!(
+                            "Failed to clean up stale backup file {}: {e}",
+                            entry.path().display()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns a unique temp file path in the worldid directory for backup operations.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn temp_backup_path(&self) -> std::path::PathBuf {
+        let filename = format!(
+            "{}{}.sqlite",
+            VAULT_BACKUP_TEMP_PREFIX,
+            uuid::Uuid::new_v4()
+        );
+        self.paths.worldid_dir().join(filename)
+    }
+
+    /// Deletes all stored credentials from the vault.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delete operation fails.
+    fn danger_delete_all_credentials(&mut self) -> StorageResult<u64> {
+        self.credential_cache.clear();
+        let state = self.state_mut()?;
+        state.vault.danger_delete_all_credentials()
+    }
+
+    fn rewrap_key_envelope(
+        &mut self,
+        new_keystore: Arc<dyn DeviceKeystore>,
+        now: u64,
+    ) -> StorageResult<()> {
+        let opened;
+        let keys = if let Some(state) = &self.state {
+            &state.keys
+        } else {
+            opened = StorageKeys::open(
+                self.keystore.as_ref(),
+                self.blob_store.as_ref(),
+                self.lock()?,
+            )?
+            .ok_or(StorageError::NotInitialized)?;
+            &opened
+        };
+        keys.rewrap(
+            new_keystore.as_ref(),
+            self.blob_store.as_ref(),
+            self.lock()?,
+            now,
+        )?;
+        self.keystore = new_keystore;
+        Ok(())
+    }
+
+    fn rekey_vault(&mut self, now: u64) -> StorageResult<()> {
+        let state = self.state()?;
+        state.vault.flush_pending_writes()?;
+        let keys = StorageKeys::generate()?;
+        keys.seal_pending(
+            self.keystore.as_ref(),
+            self.blob_store.as_ref(),
+            self.lock()?,
+            now,
+        )?;
+        // from here on, `finish_interrupted_rekey` cleans up after a crash
+        if let Err(err) = state.vault.rekey(keys.intermediate_key()) {
+            self.blob_store
+                .delete(ACCOUNT_PENDING_KEYS_FILENAME.to_string())?;
+            return Err(err);
+        }
+        if let Err(err) = state.cache.rekey(keys.intermediate_key()) {
+            // the open connection keeps working; the next open rebuilds it
+            tracing::warn!(error = %err, "failed to rekey cache");
+        }
+        let committed = keys.rewrap(
+            self.keystore.as_ref(),
+            self.blob_store.as_ref(),
+            self.lock()?,
+            now,
+        );
+        // the vault is under the new key whether or not the envelope was
+        // replaced; if not, the pending envelope still holds the key
+        self.state_mut()?.keys = keys;
+        committed?;
+        self.blob_store
+            .delete(ACCOUNT_PENDING_KEYS_FILENAME.to_string())
+    }
+
+    /// Finishes a [`CredentialStore::rekey_vault`] the process died in: the
+    /// pending key replaces the account key if the vault opens with it, and
+    /// is discarded otherwise.
+    fn finish_interrupted_rekey(&self, now: u64) -> StorageResult<()> {
+        let Some(pending) = StorageKeys::open_pending(
+            self.keystore.as_ref(),
+            self.blob_store.as_ref(),
+            self.lock()?,
+        )?
+        else {
+            return Ok(());
+        };
+        let rekeyed = CredentialVault::open_read_only(
+            &self.paths.vault_db_path(),
+            pending.intermediate_key(),
+        )
+        .is_ok();
+        if rekeyed {
+            pending.rewrap(
+                self.keystore.as_ref(),
+                self.blob_store.as_ref(),
+                self.lock()?,
+                now,
+            )?;
+        }
+        self.blob_store
+            .delete(ACCOUNT_PENDING_KEYS_FILENAME.to_string())
+    }
+
+    fn key_envelope_health(&self) -> StorageResult<EnvelopeHealth> {
+        let keys = match StorageKeys::open(
+            self.keystore.as_ref(),
+            self.blob_store.as_ref(),
+            self.lock()?,
+        ) {
+            Ok(Some(keys)) => keys,
+            Ok(None) => return Ok(EnvelopeHealth::Missing),
+            Err(err @ (StorageError::Lock(_) | StorageError::BlobStore(_))) => {
+                return Err(err)
+            }
+            Err(err) => {
+                return Ok(EnvelopeHealth::EnvelopeUnreadable {
+                    reason: err.to_string(),
+                })
+            }
+        };
+
+        let vault_path = self.paths.vault_db_path();
+        let intact = match &self.state {
+            Some(state) => state.vault.check_integrity(),
+            // nothing to check until `init` creates the vault
+            None if cfg!(target_arch = "wasm32") || !vault_path.exists() => Ok(true),
+            None => {
+                CredentialVault::open_read_only(&vault_path, keys.intermediate_key())
+                    .and_then(|vault| vault.check_integrity())
+            }
+        };
+        Ok(match intact {
+            Ok(true) => EnvelopeHealth::Healthy,
+            Ok(false) => EnvelopeHealth::VaultCorrupted {
+                reason: "integrity check failed".to_string(),
+            },
+            Err(err) => EnvelopeHealth::VaultCorrupted {
+                reason: err.to_string(),
+            },
+        })
+    }
+
+    /// Permanently destroys all storage data: encryption keys, vault, and cache.
+    fn destroy_storage(&mut self) -> StorageResult<()> {
+        let _guard = self.guard()?;
+        // Drop in-memory state: zeroizes keys and cached credentials, closes
+        // database connections.
+        self.state = None;
+        self.credential_cache.clear();
+        // Delete the encryption key envelope. Without this key the database
+        // files are unreadable even if file deletion below fails.
+        self.blob_store.delete(ACCOUNT_KEYS_FILENAME.to_string())?;
+        self.blob_store
+            .delete(ACCOUNT_PENDING_KEYS_FILENAME.to_string())?;
+        // Best-effort removal of database files and their SQLite sidecar files.
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            remove_db_files(&self.paths.vault_db_path());
+            remove_db_files(&self.paths.cache_db_path());
+        }
+        Ok(())
+    }
+
+    /// Drops the decrypted credential cache and releases the databases' page
+    /// caches. Both are rebuilt on next use.
+    fn release_memory(&mut self) -> StorageResult<()> {
+        self.credential_cache.clear();
+        self.state.as_ref().map_or(Ok(()), |state| {
+            // release both even if the first fails
+            let vault = state.vault.release_memory();
+            let cache = state.cache.release_memory();
+            vault.and(cache)
+        })
+    }
+
+    /// Commits queued writes, checkpoints and closes the databases, and
+    /// releases the storage lock. Everything is released even if a step
+    /// fails; the first error is returned.
+    fn close(&mut self) -> StorageResult<()> {
+        let result = match self.state.take() {
+            Some(state) => {
+                let vault = state
+                    .vault
+                    .flush_pending_writes()
+                    .and_then(|()| state.vault.checkpoint());
+                let cache = state.cache.checkpoint();
+                vault.and(cache)
+            }
+            None => Ok(()),
+        };
+        self.credential_cache.clear();
+        self.quota_guard = None;
+        self.lock = None;
+        result
+    }
+}
+
+impl CredentialStore {
+    fn from_inner(inner: CredentialStoreInner) -> Self {
+        let inner = Arc::new(Mutex::new(inner));
+        with_live_stores(|live| live.push(Arc::downgrade(&inner)));
+        Self {
+            inner,
+            #[cfg(not(target_arch = "wasm32"))]
+            vault_changed_tx: Mutex::new(None),
+            #[cfg(not(target_arch = "wasm32"))]
+            vault_reader: Mutex::new(None),
+            #[cfg(not(target_arch = "wasm32"))]
+            expiry_listener_stop: Mutex::new(None),
+            #[cfg(not(target_arch = "wasm32"))]
+            batch_flusher_stop: Mutex::new(None),
+            metrics: Arc::default(),
+            #[cfg(test)]
+            lock_acquisitions: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+
+    /// Creates a new storage handle from a platform provider.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage lock cannot be opened.
+    pub fn from_provider(provider: &dyn StorageProvider) -> StorageResult<Self> {
+        let inner = CredentialStoreInner::from_provider(provider)?;
+        Ok(Self::from_inner(inner))
+    }
+
+    /// Creates a new storage handle from explicit components.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage lock cannot be opened.
+    pub fn new(
+        paths: StoragePaths,
+        keystore: Arc<dyn DeviceKeystore>,
+        blob_store: Arc<dyn AtomicBlobStore>,
+    ) -> StorageResult<Self> {
+        let inner = CredentialStoreInner::new(paths, keystore, blob_store)?;
+        Ok(Self::from_inner(inner))
+    }
+
+    /// Returns the storage paths used by this handle.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the storage mutex is poisoned.
+    pub fn paths(&self) -> StorageResult<StoragePaths> {
+        self.lock_inner().map(|inner| inner.paths.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::credential_vault::UsageCounter;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider, TEST_ENVIRONMENT,
+    };
+    use crate::storage::types::StorageEstimate;
+
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct TestVaultListener(Arc<AtomicU32>);
+
+    impl VaultChangedListener for TestVaultListener {
+        fn on_vault_changed(&self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn wait_for_listener_count(count: &AtomicU32, expected: u32) {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(1);
+
+        loop {
+            let actual = count.load(Ordering::SeqCst);
+            if actual >= expected {
+                return;
+            }
+
+            if std::time::Instant::now() >= deadline {
+                break;
+            }
+
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        panic!(
+            "deadline {deadline:?} exceeded while waiting for listener count to reach {expected}",
+        );
+    }
+
+    #[test]
+    fn test_get_credential() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let paths = provider.paths().as_ref().clone();
+        let keystore = provider.keystore();
+        let blob_store = provider.blob_store();
+
+        let mut inner = CredentialStoreInner::new(paths, keystore, blob_store)
+            .expect("create inner");
+        inner.init(42, 1000).expect("init storage");
+
+        // Store a test credential
+        let issuer_schema_id = 123u64;
+        let blinding_factor = FieldElement::from(42u64);
+        let expires_at = 2000u64;
+        let core_cred = CoreCredential::new()
+            .issuer_schema_id(issuer_schema_id)
+            .genesis_issued_at(1000);
+        let credential: Credential = core_cred.into();
+        let associated_data = Some(vec![6, 7, 8]);
+
+        inner
+            .store_credential(
+                &credential,
+                &blinding_factor,
+                expires_at,
+                associated_data,
+                1000,
+            )
+            .expect("store credential");
+
+        // Retrieve the credential
+        let (credential, _blinding_factor) = inner
+            .get_credential(issuer_schema_id, 1000)
+            .expect("get credential")
+            .expect("credential should exist");
+
+        // Verify the retrieved data
+        assert_eq!(credential.issuer_schema_id(), issuer_schema_id);
+
+        // Verify non-existent credential returns None
+        let non_existent = inner
+            .get_credential(999u64, 1000)
+            .expect("get credential query should succeed");
+        assert!(
+            non_existent.is_none(),
+            "Non-existent credential should return None"
+        );
+
+        // Verify expired credential returns None
+        let expired = inner
+            .get_credential(issuer_schema_id, 2001)
+            .expect("get credential query should succeed");
+        assert!(expired.is_none(), "Expired credential should return None");
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_export_and_import_vault_backup() {
+        use world_id_core::Credential as CoreCredential;
+
+        let src_root = temp_root_path();
+        let src_provider = InMemoryStorageProvider::new(&src_root);
+        let src_store =
+            CredentialStore::from_provider(&src_provider).expect("create src store");
+        src_store.init(42, 1000).expect("init src storage");
+
+        let issuer_schema_id = 100u64;
+        let blinding_factor = FieldElement::from(7u64);
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(issuer_schema_id)
+            .genesis_issued_at(1000)
+            .into();
+
+        src_store
+            .store_credential(&cred, &blinding_factor, 9999, None, 1000)
+            .expect("store credential");
+
+        let bytes = src_store.export_vault_for_backup().expect("export vault");
+        assert!(!bytes.is_empty());
+
+        let dst_root = temp_root_path();
+        let dst_provider = InMemoryStorageProvider::new(&dst_root);
+        let dst_store =
+            CredentialStore::from_provider(&dst_provider).expect("create dst store");
+        dst_store.init(42, 1000).expect("init dst storage");
+
+        dst_store
+            .import_vault_from_backup(&bytes)
+            .expect("import vault");
+
+        let (imported_cred, imported_bf) = dst_store
+            .get_credential(issuer_schema_id, 1000)
+            .expect("get credential")
+            .expect("imported credential should exist");
+        assert_eq!(imported_cred.issuer_schema_id(), issuer_schema_id);
+        assert_eq!(imported_bf.to_bytes(), blinding_factor.to_bytes());
+
+        cleanup_test_storage(&src_root);
+        cleanup_test_storage(&dst_root);
+    }
+
+    #[test]
+    fn test_export_and_import_multiple_credentials_with_associated_data() {
+        use world_id_core::Credential as CoreCredential;
+
+        let src_root = temp_root_path();
+        let src_provider = InMemoryStorageProvider::new(&src_root);
+        let src_store =
+            CredentialStore::from_provider(&src_provider).expect("create src store");
+        src_store.init(42, 1000).expect("init src storage");
+
+        // Store credential A (schema 100) without associated data
+        let cred_a: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        src_store
+            .store_credential(&cred_a, &FieldElement::from(7u64), 9999, None, 1000)
+            .expect("store cred A");
+
+        // Store credential B (schema 200) with associated data
+        let cred_b: Credential = CoreCredential::new()
+            .issuer_schema_id(200)
+            .genesis_issued_at(2000)
+            .into();
+        src_store
+            .store_credential(
+                &cred_b,
+                &FieldElement::from(13u64),
+                9999,
+                Some(b"extra-payload-for-cred-b".to_vec()),
+                2000,
+            )
+            .expect("store cred B");
+
+        // Store credential C (schema 300) without associated data
+        let cred_c: Credential = CoreCredential::new()
+            .issuer_schema_id(300)
+            .genesis_issued_at(3000)
+            .into();
+        src_store
+            .store_credential(&cred_c, &FieldElement::from(42u64), 9999, None, 3000)
+            .expect("store cred C");
+
+        assert_eq!(src_store.list_credentials(None, 1000).unwrap().len(), 3);
+
+        // Export and import into fresh store
+        let bytes = src_store.export_vault_for_backup().expect("export vault");
+
+        let dst_root = temp_root_path();
+        let dst_provider = InMemoryStorageProvider::new(&dst_root);
+        let dst_store =
+            CredentialStore::from_provider(&dst_provider).expect("create dst store");
+        dst_store.init(42, 1000).expect("init dst storage");
+
+        dst_store
+            .import_vault_from_backup(&bytes)
+            .expect("import vault");
+
+        assert_eq!(dst_store.list_credentials(None, 1000).unwrap().len(), 3);
+
+        let (cred_a, bf_a) = dst_store
+            .get_credential(100, 1000)
+            .expect("get cred A")
+            .expect("cred A should exist");
+        assert_eq!(cred_a.issuer_schema_id(), 100);
+        assert_eq!(bf_a.to_bytes(), FieldElement::from(7u64).to_bytes());
+
+        let (cred_b, bf_b) = dst_store
+            .get_credential(200, 2000)
+            .expect("get cred B")
+            .expect("cred B should exist");
+        assert_eq!(cred_b.issuer_schema_id(), 200);
+        assert_eq!(bf_b.to_bytes(), FieldElement::from(13u64).to_bytes());
+
+        let (cred_c, bf_c) = dst_store
+            .get_credential(300, 3000)
+            .expect("get cred C")
+            .expect("cred C should exist");
+        assert_eq!(cred_c.issuer_schema_id(), 300);
+        assert_eq!(bf_c.to_bytes(), FieldElement::from(42u64).to_bytes());
+
+        cleanup_test_storage(&src_root);
+        cleanup_test_storage(&dst_root);
+    }
+
+    #[test]
+    fn test_import_vault_backup_into_non_empty_vault_fails() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+
+        let blinding_factor = FieldElement::from(7u64);
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100u64)
+            .genesis_issued_at(1000)
+            .into();
+
+        store
+            .store_credential(&cred, &blinding_factor, 9999, None, 1000)
+            .expect("store credential");
+
+        let bytes = store.export_vault_for_backup().expect("export vault");
+
+        // Importing into a non-empty vault should fail.
+        let result = store.import_vault_from_backup(&bytes);
+        assert!(result.is_err(), "import into non-empty vault should fail");
+
+        // Verify existing data is unchanged after the failed import.
+        let (cred, bf) = store
+            .get_credential(100, 1000)
+            .expect("get credential after failed import")
+            .expect("credential should still exist");
+        assert_eq!(cred.issuer_schema_id(), 100);
+        assert_eq!(bf.to_bytes(), blinding_factor.to_bytes());
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_import_vault_backup_invalid_bytes_fails() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+
+        let result = store.import_vault_from_backup(b"not a sqlite database");
+        assert!(result.is_err(), "import from invalid bytes should fail");
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_import_vault_backup_transaction_atomicity() {
+        use crate::storage::credential_vault::BACKUP_TABLES;
+        use walletkit_db::Connection;
+        use world_id_core::Credential as CoreCredential;
+
+        let src_root = temp_root_path();
+        let src_provider = InMemoryStorageProvider::new(&src_root);
+        let src_store =
+            CredentialStore::from_provider(&src_provider).expect("create src store");
+        src_store.init(42, 1000).expect("init src storage");
+
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100u64)
+            .genesis_issued_at(1000)
+            .into();
+        src_store
+            .store_credential(&cred, &FieldElement::from(7u64), 9999, None, 1000)
+            .expect("store credential");
+
+        // Export as bytes, write to a temp file so we can corrupt it.
+        let bytes = src_store.export_vault_for_backup().expect("export vault");
+        let corrupt_path = src_root.join("corrupt_backup.sqlite");
+        std::fs::write(&corrupt_path, &bytes).expect("write backup");
+
+        // Corrupt the *last* table in BACKUP_TABLES. Earlier INSERTs succeed
+        // before this one fails, testing transaction rollback.
+        assert_eq!(
+            *BACKUP_TABLES.last().unwrap(),
+            "blob_objects",
+            "update this test if BACKUP_TABLES order changes"
+        );
+        let backup_conn = Connection::open(&corrupt_path, false).expect("open backup");
+        backup_conn
+            .execute(
+                "INSERT INTO blob_objects (content_id, blob_kind, created_at, bytes)
+                 VALUES (NULL, 1, 2000, X'CAFE')",
+                &[],
+            )
+            .expect("insert corrupt row");
+        drop(backup_conn);
+
+        // Read corrupted file back as bytes
+        let corrupt_bytes = std::fs::read(&corrupt_path).expect("read corrupt backup");
+
+        let dst_root = temp_root_path();
+        let dst_provider = InMemoryStorageProvider::new(&dst_root);
+        let dst_store =
+            CredentialStore::from_provider(&dst_provider).expect("create dst store");
+        dst_store.init(42, 1000).expect("init dst storage");
+
+        let result = dst_store.import_vault_from_backup(&corrupt_bytes);
+        assert!(result.is_err(), "import with corrupt backup should fail");
+
+        // The transaction should have rolled back — destination is still empty.
+        let dst_list = dst_store.list_credentials(None, 1000).expect("list dst");
+        assert!(
+            dst_list.is_empty(),
+            "destination should be empty after failed import (transaction rolled back)"
+        );
+
+        std::fs::remove_file(&corrupt_path).ok();
+        cleanup_test_storage(&src_root);
+        cleanup_test_storage(&dst_root);
+    }
+
+    #[test]
+    fn test_danger_delete_all_credentials() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let paths = provider.paths().as_ref().clone();
+        let keystore = provider.keystore();
+        let blob_store = provider.blob_store();
+
+        let mut inner = CredentialStoreInner::new(paths, keystore, blob_store)
+            .expect("create inner");
+        inner.init(42, 1000).expect("init storage");
+
+        let blinding_factor = FieldElement::from(42u64);
+        for issuer_id in [100u64, 200u64] {
+            let cred: Credential = CoreCredential::new()
+                .issuer_schema_id(issuer_id)
+                .genesis_issued_at(1000)
+                .into();
+            inner
+                .store_credential(&cred, &blinding_factor, 2000, None, 1000)
+                .expect("store credential");
+        }
+
+        let deleted = inner.danger_delete_all_credentials().expect("delete all");
+        assert_eq!(deleted, 2);
+
+        let remaining = inner.list_credentials(None, 1000).expect("list");
+        assert!(remaining.is_empty());
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_list_credentials_does_not_wait_for_writer() {
+        use std::time::{Duration, Instant};
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 2000, None, 1000)
+            .expect("store credential");
+
+        let (started_tx, started_rx) = mpsc::channel();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                // a slow write holding both the storage mutex and the
+                // database write lock, like a batch import
+                let inner = store.lock_inner().expect("lock");
+                let writer = walletkit_db::Vault::open(
+                    &inner.paths.vault_db_path(),
+                    inner.state().expect("state").keys.intermediate_key(),
+                    |_| Ok(()),
+                )
+                .expect("open writer");
+                let tx = writer.connection().transaction_immediate().expect("begin");
+                tx.execute("DELETE FROM credential_records", &[])
+                    .expect("delete");
+                started_tx.send(()).expect("signal");
+                std::thread::sleep(Duration::from_secs(2));
+                drop(tx);
+                drop(inner);
+            });
+
+            started_rx.recv().expect("writer started");
+            let started = Instant::now();
+            let records = store.list_credentials(None, 1000).expect("list");
+            assert!(started.elapsed() < Duration::from_secs(1));
+            // the uncommitted delete is not visible
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].issuer_schema_id, 100);
+        });
+
+        store.destroy_storage().expect("destroy");
+        assert!(matches!(
+            store.list_credentials(None, 1000),
+            Err(StorageError::NotInitialized)
+        ));
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_rewrap_key_envelope_after_keystore_replacement() {
+        use crate::storage::tests_utils::{InMemoryBlobStore, InMemoryKeystore};
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let paths = StoragePaths::new(&root);
+        let blob_store: Arc<dyn AtomicBlobStore> = Arc::new(InMemoryBlobStore::new());
+        let open_store = |keystore: &Arc<InMemoryKeystore>| {
+            CredentialStore::new(paths.clone(), keystore.clone(), blob_store.clone())
+                .expect("create store")
+        };
+        let old_keystore = Arc::new(InMemoryKeystore::new());
+        let new_keystore = Arc::new(InMemoryKeystore::new());
+
+        let store = open_store(&old_keystore);
+        assert_eq!(
+            store.key_envelope_health().unwrap(),
+            EnvelopeHealth::Missing
+        );
+        assert!(matches!(
+            store.rewrap_key_envelope(new_keystore.clone(), 1000),
+            Err(StorageError::NotInitialized)
+        ));
+        store.init(42, 1000).expect("init storage");
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 2000, None, 1000)
+            .expect("store credential");
+        assert_eq!(
+            store.key_envelope_health().unwrap(),
+            EnvelopeHealth::Healthy
+        );
+
+        // the device keystore was replaced, the old envelope no longer opens
+        let migrated = open_store(&new_keystore);
+        assert!(matches!(
+            migrated.key_envelope_health().unwrap(),
+            EnvelopeHealth::EnvelopeUnreadable { .. }
+        ));
+        assert!(migrated.init(42, 1100).is_err());
+
+        // the session still open on the old handle recovers the envelope
+        store
+            .rewrap_key_envelope(new_keystore, 1100)
+            .expect("rewrap");
+        assert_eq!(
+            store.key_envelope_health().unwrap(),
+            EnvelopeHealth::Healthy
+        );
+        assert_eq!(
+            migrated.key_envelope_health().unwrap(),
+            EnvelopeHealth::Healthy
+        );
+        migrated.init(42, 1200).expect("init with the new keystore");
+        assert_eq!(migrated.list_credentials(None, 1200).unwrap().len(), 1);
+        assert!(matches!(
+            open_store(&old_keystore).key_envelope_health().unwrap(),
+            EnvelopeHealth::EnvelopeUnreadable { .. }
+        ));
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_rekey_vault_replaces_key() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 2000, None, 1000)
+            .expect("store credential");
+        let old_key = StorageKeys::open(
+            provider.keystore().as_ref(),
+            provider.blob_store().as_ref(),
+            &StorageLock::open(&store.storage_paths().unwrap().lock_path())
+                .expect("open lock"),
+        )
+        .expect("open keys")
+        .expect("envelope");
+
+        store.rekey_vault(1100).expect("rekey");
+        assert_eq!(store.list_credentials(None, 1100).unwrap().len(), 1);
+        let vault_path = store.storage_paths().unwrap().vault_db_path();
+        drop(store);
+
+        assert!(CredentialVault::open_read_only(
+            &vault_path,
+            old_key.intermediate_key()
+        )
+        .is_err());
+        assert!(provider
+            .blob_store()
+            .read(ACCOUNT_PENDING_KEYS_FILENAME.to_string())
+            .unwrap()
+            .is_none());
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1200).expect("init with the new key");
+        assert_eq!(store.list_credentials(None, 1200).unwrap().len(), 1);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_init_finishes_interrupted_rekey() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+        let paths = store.storage_paths().unwrap();
+        drop(store);
+
+        // the process died after re-encrypting the vault, before the account
+        // key envelope was replaced
+        let lock = StorageLock::open(&paths.lock_path()).expect("open lock");
+        let keystore = provider.keystore();
+        let blob_store = provider.blob_store();
+        let old_keys = StorageKeys::open(keystore.as_ref(), blob_store.as_ref(), &lock)
+            .expect("open keys")
+            .expect("envelope");
+        let new_keys = StorageKeys::generate().expect("generate key");
+        new_keys
+            .seal_pending(keystore.as_ref(), blob_store.as_ref(), &lock, 1100)
+            .expect("seal pending");
+        CredentialVault::new(&paths.vault_db_path(), old_keys.intermediate_key())
+            .expect("open vault")
+            .rekey(new_keys.intermediate_key())
+            .expect("rekey vault");
+
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1200).expect("init finishes the rekey");
+        assert!(blob_store
+            .read(ACCOUNT_PENDING_KEYS_FILENAME.to_string())
+            .unwrap()
+            .is_none());
+        drop(store);
+        let current = StorageKeys::open(keystore.as_ref(), blob_store.as_ref(), &lock)
+            .expect("open keys")
+            .expect("envelope");
+        assert!(CredentialVault::open_read_only(
+            &paths.vault_db_path(),
+            current.intermediate_key()
+        )
+        .is_ok());
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_key_envelope_health_reports_corrupted_vault() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+        let vault_path = store.storage_paths().unwrap().vault_db_path();
+        drop(store);
+
+        std::fs::write(&vault_path, vec![0xA5; 8192]).expect("corrupt vault");
+        for ext in ["sqlite-wal", "sqlite-shm"] {
+            let _ = std::fs::remove_file(vault_path.with_extension(ext));
+        }
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        assert!(matches!(
+            store.key_envelope_health().unwrap(),
+            EnvelopeHealth::VaultCorrupted { .. }
+        ));
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_failed_envelope_write_keeps_previous_envelope() {
+        use crate::storage::tests_utils::{FaultInjector, InMemoryKeystore};
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let injector = FaultInjector::default();
+        // the third blob write fails
+        injector.fail_writes_after(2);
+        let provider =
+            InMemoryStorageProvider::new(&root).with_fault_injector(injector.clone());
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 2000, None, 1000)
+            .expect("store credential");
+        store
+            .rewrap_key_envelope(provider.keystore(), 1100)
+            .expect("rewrap under the same keystore");
+
+        let new_keystore = Arc::new(InMemoryKeystore::new());
+        assert!(matches!(
+            store.rewrap_key_envelope(new_keystore.clone(), 1200),
+            Err(StorageError::BlobStore(_))
+        ));
+
+        // the failed write left the previous envelope and the vault intact
+        let reopened = CredentialStore::from_provider(&provider).expect("create store");
+        assert_eq!(
+            reopened.key_envelope_health().unwrap(),
+            EnvelopeHealth::Healthy
+        );
+        reopened.init(42, 1300).expect("init storage");
+        assert_eq!(reopened.list_credentials(None, 1300).unwrap().len(), 1);
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 2000, None, 1300)
+            .expect("store credential after the failed write");
+
+        // retrying the failed operation converges to the same state
+        injector.fail_writes_after(1);
+        store
+            .rewrap_key_envelope(new_keystore.clone(), 1400)
+            .expect("retry rewrap");
+        let migrated = CredentialStore::new(
+            provider.paths().as_ref().clone(),
+            new_keystore,
+            provider.blob_store(),
+        )
+        .expect("create store");
+        migrated.init(42, 1500).expect("init with the new keystore");
+        assert_eq!(migrated.list_credentials(None, 1500).unwrap().len(), 2);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_corrupted_envelope_fails_init() {
+        use crate::storage::tests_utils::FaultInjector;
+
+        let root = temp_root_path();
+        let injector = FaultInjector::default();
+        let provider =
+            InMemoryStorageProvider::new(&root).with_fault_injector(injector.clone());
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+        drop(store);
+
+        injector.corrupt_blob(ACCOUNT_KEYS_FILENAME);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        assert!(matches!(
+            store.key_envelope_health().unwrap(),
+            EnvelopeHealth::EnvelopeUnreadable { .. }
+        ));
+        let operations = injector.operation_count();
+        assert!(store.init(42, 1100).is_err());
+        // init looks for an interrupted rekey, reads the envelope, and does
+        // not replace it with a fresh one
+        assert_eq!(injector.operation_count(), operations + 2);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_failed_envelope_read_fails_init() {
+        use crate::storage::tests_utils::FaultInjector;
+
+        let root = temp_root_path();
+        let injector = FaultInjector::default();
+        injector.fail_reads_after(0);
+        injector.delay_operations_ms(20);
+        let provider =
+            InMemoryStorageProvider::new(&root).with_fault_injector(injector.clone());
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+
+        let started = std::time::Instant::now();
+        assert!(matches!(
+            store.init(42, 1000),
+            Err(StorageError::BlobStore(_))
+        ));
+        assert!(started.elapsed() >= std::time::Duration::from_millis(20));
+        // the failed read stops init before it creates an envelope
+        assert_eq!(injector.operation_count(), 1);
+
+        injector.fail_reads_after(usize::MAX);
+        store.init(42, 1000).expect("init storage");
+        // a read for an interrupted rekey, then a read and a write of the
+        // account key envelope
+        assert_eq!(injector.operation_count(), 4);
+
+        cleanup_test_storage(&root);
+    }
+
+    /// Reads the committed value of `counter` through the read-only vault
+    /// connection, which does not see writes queued on the writer's.
+    fn committed_usage_counter(store: &CredentialStore, counter: UsageCounter) -> u64 {
+        store
+            .read_snapshot(|vault| vault.usage_counter(counter))
+            .expect("read usage counter")
+    }
+
+    #[test]
+    fn test_write_batching_queues_usage_counters() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+        let miss = UsageCounter::MerkleCacheMiss;
+
+        // without a window every increment is committed
+        store
+            .merkle_cache_get(RegistryKind::AccountRegistry, 1000)
+            .unwrap();
+        assert_eq!(committed_usage_counter(&store, miss), 1);
+
+        store.set_write_batch_window_ms(60_000).unwrap();
+        for _ in 0..2 {
+            store
+                .merkle_cache_get(RegistryKind::AccountRegistry, 1000)
+                .unwrap();
+        }
+        // queued increments are visible to reads before the flush
+        assert_eq!(store.local_stats(0, 1000).unwrap().cache_misses, 3);
+        assert_eq!(committed_usage_counter(&store, miss), 1);
+
+        store.flush().unwrap();
+        assert_eq!(committed_usage_counter(&store, miss), 3);
+        assert_eq!(store.local_stats(0, 1000).unwrap().cache_misses, 3);
+
+        // an elapsed window is flushed in the background, without any
+        // further write or read
+        store.set_write_batch_window_ms(20).unwrap();
+        for _ in 0..2 {
+            store
+                .merkle_cache_get(RegistryKind::AccountRegistry, 1000)
+                .unwrap();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        assert_eq!(committed_usage_counter(&store, miss), 5);
+
+        // queued writes are committed when the store is dropped
+        store.set_write_batch_window_ms(60_000).unwrap();
+        store
+            .merkle_cache_get(RegistryKind::AccountRegistry, 1000)
+            .unwrap();
+        drop(store);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+        assert_eq!(committed_usage_counter(&store, miss), 6);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_durability_critical_writes_flush_queued_writes() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        // the window is applied to the vault once it is opened
+        store.set_write_batch_window_ms(60_000).unwrap();
+        store.init(42, 1000).expect("init storage");
+        let miss = UsageCounter::MerkleCacheMiss;
+
+        store
+            .merkle_cache_get(RegistryKind::AccountRegistry, 1000)
+            .unwrap();
+        assert_eq!(committed_usage_counter(&store, miss), 0);
+        store
+            .replay_guard_set(TEST_ENVIRONMENT, CoreFieldElement::from(1u64), 1000)
+            .unwrap();
+        assert_eq!(committed_usage_counter(&store, miss), 1);
+
+        store
+            .merkle_cache_get(RegistryKind::AccountRegistry, 1000)
+            .unwrap();
+        assert!(!store
+            .begin_replay_guard(
+                TEST_ENVIRONMENT,
+                CoreFieldElement::from(2u64),
+                "request",
+                1000
+            )
+            .unwrap());
+        assert_eq!(committed_usage_counter(&store, miss), 2);
+
+        store
+            .merkle_cache_get(RegistryKind::AccountRegistry, 1000)
+            .unwrap();
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 2000, None, 1000)
+            .unwrap();
+        assert_eq!(committed_usage_counter(&store, miss), 3);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_closed_store_fails_with_closed() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 2000, None, 1000)
+            .expect("store credential");
+
+        store.close().expect("close");
+        store.close().expect("closing twice is a no-op");
+        assert!(matches!(
+            store.list_credentials(None, 1000),
+            Err(StorageError::Closed)
+        ));
+        assert!(matches!(store.init(42, 1000), Err(StorageError::Closed)));
+        assert!(matches!(store.storage_paths(), Err(StorageError::Closed)));
+        assert!(matches!(store.destroy_storage(), Err(StorageError::Closed)));
+
+        let reopened = CredentialStore::from_provider(&provider).expect("reopen");
+        reopened.init(42, 1000).expect("init reopened storage");
+        assert_eq!(reopened.list_credentials(None, 1000).unwrap().len(), 1);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_close_hands_storage_over_to_next_instance() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let vault_wal = provider
+            .paths()
+            .vault_db_path()
+            .with_extension("sqlite-wal");
+        let first = CredentialStore::from_provider(&provider).expect("create store");
+        first.set_write_batch_window_ms(60_000).unwrap();
+        first.init(42, 1000).expect("init storage");
+        first
+            .merkle_cache_get(RegistryKind::AccountRegistry, 1000)
+            .unwrap();
+
+        // stands in for another process opening the same storage
+        let second = CredentialStore::from_provider(&provider).expect("create store");
+        second.init(42, 1000).expect("init storage");
+        let miss = UsageCounter::MerkleCacheMiss;
+        assert_eq!(committed_usage_counter(&second, miss), 0);
+
+        first.close().expect("close");
+        assert_eq!(committed_usage_counter(&second, miss), 1);
+        second.close().expect("close");
+        // the last connection folded the WAL into the database file
+        assert_eq!(std::fs::metadata(&vault_wal).map_or(0, |m| m.len()), 0);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_delete_credentials_reports_missing_ids() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+
+        let blinding_factor = FieldElement::from(42u64);
+        let ids = (0..15u64)
+            .map(|offset| {
+                let cred: Credential = CoreCredential::new()
+                    .issuer_schema_id(100 + offset)
+                    .genesis_issued_at(1000)
+                    .into();
+                store
+                    .store_credential(&cred, &blinding_factor, 2000, None, 1000)
+                    .expect("store credential")
+            })
+            .collect::<Vec<_>>();
+        let (already_deleted, active) = ids.split_at(5);
+        for &id in already_deleted {
+            store.delete_credential(id).expect("delete credential");
+        }
+        let never_stored = (0..5u64).map(|offset| 10_000 + offset);
+
+        let mut request = ids.clone();
+        request.extend(never_stored);
+        request.push(active[0]);
+        let report = store.delete_credentials(request).expect("bulk delete");
+
+        assert_eq!(report.deleted_count, 10);
+        let mut expected_missing = already_deleted.to_vec();
+        expected_missing.extend(10_000..10_005);
+        assert_eq!(report.not_found_ids, expected_missing);
+        assert!(store.list_credentials(None, 1000).expect("list").is_empty());
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_invalidate_for_recovery_evicts_cached_credentials() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+
+        let blinding_factor = FieldElement::from(42u64);
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        let stale_id = store
+            .store_credential(&cred, &blinding_factor, 2000, None, 1000)
+            .expect("store credential");
+        assert!(store.get_credential(100, 1000).expect("get").is_some());
+
+        assert_eq!(
+            store.invalidate_for_recovery(1, 1100).expect("invalidate"),
+            vec![stale_id]
+        );
+        assert!(store.get_credential(100, 1100).expect("get").is_none());
+
+        let fresh_id = store
+            .store_credential(&cred, &blinding_factor, 2000, None, 1200)
+            .expect("store credential");
+        assert!(store.get_credential(100, 1200).expect("get").is_some());
+        let statuses = store
+            .list_credentials(None, 1200)
+            .expect("list")
+            .into_iter()
+            .map(|record| (record.credential_id, record.status))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            vec![
+                (fresh_id, CredentialStatus::Active),
+                (stale_id, CredentialStatus::Invalidated),
+            ]
+        );
+
+        cleanup_test_storage(&root);
+    }
+
+    struct TestQuotaProvider {
+        estimate: Mutex<StorageEstimate>,
+        grant_persistence: bool,
+        persistence_requests: AtomicU32,
+    }
+
+    impl TestQuotaProvider {
+        fn new(quota: u64, usage: u64, grant_persistence: bool) -> Self {
+            Self {
+                estimate: Mutex::new(StorageEstimate {
+                    quota,
+                    usage,
+                    persistent: false,
+                }),
+                grant_persistence,
+                persistence_requests: AtomicU32::new(0),
+            }
+        }
+    }
+
+    impl StorageQuotaProvider for TestQuotaProvider {
+        fn storage_estimate(&self) -> StorageResult<StorageEstimate> {
+            Ok(*self.estimate.lock().unwrap())
+        }
+
+        fn request_persistence(&self) -> StorageResult<bool> {
+            self.persistence_requests.fetch_add(1, Ordering::SeqCst);
+            let mut estimate = self.estimate.lock().unwrap();
+            estimate.persistent = self.grant_persistence;
+            Ok(estimate.persistent)
+        }
+    }
+
+    #[test]
+    fn test_init_refused_below_quota_floor() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        let quota = Arc::new(TestQuotaProvider::new(1_000_000, 999_000, true));
+        let policy = StorageQuotaPolicy {
+            min_available_bytes: 10_000,
+            ..StorageQuotaPolicy::default()
+        };
+        store
+            .set_storage_quota_provider(quota.clone(), policy)
+            .expect("set quota provider");
+
+        let err = store.init(42, 1000).expect_err("init must be refused");
+        assert!(
+            matches!(
+                err,
+                StorageError::InsufficientStorageQuota {
+                    available: 1_000,
+                    required: 10_000,
+                }
+            ),
+            "{err}"
+        );
+        // persistence is only requested when the policy asks for it
+        assert_eq!(quota.persistence_requests.load(Ordering::SeqCst), 0);
+        assert!(matches!(
+            store.list_credentials(None, 1000),
+            Err(StorageError::NotInitialized)
+        ));
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_denied_persistence_and_large_write_quota_check() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        let quota = Arc::new(TestQuotaProvider::new(1_000_000, 0, false));
+        let policy = StorageQuotaPolicy {
+            min_available_bytes: 10_000,
+            large_write_bytes: 4_000,
+            request_persistence: true,
+        };
+        store
+            .set_storage_quota_provider(quota.clone(), policy)
+            .expect("set quota provider");
+
+        // a denied request does not block initialization while quota remains
+        store.init(42, 1000).expect("init storage");
+        assert_eq!(quota.persistence_requests.load(Ordering::SeqCst), 1);
+
+        quota.estimate.lock().unwrap().usage = 1_000_000 - 10_500;
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        let blinding_factor = FieldElement::from(7u64);
+        let err = store
+            .store_credential(&cred, &blinding_factor, 2000, Some(vec![0; 8_000]), 1000)
+            .expect_err("large write must be refused");
+        assert!(
+            matches!(err, StorageError::InsufficientStorageQuota { .. }),
+            "{err}"
+        );
+        assert!(store.list_credentials(None, 1000).expect("list").is_empty());
+
+        // small writes are not checked
+        store
+            .store_credential(&cred, &blinding_factor, 2000, None, 1000)
+            .expect("store credential");
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_danger_delete_all_credentials_empty() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let paths = provider.paths().as_ref().clone();
+        let keystore = provider.keystore();
+        let blob_store = provider.blob_store();
+
+        let mut inner = CredentialStoreInner::new(paths, keystore, blob_store)
+            .expect("create inner");
+        inner.init(42, 1000).expect("init storage");
+
+        let deleted = inner
+            .danger_delete_all_credentials()
+            .expect("delete all on empty");
+        assert_eq!(deleted, 0);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_danger_delete_all_credentials_then_store() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let paths = provider.paths().as_ref().clone();
+        let keystore = provider.keystore();
+        let blob_store = provider.blob_store();
+
+        let mut inner = CredentialStoreInner::new(paths, keystore, blob_store)
+            .expect("create inner");
+        inner.init(42, 1000).expect("init storage");
+
+        let blinding_factor = FieldElement::from(42u64);
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100u64)
+            .genesis_issued_at(1000)
+            .into();
+        inner
+            .store_credential(&cred, &blinding_factor, 2000, None, 1000)
+            .expect("store credential");
+
+        inner.danger_delete_all_credentials().expect("delete all");
+
+        let new_cred: Credential = CoreCredential::new()
+            .issuer_schema_id(200u64)
+            .genesis_issued_at(1000)
+            .into();
+        inner
+            .store_credential(&new_cred, &blinding_factor, 2000, None, 1000)
+            .expect("store after delete");
+
+        let list = inner.list_credentials(None, 1000).expect("list");
+        assert_eq!(list.len(), 1);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_export_vault_for_backup_returns_bytes() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init store");
+
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 9999, None, 1000)
+            .expect("store credential");
+
+        let bytes = store.export_vault_for_backup().expect("export vault");
+        assert!(
+            bytes.len() >= 16,
+            "exported vault too small to be a valid SQLite database"
+        );
+
+        // Verify the bytes are a valid SQLite database (magic header).
+        assert_eq!(
+            &bytes[..16],
+            b"SQLite format 3\0",
+            "exported bytes should be a valid SQLite database"
+        );
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_disclosure_memos_stay_out_of_backups_unless_enabled() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init store");
+        store
+            .set_consent_ledger_enabled(true)
+            .expect("enable consent ledger");
+
+        // backups export the memos unexpired by the system clock
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("system time")
+            .as_secs();
+        let memo = b"order #1234 at the pharmacy";
+        store
+            .finalize_proof(
+                TEST_ENVIRONMENT,
+                CoreFieldElement::from(7u64),
+                NewConsent {
+                    request_id: "request-1",
+                    rp_id_hash: &[0u8; 32],
+                    disclosed_schema_ids: &[100],
+                    consent_text_hash: None,
+                },
+                Some(memo),
+                now,
+            )
+            .expect("finalize proof");
+
+        let disclosures = store.list_disclosures(true, now).expect("list");
+        assert_eq!(disclosures.len(), 1);
+        assert_eq!(disclosures[0].disclosed_at, now);
+        assert_eq!(disclosures[0].memo.as_deref(), Some(&memo[..]));
+        let disclosures = store.list_disclosures(false, now).expect("list");
+        assert_eq!(disclosures[0].memo, None);
+
+        let in_backup = |bytes: &[u8]| bytes.windows(memo.len()).any(|w| w == memo);
+        let bytes = store.export_vault_for_backup().expect("export vault");
+        assert!(!in_backup(&bytes));
+
+        // opting in exports the memo, which an import leaves behind
+        store
+            .set_disclosure_memos_in_backups(true)
+            .expect("include memos in backups");
+        let bytes = store.export_vault_for_backup().expect("export vault");
+        assert!(in_backup(&bytes));
+
+        let dst_root = temp_root_path();
+        let dst_provider = InMemoryStorageProvider::new(&dst_root);
+        let dst_store =
+            CredentialStore::from_provider(&dst_provider).expect("create store");
+        dst_store.init(42, 1000).expect("init store");
+        dst_store
+            .import_vault_from_backup(&bytes)
+            .expect("import vault");
+        assert_eq!(dst_store.list_consents(10, None).expect("list").len(), 1);
+        assert!(dst_store
+            .list_disclosures(true, now)
+            .expect("list")
+            .is_empty());
+
+        cleanup_test_storage(&root);
+        cleanup_test_storage(&dst_root);
+    }
+
+    #[test]
+    fn test_export_vault_for_backup_roundtrip() {
+        use world_id_core::Credential as CoreCredential;
+
+        let src_root = temp_root_path();
+        let src_provider = InMemoryStorageProvider::new(&src_root);
+        let src_store =
+            CredentialStore::from_provider(&src_provider).expect("create store");
+        src_store.init(42, 1000).expect("init store");
+
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        src_store
+            .store_credential(&cred, &FieldElement::from(7u64), 9999, None, 1000)
+            .expect("store credential");
+
+        let bytes = src_store.export_vault_for_backup().expect("export vault");
+        assert!(!bytes.is_empty(), "exported bytes should not be empty");
+
+        // Import the raw bytes into a fresh store via the public API.
+        let dst_root = temp_root_path();
+        let dst_provider = InMemoryStorageProvider::new(&dst_root);
+        let dst_store =
+            CredentialStore::from_provider(&dst_provider).expect("create dst store");
+        dst_store.init(42, 1000).expect("init dst store");
+
+        dst_store
+            .import_vault_from_backup(&bytes)
+            .expect("import vault from bytes");
+
+        let (imported_cred, imported_bf) = dst_store
+            .get_credential(100, 1000)
+            .expect("get credential")
+            .expect("credential should exist");
+        assert_eq!(imported_cred.issuer_schema_id(), 100);
+        assert_eq!(imported_bf.to_bytes(), FieldElement::from(7u64).to_bytes());
+
+        cleanup_test_storage(&src_root);
+        cleanup_test_storage(&dst_root);
+    }
+
+    #[test]
+    fn test_export_cleans_up_temp_file() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init store");
+
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 9999, None, 1000)
+            .expect("store credential");
+
+        let _bytes = store.export_vault_for_backup().expect("export vault");
+
+        // After export, no temp files should remain in the worldid directory.
+        let worldid_dir = store.storage_paths().unwrap().worldid_dir().to_path_buf();
+        let stale: Vec<_> = std::fs::read_dir(&worldid_dir)
+            .unwrap()
+            .flatten()
+            .filter(|e| {
+                e.file_name()
+                    .to_str()
+                    .is_some_and(|n| n.starts_with(VAULT_BACKUP_TEMP_PREFIX))
+            })
+            .collect();
+        assert!(
+            stale.is_empty(),
+            "temp backup files should be cleaned up after export, found: {stale:?}"
+        );
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_stale_backup_files_cleaned_on_next_operation() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init store");
+
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 9999, None, 1000)
+            .expect("store credential");
+
+        // Simulate stale temp files left by a previous crash.
+        let worldid_dir = store.storage_paths().unwrap().worldid_dir().to_path_buf();
+        let stale_path =
+            worldid_dir.join(format!("{VAULT_BACKUP_TEMP_PREFIX}stale.sqlite"));
+        std::fs::write(&stale_path, b"stale plaintext data").expect("write stale file");
+        assert!(stale_path.exists(), "stale file should exist before export");
+
+        // The next backup operation should clean up the stale file.
+        let _bytes = store.export_vault_for_backup().expect("export vault");
+
+        assert!(
+            !stale_path.exists(),
+            "stale backup file should be cleaned up during export"
+        );
+
+        // Also verify no other temp files linger.
+        let remaining: Vec<_> = std::fs::read_dir(&worldid_dir)
+            .unwrap()
+            .flatten()
+            .filter(|e| {
+                e.file_name()
+                    .to_str()
+                    .is_some_and(|n| n.starts_with(VAULT_BACKUP_TEMP_PREFIX))
+            })
+            .collect();
+        assert!(
+            remaining.is_empty(),
+            "no temp backup files should remain after export, found: {remaining:?}"
+        );
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_vault_changed_listener_notified_on_store() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+
+        let count = Arc::new(AtomicU32::new(0));
+        store.set_vault_changed_listener(Arc::new(TestVaultListener(Arc::clone(
+            &count,
+        ))));
+
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 9999, None, 1000)
+            .expect("store credential");
+
+        wait_for_listener_count(&count, 1);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_vault_changed_listener_notified_on_delete() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+
+        let count = Arc::new(AtomicU32::new(0));
+        store.set_vault_changed_listener(Arc::new(TestVaultListener(Arc::clone(
+            &count,
+        ))));
+
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        let id = store
+            .store_credential(&cred, &FieldElement::from(7u64), 9999, None, 1000)
+            .expect("store credential");
+        wait_for_listener_count(&count, 1);
+
+        store.delete_credential(id).expect("delete credential");
+
+        wait_for_listener_count(&count, 2);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_vault_changed_listener_not_notified_on_failure() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+
+        let count = Arc::new(AtomicU32::new(0));
+        store.set_vault_changed_listener(Arc::new(TestVaultListener(Arc::clone(
+            &count,
+        ))));
+
+        // Deleting a non-existent credential should fail — no notification.
+        let result = store.delete_credential(999);
+        assert!(result.is_err());
+
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        assert_eq!(
+            count.load(Ordering::SeqCst),
+            0,
+            "listener should not be notified on failure"
+        );
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_no_listener_does_not_panic() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+
+        // No listener registered — mutations should still work fine.
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 9999, None, 1000)
+            .expect("store without listener should succeed");
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_destroy_storage() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 9999, None, 1000)
+            .expect("store credential");
+
+        // Destroy should succeed.
+        store.destroy_storage().expect("destroy storage");
+
+        // Database files should be removed from the storage directory.
+        let paths = StoragePaths::new(&root);
+        let remaining: Vec<_> = std::fs::read_dir(paths.worldid_dir())
+            .expect("read worldid dir")
+            .filter_map(|e| e.ok().map(|e| e.file_name()))
+            .collect();
+        // Only the lock file should remain.
+        assert!(
+            remaining.iter().all(|f| f == "lock"),
+            "expected only lock file to remain, found: {remaining:?}"
+        );
+
+        // Subsequent operations should return NotInitialized.
+        let err = store.list_credentials(None, 1000).unwrap_err();
+        assert!(
+            matches!(err, StorageError::NotInitialized),
+            "expected NotInitialized, got: {err:?}"
+        );
+
+        // Re-initialization should work.
+        store.init(42, 1000).expect("re-init storage");
+        let list = store
+            .list_credentials(None, 1000)
+            .expect("list after re-init");
+        assert!(
+            list.is_empty(),
+            "store should be empty after destroy + re-init"
+        );
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_destroy_storage_does_not_notify_vault_changed() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+
+        let count = Arc::new(AtomicU32::new(0));
+        store.set_vault_changed_listener(Arc::new(TestVaultListener(Arc::clone(
+            &count,
+        ))));
+
+        let cred: Credential = CoreCredential::new()
+            .issuer_schema_id(100)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&cred, &FieldElement::from(7u64), 9999, None, 1000)
+            .expect("store credential");
+        wait_for_listener_count(&count, 1);
+
+        store.destroy_storage().expect("destroy storage");
+
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        // Only the store should trigger a notification — destroy does not.
+        assert_eq!(
+            count.load(Ordering::SeqCst),
+            1,
+            "listener should only be notified for store, not destroy"
+        );
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_credential_reads_within_burst_hit_cache() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let paths = provider.paths().as_ref().clone();
+        let mut inner = CredentialStoreInner::new(
+            paths,
+            provider.keystore(),
+            provider.blob_store(),
+        )
+        .expect("create inner");
+        inner.init(42, 1000).expect("init storage");
+
+        let credential: Credential = CoreCredential::new()
+            .issuer_schema_id(123)
+            .genesis_issued_at(1000)
+            .into();
+        inner
+            .store_credential(&credential, &FieldElement::from(7u64), 2000, None, 1000)
+            .expect("store credential");
+
+        for now in [1000, 1001, 1002] {
+            let (credential, _) = inner
+                .get_credential(123, now)
+                .expect("get credential")
+                .expect("credential should exist");
+            assert_eq!(credential.issuer_schema_id(), 123);
+        }
+        assert_eq!(inner.vault_credential_reads, 1);
+
+        // any write invalidates the cache
+        let other: Credential = CoreCredential::new()
+            .issuer_schema_id(456)
+            .genesis_issued_at(1000)
+            .into();
+        inner
+            .store_credential(&other, &FieldElement::from(8u64), 2000, None, 1003)
+            .expect("store credential");
+        assert!(inner.credential_cache.is_empty());
+        inner.get_credential(123, 1004).expect("get credential");
+        assert_eq!(inner.vault_credential_reads, 2);
+
+        // an expired cache entry falls back to the vault
+        assert!(inner
+            .get_credential(123, 2000)
+            .expect("get credential")
+            .is_none());
+        assert_eq!(inner.vault_credential_reads, 3);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_credential_cache_sees_writes_from_other_connections() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let paths = provider.paths().as_ref().clone();
+        let mut inner = CredentialStoreInner::new(
+            paths.clone(),
+            provider.keystore(),
+            provider.blob_store(),
+        )
+        .expect("create inner");
+        inner.init(42, 1000).expect("init storage");
+
+        let credential: Credential = CoreCredential::new()
+            .issuer_schema_id(123)
+            .genesis_issued_at(1000)
+            .into();
+        inner
+            .store_credential(&credential, &FieldElement::from(7u64), 2000, None, 1000)
+            .expect("store credential");
+        inner.get_credential(123, 1000).expect("get credential");
+        assert_eq!(inner.credential_cache.len(), 1);
+
+        // e.g. another process holding the same vault
+        let other = CredentialVault::new(
+            &paths.vault_db_path(),
+            inner.state().expect("state").keys.intermediate_key(),
+        )
+        .expect("open vault");
+        other
+            .danger_delete_all_credentials()
+            .expect("delete credentials");
+
+        assert!(inner
+            .get_credential(123, 1001)
+            .expect("get credential")
+            .is_none());
+        assert_eq!(inner.vault_credential_reads, 2);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_clear_sensitive_caches() {
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+
+        let credential: Credential = CoreCredential::new()
+            .issuer_schema_id(123)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&credential, &FieldElement::from(7u64), 2000, None, 1000)
+            .expect("store credential");
+        store.get_credential(123, 1000).expect("get credential");
+        assert_eq!(store.lock_inner().unwrap().credential_cache.len(), 1);
+
+        store.clear_sensitive_caches().expect("clear caches");
+        assert!(store.lock_inner().unwrap().credential_cache.is_empty());
+
+        store
+            .set_credential_cache_capacity(0)
+            .expect("set capacity");
+        store.get_credential(123, 1000).expect("get credential");
+        store.get_credential(123, 1000).expect("get credential");
+        let inner = store.lock_inner().unwrap();
+        assert!(inner.credential_cache.is_empty());
+        assert_eq!(inner.vault_credential_reads, 3);
+        drop(inner);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_memory_pressure_releases_caches_of_live_stores() {
+        use crate::{on_memory_pressure, MemoryPressureLevel};
+        use world_id_core::Credential as CoreCredential;
+
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init storage");
+        let other_root = temp_root_path();
+        let other_provider = InMemoryStorageProvider::new(&other_root);
+        let uninitialized =
+            CredentialStore::from_provider(&other_provider).expect("create store");
+        // dropped stores are pruned instead of released
+        let dropped =
+            CredentialStore::from_provider(&other_provider).expect("create store");
+        drop(dropped);
+
+        let credential: Credential = CoreCredential::new()
+            .issuer_schema_id(123)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&credential, &FieldElement::from(7u64), 2000, None, 1000)
+            .expect("store credential");
+        store.get_credential(123, 1000).expect("get credential");
+        assert_eq!(store.lock_inner().unwrap().credential_cache.len(), 1);
+
+        on_memory_pressure(MemoryPressureLevel::Moderate);
+        assert!(store.lock_inner().unwrap().credential_cache.is_empty());
+        assert_eq!(with_live_stores(|live| live.len()), 2);
+
+        // the next reads and writes rebuild what was released
+        let (loaded, _) = store
+            .get_credential(123, 1001)
+            .expect("get credential")
+            .expect("credential should exist");
+        assert_eq!(loaded.issuer_schema_id(), 123);
+        assert_eq!(store.lock_inner().unwrap().vault_credential_reads, 2);
+        let other: Credential = CoreCredential::new()
+            .issuer_schema_id(456)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(&other, &FieldElement::from(8u64), 2000, None, 1002)
+            .expect("store credential after release");
+        assert_eq!(store.list_credentials(None, 1002).expect("list").len(), 2);
+
+        // a store busy with another operation is skipped, not waited for
+        let busy = store.lock_inner().unwrap();
+        on_memory_pressure(MemoryPressureLevel::Critical);
+        drop(busy);
+        drop(uninitialized);
+
+        cleanup_test_storage(&root);
+        cleanup_test_storage(&other_root);
+    }
+}
@@ -0,0 +1,372 @@
+//! Replay guard of the credential store.
+//!
+//! Wraps the used-nullifier cache so each proof is checked against, and then
+//! recorded in, the replay guard of its environment, and counts proof attempts,
+//! replays and successes in the store's metrics.
+
+use sha2::{Digest, Sha256};
+use world_id_core::FieldElement as CoreFieldElement;
+
+use super::{CredentialStore, CredentialStoreInner};
+use crate::storage::error::{StorageError, StorageResult};
+use crate::storage::metrics::CredentialStoreMetrics;
+use crate::storage::NewConsent;
+
+/// Digest of a proof request ID as recorded in the replay guard.
+fn request_id_digest(request_id: &str) -> [u8; 32] {
+    Sha256::digest(request_id.as_bytes()).into()
+}
+
+impl CredentialStore {
+    /// Checks whether a replay guard entry exists for the given nullifier in
+    /// `environment`.
+    ///
+    /// # Returns
+    /// - bool: true if a replay guard entry exists (hence signalling a nullifier replay), false otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query to the cache unexpectedly fails.
+    pub fn is_nullifier_replay(
+        &self,
+        environment: [u8; 32],
+        nullifier: CoreFieldElement,
+        now: u64,
+    ) -> StorageResult<bool> {
+        self.lock_inner()?
+            .is_nullifier_replay(environment, nullifier, now)
+    }
+
+    /// Checks the replay guard of `environment` before generating a proof for
+    /// the request with ID `request_id`.
+    ///
+    /// # Returns
+    /// - bool: true if the nullifier was already disclosed for this request (hence signalling a nullifier replay), false otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::NullifierConflict`] if the nullifier was
+    /// disclosed for a different request, or an error if the query to the
+    /// cache unexpectedly fails.
+    pub(crate) fn begin_replay_guard(
+        &self,
+        environment: [u8; 32],
+        nullifier: CoreFieldElement,
+        request_id: &str,
+        now: u64,
+    ) -> StorageResult<bool> {
+        CredentialStoreMetrics::increment(&self.metrics.proof_attempts);
+        let result = self.lock_inner()?.begin_replay_guard(
+            environment,
+            nullifier,
+            request_id,
+            now,
+        );
+        if matches!(
+            result,
+            Ok(true) | Err(StorageError::NullifierConflict { .. })
+        ) {
+            CredentialStoreMetrics::increment(&self.metrics.nullifier_replays);
+        }
+        result
+    }
+
+    /// After a proof has been successfully generated, creates a replay guard entry
+    /// in `environment` to avoid future replays of the same nullifier.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query to the cache unexpectedly fails.
+    pub fn replay_guard_set(
+        &self,
+        environment: [u8; 32],
+        nullifier: CoreFieldElement,
+        now: u64,
+    ) -> StorageResult<()> {
+        self.lock_inner()?
+            .replay_guard_set(environment, nullifier, now)
+    }
+
+    /// Sets the replay guard of `environment` for a generated proof, bumps the use count of the
+    /// disclosed credentials and, if the consent ledger is enabled, records
+    /// `consent`. The host's `memo` is kept with the replay guard entry, see
+    /// [`list_disclosures`](Self::list_disclosures).
+    ///
+    /// The vault writes are only committed once the replay guard is set, so a
+    /// failed replay-guard write leaves no trace of the proof behind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either write fails.
+    pub(crate) fn finalize_proof(
+        &self,
+        environment: [u8; 32],
+        nullifier: CoreFieldElement,
+        consent: NewConsent<'_>,
+        memo: Option<&[u8]>,
+        now: u64,
+    ) -> StorageResult<()> {
+        self.lock_inner()?.finalize_proof(
+            environment,
+            nullifier,
+            consent,
+            memo,
+            now,
+        )?;
+        CredentialStoreMetrics::increment(&self.metrics.proof_successes);
+        Ok(())
+    }
+}
+
+impl CredentialStoreInner {
+    /// Checks whether a replay guard entry exists for the given nullifier.
+    ///
+    /// # Returns
+    /// - bool: true if a replay guard entry exists (hence signalling a nullifier replay), false otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query to the cache unexpectedly fails.
+    fn is_nullifier_replay(
+        &self,
+        environment: [u8; 32],
+        nullifier: CoreFieldElement,
+        now: u64,
+    ) -> StorageResult<bool> {
+        let nullifier = nullifier.to_be_bytes();
+        let state = self.state()?;
+        state.cache.is_nullifier_replay(environment, nullifier, now)
+    }
+
+    fn begin_replay_guard(
+        &self,
+        environment: [u8; 32],
+        nullifier: CoreFieldElement,
+        request_id: &str,
+        now: u64,
+    ) -> StorageResult<bool> {
+        let nullifier = nullifier.to_be_bytes();
+        let state = self.state()?;
+        state.vault.flush_pending_writes()?;
+        state.cache.begin_replay_guard(
+            environment,
+            nullifier,
+            request_id_digest(request_id),
+            now,
+        )
+    }
+
+    /// After a proof has been successfully generated, creates a replay guard entry
+    /// locally to avoid future replays of the same nullifier.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query to the cache unexpectedly fails.
+    fn replay_guard_set(
+        &mut self,
+        environment: [u8; 32],
+        nullifier: CoreFieldElement,
+        now: u64,
+    ) -> StorageResult<()> {
+        let nullifier = nullifier.to_be_bytes();
+        let state = self.state_mut()?;
+        state.vault.flush_pending_writes()?;
+        state
+            .cache
+            .replay_guard_set(environment, nullifier, None, None, now)
+    }
+
+    fn finalize_proof(
+        &self,
+        environment: [u8; 32],
+        nullifier: CoreFieldElement,
+        consent: NewConsent<'_>,
+        memo: Option<&[u8]>,
+        now: u64,
+    ) -> StorageResult<()> {
+        let nullifier = nullifier.to_be_bytes();
+        let request_id = Some(request_id_digest(consent.request_id));
+        let state = self.state()?;
+        state.vault.flush_pending_writes()?;
+        state
+            .vault
+            .record_proof_with(consent, self.consent_ledger_enabled, now, || {
+                state.cache.replay_guard_set(
+                    environment,
+                    nullifier,
+                    request_id,
+                    memo,
+                    now,
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider, TEST_ENVIRONMENT,
+    };
+    use crate::storage::traits::StorageProvider;
+
+    #[test]
+    fn test_replay_guard_field_element_serialization() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let paths = provider.paths().as_ref().clone();
+        let keystore = provider.keystore();
+        let blob_store = provider.blob_store();
+
+        let mut inner = CredentialStoreInner::new(paths, keystore, blob_store)
+            .expect("create inner");
+        inner.init(42, 1000).expect("init storage");
+
+        // Create a FieldElement from a known value
+        let nullifier = CoreFieldElement::from(123_456_789u64);
+
+        // Set a replay guard
+        inner
+            .replay_guard_set(TEST_ENVIRONMENT, nullifier, 1000)
+            .expect("set replay guard");
+
+        // The same FieldElement should be properly serialized and found after the grace period
+        let exists_after_grace = inner
+            .is_nullifier_replay(TEST_ENVIRONMENT, nullifier, 1601)
+            .expect("check replay guard");
+        assert!(
+            exists_after_grace,
+            "Replay guard should exist after grace period (10 minutes)"
+        );
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_replay_guard_grace_period() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let paths = provider.paths().as_ref().clone();
+        let keystore = provider.keystore();
+        let blob_store = provider.blob_store();
+
+        let mut inner = CredentialStoreInner::new(paths, keystore, blob_store)
+            .expect("create inner");
+        inner.init(42, 1000).expect("init storage");
+
+        let nullifier = CoreFieldElement::from(999u64);
+        let set_time = 1000u64;
+
+        // Set a replay guard at time 1000
+        inner
+            .replay_guard_set(TEST_ENVIRONMENT, nullifier, set_time)
+            .expect("set replay guard");
+
+        // Within grace period (< 10 minutes): should return false
+        // Grace period is 600 seconds (10 minutes)
+        let check_time_1min = set_time + 60; // 1 minute later
+        let exists_1min = inner
+            .is_nullifier_replay(TEST_ENVIRONMENT, nullifier, check_time_1min)
+            .expect("check at 1 minute");
+        assert!(
+            !exists_1min,
+            "Replay guard should NOT be enforced during grace period (1 minute)"
+        );
+
+        let check_time_ten_min = set_time + 601; // 10 minutes later
+        let exists_ten_min = inner
+            .is_nullifier_replay(TEST_ENVIRONMENT, nullifier, check_time_ten_min)
+            .expect("check at 9 minutes");
+        assert!(
+            exists_ten_min,
+            "Replay guard should be enforced during grace period (10 minutes)"
+        );
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_replay_guard_expiration() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let paths = provider.paths().as_ref().clone();
+        let keystore = provider.keystore();
+        let blob_store = provider.blob_store();
+
+        let mut inner = CredentialStoreInner::new(paths, keystore, blob_store)
+            .expect("create inner");
+        inner.init(42, 1000).expect("init storage");
+
+        let nullifier = CoreFieldElement::from(555u64);
+        let set_time = 3000u64;
+
+        // Set a replay guard at time 3000
+        inner
+            .replay_guard_set(TEST_ENVIRONMENT, nullifier, set_time)
+            .expect("set replay guard");
+
+        // After expiration (> 1 year): should return false
+        let one_year_seconds = 365 * 24 * 60 * 60; // 31,536,000 seconds
+
+        // Just before expiration: should still exist
+        let check_time_before_exp = set_time + one_year_seconds - 1;
+        let exists_before_exp = inner
+            .is_nullifier_replay(TEST_ENVIRONMENT, nullifier, check_time_before_exp)
+            .expect("check before expiration");
+        assert!(
+            exists_before_exp,
+            "Replay guard SHOULD exist just before expiration"
+        );
+
+        // After expiration: should not exist
+        let check_time_at_exp = set_time + one_year_seconds + 1;
+        let exists_at_exp = inner
+            .is_nullifier_replay(TEST_ENVIRONMENT, nullifier, check_time_at_exp)
+            .expect("check at expiration");
+        assert!(
+            !exists_at_exp,
+            "Replay guard should NOT exist at expiration (1 year)"
+        );
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_replay_guard_idempotency() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let paths = provider.paths().as_ref().clone();
+        let keystore = provider.keystore();
+        let blob_store = provider.blob_store();
+
+        let mut inner = CredentialStoreInner::new(paths, keystore, blob_store).unwrap();
+        inner.init(42, 1000).expect("init storage");
+
+        let nullifier = CoreFieldElement::from(12345u64);
+        let first_set_time = 1000u64;
+
+        // Set a replay guard at time 1000
+        inner
+            .replay_guard_set(TEST_ENVIRONMENT, nullifier, first_set_time)
+            .unwrap();
+
+        // Try to set the same nullifier again at time 1060 (5 minutes later)
+        let second_set_time = first_set_time + 300;
+        inner
+            .replay_guard_set(TEST_ENVIRONMENT, nullifier, second_set_time)
+            .expect("second set should be idempotent");
+
+        // Check at time 1601 (10+ minutes from first set)
+        // This is past the grace period from the FIRST insertion
+        let check_time_after_grace = first_set_time + 601;
+        let exists_after_grace = inner
+            .is_nullifier_replay(TEST_ENVIRONMENT, nullifier, check_time_after_grace)
+            .expect("check after grace");
+        assert!(
+            exists_after_grace,
+            "Replay guard SHOULD be enforced - past grace period from FIRST insertion"
+        );
+
+        cleanup_test_storage(&root);
+    }
+}
@@ -0,0 +1,231 @@
+//! Consent ledger: which disclosures the user agreed to, and when.
+//!
+//! Kept in the vault rather than the cache so that cache rebuilds never drop
+//! it. Entries hold no nullifier material.
+
+use walletkit_db::{params, StepResult, Transaction, Value};
+
+use super::tenant::Scope;
+use super::{map_db_err, to_i64, to_u64, AccountId, CredentialVault};
+use crate::storage::error::{StorageError, StorageResult};
+use crate::storage::types::ConsentRecord;
+
+/// A consent ledger entry to record.
+#[derive(Debug, Clone, Copy)]
+pub struct NewConsent<'a> {
+    /// Identifier of the proof request the user consented to.
+    pub request_id: &'a str,
+    /// Hash of the requesting RP's identifier.
+    pub rp_id_hash: &'a [u8],
+    /// Issuer schemas whose credentials are disclosed.
+    pub disclosed_schema_ids: &'a [u64],
+    /// Hash of the consent text shown to the user, if any.
+    pub consent_text_hash: Option<&'a [u8]>,
+}
+
+impl CredentialVault {
+    /// Appends an entry to the consent ledger.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the insert fails.
+    pub fn record_consent(
+        &self,
+        consent: NewConsent<'_>,
+        now: u64,
+    ) -> StorageResult<u64> {
+        self.record_consent_with(consent, now, || Ok(()))
+    }
+
+    /// Appends an entry to the consent ledger, committing it only if
+    /// `before_commit` succeeds.
+    ///
+    /// Lets callers tie the entry to a write in another database: if that
+    /// write fails, the entry is rolled back.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the insert fails or `before_commit` fails, in which
+    /// case nothing is recorded.
+    pub fn record_consent_with(
+        &self,
+        consent: NewConsent<'_>,
+        now: u64,
+        before_commit: impl FnOnce() -> StorageResult<()>,
+    ) -> StorageResult<u64> {
+        self.record_consent_scoped(None, consent, now, before_commit)
+    }
+
+    pub(super) fn record_consent_scoped(
+        &self,
+        account: Option<&AccountId>,
+        consent: NewConsent<'_>,
+        now: u64,
+        before_commit: impl FnOnce() -> StorageResult<()>,
+    ) -> StorageResult<u64> {
+        let scope = self.scope(account)?;
+        let now_i64 = to_i64(now, "now")?;
+        let conn = self.vault.connection();
+        let tx = conn.transaction().map_err(|err| map_db_err(&err))?;
+
+        let consent_id = insert_consent(&tx, scope, consent, now_i64)?;
+
+        before_commit()?;
+        tx.commit().map_err(|err| map_db_err(&err))?;
+        to_u64(consent_id, "consent_id")
+    }
+
+    /// Lists consent ledger entries, most recent first.
+    ///
+    /// Returns at most `limit` entries recorded strictly before `before`, if
+    /// set, so pages can be fetched by passing the last `recorded_at` seen.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails or an entry is malformed.
+    pub fn list_consents(
+        &self,
+        limit: u32,
+        before: Option<u64>,
+    ) -> StorageResult<Vec<ConsentRecord>> {
+        self.list_consents_scoped(None, limit, before)
+    }
+
+    pub(super) fn list_consents_scoped(
+        &self,
+        account: Option<&AccountId>,
+        limit: u32,
+        before: Option<u64>,
+    ) -> StorageResult<Vec<ConsentRecord>> {
+        let scope = self.scope(account)?;
+        let before = before
+            .map(|value| to_i64(value, "before"))
+            .transpose()?
+            .map_or(Value::Null, Value::Integer);
+        let sql = format!(
+            "SELECT
+                consent_id,
+                request_id,
+                rp_id_hash,
+                disclosed_schema_ids,
+                consent_text_hash,
+                recorded_at
+             FROM consent_records
+             WHERE (?1 IS NULL OR recorded_at < ?1){account_filter}
+             ORDER BY recorded_at DESC, consent_id DESC
+             LIMIT ?2",
+            account_filter = scope.filter("", 3),
+        );
+
+        let mut stmt = self
+            .vault
+            .connection()
+            .prepare(&sql)
+            .map_err(|err| map_db_err(&err))?;
+        stmt.bind_values(&scope.bind(&[before, Value::Integer(i64::from(limit))]))
+            .map_err(|err| map_db_err(&err))?;
+        let mut records = Vec::new();
+        while let StepResult::Row(row) = stmt.step().map_err(|err| map_db_err(&err))? {
+            let consent_text_hash = row.column_blob(4);
+            records.push(ConsentRecord {
+                consent_id: to_u64(row.column_i64(0), "consent_id")?,
+                request_id: row.column_text(1),
+                rp_id_hash: row.column_blob(2),
+                disclosed_schema_ids: decode_schema_ids(&row.column_blob(3))?,
+                consent_text_hash: (!consent_text_hash.is_empty())
+                    .then_some(consent_text_hash),
+                recorded_at: to_u64(row.column_i64(5), "recorded_at")?,
+            });
+        }
+        Ok(records)
+    }
+
+    /// Deletes consent ledger entries recorded before `older_than`.
+    ///
+    /// Returns the number of deleted entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delete fails.
+    pub fn purge_consents(&self, older_than: u64) -> StorageResult<u64> {
+        self.purge_consents_scoped(None, older_than)
+    }
+
+    pub(super) fn purge_consents_scoped(
+        &self,
+        account: Option<&AccountId>,
+        older_than: u64,
+    ) -> StorageResult<u64> {
+        let scope = self.scope(account)?;
+        let older_than = to_i64(older_than, "older_than")?;
+        let deleted = self
+            .vault
+            .connection()
+            .execute(
+                &format!(
+                    "DELETE FROM consent_records WHERE recorded_at < ?1{account_filter}",
+                    account_filter = scope.filter("", 2),
+                ),
+                &scope.bind(params![older_than]),
+            )
+            .map_err(|err| map_db_err(&err))?;
+        Ok(deleted as u64)
+    }
+}
+
+/// Inserts `consent` into the ledger within `tx`, returning its ID.
+pub(super) fn insert_consent(
+    tx: &Transaction<'_>,
+    scope: Scope<'_>,
+    consent: NewConsent<'_>,
+    now: i64,
+) -> StorageResult<i64> {
+    let consent_text_hash = consent
+        .consent_text_hash
+        .map_or(Value::Null, |hash| Value::Blob(hash.to_vec()));
+    tx.query_row(
+        &format!(
+            "INSERT INTO consent_records (
+                request_id,
+                rp_id_hash,
+                disclosed_schema_ids,
+                consent_text_hash,
+                recorded_at{col}
+            ) VALUES (?1, ?2, ?3, ?4, ?5{val})
+            RETURNING consent_id",
+            col = scope.column(),
+            val = scope.placeholder(6),
+        ),
+        &scope.bind(params![
+            consent.request_id,
+            consent.rp_id_hash,
+            encode_schema_ids(consent.disclosed_schema_ids),
+            consent_text_hash,
+            now,
+        ]),
+        |stmt| Ok(stmt.column_i64(0)),
+    )
+    .map_err(|err| map_db_err(&err))
+}
+
+/// Encodes schema IDs as concatenated big-endian `u64`s.
+fn encode_schema_ids(ids: &[u64]) -> Vec<u8> {
+    ids.iter().flat_map(|id| id.to_be_bytes()).collect()
+}
+
+fn decode_schema_ids(bytes: &[u8]) -> StorageResult<Vec<u64>> {
+    let chunks = bytes.chunks_exact(8);
+    if !chunks.remainder().is_empty() {
+        return Err(StorageError::VaultDb(format!(
+            "disclosed_schema_ids length {} is not a multiple of 8",
+            bytes.len()
+        )));
+    }
+    Ok(chunks
+        .map(|chunk| {
+            let mut id = [0u8; 8];
+            id.copy_from_slice(chunk);
+            u64::from_be_bytes(id)
+        })
+        .collect())
+}
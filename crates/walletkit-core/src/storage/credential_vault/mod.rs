@@ -5,32 +5,123 @@
 //! integrity-check machinery and the shared `blob_objects` table come from
 //! [`walletkit_db`].
 
+mod consent;
 mod schema;
+mod secure_prefs;
+mod tenant;
 #[cfg(test)]
 mod tests;
+mod usage;
 
 use std::path::Path;
 
+use crate::storage::entropy;
 use crate::storage::error::{StorageError, StorageResult};
-use crate::storage::types::{BlobKind, CredentialRecord};
-use schema::{ensure_schema, VAULT_SCHEMA_VERSION};
+use crate::storage::inventory::{InventoryEntry, RetentionPolicy};
+use crate::storage::types::{
+    BlobKind, BulkDeleteReport, CredentialRecord, CredentialStatus,
+};
+use crate::storage::StorageLockGuard;
+pub use consent::NewConsent;
+use schema::{
+    ensure_multi_tenant_schema, ensure_schema, is_multi_tenant,
+    MERKLE_REFRESH_SEED_VERSION, VAULT_SCHEMA_VERSION,
+};
 use secrecy::SecretBox;
-use walletkit_db::{blobs, cipher, params, DbError, Row, StepResult, Value, Vault};
+pub use secure_prefs::{
+    MAX_SECURE_PREFS, MAX_SECURE_PREF_KEY_BYTES, MAX_SECURE_PREF_VALUE_BYTES,
+};
+use tenant::Scope;
+pub use tenant::{AccountId, BoundCredentialVault, TenantMode};
+use usage::UsageBatch;
+pub(crate) use usage::UsageCounter;
+use walletkit_db::{
+    blobs, cipher, migration, params, DbError, Row, StepResult, Transaction, Value,
+    Vault,
+};
 
 /// Tables included in plaintext vault backups, in order.
 ///
 /// `vault_meta` is intentionally excluded: on restore, the destination vault
 /// already has its own `vault_meta` (created by `schema::ensure_schema` +
 /// `init_leaf_index`) with the authoritative `leaf_index` from the
-/// authenticator.
+/// authenticator. `usage_counters` is excluded too: the counters describe
+/// activity on this device, not account state.
 ///
 /// **Note:** New tables added to the vault schema must be added here too.
-pub(crate) const BACKUP_TABLES: &[&str] = &["credential_records", "blob_objects"];
+pub(crate) const BACKUP_TABLES: &[&str] = &[
+    "consent_records",
+    "credential_records",
+    "secure_prefs_key",
+    "secure_prefs",
+    "blob_objects",
+];
+
+/// What the vault database stores, see
+/// [`data_inventory`](crate::storage::inventory::data_inventory).
+///
+/// **Note:** New tables added to the vault schema must be added here too.
+pub(crate) const INVENTORY: &[InventoryEntry] = &[
+    InventoryEntry {
+        name: "credentials",
+        description: "Credentials issued to the holder, their associated data \
+            and the blinding factor of the holder's subject for each issuer \
+            schema, with how often and when each was disclosed in a proof.",
+        locations: &[
+            "worldid/account.vault.sqlite:credential_records",
+            "worldid/account.vault.sqlite:blob_objects",
+        ],
+        contains_personal_data: true,
+        retention: RetentionPolicy::UntilDeleted,
+    },
+    InventoryEntry {
+        name: "account",
+        description: "The leaf index of the holder's account in the World ID \
+            registry, its recovery counter and a random seed that spreads \
+            this device's background Merkle proof refreshes.",
+        locations: &["worldid/account.vault.sqlite:vault_meta"],
+        contains_personal_data: true,
+        retention: RetentionPolicy::UntilDeleted,
+    },
+    InventoryEntry {
+        name: "consent_ledger",
+        description: "For each proof, the request ID, a hash of the relying \
+            party's ID, the disclosed issuer schemas and a hash of the consent \
+            text shown. Only recorded if the host enables the ledger.",
+        locations: &["worldid/account.vault.sqlite:consent_records"],
+        contains_personal_data: true,
+        retention: RetentionPolicy::UntilDeleted,
+    },
+    InventoryEntry {
+        name: "secure_prefs",
+        description: "Preferences the host stores through `SecurePrefs`, \
+            sealed with a data key kept in the vault.",
+        locations: &[
+            "worldid/account.vault.sqlite:secure_prefs",
+            "worldid/account.vault.sqlite:secure_prefs_key",
+        ],
+        contains_personal_data: true,
+        retention: RetentionPolicy::UntilDeleted,
+    },
+    InventoryEntry {
+        name: "usage_counters",
+        description: "Counts of proofs and other activity on this device.",
+        locations: &["worldid/account.vault.sqlite:usage_counters"],
+        contains_personal_data: true,
+        retention: RetentionPolicy::UntilDeleted,
+    },
+];
+
+/// Credential blob, subject blinding factor, and `expires_at` of a stored
+/// credential.
+pub(crate) type CredentialRow = (Vec<u8>, Vec<u8>, u64);
 
 /// Encrypted vault database wrapper around [`walletkit_db::Vault`].
 #[derive(Debug)]
 pub struct CredentialVault {
     vault: Vault,
+    tenant_mode: TenantMode,
+    usage_batch: UsageBatch,
 }
 
 impl CredentialVault {
@@ -48,7 +139,86 @@ impl CredentialVault {
             blobs::ensure_schema(conn)?;
             ensure_schema(conn)
         })?;
-        Ok(Self { vault })
+        Self::with_mode(vault, TenantMode::SingleAccount)
+    }
+
+    /// Opens or creates a [`TenantMode::MultiTenant`] vault at `path`, shared
+    /// by many accounts.
+    ///
+    /// Every credential-specific table carries an `account_id` column;
+    /// operations must be scoped with [`with_account`](Self::with_account).
+    /// The lock guard ensures no other process initializes the schema
+    /// concurrently.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened, keyed, or
+    /// initialized, or if it was created as a single-account vault.
+    pub fn new_multi_tenant(
+        path: &Path,
+        k_intermediate: &SecretBox<[u8; 32]>,
+        _lock: &StorageLockGuard,
+    ) -> StorageResult<Self> {
+        let vault = Vault::open(path, k_intermediate, |conn| {
+            blobs::ensure_schema(conn)?;
+            ensure_multi_tenant_schema(conn)
+        })?;
+        Self::with_mode(vault, TenantMode::MultiTenant)
+    }
+
+    /// Opens a read-only connection to the single-account vault at `path`,
+    /// which must already exist.
+    ///
+    /// Reads see the last committed state and do not wait for an open write
+    /// transaction on the writer's connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened or keyed, or if it
+    /// is a multi-tenant vault.
+    pub fn open_read_only(
+        path: &Path,
+        k_intermediate: &SecretBox<[u8; 32]>,
+    ) -> StorageResult<Self> {
+        let vault = Vault::open_read_only(path, k_intermediate)?;
+        Self::with_mode(vault, TenantMode::SingleAccount)
+    }
+
+    fn with_mode(vault: Vault, tenant_mode: TenantMode) -> StorageResult<Self> {
+        let multi_tenant =
+            is_multi_tenant(vault.connection()).map_err(|err| map_db_err(&err))?;
+        if multi_tenant != (tenant_mode == TenantMode::MultiTenant) {
+            return Err(StorageError::VaultDb(format!(
+                "vault tenant mode mismatch: expected {tenant_mode:?}"
+            )));
+        }
+        Ok(Self {
+            vault,
+            tenant_mode,
+            usage_batch: UsageBatch::default(),
+        })
+    }
+
+    /// Returns how rows in this vault are partitioned.
+    #[must_use]
+    pub const fn tenant_mode(&self) -> TenantMode {
+        self.tenant_mode
+    }
+
+    /// Scopes all further operations to `account_id`.
+    ///
+    /// Only meaningful for [`TenantMode::MultiTenant`] vaults; on a
+    /// single-account vault every operation of the returned view fails.
+    #[must_use]
+    pub const fn with_account<'a>(
+        &'a self,
+        account_id: &'a AccountId,
+    ) -> BoundCredentialVault<'a> {
+        BoundCredentialVault::new(self, account_id)
+    }
+
+    fn scope<'a>(&self, account: Option<&'a AccountId>) -> StorageResult<Scope<'a>> {
+        Scope::new(self.tenant_mode, account)
     }
 
     /// Initializes or validates the leaf index for this vault.
@@ -61,22 +231,36 @@ impl CredentialVault {
     ///
     /// Returns an error if the stored leaf index does not match.
     pub fn init_leaf_index(&self, leaf_index: u64, now: u64) -> StorageResult<()> {
+        self.init_leaf_index_scoped(None, leaf_index, now)
+    }
+
+    fn init_leaf_index_scoped(
+        &self,
+        account: Option<&AccountId>,
+        leaf_index: u64,
+        now: u64,
+    ) -> StorageResult<()> {
+        let scope = self.scope(account)?;
         let leaf_index_i64 = to_i64(leaf_index, "leaf_index")?;
         let now_i64 = to_i64(now, "now")?;
         let conn = self.vault.connection();
         let tx = conn.transaction().map_err(|err| map_db_err(&err))?;
         let stored = tx
             .query_row(
-                "INSERT INTO vault_meta (schema_version, leaf_index, created_at, updated_at)
-                 VALUES (?1, ?2, ?3, ?3)
-                 ON CONFLICT(schema_version) DO UPDATE SET
-                     leaf_index = CASE
-                         WHEN vault_meta.leaf_index IS NULL
-                         THEN excluded.leaf_index
-                         ELSE vault_meta.leaf_index
-                     END
-                 RETURNING leaf_index",
-                params![VAULT_SCHEMA_VERSION, leaf_index_i64, now_i64],
+                &format!(
+                    "INSERT INTO vault_meta (schema_version, leaf_index, created_at, updated_at{col})
+                     VALUES (?1, ?2, ?3, ?3{val})
+                     ON CONFLICT(schema_version{col}) DO UPDATE SET
+                         leaf_index = CASE
+                             WHEN vault_meta.leaf_index IS NULL
+                             THEN excluded.leaf_index
+                             ELSE vault_meta.leaf_index
+                         END
+                     RETURNING leaf_index",
+                    col = scope.column(),
+                    val = scope.placeholder(4),
+                ),
+                &scope.bind(params![VAULT_SCHEMA_VERSION, leaf_index_i64, now_i64]),
                 |stmt| Ok(stmt.column_i64(0)),
             )
             .map_err(|err| map_db_err(&err))?;
@@ -91,6 +275,84 @@ impl CredentialVault {
         Ok(())
     }
 
+    /// Generates the seed returned by
+    /// [`merkle_refresh_seed`](Self::merkle_refresh_seed) unless the vault
+    /// already has one. Must run after
+    /// [`init_leaf_index`](Self::init_leaf_index).
+    ///
+    /// The seed is created here rather than on first use so that reading it
+    /// never writes, and it lives in `vault_meta` so that it survives cache
+    /// rebuilds and is not carried over by backups.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no randomness is available or the write fails.
+    pub fn init_merkle_refresh_seed(&self) -> StorageResult<()> {
+        // only single-account vaults have the column
+        self.scope(None)?;
+        let mut seed = [0u8; 32];
+        entropy::fill(&mut seed)?;
+        self.vault
+            .connection()
+            .execute(
+                "UPDATE vault_meta SET merkle_refresh_seed = ?1
+                 WHERE schema_version = ?2 AND merkle_refresh_seed IS NULL",
+                params![seed.as_slice(), VAULT_SCHEMA_VERSION],
+            )
+            .map_err(|err| map_db_err(&err))?;
+        Ok(())
+    }
+
+    /// Returns the per-device seed used to jitter background Merkle proof
+    /// refreshes, or `None` if [`init_merkle_refresh_seed`](Self::init_merkle_refresh_seed)
+    /// has not run on this vault yet. Does not write, so it also works on a
+    /// read-only connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails or the stored seed is malformed.
+    pub fn merkle_refresh_seed(&self) -> StorageResult<Option<[u8; 32]>> {
+        self.scope(None)?;
+        let conn = self.vault.connection();
+        // a read-only connection may see a vault last written by a version
+        // without the column
+        if migration::schema_version(conn).map_err(|err| map_db_err(&err))?
+            < MERKLE_REFRESH_SEED_VERSION
+        {
+            return Ok(None);
+        }
+        let seed = conn
+            .query_row_optional(
+                "SELECT merkle_refresh_seed FROM vault_meta
+                 WHERE schema_version = ?1 AND merkle_refresh_seed IS NOT NULL",
+                params![VAULT_SCHEMA_VERSION],
+                |row| Ok(row.column_blob(0)),
+            )
+            .map_err(|err| map_db_err(&err))?;
+        seed.map(|bytes| {
+            <[u8; 32]>::try_from(bytes.as_slice()).map_err(|_| {
+                StorageError::CorruptedVault(
+                    "Merkle refresh seed is not 32 bytes".to_string(),
+                )
+            })
+        })
+        .transpose()
+    }
+
+    /// Returns the connection's `PRAGMA data_version`, which changes whenever
+    /// another connection commits to the vault, including one in another
+    /// process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pragma cannot be read.
+    pub fn data_version(&self) -> StorageResult<i64> {
+        self.vault
+            .connection()
+            .query_row("PRAGMA data_version", &[], |row| Ok(row.column_i64(0)))
+            .map_err(|err| map_db_err(&err))
+    }
+
     /// Stores a credential and optional associated data.
     ///
     /// Blob content is deduplicated by content id to avoid storing identical
@@ -99,6 +361,32 @@ impl CredentialVault {
     /// # Errors
     ///
     /// Returns an error if any insert fails.
+    #[expect(
+        clippy::too_many_arguments,
+        reason = "fields mirror the credential record schema"
+    )]
+    pub fn store_credential(
+        &self,
+        issuer_schema_id: u64,
+        subject_blinding_factor: Vec<u8>,
+        genesis_issued_at: u64,
+        expires_at: u64,
+        credential_blob: Vec<u8>,
+        associated_data: Option<Vec<u8>>,
+        now: u64,
+    ) -> StorageResult<u64> {
+        self.store_credential_scoped(
+            None,
+            issuer_schema_id,
+            subject_blinding_factor,
+            genesis_issued_at,
+            expires_at,
+            credential_blob,
+            associated_data,
+            now,
+        )
+    }
+
     #[expect(
         clippy::too_many_arguments,
         reason = "fields mirror the credential record schema"
@@ -107,8 +395,9 @@ impl CredentialVault {
         clippy::needless_pass_by_value,
         reason = "byte buffers are consumed here; callers don't reuse them"
     )]
-    pub fn store_credential(
+    fn store_credential_scoped(
         &self,
+        account: Option<&AccountId>,
         issuer_schema_id: u64,
         subject_blinding_factor: Vec<u8>,
         genesis_issued_at: u64,
@@ -117,6 +406,7 @@ impl CredentialVault {
         associated_data: Option<Vec<u8>>,
         now: u64,
     ) -> StorageResult<u64> {
+        let scope = self.scope(account)?;
         let now_i64 = to_i64(now, "now")?;
         let issuer_schema_id_i64 = to_i64(issuer_schema_id, "issuer_schema_id")?;
         let genesis_issued_at_i64 = to_i64(genesis_issued_at, "genesis_issued_at")?;
@@ -145,17 +435,30 @@ impl CredentialVault {
 
         let credential_id = tx
             .query_row(
-                "INSERT INTO credential_records (
-                    issuer_schema_id,
-                    subject_blinding_factor,
-                    genesis_issued_at,
-                    expires_at,
-                    updated_at,
-                    credential_blob_cid,
-                    associated_data_cid
-                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
-                RETURNING credential_id",
-                params![
+                &format!(
+                    "INSERT INTO credential_records (
+                        issuer_schema_id,
+                        subject_blinding_factor,
+                        genesis_issued_at,
+                        expires_at,
+                        updated_at,
+                        credential_blob_cid,
+                        associated_data_cid,
+                        recovery_counter{col}
+                    ) VALUES (
+                        ?1, ?2, ?3, ?4, ?5, ?6, ?7,
+                        COALESCE(
+                            (SELECT MAX(recovery_counter) FROM vault_meta
+                             WHERE 1 = 1{account_filter}),
+                            0
+                        ){val}
+                    )
+                    RETURNING credential_id",
+                    col = scope.column(),
+                    val = scope.placeholder(8),
+                    account_filter = scope.filter("", 8),
+                ),
+                &scope.bind(params![
                     issuer_schema_id_i64,
                     subject_blinding_factor,
                     genesis_issued_at_i64,
@@ -163,7 +466,7 @@ impl CredentialVault {
                     now_i64,
                     credential_blob_id.as_slice(),
                     ad_cid_value,
-                ],
+                ]),
                 |stmt| Ok(stmt.column_i64(0)),
             )
             .map_err(|err| map_db_err(&err))?;
@@ -188,31 +491,55 @@ impl CredentialVault {
         issuer_schema_id: Option<u64>,
         now: u64,
     ) -> StorageResult<Vec<CredentialRecord>> {
+        self.list_credentials_scoped(None, issuer_schema_id, None, now)
+    }
+
+    /// Lists credentials not used in a proof since `since`, as candidates for
+    /// cleanup.
+    ///
+    /// A credential never used counts from when it was stored. Ordered like
+    /// [`list_credentials`](Self::list_credentials).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn list_unused_credentials(
+        &self,
+        since: u64,
+        now: u64,
+    ) -> StorageResult<Vec<CredentialRecord>> {
+        self.list_credentials_scoped(None, None, Some(since), now)
+    }
+
+    fn list_credentials_scoped(
+        &self,
+        account: Option<&AccountId>,
+        issuer_schema_id: Option<u64>,
+        unused_since: Option<u64>,
+        now: u64,
+    ) -> StorageResult<Vec<CredentialRecord>> {
+        let scope = self.scope(account)?;
         let now_i64 = to_i64(now, "now")?;
         let issuer_schema_id_i64 = issuer_schema_id
             .map(|value| to_i64(value, "issuer_schema_id"))
             .transpose()?;
+        let unused_since_i64 = unused_since
+            .map(|value| to_i64(value, "since"))
+            .transpose()?;
 
+        let (sql, params) = list_credentials_query(
+            scope,
+            issuer_schema_id_i64,
+            unused_since_i64,
+            now_i64,
+        );
         let mut records = Vec::new();
-        let issuer_filter = issuer_schema_id_i64.map_or(Value::Null, Value::Integer);
-
-        let sql = "SELECT
-                cr.credential_id,
-                cr.issuer_schema_id,
-                cr.genesis_issued_at,
-                cr.expires_at,
-                CASE WHEN cr.expires_at <= ?1 THEN 1 ELSE 0 END AS is_expired
-             FROM credential_records cr
-             WHERE (?2 IS NULL OR cr.issuer_schema_id = ?2)
-             ORDER BY cr.updated_at DESC";
-
         let mut stmt = self
             .vault
             .connection()
-            .prepare(sql)
-            .map_err(|err| map_db_err(&err))?;
-        stmt.bind_values(&[Value::Integer(now_i64), issuer_filter])
+            .prepare(&sql)
             .map_err(|err| map_db_err(&err))?;
+        stmt.bind_values(&params).map_err(|err| map_db_err(&err))?;
         while let StepResult::Row(row) = stmt.step().map_err(|err| map_db_err(&err))? {
             records.push(map_record(&row)?);
         }
@@ -230,14 +557,26 @@ impl CredentialVault {
     /// Returns an error if the delete query fails or the credential ID does
     /// not exist.
     pub fn delete_credential(&self, credential_id: u64) -> StorageResult<()> {
+        self.delete_credential_scoped(None, credential_id)
+    }
+
+    fn delete_credential_scoped(
+        &self,
+        account: Option<&AccountId>,
+        credential_id: u64,
+    ) -> StorageResult<()> {
+        let scope = self.scope(account)?;
         let credential_id_i64 = to_i64(credential_id, "credential_id")?;
         let conn = self.vault.connection();
         let tx = conn.transaction().map_err(|err| map_db_err(&err))?;
 
         let deleted = tx
             .execute(
-                "DELETE FROM credential_records WHERE credential_id = ?1",
-                params![credential_id_i64],
+                &format!(
+                    "DELETE FROM credential_records WHERE credential_id = ?1{account_filter}",
+                    account_filter = scope.filter("", 2),
+                ),
+                &scope.bind(params![credential_id_i64]),
             )
             .map_err(|err| map_db_err(&err))?;
 
@@ -245,34 +584,133 @@ impl CredentialVault {
             return Err(StorageError::CredentialIdNotFound { credential_id });
         }
 
-        // Delete orphaned credential blobs
-        tx.execute(
-            "DELETE FROM blob_objects
-             WHERE blob_kind = ?1
-               AND NOT EXISTS (
-                   SELECT 1
-                   FROM credential_records cr
-                   WHERE cr.credential_blob_cid = blob_objects.content_id
-               )",
-            params![BlobKind::CredentialBlob.as_i64()],
-        )
-        .map_err(|err| map_db_err(&err))?;
+        delete_orphaned_blobs(&tx)?;
+
+        tx.commit().map_err(|err| map_db_err(&err))?;
+        Ok(())
+    }
+
+    /// Deletes several credential records by ID in a single transaction.
+    ///
+    /// IDs that do not exist are reported rather than failing the batch;
+    /// repeated IDs are only processed once. Orphaned blobs are removed as in
+    /// [`delete_credential`](Self::delete_credential).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any query fails, in which case nothing is deleted.
+    pub fn delete_credentials(
+        &self,
+        credential_ids: &[u64],
+    ) -> StorageResult<BulkDeleteReport> {
+        self.delete_credentials_scoped(None, credential_ids)
+    }
+
+    fn delete_credentials_scoped(
+        &self,
+        account: Option<&AccountId>,
+        credential_ids: &[u64],
+    ) -> StorageResult<BulkDeleteReport> {
+        let scope = self.scope(account)?;
+        let sql = format!(
+            "DELETE FROM credential_records WHERE credential_id = ?1{account_filter}",
+            account_filter = scope.filter("", 2),
+        );
+        let conn = self.vault.connection();
+        let tx = conn.transaction().map_err(|err| map_db_err(&err))?;
+
+        let mut report = BulkDeleteReport::default();
+        let mut seen = std::collections::HashSet::new();
+        for &credential_id in credential_ids {
+            if !seen.insert(credential_id) {
+                continue;
+            }
+            let credential_id_i64 = to_i64(credential_id, "credential_id")?;
+            let deleted = tx
+                .execute(&sql, &scope.bind(params![credential_id_i64]))
+                .map_err(|err| map_db_err(&err))?;
+            if deleted == 0 {
+                report.not_found_ids.push(credential_id);
+            } else {
+                report.deleted_count += 1;
+            }
+        }
+
+        if report.deleted_count > 0 {
+            delete_orphaned_blobs(&tx)?;
+        }
+
+        tx.commit().map_err(|err| map_db_err(&err))?;
+        Ok(report)
+    }
+
+    /// Records `recovery_counter` as the account's current recovery counter
+    /// and invalidates every credential stored under an older counter.
+    ///
+    /// Invalidated credentials stay listed with
+    /// [`CredentialStatus::Invalidated`] but are never returned for proving.
+    /// Credentials stored afterwards are stamped with the new counter. The
+    /// recorded counter never decreases.
+    ///
+    /// Returns the IDs of the credentials invalidated by this call.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any query fails, in which case nothing changes.
+    pub fn invalidate_for_recovery(
+        &self,
+        recovery_counter: u64,
+        now: u64,
+    ) -> StorageResult<Vec<u64>> {
+        self.invalidate_for_recovery_scoped(None, recovery_counter, now)
+    }
+
+    fn invalidate_for_recovery_scoped(
+        &self,
+        account: Option<&AccountId>,
+        recovery_counter: u64,
+        now: u64,
+    ) -> StorageResult<Vec<u64>> {
+        let scope = self.scope(account)?;
+        let counter_i64 = to_i64(recovery_counter, "recovery_counter")?;
+        let now_i64 = to_i64(now, "now")?;
+        let conn = self.vault.connection();
+        let tx = conn.transaction().map_err(|err| map_db_err(&err))?;
 
-        // Delete orphaned associated data blobs
         tx.execute(
-            "DELETE FROM blob_objects
-             WHERE blob_kind = ?1
-               AND NOT EXISTS (
-                   SELECT 1
-                   FROM credential_records cr
-                   WHERE cr.associated_data_cid = blob_objects.content_id
-               )",
-            params![BlobKind::AssociatedData.as_i64()],
+            &format!(
+                "UPDATE vault_meta
+                 SET recovery_counter = MAX(recovery_counter, ?1)
+                 WHERE 1 = 1{account_filter}",
+                account_filter = scope.filter("", 2),
+            ),
+            &scope.bind(params![counter_i64]),
         )
         .map_err(|err| map_db_err(&err))?;
 
+        let mut invalidated = Vec::new();
+        {
+            let mut stmt = tx
+                .prepare(&format!(
+                    "UPDATE credential_records
+                     SET invalidated_at = ?2
+                     WHERE recovery_counter < ?1
+                       AND invalidated_at IS NULL{account_filter}
+                     RETURNING credential_id",
+                    account_filter = scope.filter("", 3),
+                ))
+                .map_err(|err| map_db_err(&err))?;
+            stmt.bind_values(&scope.bind(params![counter_i64, now_i64]))
+                .map_err(|err| map_db_err(&err))?;
+            while let StepResult::Row(row) =
+                stmt.step().map_err(|err| map_db_err(&err))?
+            {
+                invalidated.push(to_u64(row.column_i64(0), "credential_id")?);
+            }
+        }
+
         tx.commit().map_err(|err| map_db_err(&err))?;
-        Ok(())
+        Ok(invalidated)
     }
 
     /// Retrieves the credential bytes and blinding factor by issuer schema ID.
@@ -288,37 +726,117 @@ impl CredentialVault {
         issuer_schema_id: u64,
         now: u64,
     ) -> StorageResult<Option<(Vec<u8>, Vec<u8>)>> {
+        Ok(self
+            .fetch_credential_and_blinding_factor_scoped(None, issuer_schema_id, now)?
+            .map(|(credential, blinding_factor, _)| (credential, blinding_factor)))
+    }
+
+    /// Like [`fetch_credential_and_blinding_factor`](Self::fetch_credential_and_blinding_factor),
+    /// additionally returning the credential's `expires_at`.
+    pub(crate) fn fetch_credential_with_expiry(
+        &self,
+        issuer_schema_id: u64,
+        now: u64,
+    ) -> StorageResult<Option<CredentialRow>> {
+        self.fetch_credential_and_blinding_factor_scoped(None, issuer_schema_id, now)
+    }
+
+    fn fetch_credential_and_blinding_factor_scoped(
+        &self,
+        account: Option<&AccountId>,
+        issuer_schema_id: u64,
+        now: u64,
+    ) -> StorageResult<Option<CredentialRow>> {
+        let scope = self.scope(account)?;
         let expires = to_i64(now, "now")?;
         let issuer_schema_id_i64 = to_i64(issuer_schema_id, "issuer_schema_id")?;
 
-        let sql = "SELECT
+        let sql = format!(
+            "SELECT
                 cr.subject_blinding_factor,
-                blob.bytes as credential_blob
+                blob.bytes as credential_blob,
+                cr.expires_at
              FROM credential_records cr
              INNER JOIN blob_objects blob ON cr.credential_blob_cid = blob.content_id
-             WHERE cr.expires_at > ?1 AND cr.issuer_schema_id = ?2
+             WHERE cr.expires_at > ?1
+               AND cr.issuer_schema_id = ?2
+               AND cr.invalidated_at IS NULL{account_filter}
              ORDER BY cr.updated_at DESC
-             LIMIT 1";
+             LIMIT 1",
+            account_filter = scope.filter("cr.", 3),
+        );
 
         let mut stmt = self
             .vault
             .connection()
-            .prepare(sql)
+            .prepare(&sql)
             .map_err(|err| map_db_err(&err))?;
-        stmt.bind_values(params![expires, issuer_schema_id_i64])
+        stmt.bind_values(&scope.bind(params![expires, issuer_schema_id_i64]))
             .map_err(|err| map_db_err(&err))?;
         match stmt.step().map_err(|err| map_db_err(&err))? {
             StepResult::Row(row) => {
                 let blinding_factor = row.column_blob(0);
                 let credential_blob = row.column_blob(1);
-                Ok(Some((credential_blob, blinding_factor)))
+                let expires_at = to_u64(row.column_i64(2), "expires_at")?;
+                Ok(Some((credential_blob, blinding_factor, expires_at)))
             }
             StepResult::Done => Ok(None),
         }
     }
 
-    /// **Development only.** Permanently deletes all credentials and their
-    /// associated blob data from the vault.
+    /// Records a generated proof: bumps the use count and last use of the
+    /// credential disclosed for each of `consent.disclosed_schema_ids` and, if
+    /// `record_consent` is set, appends `consent` to the consent ledger.
+    ///
+    /// The disclosed credential is the one
+    /// [`fetch_credential_and_blinding_factor`](Self::fetch_credential_and_blinding_factor)
+    /// returns. Only the count and the time are stored, nothing identifying
+    /// the RP. Nothing is committed unless `before_commit` succeeds, so
+    /// callers can tie the writes to the replay guard.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a write fails or `before_commit` fails, in which
+    /// case nothing is recorded.
+    pub fn record_proof_with(
+        &self,
+        consent: NewConsent<'_>,
+        record_consent: bool,
+        now: u64,
+        before_commit: impl FnOnce() -> StorageResult<()>,
+    ) -> StorageResult<()> {
+        let scope = self.scope(None)?;
+        let now_i64 = to_i64(now, "now")?;
+        let conn = self.vault.connection();
+        let tx = conn.transaction().map_err(|err| map_db_err(&err))?;
+
+        for issuer_schema_id in consent.disclosed_schema_ids {
+            let issuer_schema_id = to_i64(*issuer_schema_id, "issuer_schema_id")?;
+            tx.execute(
+                "UPDATE credential_records
+                 SET use_count = use_count + 1, last_used_at = ?1
+                 WHERE credential_id = (
+                     SELECT credential_id FROM credential_records
+                     WHERE expires_at > ?1
+                       AND issuer_schema_id = ?2
+                       AND invalidated_at IS NULL
+                     ORDER BY updated_at DESC
+                     LIMIT 1
+                 )",
+                params![now_i64, issuer_schema_id],
+            )
+            .map_err(|err| map_db_err(&err))?;
+        }
+        if record_consent {
+            consent::insert_consent(&tx, scope, consent, now_i64)?;
+        }
+
+        before_commit()?;
+        tx.commit().map_err(|err| map_db_err(&err))
+    }
+
+    /// **Development only.** Permanently deletes all credentials, their
+    /// associated blob data and the secure prefs from the vault.
     ///
     /// This is a destructive, unrecoverable operation. Do not call in
     /// production. Vault metadata (leaf index, schema version) is preserved.
@@ -327,6 +845,28 @@ impl CredentialVault {
     ///
     /// Returns an error if the delete operation fails.
     pub fn danger_delete_all_credentials(&self) -> StorageResult<u64> {
+        self.danger_delete_all_credentials_scoped(None)
+    }
+
+    fn danger_delete_all_credentials_scoped(
+        &self,
+        account: Option<&AccountId>,
+    ) -> StorageResult<u64> {
+        let scope = self.scope(account)?;
+        if account.is_some() {
+            let conn = self.vault.connection();
+            let tx = conn.transaction().map_err(|err| map_db_err(&err))?;
+            let deleted = tx
+                .execute(
+                    "DELETE FROM credential_records WHERE account_id = ?1",
+                    &scope.bind(&[]),
+                )
+                .map_err(|err| map_db_err(&err))?;
+            delete_orphaned_blobs(&tx)?;
+            tx.commit().map_err(|err| map_db_err(&err))?;
+            return Ok(deleted as u64);
+        }
+
         let conn = self.vault.connection();
         let tx = conn.transaction().map_err(|err| map_db_err(&err))?;
 
@@ -336,6 +876,7 @@ impl CredentialVault {
 
         tx.execute("DELETE FROM blob_objects", &[])
             .map_err(|err| map_db_err(&err))?;
+        secure_prefs::clear_secure_prefs(&tx)?;
 
         tx.commit().map_err(|err| map_db_err(&err))?;
         Ok(deleted as u64)
@@ -350,6 +891,36 @@ impl CredentialVault {
         cipher::integrity_check(self.vault.connection()).map_err(|e| map_db_err(&e))
     }
 
+    /// Re-encrypts the vault under `new_key`, see
+    /// [`walletkit_db::Vault::rekey`]. No read-only connection may be open.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the rekey fails; the vault then keeps its key.
+    pub fn rekey(&self, new_key: &SecretBox<[u8; 32]>) -> StorageResult<()> {
+        self.vault.rekey(new_key).map_err(Into::into)
+    }
+
+    /// Checkpoints the vault's WAL into the database file, see
+    /// [`walletkit_db::Vault::checkpoint`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the checkpoint cannot run.
+    pub fn checkpoint(&self) -> StorageResult<()> {
+        self.vault.checkpoint().map_err(Into::into)
+    }
+
+    /// Releases the connection's page cache and checkpoints the WAL without
+    /// waiting, see [`walletkit_db::Vault::release_memory`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection cannot release its memory.
+    pub fn release_memory(&self) -> StorageResult<()> {
+        self.vault.release_memory().map_err(Into::into)
+    }
+
     /// Exports a plaintext (unencrypted) copy of the vault to `dest`.
     ///
     /// Callers that need cross-process exclusion (to keep a concurrent
@@ -362,6 +933,7 @@ impl CredentialVault {
     ///
     /// Returns an error if the export fails.
     pub fn export_plaintext(&self, dest: &Path) -> StorageResult<()> {
+        self.scope(None)?;
         let conn = self.vault.connection();
         if dest.exists() {
             std::fs::remove_file(dest).map_err(|e| {
@@ -383,24 +955,123 @@ impl CredentialVault {
     ///
     /// Returns an error if the import fails.
     pub fn import_plaintext(&self, source: &Path) -> StorageResult<()> {
+        self.scope(None)?;
         let conn = self.vault.connection();
         cipher::import_plaintext_copy(conn, source, BACKUP_TABLES)
             .map_err(|e| map_db_err(&e))
     }
 }
 
+/// SQL and parameters listing the credentials of `scope`, most recently
+/// updated first, optionally restricted to an issuer schema and to
+/// credentials not used since `unused_since`.
+fn list_credentials_query(
+    scope: Scope<'_>,
+    issuer_schema_id: Option<i64>,
+    unused_since: Option<i64>,
+    now: i64,
+) -> (String, Vec<Value>) {
+    // `?2 IS NULL OR issuer_schema_id = ?2` would keep SQLite from using the
+    // issuer schema index, so conditions are only added when set.
+    let mut params = vec![Value::Integer(now)];
+    let mut conditions = Vec::new();
+    if let Some(issuer_schema_id) = issuer_schema_id {
+        params.push(Value::Integer(issuer_schema_id));
+        conditions.push(format!("cr.issuer_schema_id = ?{}", params.len()));
+    }
+    if let Some(unused_since) = unused_since {
+        // never-used credentials count from when they were stored
+        params.push(Value::Integer(unused_since));
+        conditions.push(format!(
+            "COALESCE(cr.last_used_at, cr.updated_at) < ?{}",
+            params.len()
+        ));
+    }
+    let filter = if conditions.is_empty() {
+        "TRUE".to_string()
+    } else {
+        conditions.join(" AND ")
+    };
+    let sql = format!(
+        "SELECT
+            cr.credential_id,
+            cr.issuer_schema_id,
+            cr.genesis_issued_at,
+            cr.expires_at,
+            CASE WHEN cr.expires_at <= ?1 THEN 1 ELSE 0 END AS is_expired,
+            cr.recovery_counter,
+            cr.invalidated_at IS NOT NULL AS is_invalidated,
+            cr.associated_data_cid IS NOT NULL AS has_associated_data,
+            cr.use_count,
+            cr.last_used_at
+         FROM credential_records cr
+         WHERE {filter}{account_filter}
+         ORDER BY cr.updated_at DESC",
+        account_filter = scope.filter("cr.", params.len() + 1),
+    );
+    (sql, scope.bind(&params))
+}
+
+/// Deletes credential and associated-data blobs no longer referenced by any
+/// credential record.
+fn delete_orphaned_blobs(tx: &Transaction<'_>) -> StorageResult<()> {
+    // Delete orphaned credential blobs
+    tx.execute(
+        "DELETE FROM blob_objects
+         WHERE blob_kind = ?1
+           AND NOT EXISTS (
+               SELECT 1
+               FROM credential_records cr
+               WHERE cr.credential_blob_cid = blob_objects.content_id
+           )",
+        params![BlobKind::CredentialBlob.as_i64()],
+    )
+    .map_err(|err| map_db_err(&err))?;
+
+    // Delete orphaned associated data blobs
+    tx.execute(
+        "DELETE FROM blob_objects
+         WHERE blob_kind = ?1
+           AND NOT EXISTS (
+               SELECT 1
+               FROM credential_records cr
+               WHERE cr.associated_data_cid = blob_objects.content_id
+           )",
+        params![BlobKind::AssociatedData.as_i64()],
+    )
+    .map_err(|err| map_db_err(&err))?;
+    Ok(())
+}
+
 fn map_record(row: &Row<'_, '_>) -> StorageResult<CredentialRecord> {
     let credential_id = row.column_i64(0);
     let issuer_schema_id = row.column_i64(1);
     let genesis_issued_at = row.column_i64(2);
     let expires_at = row.column_i64(3);
     let is_expired = row.column_i64(4);
+    let recovery_counter = row.column_i64(5);
+    let status = if row.column_i64(6) != 0 {
+        CredentialStatus::Invalidated
+    } else if is_expired != 0 {
+        CredentialStatus::Expired
+    } else {
+        CredentialStatus::Active
+    };
     Ok(CredentialRecord {
         credential_id: to_u64(credential_id, "credential_id")?,
         issuer_schema_id: to_u64(issuer_schema_id, "issuer_schema_id")?,
         genesis_issued_at: to_u64(genesis_issued_at, "genesis_issued_at")?,
         expires_at: to_u64(expires_at, "expires_at")?,
         is_expired: is_expired != 0,
+        recovery_counter: to_u64(recovery_counter, "recovery_counter")?,
+        status,
+        has_associated_data: row.column_i64(7) != 0,
+        use_count: to_u64(row.column_i64(8), "use_count")?,
+        last_used_at: if row.is_column_null(9) {
+            None
+        } else {
+            Some(to_u64(row.column_i64(9), "last_used_at")?)
+        },
     })
 }
 
@@ -417,5 +1088,8 @@ fn to_u64(value: i64, label: &str) -> StorageResult<u64> {
 }
 
 fn map_db_err(err: &DbError) -> StorageError {
-    StorageError::VaultDb(err.to_string())
+    StorageError::VaultSqlite {
+        code: err.code.0,
+        message: err.message.clone(),
+    }
 }
@@ -4,20 +4,21 @@
 //! [`walletkit_db::Blobs::ensure_schema`]; this module owns only the
 //! credential-specific tables.
 
+use walletkit_db::migration::{run_migrations, Migration, MigrationProgress};
 use walletkit_db::{Connection, DbResult};
 
+use crate::storage::VAULT_APPLICATION_ID;
+
 pub(super) const VAULT_SCHEMA_VERSION: i64 = 1;
 
-/// Creates the credential-vault tables, indexes, and triggers.
+/// Migrations of the single-account vault.
 ///
-/// **Backup sensitivity:** Schema changes here affect plaintext vault
-/// backups.
-/// - New tables must be added to [`super::BACKUP_TABLES`].
-/// - Column changes (especially new `NOT NULL` columns without defaults) can
-///   break restoring older backups into a newer schema.
-pub(super) fn ensure_schema(conn: &Connection) -> DbResult<()> {
-    conn.execute_batch(
-        "CREATE TABLE IF NOT EXISTS vault_meta (
+/// Version 1 is idempotent: vaults created before migrations were tracked
+/// already have these tables but report `user_version = 0`.
+pub(super) const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        sql: "CREATE TABLE IF NOT EXISTS vault_meta (
             schema_version  INTEGER NOT NULL,
             leaf_index      INTEGER,
             created_at      INTEGER NOT NULL,
@@ -53,5 +54,215 @@ pub(super) fn ensure_schema(conn: &Connection) -> DbResult<()> {
         CREATE INDEX IF NOT EXISTS idx_cred_by_expiry
         ON credential_records (expires_at);
 ",
+        description: "vault metadata and credential records",
+        data_migration: None,
+    },
+    RECOVERY_COUNTER_MIGRATION,
+    Migration {
+        version: 3,
+        sql: "CREATE TABLE consent_records (
+            consent_id           INTEGER NOT NULL PRIMARY KEY,
+            request_id           TEXT    NOT NULL,
+            rp_id_hash           BLOB    NOT NULL,
+            disclosed_schema_ids BLOB    NOT NULL,
+            consent_text_hash    BLOB,
+            recorded_at          INTEGER NOT NULL
+        );
+
+        CREATE INDEX idx_consent_by_recorded_at
+        ON consent_records (recorded_at DESC, consent_id DESC);
+",
+        description: "consent ledger",
+        data_migration: None,
+    },
+    Migration {
+        version: 4,
+        sql: "CREATE TABLE secure_prefs (
+            pref_key     TEXT NOT NULL PRIMARY KEY,
+            sealed_value BLOB NOT NULL
+        );
+
+        CREATE TABLE secure_prefs_key (
+            id       INTEGER NOT NULL PRIMARY KEY CHECK (id = 0),
+            data_key BLOB    NOT NULL
+        );
+",
+        description: "secure prefs",
+        data_migration: None,
+    },
+    Migration {
+        version: 5,
+        sql: "CREATE TABLE usage_counters (
+            counter_name TEXT    NOT NULL PRIMARY KEY,
+            value        INTEGER NOT NULL
+        );
+",
+        description: "local usage counters",
+        data_migration: None,
+    },
+    credential_usage_migration(6),
+    Migration {
+        version: MERKLE_REFRESH_SEED_VERSION,
+        sql: "ALTER TABLE vault_meta ADD COLUMN merkle_refresh_seed BLOB;
+",
+        description: "per-device Merkle proof refresh seed",
+        data_migration: None,
+    },
+];
+
+/// Version of the single-account vault that added
+/// `vault_meta.merkle_refresh_seed`.
+pub(super) const MERKLE_REFRESH_SEED_VERSION: u32 = 7;
+
+/// Migrations of the multi-tenant vault. See [`MIGRATIONS`].
+///
+/// Secure prefs and usage counters are single-account only and have no
+/// multi-tenant tables.
+const MULTI_TENANT_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        sql: "CREATE TABLE IF NOT EXISTS vault_meta (
+            schema_version  INTEGER NOT NULL,
+            account_id      BLOB    NOT NULL,
+            leaf_index      INTEGER,
+            created_at      INTEGER NOT NULL,
+            updated_at      INTEGER NOT NULL
+        );
+
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_vault_meta_schema_version
+        ON vault_meta (schema_version, account_id);
+
+        CREATE TRIGGER IF NOT EXISTS vault_meta_set_updated_at
+        AFTER UPDATE ON vault_meta
+        FOR EACH ROW
+        BEGIN
+            UPDATE vault_meta
+            SET updated_at = CAST(strftime('%s','now') AS INTEGER)
+            WHERE schema_version = NEW.schema_version
+              AND account_id = NEW.account_id;
+        END;
+
+        CREATE TABLE IF NOT EXISTS credential_records (
+            credential_id           INTEGER NOT NULL PRIMARY KEY,
+            account_id              BLOB    NOT NULL,
+            issuer_schema_id        INTEGER NOT NULL,
+            subject_blinding_factor BLOB    NOT NULL,
+            genesis_issued_at        INTEGER NOT NULL,
+            expires_at              INTEGER NOT NULL,
+            updated_at              INTEGER NOT NULL,
+            credential_blob_cid     BLOB    NOT NULL,
+            associated_data_cid     BLOB
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_cred_by_account_issuer_schema
+        ON credential_records (account_id, issuer_schema_id, expires_at);
+
+        CREATE INDEX IF NOT EXISTS idx_cred_by_account_updated
+        ON credential_records (account_id, updated_at DESC);
+",
+        description: "account-partitioned vault metadata and credential records",
+        data_migration: None,
+    },
+    RECOVERY_COUNTER_MIGRATION,
+    Migration {
+        version: 3,
+        sql: "CREATE TABLE consent_records (
+            consent_id           INTEGER NOT NULL PRIMARY KEY,
+            account_id           BLOB    NOT NULL,
+            request_id           TEXT    NOT NULL,
+            rp_id_hash           BLOB    NOT NULL,
+            disclosed_schema_ids BLOB    NOT NULL,
+            consent_text_hash    BLOB,
+            recorded_at          INTEGER NOT NULL
+        );
+
+        CREATE INDEX idx_consent_by_account_recorded_at
+        ON consent_records (account_id, recorded_at DESC, consent_id DESC);
+",
+        description: "account-partitioned consent ledger",
+        data_migration: None,
+    },
+    credential_usage_migration(4),
+];
+
+/// Records the account's recovery counter on the vault and on each credential.
+///
+/// Credentials issued before this migration are assumed to predate any
+/// recovery (counter `0`).
+const RECOVERY_COUNTER_MIGRATION: Migration = Migration {
+    version: 2,
+    sql: "ALTER TABLE vault_meta
+            ADD COLUMN recovery_counter INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE credential_records
+            ADD COLUMN recovery_counter INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE credential_records
+            ADD COLUMN invalidated_at INTEGER;
+",
+    description: "recovery counter on vault metadata and credential records",
+    data_migration: None,
+};
+
+/// Records how often and when each credential was disclosed in a proof.
+///
+/// Existing credentials start unused. Nothing identifying the RP is kept on
+/// the record.
+const fn credential_usage_migration(version: u32) -> Migration {
+    Migration {
+        version,
+        sql: "ALTER TABLE credential_records
+            ADD COLUMN use_count INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE credential_records
+            ADD COLUMN last_used_at INTEGER;
+",
+        description: "credential use count and last use",
+        data_migration: None,
+    }
+}
+
+fn log_progress(progress: MigrationProgress) {
+    tracing::info!(
+        "vault migration {}/{} applied (schema version {})",
+        progress.migration_index + 1,
+        progress.total,
+        progress.version_applied
+    );
+}
+
+/// Creates the credential-vault tables, indexes, and triggers.
+///
+/// **Backup sensitivity:** Schema changes here affect plaintext vault
+/// backups.
+/// - New tables must be added to [`super::BACKUP_TABLES`].
+/// - Column changes (especially new `NOT NULL` columns without defaults) can
+///   break restoring older backups into a newer schema.
+pub(super) fn ensure_schema(conn: &Connection) -> DbResult<()> {
+    run_migrations(conn, MIGRATIONS, log_progress)?;
+    set_application_id(conn)
+}
+
+/// Creates the multi-tenant variant of the credential-vault tables.
+///
+/// Mirrors [`ensure_schema`], with an `account_id` column on every
+/// credential-specific table. The shared `blob_objects` table is
+/// content-addressed and stays unpartitioned; blobs are only reachable through
+/// an account's `credential_records`.
+pub(super) fn ensure_multi_tenant_schema(conn: &Connection) -> DbResult<()> {
+    run_migrations(conn, MULTI_TENANT_MIGRATIONS, log_progress)?;
+    set_application_id(conn)
+}
+
+/// Marks the database as a vault. Vaults created before the application ID
+/// was set get it on their next writable open.
+fn set_application_id(conn: &Connection) -> DbResult<()> {
+    conn.execute_batch(&format!("PRAGMA application_id = {VAULT_APPLICATION_ID};"))
+}
+
+/// Returns `true` if the vault was created with [`ensure_multi_tenant_schema`].
+pub(super) fn is_multi_tenant(conn: &Connection) -> DbResult<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('credential_records')
+         WHERE name = 'account_id'",
+        &[],
+        |row| Ok(row.column_i64(0) != 0),
     )
 }
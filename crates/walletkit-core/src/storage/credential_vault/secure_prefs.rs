@@ -0,0 +1,240 @@
+//! Small host secrets kept next to the credentials.
+//!
+//! Stored in the vault so that backups carry them and wipes remove them.
+//! The vault is already encrypted at rest; each value is additionally sealed
+//! with a random per-vault data key and the pref's name as associated data,
+//! so a value copied to another name fails to open. The data key lives in
+//! the vault too, keeping backups restorable on a device with a different
+//! `K_intermediate`.
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use walletkit_db::{params, StepResult, Transaction};
+use zeroize::Zeroizing;
+
+use super::{map_db_err, CredentialVault};
+use crate::storage::entropy;
+use crate::storage::error::{StorageError, StorageResult};
+
+/// Maximum length of a pref name, in bytes.
+pub const MAX_SECURE_PREF_KEY_BYTES: usize = 128;
+
+/// Maximum size of a pref value, in bytes.
+pub const MAX_SECURE_PREF_VALUE_BYTES: usize = 16 * 1024;
+
+/// Maximum number of prefs.
+pub const MAX_SECURE_PREFS: u32 = 64;
+
+const NONCE_LEN: usize = 24;
+
+/// Prefix of the associated data binding a value to its name.
+const VALUE_AD_PREFIX: &[u8] = b"worldid:secure-pref:";
+
+impl CredentialVault {
+    /// Stores `value` under `key`, replacing any previous value.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::SecurePrefsLimit`] if the name is empty or too
+    /// long, the value too large, or the pref would exceed the pref count,
+    /// and an error if the write fails.
+    pub fn secure_prefs_set(&self, key: &str, value: &[u8]) -> StorageResult<()> {
+        scope_single_account(self)?;
+        check_key(key)?;
+        if value.len() > MAX_SECURE_PREF_VALUE_BYTES {
+            return Err(StorageError::SecurePrefsLimit(format!(
+                "value of {} bytes exceeds the limit of {MAX_SECURE_PREF_VALUE_BYTES} bytes",
+                value.len()
+            )));
+        }
+
+        let conn = self.vault.connection();
+        let tx = conn.transaction().map_err(|err| map_db_err(&err))?;
+        let count = tx
+            .query_row(
+                "SELECT COUNT(*) FROM secure_prefs WHERE pref_key != ?1",
+                params![key],
+                |row| Ok(row.column_i64(0)),
+            )
+            .map_err(|err| map_db_err(&err))?;
+        if count >= i64::from(MAX_SECURE_PREFS) {
+            return Err(StorageError::SecurePrefsLimit(format!(
+                "at most {MAX_SECURE_PREFS} prefs can be stored"
+            )));
+        }
+
+        let data_key = data_key(&tx)?;
+        let sealed = seal(&data_key, key, value)?;
+        tx.execute(
+            "INSERT INTO secure_prefs (pref_key, sealed_value) VALUES (?1, ?2)
+             ON CONFLICT(pref_key) DO UPDATE SET sealed_value = excluded.sealed_value",
+            params![key, sealed],
+        )
+        .map_err(|err| map_db_err(&err))?;
+        tx.commit().map_err(|err| map_db_err(&err))
+    }
+
+    /// Returns the value stored under `key`, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails or the value does not open under
+    /// `key`.
+    pub fn secure_prefs_get(&self, key: &str) -> StorageResult<Option<Vec<u8>>> {
+        scope_single_account(self)?;
+        let conn = self.vault.connection();
+        let Some(sealed) = conn
+            .query_row_optional(
+                "SELECT sealed_value FROM secure_prefs WHERE pref_key = ?1",
+                params![key],
+                |row| Ok(row.column_blob(0)),
+            )
+            .map_err(|err| map_db_err(&err))?
+        else {
+            return Ok(None);
+        };
+        let data_key = conn
+            .query_row_optional(
+                "SELECT data_key FROM secure_prefs_key WHERE id = 0",
+                &[],
+                |row| Ok(Zeroizing::new(row.column_blob(0))),
+            )
+            .map_err(|err| map_db_err(&err))?
+            .ok_or_else(|| {
+                StorageError::CorruptedVault(
+                    "secure prefs data key missing".to_string(),
+                )
+            })?;
+        open(&data_key, key, &sealed).map(Some)
+    }
+
+    /// Deletes the value stored under `key`.
+    ///
+    /// Returns `false` if there was none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delete fails.
+    pub fn secure_prefs_delete(&self, key: &str) -> StorageResult<bool> {
+        scope_single_account(self)?;
+        let deleted = self
+            .vault
+            .connection()
+            .execute("DELETE FROM secure_prefs WHERE pref_key = ?1", params![key])
+            .map_err(|err| map_db_err(&err))?;
+        Ok(deleted > 0)
+    }
+
+    /// Lists the names of all stored prefs, in lexicographic order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn secure_prefs_keys(&self) -> StorageResult<Vec<String>> {
+        scope_single_account(self)?;
+        let mut stmt = self
+            .vault
+            .connection()
+            .prepare("SELECT pref_key FROM secure_prefs ORDER BY pref_key")
+            .map_err(|err| map_db_err(&err))?;
+        let mut keys = Vec::new();
+        while let StepResult::Row(row) = stmt.step().map_err(|err| map_db_err(&err))? {
+            keys.push(row.column_text(0));
+        }
+        Ok(keys)
+    }
+}
+
+/// Deletes all prefs and the data key, inside the caller's transaction.
+pub(super) fn clear_secure_prefs(tx: &Transaction<'_>) -> StorageResult<()> {
+    tx.execute_batch("DELETE FROM secure_prefs; DELETE FROM secure_prefs_key;")
+        .map_err(|err| map_db_err(&err))
+}
+
+/// Secure prefs only exist in single-account vaults.
+fn scope_single_account(vault: &CredentialVault) -> StorageResult<()> {
+    vault.scope(None).map(|_| ())
+}
+
+fn check_key(key: &str) -> StorageResult<()> {
+    if key.is_empty() {
+        return Err(StorageError::SecurePrefsLimit(
+            "pref name must not be empty".to_string(),
+        ));
+    }
+    if key.len() > MAX_SECURE_PREF_KEY_BYTES {
+        return Err(StorageError::SecurePrefsLimit(format!(
+            "pref name of {} bytes exceeds the limit of {MAX_SECURE_PREF_KEY_BYTES} bytes",
+            key.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the vault's data key, generating it on first use.
+fn data_key(tx: &Transaction<'_>) -> StorageResult<Zeroizing<Vec<u8>>> {
+    let mut fresh = Zeroizing::new(vec![0u8; 32]);
+    entropy::fill(&mut fresh)?;
+    tx.execute(
+        "INSERT INTO secure_prefs_key (id, data_key) VALUES (0, ?1)
+         ON CONFLICT(id) DO NOTHING",
+        params![fresh.as_slice()],
+    )
+    .map_err(|err| map_db_err(&err))?;
+    tx.query_row(
+        "SELECT data_key FROM secure_prefs_key WHERE id = 0",
+        &[],
+        |row| Ok(Zeroizing::new(row.column_blob(0))),
+    )
+    .map_err(|err| map_db_err(&err))
+}
+
+fn value_ad(key: &str) -> Vec<u8> {
+    [VALUE_AD_PREFIX, key.as_bytes()].concat()
+}
+
+fn cipher(data_key: &[u8]) -> StorageResult<XChaCha20Poly1305> {
+    XChaCha20Poly1305::new_from_slice(data_key).map_err(|_| {
+        StorageError::CorruptedVault("invalid secure prefs data key".to_string())
+    })
+}
+
+/// Encrypts `value`, returning `nonce || ciphertext`.
+fn seal(data_key: &[u8], key: &str, value: &[u8]) -> StorageResult<Vec<u8>> {
+    let mut nonce = [0u8; NONCE_LEN];
+    entropy::fill_nonce(&mut nonce)?;
+    let ciphertext = cipher(data_key)?
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: value,
+                aad: &value_ad(key),
+            },
+        )
+        .map_err(|err| StorageError::Crypto(err.to_string()))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+fn open(data_key: &[u8], key: &str, sealed: &[u8]) -> StorageResult<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        return Err(StorageError::CorruptedVault(format!(
+            "secure pref {key:?} too short"
+        )));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    cipher(data_key)?
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &value_ad(key),
+            },
+        )
+        .map_err(|_| {
+            StorageError::CorruptedVault(format!("secure pref {key:?} does not open"))
+        })
+}
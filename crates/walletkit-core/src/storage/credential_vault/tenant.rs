@@ -0,0 +1,296 @@
+//! Row-level account partitioning for vaults shared by many accounts.
+//!
+//! A [`TenantMode::MultiTenant`] vault adds an `account_id` column to every
+//! credential-specific table. All reads and writes go through a
+//! [`BoundCredentialVault`], obtained from [`CredentialVault::with_account`],
+//! which filters every query by that account.
+
+use walletkit_db::Value;
+
+use super::{CredentialVault, NewConsent};
+use crate::storage::error::{StorageError, StorageResult};
+use crate::storage::types::{BulkDeleteReport, ConsentRecord, CredentialRecord};
+
+/// How rows in a [`CredentialVault`] are partitioned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TenantMode {
+    /// The vault file belongs to a single account (the on-device layout).
+    SingleAccount,
+    /// The vault file is shared by many accounts, partitioned by `account_id`.
+    MultiTenant,
+}
+
+/// Opaque identifier of an account in a multi-tenant vault.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AccountId(Vec<u8>);
+
+impl AccountId {
+    /// Creates an account identifier from its raw bytes.
+    #[must_use]
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        Self(bytes.into())
+    }
+
+    /// Returns the raw identifier bytes.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Account filter applied to a vault query.
+///
+/// `None` targets a single-account vault; `Some` restricts a multi-tenant
+/// vault to one account.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Scope<'a>(Option<&'a AccountId>);
+
+impl<'a> Scope<'a> {
+    /// Validates that `account` matches the vault's tenant mode.
+    pub(super) fn new(
+        mode: TenantMode,
+        account: Option<&'a AccountId>,
+    ) -> StorageResult<Self> {
+        match (mode, account) {
+            (TenantMode::SingleAccount, None) | (TenantMode::MultiTenant, Some(_)) => {
+                Ok(Self(account))
+            }
+            (TenantMode::SingleAccount, Some(_)) => Err(StorageError::VaultDb(
+                "vault is single-account; account scoping is not supported".to_string(),
+            )),
+            (TenantMode::MultiTenant, None) => Err(StorageError::VaultDb(
+                "vault is multi-tenant; use `with_account` to scope operations"
+                    .to_string(),
+            )),
+        }
+    }
+
+    /// Extra column list entry for inserts (`, account_id`).
+    pub(super) const fn column(self) -> &'static str {
+        if self.0.is_some() {
+            ", account_id"
+        } else {
+            ""
+        }
+    }
+
+    /// Extra placeholder for inserts, bound to parameter `index`.
+    pub(super) fn placeholder(self, index: usize) -> String {
+        self.0.map_or_else(String::new, |_| format!(", ?{index}"))
+    }
+
+    /// `WHERE` clause fragment restricting `table` to the account, bound to
+    /// parameter `index`.
+    pub(super) fn filter(self, table: &str, index: usize) -> String {
+        self.0.map_or_else(String::new, |_| {
+            format!(" AND {table}account_id = ?{index}")
+        })
+    }
+
+    /// Appends the account parameter, if any.
+    pub(super) fn bind(self, params: &[Value]) -> Vec<Value> {
+        let mut params = params.to_vec();
+        if let Some(account) = self.0 {
+            params.push(Value::Blob(account.as_bytes().to_vec()));
+        }
+        params
+    }
+}
+
+/// A [`CredentialVault`] view scoped to a single account of a multi-tenant
+/// vault.
+///
+/// Shares the vault's underlying connection; every query is bound to the
+/// account.
+#[derive(Debug, Clone, Copy)]
+pub struct BoundCredentialVault<'a> {
+    vault: &'a CredentialVault,
+    account: &'a AccountId,
+}
+
+impl<'a> BoundCredentialVault<'a> {
+    pub(super) const fn new(
+        vault: &'a CredentialVault,
+        account: &'a AccountId,
+    ) -> Self {
+        Self { vault, account }
+    }
+
+    /// Returns the account this view is bound to.
+    #[must_use]
+    pub const fn account_id(&self) -> &'a AccountId {
+        self.account
+    }
+
+    /// See [`CredentialVault::init_leaf_index`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stored leaf index for this account does not
+    /// match.
+    pub fn init_leaf_index(&self, leaf_index: u64, now: u64) -> StorageResult<()> {
+        self.vault
+            .init_leaf_index_scoped(Some(self.account), leaf_index, now)
+    }
+
+    /// See [`CredentialVault::store_credential`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any insert fails.
+    #[expect(
+        clippy::too_many_arguments,
+        reason = "fields mirror the credential record schema"
+    )]
+    pub fn store_credential(
+        &self,
+        issuer_schema_id: u64,
+        subject_blinding_factor: Vec<u8>,
+        genesis_issued_at: u64,
+        expires_at: u64,
+        credential_blob: Vec<u8>,
+        associated_data: Option<Vec<u8>>,
+        now: u64,
+    ) -> StorageResult<u64> {
+        self.vault.store_credential_scoped(
+            Some(self.account),
+            issuer_schema_id,
+            subject_blinding_factor,
+            genesis_issued_at,
+            expires_at,
+            credential_blob,
+            associated_data,
+            now,
+        )
+    }
+
+    /// See [`CredentialVault::list_credentials`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn list_credentials(
+        &self,
+        issuer_schema_id: Option<u64>,
+        now: u64,
+    ) -> StorageResult<Vec<CredentialRecord>> {
+        self.vault.list_credentials_scoped(
+            Some(self.account),
+            issuer_schema_id,
+            None,
+            now,
+        )
+    }
+
+    /// See [`CredentialVault::delete_credential`]. Credentials belonging to
+    /// other accounts are reported as not found.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delete query fails or the credential ID does
+    /// not exist for this account.
+    pub fn delete_credential(&self, credential_id: u64) -> StorageResult<()> {
+        self.vault
+            .delete_credential_scoped(Some(self.account), credential_id)
+    }
+
+    /// See [`CredentialVault::delete_credentials`]. Credentials belonging to
+    /// other accounts are reported as not found.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any query fails.
+    pub fn delete_credentials(
+        &self,
+        credential_ids: &[u64],
+    ) -> StorageResult<BulkDeleteReport> {
+        self.vault
+            .delete_credentials_scoped(Some(self.account), credential_ids)
+    }
+
+    /// See [`CredentialVault::invalidate_for_recovery`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any query fails.
+    pub fn invalidate_for_recovery(
+        &self,
+        recovery_counter: u64,
+        now: u64,
+    ) -> StorageResult<Vec<u64>> {
+        self.vault.invalidate_for_recovery_scoped(
+            Some(self.account),
+            recovery_counter,
+            now,
+        )
+    }
+
+    /// See [`CredentialVault::fetch_credential_and_blinding_factor`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn fetch_credential_and_blinding_factor(
+        &self,
+        issuer_schema_id: u64,
+        now: u64,
+    ) -> StorageResult<Option<(Vec<u8>, Vec<u8>)>> {
+        Ok(self
+            .vault
+            .fetch_credential_and_blinding_factor_scoped(
+                Some(self.account),
+                issuer_schema_id,
+                now,
+            )?
+            .map(|(credential, blinding_factor, _)| (credential, blinding_factor)))
+    }
+
+    /// See [`CredentialVault::record_consent`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the insert fails.
+    pub fn record_consent(
+        &self,
+        consent: NewConsent<'_>,
+        now: u64,
+    ) -> StorageResult<u64> {
+        self.vault
+            .record_consent_scoped(Some(self.account), consent, now, || Ok(()))
+    }
+
+    /// See [`CredentialVault::list_consents`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub fn list_consents(
+        &self,
+        limit: u32,
+        before: Option<u64>,
+    ) -> StorageResult<Vec<ConsentRecord>> {
+        self.vault
+            .list_consents_scoped(Some(self.account), limit, before)
+    }
+
+    /// See [`CredentialVault::purge_consents`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delete fails.
+    pub fn purge_consents(&self, older_than: u64) -> StorageResult<u64> {
+        self.vault
+            .purge_consents_scoped(Some(self.account), older_than)
+    }
+
+    /// **Development only.** Permanently deletes all credentials of this
+    /// account, and any blobs no longer referenced by another account.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the delete operation fails.
+    pub fn danger_delete_all_credentials(&self) -> StorageResult<u64> {
+        self.vault
+            .danger_delete_all_credentials_scoped(Some(self.account))
+    }
+}
@@ -1,10 +1,12 @@
 //! Vault database unit tests.
 
+use super::schema::MIGRATIONS;
 use super::*;
 use secrecy::SecretBox;
 use std::fs;
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
+use walletkit_db::migration::run_migrations;
 
 fn temp_vault_path() -> PathBuf {
     let mut path = std::env::temp_dir();
@@ -55,7 +57,7 @@ fn test_vault_wrong_key_fails() {
     let wrong_key = SecretBox::init_with(|| [0x02u8; 32]);
     let err = CredentialVault::new(&path, &wrong_key).expect_err("wrong key");
     match err {
-        StorageError::VaultDb(_) | StorageError::CorruptedVault(_) => {}
+        StorageError::VaultSqlite { .. } | StorageError::CorruptedVault(_) => {}
         _ => panic!("unexpected error: {err}"),
     }
     cleanup_vault_files(&path);
@@ -90,6 +92,27 @@ fn test_leaf_index_immutable() {
     cleanup_lock_file(&lock_path);
 }
 
+#[test]
+fn test_merkle_refresh_seed_is_created_once() {
+    let path = temp_vault_path();
+    let key = SecretBox::init_with(|| [0x05u8; 32]);
+    let db = CredentialVault::new(&path, &key).expect("create vault");
+    db.init_leaf_index(7, 100).expect("init leaf index");
+    assert_eq!(db.merkle_refresh_seed().expect("read seed"), None);
+
+    db.init_merkle_refresh_seed().expect("init seed");
+    let seed = db
+        .merkle_refresh_seed()
+        .expect("read seed")
+        .expect("seed created");
+    db.init_merkle_refresh_seed().expect("init seed again");
+    drop(db);
+
+    let db = CredentialVault::open_read_only(&path, &key).expect("open read-only");
+    assert_eq!(db.merkle_refresh_seed().expect("read seed"), Some(seed));
+    cleanup_vault_files(&path);
+}
+
 #[test]
 fn test_store_credential_without_associated_data() {
     let path = temp_vault_path();
@@ -281,6 +304,247 @@ fn test_list_credentials_marks_expired() {
     cleanup_lock_file(&lock_path);
 }
 
+#[test]
+fn test_invalidate_for_recovery_excludes_stale_credentials() {
+    let path = temp_vault_path();
+    let key = SecretBox::init_with(|| [0x0Au8; 32]);
+    let db = CredentialVault::new(&path, &key).expect("create vault");
+    db.init_leaf_index(42, 1000).expect("init leaf index");
+    let stale_id = db
+        .store_credential(
+            400,
+            sample_blinding_factor(),
+            1,
+            5000,
+            b"stale".to_vec(),
+            None,
+            1000,
+        )
+        .expect("store stale credential");
+
+    // the account is recovered: counter bumped from 0 to 1
+    assert_eq!(
+        db.invalidate_for_recovery(1, 1100).expect("invalidate"),
+        vec![stale_id]
+    );
+    assert!(db
+        .invalidate_for_recovery(1, 1200)
+        .expect("invalidate again")
+        .is_empty());
+    assert!(db
+        .fetch_credential_and_blinding_factor(400, 1200)
+        .expect("fetch stale")
+        .is_none());
+
+    let fresh_id = db
+        .store_credential(
+            400,
+            sample_blinding_factor(),
+            1,
+            5000,
+            b"fresh".to_vec(),
+            None,
+            1300,
+        )
+        .expect("store fresh credential");
+    let (credential, _) = db
+        .fetch_credential_and_blinding_factor(400, 1300)
+        .expect("fetch fresh")
+        .expect("fresh credential");
+    assert_eq!(credential, b"fresh".to_vec());
+    // an outdated counter never re-invalidates newer credentials
+    assert!(db
+        .invalidate_for_recovery(0, 1400)
+        .expect("invalidate with old counter")
+        .is_empty());
+
+    let records = db.list_credentials(Some(400), 1400).expect("list");
+    let status_of = |id: u64| {
+        records
+            .iter()
+            .find(|record| record.credential_id == id)
+            .map(|record| (record.status, record.recovery_counter))
+    };
+    assert_eq!(
+        status_of(stale_id),
+        Some((CredentialStatus::Invalidated, 0))
+    );
+    assert_eq!(status_of(fresh_id), Some((CredentialStatus::Active, 1)));
+
+    cleanup_vault_files(&path);
+}
+
+#[test]
+fn test_consent_ledger_pages_and_purges() {
+    let path = temp_vault_path();
+    let key = SecretBox::init_with(|| [0x0Au8; 32]);
+    let db = CredentialVault::new(&path, &key).expect("create vault");
+    for (index, recorded_at) in [1000u64, 1100, 1200].into_iter().enumerate() {
+        db.record_consent(
+            NewConsent {
+                request_id: &format!("request-{index}"),
+                rp_id_hash: &[0xAB; 32],
+                disclosed_schema_ids: &[100, u64::MAX],
+                consent_text_hash: (index == 0).then_some(&[0xCD; 32][..]),
+            },
+            recorded_at,
+        )
+        .expect("record consent");
+    }
+
+    let first_page = db.list_consents(2, None).expect("list first page");
+    assert_eq!(
+        first_page
+            .iter()
+            .map(|record| record.request_id.as_str())
+            .collect::<Vec<_>>(),
+        ["request-2", "request-1"]
+    );
+    assert_eq!(first_page[0].disclosed_schema_ids, vec![100, u64::MAX]);
+    assert_eq!(first_page[0].consent_text_hash, None);
+
+    let second_page = db
+        .list_consents(2, Some(first_page[1].recorded_at))
+        .expect("list second page");
+    assert_eq!(second_page.len(), 1);
+    assert_eq!(second_page[0].request_id, "request-0");
+    assert_eq!(second_page[0].consent_text_hash, Some(vec![0xCD; 32]));
+
+    assert_eq!(db.purge_consents(1100).expect("purge"), 1);
+    assert_eq!(db.list_consents(10, None).expect("list").len(), 2);
+
+    cleanup_vault_files(&path);
+}
+
+#[test]
+fn test_consent_rolled_back_when_commit_hook_fails() {
+    let path = temp_vault_path();
+    let key = SecretBox::init_with(|| [0x0Au8; 32]);
+    let db = CredentialVault::new(&path, &key).expect("create vault");
+    let consent = NewConsent {
+        request_id: "request",
+        rp_id_hash: &[0xAB; 32],
+        disclosed_schema_ids: &[100],
+        consent_text_hash: None,
+    };
+
+    db.record_consent_with(consent, 1000, || {
+        Err(StorageError::CacheDb(
+            "replay guard write failed".to_string(),
+        ))
+    })
+    .expect_err("hook failure aborts the entry");
+    assert!(db.list_consents(10, None).expect("list").is_empty());
+
+    db.record_consent_with(consent, 1000, || Ok(()))
+        .expect("record consent");
+    assert_eq!(db.list_consents(10, None).expect("list").len(), 1);
+
+    cleanup_vault_files(&path);
+}
+
+#[test]
+fn test_record_proof_bumps_disclosed_credential_atomically() {
+    let path = temp_vault_path();
+    let key = SecretBox::init_with(|| [0x0Au8; 32]);
+    let db = CredentialVault::new(&path, &key).expect("create vault");
+    let store = |issuer_schema_id, blob: &[u8], now| {
+        db.store_credential(
+            issuer_schema_id,
+            sample_blinding_factor(),
+            1,
+            5000,
+            blob.to_vec(),
+            None,
+            now,
+        )
+        .expect("store credential")
+    };
+    let older = store(100, b"older", 1000);
+    let disclosed = store(100, b"newer", 1100);
+    let other = store(200, b"other", 1000);
+    let consent = NewConsent {
+        request_id: "request",
+        rp_id_hash: &[0xAB; 32],
+        disclosed_schema_ids: &[100],
+        consent_text_hash: None,
+    };
+    let usage = |credential_id| {
+        let record = db
+            .list_credentials(None, 2000)
+            .expect("list")
+            .into_iter()
+            .find(|record| record.credential_id == credential_id)
+            .expect("record");
+        (record.use_count, record.last_used_at)
+    };
+
+    db.record_proof_with(consent, false, 2000, || {
+        Err(StorageError::CacheDb(
+            "replay guard write failed".to_string(),
+        ))
+    })
+    .expect_err("hook failure aborts the usage update");
+    assert_eq!(usage(disclosed), (0, None));
+
+    db.record_proof_with(consent, false, 2000, || Ok(()))
+        .expect("record proof");
+    db.record_proof_with(consent, true, 3000, || Ok(()))
+        .expect("record proof");
+    assert_eq!(usage(disclosed), (2, Some(3000)));
+    assert_eq!(usage(older), (0, None));
+    assert_eq!(usage(other), (0, None));
+    // the consent entry is only written when asked for
+    assert_eq!(db.list_consents(10, None).expect("list").len(), 1);
+
+    // unused since 2500: the disclosed credential was used at 3000, the
+    // others never, counting from when they were stored
+    let unused = db.list_unused_credentials(2500, 4000).expect("list unused");
+    let mut unused_ids = unused
+        .iter()
+        .map(|record| record.credential_id)
+        .collect::<Vec<_>>();
+    unused_ids.sort_unstable();
+    assert_eq!(unused_ids, vec![older, other]);
+    assert!(db
+        .list_unused_credentials(1000, 4000)
+        .expect("list unused")
+        .is_empty());
+
+    cleanup_vault_files(&path);
+}
+
+#[test]
+fn test_migration_adds_credential_usage_to_existing_records() {
+    let path = temp_vault_path();
+    let key = SecretBox::init_with(|| [0x0Bu8; 32]);
+    // a vault from before the usage columns
+    let legacy = Vault::open(&path, &key, |conn| {
+        blobs::ensure_schema(conn)?;
+        run_migrations(conn, &MIGRATIONS[..5], |_| {})?;
+        conn.execute_batch(
+            "INSERT INTO credential_records (
+                issuer_schema_id,
+                subject_blinding_factor,
+                genesis_issued_at,
+                expires_at,
+                updated_at,
+                credential_blob_cid
+            ) VALUES (100, x'11', 1, 5000, 1000, x'00');",
+        )
+    })
+    .expect("create legacy vault");
+    drop(legacy);
+
+    let db = CredentialVault::new(&path, &key).expect("migrate vault");
+    let records = db.list_credentials(None, 2000).expect("list");
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].use_count, 0);
+    assert_eq!(records[0].last_used_at, None);
+
+    cleanup_vault_files(&path);
+}
+
 #[test]
 fn test_list_credentials_by_issuer_includes_expired() {
     let path = temp_vault_path();
@@ -527,9 +791,239 @@ fn test_vault_corruption_handling() {
     fs::write(&path, b"corrupt").expect("corrupt file");
     let err = CredentialVault::new(&path, &key).expect_err("corrupt vault");
     match err {
-        StorageError::VaultDb(_) | StorageError::CorruptedVault(_) => {}
+        StorageError::VaultSqlite { .. } | StorageError::CorruptedVault(_) => {}
         _ => panic!("unexpected error: {err}"),
     }
     cleanup_vault_files(&path);
     cleanup_lock_file(&lock_path);
 }
+
+#[test]
+fn test_multi_tenant_accounts_are_isolated() {
+    let path = temp_vault_path();
+    let lock_path = temp_lock_path();
+    let key = SecretBox::init_with(|| [0x0Du8; 32]);
+    let lock = crate::storage::StorageLock::open(&lock_path).expect("open lock");
+    let guard = lock.lock().expect("lock");
+    let db = CredentialVault::new_multi_tenant(&path, &key, &guard)
+        .expect("create multi-tenant vault");
+    assert_eq!(db.tenant_mode(), TenantMode::MultiTenant);
+
+    let alice_id = AccountId::new(b"alice".to_vec());
+    let bob_id = AccountId::new(b"bob".to_vec());
+    let alice = db.with_account(&alice_id);
+    let bob = db.with_account(&bob_id);
+
+    // each account keeps its own leaf index
+    alice.init_leaf_index(1, 100).expect("init alice");
+    bob.init_leaf_index(2, 100).expect("init bob");
+    assert!(matches!(
+        alice.init_leaf_index(2, 200),
+        Err(StorageError::InvalidLeafIndex { expected: 1, .. })
+    ));
+
+    // identical payloads dedupe in blob_objects but stay scoped per account
+    let alice_cred = alice
+        .store_credential(
+            10,
+            sample_blinding_factor(),
+            123,
+            2000,
+            b"shared".to_vec(),
+            None,
+            1000,
+        )
+        .expect("store alice");
+    bob.store_credential(
+        10,
+        sample_blinding_factor(),
+        123,
+        2000,
+        b"shared".to_vec(),
+        None,
+        1000,
+    )
+    .expect("store bob");
+    bob.store_credential(
+        20,
+        sample_blinding_factor(),
+        123,
+        2000,
+        b"bob-only".to_vec(),
+        None,
+        1000,
+    )
+    .expect("store bob");
+
+    assert_eq!(alice.list_credentials(None, 1000).expect("list").len(), 1);
+    assert_eq!(bob.list_credentials(None, 1000).expect("list").len(), 2);
+    assert!(alice
+        .fetch_credential_and_blinding_factor(20, 1000)
+        .expect("fetch")
+        .is_none());
+
+    // bob cannot delete alice's credential
+    assert!(matches!(
+        bob.delete_credential(alice_cred),
+        Err(StorageError::CredentialIdNotFound { .. })
+    ));
+
+    // wiping bob keeps the blob alice still references
+    assert_eq!(bob.danger_delete_all_credentials().expect("wipe bob"), 2);
+    let (credential_blob, _) = alice
+        .fetch_credential_and_blinding_factor(10, 1000)
+        .expect("fetch")
+        .expect("alice credential");
+    assert_eq!(credential_blob, b"shared");
+
+    // unscoped access is rejected
+    assert!(db.list_credentials(None, 1000).is_err());
+    drop(db);
+
+    // reopening in the wrong mode is rejected
+    assert!(CredentialVault::new(&path, &key).is_err());
+    drop(guard);
+    cleanup_vault_files(&path);
+    cleanup_lock_file(&lock_path);
+}
+
+#[test]
+fn test_single_account_vault_rejects_account_scope() {
+    let path = temp_vault_path();
+    let lock_path = temp_lock_path();
+    let key = SecretBox::init_with(|| [0x0Eu8; 32]);
+    let db = CredentialVault::new(&path, &key).expect("create vault");
+    assert_eq!(db.tenant_mode(), TenantMode::SingleAccount);
+    let account = AccountId::new(b"alice".to_vec());
+    assert!(db
+        .with_account(&account)
+        .list_credentials(None, 1000)
+        .is_err());
+    drop(db);
+
+    let lock = crate::storage::StorageLock::open(&lock_path).expect("open lock");
+    let guard = lock.lock().expect("lock");
+    assert!(CredentialVault::new_multi_tenant(&path, &key, &guard).is_err());
+    drop(guard);
+    cleanup_vault_files(&path);
+    cleanup_lock_file(&lock_path);
+}
+
+#[test]
+fn test_secure_pref_bound_to_its_name() {
+    let path = temp_vault_path();
+    let key = SecretBox::init_with(|| [0x42u8; 32]);
+    let db = CredentialVault::new(&path, &key).expect("create vault");
+    db.secure_prefs_set("a", b"value of a").expect("set a");
+    db.secure_prefs_set("b", b"value of b").expect("set b");
+
+    // copy the sealed value of `a` over `b`
+    db.vault
+        .connection()
+        .execute(
+            "UPDATE secure_prefs SET sealed_value =
+                (SELECT sealed_value FROM secure_prefs WHERE pref_key = 'a')
+             WHERE pref_key = 'b'",
+            &[],
+        )
+        .expect("swap");
+    assert_eq!(
+        db.secure_prefs_get("a").expect("get a"),
+        Some(b"value of a".to_vec())
+    );
+    let err = db
+        .secure_prefs_get("b")
+        .expect_err("moved value must not open");
+    assert!(matches!(err, StorageError::CorruptedVault(_)), "{err}");
+
+    cleanup_vault_files(&path);
+}
+
+fn query_plan(db: &CredentialVault, sql: &str) -> String {
+    let mut stmt = db
+        .vault
+        .connection()
+        .prepare(&format!("EXPLAIN QUERY PLAN {sql}"))
+        .expect("prepare plan");
+    let mut plan = String::new();
+    while let StepResult::Row(row) = stmt.step().expect("step plan") {
+        plan.push_str(&row.column_text(3));
+        plan.push('\n');
+    }
+    plan
+}
+
+#[test]
+fn test_list_credentials_by_issuer_uses_index() {
+    let path = temp_vault_path();
+    let key = SecretBox::init_with(|| [0x08u8; 32]);
+    let db = CredentialVault::new(&path, &key).expect("create vault");
+
+    let scope = db.scope(None).expect("scope");
+    let (sql, _) = list_credentials_query(scope, Some(100), None, 1000);
+    let plan = query_plan(&db, &sql);
+    assert!(
+        plan.contains("USING INDEX idx_cred_by_issuer_schema"),
+        "schema-filtered listing must use the index:\n{plan}"
+    );
+    assert!(
+        !plan.contains("TEMP B-TREE"),
+        "index must provide the order:\n{plan}"
+    );
+
+    cleanup_vault_files(&path);
+}
+
+/// Compares listing one schema out of 10 000 credentials across 100 schemas
+/// with and without the issuer schema index.
+///
+/// `cargo test -p walletkit-core bench_list_credentials_by_issuer --
+/// --ignored --nocapture`
+#[test]
+#[ignore = "benchmark"]
+fn bench_list_credentials_by_issuer() {
+    const SCHEMAS: u64 = 100;
+    const CREDENTIALS: u64 = 10_000;
+    const ROUNDS: u32 = 100;
+
+    let path = temp_vault_path();
+    let key = SecretBox::init_with(|| [0x08u8; 32]);
+    let db = CredentialVault::new(&path, &key).expect("create vault");
+    for i in 0..CREDENTIALS {
+        db.store_credential(
+            i % SCHEMAS,
+            sample_blinding_factor(),
+            1,
+            2000,
+            i.to_be_bytes().to_vec(),
+            None,
+            1000 + i,
+        )
+        .expect("store credential");
+    }
+
+    let scope = db.scope(None).expect("scope");
+    let (indexed_sql, params) = list_credentials_query(scope, Some(42), None, 1000);
+    let linear_sql = indexed_sql.replace(
+        "FROM credential_records cr",
+        "FROM credential_records cr NOT INDEXED",
+    );
+    let time = |sql: &str| {
+        let started = std::time::Instant::now();
+        for _ in 0..ROUNDS {
+            let mut stmt = db.vault.connection().prepare(sql).expect("prepare");
+            stmt.bind_values(&params).expect("bind");
+            let mut rows = 0;
+            while let StepResult::Row(_) = stmt.step().expect("step") {
+                rows += 1;
+            }
+            assert_eq!(rows, CREDENTIALS / SCHEMAS);
+        }
+        started.elapsed() / ROUNDS
+    };
+    let linear = time(&linear_sql);
+    let indexed = time(&indexed_sql);
+    println!("list by issuer schema: linear {linear:?}, indexed {indexed:?}");
+
+    cleanup_vault_files(&path);
+}
@@ -0,0 +1,196 @@
+//! Local usage counters, for activity statistics shown to the user.
+//!
+//! Kept in the vault rather than the cache so that cache rebuilds never reset
+//! them. Counters are plain tallies and hold no request or nullifier material.
+//!
+//! Counters are bumped on every Merkle cache lookup, so with a write batch
+//! window set (see [`CredentialVault::set_write_batch_window`]) increments are
+//! queued in memory and committed together in one transaction, instead of one
+//! transaction and fsync each.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use walletkit_db::params;
+
+use super::{map_db_err, to_i64, to_u64, CredentialVault};
+use crate::storage::error::StorageResult;
+
+/// A persisted usage counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UsageCounter {
+    /// Merkle inclusion proof lookups answered from the cache.
+    MerkleCacheHit,
+    /// Merkle inclusion proof lookups without a usable cached proof.
+    MerkleCacheMiss,
+}
+
+impl UsageCounter {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::MerkleCacheHit => "merkle_cache_hit",
+            Self::MerkleCacheMiss => "merkle_cache_miss",
+        }
+    }
+}
+
+/// Usage counter increments not yet committed to the vault.
+#[derive(Debug, Default)]
+pub(super) struct UsageBatch {
+    /// How long increments may stay queued; zero writes them through.
+    window: Duration,
+    pending: Mutex<PendingIncrements>,
+}
+
+#[derive(Debug, Default)]
+struct PendingIncrements {
+    deltas: BTreeMap<UsageCounter, u64>,
+    /// When the oldest queued increment was queued.
+    queued_at: Option<Instant>,
+}
+
+impl UsageBatch {
+    fn lock(&self) -> std::sync::MutexGuard<'_, PendingIncrements> {
+        // the map is only updated after a successful commit, so it stays
+        // consistent across a panic
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl CredentialVault {
+    /// Adds one to `counter`.
+    ///
+    /// With a write batch window set, the increment is queued and committed
+    /// with the others once the window has elapsed, see
+    /// [`flush_due_writes`](Self::flush_due_writes), or on
+    /// [`flush_pending_writes`](Self::flush_pending_writes).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the vault is multi-tenant or the write fails.
+    pub fn increment_usage_counter(&self, counter: UsageCounter) -> StorageResult<()> {
+        self.scope(None)?;
+        if self.usage_batch.window.is_zero() {
+            return self.commit_usage_increments(&BTreeMap::from([(counter, 1)]));
+        }
+
+        {
+            let mut pending = self.usage_batch.lock();
+            *pending.deltas.entry(counter).or_default() += 1;
+            pending.queued_at.get_or_insert_with(Instant::now);
+        }
+        self.flush_due_writes()
+    }
+
+    /// Returns the value of `counter`, `0` if it was never incremented.
+    ///
+    /// Includes increments that are still queued.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the vault is multi-tenant or the query fails.
+    pub fn usage_counter(&self, counter: UsageCounter) -> StorageResult<u64> {
+        self.scope(None)?;
+        self.flush_due_writes()?;
+        let value = self
+            .vault
+            .connection()
+            .query_row_optional(
+                "SELECT value FROM usage_counters WHERE counter_name = ?1",
+                params![counter.name()],
+                |row| Ok(row.column_i64(0)),
+            )
+            .map_err(|err| map_db_err(&err))?;
+        let queued = self
+            .usage_batch
+            .lock()
+            .deltas
+            .get(&counter)
+            .copied()
+            .unwrap_or(0);
+        Ok(to_u64(value.unwrap_or(0), "usage counter")?.saturating_add(queued))
+    }
+
+    /// Sets how long usage counter increments may be queued before they are
+    /// committed together. Zero, the default, commits every increment on its
+    /// own and flushes the increments already queued.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if flushing the queued increments fails.
+    pub fn set_write_batch_window(&mut self, window: Duration) -> StorageResult<()> {
+        self.usage_batch.window = window;
+        if window.is_zero() {
+            self.flush_pending_writes()?;
+        }
+        Ok(())
+    }
+
+    /// Commits the queued usage counter increments if the oldest has been
+    /// queued for the write batch window.
+    ///
+    /// Runs on every increment and counter read, and periodically from the
+    /// store, so queued increments are not held past the window.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the write fails, in which case the increments stay
+    /// queued.
+    pub fn flush_due_writes(&self) -> StorageResult<()> {
+        let due =
+            self.usage_batch.lock().queued_at.is_some_and(|queued_at| {
+                queued_at.elapsed() >= self.usage_batch.window
+            });
+        if due {
+            self.flush_pending_writes()?;
+        }
+        Ok(())
+    }
+
+    /// Commits all queued usage counter increments in one transaction.
+    ///
+    /// Callers flush before durability-critical writes, so that no queued
+    /// write is left behind a write that must survive a crash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the write fails, in which case the increments stay
+    /// queued.
+    pub fn flush_pending_writes(&self) -> StorageResult<()> {
+        let mut pending = self.usage_batch.lock();
+        if pending.deltas.is_empty() {
+            return Ok(());
+        }
+        self.commit_usage_increments(&pending.deltas)?;
+        pending.deltas.clear();
+        pending.queued_at = None;
+        drop(pending);
+        Ok(())
+    }
+
+    fn commit_usage_increments(
+        &self,
+        deltas: &BTreeMap<UsageCounter, u64>,
+    ) -> StorageResult<()> {
+        let conn = self.vault.connection();
+        let tx = conn.transaction().map_err(|err| map_db_err(&err))?;
+        for (counter, delta) in deltas {
+            tx.execute(
+                "INSERT INTO usage_counters (counter_name, value) VALUES (?1, ?2)
+                 ON CONFLICT(counter_name) DO UPDATE SET value = value + excluded.value",
+                params![counter.name(), to_i64(*delta, "usage counter")?],
+            )
+            .map_err(|err| map_db_err(&err))?;
+        }
+        tx.commit().map_err(|err| map_db_err(&err))
+    }
+}
+
+impl Drop for CredentialVault {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_pending_writes() {
+            tracing::warn!("failed to flush queued usage counters: {e}");
+        }
+    }
+}
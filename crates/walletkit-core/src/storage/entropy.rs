@@ -0,0 +1,171 @@
+//! Fallible access to OS randomness.
+//!
+//! `OsRng::fill_bytes` panics when `getrandom` fails, which happens early in
+//! boot on some embedded Android builds. [`fill`] returns
+//! [`StorageError::EntropyUnavailable`] instead. Hot paths that draw many
+//! values, such as AEAD nonces during batch operations, use [`fill_nonce`],
+//! which serves them from a per-thread `ChaCha` CSPRNG reseeded from the OS
+//! every [`RESEED_INTERVAL`] bytes.
+
+use std::cell::RefCell;
+
+use rand::rngs::{OsRng, StdRng};
+use rand::{RngCore, SeedableRng};
+use zeroize::Zeroizing;
+
+use super::error::{StorageError, StorageResult};
+use crate::error::WalletKitError;
+
+/// Bytes served by a nonce generator before it is reseeded from the OS.
+const RESEED_INTERVAL: usize = 64 * 1024;
+
+/// A `ChaCha` generator and the bytes it may serve before reseeding.
+struct NonceRng {
+    rng: StdRng,
+    bytes_until_reseed: usize,
+}
+
+impl NonceRng {
+    fn from_os() -> StorageResult<Self> {
+        let mut seed = Zeroizing::new([0u8; 32]);
+        fill(seed.as_mut())?;
+        Ok(Self {
+            rng: StdRng::from_seed(*seed),
+            bytes_until_reseed: RESEED_INTERVAL,
+        })
+    }
+}
+
+thread_local! {
+    static NONCE_RNG: RefCell<Option<NonceRng>> = const { RefCell::new(None) };
+}
+
+/// Fills `dest` from the OS random source.
+///
+/// Use this for keys and seeds.
+///
+/// # Errors
+///
+/// Returns [`StorageError::EntropyUnavailable`] if the OS source fails.
+pub fn fill(dest: &mut [u8]) -> StorageResult<()> {
+    #[cfg(test)]
+    if tests::is_failing() {
+        return Err(StorageError::EntropyUnavailable(
+            "injected failure".to_string(),
+        ));
+    }
+    OsRng
+        .try_fill_bytes(dest)
+        .map_err(|err| StorageError::EntropyUnavailable(err.to_string()))
+}
+
+/// Fills `dest` from this thread's nonce generator, seeding it from the OS
+/// first if needed.
+///
+/// Use this for nonces, which must be unique but are not secret.
+///
+/// # Errors
+///
+/// Returns [`StorageError::EntropyUnavailable`] if the generator must be
+/// reseeded and the OS source fails.
+pub fn fill_nonce(dest: &mut [u8]) -> StorageResult<()> {
+    NONCE_RNG.with(|slot| {
+        let mut slot = slot.borrow_mut();
+        let mut state = match slot.take() {
+            Some(state) if state.bytes_until_reseed >= dest.len() => state,
+            _ => NonceRng::from_os()?,
+        };
+        state.rng.fill_bytes(dest);
+        state.bytes_until_reseed = state.bytes_until_reseed.saturating_sub(dest.len());
+        *slot = Some(state);
+        Ok(())
+    })
+}
+
+/// Checks that the OS random source works, so a host can detect an
+/// unavailable source at startup instead of on the first storage write.
+///
+/// Call it once, e.g. before opening the [`CredentialStore`](super::CredentialStore).
+///
+/// # Errors
+///
+/// Returns a storage error with [`StorageError::EntropyUnavailable`] if the
+/// source fails or returns repeated output.
+#[uniffi::export]
+pub fn entropy_selfcheck() -> Result<(), WalletKitError> {
+    let mut first = Zeroizing::new([0u8; 32]);
+    let mut second = Zeroizing::new([0u8; 32]);
+    fill(first.as_mut())?;
+    fill(second.as_mut())?;
+    if first == second {
+        return Err(StorageError::EntropyUnavailable(
+            "random source returned repeated output".to_string(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    thread_local! {
+        static FAILING: Cell<bool> = const { Cell::new(false) };
+    }
+
+    pub(super) fn is_failing() -> bool {
+        FAILING.with(Cell::get)
+    }
+
+    /// Makes the OS random source fail on this thread until dropped.
+    pub struct FailingEntropy;
+
+    impl FailingEntropy {
+        pub(crate) fn install() -> Self {
+            FAILING.with(|failing| failing.set(true));
+            // the nonce generator must reseed, and fail, on its next use
+            NONCE_RNG.with(|slot| slot.borrow_mut().take());
+            Self
+        }
+    }
+
+    impl Drop for FailingEntropy {
+        fn drop(&mut self) {
+            FAILING.with(|failing| failing.set(false));
+        }
+    }
+
+    #[test]
+    fn test_selfcheck() {
+        entropy_selfcheck().expect("entropy available");
+
+        let _failing = FailingEntropy::install();
+        let error = entropy_selfcheck().unwrap_err();
+        assert!(
+            matches!(error, WalletKitError::Storage { ref error, .. } if error.contains("entropy")),
+            "unexpected error: {error:?}"
+        );
+    }
+
+    #[test]
+    fn test_fill_nonce_reseeds() {
+        let mut nonce = [0u8; 24];
+        fill_nonce(&mut nonce).expect("seed");
+        let mut next = [0u8; 24];
+        fill_nonce(&mut next).expect("draw");
+        assert_ne!(nonce, next);
+
+        // a seeded generator keeps serving nonces until it must reseed
+        FAILING.with(|failing| failing.set(true));
+        fill_nonce(&mut next).expect("no reseed needed");
+        let mut large = vec![0u8; RESEED_INTERVAL];
+        let error = fill_nonce(&mut large).unwrap_err();
+        FAILING.with(|failing| failing.set(false));
+        assert!(matches!(error, StorageError::EntropyUnavailable(_)));
+
+        fill_nonce(&mut large).expect("reseeded");
+    }
+}
@@ -5,8 +5,38 @@ use thiserror::Error;
 /// Result type for storage operations.
 pub type StorageResult<T> = Result<T, StorageError>;
 
+/// SHA-256 digest of a proof request ID, as recorded in the replay guard.
+///
+/// Crosses the FFI boundary as bytes.
+pub type RequestIdDigest = [u8; 32];
+
+uniffi::custom_type!(RequestIdDigest, Vec<u8>, {
+    remote,
+    lower: |digest| digest.to_vec(),
+    try_lift: |bytes| {
+        RequestIdDigest::try_from(bytes.as_slice())
+            .map_err(|_| uniffi::deps::anyhow::anyhow!("request ID digest must be 32 bytes"))
+    },
+});
+
+/// How the host app should react to a [`StorageError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum ErrorSeverity {
+    /// The operation may succeed if retried later (e.g. lock contention or a
+    /// busy database). Retry with backoff; no data is lost.
+    Transient,
+    /// The operation cannot succeed until the user or app does something
+    /// (unlock the device, update the app, initialize storage, obtain a
+    /// credential). Surface the error instead of retrying.
+    RequiresUserAction,
+    /// Storage is unusable or the library was misused. Retrying will not
+    /// help; if [`StorageError::is_corruption`] is `true` the app should offer
+    /// to wipe and restore from backup, otherwise report to support.
+    Fatal,
+}
+
 /// Errors raised by credential storage primitives.
-#[derive(Debug, Error, uniffi::Error)]
+#[derive(Debug, Error, uniffi::Error, strum::EnumCount)]
 pub enum StorageError {
     /// Errors coming from the device keystore.
     #[error("keystore error: {0}")]
@@ -44,6 +74,24 @@ pub enum StorageError {
     #[error("cache db error: {0}")]
     CacheDb(String),
 
+    /// A `SQLite` call on the vault database failed.
+    #[error("vault db error: sqlite error {code}: {message}")]
+    VaultSqlite {
+        /// `SQLite` (extended) result code.
+        code: i32,
+        /// Message reported by `SQLite`.
+        message: String,
+    },
+
+    /// A `SQLite` call on the cache database failed.
+    #[error("cache db error: sqlite error {code}: {message}")]
+    CacheSqlite {
+        /// `SQLite` (extended) result code.
+        code: i32,
+        /// Message reported by `SQLite`.
+        message: String,
+    },
+
     /// Leaf index mismatch during initialization.
     #[error("leaf index mismatch: expected {expected}, got {provided}")]
     InvalidLeafIndex {
@@ -62,9 +110,22 @@ pub enum StorageError {
     NotInitialized,
 
     /// Nullifier already disclosed for a different request.
+    ///
+    /// No longer returned; see [`Self::NullifierConflict`].
     #[error("nullifier already disclosed")]
     NullifierAlreadyDisclosed,
 
+    /// Nullifier already disclosed for a different proof request.
+    #[error(
+        "nullifier already disclosed for request {}",
+        hex::encode(conflicting_request_id)
+    )]
+    NullifierConflict {
+        /// SHA-256 digest of the ID of the request the nullifier was
+        /// disclosed for.
+        conflicting_request_id: RequestIdDigest,
+    },
+
     /// Credential not found in the vault.
     #[error("credential not found")]
     CredentialNotFound,
@@ -86,6 +147,148 @@ pub enum StorageError {
     /// Unexpected `UniFFI` callback error.
     #[error("unexpected uniffi callback error: {0}")]
     UnexpectedUniFFICallbackError(String),
+
+    /// A Merkle cache entry carries a registry kind this version does not know.
+    #[error("unknown registry kind: {0}")]
+    UnknownRegistryKind(u8),
+
+    /// Not enough storage quota is available to proceed safely.
+    #[error(
+        "insufficient storage quota: {available} bytes available, {required} required; \
+         request persistent storage or free up space"
+    )]
+    InsufficientStorageQuota {
+        /// Bytes still available to the origin.
+        available: u64,
+        /// Bytes required by the operation.
+        required: u64,
+    },
+
+    /// A [`SecurePrefs`](super::SecurePrefs) name, value or count limit was
+    /// exceeded.
+    #[error("secure prefs limit exceeded: {0}")]
+    SecurePrefsLimit(String),
+
+    /// The store was closed with
+    /// [`CredentialStore::close`](super::CredentialStore::close).
+    #[error("credential store is closed")]
+    Closed,
+
+    /// The OS random source failed. It may recover, e.g. later in boot.
+    #[error("entropy unavailable: {0}")]
+    EntropyUnavailable(String),
+}
+
+impl StorageError {
+    /// Classifies the error so hosts can decide whether to retry, prompt the
+    /// user, or wipe / escalate. See [`ErrorSeverity`] for the expected response.
+    #[must_use]
+    pub const fn severity(&self) -> ErrorSeverity {
+        // No wildcard: new variants must be classified explicitly.
+        match self {
+            Self::VaultSqlite { code, .. } | Self::CacheSqlite { code, .. } => {
+                sqlite_severity(*code)
+            }
+            Self::BlobStore(_)
+            | Self::Lock(_)
+            | Self::CorruptedCacheEntry { .. }
+            | Self::EntropyUnavailable(_) => ErrorSeverity::Transient,
+            Self::Keystore(_)
+            | Self::UnsupportedEnvelopeVersion(_)
+            | Self::InvalidLeafIndex { .. }
+            | Self::NotInitialized
+            | Self::NullifierAlreadyDisclosed
+            | Self::NullifierConflict { .. }
+            | Self::CredentialNotFound
+            | Self::CredentialIdNotFound { .. }
+            | Self::InsufficientStorageQuota { .. }
+            | Self::SecurePrefsLimit(_) => ErrorSeverity::RequiresUserAction,
+            Self::Serialization(_)
+            | Self::Crypto(_)
+            | Self::InvalidEnvelope(_)
+            | Self::CorruptedVault(_)
+            | Self::VaultDb(_)
+            | Self::CacheDb(_)
+            | Self::UnexpectedUniFFICallbackError(_)
+            | Self::UnknownRegistryKind(_)
+            | Self::Closed => ErrorSeverity::Fatal,
+        }
+    }
+
+    /// Whether the error indicates corrupted persisted data. The cache rebuilds
+    /// itself; a corrupted vault or key envelope must be wiped and restored.
+    #[must_use]
+    pub const fn is_corruption(&self) -> bool {
+        match self {
+            Self::VaultSqlite { code, .. } | Self::CacheSqlite { code, .. } => {
+                matches!(*code & 0xff, SQLITE_CORRUPT | SQLITE_NOTADB)
+            }
+            Self::InvalidEnvelope(_)
+            | Self::CorruptedVault(_)
+            | Self::CorruptedCacheEntry { .. }
+            | Self::UnknownRegistryKind(_) => true,
+            Self::Keystore(_)
+            | Self::BlobStore(_)
+            | Self::Lock(_)
+            | Self::Serialization(_)
+            | Self::Crypto(_)
+            | Self::UnsupportedEnvelopeVersion(_)
+            | Self::VaultDb(_)
+            | Self::CacheDb(_)
+            | Self::InvalidLeafIndex { .. }
+            | Self::NotInitialized
+            | Self::NullifierAlreadyDisclosed
+            | Self::NullifierConflict { .. }
+            | Self::CredentialNotFound
+            | Self::CredentialIdNotFound { .. }
+            | Self::InsufficientStorageQuota { .. }
+            | Self::SecurePrefsLimit(_)
+            | Self::UnexpectedUniFFICallbackError(_)
+            | Self::Closed
+            | Self::EntropyUnavailable(_) => false,
+        }
+    }
+}
+
+/// Primary `SQLite` result codes that [`StorageError::severity`] and
+/// [`StorageError::is_corruption`] tell apart.
+const SQLITE_BUSY: i32 = 5;
+const SQLITE_LOCKED: i32 = 6;
+const SQLITE_CORRUPT: i32 = 11;
+const SQLITE_FULL: i32 = 13;
+const SQLITE_NOTADB: i32 = 26;
+
+/// Classifies a `SQLite` result code. Only contention is worth retrying; a
+/// corrupted database or a wrong key (`SQLITE_NOTADB`) never recovers.
+const fn sqlite_severity(code: i32) -> ErrorSeverity {
+    match code & 0xff {
+        SQLITE_BUSY | SQLITE_LOCKED => ErrorSeverity::Transient,
+        SQLITE_FULL => ErrorSeverity::RequiresUserAction,
+        _ => ErrorSeverity::Fatal,
+    }
+}
+
+/// Returns the [`ErrorSeverity`] of a storage error raised to the host.
+#[uniffi::export]
+#[must_use]
+#[expect(
+    clippy::needless_pass_by_value,
+    reason = "foreign bindings pass errors by value"
+)]
+pub fn storage_error_severity(error: StorageError) -> ErrorSeverity {
+    error.severity()
+}
+
+/// Returns whether a storage error raised to the host indicates corrupted
+/// persisted data.
+#[uniffi::export]
+#[must_use]
+#[expect(
+    clippy::needless_pass_by_value,
+    reason = "foreign bindings pass errors by value"
+)]
+pub fn storage_error_is_corruption(error: StorageError) -> bool {
+    error.is_corruption()
 }
 
 impl From<uniffi::UnexpectedUniFFICallbackError> for StorageError {
@@ -113,10 +316,200 @@ impl From<walletkit_db::StoreError> for StorageError {
             walletkit_db::StoreError::UnsupportedEnvelopeVersion(v) => {
                 Self::UnsupportedEnvelopeVersion(v)
             }
-            walletkit_db::StoreError::Db(e) => Self::VaultDb(e.to_string()),
+            walletkit_db::StoreError::Db(e) => Self::VaultSqlite {
+                code: e.code.0,
+                message: e.message,
+            },
             walletkit_db::StoreError::IntegrityCheckFailed(s) => {
                 Self::CorruptedVault(s)
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use strum::EnumCount;
+
+    use super::*;
+
+    #[test]
+    #[expect(clippy::too_many_lines, reason = "one entry per variant")]
+    fn test_every_variant_is_classified() {
+        let all = [
+            (
+                StorageError::Keystore(String::new()),
+                ErrorSeverity::RequiresUserAction,
+                false,
+            ),
+            (
+                StorageError::BlobStore(String::new()),
+                ErrorSeverity::Transient,
+                false,
+            ),
+            (
+                StorageError::Lock(String::new()),
+                ErrorSeverity::Transient,
+                false,
+            ),
+            (
+                StorageError::Serialization(String::new()),
+                ErrorSeverity::Fatal,
+                false,
+            ),
+            (
+                StorageError::Crypto(String::new()),
+                ErrorSeverity::Fatal,
+                false,
+            ),
+            (
+                StorageError::InvalidEnvelope(String::new()),
+                ErrorSeverity::Fatal,
+                true,
+            ),
+            (
+                StorageError::UnsupportedEnvelopeVersion(2),
+                ErrorSeverity::RequiresUserAction,
+                false,
+            ),
+            (
+                StorageError::VaultDb(String::new()),
+                ErrorSeverity::Fatal,
+                false,
+            ),
+            (
+                StorageError::CacheDb(String::new()),
+                ErrorSeverity::Fatal,
+                false,
+            ),
+            (
+                StorageError::VaultSqlite {
+                    code: SQLITE_BUSY,
+                    message: String::new(),
+                },
+                ErrorSeverity::Transient,
+                false,
+            ),
+            (
+                StorageError::CacheSqlite {
+                    code: SQLITE_NOTADB,
+                    message: String::new(),
+                },
+                ErrorSeverity::Fatal,
+                true,
+            ),
+            (
+                StorageError::InvalidLeafIndex {
+                    expected: 1,
+                    provided: 2,
+                },
+                ErrorSeverity::RequiresUserAction,
+                false,
+            ),
+            (
+                StorageError::CorruptedVault(String::new()),
+                ErrorSeverity::Fatal,
+                true,
+            ),
+            (
+                StorageError::NotInitialized,
+                ErrorSeverity::RequiresUserAction,
+                false,
+            ),
+            (
+                StorageError::NullifierAlreadyDisclosed,
+                ErrorSeverity::RequiresUserAction,
+                false,
+            ),
+            (
+                StorageError::NullifierConflict {
+                    conflicting_request_id: [0; 32],
+                },
+                ErrorSeverity::RequiresUserAction,
+                false,
+            ),
+            (
+                StorageError::CredentialNotFound,
+                ErrorSeverity::RequiresUserAction,
+                false,
+            ),
+            (
+                StorageError::CredentialIdNotFound { credential_id: 7 },
+                ErrorSeverity::RequiresUserAction,
+                false,
+            ),
+            (
+                StorageError::CorruptedCacheEntry { key_prefix: 1 },
+                ErrorSeverity::Transient,
+                true,
+            ),
+            (
+                StorageError::UnexpectedUniFFICallbackError(String::new()),
+                ErrorSeverity::Fatal,
+                false,
+            ),
+            (
+                StorageError::UnknownRegistryKind(9),
+                ErrorSeverity::Fatal,
+                true,
+            ),
+            (
+                StorageError::InsufficientStorageQuota {
+                    available: 1,
+                    required: 2,
+                },
+                ErrorSeverity::RequiresUserAction,
+                false,
+            ),
+            (
+                StorageError::SecurePrefsLimit(String::new()),
+                ErrorSeverity::RequiresUserAction,
+                false,
+            ),
+            (StorageError::Closed, ErrorSeverity::Fatal, false),
+            (
+                StorageError::EntropyUnavailable(String::new()),
+                ErrorSeverity::Transient,
+                false,
+            ),
+        ];
+        // keep this list in sync when adding variants
+        assert_eq!(all.len(), StorageError::COUNT);
+
+        for (error, severity, is_corruption) in all {
+            assert_eq!(error.severity(), severity, "{error:?}");
+            assert_eq!(error.is_corruption(), is_corruption, "{error:?}");
+        }
+    }
+
+    #[test]
+    fn test_sqlite_errors_are_classified_by_result_code() {
+        let vault = |code| StorageError::VaultSqlite {
+            code,
+            message: String::new(),
+        };
+        let cache = |code| StorageError::CacheSqlite {
+            code,
+            message: String::new(),
+        };
+        for (code, severity, is_corruption) in [
+            (SQLITE_BUSY, ErrorSeverity::Transient, false),
+            // SQLITE_BUSY_SNAPSHOT
+            (517, ErrorSeverity::Transient, false),
+            (SQLITE_LOCKED, ErrorSeverity::Transient, false),
+            (SQLITE_FULL, ErrorSeverity::RequiresUserAction, false),
+            (SQLITE_CORRUPT, ErrorSeverity::Fatal, true),
+            // SQLITE_CORRUPT_INDEX
+            (779, ErrorSeverity::Fatal, true),
+            (SQLITE_NOTADB, ErrorSeverity::Fatal, true),
+            // SQLITE_IOERR, SQLITE_CANTOPEN
+            (10, ErrorSeverity::Fatal, false),
+            (14, ErrorSeverity::Fatal, false),
+        ] {
+            for error in [vault(code), cache(code)] {
+                assert_eq!(error.severity(), severity, "{error:?}");
+                assert_eq!(error.is_corruption(), is_corruption, "{error:?}");
+            }
+        }
+    }
+}
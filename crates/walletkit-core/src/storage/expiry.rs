@@ -0,0 +1,359 @@
+//! Credential expiry notifications.
+//!
+//! The vault only records `expires_at`; nothing fires when that moment passes.
+//! The watchers in this module periodically list credentials and report each
+//! credential that transitions from active to expired since the previous poll.
+//! Each watcher polls on its own thread, so the blocking vault queries never run
+//! on the caller's async executor and no particular runtime is required.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+
+use super::error::StorageResult;
+use super::traits::CredentialExpiryListener;
+use super::types::CredentialExpiryEvent;
+use super::CredentialStore;
+
+/// Capacity of the expiry broadcast channel. Lagging receivers skip the oldest
+/// events rather than blocking the watcher.
+const EXPIRY_CHANNEL_CAPACITY: usize = 64;
+
+/// Shortest poll interval the watchers accept. Shorter intervals, including
+/// zero, are raised to this so a host-supplied value cannot spin the poller.
+const MIN_EXPIRY_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Clock used by the watchers, returning seconds since the UNIX epoch.
+pub(crate) type Clock = Arc<dyn Fn() -> u64 + Send + Sync>;
+
+fn system_clock() -> Clock {
+    Arc::new(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+    })
+}
+
+/// Tracks the last-known expiry status of each credential between polls.
+#[derive(Default)]
+struct ExpiryTracker {
+    expired: HashMap<u64, bool>,
+}
+
+impl ExpiryTracker {
+    /// Lists credentials at `now` and returns the ones that became expired
+    /// since the previous poll. Credentials that are already expired when first
+    /// seen are not reported.
+    fn poll(
+        &mut self,
+        store: &CredentialStore,
+        now: u64,
+    ) -> StorageResult<Vec<CredentialExpiryEvent>> {
+        let records = store.list_credentials(None, now)?;
+        let mut events = Vec::new();
+        let mut expired = HashMap::with_capacity(records.len());
+        for record in records {
+            if record.is_expired
+                && self.expired.get(&record.credential_id) == Some(&false)
+            {
+                events.push(CredentialExpiryEvent {
+                    credential_id: record.credential_id,
+                    issuer_schema_id: record.issuer_schema_id,
+                    expired_at: record.expires_at,
+                });
+            }
+            expired.insert(record.credential_id, record.is_expired);
+        }
+        self.expired = expired;
+        Ok(events)
+    }
+}
+
+impl CredentialStore {
+    /// Watches stored credentials and broadcasts an event whenever one of them
+    /// expires.
+    ///
+    /// A background thread polls [`list_credentials`](Self::list_credentials)
+    /// every `poll_interval` (at least 50 ms). The thread holds only a weak
+    /// reference to the store and stops once every receiver has been dropped or
+    /// the store itself is dropped.
+    #[must_use]
+    pub fn watch_expiry_changes(
+        self: &Arc<Self>,
+        poll_interval: Duration,
+    ) -> broadcast::Receiver<CredentialExpiryEvent> {
+        self.watch_expiry_changes_with_clock(poll_interval, system_clock())
+    }
+
+    pub(crate) fn watch_expiry_changes_with_clock(
+        self: &Arc<Self>,
+        poll_interval: Duration,
+        clock: Clock,
+    ) -> broadcast::Receiver<CredentialExpiryEvent> {
+        let (tx, rx) = broadcast::channel(EXPIRY_CHANNEL_CAPACITY);
+        let store = Arc::downgrade(self);
+
+        if let Err(e) = std::thread::Builder::new()
+            .name("walletkit-expiry-watch".into())
+            .spawn(move || {
+                run_expiry_poller(
+                    &store,
+                    || tx.receiver_count() == 0,
+                    poll_interval,
+                    &clock,
+                    |event| {
+                        // only fails when all receivers are gone, checked before the next poll
+                        let _ = tx.send(event);
+                    },
+                );
+            })
+        {
+            tracing::error!("failed to spawn expiry watch thread: {e}");
+        }
+
+        rx
+    }
+}
+
+#[uniffi::export]
+impl CredentialStore {
+    /// Registers a listener that is called whenever a stored credential
+    /// expires, polling every `poll_interval_ms` milliseconds (at least 50).
+    ///
+    /// Only one listener can be active at a time — calling this replaces any
+    /// previously registered listener. Polling happens on a dedicated
+    /// background thread, which stops when the listener is replaced or the
+    /// store is dropped.
+    ///
+    /// The listener runs on that thread after each poll has released the
+    /// store, so it may call back into this `CredentialStore`. A slow listener
+    /// delays the next poll.
+    pub fn set_credential_expiry_listener(
+        self: Arc<Self>,
+        listener: Arc<dyn CredentialExpiryListener>,
+        poll_interval_ms: u64,
+    ) {
+        let stop = Arc::new(AtomicBool::new(false));
+        let store = Arc::downgrade(&self);
+        let thread_stop = Arc::clone(&stop);
+        let poll_interval = Duration::from_millis(poll_interval_ms);
+
+        match std::thread::Builder::new()
+            .name("walletkit-expiry-notify".into())
+            .spawn(move || {
+                run_expiry_poller(
+                    &store,
+                    || thread_stop.load(Ordering::SeqCst),
+                    poll_interval,
+                    &system_clock(),
+                    |event| listener.on_credential_expiry(event),
+                );
+            }) {
+            Ok(_) => {
+                if let Ok(mut guard) = self.expiry_listener_stop.lock() {
+                    if let Some(previous) = guard.replace(stop) {
+                        previous.store(true, Ordering::SeqCst);
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::error!("failed to spawn expiry notification thread: {e}");
+            }
+        }
+    }
+}
+
+/// Polls `store` until `stopped` returns true or the store is dropped,
+/// delivering each expiry event to `deliver`.
+fn run_expiry_poller(
+    store: &Weak<CredentialStore>,
+    stopped: impl Fn() -> bool,
+    poll_interval: Duration,
+    clock: &Clock,
+    mut deliver: impl FnMut(CredentialExpiryEvent),
+) {
+    let poll_interval = poll_interval.max(MIN_EXPIRY_POLL_INTERVAL);
+    let mut tracker = ExpiryTracker::default();
+    while !stopped() {
+        let Some(store) = store.upgrade() else {
+            break;
+        };
+        let result = tracker.poll(&store, clock());
+        // release the store before delivering so a dropped store is noticed
+        drop(store);
+        match result {
+            Ok(events) => events.into_iter().for_each(&mut deliver),
+            Err(e) => tracing::warn!("credential expiry poll failed: {e}"),
+        }
+        std::thread::sleep(poll_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+    use std::sync::Mutex;
+
+    use world_id_core::Credential as CoreCredential;
+
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+    use crate::{Credential, FieldElement};
+
+    fn store_with_credential(
+        root: &std::path::Path,
+        expires_at: u64,
+    ) -> (Arc<CredentialStore>, u64) {
+        let provider = InMemoryStorageProvider::new(root);
+        let store =
+            Arc::new(CredentialStore::from_provider(&provider).expect("create store"));
+        store.init(42, 1000).expect("init storage");
+
+        let credential: Credential = CoreCredential::new()
+            .issuer_schema_id(7)
+            .genesis_issued_at(1000)
+            .into();
+        let credential_id = store
+            .store_credential(
+                &credential,
+                &FieldElement::from(1u64),
+                expires_at,
+                None,
+                1000,
+            )
+            .expect("store credential");
+        (store, credential_id)
+    }
+
+    fn test_clock(now: &Arc<AtomicU64>) -> Clock {
+        let now = Arc::clone(now);
+        Arc::new(move || now.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn test_watch_expiry_changes_emits_event() {
+        let root = temp_root_path();
+        let (store, credential_id) = store_with_credential(&root, 1001);
+        let now = Arc::new(AtomicU64::new(1000));
+
+        // a zero interval is raised to the minimum instead of spinning
+        let mut rx =
+            store.watch_expiry_changes_with_clock(Duration::ZERO, test_clock(&now));
+
+        // the credential expires 100 ms after the watcher starts
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        now.store(1001, Ordering::SeqCst);
+
+        let event = tokio::time::timeout(Duration::from_millis(200), rx.recv())
+            .await
+            .expect("expiry event within 200 ms")
+            .expect("receive expiry event");
+        assert_eq!(
+            event,
+            CredentialExpiryEvent {
+                credential_id,
+                issuer_schema_id: 7,
+                expired_at: 1001,
+            }
+        );
+
+        // expired credentials are only reported once
+        let next = tokio::time::timeout(Duration::from_millis(100), rx.recv()).await;
+        assert!(next.is_err());
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_tracker_ignores_already_expired_credentials() {
+        let root = temp_root_path();
+        let (store, _) = store_with_credential(&root, 1001);
+        let mut tracker = ExpiryTracker::default();
+
+        assert!(tracker.poll(&store, 2000).expect("poll").is_empty());
+        assert!(tracker.poll(&store, 2001).expect("poll").is_empty());
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_credentials_expiring_within() {
+        let root = temp_root_path();
+        let (store, credential_id) = store_with_credential(&root, 2000);
+
+        assert!(store
+            .credentials_expiring_within(999, 1000)
+            .expect("query")
+            .is_empty());
+        let expiring = store
+            .credentials_expiring_within(1000, 1000)
+            .expect("query");
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].credential_id, credential_id);
+        // expired credentials are no longer expiring
+        assert!(store
+            .credentials_expiring_within(1000, 2000)
+            .expect("query")
+            .is_empty());
+
+        cleanup_test_storage(&root);
+    }
+
+    struct RecordingListener(Mutex<Vec<CredentialExpiryEvent>>);
+
+    impl CredentialExpiryListener for RecordingListener {
+        fn on_credential_expiry(&self, event: CredentialExpiryEvent) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    #[test]
+    fn test_expiry_listener_receives_event() {
+        let root = temp_root_path();
+        let (store, credential_id) = store_with_credential(&root, 1001);
+        let now = Arc::new(AtomicU64::new(1000));
+        let listener = RecordingListener(Mutex::new(Vec::new()));
+        let stop = AtomicBool::new(false);
+
+        std::thread::scope(|s| {
+            let weak = Arc::downgrade(&store);
+            let clock = test_clock(&now);
+            let (listener, stop) = (&listener, &stop);
+            s.spawn(move || {
+                run_expiry_poller(
+                    &weak,
+                    || stop.load(Ordering::SeqCst),
+                    Duration::from_millis(10),
+                    &clock,
+                    |event| {
+                        // the listener may call back into the store
+                        let store = weak.upgrade().expect("store alive");
+                        store.list_credentials(None, 1001).expect("list");
+                        listener.on_credential_expiry(event);
+                    },
+                );
+            });
+
+            std::thread::sleep(Duration::from_millis(50));
+            now.store(1001, Ordering::SeqCst);
+            let deadline = std::time::Instant::now() + Duration::from_secs(1);
+            while listener.0.lock().unwrap().is_empty()
+                && std::time::Instant::now() < deadline
+            {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            stop.store(true, Ordering::SeqCst);
+        });
+
+        let events = listener.0.into_inner().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].credential_id, credential_id);
+
+        cleanup_test_storage(&root);
+    }
+}
@@ -2,12 +2,9 @@
 
 use std::{
     fs,
-    io::Read,
     path::{Path, PathBuf},
 };
 
-use sha2::{Digest, Sha256};
-
 use super::{StorageError, StoragePaths, StorageResult};
 
 /// Writes embedded Groth16 material to the cache paths managed by [`StoragePaths`].
@@ -59,12 +56,10 @@ fn has_valid_cached_material(paths: &StoragePaths) -> StorageResult<bool> {
     ];
 
     for (path, expected_fingerprint) in entries {
-        if !path.is_file() {
-            return Ok(false);
-        }
-
-        let actual_fingerprint = file_sha256_hex(&path)?;
-        if actual_fingerprint != expected_fingerprint {
+        // hashed once per process, after which the marker is trusted
+        let verified = crate::artifacts::verify_file(&path, expected_fingerprint)
+            .map_err(|error| StorageError::CacheDb(error.to_string()))?;
+        if !verified {
             return Ok(false);
         }
     }
@@ -72,24 +67,6 @@ fn has_valid_cached_material(paths: &StoragePaths) -> StorageResult<bool> {
     Ok(true)
 }
 
-fn file_sha256_hex(path: &Path) -> StorageResult<String> {
-    let mut file = fs::File::open(path)
-        .map_err(|error| StorageError::CacheDb(error.to_string()))?;
-    let mut hasher = Sha256::new();
-    let mut buffer = [0u8; 16 * 1024];
-    loop {
-        let bytes_read = file
-            .read(&mut buffer)
-            .map_err(|error| StorageError::CacheDb(error.to_string()))?;
-        if bytes_read == 0 {
-            break;
-        }
-        hasher.update(&buffer[..bytes_read]);
-    }
-
-    Ok(hex::encode(hasher.finalize()))
-}
-
 fn write_atomic(path: &Path, bytes: &[u8]) -> StorageResult<()> {
     let tmp_path = PathBuf::from(format!("{}.tmp", path.to_string_lossy()));
     fs::write(&tmp_path, bytes)
@@ -0,0 +1,172 @@
+//! Keyless identification of storage files, for support.
+//!
+//! Given a file from a pulled app-data directory, [`inspect_file`] tells
+//! whether it is a vault, a cache, a key envelope, or something else, and
+//! which version, without any keys.
+//!
+//! The vault and cache set `PRAGMA application_id` to [`VAULT_APPLICATION_ID`]
+//! and [`CACHE_APPLICATION_ID`] and track their schema in
+//! `PRAGMA user_version`. Both are in the clear in plaintext vault backups.
+//! In the encrypted databases they are encrypted with the rest of the first
+//! page, so those are only recognised as encrypted databases and named after
+//! their file name. Files written before the application IDs were set report
+//! an application ID of 0 and are treated the same way.
+
+use std::path::Path;
+
+use walletkit_db::inspect::FileFormat;
+
+use super::paths::{CACHE_FILENAME, VAULT_FILENAME};
+use super::{CACHE_APPLICATION_ID, VAULT_APPLICATION_ID};
+use crate::error::WalletKitError;
+
+/// What kind of storage file [`inspect_file`] found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum StorageFileKind {
+    /// The credential vault, or a plaintext backup of it.
+    Vault,
+    /// The cache database.
+    Cache,
+    /// A sealed key envelope, e.g. `account_keys.bin`.
+    KeyEnvelope,
+    /// A database that is neither the vault nor the cache, or an encrypted
+    /// one whose file name does not say which it is.
+    OtherDatabase,
+    /// Not a storage file.
+    Unknown,
+}
+
+/// The result of [`inspect_file`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct FileInspection {
+    /// What the file is.
+    pub kind: StorageFileKind,
+    /// Whether the file's contents are encrypted.
+    pub encrypted: bool,
+    /// The schema version of a plaintext database (`PRAGMA user_version`) or
+    /// the format version of a key envelope. `None` if it cannot be read
+    /// without keys.
+    pub version: Option<u32>,
+    /// Seconds since the UNIX epoch when the file was created, if recorded
+    /// in the clear.
+    pub created_at: Option<u64>,
+}
+
+/// Identifies the storage file at `path` without any keys.
+///
+/// # Errors
+/// Returns [`WalletKitError::Generic`] if the file cannot be read.
+#[uniffi::export]
+pub fn inspect_file(path: &str) -> Result<FileInspection, WalletKitError> {
+    let path = Path::new(path);
+    let format = walletkit_db::inspect::inspect_file(path).map_err(|error| {
+        WalletKitError::Generic {
+            error: format!("failed to inspect {}: {error}", path.display()),
+        }
+    })?;
+    let kind_from_name = || match path.file_name().and_then(|name| name.to_str()) {
+        Some(VAULT_FILENAME) => StorageFileKind::Vault,
+        Some(CACHE_FILENAME) => StorageFileKind::Cache,
+        _ => StorageFileKind::OtherDatabase,
+    };
+
+    Ok(match format {
+        FileFormat::PlaintextDatabase {
+            application_id,
+            user_version,
+        } => FileInspection {
+            kind: match application_id {
+                VAULT_APPLICATION_ID => StorageFileKind::Vault,
+                CACHE_APPLICATION_ID => StorageFileKind::Cache,
+                0 => kind_from_name(),
+                _ => StorageFileKind::OtherDatabase,
+            },
+            encrypted: false,
+            version: Some(user_version),
+            created_at: None,
+        },
+        FileFormat::EncryptedDatabase { .. } => FileInspection {
+            kind: kind_from_name(),
+            encrypted: true,
+            version: None,
+            created_at: None,
+        },
+        FileFormat::KeyEnvelope {
+            version,
+            created_at,
+            ..
+        } => FileInspection {
+            kind: StorageFileKind::KeyEnvelope,
+            encrypted: true,
+            version: Some(version),
+            created_at: Some(created_at),
+        },
+        FileFormat::Unknown => FileInspection {
+            kind: StorageFileKind::Unknown,
+            encrypted: false,
+            version: None,
+            created_at: None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::RngCore;
+
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+    use crate::storage::{CredentialStore, StoragePaths, StorageProvider};
+
+    fn inspect(path: &Path) -> FileInspection {
+        inspect_file(path.to_str().expect("utf-8 path")).expect("inspect")
+    }
+
+    #[test]
+    fn test_inspect_storage_files() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("store");
+        store.init(1, 100).expect("init storage");
+        let backup = store.export_vault_for_backup().expect("export backup");
+        drop(store);
+
+        let paths = StoragePaths::new(&root);
+        let vault = inspect(&paths.vault_db_path());
+        assert_eq!(vault.kind, StorageFileKind::Vault);
+        assert!(vault.encrypted);
+        assert_eq!(vault.version, None);
+        assert_eq!(inspect(&paths.cache_db_path()).kind, StorageFileKind::Cache);
+
+        let backup_path = root.join("vault-backup.sqlite");
+        std::fs::write(&backup_path, backup).expect("write backup");
+        let backup = inspect(&backup_path);
+        assert_eq!(backup.kind, StorageFileKind::Vault);
+        assert!(!backup.encrypted);
+        assert!(backup.version.is_some_and(|version| version > 0));
+
+        let envelope_path = root.join(crate::storage::ACCOUNT_KEYS_FILENAME);
+        let envelope = provider
+            .blob_store()
+            .read(crate::storage::ACCOUNT_KEYS_FILENAME.to_string())
+            .expect("read envelope")
+            .expect("envelope exists");
+        std::fs::write(&envelope_path, envelope).expect("write envelope");
+        let envelope = inspect(&envelope_path);
+        assert_eq!(envelope.kind, StorageFileKind::KeyEnvelope);
+        assert_eq!(envelope.version, Some(1));
+        assert!(envelope.created_at.is_some());
+
+        let garbage_path = root.join("garbage.bin");
+        let mut garbage = vec![0u8; 4096];
+        rand::rngs::OsRng.fill_bytes(&mut garbage);
+        std::fs::write(&garbage_path, garbage).expect("write garbage");
+        assert_eq!(inspect(&garbage_path).kind, StorageFileKind::Unknown);
+
+        assert!(inspect_file(root.join("missing").to_str().unwrap()).is_err());
+
+        cleanup_test_storage(&root);
+    }
+}
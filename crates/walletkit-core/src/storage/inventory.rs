@@ -0,0 +1,181 @@
+//! Inventory of the data `WalletKit` persists on device, for privacy
+//! disclosures such as app store privacy questionnaires and data subject
+//! access requests.
+//!
+//! Each storage module lists what it persists in an `INVENTORY` constant next
+//! to the code that writes it; [`data_inventory`] collects them. A test checks
+//! that every table the vault and cache schemas create is listed, so a new
+//! table cannot be left out.
+
+/// How long `WalletKit` keeps a category of data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum RetentionPolicy {
+    /// Kept until the holder or the host deletes it, at the latest until the
+    /// storage is destroyed.
+    UntilDeleted,
+    /// Expires `max_age_seconds` after it was written. Expired data is deleted
+    /// the next time the database holding it is written to.
+    Expiring {
+        /// Time after which the data expires, in seconds.
+        max_age_seconds: u64,
+    },
+    /// Deleted by `WalletKit` once the operation that wrote it completes.
+    Transient,
+}
+
+/// A category of data `WalletKit` persists on device, see [`data_inventory`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct DataCategory {
+    /// Stable identifier of the category, e.g. `credentials`.
+    pub name: String,
+    /// What the data is and why it is kept.
+    pub description: String,
+    /// Where the data is kept. Files are paths relative to the storage root
+    /// (see [`StoragePaths`](super::StoragePaths)) and database tables are
+    /// `<file>:<table>`. Names without a directory are entries of the host's
+    /// [`AtomicBlobStore`](super::AtomicBlobStore), whose location the host
+    /// determines.
+    pub tables_or_files: Vec<String>,
+    /// Whether the data relates to the holder, as opposed to public protocol
+    /// data or configuration.
+    pub contains_personal_data: bool,
+    /// How long the data is kept.
+    pub retention: RetentionPolicy,
+}
+
+/// An entry of a module's `INVENTORY`.
+pub(crate) struct InventoryEntry {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub locations: &'static [&'static str],
+    pub contains_personal_data: bool,
+    pub retention: RetentionPolicy,
+}
+
+impl From<&InventoryEntry> for DataCategory {
+    fn from(entry: &InventoryEntry) -> Self {
+        Self {
+            name: entry.name.to_string(),
+            description: entry.description.to_string(),
+            tables_or_files: entry.locations.iter().map(ToString::to_string).collect(),
+            contains_personal_data: entry.contains_personal_data,
+            retention: entry.retention,
+        }
+    }
+}
+
+/// Returns every category of data `WalletKit` persists on device, e.g. for an
+/// in-app privacy screen.
+#[uniffi::export]
+#[must_use]
+pub fn data_inventory() -> Vec<DataCategory> {
+    let mut entries: Vec<&InventoryEntry> = Vec::new();
+    entries.extend(super::credential_vault::INVENTORY);
+    entries.extend(super::cache::INVENTORY);
+    entries.extend(super::keys::INVENTORY);
+    entries.extend(super::paths::INVENTORY);
+    #[cfg(not(target_arch = "wasm32"))]
+    entries.extend(super::credential_storage::INVENTORY);
+    #[cfg(feature = "v3")]
+    entries.extend(crate::migration::INVENTORY);
+    entries.into_iter().map(DataCategory::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use secrecy::SecretBox;
+    use walletkit_db::{Connection, StepResult, Vault};
+
+    use super::*;
+    use crate::storage::tests_utils::{cleanup_test_storage, temp_root_path};
+    use crate::storage::{CacheDb, CredentialVault, StorageLock, StoragePaths};
+
+    fn tables(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT name FROM sqlite_master
+                 WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+            )
+            .expect("prepare");
+        let mut tables = Vec::new();
+        while let StepResult::Row(row) = stmt.step().expect("step") {
+            tables.push(row.column_text(0));
+        }
+        tables
+    }
+
+    /// Lists the tables of the database at `path`, as `<file>:<table>`.
+    fn locations(
+        paths: &StoragePaths,
+        path: &std::path::Path,
+        key: &SecretBox<[u8; 32]>,
+    ) -> Vec<String> {
+        let vault = Vault::open(path, key, |_| Ok(())).expect("open database");
+        let file = path.strip_prefix(paths.root()).expect("under the root");
+        tables(vault.connection())
+            .into_iter()
+            .map(|table| format!("{}:{table}", file.display()))
+            .collect()
+    }
+
+    #[test]
+    fn test_inventory_lists_every_table() {
+        let root = temp_root_path();
+        let paths = StoragePaths::new(&root);
+        std::fs::create_dir_all(paths.worldid_dir()).expect("create worldid dir");
+        let key = SecretBox::init_with(|| [0x11u8; 32]);
+        let listed: HashSet<String> = data_inventory()
+            .into_iter()
+            .flat_map(|category| category.tables_or_files)
+            .collect();
+
+        drop(CredentialVault::new(&paths.vault_db_path(), &key).expect("vault"));
+        drop(CacheDb::new(&paths.cache_db_path(), &key).expect("cache"));
+        let mut created = locations(&paths, &paths.vault_db_path(), &key);
+        created.extend(locations(&paths, &paths.cache_db_path(), &key));
+
+        // the multi-tenant vault may add tables of its own
+        let multi_tenant_path = paths.worldid_dir().join("multi_tenant.sqlite");
+        let lock = StorageLock::open(&paths.lock_path()).expect("lock");
+        let guard = lock.lock().expect("lock");
+        drop(
+            CredentialVault::new_multi_tenant(&multi_tenant_path, &key, &guard)
+                .expect("multi-tenant vault"),
+        );
+        created.extend(locations(&paths, &multi_tenant_path, &key).into_iter().map(
+            |location| {
+                location.replace(
+                    "multi_tenant.sqlite",
+                    &paths
+                        .vault_db_path()
+                        .file_name()
+                        .expect("file name")
+                        .to_string_lossy(),
+                )
+            },
+        ));
+
+        let missing: Vec<_> = created
+            .iter()
+            .filter(|location| !listed.contains(*location))
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "missing from the inventory: {missing:?}"
+        );
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_inventory_names_are_unique() {
+        let inventory = data_inventory();
+        let names: HashSet<_> =
+            inventory.iter().map(|category| &category.name).collect();
+        assert_eq!(names.len(), inventory.len());
+        assert!(inventory
+            .iter()
+            .all(|category| !category.tables_or_files.is_empty()));
+    }
+}
@@ -6,16 +6,28 @@
 //! `K_intermediate` hierarchy, envelope sealing, and encryption are described in the
 //! `walletkit-db` README.
 
-use secrecy::SecretBox;
+use secrecy::{ExposeSecretMut, SecretBox};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use super::{
     error::StorageResult,
+    inventory::{InventoryEntry, RetentionPolicy},
     traits::{AtomicBlobStore, DeviceKeystore},
-    ACCOUNT_KEYS_FILENAME, ACCOUNT_KEY_ENVELOPE_AD,
+    ACCOUNT_KEYS_FILENAME, ACCOUNT_KEY_ENVELOPE_AD, ACCOUNT_PENDING_KEYS_FILENAME,
 };
 use walletkit_db::Lock;
 
+/// What key management stores, see
+/// [`data_inventory`](super::inventory::data_inventory).
+pub(crate) const INVENTORY: &[InventoryEntry] = &[InventoryEntry {
+    name: "storage_keys",
+    description: "The key the vault and cache databases are encrypted with, \
+        sealed by a device keystore.",
+    locations: &[ACCOUNT_KEYS_FILENAME, ACCOUNT_PENDING_KEYS_FILENAME],
+    contains_personal_data: false,
+    retention: RetentionPolicy::UntilDeleted,
+}];
+
 /// In-memory account keys derived from the account key envelope.
 ///
 /// Keys are held in memory for the lifetime of the storage handle.
@@ -49,6 +61,112 @@ impl StorageKeys {
         Ok(Self { intermediate_key })
     }
 
+    /// Opens the existing account key envelope without creating one.
+    ///
+    /// Returns `None` if there is no envelope.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the envelope cannot be read, decrypted, or parsed.
+    pub fn open(
+        keystore: &dyn DeviceKeystore,
+        blob_store: &dyn AtomicBlobStore,
+        lock: &Lock,
+    ) -> StorageResult<Option<Self>> {
+        let intermediate_key = walletkit_db::open_envelope_key(
+            &Ks(keystore),
+            &Bs(blob_store),
+            lock,
+            ACCOUNT_KEYS_FILENAME,
+            ACCOUNT_KEY_ENVELOPE_AD,
+        )?;
+        Ok(intermediate_key.map(|intermediate_key| Self { intermediate_key }))
+    }
+
+    /// Re-seals `K_intermediate` under `new_keystore` and replaces the account
+    /// key envelope. The vault and cache keep their key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sealing fails, the new envelope does not open under
+    /// `new_keystore`, or persistence to the blob store fails.
+    pub fn rewrap(
+        &self,
+        new_keystore: &dyn DeviceKeystore,
+        blob_store: &dyn AtomicBlobStore,
+        lock: &Lock,
+        now: u64,
+    ) -> StorageResult<()> {
+        walletkit_db::rewrap_envelope_key(
+            &Ks(new_keystore),
+            &Bs(blob_store),
+            lock,
+            ACCOUNT_KEYS_FILENAME,
+            ACCOUNT_KEY_ENVELOPE_AD,
+            &self.intermediate_key,
+            now,
+        )?;
+        Ok(())
+    }
+
+    /// Generates a new random `K_intermediate` that is not sealed anywhere
+    /// yet, for [`CredentialStore::rekey_vault`](super::CredentialStore::rekey_vault).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the system entropy source fails.
+    pub(super) fn generate() -> StorageResult<Self> {
+        let mut intermediate_key = SecretBox::new(Box::new([0u8; 32]));
+        super::entropy::fill(intermediate_key.expose_secret_mut())?;
+        Ok(Self { intermediate_key })
+    }
+
+    /// Seals `K_intermediate` into the pending envelope, which holds the new
+    /// key while the databases are re-encrypted under it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sealing fails or persistence to the blob store
+    /// fails.
+    pub(super) fn seal_pending(
+        &self,
+        keystore: &dyn DeviceKeystore,
+        blob_store: &dyn AtomicBlobStore,
+        lock: &Lock,
+        now: u64,
+    ) -> StorageResult<()> {
+        walletkit_db::rewrap_envelope_key(
+            &Ks(keystore),
+            &Bs(blob_store),
+            lock,
+            ACCOUNT_PENDING_KEYS_FILENAME,
+            ACCOUNT_KEY_ENVELOPE_AD,
+            &self.intermediate_key,
+            now,
+        )?;
+        Ok(())
+    }
+
+    /// Opens the pending envelope left by an interrupted rekey, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the envelope cannot be read, decrypted, or parsed.
+    pub(super) fn open_pending(
+        keystore: &dyn DeviceKeystore,
+        blob_store: &dyn AtomicBlobStore,
+        lock: &Lock,
+    ) -> StorageResult<Option<Self>> {
+        let intermediate_key = walletkit_db::open_envelope_key(
+            &Ks(keystore),
+            &Bs(blob_store),
+            lock,
+            ACCOUNT_PENDING_KEYS_FILENAME,
+            ACCOUNT_KEY_ENVELOPE_AD,
+        )?;
+        Ok(intermediate_key.map(|intermediate_key| Self { intermediate_key }))
+    }
+
     /// Returns a reference to the intermediate key's [`SecretBox`].
     #[must_use]
     pub const fn intermediate_key(&self) -> &SecretBox<[u8; 32]> {
@@ -112,6 +230,7 @@ mod tests {
     use super::*;
     use crate::storage::error::StorageError;
     use crate::storage::tests_utils::{InMemoryBlobStore, InMemoryKeystore};
+    use crate::storage::DeterministicKeystore;
     use secrecy::ExposeSecret;
     use uuid::Uuid;
     use walletkit_db::Lock;
@@ -189,4 +308,42 @@ mod tests {
         }
         let _ = std::fs::remove_file(lock_path);
     }
+
+    #[test]
+    fn test_storage_keys_opens_golden_envelope() {
+        let keystore = InMemoryKeystore::with_key([0x11; 32]);
+        let wrapped = keystore
+            .seal_with_nonce(ACCOUNT_KEY_ENVELOPE_AD, &[0x42; 32], &[0x07; 24])
+            .expect("seal");
+        // nonce || ciphertext || tag
+        assert_eq!(
+            hex::encode(&wrapped),
+            "070707070707070707070707070707070707070707070707\
+             4da798bf160637949d78c343ea4919cf71c1ff01b5beb880c56ff3ba324fa89f\
+             48534d8a88dfc90d49e455472f6c53b4"
+        );
+
+        // same layout as `walletkit_db`'s `KeyEnvelope`
+        let envelope = ciborium::Value::Map(vec![
+            (ciborium::Value::Text("version".into()), 1.into()),
+            (
+                ciborium::Value::Text("wrapped_k_intermediate".into()),
+                ciborium::Value::Array(wrapped.into_iter().map(Into::into).collect()),
+            ),
+            (ciborium::Value::Text("created_at".into()), 100.into()),
+            (ciborium::Value::Text("updated_at".into()), 100.into()),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&envelope, &mut bytes).expect("encode envelope");
+        let blob_store = InMemoryBlobStore::new();
+        blob_store
+            .write_atomic(ACCOUNT_KEYS_FILENAME.to_string(), bytes)
+            .expect("write");
+
+        let lock_path = temp_lock_path();
+        let lock = Lock::open(&lock_path).expect("open lock");
+        let keys = StorageKeys::init(&keystore, &blob_store, &lock, 200).expect("init");
+        assert_eq!(keys.intermediate_key.expose_secret(), &[0x42; 32]);
+        let _ = std::fs::remove_file(lock_path);
+    }
 }
@@ -0,0 +1,114 @@
+//! Copying credentials out of a store left behind by an older app version.
+//!
+//! Unlike [`CredentialStore::import_vault_from_backup`], which restores a
+//! plaintext copy of the whole vault, the import goes credential by
+//! credential through the public store API, so it works across vault schema
+//! versions and reports per-credential failures instead of aborting.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::error::{StorageError, StorageResult};
+use super::types::{CredentialStatus, MigrationReport};
+use super::CredentialStore;
+
+#[uniffi::export]
+impl CredentialStore {
+    /// Imports the usable credentials of `legacy` into this store.
+    ///
+    /// Expired and invalidated credentials are skipped, as are older
+    /// credentials of an issuer schema that has a newer one. Associated data
+    /// is not carried over. Both stores must be initialized for the same
+    /// account.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either store is not initialized, the stores belong
+    /// to different accounts, or `legacy` cannot be listed. Failures on
+    /// individual credentials are reported in [`MigrationReport::errors`].
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn import_from_legacy_store(
+        &self,
+        legacy: Arc<Self>,
+        now: u64,
+    ) -> StorageResult<MigrationReport> {
+        self.import_from_legacy(&legacy, now, false)
+    }
+
+    /// Like [`import_from_legacy_store`](Self::import_from_legacy_store), but
+    /// only reads `legacy` and returns the report without writing anything.
+    ///
+    /// # Errors
+    ///
+    /// See [`import_from_legacy_store`](Self::import_from_legacy_store).
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn import_from_legacy_store_dry_run(
+        &self,
+        legacy: Arc<Self>,
+        now: u64,
+    ) -> StorageResult<MigrationReport> {
+        self.import_from_legacy(&legacy, now, true)
+    }
+}
+
+impl CredentialStore {
+    fn import_from_legacy(
+        &self,
+        legacy: &Self,
+        now: u64,
+        dry_run: bool,
+    ) -> StorageResult<MigrationReport> {
+        let expected = self.leaf_index()?;
+        let provided = legacy.leaf_index()?;
+        if expected != provided {
+            return Err(StorageError::InvalidLeafIndex { expected, provided });
+        }
+
+        let mut report = MigrationReport::default();
+        let mut seen_schemas = HashSet::new();
+        // listed most recently updated first, the credential `get_credential`
+        // returns for a schema comes before the ones it supersedes
+        for record in legacy.list_credentials(None, now)? {
+            if record.status != CredentialStatus::Active
+                || !seen_schemas.insert(record.issuer_schema_id)
+            {
+                report.skipped += 1;
+                continue;
+            }
+            let (credential, blinding_factor) = match legacy
+                .get_credential_uncached(record.issuer_schema_id, now)
+            {
+                Ok(Some(loaded)) => loaded,
+                Ok(None) => {
+                    report.errors.push(format!(
+                        "credential {}: listed but not loadable",
+                        record.credential_id
+                    ));
+                    continue;
+                }
+                Err(err) => {
+                    report
+                        .errors
+                        .push(format!("credential {}: {err}", record.credential_id));
+                    continue;
+                }
+            };
+            if !dry_run {
+                if let Err(err) = self.store_credential(
+                    &credential,
+                    &blinding_factor,
+                    record.expires_at,
+                    None,
+                    now,
+                ) {
+                    report
+                        .errors
+                        .push(format!("credential {}: {err}", record.credential_id));
+                    continue;
+                }
+            }
+            report.migrated += 1;
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,271 @@
+//! Aggregate activity statistics of a [`CredentialStore`], computed on the
+//! device for a "your World ID activity" screen.
+//!
+//! The output only holds counts. It never carries a nullifier, request ID,
+//! RP ID or address, so hosts may show it, or send it to their analytics
+//! with the user's consent, without further filtering. Fields added to
+//! [`LocalStats`] must keep it that way.
+
+use std::collections::BTreeMap;
+
+use super::credential_vault::UsageCounter;
+use super::error::StorageResult;
+use super::types::CredentialStatus;
+use super::CredentialStore;
+
+/// Activity statistics returned by [`CredentialStore::local_stats`].
+///
+/// Aggregate counts only; see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct LocalStats {
+    /// Proofs generated in the requested window, counted from the replay
+    /// guard. Repeated proofs for the same nullifier count once.
+    pub proofs_generated: u64,
+    /// Active credentials held at `now`, per issuer schema, in ascending
+    /// issuer schema order.
+    pub credentials_by_schema: Vec<SchemaHoldings>,
+    /// Merkle inclusion proof lookups answered from the cache, since the
+    /// vault was created.
+    pub cache_hits: u64,
+    /// Merkle inclusion proof lookups without a usable cached proof, since
+    /// the vault was created.
+    pub cache_misses: u64,
+}
+
+/// Number of active credentials held for one issuer schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Record)]
+pub struct SchemaHoldings {
+    /// The issuer schema.
+    pub issuer_schema_id: u64,
+    /// Active credentials of this schema.
+    pub count: u64,
+}
+
+impl LocalStats {
+    /// Fraction of Merkle inclusion proof lookups answered from the cache,
+    /// or `None` if there were none.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+}
+
+#[uniffi::export]
+impl CredentialStore {
+    /// Computes aggregate activity statistics, with proofs counted over the
+    /// window `[since, now)`.
+    ///
+    /// Proof counts come from the replay guard in the cache, so they reset
+    /// if the cache is rebuilt and only reach back as far as its one-year
+    /// retention. Cache hit counters are kept in the vault.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or a query fails.
+    pub fn local_stats(&self, since: u64, now: u64) -> StorageResult<LocalStats> {
+        self.with_databases(|vault, cache| {
+            let mut holdings = BTreeMap::<u64, u64>::new();
+            for record in vault.list_credentials(None, now)? {
+                if record.status == CredentialStatus::Active {
+                    *holdings.entry(record.issuer_schema_id).or_default() += 1;
+                }
+            }
+            Ok(LocalStats {
+                proofs_generated: cache.count_replay_guard_entries(since, now)?,
+                credentials_by_schema: holdings
+                    .into_iter()
+                    .map(|(issuer_schema_id, count)| SchemaHoldings {
+                        issuer_schema_id,
+                        count,
+                    })
+                    .collect(),
+                cache_hits: vault.usage_counter(UsageCounter::MerkleCacheHit)?,
+                cache_misses: vault.usage_counter(UsageCounter::MerkleCacheMiss)?,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use world_id_core::primitives::merkle::{
+        AccountInclusionProof, MerkleInclusionProof,
+    };
+    use world_id_core::primitives::{AuthenticatorPublicKeySet, TREE_DEPTH};
+    use world_id_core::{
+        Credential as CoreCredential, FieldElement as CoreFieldElement,
+    };
+
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider, TEST_ENVIRONMENT,
+    };
+    use crate::storage::{NewConsent, RegistryKind};
+    use crate::{Credential, FieldElement};
+
+    fn store_credential(
+        store: &CredentialStore,
+        issuer_schema_id: u64,
+        expires_at: u64,
+    ) {
+        let credential: Credential = CoreCredential::new()
+            .issuer_schema_id(issuer_schema_id)
+            .genesis_issued_at(1000)
+            .into();
+        store
+            .store_credential(
+                &credential,
+                &FieldElement::from(7u64),
+                expires_at,
+                None,
+                1000,
+            )
+            .expect("store credential");
+    }
+
+    fn prove(store: &CredentialStore, nullifier: u64, now: u64) {
+        let consent = NewConsent {
+            request_id: "request",
+            rp_id_hash: &[0u8; 32],
+            disclosed_schema_ids: &[],
+            consent_text_hash: None,
+        };
+        store
+            .finalize_proof(
+                TEST_ENVIRONMENT,
+                CoreFieldElement::from(nullifier),
+                consent,
+                None,
+                now,
+            )
+            .expect("finalize proof");
+    }
+
+    #[test]
+    fn test_local_stats_aggregates_activity() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("store");
+        store.init(42, 1000).expect("init storage");
+
+        store_credential(&store, 2, 9999);
+        store_credential(&store, 1, 9999);
+        store_credential(&store, 2, 9999);
+        store_credential(&store, 3, 1500);
+        for (nullifier, now) in [(1, 1000), (2, 1100), (2, 1200), (3, 1300)] {
+            prove(&store, nullifier, now);
+        }
+
+        assert!(store
+            .merkle_cache_get(RegistryKind::AccountRegistry, 1000)
+            .unwrap()
+            .is_none());
+        let account_inclusion_proof = AccountInclusionProof {
+            inclusion_proof: MerkleInclusionProof::new(
+                CoreFieldElement::from(123u64),
+                42,
+                [CoreFieldElement::from(0u64); TREE_DEPTH],
+            ),
+            authenticator_pubkeys: AuthenticatorPublicKeySet::new(vec![])
+                .expect("key set"),
+        };
+        store
+            .merkle_cache_put(
+                RegistryKind::AccountRegistry,
+                &account_inclusion_proof,
+                1000,
+                600,
+            )
+            .expect("cache put");
+        for _ in 0..3 {
+            assert!(store
+                .merkle_cache_get(RegistryKind::AccountRegistry, 1010)
+                .unwrap()
+                .is_some());
+        }
+
+        let stats = store.local_stats(1050, 2000).expect("local stats");
+        assert_eq!(
+            stats,
+            LocalStats {
+                // nullifier 1 is before the window, nullifier 2 counts once
+                proofs_generated: 2,
+                // schema 3 expired at 1500
+                credentials_by_schema: vec![
+                    SchemaHoldings {
+                        issuer_schema_id: 1,
+                        count: 1,
+                    },
+                    SchemaHoldings {
+                        issuer_schema_id: 2,
+                        count: 2,
+                    },
+                ],
+                cache_hits: 3,
+                cache_misses: 1,
+            }
+        );
+        assert_eq!(stats.cache_hit_rate(), Some(0.75));
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_cache_counters_survive_cache_rebuild() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("store");
+        store.init(42, 1000).expect("init storage");
+        store
+            .merkle_cache_get(RegistryKind::AccountRegistry, 1000)
+            .expect("cache lookup");
+        let cache_path = store.storage_paths().unwrap().cache_db_path();
+        drop(store);
+
+        // as if the cache had been deleted to be rebuilt
+        std::fs::remove_file(&cache_path).expect("delete cache");
+        let _ = std::fs::remove_file(cache_path.with_extension("sqlite-wal"));
+        let _ = std::fs::remove_file(cache_path.with_extension("sqlite-shm"));
+        let store = CredentialStore::from_provider(&provider).expect("store");
+        store.init(42, 1000).expect("init storage");
+
+        let stats = store.local_stats(0, 2000).expect("local stats");
+        assert_eq!((stats.cache_hits, stats.cache_misses), (0, 1));
+        assert_eq!(stats.cache_hit_rate(), Some(0.0));
+
+        cleanup_test_storage(&root);
+    }
+
+    /// Fails to compile if a field is added to [`LocalStats`] or
+    /// [`SchemaHoldings`], or a field's type changes: every field must remain
+    /// a count, never an identifier.
+    #[test]
+    fn test_local_stats_only_holds_counts() {
+        let stats = LocalStats {
+            proofs_generated: 1,
+            credentials_by_schema: vec![SchemaHoldings {
+                issuer_schema_id: 1,
+                count: 1,
+            }],
+            cache_hits: 0,
+            cache_misses: 0,
+        };
+
+        let LocalStats {
+            proofs_generated,
+            credentials_by_schema,
+            cache_hits,
+            cache_misses,
+        } = stats;
+        let _: [u64; 3] = [proofs_generated, cache_hits, cache_misses];
+        for SchemaHoldings {
+            issuer_schema_id,
+            count,
+        } in credentials_by_schema
+        {
+            let _: [u64; 2] = [issuer_schema_id, count];
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Operational counters of a [`CredentialStore`].
+//!
+//! Counters start at zero for each store handle and are not persisted. They
+//! are meant to be sampled periodically by the host's monitoring, see
+//! [`CredentialStoreMetrics::snapshot`].
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use super::CredentialStore;
+
+/// Counters updated by a [`CredentialStore`] as it is used.
+#[derive(Debug, Default, uniffi::Object)]
+pub struct CredentialStoreMetrics {
+    /// Proof generations that reached the replay guard check, i.e. whose
+    /// nullifier was derived.
+    pub proof_attempts: AtomicU64,
+    /// Proofs whose replay guard entry was recorded after generation.
+    pub proof_successes: AtomicU64,
+    /// Merkle inclusion proof lookups answered from the cache.
+    pub cache_hits: AtomicU64,
+    /// Merkle inclusion proof lookups that found no usable cached proof.
+    pub cache_misses: AtomicU64,
+    /// Proof attempts refused because the nullifier was already disclosed,
+    /// for the same or a different request.
+    pub nullifier_replays: AtomicU64,
+}
+
+/// Point-in-time copy of [`CredentialStoreMetrics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Record)]
+pub struct MetricsSnapshot {
+    /// See [`CredentialStoreMetrics::proof_attempts`].
+    pub proof_attempts: u64,
+    /// See [`CredentialStoreMetrics::proof_successes`].
+    pub proof_successes: u64,
+    /// See [`CredentialStoreMetrics::cache_hits`].
+    pub cache_hits: u64,
+    /// See [`CredentialStoreMetrics::cache_misses`].
+    pub cache_misses: u64,
+    /// See [`CredentialStoreMetrics::nullifier_replays`].
+    pub nullifier_replays: u64,
+}
+
+#[uniffi::export]
+impl CredentialStoreMetrics {
+    /// Returns the current value of every counter.
+    ///
+    /// Counters are read one at a time, so a snapshot taken during a proof
+    /// may e.g. include its attempt but not yet its success.
+    #[must_use]
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            proof_attempts: self.proof_attempts.load(Ordering::Relaxed),
+            proof_successes: self.proof_successes.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
+            nullifier_replays: self.nullifier_replays.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl CredentialStoreMetrics {
+    pub(super) fn increment(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[uniffi::export]
+impl CredentialStore {
+    /// Returns the operational counters of this store handle.
+    #[must_use]
+    pub fn metrics(&self) -> Arc<CredentialStoreMetrics> {
+        Arc::clone(&self.metrics)
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl MetricsSnapshot {
+    /// Renders the counters in the Prometheus text exposition format, as
+    /// `walletkit_credential_store_*_total` counters.
+    #[must_use]
+    pub fn to_prometheus_text(&self) -> String {
+        use std::fmt::Write as _;
+
+        let counters = [
+            (
+                "proof_attempts",
+                "Proof generations that reached the replay guard check.",
+                self.proof_attempts,
+            ),
+            (
+                "proof_successes",
+                "Proofs recorded in the replay guard after generation.",
+                self.proof_successes,
+            ),
+            (
+                "cache_hits",
+                "Merkle inclusion proof lookups answered from the cache.",
+                self.cache_hits,
+            ),
+            (
+                "cache_misses",
+                "Merkle inclusion proof lookups without a usable cached proof.",
+                self.cache_misses,
+            ),
+            (
+                "nullifier_replays",
+                "Proof attempts refused because the nullifier was already disclosed.",
+                self.nullifier_replays,
+            ),
+        ];
+        let mut text = String::new();
+        for (name, help, value) in counters {
+            let name = format!("walletkit_credential_store_{name}_total");
+            // writing to a String cannot fail
+            let _ = writeln!(text, "# HELP {name} {help}");
+            let _ = writeln!(text, "# TYPE {name} counter");
+            let _ = writeln!(text, "{name} {value}");
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use world_id_core::primitives::merkle::{
+        AccountInclusionProof, MerkleInclusionProof,
+    };
+    use world_id_core::primitives::{AuthenticatorPublicKeySet, TREE_DEPTH};
+    use world_id_core::FieldElement as CoreFieldElement;
+
+    use super::*;
+    use crate::storage::error::StorageError;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider, TEST_ENVIRONMENT,
+    };
+    use crate::storage::{NewConsent, RegistryKind};
+
+    fn consent(request_id: &str) -> NewConsent<'_> {
+        NewConsent {
+            request_id,
+            rp_id_hash: &[0u8; 32],
+            disclosed_schema_ids: &[],
+            consent_text_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_replay_guard_counters() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("store");
+        store.init(42, 100).expect("init storage");
+        let metrics = store.metrics();
+        let nullifier = CoreFieldElement::from(7u64);
+
+        assert!(!store
+            .begin_replay_guard(TEST_ENVIRONMENT, nullifier, "a", 1000)
+            .unwrap());
+        store
+            .finalize_proof(TEST_ENVIRONMENT, nullifier, consent("a"), None, 1000)
+            .expect("finalize proof");
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.proof_attempts, 1);
+        assert_eq!(snapshot.proof_successes, 1);
+        assert_eq!(snapshot.nullifier_replays, 0);
+
+        // past the grace period: a replay for the same request, a conflict
+        // for another one
+        assert!(store
+            .begin_replay_guard(TEST_ENVIRONMENT, nullifier, "a", 2000)
+            .unwrap());
+        assert!(matches!(
+            store.begin_replay_guard(TEST_ENVIRONMENT, nullifier, "b", 2000),
+            Err(StorageError::NullifierConflict { .. })
+        ));
+        assert_eq!(
+            metrics.snapshot(),
+            MetricsSnapshot {
+                proof_attempts: 3,
+                proof_successes: 1,
+                cache_hits: 0,
+                cache_misses: 0,
+                nullifier_replays: 2,
+            }
+        );
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_cache_counters() {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("store");
+        store.init(42, 100).expect("init storage");
+
+        assert!(store
+            .merkle_cache_get(RegistryKind::AccountRegistry, 100)
+            .unwrap()
+            .is_none());
+        let account_inclusion_proof = AccountInclusionProof {
+            inclusion_proof: MerkleInclusionProof::new(
+                CoreFieldElement::from(123u64),
+                42,
+                [CoreFieldElement::from(0u64); TREE_DEPTH],
+            ),
+            authenticator_pubkeys: AuthenticatorPublicKeySet::new(vec![])
+                .expect("key set"),
+        };
+        store
+            .merkle_cache_put(
+                RegistryKind::AccountRegistry,
+                &account_inclusion_proof,
+                100,
+                60,
+            )
+            .expect("cache put");
+        assert!(store
+            .merkle_cache_get(RegistryKind::AccountRegistry, 110)
+            .unwrap()
+            .is_some());
+        assert!(store
+            .merkle_cache_get_with_age(RegistryKind::AccountRegistry, 110)
+            .unwrap()
+            .is_some());
+
+        let snapshot = store.metrics().snapshot();
+        assert_eq!((snapshot.cache_hits, snapshot.cache_misses), (2, 1));
+
+        cleanup_test_storage(&root);
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn test_prometheus_text() {
+        let snapshot = MetricsSnapshot {
+            proof_attempts: 3,
+            proof_successes: 2,
+            cache_hits: 5,
+            cache_misses: 1,
+            nullifier_replays: 1,
+        };
+        let text = snapshot.to_prometheus_text();
+
+        assert!(text.starts_with(
+            "# HELP walletkit_credential_store_proof_attempts_total Proof generations"
+        ));
+        assert!(text.contains(
+            "# TYPE walletkit_credential_store_cache_hits_total counter\n\
+             walletkit_credential_store_cache_hits_total 5\n"
+        ));
+        assert_eq!(text.lines().count(), 15);
+    }
+}
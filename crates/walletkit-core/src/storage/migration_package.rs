@@ -0,0 +1,432 @@
+//! Encrypted packages for moving an account to a device on another platform.
+//!
+//! The account key envelope is sealed by the platform keystore and cannot
+//! leave the device, so a vault cannot simply be copied from iOS to Android
+//! (or back). Instead, the old device exports a [`MigrationPackage`] holding
+//! the account state and a plaintext copy of the vault, encrypted to a
+//! [`MigrationRecipient`] key generated on the new device. Importing the
+//! package initializes the new store, which wraps the data under the new
+//! device's keystore as usual.
+//!
+//! The package is encrypted with ECIES over secp256k1: an ephemeral sender key
+//! is agreed with the recipient key via ECDH, HKDF-SHA256 derives the content
+//! key, and each part is sealed with XChaCha20-Poly1305.
+
+use std::sync::Arc;
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use hkdf::Hkdf;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::{PublicKey, SecretKey};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use super::entropy;
+use super::error::{StorageError, StorageResult};
+use super::CredentialStore;
+
+/// Current [`MigrationPackage`] format version.
+pub const MIGRATION_PACKAGE_VERSION: u8 = 1;
+
+/// Length of a SEC1-compressed secp256k1 public key.
+const PUBLIC_KEY_LEN: usize = 33;
+
+const NONCE_LEN: usize = 24;
+
+/// HKDF info for the content key.
+const CONTENT_KEY_INFO: &[u8] = b"worldid:migration-package:v1";
+
+/// Associated data binding each sealed part to its role.
+const STATE_AD: &[u8] = b"worldid:migration-package:state";
+const VAULT_AD: &[u8] = b"worldid:migration-package:vault";
+
+/// An account exported for import on another device.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct MigrationPackage {
+    /// Format version, see [`MIGRATION_PACKAGE_VERSION`].
+    pub version: u8,
+    /// Encrypted account state (the leaf index).
+    pub encrypted_state: Vec<u8>,
+    /// Encrypted plaintext copy of the vault.
+    pub encrypted_vault: Vec<u8>,
+    /// SEC1-compressed ephemeral public key of the sender (33 bytes).
+    pub sender_pub: Vec<u8>,
+}
+
+/// Key pair the new device generates to receive a [`MigrationPackage`].
+///
+/// The secret key never leaves Rust; share [`public_key`](Self::public_key)
+/// with the old device (e.g. through a QR code) and keep this object alive
+/// until the package is imported.
+#[derive(uniffi::Object)]
+pub struct MigrationRecipient {
+    secret_key: SecretKey,
+}
+
+#[uniffi::export]
+impl MigrationRecipient {
+    /// Generates a fresh recipient key pair.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::EntropyUnavailable`] if the OS random source
+    /// fails.
+    #[uniffi::constructor]
+    pub fn new() -> StorageResult<Arc<Self>> {
+        Ok(Arc::new(Self {
+            secret_key: random_secret_key()?,
+        }))
+    }
+
+    /// Returns the SEC1-compressed public key (33 bytes).
+    #[must_use]
+    pub fn public_key(&self) -> Vec<u8> {
+        compress(&self.secret_key.public_key())
+    }
+}
+
+#[uniffi::export]
+impl CredentialStore {
+    /// Exports the account as a [`MigrationPackage`] encrypted to
+    /// `recipient_pub`, the SEC1-compressed public key of a
+    /// [`MigrationRecipient`] on the new device.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized, `recipient_pub` is
+    /// not a valid public key, or the vault export fails.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn export_migration_package(
+        &self,
+        recipient_pub: Vec<u8>,
+    ) -> StorageResult<MigrationPackage> {
+        if recipient_pub.len() != PUBLIC_KEY_LEN {
+            return Err(StorageError::Crypto(format!(
+                "recipient public key must be {PUBLIC_KEY_LEN} bytes, got {}",
+                recipient_pub.len()
+            )));
+        }
+        let recipient_pub = PublicKey::from_sec1_bytes(&recipient_pub)
+            .map_err(|_| StorageError::Crypto("invalid recipient public key".into()))?;
+        let leaf_index = self.leaf_index()?;
+        let vault = Zeroizing::new(self.export_vault_for_backup()?);
+
+        let ephemeral = random_secret_key()?;
+        let sender_pub = compress(&ephemeral.public_key());
+        let key = content_key(&ephemeral, &recipient_pub, &sender_pub)?;
+        Ok(MigrationPackage {
+            version: MIGRATION_PACKAGE_VERSION,
+            encrypted_state: seal(&key, STATE_AD, &leaf_index.to_be_bytes())?,
+            encrypted_vault: seal(&key, VAULT_AD, &vault)?,
+            sender_pub,
+        })
+    }
+
+    /// Imports a [`MigrationPackage`] addressed to `recipient`.
+    ///
+    /// Initializes the store with the exported leaf index and imports the
+    /// exported credentials; the store's own keystore protects them from then
+    /// on. Intended for a fresh install: importing into a store initialized
+    /// for a different account fails with [`StorageError::InvalidLeafIndex`].
+    /// The package is decrypted and checked before the store is initialized,
+    /// and a fresh store is left uninitialized if the import fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package has an unsupported version, was not
+    /// encrypted to `recipient` or was tampered with, does not hold a vault,
+    /// or if initialization or the vault import fails.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn import_migration_package(
+        &self,
+        package: MigrationPackage,
+        recipient: Arc<MigrationRecipient>,
+        now: u64,
+    ) -> StorageResult<()> {
+        if package.version != MIGRATION_PACKAGE_VERSION {
+            return Err(StorageError::Serialization(format!(
+                "unsupported migration package version {}",
+                package.version
+            )));
+        }
+        let sender_pub = PublicKey::from_sec1_bytes(&package.sender_pub)
+            .map_err(|_| StorageError::Crypto("invalid sender public key".into()))?;
+        let key = content_key(&recipient.secret_key, &sender_pub, &package.sender_pub)?;
+
+        let state = open(&key, STATE_AD, &package.encrypted_state)?;
+        let leaf_index = <[u8; 8]>::try_from(state.as_slice())
+            .map(u64::from_be_bytes)
+            .map_err(|_| {
+                StorageError::Serialization(format!(
+                    "migration state must be 8 bytes, got {}",
+                    state.len()
+                ))
+            })?;
+        let vault = open(&key, VAULT_AD, &package.encrypted_vault)?;
+
+        self.init_from_backup(leaf_index, now, &vault)
+    }
+}
+
+fn compress(public_key: &PublicKey) -> Vec<u8> {
+    public_key.to_encoded_point(true).as_bytes().to_vec()
+}
+
+/// Derives the content key shared by `secret_key` and `peer_pub`.
+///
+/// `sender_pub` is the package's ephemeral key, used as HKDF salt so each
+/// package gets its own key even if the ECDH output were reused.
+fn content_key(
+    secret_key: &SecretKey,
+    peer_pub: &PublicKey,
+    sender_pub: &[u8],
+) -> StorageResult<Zeroizing<[u8; 32]>> {
+    let shared = k256::ecdh::diffie_hellman(
+        secret_key.to_nonzero_scalar(),
+        peer_pub.as_affine(),
+    );
+    let mut key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(sender_pub), shared.raw_secret_bytes())
+        .expand(CONTENT_KEY_INFO, key.as_mut())
+        .map_err(|err| StorageError::Crypto(err.to_string()))?;
+    Ok(key)
+}
+
+/// Draws a secret key from the OS random source.
+fn random_secret_key() -> StorageResult<SecretKey> {
+    let mut bytes = Zeroizing::new([0u8; 32]);
+    entropy::fill(bytes.as_mut())?;
+    // fails only for zero or a value past the curve order, with negligible
+    // probability
+    SecretKey::from_slice(bytes.as_ref())
+        .map_err(|_| StorageError::Crypto("random scalar out of range".to_string()))
+}
+
+/// Encrypts `plaintext`, returning `nonce || ciphertext`.
+fn seal(key: &[u8; 32], aad: &[u8], plaintext: &[u8]) -> StorageResult<Vec<u8>> {
+    let mut nonce = [0u8; NONCE_LEN];
+    entropy::fill_nonce(&mut nonce)?;
+    let ciphertext = XChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|err| StorageError::Crypto(err.to_string()))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+fn open(
+    key: &[u8; 32],
+    aad: &[u8],
+    sealed: &[u8],
+) -> StorageResult<Zeroizing<Vec<u8>>> {
+    if sealed.len() < NONCE_LEN {
+        return Err(StorageError::Crypto(
+            "migration package part too short".to_string(),
+        ));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map(Zeroizing::new)
+        .map_err(|_| {
+            StorageError::Crypto("failed to decrypt migration package".to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use world_id_core::Credential as CoreCredential;
+
+    use super::*;
+    use crate::storage::entropy::tests::FailingEntropy;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+    use crate::{Credential, FieldElement};
+
+    /// Returns a store on a fresh "device" with its own keystore.
+    fn new_device() -> (CredentialStore, std::path::PathBuf) {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        (store, root)
+    }
+
+    #[test]
+    fn test_migration_package_round_trip() {
+        let (old_store, old_root) = new_device();
+        old_store.init(42, 1000).expect("init old device");
+        let credential: Credential = CoreCredential::new()
+            .issuer_schema_id(100u64)
+            .genesis_issued_at(1000)
+            .into();
+        old_store
+            .store_credential(&credential, &FieldElement::from(7u64), 9999, None, 1000)
+            .expect("store credential");
+
+        let recipient = MigrationRecipient::new().expect("recipient");
+        let package = old_store
+            .export_migration_package(recipient.public_key())
+            .expect("export package");
+        assert_eq!(package.sender_pub.len(), PUBLIC_KEY_LEN);
+
+        let (new_store, new_root) = new_device();
+        new_store
+            .import_migration_package(package, recipient, 2000)
+            .expect("import package");
+        assert_eq!(new_store.leaf_index().expect("leaf index"), 42);
+        let records = new_store.list_credentials(None, 2000).expect("list");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].issuer_schema_id, 100);
+
+        cleanup_test_storage(&old_root);
+        cleanup_test_storage(&new_root);
+    }
+
+    #[test]
+    fn test_migration_package_rejects_wrong_recipient_and_tampering() {
+        let (old_store, old_root) = new_device();
+        old_store.init(42, 1000).expect("init old device");
+        let recipient = MigrationRecipient::new().expect("recipient");
+        let package = old_store
+            .export_migration_package(recipient.public_key())
+            .expect("export package");
+
+        let (new_store, new_root) = new_device();
+        let err = new_store
+            .import_migration_package(
+                package.clone(),
+                MigrationRecipient::new().expect("recipient"),
+                2000,
+            )
+            .expect_err("wrong recipient");
+        assert!(matches!(err, StorageError::Crypto(_)));
+
+        // swapping the parts must not decrypt either
+        let mut swapped = package.clone();
+        std::mem::swap(&mut swapped.encrypted_state, &mut swapped.encrypted_vault);
+        assert!(new_store
+            .import_migration_package(swapped, Arc::clone(&recipient), 2000)
+            .is_err());
+
+        let mut future = package;
+        future.version = MIGRATION_PACKAGE_VERSION + 1;
+        assert!(matches!(
+            new_store.import_migration_package(future, recipient, 2000),
+            Err(StorageError::Serialization(_))
+        ));
+        // nothing was imported
+        assert!(matches!(
+            new_store.leaf_index(),
+            Err(StorageError::NotInitialized)
+        ));
+
+        cleanup_test_storage(&old_root);
+        cleanup_test_storage(&new_root);
+    }
+
+    /// Packages `vault` for `recipient` the way an old device would.
+    fn package_for(
+        recipient: &MigrationRecipient,
+        leaf_index: u64,
+        vault: &[u8],
+    ) -> MigrationPackage {
+        let ephemeral = random_secret_key().expect("ephemeral key");
+        let sender_pub = compress(&ephemeral.public_key());
+        let key =
+            content_key(&ephemeral, &recipient.secret_key.public_key(), &sender_pub)
+                .expect("content key");
+        MigrationPackage {
+            version: MIGRATION_PACKAGE_VERSION,
+            encrypted_state: seal(&key, STATE_AD, &leaf_index.to_be_bytes())
+                .expect("seal state"),
+            encrypted_vault: seal(&key, VAULT_AD, vault).expect("seal vault"),
+            sender_pub,
+        }
+    }
+
+    #[test]
+    fn test_bad_migration_package_leaves_store_uninitialized() {
+        let (old_store, old_root) = new_device();
+        old_store.init(42, 1000).expect("init old device");
+        let vault = old_store.export_vault_for_backup().expect("export vault");
+        let recipient = MigrationRecipient::new().expect("recipient");
+        let (new_store, new_root) = new_device();
+
+        // not a vault: rejected before the store is initialized
+        let err = new_store
+            .import_migration_package(
+                package_for(&recipient, 42, b"not a vault"),
+                Arc::clone(&recipient),
+                2000,
+            )
+            .expect_err("not a vault");
+        assert!(matches!(err, StorageError::VaultDb(_)));
+        assert!(matches!(
+            new_store.leaf_index(),
+            Err(StorageError::NotInitialized)
+        ));
+
+        // a vault header over corrupt pages fails the import; the init is undone
+        let mut corrupt = vault.clone();
+        corrupt[100..].fill(0xFF);
+        assert!(new_store
+            .import_migration_package(
+                package_for(&recipient, 42, &corrupt),
+                Arc::clone(&recipient),
+                2000,
+            )
+            .is_err());
+        assert!(matches!(
+            new_store.leaf_index(),
+            Err(StorageError::NotInitialized)
+        ));
+
+        // and the restore can be retried
+        new_store
+            .import_migration_package(
+                package_for(&recipient, 42, &vault),
+                recipient,
+                2000,
+            )
+            .expect("import package");
+        assert_eq!(new_store.leaf_index().expect("leaf index"), 42);
+
+        cleanup_test_storage(&old_root);
+        cleanup_test_storage(&new_root);
+    }
+
+    #[test]
+    fn test_migration_fails_gracefully_without_entropy() {
+        let (store, root) = new_device();
+        store.init(42, 1000).expect("init");
+        let recipient = MigrationRecipient::new().expect("recipient");
+
+        let _failing = FailingEntropy::install();
+        assert!(matches!(
+            MigrationRecipient::new(),
+            Err(StorageError::EntropyUnavailable(_))
+        ));
+        assert!(matches!(
+            store.export_migration_package(recipient.public_key()),
+            Err(StorageError::EntropyUnavailable(_))
+        ));
+
+        cleanup_test_storage(&root);
+    }
+}
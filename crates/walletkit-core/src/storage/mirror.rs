@@ -0,0 +1,205 @@
+//! Mirrored blob storage for the account key envelope.
+//!
+//! Losing `account_keys.bin` makes the vault unreadable, so deployments that
+//! cannot tolerate that can keep a second copy in an independent location
+//! (e.g. a separate volume or a backup-excluded container). Available with the
+//! `mirrored-vault` feature.
+//!
+//! Writes go to the mirror first and then to the primary, so after an
+//! interrupted write the mirror is never older than the primary and
+//! [`MirroredBlobStore::repair_from_mirror`] can always roll the primary
+//! forward.
+
+use std::sync::Arc;
+
+use super::error::{StorageError, StorageResult};
+use super::traits::AtomicBlobStore;
+
+/// [`AtomicBlobStore`] that keeps every blob in two underlying stores.
+///
+/// Reads are served from the primary and fall back to the mirror when the
+/// primary copy cannot be read or is missing.
+pub struct MirroredBlobStore {
+    primary: Arc<dyn AtomicBlobStore>,
+    mirror: Arc<dyn AtomicBlobStore>,
+}
+
+impl MirroredBlobStore {
+    /// Creates a store mirroring `primary` into `mirror`.
+    #[must_use]
+    pub fn new(
+        primary: Arc<dyn AtomicBlobStore>,
+        mirror: Arc<dyn AtomicBlobStore>,
+    ) -> Self {
+        Self { primary, mirror }
+    }
+
+    /// Copies the mirror's blob at `path` over the primary if the primary copy
+    /// is unreadable, missing, or differs from the mirror.
+    ///
+    /// Returns `true` if the primary was rewritten.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mirror cannot be read or has no blob at `path`,
+    /// or if the primary cannot be written.
+    pub fn repair_from_mirror(&self, path: &str) -> StorageResult<bool> {
+        let Some(expected) = self.mirror.read(path.to_string())? else {
+            return Err(StorageError::BlobStore(format!(
+                "mirror has no blob at {path}"
+            )));
+        };
+        match self.primary.read(path.to_string()) {
+            Ok(Some(bytes)) if bytes == expected => return Ok(false),
+            Ok(_) => {}
+            Err(err) => {
+                tracing::warn!("primary blob {path} is unreadable: {err}");
+            }
+        }
+        self.primary.write_atomic(path.to_string(), expected)?;
+        Ok(true)
+    }
+}
+
+impl AtomicBlobStore for MirroredBlobStore {
+    fn read(&self, path: String) -> StorageResult<Option<Vec<u8>>> {
+        match self.primary.read(path.clone()) {
+            Ok(Some(bytes)) => Ok(Some(bytes)),
+            Ok(None) => self.mirror.read(path),
+            Err(err) => {
+                tracing::warn!(
+                    "primary blob {path} is unreadable, reading mirror: {err}"
+                );
+                self.mirror.read(path)
+            }
+        }
+    }
+
+    fn write_atomic(&self, path: String, bytes: Vec<u8>) -> StorageResult<()> {
+        self.mirror.write_atomic(path.clone(), bytes.clone())?;
+        self.primary.write_atomic(path, bytes)
+    }
+
+    fn delete(&self, path: String) -> StorageResult<()> {
+        self.primary.delete(path.clone())?;
+        self.mirror.delete(path)
+    }
+}
+
+/// Creates a blob store that mirrors every write to `primary` into `mirror`.
+///
+/// Return it from [`StorageProvider::blob_store`](super::StorageProvider::blob_store)
+/// to keep a redundant copy of the account key envelope.
+#[uniffi::export]
+#[must_use]
+pub fn mirrored_blob_store(
+    primary: Arc<dyn AtomicBlobStore>,
+    mirror: Arc<dyn AtomicBlobStore>,
+) -> Arc<dyn AtomicBlobStore> {
+    Arc::new(MirroredBlobStore::new(primary, mirror))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use secrecy::ExposeSecret;
+    use uuid::Uuid;
+    use walletkit_db::Lock;
+
+    use super::*;
+    use crate::storage::tests_utils::{InMemoryBlobStore, InMemoryKeystore};
+    use crate::storage::{StorageKeys, ACCOUNT_KEYS_FILENAME};
+
+    /// In-memory store whose reads fail once it is marked corrupted.
+    #[derive(Default)]
+    struct CorruptibleBlobStore {
+        inner: InMemoryBlobStore,
+        corrupted: AtomicBool,
+    }
+
+    impl AtomicBlobStore for CorruptibleBlobStore {
+        fn read(&self, path: String) -> StorageResult<Option<Vec<u8>>> {
+            if self.corrupted.load(Ordering::SeqCst) {
+                return Err(StorageError::BlobStore("checksum mismatch".to_string()));
+            }
+            self.inner.read(path)
+        }
+
+        fn write_atomic(&self, path: String, bytes: Vec<u8>) -> StorageResult<()> {
+            self.corrupted.store(false, Ordering::SeqCst);
+            self.inner.write_atomic(path, bytes)
+        }
+
+        fn delete(&self, path: String) -> StorageResult<()> {
+            self.inner.delete(path)
+        }
+    }
+
+    fn temp_lock_path() -> std::path::PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!("walletkit-mirror-lock-{}.lock", Uuid::new_v4()));
+        path
+    }
+
+    #[test]
+    fn test_corrupted_primary_falls_back_to_mirror() {
+        let keystore = InMemoryKeystore::new();
+        let primary = Arc::new(CorruptibleBlobStore::default());
+        let mirror = Arc::new(InMemoryBlobStore::new());
+        let store = MirroredBlobStore::new(primary.clone(), mirror.clone());
+        let lock_path = temp_lock_path();
+        let lock = Lock::open(&lock_path).expect("open lock");
+
+        let keys_first =
+            StorageKeys::init(&keystore, &store, &lock, 100).expect("init");
+        assert!(mirror
+            .read(ACCOUNT_KEYS_FILENAME.to_string())
+            .expect("read mirror")
+            .is_some());
+
+        primary.corrupted.store(true, Ordering::SeqCst);
+        let keys_second =
+            StorageKeys::init(&keystore, &store, &lock, 200).expect("open");
+        assert_eq!(
+            keys_first.intermediate_key().expose_secret(),
+            keys_second.intermediate_key().expose_secret()
+        );
+
+        assert!(store
+            .repair_from_mirror(ACCOUNT_KEYS_FILENAME)
+            .expect("repair"));
+        assert_eq!(
+            primary
+                .read(ACCOUNT_KEYS_FILENAME.to_string())
+                .expect("read"),
+            mirror
+                .read(ACCOUNT_KEYS_FILENAME.to_string())
+                .expect("read")
+        );
+        assert!(!store
+            .repair_from_mirror(ACCOUNT_KEYS_FILENAME)
+            .expect("repair"));
+        let _ = std::fs::remove_file(lock_path);
+    }
+
+    #[test]
+    fn test_missing_primary_reads_mirror() {
+        let primary = Arc::new(InMemoryBlobStore::new());
+        let mirror = Arc::new(InMemoryBlobStore::new());
+        let store = MirroredBlobStore::new(primary.clone(), mirror);
+        store
+            .write_atomic("blob".to_string(), vec![1, 2, 3])
+            .expect("write");
+
+        primary.delete("blob".to_string()).expect("delete");
+        assert_eq!(
+            store.read("blob".to_string()).expect("read"),
+            Some(vec![1, 2, 3])
+        );
+
+        store.delete("blob".to_string()).expect("delete");
+        assert_eq!(store.read("blob".to_string()).expect("read"), None);
+        assert!(store.repair_from_mirror("blob").is_err());
+    }
+}
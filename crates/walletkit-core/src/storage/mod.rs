@@ -38,40 +38,113 @@
 //! location is host-determined (not necessarily under `worldid/`); backup and
 //! deletion must include it.
 //!
+//! `inspect_file` identifies these files without keys, for support (native
+//! targets only).
+//!
+//! In browsers, the `platform-web` feature adds `storage::web`, which keeps
+//! the databases and the key envelope in the origin private file system.
+//!
 //! ## Security and privacy properties
 //!
 //! Encryption, the sealed-envelope threat model, and integrity checks are covered by
 //! the `walletkit-db` README.
 
+#[cfg(not(target_arch = "wasm32"))]
+mod async_reads;
 pub mod cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod credential_audit;
+pub mod credential_cache;
 pub mod credential_storage;
 pub mod credential_vault;
+pub(crate) mod entropy;
 pub mod error;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod expiry;
 #[cfg(all(not(target_arch = "wasm32"), feature = "embed-zkeys"))]
 pub mod groth16_cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod inspect;
+pub mod inventory;
 pub mod keys;
+pub mod legacy_import;
+pub mod local_stats;
+pub mod metrics;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod migration_package;
+#[cfg(any(test, feature = "mirrored-vault"))]
+pub mod mirror;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod passphrase_backup;
 pub mod paths;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod qr_transfer;
+mod quota;
+pub mod secure_prefs;
 pub mod traits;
 pub mod types;
+#[cfg(all(target_arch = "wasm32", feature = "platform-web"))]
+pub mod web;
 
 pub use cache::CacheDb;
+#[cfg(not(target_arch = "wasm32"))]
+pub use credential_audit::CredentialAuditReport;
 pub use credential_storage::CredentialStore;
-pub use credential_vault::CredentialVault;
-pub use error::{StorageError, StorageResult};
+pub use credential_vault::{
+    AccountId, BoundCredentialVault, CredentialVault, NewConsent, TenantMode,
+    MAX_SECURE_PREFS, MAX_SECURE_PREF_KEY_BYTES, MAX_SECURE_PREF_VALUE_BYTES,
+};
+pub use entropy::entropy_selfcheck;
+pub use error::{ErrorSeverity, StorageError, StorageResult};
 #[cfg(all(not(target_arch = "wasm32"), feature = "embed-zkeys"))]
 pub use groth16_cache::cache_embedded_groth16_material;
+#[cfg(not(target_arch = "wasm32"))]
+pub use inspect::{inspect_file, FileInspection, StorageFileKind};
+pub use inventory::{data_inventory, DataCategory, RetentionPolicy};
 pub use keys::StorageKeys;
+pub use local_stats::{LocalStats, SchemaHoldings};
+pub use metrics::{CredentialStoreMetrics, MetricsSnapshot};
+#[cfg(not(target_arch = "wasm32"))]
+pub use migration_package::{
+    MigrationPackage, MigrationRecipient, MIGRATION_PACKAGE_VERSION,
+};
+#[cfg(any(test, feature = "mirrored-vault"))]
+pub use mirror::{mirrored_blob_store, MirroredBlobStore};
+#[cfg(not(target_arch = "wasm32"))]
+pub use passphrase_backup::PASSPHRASE_BACKUP_VERSION;
 pub use paths::StoragePaths;
+#[cfg(not(target_arch = "wasm32"))]
+pub use qr_transfer::{QrTransferBundle, QR_TRANSFER_BUNDLE_VERSION};
+pub use secure_prefs::SecurePrefs;
+#[cfg(not(target_arch = "wasm32"))]
+pub use traits::CredentialExpiryListener;
+#[cfg(any(test, feature = "deterministic-crypto"))]
+pub use traits::DeterministicKeystore;
 pub use traits::{
-    AtomicBlobStore, DeviceKeystore, StorageProvider, VaultChangedListener,
+    AtomicBlobStore, DeviceKeystore, StorageProvider, StorageQuotaProvider,
+    VaultChangedListener,
 };
 pub use types::{
-    BlobKind, ContentId, CredentialRecord, Nullifier, ReplayGuardKind,
-    ReplayGuardResult, RequestId,
+    BlobKind, BloomStats, BulkDeleteReport, CacheConfig, ConsentRecord, ContentId,
+    CredentialExpiryEvent, CredentialFilter, CredentialRecord, CredentialStatus,
+    DisclosureRecord, EnvelopeHealth, KeychainAccessibility, MigrationReport,
+    Nullifier, RegistryKind, ReplayGuardKind, ReplayGuardResult, RequestId,
 };
 pub use walletkit_db::{Lock as StorageLock, LockGuard as StorageLockGuard};
+#[cfg(all(target_arch = "wasm32", feature = "platform-web"))]
+pub use web::{
+    OpfsBlobStore, OpfsVaultStore, WebKeystore, WebLockGuard, WebLockManager,
+    WebStorageProvider,
+};
+
+/// `PRAGMA application_id` of the vault database (`"WKVT"`).
+pub const VAULT_APPLICATION_ID: u32 = 0x574B_5654;
+/// `PRAGMA application_id` of the cache database (`"WKCH"`).
+pub const CACHE_APPLICATION_ID: u32 = 0x574B_4348;
 
 pub(crate) const ACCOUNT_KEYS_FILENAME: &str = "account_keys.bin";
+/// Envelope of the new key while [`CredentialStore::rekey_vault`] runs.
+pub(crate) const ACCOUNT_PENDING_KEYS_FILENAME: &str = "account_keys.pending.bin";
 pub(crate) const ACCOUNT_KEY_ENVELOPE_AD: &[u8] = b"worldid:account-key-envelope";
 
 #[cfg(test)]
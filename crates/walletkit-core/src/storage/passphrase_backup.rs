@@ -0,0 +1,403 @@
+//! Passphrase-encrypted backups of the whole vault.
+//!
+//! [`CredentialStore::export_vault_for_backup`] hands the host a plaintext
+//! copy of the vault. To store it somewhere the host does not control (e.g.
+//! iCloud or Google Drive), [`CredentialStore::export_backup`] instead seals
+//! the account state and that copy into one archive under a key derived from
+//! a user passphrase, which [`CredentialStore::import_backup`] restores on
+//! any device.
+//!
+//! The key is derived with Argon2id from the passphrase and a random salt,
+//! and the archive is sealed with XChaCha20-Poly1305. Its layout is
+//!
+//! ```text
+//! magic "WKPB" | version (1) | m_cost_kib, t_cost, p_cost (u32 BE each)
+//!   | salt (16) | nonce (24) | ciphertext
+//! ```
+//!
+//! where everything before the nonce is authenticated as associated data, so
+//! the KDF parameters cannot be changed without failing decryption. The
+//! plaintext is the leaf index (u64 BE) followed by the vault copy.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use zeroize::Zeroizing;
+
+use super::entropy;
+use super::error::{StorageError, StorageResult};
+use super::CredentialStore;
+
+/// Current passphrase backup format version.
+pub const PASSPHRASE_BACKUP_VERSION: u8 = 1;
+
+/// Leading bytes of every passphrase backup.
+const MAGIC: &[u8; 4] = b"WKPB";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Length of the authenticated header: magic, version, KDF parameters, salt.
+const HEADER_LEN: usize = MAGIC.len() + 1 + 3 * 4 + SALT_LEN;
+
+/// Argon2id parameters used for new backups (64 MiB, 3 passes, 1 lane).
+const DEFAULT_KDF: KdfParams = KdfParams {
+    m_cost_kib: 64 * 1024,
+    t_cost: 3,
+    p_cost: 1,
+};
+
+/// Largest Argon2id parameters accepted on import, so a crafted archive
+/// cannot make the device allocate or compute without bound.
+const MAX_KDF: KdfParams = KdfParams {
+    m_cost_kib: 256 * 1024,
+    t_cost: 10,
+    p_cost: 4,
+};
+
+/// Argon2id cost parameters, stored in the archive header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KdfParams {
+    m_cost_kib: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+#[uniffi::export]
+impl CredentialStore {
+    /// Exports the account and all its credentials as an archive encrypted
+    /// under `passphrase`, for storage outside the device, see the
+    /// [module documentation](super::passphrase_backup).
+    ///
+    /// Deriving the key takes a noticeable moment and 64 MiB of memory by
+    /// design; call this off the main thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::Crypto`] if `passphrase` is empty, or an error
+    /// if the store is not initialized or the vault export fails.
+    pub fn export_backup(&self, passphrase: &str) -> StorageResult<Vec<u8>> {
+        self.export_backup_with(passphrase, DEFAULT_KDF)
+    }
+
+    /// Restores an archive from [`export_backup`](Self::export_backup),
+    /// initializing the store with the exported leaf index and importing the
+    /// exported credentials under this device's keystore.
+    ///
+    /// Intended for a fresh install: importing into a store initialized for a
+    /// different account fails with [`StorageError::InvalidLeafIndex`], and
+    /// importing into a vault that already holds credentials fails. The
+    /// archive is decrypted and checked before the store is initialized, and
+    /// a fresh store is left uninitialized if the import fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::Crypto`] if the passphrase is wrong or the
+    /// archive was tampered with, [`StorageError::Serialization`] if it is
+    /// not a passphrase backup or has an unsupported version or KDF
+    /// parameters, or an error if initialization or the vault import fails.
+    pub fn import_backup(
+        &self,
+        backup: &[u8],
+        passphrase: &str,
+        now: u64,
+    ) -> StorageResult<()> {
+        let (header, sealed) = parse_header(backup)?;
+        let key = derive_key(passphrase, header.salt, header.kdf)?;
+        if sealed.len() < NONCE_LEN {
+            return Err(StorageError::Serialization(
+                "passphrase backup is truncated".to_string(),
+            ));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = XChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &backup[..HEADER_LEN],
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| {
+                StorageError::Crypto(
+                    "failed to decrypt backup: wrong passphrase or tampered archive"
+                        .to_string(),
+                )
+            })?;
+
+        let leaf_index = plaintext
+            .get(..8)
+            .and_then(|bytes| <[u8; 8]>::try_from(bytes).ok())
+            .map(u64::from_be_bytes)
+            .ok_or_else(|| {
+                StorageError::Serialization("backup state is truncated".to_string())
+            })?;
+        self.init_from_backup(leaf_index, now, &plaintext[8..])
+    }
+}
+
+impl CredentialStore {
+    fn export_backup_with(
+        &self,
+        passphrase: &str,
+        kdf: KdfParams,
+    ) -> StorageResult<Vec<u8>> {
+        let leaf_index = self.leaf_index()?;
+        let vault = Zeroizing::new(self.export_vault_for_backup()?);
+        seal_backup(passphrase, kdf, leaf_index, &vault)
+    }
+}
+
+/// Seals `leaf_index` and a plaintext vault copy into a backup archive.
+fn seal_backup(
+    passphrase: &str,
+    kdf: KdfParams,
+    leaf_index: u64,
+    vault: &[u8],
+) -> StorageResult<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    entropy::fill(&mut salt)?;
+    let key = derive_key(passphrase, &salt, kdf)?;
+
+    let mut backup = Vec::with_capacity(HEADER_LEN);
+    backup.extend_from_slice(MAGIC);
+    backup.push(PASSPHRASE_BACKUP_VERSION);
+    backup.extend_from_slice(&kdf.m_cost_kib.to_be_bytes());
+    backup.extend_from_slice(&kdf.t_cost.to_be_bytes());
+    backup.extend_from_slice(&kdf.p_cost.to_be_bytes());
+    backup.extend_from_slice(&salt);
+
+    let mut plaintext = Zeroizing::new(Vec::with_capacity(8 + vault.len()));
+    plaintext.extend_from_slice(&leaf_index.to_be_bytes());
+    plaintext.extend_from_slice(vault);
+
+    let mut nonce = [0u8; NONCE_LEN];
+    entropy::fill_nonce(&mut nonce)?;
+    let ciphertext = XChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: &backup,
+            },
+        )
+        .map_err(|err| StorageError::Crypto(err.to_string()))?;
+    backup.extend_from_slice(&nonce);
+    backup.extend_from_slice(&ciphertext);
+    Ok(backup)
+}
+
+/// The authenticated header of a backup.
+struct Header<'a> {
+    kdf: KdfParams,
+    salt: &'a [u8],
+}
+
+/// Splits `backup` into its header and the sealed remainder.
+fn parse_header(backup: &[u8]) -> StorageResult<(Header<'_>, &[u8])> {
+    let not_a_backup =
+        || StorageError::Serialization("not a passphrase backup".to_string());
+    let (header, sealed) = backup
+        .split_at_checked(HEADER_LEN)
+        .ok_or_else(not_a_backup)?;
+    let (magic, rest) = header.split_at(MAGIC.len());
+    if magic != MAGIC {
+        return Err(not_a_backup());
+    }
+    if rest[0] != PASSPHRASE_BACKUP_VERSION {
+        return Err(StorageError::Serialization(format!(
+            "unsupported passphrase backup version {}",
+            rest[0]
+        )));
+    }
+    let word = |index: usize| {
+        let start = 1 + 4 * index;
+        u32::from_be_bytes([
+            rest[start],
+            rest[start + 1],
+            rest[start + 2],
+            rest[start + 3],
+        ])
+    };
+    let kdf = KdfParams {
+        m_cost_kib: word(0),
+        t_cost: word(1),
+        p_cost: word(2),
+    };
+    if kdf.m_cost_kib > MAX_KDF.m_cost_kib
+        || kdf.t_cost > MAX_KDF.t_cost
+        || kdf.p_cost > MAX_KDF.p_cost
+    {
+        return Err(StorageError::Serialization(format!(
+            "passphrase backup KDF parameters exceed the limit: {kdf:?}"
+        )));
+    }
+    Ok((
+        Header {
+            kdf,
+            salt: &rest[13..],
+        },
+        sealed,
+    ))
+}
+
+/// Derives the archive key from `passphrase` with Argon2id.
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    kdf: KdfParams,
+) -> StorageResult<Zeroizing<[u8; 32]>> {
+    if passphrase.is_empty() {
+        return Err(StorageError::Crypto(
+            "backup passphrase must not be empty".to_string(),
+        ));
+    }
+    let params = Params::new(kdf.m_cost_kib, kdf.t_cost, kdf.p_cost, Some(32))
+        .map_err(|err| {
+            StorageError::Serialization(format!("invalid KDF parameters: {err}"))
+        })?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|err| StorageError::Crypto(err.to_string()))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use world_id_core::Credential as CoreCredential;
+
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+    use crate::{Credential, FieldElement};
+
+    /// Cheap parameters so tests do not spend seconds in the KDF.
+    const TEST_KDF: KdfParams = KdfParams {
+        m_cost_kib: 8,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    fn new_device() -> (CredentialStore, std::path::PathBuf) {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        (store, root)
+    }
+
+    #[test]
+    fn test_passphrase_backup_round_trip() {
+        let (old_store, old_root) = new_device();
+        old_store.init(42, 1000).expect("init old device");
+        let credential: Credential = CoreCredential::new()
+            .issuer_schema_id(100u64)
+            .genesis_issued_at(1000)
+            .into();
+        old_store
+            .store_credential(&credential, &FieldElement::from(7u64), 9999, None, 1000)
+            .expect("store credential");
+        let backup = old_store
+            .export_backup_with("correct horse", TEST_KDF)
+            .expect("export backup");
+        assert_eq!(&backup[..4], MAGIC);
+
+        let (new_store, new_root) = new_device();
+        assert!(matches!(
+            new_store.import_backup(&backup, "wrong horse", 2000),
+            Err(StorageError::Crypto(_))
+        ));
+        new_store
+            .import_backup(&backup, "correct horse", 2000)
+            .expect("import backup");
+        assert_eq!(new_store.leaf_index().expect("leaf index"), 42);
+        let records = new_store.list_credentials(None, 2000).expect("list");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].issuer_schema_id, 100);
+
+        cleanup_test_storage(&old_root);
+        cleanup_test_storage(&new_root);
+    }
+
+    #[test]
+    fn test_bad_passphrase_backup_leaves_store_uninitialized() {
+        let (old_store, old_root) = new_device();
+        old_store.init(42, 1000).expect("init old device");
+        let vault = old_store.export_vault_for_backup().expect("export vault");
+        let (new_store, new_root) = new_device();
+
+        let not_a_vault =
+            seal_backup("passphrase", TEST_KDF, 42, b"not a vault").expect("seal");
+        assert!(matches!(
+            new_store.import_backup(&not_a_vault, "passphrase", 2000),
+            Err(StorageError::VaultDb(_))
+        ));
+        assert!(matches!(
+            new_store.leaf_index(),
+            Err(StorageError::NotInitialized)
+        ));
+
+        let mut corrupt = vault.clone();
+        corrupt[100..].fill(0xFF);
+        let corrupt = seal_backup("passphrase", TEST_KDF, 42, &corrupt).expect("seal");
+        assert!(new_store
+            .import_backup(&corrupt, "passphrase", 2000)
+            .is_err());
+        assert!(matches!(
+            new_store.leaf_index(),
+            Err(StorageError::NotInitialized)
+        ));
+
+        let backup = seal_backup("passphrase", TEST_KDF, 42, &vault).expect("seal");
+        new_store
+            .import_backup(&backup, "passphrase", 2000)
+            .expect("import backup");
+        assert_eq!(new_store.leaf_index().expect("leaf index"), 42);
+
+        cleanup_test_storage(&old_root);
+        cleanup_test_storage(&new_root);
+    }
+
+    #[test]
+    fn test_passphrase_backup_rejects_tampered_header() {
+        let (store, root) = new_device();
+        store.init(42, 1000).expect("init");
+        let backup = store
+            .export_backup_with("passphrase", TEST_KDF)
+            .expect("export backup");
+        assert!(matches!(
+            store.export_backup(""),
+            Err(StorageError::Crypto(_))
+        ));
+
+        let (new_store, new_root) = new_device();
+        // raising t_cost keeps the parameters valid but breaks the AAD
+        let mut tampered = backup.clone();
+        tampered[12] ^= 0x02;
+        assert!(matches!(
+            new_store.import_backup(&tampered, "passphrase", 2000),
+            Err(StorageError::Crypto(_))
+        ));
+        let mut future = backup;
+        future[4] = PASSPHRASE_BACKUP_VERSION + 1;
+        assert!(matches!(
+            new_store.import_backup(&future, "passphrase", 2000),
+            Err(StorageError::Serialization(_))
+        ));
+        assert!(matches!(
+            new_store.import_backup(b"WKPB", "passphrase", 2000),
+            Err(StorageError::Serialization(_))
+        ));
+        assert!(matches!(
+            new_store.leaf_index(),
+            Err(StorageError::NotInitialized)
+        ));
+
+        cleanup_test_storage(&root);
+        cleanup_test_storage(&new_root);
+    }
+}
@@ -20,8 +20,10 @@
 
 use std::path::{Path, PathBuf};
 
-const VAULT_FILENAME: &str = "account.vault.sqlite";
-const CACHE_FILENAME: &str = "account.cache.sqlite";
+use super::inventory::{InventoryEntry, RetentionPolicy};
+
+pub(crate) const VAULT_FILENAME: &str = "account.vault.sqlite";
+pub(crate) const CACHE_FILENAME: &str = "account.cache.sqlite";
 const LOCK_FILENAME: &str = "lock";
 const GROTH16_DIRNAME: &str = "groth16";
 const QUERY_ZKEY_FILENAME: &str = "OPRFQuery.arks.zkey";
@@ -29,6 +31,33 @@ const NULLIFIER_ZKEY_FILENAME: &str = "OPRFNullifier.arks.zkey";
 const QUERY_GRAPH_FILENAME: &str = "OPRFQueryGraph.bin";
 const NULLIFIER_GRAPH_FILENAME: &str = "OPRFNullifierGraph.bin";
 
+/// Files under `<root>/worldid` outside the databases, see
+/// [`data_inventory`](super::inventory::data_inventory).
+pub(crate) const INVENTORY: &[InventoryEntry] = &[
+    InventoryEntry {
+        name: "storage_lock",
+        description: "An empty file locked while a process writes to the \
+            storage.",
+        locations: &["worldid/lock"],
+        contains_personal_data: false,
+        retention: RetentionPolicy::UntilDeleted,
+    },
+    InventoryEntry {
+        name: "groth16_material",
+        description: "The public proving keys and witness graphs of the \
+            World ID circuits, with a marker next to each recording that it \
+            was verified, and partial downloads (`.part`) of them.",
+        locations: &[
+            "worldid/groth16/OPRFQuery.arks.zkey",
+            "worldid/groth16/OPRFNullifier.arks.zkey",
+            "worldid/groth16/OPRFQueryGraph.bin",
+            "worldid/groth16/OPRFNullifierGraph.bin",
+        ],
+        contains_personal_data: false,
+        retention: RetentionPolicy::UntilDeleted,
+    },
+];
+
 /// Paths for credential storage artifacts under `<root>/worldid`.
 #[derive(Debug, Clone, uniffi::Object)]
 pub struct StoragePaths {
@@ -0,0 +1,415 @@
+//! Moving an account to a nearby device through a sequence of QR codes.
+//!
+//! The old device exports a [`MigrationPackage`] encrypted to the new
+//! device's [`MigrationRecipient`] key and splits it into
+//! [`QrTransferBundle`] chunks, each small enough for one QR code. The new
+//! device scans the codes in any order, reassembles the bundle with
+//! [`QrTransferBundle::from_scanned_chunks`] and imports it.
+//!
+//! Each chunk is `version (1) || transfer id (4) || index (2) || count (2) ||
+//! payload`, so chunks of different transfers are never mixed up. The
+//! concatenated payloads are `sender_pub (33) || state length (4, BE) ||
+//! encrypted state || encrypted vault`.
+
+use std::sync::Arc;
+
+use qrcode::{render::svg, EcLevel, QrCode};
+
+use super::entropy;
+use super::error::{StorageError, StorageResult};
+use super::migration_package::{
+    MigrationPackage, MigrationRecipient, MIGRATION_PACKAGE_VERSION,
+};
+use super::CredentialStore;
+
+/// Current [`QrTransferBundle`] format version.
+pub const QR_TRANSFER_BUNDLE_VERSION: u8 = 1;
+
+/// Bytes a version 40 QR code holds in byte mode at error correction level L.
+const QR_CAPACITY: usize = 2953;
+
+/// Maximum length of a chunk, header included.
+const MAX_CHUNK_LEN: usize = QR_CAPACITY - 1;
+
+const HEADER_LEN: usize = 9;
+
+/// Length of a SEC1-compressed secp256k1 public key.
+const PUBLIC_KEY_LEN: usize = 33;
+
+/// A [`MigrationPackage`] split into chunks that each fit in a QR code.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Object)]
+pub struct QrTransferBundle {
+    /// Format version, see [`QR_TRANSFER_BUNDLE_VERSION`].
+    pub version: u8,
+    /// The chunks, in order, each shorter than 2953 bytes.
+    pub chunks: Vec<Vec<u8>>,
+}
+
+#[uniffi::export]
+impl QrTransferBundle {
+    /// Reassembles a bundle from scanned chunks, in any order.
+    ///
+    /// Duplicate scans of a chunk are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::Serialization`] if a chunk is malformed, has
+    /// an unsupported version, belongs to another transfer, or if chunks are
+    /// missing.
+    #[uniffi::constructor]
+    pub fn from_scanned_chunks(chunks: Vec<Vec<u8>>) -> StorageResult<Arc<Self>> {
+        let mut ordered: Vec<Option<Vec<u8>>> = Vec::new();
+        let mut transfer = None;
+        for chunk in chunks {
+            let header = ChunkHeader::parse(&chunk)?;
+            let (transfer_id, count) = *transfer.get_or_insert_with(|| {
+                ordered.resize(usize::from(header.count), None);
+                (header.transfer_id, header.count)
+            });
+            if header.transfer_id != transfer_id || header.count != count {
+                return Err(StorageError::Serialization(
+                    "chunk belongs to another QR transfer".to_string(),
+                ));
+            }
+            ordered[usize::from(header.index)] = Some(chunk);
+        }
+
+        let chunks = ordered.into_iter().collect::<Option<Vec<_>>>();
+        match chunks {
+            Some(chunks) if !chunks.is_empty() => Ok(Arc::new(Self {
+                version: QR_TRANSFER_BUNDLE_VERSION,
+                chunks,
+            })),
+            _ => Err(StorageError::Serialization(
+                "QR transfer is missing chunks".to_string(),
+            )),
+        }
+    }
+
+    /// Returns the number of chunks, i.e. of QR codes to show.
+    #[must_use]
+    pub fn chunk_count(&self) -> u32 {
+        // at most `u16::MAX` chunks, see `split`
+        u32::try_from(self.chunks.len()).unwrap_or(u32::MAX)
+    }
+
+    /// Renders chunk `chunk_index` as a QR code, returned as a UTF-8 SVG
+    /// document.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::Serialization`] if there is no such chunk or
+    /// it does not fit in a QR code.
+    pub fn to_qr_bytes(&self, chunk_index: u32) -> StorageResult<Vec<u8>> {
+        let chunk = usize::try_from(chunk_index)
+            .ok()
+            .and_then(|index| self.chunks.get(index))
+            .ok_or_else(|| {
+                StorageError::Serialization(format!(
+                    "chunk {chunk_index} out of range, bundle has {}",
+                    self.chunks.len()
+                ))
+            })?;
+        let code = QrCode::with_error_correction_level(chunk, EcLevel::L)
+            .map_err(|err| StorageError::Serialization(err.to_string()))?;
+        Ok(code.render::<svg::Color<'_>>().build().into_bytes())
+    }
+}
+
+impl QrTransferBundle {
+    /// Splits `package` into chunks.
+    fn split(package: &MigrationPackage) -> StorageResult<Self> {
+        let state_len = u32::try_from(package.encrypted_state.len()).map_err(|_| {
+            StorageError::Serialization("migration state too large".to_string())
+        })?;
+        let mut payload = package.sender_pub.clone();
+        payload.extend_from_slice(&state_len.to_be_bytes());
+        payload.extend_from_slice(&package.encrypted_state);
+        payload.extend_from_slice(&package.encrypted_vault);
+
+        let pieces = payload.chunks(MAX_CHUNK_LEN - HEADER_LEN);
+        let count = u16::try_from(pieces.len()).map_err(|_| {
+            StorageError::Serialization(format!(
+                "vault too large for a QR transfer: {} bytes",
+                package.encrypted_vault.len()
+            ))
+        })?;
+        let mut transfer_id = [0u8; 4];
+        entropy::fill(&mut transfer_id)?;
+
+        let chunks = (0..count)
+            .zip(pieces)
+            .map(|(index, piece)| {
+                let mut chunk = Vec::with_capacity(HEADER_LEN + piece.len());
+                chunk.push(QR_TRANSFER_BUNDLE_VERSION);
+                chunk.extend_from_slice(&transfer_id);
+                chunk.extend_from_slice(&index.to_be_bytes());
+                chunk.extend_from_slice(&count.to_be_bytes());
+                chunk.extend_from_slice(piece);
+                chunk
+            })
+            .collect();
+        Ok(Self {
+            version: QR_TRANSFER_BUNDLE_VERSION,
+            chunks,
+        })
+    }
+
+    /// Reassembles the [`MigrationPackage`] from the chunks.
+    fn join(&self) -> StorageResult<MigrationPackage> {
+        if self.version != QR_TRANSFER_BUNDLE_VERSION {
+            return Err(StorageError::Serialization(format!(
+                "unsupported QR transfer bundle version {}",
+                self.version
+            )));
+        }
+        let mut payload = Vec::new();
+        for (position, chunk) in self.chunks.iter().enumerate() {
+            let header = ChunkHeader::parse(chunk)?;
+            if usize::from(header.index) != position
+                || usize::from(header.count) != self.chunks.len()
+            {
+                return Err(StorageError::Serialization(
+                    "QR transfer chunks out of order".to_string(),
+                ));
+            }
+            payload.extend_from_slice(&chunk[HEADER_LEN..]);
+        }
+
+        let malformed =
+            || StorageError::Serialization("malformed QR transfer payload".to_string());
+        let (sender_pub, rest) = payload
+            .split_at_checked(PUBLIC_KEY_LEN)
+            .ok_or_else(malformed)?;
+        let (state_len, rest) = rest.split_first_chunk::<4>().ok_or_else(malformed)?;
+        let state_len =
+            usize::try_from(u32::from_be_bytes(*state_len)).map_err(|_| malformed())?;
+        let (encrypted_state, encrypted_vault) =
+            rest.split_at_checked(state_len).ok_or_else(malformed)?;
+        Ok(MigrationPackage {
+            version: MIGRATION_PACKAGE_VERSION,
+            encrypted_state: encrypted_state.to_vec(),
+            encrypted_vault: encrypted_vault.to_vec(),
+            sender_pub: sender_pub.to_vec(),
+        })
+    }
+}
+
+/// Header of a [`QrTransferBundle`] chunk.
+struct ChunkHeader {
+    transfer_id: [u8; 4],
+    index: u16,
+    count: u16,
+}
+
+impl ChunkHeader {
+    fn parse(chunk: &[u8]) -> StorageResult<Self> {
+        let Some((&[version, t0, t1, t2, t3, i0, i1, c0, c1], _)) =
+            chunk.split_first_chunk::<HEADER_LEN>()
+        else {
+            return Err(StorageError::Serialization(format!(
+                "QR transfer chunk of {} bytes is too short",
+                chunk.len()
+            )));
+        };
+        if version != QR_TRANSFER_BUNDLE_VERSION {
+            return Err(StorageError::Serialization(format!(
+                "unsupported QR transfer bundle version {version}"
+            )));
+        }
+        let header = Self {
+            transfer_id: [t0, t1, t2, t3],
+            index: u16::from_be_bytes([i0, i1]),
+            count: u16::from_be_bytes([c0, c1]),
+        };
+        if header.index >= header.count {
+            return Err(StorageError::Serialization(format!(
+                "QR transfer chunk index {} out of range for {} chunks",
+                header.index, header.count
+            )));
+        }
+        Ok(header)
+    }
+}
+
+#[uniffi::export]
+impl CredentialStore {
+    /// Exports the account as a [`QrTransferBundle`] encrypted to
+    /// `target_pub`, the SEC1-compressed public key of a
+    /// [`MigrationRecipient`] on the receiving device.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package cannot be exported (see
+    /// [`export_migration_package`](Self::export_migration_package)) or is
+    /// too large for a QR transfer.
+    pub fn export_to_qr_transfer_bundle(
+        &self,
+        target_pub: Vec<u8>,
+    ) -> StorageResult<Arc<QrTransferBundle>> {
+        let package = self.export_migration_package(target_pub)?;
+        QrTransferBundle::split(&package).map(Arc::new)
+    }
+
+    /// Imports a [`QrTransferBundle`] addressed to `recipient`.
+    ///
+    /// Like [`import_migration_package`](Self::import_migration_package),
+    /// intended for a fresh install. The bundle is reassembled, decrypted and
+    /// checked before the store is initialized, and a fresh store is left
+    /// uninitialized if the import fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bundle is malformed, or if the package cannot
+    /// be imported (see
+    /// [`import_migration_package`](Self::import_migration_package)).
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn import_from_qr_transfer_bundle(
+        &self,
+        bundle: Arc<QrTransferBundle>,
+        recipient: Arc<MigrationRecipient>,
+        now: u64,
+    ) -> StorageResult<()> {
+        self.import_migration_package(bundle.join()?, recipient, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use world_id_core::Credential as CoreCredential;
+
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+    use crate::{Credential, FieldElement};
+
+    fn new_device() -> (CredentialStore, std::path::PathBuf) {
+        let root = temp_root_path();
+        let provider = InMemoryStorageProvider::new(&root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        (store, root)
+    }
+
+    #[test]
+    fn test_qr_transfer_round_trip() {
+        let (old_store, old_root) = new_device();
+        old_store.init(42, 1000).expect("init old device");
+        for issuer_schema_id in 1..=3u64 {
+            let credential: Credential = CoreCredential::new()
+                .issuer_schema_id(issuer_schema_id)
+                .genesis_issued_at(1000)
+                .into();
+            old_store
+                .store_credential(
+                    &credential,
+                    &FieldElement::from(7u64),
+                    9999,
+                    Some(vec![0xab; 4096]),
+                    1000,
+                )
+                .expect("store credential");
+        }
+
+        let recipient = MigrationRecipient::new().expect("recipient");
+        let bundle = old_store
+            .export_to_qr_transfer_bundle(recipient.public_key())
+            .expect("export bundle");
+        assert!(bundle.chunk_count() > 1);
+        assert!(bundle.chunks.iter().all(|chunk| chunk.len() < QR_CAPACITY));
+        let svg = bundle.to_qr_bytes(0).expect("render QR code");
+        assert!(String::from_utf8(svg).expect("utf-8").contains("<svg"));
+        assert!(bundle.to_qr_bytes(bundle.chunk_count()).is_err());
+
+        // scanned in reverse, with a duplicate
+        let mut scanned = bundle.chunks.clone();
+        scanned.reverse();
+        scanned.push(bundle.chunks[0].clone());
+        let received =
+            QrTransferBundle::from_scanned_chunks(scanned).expect("reassemble");
+        assert_eq!(received, bundle);
+
+        let (new_store, new_root) = new_device();
+        new_store
+            .import_from_qr_transfer_bundle(received, recipient, 2000)
+            .expect("import bundle");
+        assert_eq!(new_store.leaf_index().expect("leaf index"), 42);
+        let records = new_store.list_credentials(None, 2000).expect("list");
+        assert_eq!(records.len(), 3);
+
+        cleanup_test_storage(&old_root);
+        cleanup_test_storage(&new_root);
+    }
+
+    #[test]
+    fn test_tampered_qr_transfer_leaves_store_uninitialized() {
+        let (old_store, old_root) = new_device();
+        old_store.init(42, 1000).expect("init old device");
+        let recipient = MigrationRecipient::new().expect("recipient");
+        let bundle = old_store
+            .export_to_qr_transfer_bundle(recipient.public_key())
+            .expect("export bundle");
+
+        let mut chunks = bundle.chunks.clone();
+        let last = chunks.last_mut().expect("chunk");
+        *last.last_mut().expect("payload") ^= 1;
+        let tampered =
+            QrTransferBundle::from_scanned_chunks(chunks).expect("reassemble");
+
+        let (new_store, new_root) = new_device();
+        assert!(matches!(
+            new_store.import_from_qr_transfer_bundle(
+                tampered,
+                Arc::clone(&recipient),
+                2000
+            ),
+            Err(StorageError::Crypto(_))
+        ));
+        assert!(matches!(
+            new_store.leaf_index(),
+            Err(StorageError::NotInitialized)
+        ));
+        new_store
+            .import_from_qr_transfer_bundle(bundle, recipient, 2000)
+            .expect("import bundle");
+        assert_eq!(new_store.leaf_index().expect("leaf index"), 42);
+
+        cleanup_test_storage(&old_root);
+        cleanup_test_storage(&new_root);
+    }
+
+    #[test]
+    fn test_qr_transfer_rejects_incomplete_or_mixed_scans() {
+        let (store, root) = new_device();
+        store.init(42, 1000).expect("init");
+        let recipient = MigrationRecipient::new().expect("recipient");
+        let export = || {
+            store
+                .export_to_qr_transfer_bundle(recipient.public_key())
+                .expect("export bundle")
+        };
+        let first = export();
+        let second = export();
+
+        assert!(matches!(
+            QrTransferBundle::from_scanned_chunks(vec![]),
+            Err(StorageError::Serialization(_))
+        ));
+        assert!(matches!(
+            QrTransferBundle::from_scanned_chunks(vec![vec![
+                QR_TRANSFER_BUNDLE_VERSION
+                    + 1;
+                HEADER_LEN
+            ]]),
+            Err(StorageError::Serialization(_))
+        ));
+        let mut mixed = first.chunks.clone();
+        mixed.extend(second.chunks.iter().cloned());
+        assert!(matches!(
+            QrTransferBundle::from_scanned_chunks(mixed),
+            Err(StorageError::Serialization(_))
+        ));
+
+        cleanup_test_storage(&root);
+    }
+}
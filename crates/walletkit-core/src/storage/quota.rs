@@ -0,0 +1,72 @@
+//! Storage quota checks for platforms that can evict or cap storage.
+//!
+//! A torn vault write is much worse than a refused one, so when the host
+//! registers a [`StorageQuotaProvider`] the store checks the quota before
+//! initializing and before large writes rather than letting `SQLite` fail
+//! mid-transaction.
+
+use std::sync::Arc;
+
+use super::error::{StorageError, StorageResult};
+use super::traits::StorageQuotaProvider;
+use super::types::{StorageEstimate, StorageQuotaPolicy};
+
+/// A registered quota provider and the policy enforced against it.
+pub(super) struct QuotaGuard {
+    provider: Arc<dyn StorageQuotaProvider>,
+    policy: StorageQuotaPolicy,
+}
+
+impl QuotaGuard {
+    pub(super) fn new(
+        provider: Arc<dyn StorageQuotaProvider>,
+        policy: StorageQuotaPolicy,
+    ) -> Self {
+        Self { provider, policy }
+    }
+
+    /// Checks that enough quota is available to initialize the store,
+    /// requesting persistent storage first if the policy asks for it.
+    ///
+    /// A denied persistence request is not an error on its own; it only means
+    /// the platform may still evict the data.
+    pub(super) fn preflight(&self) -> StorageResult<StorageEstimate> {
+        let mut estimate = self.provider.storage_estimate()?;
+        if self.policy.request_persistence && !estimate.persistent {
+            estimate.persistent = self.provider.request_persistence()?;
+            if !estimate.persistent {
+                tracing::warn!(
+                    "persistent storage was denied; stored credentials may be evicted"
+                );
+            }
+        }
+        self.ensure_available(&estimate, 0)?;
+        Ok(estimate)
+    }
+
+    /// Checks that a write of `bytes` leaves at least the policy minimum
+    /// available. Writes smaller than `large_write_bytes` are not checked.
+    pub(super) fn check_write(&self, bytes: u64) -> StorageResult<()> {
+        if bytes < self.policy.large_write_bytes {
+            return Ok(());
+        }
+        let estimate = self.provider.storage_estimate()?;
+        self.ensure_available(&estimate, bytes)
+    }
+
+    const fn ensure_available(
+        &self,
+        estimate: &StorageEstimate,
+        bytes: u64,
+    ) -> StorageResult<()> {
+        let required = self.policy.min_available_bytes.saturating_add(bytes);
+        let available = estimate.available();
+        if available < required {
+            return Err(StorageError::InsufficientStorageQuota {
+                available,
+                required,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+//! Key-value store for small host secrets, kept in the vault.
+//!
+//! Meant for values that belong next to World ID state, like push tokens for
+//! gateway callbacks or the last used RP IDs, so hosts don't need their own
+//! encryption for them. Prefs are included in vault backups and removed by
+//! [`CredentialStore::danger_delete_all_credentials`] and
+//! [`CredentialStore::destroy_storage`]. See
+//! [`MAX_SECURE_PREF_KEY_BYTES`], [`MAX_SECURE_PREF_VALUE_BYTES`] and
+//! [`MAX_SECURE_PREFS`] for the limits.
+
+use std::sync::Arc;
+
+use super::error::StorageResult;
+use super::CredentialStore;
+#[cfg(doc)]
+use super::{MAX_SECURE_PREFS, MAX_SECURE_PREF_KEY_BYTES, MAX_SECURE_PREF_VALUE_BYTES};
+
+/// Secure preferences of a [`CredentialStore`].
+#[derive(Debug, uniffi::Object)]
+pub struct SecurePrefs {
+    store: Arc<CredentialStore>,
+}
+
+#[uniffi::export]
+impl CredentialStore {
+    /// Returns the secure preferences kept in this store's vault.
+    #[must_use]
+    pub fn secure_prefs(self: Arc<Self>) -> Arc<SecurePrefs> {
+        Arc::new(SecurePrefs { store: self })
+    }
+}
+
+#[uniffi::export]
+impl SecurePrefs {
+    /// Stores `value` under `key`, replacing any previous value.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::SecurePrefsLimit`](super::StorageError::SecurePrefsLimit)
+    /// if a limit is exceeded, or an error if the store is not initialized or
+    /// the write fails.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn set(&self, key: String, value: Vec<u8>) -> StorageResult<()> {
+        self.store
+            .with_vault(|vault| vault.secure_prefs_set(&key, &value))?;
+        self.store.notify_vault_changed();
+        Ok(())
+    }
+
+    /// Returns the value stored under `key`, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized, the read fails, or
+    /// the stored value was tampered with.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn get(&self, key: String) -> StorageResult<Option<Vec<u8>>> {
+        self.store.with_vault(|vault| vault.secure_prefs_get(&key))
+    }
+
+    /// Deletes the value stored under `key`. Deleting a missing key is not an
+    /// error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the delete fails.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn delete(&self, key: String) -> StorageResult<()> {
+        if self
+            .store
+            .with_vault(|vault| vault.secure_prefs_delete(&key))?
+        {
+            self.store.notify_vault_changed();
+        }
+        Ok(())
+    }
+
+    /// Lists the stored keys, in lexicographic order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store is not initialized or the read fails.
+    pub fn keys(&self) -> StorageResult<Vec<String>> {
+        self.store
+            .with_vault(super::CredentialVault::secure_prefs_keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests_utils::{
+        cleanup_test_storage, temp_root_path, InMemoryStorageProvider,
+    };
+    use crate::storage::{
+        StorageError, MAX_SECURE_PREFS, MAX_SECURE_PREF_KEY_BYTES,
+        MAX_SECURE_PREF_VALUE_BYTES,
+    };
+
+    fn new_store(root: &std::path::Path) -> Arc<CredentialStore> {
+        let provider = InMemoryStorageProvider::new(root);
+        let store = CredentialStore::from_provider(&provider).expect("create store");
+        store.init(42, 1000).expect("init");
+        Arc::new(store)
+    }
+
+    fn assert_limit<T: std::fmt::Debug>(result: StorageResult<T>) {
+        match result {
+            Err(StorageError::SecurePrefsLimit(_)) => {}
+            other => panic!("expected a limit error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let root = temp_root_path();
+        let prefs = new_store(&root).secure_prefs();
+
+        assert_eq!(prefs.get("push_token".to_string()).unwrap(), None);
+        prefs
+            .set("push_token".to_string(), b"token-1".to_vec())
+            .expect("set");
+        prefs
+            .set("last_rp".to_string(), b"rp_123".to_vec())
+            .expect("set");
+        prefs
+            .set("push_token".to_string(), b"token-2".to_vec())
+            .expect("overwrite");
+        prefs
+            .set("empty".to_string(), Vec::new())
+            .expect("set empty");
+
+        assert_eq!(
+            prefs.get("push_token".to_string()).unwrap(),
+            Some(b"token-2".to_vec())
+        );
+        assert_eq!(prefs.get("empty".to_string()).unwrap(), Some(Vec::new()));
+        assert_eq!(prefs.keys().unwrap(), ["empty", "last_rp", "push_token"]);
+
+        prefs.delete("last_rp".to_string()).expect("delete");
+        prefs.delete("last_rp".to_string()).expect("delete missing");
+        assert_eq!(prefs.get("last_rp".to_string()).unwrap(), None);
+        assert_eq!(prefs.keys().unwrap(), ["empty", "push_token"]);
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_wiped_with_credentials() {
+        let root = temp_root_path();
+        let store = new_store(&root);
+        let prefs = Arc::clone(&store).secure_prefs();
+        prefs
+            .set("push_token".to_string(), b"token".to_vec())
+            .unwrap();
+
+        store.danger_delete_all_credentials().expect("delete all");
+        assert!(prefs.keys().unwrap().is_empty());
+        // a new data key is generated on the next write
+        prefs
+            .set("push_token".to_string(), b"again".to_vec())
+            .unwrap();
+        assert_eq!(
+            prefs.get("push_token".to_string()).unwrap(),
+            Some(b"again".to_vec())
+        );
+
+        store.destroy_storage().expect("destroy");
+        assert!(matches!(prefs.keys(), Err(StorageError::NotInitialized)));
+        let store = new_store(&root);
+        assert!(store.secure_prefs().keys().unwrap().is_empty());
+
+        cleanup_test_storage(&root);
+    }
+
+    #[test]
+    fn test_carried_by_backup() {
+        let src_root = temp_root_path();
+        let src = new_store(&src_root);
+        Arc::clone(&src)
+            .secure_prefs()
+            .set("push_token".to_string(), b"token".to_vec())
+            .unwrap();
+        let backup = src.export_vault_for_backup().expect("export");
+
+        // the destination has its own keystore and `K_intermediate`
+        let dst_root = temp_root_path();
+        let dst = new_store(&dst_root);
+        dst.import_vault_from_backup(&backup).expect("import");
+        assert_eq!(
+            dst.secure_prefs().get("push_token".to_string()).unwrap(),
+            Some(b"token".to_vec())
+        );
+
+        cleanup_test_storage(&src_root);
+        cleanup_test_storage(&dst_root);
+    }
+
+    #[test]
+    fn test_limits() {
+        let root = temp_root_path();
+        let prefs = new_store(&root).secure_prefs();
+
+        assert_limit(prefs.set(String::new(), Vec::new()));
+        assert_limit(prefs.set("k".repeat(MAX_SECURE_PREF_KEY_BYTES + 1), Vec::new()));
+        prefs
+            .set("k".repeat(MAX_SECURE_PREF_KEY_BYTES), Vec::new())
+            .expect("name at the limit");
+        assert_limit(
+            prefs.set("big".to_string(), vec![0; MAX_SECURE_PREF_VALUE_BYTES + 1]),
+        );
+        prefs
+            .set("big".to_string(), vec![0; MAX_SECURE_PREF_VALUE_BYTES])
+            .expect("value at the limit");
+
+        for i in 2..MAX_SECURE_PREFS {
+            prefs.set(format!("pref_{i}"), vec![1]).expect("set");
+        }
+        assert_limit(prefs.set("one_too_many".to_string(), vec![1]));
+        // overwriting an existing pref does not count against the limit
+        prefs
+            .set("big".to_string(), vec![2])
+            .expect("overwrite at the limit");
+        assert_eq!(prefs.keys().unwrap().len(), MAX_SECURE_PREFS as usize);
+
+        cleanup_test_storage(&root);
+    }
+}
@@ -1,10 +1,11 @@
 //! Test helpers for credential storage.
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs,
     path::PathBuf,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use chacha20poly1305::{
@@ -17,12 +18,15 @@ use uuid::Uuid;
 use std::path::Path;
 
 use super::{
-    error::StorageError,
+    error::{StorageError, StorageResult},
     paths::StoragePaths,
-    traits::{DeviceKeystore, StorageProvider},
+    traits::{DeterministicKeystore, DeviceKeystore, StorageProvider},
     AtomicBlobStore,
 };
 
+/// Environment scope of the replay guard and session seeds in tests.
+pub const TEST_ENVIRONMENT: [u8; 32] = [0xEE; 32];
+
 pub struct InMemoryKeystore {
     key: [u8; 32],
 }
@@ -55,6 +59,11 @@ impl InMemoryKeystore {
         OsRng.fill_bytes(&mut key);
         Self { key }
     }
+
+    /// Creates a keystore with a fixed device key, for reproducible output.
+    pub const fn with_key(key: [u8; 32]) -> Self {
+        Self { key }
+    }
 }
 
 impl Default for InMemoryKeystore {
@@ -69,22 +78,9 @@ impl DeviceKeystore for InMemoryKeystore {
         associated_data: Vec<u8>,
         plaintext: Vec<u8>,
     ) -> Result<Vec<u8>, StorageError> {
-        let cipher = XChaCha20Poly1305::new(Key::from_slice(&self.key));
         let mut nonce_bytes = [0u8; 24];
-        OsRng.fill_bytes(&mut nonce_bytes);
-        let ciphertext = cipher
-            .encrypt(
-                XNonce::from_slice(&nonce_bytes),
-                Payload {
-                    msg: &plaintext,
-                    aad: &associated_data,
-                },
-            )
-            .map_err(|err| StorageError::Crypto(err.to_string()))?;
-        let mut out = Vec::with_capacity(nonce_bytes.len() + ciphertext.len());
-        out.extend_from_slice(&nonce_bytes);
-        out.extend_from_slice(&ciphertext);
-        Ok(out)
+        super::entropy::fill_nonce(&mut nonce_bytes)?;
+        self.seal_with_nonce(&associated_data, &plaintext, &nonce_bytes)
     }
 
     fn open_sealed(
@@ -111,6 +107,36 @@ impl DeviceKeystore for InMemoryKeystore {
     }
 }
 
+impl DeterministicKeystore for InMemoryKeystore {
+    fn seal_with_nonce(
+        &self,
+        associated_data: &[u8],
+        plaintext: &[u8],
+        nonce: &[u8],
+    ) -> Result<Vec<u8>, StorageError> {
+        if nonce.len() != 24 {
+            return Err(StorageError::Crypto(format!(
+                "nonce length mismatch: expected 24, got {}",
+                nonce.len()
+            )));
+        }
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(&self.key));
+        let ciphertext = cipher
+            .encrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: associated_data,
+                },
+            )
+            .map_err(|err| StorageError::Crypto(err.to_string()))?;
+        let mut out = Vec::with_capacity(nonce.len() + ciphertext.len());
+        out.extend_from_slice(nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+}
+
 pub struct InMemoryBlobStore {
     blobs: Mutex<HashMap<String, Vec<u8>>>,
 }
@@ -155,9 +181,122 @@ impl AtomicBlobStore for InMemoryBlobStore {
     }
 }
 
+#[derive(Default)]
+struct FaultState {
+    reads_left: Option<usize>,
+    writes_left: Option<usize>,
+    corrupted: HashSet<String>,
+    delay: Duration,
+    operations: usize,
+}
+
+/// Failures injected into the blob store of an [`InMemoryStorageProvider`],
+/// see [`InMemoryStorageProvider::with_fault_injector`].
+///
+/// Clones share their configuration, so faults can be set up or changed
+/// after the provider is built. Deletes count as writes.
+#[derive(Clone, Default)]
+pub struct FaultInjector {
+    state: Arc<Mutex<FaultState>>,
+}
+
+#[derive(Clone, Copy)]
+enum Operation {
+    Read,
+    Write,
+}
+
+impl FaultInjector {
+    /// Lets the next `n` reads succeed and fails every read after them.
+    pub fn fail_reads_after(&self, n: usize) {
+        self.state().reads_left = Some(n);
+    }
+
+    /// Lets the next `n` writes succeed and fails every write after them.
+    pub fn fail_writes_after(&self, n: usize) {
+        self.state().writes_left = Some(n);
+    }
+
+    /// Flips every byte of the blob at `name` when it is read.
+    pub fn corrupt_blob(&self, name: &str) {
+        self.state().corrupted.insert(name.to_string());
+    }
+
+    /// Delays every operation by `ms` milliseconds.
+    pub fn delay_operations_ms(&self, ms: u64) {
+        self.state().delay = Duration::from_millis(ms);
+    }
+
+    /// Returns the number of operations attempted so far, failed ones
+    /// included.
+    pub fn operation_count(&self) -> usize {
+        self.state().operations
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, FaultState> {
+        self.state.lock().expect("fault injector mutex poisoned")
+    }
+
+    fn intercept(&self, operation: Operation) -> StorageResult<()> {
+        let (delay, result) = {
+            let mut state = self.state();
+            state.operations += 1;
+            let (left, kind) = match operation {
+                Operation::Read => (&mut state.reads_left, "read"),
+                Operation::Write => (&mut state.writes_left, "write"),
+            };
+            let result = match left {
+                Some(0) => {
+                    Err(StorageError::BlobStore(format!("injected {kind} failure")))
+                }
+                Some(left) => {
+                    *left -= 1;
+                    Ok(())
+                }
+                None => Ok(()),
+            };
+            (state.delay, result)
+        };
+        std::thread::sleep(delay);
+        result
+    }
+}
+
+/// Blob store that runs every operation through a [`FaultInjector`].
+struct FaultyBlobStore {
+    inner: Arc<dyn AtomicBlobStore>,
+    injector: FaultInjector,
+}
+
+impl AtomicBlobStore for FaultyBlobStore {
+    fn read(&self, path: String) -> StorageResult<Option<Vec<u8>>> {
+        self.injector.intercept(Operation::Read)?;
+        let corrupted = self.injector.state().corrupted.contains(&path);
+        let bytes = self.inner.read(path)?;
+        Ok(bytes.map(|mut bytes| {
+            if corrupted {
+                for byte in &mut bytes {
+                    *byte = !*byte;
+                }
+            }
+            bytes
+        }))
+    }
+
+    fn write_atomic(&self, path: String, bytes: Vec<u8>) -> StorageResult<()> {
+        self.injector.intercept(Operation::Write)?;
+        self.inner.write_atomic(path, bytes)
+    }
+
+    fn delete(&self, path: String) -> StorageResult<()> {
+        self.injector.intercept(Operation::Write)?;
+        self.inner.delete(path)
+    }
+}
+
 pub struct InMemoryStorageProvider {
     keystore: Arc<InMemoryKeystore>,
-    blob_store: Arc<InMemoryBlobStore>,
+    blob_store: Arc<dyn AtomicBlobStore>,
     paths: Arc<StoragePaths>,
 }
 
@@ -169,6 +308,21 @@ impl InMemoryStorageProvider {
             paths: Arc::new(StoragePaths::new(root)),
         }
     }
+
+    /// Routes blob store operations through `injector`.
+    ///
+    /// The vault and cache databases are opened from [`StoragePaths`] rather
+    /// than through the provider, so only the blob store is intercepted.
+    #[must_use]
+    pub fn with_fault_injector(self, injector: FaultInjector) -> Self {
+        Self {
+            blob_store: Arc::new(FaultyBlobStore {
+                inner: self.blob_store,
+                injector,
+            }),
+            ..self
+        }
+    }
 }
 
 impl StorageProvider for InMemoryStorageProvider {
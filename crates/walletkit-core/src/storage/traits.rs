@@ -6,19 +6,24 @@
 //!
 //! # Expected platform components
 //!
-//! - **iOS (Swift):** [`DeviceKeystore`] backed by Keychain / Secure Enclave;
+//! - **iOS (Swift):** [`DeviceKeystore`] backed by Keychain / Secure Enclave,
+//!   with a configurable [`KeychainAccessibility`](super::KeychainAccessibility);
 //!   [`AtomicBlobStore`] over the app container filesystem (atomic replace).
 //! - **Android (Kotlin):** [`DeviceKeystore`] backed by the Android Keystore;
 //!   [`AtomicBlobStore`] over app internal storage (atomic replace).
 //! - **Node.js:** file-backed [`DeviceKeystore`] (development; production can use an
 //!   OS keystore); [`AtomicBlobStore`] over app internal storage.
 //! - **Browser (WASM):** `WebCrypto`-backed [`DeviceKeystore`]; [`AtomicBlobStore`]
-//!   over an origin-private storage namespace.
+//!   over an origin-private storage namespace. The `platform-web` feature
+//!   provides both, and the OPFS-backed databases, in `storage::web`.
 
 use std::sync::Arc;
 
 use super::error::StorageResult;
 use super::paths::StoragePaths;
+#[cfg(not(target_arch = "wasm32"))]
+use super::types::CredentialExpiryEvent;
+use super::types::StorageEstimate;
 
 /// Device keystore interface used to seal and open account keys.
 #[uniffi::export(with_foreign)]
@@ -52,6 +57,30 @@ pub trait DeviceKeystore: Send + Sync {
     ) -> StorageResult<Vec<u8>>;
 }
 
+/// Deterministic sealing for golden-file tests.
+///
+/// A Rust-only extension of [`DeviceKeystore`] (it is not part of the foreign
+/// interface) available with the `deterministic-crypto` feature.
+///
+/// **Production keystores must NOT implement this trait.** A caller-chosen
+/// nonce can be reused, which breaks the confidentiality and integrity of
+/// everything sealed under the device key.
+#[cfg(any(test, feature = "deterministic-crypto"))]
+pub trait DeterministicKeystore: DeviceKeystore {
+    /// Seals like [`DeviceKeystore::seal`], using `nonce` instead of a random
+    /// one, so the output is reproducible.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the nonce has the wrong length or the seal fails.
+    fn seal_with_nonce(
+        &self,
+        associated_data: &[u8],
+        plaintext: &[u8],
+        nonce: &[u8],
+    ) -> StorageResult<Vec<u8>>;
+}
+
 /// Atomic blob store for small binary files (e.g., `account_keys.bin`).
 #[uniffi::export(with_foreign)]
 pub trait AtomicBlobStore: Send + Sync {
@@ -77,6 +106,30 @@ pub trait AtomicBlobStore: Send + Sync {
     fn delete(&self, path: String) -> StorageResult<()>;
 }
 
+/// Reports and negotiates the storage quota available to the host.
+///
+/// Meant for browsers, where origin storage can be evicted or capped (Safari
+/// ITP, private browsing). A web host wraps `navigator.storage.estimate()` and
+/// `navigator.storage.persist()`, resolving the promises before returning.
+/// Register via [`super::CredentialStore::set_storage_quota_provider`].
+#[uniffi::export(with_foreign)]
+pub trait StorageQuotaProvider: Send + Sync {
+    /// Returns the current quota, usage and persistence state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the estimate is unavailable.
+    fn storage_estimate(&self) -> StorageResult<StorageEstimate>;
+
+    /// Asks the platform to make storage persistent. Returns whether storage
+    /// is persistent afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request cannot be made.
+    fn request_persistence(&self) -> StorageResult<bool>;
+}
+
 /// Provider responsible for platform-specific storage components and paths.
 #[uniffi::export(with_foreign)]
 pub trait StorageProvider: Send + Sync {
@@ -115,3 +168,22 @@ pub trait VaultChangedListener: Send + Sync {
     /// Called after a credential is added or removed.
     fn on_vault_changed(&self);
 }
+
+/// Listener notified when a stored credential expires.
+///
+/// Register via [`super::CredentialStore::set_credential_expiry_listener`]. The
+/// store is polled on a dedicated background thread and the callback is
+/// delivered on that thread.
+///
+/// # Safety
+///
+/// **Warning:** implementors **must not** call back into
+/// [`super::CredentialStore`] from
+/// [`on_credential_expiry`](CredentialExpiryListener::on_credential_expiry) —
+/// doing so will deadlock.
+#[cfg(not(target_arch = "wasm32"))]
+#[uniffi::export(with_foreign)]
+pub trait CredentialExpiryListener: Send + Sync {
+    /// Called once for each credential that expired since the previous poll.
+    fn on_credential_expiry(&self, event: CredentialExpiryEvent);
+}
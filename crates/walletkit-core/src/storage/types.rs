@@ -33,6 +33,37 @@ impl TryFrom<i64> for BlobKind {
     }
 }
 
+/// Registry whose Merkle inclusion proof is cached.
+///
+/// The kind is part of the cache key, so proofs for different registries never
+/// overwrite each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+#[repr(u8)]
+pub enum RegistryKind {
+    /// The World ID account registry.
+    AccountRegistry = 1,
+}
+
+impl RegistryKind {
+    /// Every known kind.
+    pub(crate) const ALL: [Self; 1] = [Self::AccountRegistry];
+
+    pub(crate) const fn as_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+impl TryFrom<u8> for RegistryKind {
+    type Error = StorageError;
+
+    fn try_from(value: u8) -> StorageResult<Self> {
+        match value {
+            1 => Ok(Self::AccountRegistry),
+            _ => Err(StorageError::UnknownRegistryKind(value)),
+        }
+    }
+}
+
 pub use walletkit_db::ContentId;
 
 /// Request identifier for replay guard.
@@ -41,6 +72,18 @@ pub type RequestId = [u8; 32];
 /// Nullifier identifier used for replay safety.
 pub type Nullifier = [u8; 32];
 
+/// Usability of a stored credential.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum CredentialStatus {
+    /// The credential can be used in proofs.
+    Active,
+    /// The credential has expired (`now >= expires_at`).
+    Expired,
+    /// The credential was issued before the account's latest recovery and can
+    /// no longer be used in proofs.
+    Invalidated,
+}
+
 /// In-memory representation of stored credential metadata.
 ///
 /// This is intentionally small and excludes blobs; full credential payloads can
@@ -59,6 +102,298 @@ pub struct CredentialRecord {
     ///
     /// This value is computed when listing credentials and is not persisted.
     pub is_expired: bool,
+    /// Account recovery counter in effect when the credential was stored.
+    pub recovery_counter: u64,
+    /// Usability of the credential at query time.
+    ///
+    /// [`CredentialStatus::Invalidated`] takes precedence over
+    /// [`CredentialStatus::Expired`].
+    pub status: CredentialStatus,
+    /// Whether associated data was stored with the credential.
+    pub has_associated_data: bool,
+    /// Number of proofs the credential was disclosed in.
+    pub use_count: u64,
+    /// When the credential was last disclosed in a proof (seconds), if ever.
+    pub last_used_at: Option<u64>,
+}
+
+#[uniffi::export]
+impl CredentialRecord {
+    /// Whether the credential can be used in a proof at `now`.
+    ///
+    /// Unlike [`status`](Self::status), which is computed when the record is
+    /// listed, this is evaluated against the given `now`.
+    #[must_use]
+    pub fn is_eligible(&self, now: u64) -> bool {
+        self.is_eligible_with_grace_period(now, 0)
+    }
+
+    /// Like [`is_eligible`](Self::is_eligible), treating the credential as
+    /// expiring `grace_seconds` after `expires_at`.
+    ///
+    /// Tolerates clock skew between the device and verifiers such as NFC
+    /// readers. **Security tradeoff:** any grace period lets a credential be
+    /// presented after it has technically expired; keep it to a few seconds.
+    #[must_use]
+    pub fn is_eligible_with_grace_period(&self, now: u64, grace_seconds: u64) -> bool {
+        self.status != CredentialStatus::Invalidated
+            && now < self.expires_at.saturating_add(grace_seconds)
+    }
+}
+
+/// Selects credentials by issuer schema and eligibility.
+#[derive(Debug, Clone, Default, PartialEq, Eq, uniffi::Record)]
+pub struct CredentialFilter {
+    /// Only match credentials of this issuer schema, if set.
+    pub issuer_schema_id: Option<u64>,
+    /// Seconds past `expires_at` during which a credential still matches.
+    ///
+    /// See [`CredentialRecord::is_eligible_with_grace_period`] for the
+    /// security tradeoff.
+    pub expiry_grace_seconds: u64,
+}
+
+#[uniffi::export]
+impl CredentialFilter {
+    /// Returns a copy of the filter restricted to `issuer_schema_id`.
+    #[must_use]
+    pub fn with_issuer_schema_id(&self, issuer_schema_id: u64) -> Self {
+        Self {
+            issuer_schema_id: Some(issuer_schema_id),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of the filter that still matches credentials up to
+    /// `seconds` after they expire.
+    ///
+    /// This reduces failures caused by clock skew, at the cost of accepting
+    /// technically expired credentials. See
+    /// [`CredentialRecord::is_eligible_with_grace_period`].
+    #[must_use]
+    pub fn with_expiry_grace_period(&self, seconds: u64) -> Self {
+        Self {
+            expiry_grace_seconds: seconds,
+            ..self.clone()
+        }
+    }
+
+    /// Whether `record` is of the selected issuer schema and eligible at
+    /// `now`, including the grace period.
+    #[must_use]
+    pub fn matches(&self, record: &CredentialRecord, now: u64) -> bool {
+        self.issuer_schema_id
+            .is_none_or(|issuer_schema_id| record.issuer_schema_id == issuer_schema_id)
+            && record.is_eligible_with_grace_period(now, self.expiry_grace_seconds)
+    }
+}
+
+/// A disclosure the user consented to, as kept in the consent ledger.
+///
+/// Records carry no nullifier material, so they cannot be linked to the
+/// proofs the RP received.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct ConsentRecord {
+    /// Ledger entry identifier.
+    pub consent_id: u64,
+    /// Identifier of the proof request the user consented to.
+    pub request_id: String,
+    /// Hash of the requesting RP's identifier.
+    pub rp_id_hash: Vec<u8>,
+    /// Issuer schemas whose credentials were disclosed.
+    pub disclosed_schema_ids: Vec<u64>,
+    /// Hash of the consent text shown to the user, if any.
+    pub consent_text_hash: Option<Vec<u8>>,
+    /// When consent was recorded (seconds).
+    pub recorded_at: u64,
+}
+
+/// A proof disclosed from this device, as kept by the replay guard, see
+/// [`CredentialStore::list_disclosures`](super::CredentialStore::list_disclosures).
+///
+/// Records name neither the RP nor the nullifier.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct DisclosureRecord {
+    /// When the proof was generated (seconds).
+    pub disclosed_at: u64,
+    /// The memo the host attached when generating the proof, if requested
+    /// and present.
+    pub memo: Option<Vec<u8>>,
+}
+
+/// Outcome of deleting several credentials at once.
+#[derive(Debug, Clone, Default, PartialEq, Eq, uniffi::Record)]
+pub struct BulkDeleteReport {
+    /// Number of credentials deleted.
+    pub deleted_count: u64,
+    /// Requested credential IDs that were not found.
+    pub not_found_ids: Vec<u64>,
+}
+
+/// Outcome of importing credentials from another store, see
+/// [`CredentialStore::import_from_legacy_store`](super::CredentialStore::import_from_legacy_store).
+#[derive(Debug, Clone, Default, PartialEq, Eq, uniffi::Record)]
+pub struct MigrationReport {
+    /// Number of credentials imported (or, in a dry run, that would be).
+    pub migrated: u32,
+    /// Number of credentials left behind because they are expired,
+    /// invalidated, or superseded by a newer credential of the same schema.
+    pub skipped: u32,
+    /// One message per credential that could not be read or stored.
+    pub errors: Vec<String>,
+}
+
+/// Storage quota reported by a [`StorageQuotaProvider`](super::StorageQuotaProvider).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Record)]
+pub struct StorageEstimate {
+    /// Total bytes the origin may use.
+    pub quota: u64,
+    /// Bytes currently used by the origin.
+    pub usage: u64,
+    /// Whether storage is protected from eviction.
+    pub persistent: bool,
+}
+
+impl StorageEstimate {
+    /// Returns the bytes still available.
+    #[must_use]
+    pub const fn available(&self) -> u64 {
+        self.quota.saturating_sub(self.usage)
+    }
+}
+
+/// Quota requirements enforced when a [`StorageQuotaProvider`](super::StorageQuotaProvider)
+/// is registered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Record)]
+pub struct StorageQuotaPolicy {
+    /// Minimum available bytes required to initialize the store, and to remain
+    /// after a large write.
+    pub min_available_bytes: u64,
+    /// Writes of at least this many bytes check the quota before starting the
+    /// vault transaction.
+    pub large_write_bytes: u64,
+    /// Request persistent storage on initialization if it is not already
+    /// persistent.
+    pub request_persistence: bool,
+}
+
+impl Default for StorageQuotaPolicy {
+    fn default() -> Self {
+        Self {
+            min_available_bytes: 10 * 1024 * 1024,
+            large_write_bytes: 64 * 1024,
+            request_persistence: false,
+        }
+    }
+}
+
+/// Size bounds for the cache database.
+///
+/// When a bound is set, inserting an entry of that type evicts the least
+/// recently inserted entries beyond it. `None` leaves the type unbounded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, uniffi::Record)]
+pub struct CacheConfig {
+    /// Maximum number of cached Merkle inclusion proofs.
+    pub max_merkle_entries: Option<u64>,
+    /// Maximum number of cached session seeds.
+    pub max_session_keys: Option<u64>,
+}
+
+/// Fill statistics of the replay guard's in-memory Bloom filter, as reported
+/// by [`CredentialStore::bloom_filter_stats`](super::CredentialStore::bloom_filter_stats).
+#[derive(Debug, Clone, Copy, PartialEq, uniffi::Record)]
+pub struct BloomStats {
+    /// Number of nullifiers in the filter, estimated from the set bits.
+    /// Includes nullifiers whose replay guard entry has since expired.
+    pub estimated_entries: u64,
+    /// Fraction of set bits, from 0 to 1. The false-positive rate is about
+    /// `bit_saturation ^ 5`.
+    pub bit_saturation: f64,
+}
+
+/// When the iOS Keychain item holding the device key may be read.
+///
+/// The iOS [`DeviceKeystore`](super::DeviceKeystore) stores its key with the
+/// `kSecAttrAccessible` value from [`security_attribute`](Self::security_attribute).
+/// Opening the store needs that key, so the accessibility decides whether
+/// background work (e.g. a background app refresh that refreshes credentials
+/// or Merkle proofs) can run while the device is locked:
+///
+/// - `WhenUnlocked*`: the key is only readable while the device is unlocked.
+///   Background work started while locked fails to open the store.
+/// - `AfterFirstUnlock*`: the key stays readable after the first unlock since
+///   boot, so background work succeeds while locked, at the cost of exposing
+///   the key to anyone who obtains the device locked but unlocked since boot.
+/// - `*ThisDeviceOnly`: the item is excluded from backups and never migrates
+///   to another device. Without it, a restored backup carries the key along.
+///
+/// Changing the accessibility of an existing item requires the host to
+/// rewrite it; the key itself is unchanged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, uniffi::Enum)]
+pub enum KeychainAccessibility {
+    /// `kSecAttrAccessibleWhenUnlocked`.
+    WhenUnlocked,
+    /// `kSecAttrAccessibleWhenUnlockedThisDeviceOnly`. The most restrictive,
+    /// and the default.
+    #[default]
+    WhenUnlockedThisDeviceOnly,
+    /// `kSecAttrAccessibleAfterFirstUnlock`.
+    AfterFirstUnlock,
+    /// `kSecAttrAccessibleAfterFirstUnlockThisDeviceOnly`. The recommended
+    /// choice for apps that refresh in the background.
+    AfterFirstUnlockThisDeviceOnly,
+}
+
+#[uniffi::export]
+impl KeychainAccessibility {
+    /// Returns the value of the corresponding `kSecAttrAccessible*` constant,
+    /// to pass as `kSecAttrAccessible` in the Keychain query.
+    #[must_use]
+    pub fn security_attribute(&self) -> String {
+        match self {
+            Self::WhenUnlocked => "ak",
+            Self::WhenUnlockedThisDeviceOnly => "aku",
+            Self::AfterFirstUnlock => "ck",
+            Self::AfterFirstUnlockThisDeviceOnly => "cku",
+        }
+        .to_string()
+    }
+}
+
+/// Health of the account key envelope and the vault it unlocks, as reported
+/// by [`CredentialStore::key_envelope_health`](super::CredentialStore::key_envelope_health).
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum EnvelopeHealth {
+    /// The envelope opens and the vault passes its integrity check.
+    Healthy,
+    /// No envelope exists: storage was never initialized or was destroyed.
+    Missing,
+    /// The envelope exists but the device keystore cannot open it, e.g. after
+    /// a passcode change or a keystore migration. While a session is still
+    /// open,
+    /// [`CredentialStore::rewrap_key_envelope`](super::CredentialStore::rewrap_key_envelope)
+    /// can recover it.
+    EnvelopeUnreadable {
+        /// Why the envelope could not be opened.
+        reason: String,
+    },
+    /// The envelope opens but the vault database is corrupted or does not
+    /// open with its key. Restore from backup.
+    VaultCorrupted {
+        /// Why the vault check failed.
+        reason: String,
+    },
+}
+
+/// Emitted when a stored credential transitions from active to expired.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct CredentialExpiryEvent {
+    /// Credential identifier.
+    pub credential_id: u64,
+    /// Issuer schema identifier.
+    pub issuer_schema_id: u64,
+    /// Expiry timestamp (seconds).
+    pub expired_at: u64,
 }
 
 /// FFI-friendly replay guard result kind.
@@ -78,3 +413,68 @@ pub struct ReplayGuardResult {
     /// Stored proof package bytes.
     pub bytes: Vec<u8>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(expires_at: u64) -> CredentialRecord {
+        CredentialRecord {
+            credential_id: 1,
+            issuer_schema_id: 100,
+            genesis_issued_at: 0,
+            expires_at,
+            is_expired: false,
+            recovery_counter: 0,
+            status: CredentialStatus::Active,
+            has_associated_data: false,
+            use_count: 0,
+            last_used_at: None,
+        }
+    }
+
+    #[test]
+    fn test_grace_period_extends_eligibility() {
+        let now = 10_000;
+        let expired = record(now - 30);
+
+        assert!(!expired.is_eligible(now));
+        assert!(!expired.is_eligible_with_grace_period(now, 0));
+        assert!(expired.is_eligible_with_grace_period(now, 60));
+
+        let filter = CredentialFilter::default().with_issuer_schema_id(100);
+        assert!(!filter.matches(&expired, now));
+        assert!(filter.with_expiry_grace_period(60).matches(&expired, now));
+        assert!(!filter
+            .with_issuer_schema_id(101)
+            .with_expiry_grace_period(60)
+            .matches(&expired, now));
+    }
+
+    #[test]
+    fn test_grace_period_does_not_revive_invalidated_credentials() {
+        let invalidated = CredentialRecord {
+            status: CredentialStatus::Invalidated,
+            ..record(20_000)
+        };
+        assert!(!invalidated.is_eligible_with_grace_period(10_000, 60));
+    }
+
+    #[test]
+    fn test_keychain_accessibility_security_attribute() {
+        // values of the Security framework's `kSecAttrAccessible*` constants
+        let cases = [
+            (KeychainAccessibility::WhenUnlocked, "ak"),
+            (KeychainAccessibility::WhenUnlockedThisDeviceOnly, "aku"),
+            (KeychainAccessibility::AfterFirstUnlock, "ck"),
+            (KeychainAccessibility::AfterFirstUnlockThisDeviceOnly, "cku"),
+        ];
+        for (accessibility, expected) in cases {
+            assert_eq!(accessibility.security_attribute(), expected);
+        }
+        assert_eq!(
+            KeychainAccessibility::default(),
+            KeychainAccessibility::WhenUnlockedThisDeviceOnly
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Atomic blob store over the origin private file system.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use js_sys::Uint8Array;
+use sha2::{Digest, Sha256};
+use wasm_bindgen::JsValue;
+
+use super::{call, call_async, object, opfs_directory};
+use crate::storage::error::{StorageError, StorageResult};
+use crate::storage::traits::AtomicBlobStore;
+
+/// The two files the blobs alternate between.
+const SLOT_FILES: [&str; 2] = ["blobs.0", "blobs.1"];
+
+/// Magic bytes at the start of a slot.
+const SLOT_MAGIC: &[u8; 4] = b"WKBS";
+
+/// Version of the slot format.
+const SLOT_VERSION: u8 = 1;
+
+/// Length of the slot header: magic, version, generation.
+const SLOT_HEADER_LEN: usize = 4 + 1 + 8;
+
+static NEXT_STORE_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Sync access handles of the open stores, by store ID. JS objects cannot
+    /// leave the worker that created them, so the store only holds its ID.
+    static SLOT_HANDLES: RefCell<HashMap<u64, [JsValue; 2]>> =
+        RefCell::new(HashMap::new());
+}
+
+/// An [`AtomicBlobStore`] in an OPFS directory.
+///
+/// All blobs live in one of two slot files, each holding a complete, checksummed
+/// snapshot with a generation number. A write goes to the slot not holding the
+/// current snapshot and only takes effect once flushed, so an interrupted write
+/// leaves the previous snapshot in place. Opening picks the valid slot with the
+/// highest generation. The blobs are few and small (key envelopes), so the store
+/// keeps them in memory.
+pub struct OpfsBlobStore {
+    id: u64,
+    state: Mutex<SlotState>,
+}
+
+struct SlotState {
+    /// Index into [`SLOT_FILES`] of the current snapshot.
+    active: usize,
+    generation: u64,
+    blobs: BTreeMap<String, Vec<u8>>,
+}
+
+impl OpfsBlobStore {
+    /// Opens the blob store in the OPFS directory `directory`.
+    ///
+    /// Must be called in a dedicated worker; the store can only be used on that
+    /// worker.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::BlobStore`] if OPFS is unavailable or the slot
+    /// files cannot be opened, e.g. because another tab holds them.
+    pub async fn open(directory: &str) -> StorageResult<Self> {
+        let handles = open_handles(directory).await.map_err(|err| {
+            StorageError::BlobStore(format!(
+                "failed to open OPFS blob store {directory}: {err:?}"
+            ))
+        })?;
+        let mut state = SlotState {
+            active: 1,
+            generation: 0,
+            blobs: BTreeMap::new(),
+        };
+        for (slot, handle) in handles.iter().enumerate() {
+            let snapshot = read_slot(handle)
+                .map_err(|err| blob_error("read", &err))?
+                .and_then(|bytes| decode_snapshot(&bytes));
+            if let Some((generation, blobs)) = snapshot {
+                if generation > state.generation {
+                    state = SlotState {
+                        active: slot,
+                        generation,
+                        blobs,
+                    };
+                }
+            }
+        }
+
+        let id = NEXT_STORE_ID.fetch_add(1, Ordering::Relaxed);
+        SLOT_HANDLES.with(|slots| slots.borrow_mut().insert(id, handles));
+        Ok(Self {
+            id,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Writes the blobs with `change` applied as the next snapshot.
+    fn commit(
+        &self,
+        change: impl FnOnce(&mut BTreeMap<String, Vec<u8>>),
+    ) -> StorageResult<()> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| StorageError::BlobStore("mutex poisoned".to_string()))?;
+        let mut blobs = state.blobs.clone();
+        change(&mut blobs);
+        let generation = state.generation + 1;
+        let target = 1 - state.active;
+        let bytes = encode_snapshot(generation, &blobs);
+
+        SLOT_HANDLES.with(|slots| {
+            let slots = slots.borrow();
+            let handle = slots
+                .get(&self.id)
+                .map(|handles| &handles[target])
+                .ok_or_else(|| {
+                    StorageError::BlobStore(
+                        "OPFS blob store used outside the worker that opened it"
+                            .to_string(),
+                    )
+                })?;
+            write_slot(handle, &bytes).map_err(|err| blob_error("write", &err))
+        })?;
+        *state = SlotState {
+            active: target,
+            generation,
+            blobs,
+        };
+        Ok(())
+    }
+}
+
+impl AtomicBlobStore for OpfsBlobStore {
+    fn read(&self, path: String) -> StorageResult<Option<Vec<u8>>> {
+        let state = self
+            .state
+            .lock()
+            .map_err(|_| StorageError::BlobStore("mutex poisoned".to_string()))?;
+        Ok(state.blobs.get(&path).cloned())
+    }
+
+    fn write_atomic(&self, path: String, bytes: Vec<u8>) -> StorageResult<()> {
+        self.commit(|blobs| {
+            blobs.insert(path, bytes);
+        })
+    }
+
+    fn delete(&self, path: String) -> StorageResult<()> {
+        self.commit(|blobs| {
+            blobs.remove(&path);
+        })
+    }
+}
+
+impl Drop for OpfsBlobStore {
+    fn drop(&mut self) {
+        let handles = SLOT_HANDLES.with(|slots| slots.borrow_mut().remove(&self.id));
+        for handle in handles.iter().flatten() {
+            let _ = call(handle, "close", &[]);
+        }
+    }
+}
+
+fn blob_error(operation: &str, err: &JsValue) -> StorageError {
+    StorageError::BlobStore(format!("OPFS {operation} failed: {err:?}"))
+}
+
+/// Opens sync access handles to both slot files in `directory`.
+async fn open_handles(directory: &str) -> Result<[JsValue; 2], JsValue> {
+    let directory = opfs_directory(directory).await?;
+    let mut handles = [JsValue::UNDEFINED, JsValue::UNDEFINED];
+    for (handle, name) in handles.iter_mut().zip(SLOT_FILES) {
+        let file = call_async(
+            &directory,
+            "getFileHandle",
+            &[&name.into(), &object(&[("create", true.into())])?],
+        )
+        .await?;
+        *handle = call_async(&file, "createSyncAccessHandle", &[]).await?;
+    }
+    Ok(handles)
+}
+
+/// Reads a whole slot file; `None` if it is empty.
+fn read_slot(handle: &JsValue) -> Result<Option<Vec<u8>>, JsValue> {
+    let size = call(handle, "getSize", &[])?.as_f64().unwrap_or(0.0);
+    if size < 1.0 {
+        return Ok(None);
+    }
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "OPFS sizes are non-negative integers; slots are small"
+    )]
+    let buffer = Uint8Array::new_with_length(size as u32);
+    call(
+        handle,
+        "read",
+        &[&buffer.clone().into(), &object(&[("at", 0u32.into())])?],
+    )?;
+    Ok(Some(buffer.to_vec()))
+}
+
+/// Replaces the contents of a slot file with `bytes` and flushes it.
+fn write_slot(handle: &JsValue, bytes: &[u8]) -> Result<(), JsValue> {
+    let buffer = Uint8Array::from(bytes);
+    call(
+        handle,
+        "write",
+        &[&buffer.into(), &object(&[("at", 0u32.into())])?],
+    )?;
+    let len = u32::try_from(bytes.len())
+        .map_err(|_| JsValue::from_str("blob store snapshot too large"))?;
+    call(handle, "truncate", &[&len.into()])?;
+    call(handle, "flush", &[])?;
+    Ok(())
+}
+
+/// Encodes a snapshot: header, `(name, bytes)` entries, SHA-256 of all that.
+fn encode_snapshot(generation: u64, blobs: &BTreeMap<String, Vec<u8>>) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(SLOT_MAGIC);
+    out.push(SLOT_VERSION);
+    out.extend_from_slice(&generation.to_be_bytes());
+    for (name, bytes) in blobs {
+        for field in [name.as_bytes(), bytes.as_slice()] {
+            #[expect(
+                clippy::cast_possible_truncation,
+                reason = "blob names and contents are far below 4 GiB"
+            )]
+            let len = field.len() as u32;
+            out.extend_from_slice(&len.to_be_bytes());
+            out.extend_from_slice(field);
+        }
+    }
+    let digest = Sha256::digest(&out);
+    out.extend_from_slice(&digest);
+    out
+}
+
+/// Decodes a snapshot; `None` if it is torn, corrupt or of another version.
+fn decode_snapshot(bytes: &[u8]) -> Option<(u64, BTreeMap<String, Vec<u8>>)> {
+    let (content, digest) = bytes.split_at_checked(bytes.len().checked_sub(32)?)?;
+    if content.len() < SLOT_HEADER_LEN
+        || Sha256::digest(content).as_slice() != digest
+        || &content[..4] != SLOT_MAGIC
+        || content[4] != SLOT_VERSION
+    {
+        return None;
+    }
+    let generation = u64::from_be_bytes(content[5..SLOT_HEADER_LEN].try_into().ok()?);
+
+    let mut rest = &content[SLOT_HEADER_LEN..];
+    let mut next_field = || {
+        let (len, tail) = rest.split_at_checked(4)?;
+        let len = u32::from_be_bytes(len.try_into().ok()?) as usize;
+        let (field, tail) = tail.split_at_checked(len)?;
+        rest = tail;
+        Some(field.to_vec())
+    };
+    let mut blobs = BTreeMap::new();
+    while let Some(name) = next_field() {
+        let bytes = next_field()?;
+        blobs.insert(String::from_utf8(name).ok()?, bytes);
+    }
+    rest.is_empty().then_some((generation, blobs))
+}
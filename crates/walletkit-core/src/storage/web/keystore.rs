@@ -0,0 +1,243 @@
+//! Device keystore over `WebCrypto` and `IndexedDB`.
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use js_sys::{Array, Promise, Uint8Array};
+use wasm_bindgen::{closure::Closure, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use zeroize::Zeroizing;
+
+use super::{call, call_async, get, object};
+use crate::storage::entropy::fill_nonce;
+use crate::storage::error::{StorageError, StorageResult};
+use crate::storage::traits::DeviceKeystore;
+
+/// `IndexedDB` database holding the wrapping keys.
+const DATABASE: &str = "walletkit-keystore";
+
+/// Object store in [`DATABASE`], keyed by keystore name.
+const STORE: &str = "keys";
+
+/// Length of the AES-GCM IV used to wrap the device key.
+const IV_LEN: usize = 12;
+
+/// A [`DeviceKeystore`] for browsers.
+///
+/// The device key is a random 32-byte key, encrypted under a non-extractable
+/// `WebCrypto` AES-GCM key. Both are stored in `IndexedDB`, so script running
+/// in the origin can use the wrapping key but not read it. The device key is
+/// unwrapped once, by [`WebKeystore::open`], and kept in wasm memory to seal
+/// and open with XChaCha20-Poly1305.
+///
+/// Browsers offer nothing comparable to a secure enclave: anyone who can run
+/// script in the origin can unwrap the key.
+pub struct WebKeystore {
+    key: Zeroizing<[u8; 32]>,
+}
+
+impl WebKeystore {
+    /// Opens the keystore `name`, creating its keys on first use.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::Keystore`] if `IndexedDB` or `WebCrypto` is
+    /// unavailable or the stored key cannot be unwrapped.
+    pub async fn open(name: &str) -> StorageResult<Self> {
+        let key = load_or_create(name).await.map_err(|err| {
+            StorageError::Keystore(format!(
+                "failed to open web keystore {name}: {err:?}"
+            ))
+        })?;
+        Ok(Self { key })
+    }
+}
+
+impl DeviceKeystore for WebKeystore {
+    fn seal(
+        &self,
+        associated_data: Vec<u8>,
+        plaintext: Vec<u8>,
+    ) -> StorageResult<Vec<u8>> {
+        let mut nonce = [0u8; 24];
+        fill_nonce(&mut nonce)?;
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(&*self.key));
+        let ciphertext = cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: &associated_data,
+                },
+            )
+            .map_err(|err| StorageError::Crypto(err.to_string()))?;
+        let mut out = Vec::with_capacity(nonce.len() + ciphertext.len());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    fn open_sealed(
+        &self,
+        associated_data: Vec<u8>,
+        ciphertext: Vec<u8>,
+    ) -> StorageResult<Vec<u8>> {
+        if ciphertext.len() < 24 {
+            return Err(StorageError::InvalidEnvelope(
+                "keystore ciphertext too short".to_string(),
+            ));
+        }
+        let (nonce, payload) = ciphertext.split_at(24);
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(&*self.key));
+        cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: payload,
+                    aad: &associated_data,
+                },
+            )
+            .map_err(|err| StorageError::Crypto(err.to_string()))
+    }
+}
+
+/// Unwraps the device key stored under `name`, or creates and stores one.
+async fn load_or_create(name: &str) -> Result<Zeroizing<[u8; 32]>, JsValue> {
+    let db = open_database().await?;
+    if let Some(record) = read_record(&db, name).await? {
+        return unwrap_key(&record).await;
+    }
+
+    let subtle = get(&get(&js_sys::global(), "crypto")?, "subtle")?;
+    let wrapping_key = call_async(
+        &subtle,
+        "generateKey",
+        &[
+            &object(&[("name", "AES-GCM".into()), ("length", 256u32.into())])?,
+            &false.into(),
+            &Array::of2(&"encrypt".into(), &"decrypt".into()).into(),
+        ],
+    )
+    .await?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    let mut iv = [0u8; IV_LEN];
+    getrandom::fill(&mut *key)
+        .and_then(|()| getrandom::fill(&mut iv))
+        .map_err(|err| JsValue::from_str(&err.to_string()))?;
+
+    let plaintext = Uint8Array::from(&key[..]);
+    let wrapped = call_async(
+        &subtle,
+        "encrypt",
+        &[&aes_gcm(&iv)?, &wrapping_key, &plaintext.clone().into()],
+    )
+    .await;
+    plaintext.fill(0, 0, plaintext.length());
+    let record = object(&[
+        ("key", wrapping_key),
+        ("iv", Uint8Array::from(&iv[..]).into()),
+        ("wrapped", wrapped?),
+    ])?;
+
+    // another tab may have created the key in the meantime; theirs wins
+    if add_record(&db, name, &record).await.is_err() {
+        let record = read_record(&db, name)
+            .await?
+            .ok_or_else(|| JsValue::from_str("keystore record vanished"))?;
+        return unwrap_key(&record).await;
+    }
+    Ok(key)
+}
+
+/// Decrypts the device key in `record`.
+async fn unwrap_key(record: &JsValue) -> Result<Zeroizing<[u8; 32]>, JsValue> {
+    let subtle = get(&get(&js_sys::global(), "crypto")?, "subtle")?;
+    let iv = Uint8Array::new(&get(record, "iv")?).to_vec();
+    let plaintext = call_async(
+        &subtle,
+        "decrypt",
+        &[
+            &aes_gcm(&iv)?,
+            &get(record, "key")?,
+            &get(record, "wrapped")?,
+        ],
+    )
+    .await?;
+    let plaintext = Uint8Array::new(&plaintext);
+    let mut key = Zeroizing::new([0u8; 32]);
+    let copied = if plaintext.length() as usize == key.len() {
+        plaintext.copy_to(&mut key[..]);
+        true
+    } else {
+        false
+    };
+    plaintext.fill(0, 0, plaintext.length());
+    if copied {
+        Ok(key)
+    } else {
+        Err(JsValue::from_str("stored device key has the wrong length"))
+    }
+}
+
+/// AES-GCM algorithm parameters with `iv`.
+fn aes_gcm(iv: &[u8]) -> Result<JsValue, JsValue> {
+    object(&[
+        ("name", "AES-GCM".into()),
+        ("iv", Uint8Array::from(iv).into()),
+    ])
+}
+
+/// Opens [`DATABASE`], creating [`STORE`] on first use.
+async fn open_database() -> Result<JsValue, JsValue> {
+    let factory = get(&js_sys::global(), "indexedDB")?;
+    let open = call(&factory, "open", &[&DATABASE.into(), &1u32.into()])?;
+    let upgrading = open.clone();
+    let on_upgrade = Closure::once_into_js(move |_event: JsValue| {
+        if let Ok(db) = get(&upgrading, "result") {
+            let _ = call(&db, "createObjectStore", &[&STORE.into()]);
+        }
+    });
+    js_sys::Reflect::set(&open, &"onupgradeneeded".into(), &on_upgrade)?;
+    request(&open).await
+}
+
+/// Reads the record `name`, if present.
+async fn read_record(db: &JsValue, name: &str) -> Result<Option<JsValue>, JsValue> {
+    let store = object_store(db, "readonly")?;
+    let record = request(&call(&store, "get", &[&name.into()])?).await?;
+    Ok((!record.is_undefined()).then_some(record))
+}
+
+/// Adds the record `name`. Fails if it already exists.
+async fn add_record(db: &JsValue, name: &str, record: &JsValue) -> Result<(), JsValue> {
+    let store = object_store(db, "readwrite")?;
+    request(&call(&store, "add", &[record, &name.into()])?)
+        .await
+        .map(|_| ())
+}
+
+/// Opens [`STORE`] in a new transaction.
+fn object_store(db: &JsValue, mode: &str) -> Result<JsValue, JsValue> {
+    let transaction = call(db, "transaction", &[&STORE.into(), &mode.into()])?;
+    call(&transaction, "objectStore", &[&STORE.into()])
+}
+
+/// Waits for an `IDBRequest` and returns its result.
+async fn request(request: &JsValue) -> Result<JsValue, JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        let succeeded = request.clone();
+        let on_success = Closure::once_into_js(move |_event: JsValue| {
+            let result = get(&succeeded, "result").unwrap_or(JsValue::UNDEFINED);
+            let _ = resolve.call1(&JsValue::UNDEFINED, &result);
+        });
+        let failed = request.clone();
+        let on_error = Closure::once_into_js(move |_event: JsValue| {
+            let error = get(&failed, "error").unwrap_or(JsValue::UNDEFINED);
+            let _ = reject.call1(&JsValue::UNDEFINED, &error);
+        });
+        let _ = js_sys::Reflect::set(request, &"onsuccess".into(), &on_success);
+        let _ = js_sys::Reflect::set(request, &"onerror".into(), &on_error);
+    });
+    JsFuture::from(promise).await
+}
@@ -0,0 +1,80 @@
+//! Cross-tab exclusion with the Web Locks API.
+
+use js_sys::{Function, Promise};
+use wasm_bindgen::{closure::Closure, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use super::{call, get};
+use crate::storage::error::{StorageError, StorageResult};
+
+/// Exclusive locks shared by all tabs and workers of the origin.
+///
+/// The file lock that guards the databases on native targets is a no-op in
+/// browsers. Hosts that may open the same storage from several tabs acquire
+/// a lock before opening a [`CredentialStore`](crate::storage::CredentialStore)
+/// and hold the [`WebLockGuard`] for as long as they use it. The browser
+/// releases the lock if the tab or worker goes away.
+pub struct WebLockManager {
+    namespace: String,
+}
+
+/// Holds a lock from [`WebLockManager::acquire`] until dropped.
+pub struct WebLockGuard {
+    release: Function,
+}
+
+impl WebLockManager {
+    /// Creates a lock manager whose lock names are prefixed with `namespace`,
+    /// e.g. the namespace passed to
+    /// [`WebStorageProvider::open`](super::WebStorageProvider::open).
+    #[must_use]
+    pub fn new(namespace: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+        }
+    }
+
+    /// Waits until the lock `name` is free and takes it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::Lock`] if the Web Locks API is unavailable.
+    pub async fn acquire(&self, name: &str) -> StorageResult<WebLockGuard> {
+        let lock_name = format!("walletkit:{}:{name}", self.namespace);
+        request_lock(&lock_name).await.map_err(|err| {
+            StorageError::Lock(format!(
+                "failed to acquire web lock {lock_name}: {err:?}"
+            ))
+        })
+    }
+}
+
+/// Requests `name` and resolves once it is granted. The lock is held until
+/// the promise returned to the lock callback settles, which the guard does
+/// on drop.
+async fn request_lock(name: &str) -> Result<WebLockGuard, JsValue> {
+    let locks = get(&get(&js_sys::global(), "navigator")?, "locks")?;
+
+    let mut release = None;
+    let held = Promise::new(&mut |resolve, _reject| release = Some(resolve));
+    let mut grant = None;
+    let granted = Promise::new(&mut |resolve, _reject| grant = Some(resolve));
+    let (Some(release), Some(grant)) = (release, grant) else {
+        return Err(JsValue::from_str("promise executor did not run"));
+    };
+
+    let on_grant = Closure::once_into_js(move |_lock: JsValue| -> JsValue {
+        let _ = grant.call0(&JsValue::UNDEFINED);
+        held.into()
+    });
+    // the returned promise settles when the lock is released
+    call(&locks, "request", &[&name.into(), &on_grant])?;
+    JsFuture::from(granted).await?;
+    Ok(WebLockGuard { release })
+}
+
+impl Drop for WebLockGuard {
+    fn drop(&mut self) {
+        let _ = self.release.call0(&JsValue::UNDEFINED);
+    }
+}
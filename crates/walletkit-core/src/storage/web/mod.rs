@@ -0,0 +1,121 @@
+//! Browser implementations of the storage platform interfaces, for wasm32
+//! builds with the `platform-web` feature.
+//!
+//! - [`WebKeystore`] — the device key, wrapped by a non-extractable
+//!   `WebCrypto` AES-GCM key kept in `IndexedDB`.
+//! - [`OpfsBlobStore`] — small blobs such as `account_keys.bin` in the origin
+//!   private file system (OPFS).
+//! - [`OpfsVaultStore`] — the vault and cache databases in OPFS.
+//! - [`WebLockManager`] — Web Locks, to keep other tabs and workers off the
+//!   databases.
+//!
+//! [`WebStorageProvider`] bundles the first three into a
+//! [`StorageProvider`].
+//!
+//! The storage traits are synchronous, the browser APIs are not. Each
+//! component therefore does its asynchronous work when it is opened: the
+//! keystore unwraps the device key into wasm memory, and the OPFS stores
+//! open synchronous access handles, which browsers only offer in dedicated
+//! workers. Storage must be opened and used in a dedicated worker, and a
+//! component must be used on the worker that opened it.
+
+mod blob_store;
+mod keystore;
+mod locks;
+mod vault_store;
+
+use std::sync::Arc;
+
+use js_sys::{Array, Function, Object, Promise, Reflect};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+pub use blob_store::OpfsBlobStore;
+pub use keystore::WebKeystore;
+pub use locks::{WebLockGuard, WebLockManager};
+pub use vault_store::OpfsVaultStore;
+
+use super::error::StorageResult;
+use super::paths::StoragePaths;
+use super::traits::{AtomicBlobStore, DeviceKeystore, StorageProvider};
+
+/// A [`StorageProvider`] over [`WebKeystore`], [`OpfsBlobStore`] and
+/// [`OpfsVaultStore`].
+pub struct WebStorageProvider {
+    keystore: Arc<WebKeystore>,
+    blob_store: Arc<OpfsBlobStore>,
+    vault_store: OpfsVaultStore,
+}
+
+impl WebStorageProvider {
+    /// Opens the keystore, blob store and vault store for `namespace`, e.g.
+    /// one per app on a shared origin.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the components cannot be opened.
+    pub async fn open(namespace: &str) -> StorageResult<Self> {
+        Ok(Self {
+            keystore: Arc::new(WebKeystore::open(namespace).await?),
+            blob_store: Arc::new(OpfsBlobStore::open(namespace).await?),
+            vault_store: OpfsVaultStore::open(namespace).await?,
+        })
+    }
+}
+
+impl StorageProvider for WebStorageProvider {
+    fn keystore(&self) -> Arc<dyn DeviceKeystore> {
+        self.keystore.clone()
+    }
+
+    fn blob_store(&self) -> Arc<dyn AtomicBlobStore> {
+        self.blob_store.clone()
+    }
+
+    fn paths(&self) -> Arc<StoragePaths> {
+        self.vault_store.paths()
+    }
+}
+
+/// Reads `target[key]`.
+fn get(target: &JsValue, key: &str) -> Result<JsValue, JsValue> {
+    Reflect::get(target, &JsValue::from_str(key))
+}
+
+/// Calls `target[method](...args)`.
+fn call(target: &JsValue, method: &str, args: &[&JsValue]) -> Result<JsValue, JsValue> {
+    let function: Function = get(target, method)?.dyn_into()?;
+    let args = args.iter().map(|arg| (*arg).clone()).collect::<Array>();
+    Reflect::apply(&function, target, &args)
+}
+
+/// Calls `target[method](...args)` and awaits the returned promise.
+async fn call_async(
+    target: &JsValue,
+    method: &str,
+    args: &[&JsValue],
+) -> Result<JsValue, JsValue> {
+    let promise: Promise = call(target, method, args)?.dyn_into()?;
+    JsFuture::from(promise).await
+}
+
+/// Builds a plain object from `entries`.
+fn object(entries: &[(&str, JsValue)]) -> Result<JsValue, JsValue> {
+    let object = Object::new();
+    for (key, value) in entries {
+        Reflect::set(&object, &JsValue::from_str(key), value)?;
+    }
+    Ok(object.into())
+}
+
+/// Opens (or creates) the OPFS directory `name`.
+async fn opfs_directory(name: &str) -> Result<JsValue, JsValue> {
+    let storage = get(&get(&js_sys::global(), "navigator")?, "storage")?;
+    let root = call_async(&storage, "getDirectory", &[]).await?;
+    call_async(
+        &root,
+        "getDirectoryHandle",
+        &[&name.into(), &object(&[("create", true.into())])?],
+    )
+    .await
+}
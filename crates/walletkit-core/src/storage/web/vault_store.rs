@@ -0,0 +1,82 @@
+//! Vault and cache databases in the origin private file system.
+
+use std::sync::{Arc, Mutex};
+
+use sqlite_wasm_vfs::sahpool::{install, OpfsSAHPoolCfg};
+
+use crate::storage::error::{StorageError, StorageResult};
+use crate::storage::paths::StoragePaths;
+
+/// Name of the OPFS VFS that holds the databases.
+const VFS_NAME: &str = "walletkit-opfs";
+
+/// OPFS directory the VFS was installed for; the VFS can be installed once.
+static INSTALLED_DIRECTORY: Mutex<Option<String>> = Mutex::new(None);
+
+/// Puts the vault and cache databases in OPFS.
+///
+/// Opening installs the `opfs-sahpool` `SQLite` VFS of `sqlite-wasm-vfs` over
+/// the OPFS directory `directory` and makes `sqlite3mc` encrypt databases on
+/// it, so the databases are encrypted exactly as on native targets. The
+/// databases are then found at [`OpfsVaultStore::paths`].
+///
+/// The VFS keeps its files open through synchronous access handles, so only
+/// one tab or worker can hold the databases at a time; see
+/// [`WebLockManager`](super::WebLockManager) to wait for them instead of
+/// failing.
+pub struct OpfsVaultStore {
+    paths: Arc<StoragePaths>,
+}
+
+impl OpfsVaultStore {
+    /// Installs the OPFS VFS over the OPFS directory `directory`.
+    ///
+    /// Must be called in a dedicated worker. The VFS stays installed for the
+    /// life of the worker, so opening again with the same directory is cheap,
+    /// and opening with another directory fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::VaultDb`] if OPFS is unavailable, its files are
+    /// held by another tab, or the VFS is installed for another directory.
+    pub async fn open(directory: &str) -> StorageResult<Self> {
+        let installed = INSTALLED_DIRECTORY
+            .lock()
+            .map_err(|_| StorageError::VaultDb("mutex poisoned".to_string()))?
+            .clone();
+        match installed {
+            Some(installed) if installed == directory => {}
+            Some(installed) => {
+                return Err(StorageError::VaultDb(format!(
+                    "OPFS VFS already installed for {installed}"
+                )));
+            }
+            None => {
+                let config = OpfsSAHPoolCfg {
+                    vfs_name: VFS_NAME.to_string(),
+                    directory: directory.to_string(),
+                    ..OpfsSAHPoolCfg::default()
+                };
+                install(&config, false).await.map_err(|err| {
+                    StorageError::VaultDb(format!(
+                        "failed to install OPFS VFS in {directory}: {err:?}"
+                    ))
+                })?;
+                walletkit_db::cipher::use_encrypted_vfs(VFS_NAME)
+                    .map_err(|err| StorageError::VaultDb(err.to_string()))?;
+                *INSTALLED_DIRECTORY.lock().map_err(|_| {
+                    StorageError::VaultDb("mutex poisoned".to_string())
+                })? = Some(directory.to_string());
+            }
+        }
+        Ok(Self {
+            paths: Arc::new(StoragePaths::new("/")),
+        })
+    }
+
+    /// Returns the storage paths of the databases inside the VFS.
+    #[must_use]
+    pub fn paths(&self) -> Arc<StoragePaths> {
+        self.paths.clone()
+    }
+}
@@ -0,0 +1,630 @@
+//! Certificate pinning for the HTTPS clients `WalletKit` builds itself.
+//!
+//! After [`set_tls_pins`], every new client verifies server certificates as
+//! usual against the web PKI roots and, for hosts matching a [`TlsPin`],
+//! additionally requires a validated chain whose leaf, intermediate or root
+//! carries a pinned public key. Certificates the server sends that are not
+//! part of that chain do not count. Clients created before the call are not
+//! pinned, so set the pins at startup.
+//!
+//! # Gateway and indexer: blocked upstream
+//!
+//! Pinning the gateway and indexer through the authenticator `Config` is
+//! **not** implemented. `world-id-core` builds those clients itself with
+//! `reqwest::Client::new()` and offers no way to pass a client or TLS
+//! configuration, so they are not pinned, whatever the pins set here. Until
+//! `world-id-core` accepts a preconfigured client, hosts that must pin them
+//! have to route that traffic through their own pinned HTTP stack.
+
+use std::error::Error as StdError;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::WebPkiSupportedAlgorithms;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, OtherError, RootCertStore,
+    SignatureScheme,
+};
+use sha2::{Digest, Sha256};
+use webpki::{EndEntityCert, KeyUsage, VerifiedPath};
+
+use crate::error::WalletKitError;
+use crate::Environment;
+
+/// A pinned public key for the hosts matching `host_pattern`.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct TlsPin {
+    /// An exact host name or IP address, or `*.` followed by a domain to
+    /// match its direct subdomains, e.g. `*.worldcoin.org`.
+    pub host_pattern: String,
+    /// SHA-256 of the DER-encoded `SubjectPublicKeyInfo` of the leaf, an
+    /// intermediate or the root certificate, 32 bytes.
+    pub spki_sha256: Vec<u8>,
+}
+
+/// The pins for [`set_tls_pins`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct TlsPinConfig {
+    /// Pins by host. A host matching several pins accepts any of their keys.
+    pub pins: Vec<TlsPin>,
+    /// The environment the clients talk to.
+    pub environment: Environment,
+    /// Skips the pin checks, keeping the normal certificate validation, for
+    /// local mock servers. Only allowed in [`Environment::Staging`].
+    #[uniffi(default = false)]
+    pub ignore_pins_in_staging: bool,
+}
+
+/// TLS configuration of new clients, `None` until pins are set.
+static PINNED_TLS: RwLock<Option<Arc<ClientConfig>>> = RwLock::new(None);
+
+/// Pins the certificates of the hosts in `config` for every HTTPS client
+/// `WalletKit` creates from now on, replacing the pins of a previous call.
+///
+/// This does **not** pin the gateway and indexer clients of an
+/// [`Authenticator`](crate::Authenticator), which `world-id-core` builds
+/// without a way to configure TLS, see the [module documentation](self).
+///
+/// # Errors
+/// Returns [`WalletKitError::InvalidInput`] if a pin is malformed, if
+/// `ignore_pins_in_staging` is set for [`Environment::Production`], or if the
+/// shared client cannot be built with the pins, which then stay unchanged.
+#[uniffi::export]
+pub fn set_tls_pins(config: TlsPinConfig) -> Result<(), WalletKitError> {
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    install(client_config(config, roots)?)
+}
+
+/// Makes `tls` the configuration of new clients and rebuilds the shared
+/// client with it, keeping the previous pins if that fails.
+fn install(tls: ClientConfig) -> Result<(), WalletKitError> {
+    let slot = || PINNED_TLS.write().unwrap_or_else(PoisonError::into_inner);
+    crate::http_client::rebuild_shared_client(
+        || slot().replace(Arc::new(tls)),
+        |previous| *slot() = previous,
+    )
+    .map_err(|reason| WalletKitError::InvalidInput {
+        attribute: "tls_pins".to_string(),
+        reason,
+    })
+}
+
+/// Starts an HTTP client enforcing the pins of [`set_tls_pins`], if any.
+pub(crate) fn client_builder() -> reqwest::ClientBuilder {
+    // the slot is only ever replaced whole, so a panic cannot corrupt it
+    let tls = PINNED_TLS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    let builder = reqwest::Client::builder();
+    match tls {
+        Some(tls) => builder.use_preconfigured_tls((*tls).clone()),
+        None => builder,
+    }
+}
+
+/// Validates `config` and builds a TLS configuration trusting `roots` and
+/// enforcing its pins.
+fn client_config(
+    config: TlsPinConfig,
+    roots: RootCertStore,
+) -> Result<ClientConfig, WalletKitError> {
+    let invalid = |reason: &str| WalletKitError::InvalidInput {
+        attribute: "tls_pins".to_string(),
+        reason: reason.to_string(),
+    };
+    if let Some(pin) = config
+        .pins
+        .iter()
+        .find(|pin| pin.host_pattern.is_empty() || pin.spki_sha256.len() != 32)
+    {
+        return Err(invalid(&format!(
+            "pin for `{}` needs a host pattern and a 32-byte SHA-256",
+            pin.host_pattern
+        )));
+    }
+    let pins = match (config.environment, config.ignore_pins_in_staging) {
+        (Environment::Staging, true) => Vec::new(),
+        (Environment::Production, true) => {
+            return Err(invalid("pins cannot be ignored in production"));
+        }
+        (_, false) => config.pins,
+    };
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let roots = Arc::new(roots);
+    let webpki = WebPkiServerVerifier::builder_with_provider(
+        Arc::clone(&roots),
+        Arc::clone(&provider),
+    )
+    .build()
+    .map_err(|err| invalid(&err.to_string()))?;
+    let verifier = PinningVerifier {
+        webpki,
+        roots,
+        algorithms: provider.signature_verification_algorithms,
+        pins,
+    };
+    Ok(ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|err| invalid(&err.to_string()))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth())
+}
+
+/// Whether `host` matches `pattern`, see [`TlsPin::host_pattern`].
+fn host_matches(pattern: &str, host: &str) -> bool {
+    pattern.strip_prefix("*.").map_or_else(
+        || pattern.eq_ignore_ascii_case(host),
+        |domain| {
+            host.split_once('.').is_some_and(|(label, rest)| {
+                !label.is_empty() && rest.eq_ignore_ascii_case(domain)
+            })
+        },
+    )
+}
+
+/// SHA-256 of the DER-encoded `SubjectPublicKeyInfo` of every certificate
+/// in `path`, from the leaf to the trust anchor.
+fn path_spki_sha256(path: &VerifiedPath<'_>) -> Vec<[u8; 32]> {
+    let certs = std::iter::once(path.end_entity().subject_public_key_info())
+        .chain(
+            path.intermediate_certificates()
+                .map(webpki::Cert::subject_public_key_info),
+        )
+        .map(|spki| Sha256::digest(spki.as_ref()).into());
+    // trust anchors keep the contents of the SPKI `SEQUENCE` only
+    let anchor = der_sequence(path.anchor().subject_public_key_info.as_ref());
+    certs.chain([Sha256::digest(anchor).into()]).collect()
+}
+
+/// DER encoding of a `SEQUENCE` with `contents`.
+fn der_sequence(contents: &[u8]) -> Vec<u8> {
+    let len = contents.len().to_be_bytes();
+    let significant = len.iter().position(|&byte| byte != 0).unwrap_or(len.len());
+    let mut der = vec![0x30];
+    if let Ok(short @ ..0x80) = u8::try_from(contents.len()) {
+        der.push(short);
+    } else {
+        // at most 8 length bytes, so the count fits the low bits
+        #[allow(clippy::cast_possible_truncation)]
+        der.push(0x80 | (len.len() - significant) as u8);
+        der.extend_from_slice(&len[significant..]);
+    }
+    der.extend_from_slice(contents);
+    der
+}
+
+/// A presented certificate chain matched none of the pins of its host.
+#[derive(Debug, thiserror::Error)]
+#[error("certificate chain of {host} matches none of its TLS pins")]
+struct PinMismatch {
+    host: String,
+}
+
+/// Returns the host whose pins `error` failed on, if it is a pin mismatch.
+///
+/// rustls errors reach reqwest wrapped in (possibly nested)
+/// [`std::io::Error`]s, whose `source` skips the wrapped error, so those are
+/// unwrapped explicitly.
+pub(crate) fn pin_mismatch_host(error: &(dyn StdError + 'static)) -> Option<String> {
+    let mut next = Some(error);
+    while let Some(error) = next {
+        let mut wrapped = error;
+        while let Some(inner) = wrapped
+            .downcast_ref::<std::io::Error>()
+            .and_then(std::io::Error::get_ref)
+        {
+            wrapped = inner;
+        }
+        if let Some(rustls::Error::InvalidCertificate(CertificateError::Other(other))) =
+            wrapped.downcast_ref::<rustls::Error>()
+        {
+            if let Some(mismatch) = other.0.downcast_ref::<PinMismatch>() {
+                return Some(mismatch.host.clone());
+            }
+        }
+        next = error.source();
+    }
+    None
+}
+
+/// Verifies certificates with the web PKI, then checks the pins.
+#[derive(Debug)]
+struct PinningVerifier {
+    webpki: Arc<WebPkiServerVerifier>,
+    /// The roots `webpki` trusts, to rebuild the chain for the pin check.
+    roots: Arc<RootCertStore>,
+    algorithms: WebPkiSupportedAlgorithms,
+    pins: Vec<TlsPin>,
+}
+
+impl PinningVerifier {
+    /// Whether a chain from `end_entity` over some of `intermediates` to a
+    /// root validates and carries one of `pins`. Presented certificates that
+    /// no such chain goes through are never matched, so a server cannot pass
+    /// the check by appending a certificate with a pinned key.
+    fn has_pinned_path(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+        pins: &[&TlsPin],
+    ) -> Result<bool, rustls::Error> {
+        let cert = EndEntityCert::try_from(end_entity).map_err(|_| {
+            rustls::Error::InvalidCertificate(CertificateError::BadEncoding)
+        })?;
+        let pinned = |path: &VerifiedPath<'_>| {
+            let keys = path_spki_sha256(path);
+            if pins
+                .iter()
+                .any(|pin| keys.iter().any(|key| pin.spki_sha256 == key))
+            {
+                Ok(())
+            } else {
+                // rejects this candidate chain, path building tries the others
+                Err(webpki::Error::UnknownIssuer)
+            }
+        };
+        Ok(cert
+            .verify_for_usage(
+                self.algorithms.all,
+                &self.roots.roots,
+                intermediates,
+                now,
+                KeyUsage::server_auth(),
+                None,
+                Some(&pinned),
+            )
+            .is_ok())
+    }
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.webpki.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        let host = server_name.to_str();
+        let pins = self
+            .pins
+            .iter()
+            .filter(|pin| host_matches(&pin.host_pattern, &host))
+            .collect::<Vec<_>>();
+        if pins.is_empty()
+            || self.has_pinned_path(end_entity, intermediates, now, &pins)?
+        {
+            return Ok(verified);
+        }
+        Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+            OtherError(Arc::new(PinMismatch {
+                host: host.into_owned(),
+            })),
+        )))
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.webpki.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.webpki.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.webpki.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustls::pki_types::PrivateKeyDer;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsAcceptor;
+
+    use super::*;
+    use crate::http_request::Request;
+
+    const CA: &[u8] = include_bytes!("../tests/fixtures/tls/ca.der");
+    const SERVER: &[u8] = include_bytes!("../tests/fixtures/tls/server.der");
+    const SERVER_KEY: &[u8] = include_bytes!("../tests/fixtures/tls/server.key.der");
+    const DECOY: &[u8] = include_bytes!("../tests/fixtures/tls/decoy.der");
+    /// Printed by `tests/fixtures/tls/generate.sh`.
+    const SERVER_PIN: &str =
+        "a16a36f760b2820886a5d09ebdf396234b21f411a0d0351521346aaa278b6023";
+    const CA_PIN: &str =
+        "aa861bd230780ed8161a1712f1f15ba93774cda205eaf5385a242f7d52c01a92";
+    const DECOY_PIN: &str =
+        "c04cd70bccef4e6932d3bb5ee6595a36e2ebf6759596813e28b66c2e15fd0f1c";
+
+    /// Serves `200 ok` over TLS with the fixture chain on a local port.
+    async fn tls_server() -> String {
+        tls_server_with_chain(vec![SERVER, CA]).await
+    }
+
+    /// Serves `200 ok` over TLS on a local port, presenting `chain`, which
+    /// starts with the fixture leaf.
+    async fn tls_server_with_chain(chain: Vec<&[u8]>) -> String {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                chain
+                    .into_iter()
+                    .map(|cert| CertificateDer::from(cert.to_vec()))
+                    .collect(),
+                PrivateKeyDer::try_from(SERVER_KEY.to_vec()).unwrap(),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("https://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    // rejected handshakes are expected
+                    let Ok(mut stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                        let Ok(read @ 1..) = stream.read(&mut buf).await else {
+                            return;
+                        };
+                        request.extend_from_slice(&buf[..read]);
+                    }
+                    let _ = stream
+                        .write_all(
+                            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+                        )
+                        .await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        url
+    }
+
+    fn pin(host_pattern: &str, spki_sha256: &str) -> TlsPin {
+        TlsPin {
+            host_pattern: host_pattern.to_string(),
+            spki_sha256: hex::decode(spki_sha256).unwrap(),
+        }
+    }
+
+    fn test_roots() -> RootCertStore {
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from(CA.to_vec())).unwrap();
+        roots
+    }
+
+    async fn fetch(
+        url: &str,
+        config: TlsPinConfig,
+        roots: RootCertStore,
+    ) -> Result<String, WalletKitError> {
+        let tls = client_config(config, roots)?;
+        let client = reqwest::Client::builder()
+            .use_preconfigured_tls(tls)
+            .build()
+            .expect("build client");
+        let request = Request::with_client(client, "test".to_string());
+        let response = request.handle(request.get(url)).await?;
+        Ok(response.text().await?)
+    }
+
+    fn staging(pins: Vec<TlsPin>) -> TlsPinConfig {
+        TlsPinConfig {
+            pins,
+            environment: Environment::Staging,
+            ignore_pins_in_staging: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_accepts_pinned_certificates() {
+        let url = tls_server().await;
+
+        for pins in [
+            vec![pin("127.0.0.1", SERVER_PIN)],
+            vec![pin("127.0.0.1", CA_PIN)],
+            vec![
+                pin("127.0.0.1", &"00".repeat(32)),
+                pin("127.0.0.1", SERVER_PIN),
+            ],
+            // pins of other hosts do not apply
+            vec![pin("*.worldcoin.org", &"00".repeat(32))],
+        ] {
+            let body = fetch(&url, staging(pins.clone()), test_roots())
+                .await
+                .unwrap_or_else(|err| panic!("{pins:?}: {err}"));
+            assert_eq!(body, "ok");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unpinned_certificates() {
+        let url = tls_server().await;
+
+        let error = fetch(
+            &url,
+            staging(vec![pin("127.0.0.1", &"00".repeat(32))]),
+            test_roots(),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(error, WalletKitError::TlsPinMismatch { ref host } if host == "127.0.0.1"),
+            "unexpected error: {error:?}"
+        );
+
+        // the pin does not replace the normal validation
+        let error = fetch(
+            &url,
+            staging(vec![pin("127.0.0.1", SERVER_PIN)]),
+            RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(error, WalletKitError::NetworkError { .. }),
+            "unexpected error: {error:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_pinned_keys_outside_the_validated_chain() {
+        // a valid but unpinned leaf with an appended certificate carrying the
+        // pinned key, which does not sign anything in the chain
+        let url = tls_server_with_chain(vec![SERVER, CA, DECOY]).await;
+
+        let error = fetch(
+            &url,
+            staging(vec![pin("127.0.0.1", DECOY_PIN)]),
+            test_roots(),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(error, WalletKitError::TlsPinMismatch { ref host } if host == "127.0.0.1"),
+            "unexpected error: {error:?}"
+        );
+
+        // the extra certificate does not get in the way of the real chain
+        let body = fetch(
+            &url,
+            staging(vec![pin("127.0.0.1", SERVER_PIN)]),
+            test_roots(),
+        )
+        .await
+        .expect("pinned leaf");
+        assert_eq!(body, "ok");
+    }
+
+    #[tokio::test]
+    async fn test_rejected_client_config_keeps_the_pins() {
+        let url = tls_server().await;
+        let mut roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        roots.add(CertificateDer::from(CA.to_vec())).unwrap();
+        let pinned = staging(vec![pin("127.0.0.1", &"00".repeat(32))]);
+        install(client_config(pinned, roots).unwrap()).unwrap();
+
+        let rejected =
+            crate::http_client::set_http_client_config(crate::HttpClientConfig {
+                proxy_url: Some("not a url".to_string()),
+                ..crate::HttpClientConfig::default()
+            });
+        assert!(matches!(rejected, Err(WalletKitError::InvalidInput { .. })));
+
+        let request = Request::new("test".to_string());
+        let error = request.handle(request.get(&url)).await.unwrap_err();
+        assert!(
+            matches!(error, WalletKitError::TlsPinMismatch { ref host } if host == "127.0.0.1"),
+            "unexpected error: {error:?}"
+        );
+
+        set_tls_pins(staging(Vec::new())).unwrap();
+    }
+
+    #[test]
+    fn test_der_sequence_lengths() {
+        assert_eq!(der_sequence(&[1, 2]), [0x30, 2, 1, 2]);
+        let long = der_sequence(&[0; 0x80]);
+        assert_eq!(long[..3], [0x30, 0x81, 0x80]);
+        assert_eq!(long.len(), 3 + 0x80);
+        let longer = der_sequence(&[0; 0x123]);
+        assert_eq!(longer[..4], [0x30, 0x82, 0x01, 0x23]);
+    }
+
+    #[tokio::test]
+    async fn test_ignores_pins_only_in_staging() {
+        let url = tls_server().await;
+        let pins = vec![pin("127.0.0.1", &"00".repeat(32))];
+
+        let body = fetch(
+            &url,
+            TlsPinConfig {
+                ignore_pins_in_staging: true,
+                ..staging(pins.clone())
+            },
+            test_roots(),
+        )
+        .await
+        .expect("pins ignored in staging");
+        assert_eq!(body, "ok");
+
+        let error = set_tls_pins(TlsPinConfig {
+            pins,
+            environment: Environment::Production,
+            ignore_pins_in_staging: true,
+        })
+        .unwrap_err();
+        assert!(matches!(error, WalletKitError::InvalidInput { .. }));
+    }
+
+    #[test]
+    fn test_rejects_malformed_pins() {
+        assert!(matches!(
+            set_tls_pins(staging(vec![pin("127.0.0.1", "00")])),
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            set_tls_pins(staging(vec![pin("", SERVER_PIN)])),
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+    }
+
+    #[test]
+    fn test_host_patterns() {
+        assert!(host_matches(
+            "gateway.worldcoin.org",
+            "Gateway.Worldcoin.org"
+        ));
+        assert!(!host_matches(
+            "gateway.worldcoin.org",
+            "indexer.worldcoin.org"
+        ));
+        assert!(host_matches("*.worldcoin.org", "indexer.worldcoin.org"));
+        assert!(!host_matches("*.worldcoin.org", "worldcoin.org"));
+        assert!(!host_matches("*.worldcoin.org", "a.b.worldcoin.org"));
+        assert!(!host_matches("*.worldcoin.org", ".worldcoin.org"));
+    }
+}
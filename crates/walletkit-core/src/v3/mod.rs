@@ -14,4 +14,4 @@ pub use credential_type::CredentialType;
 // Private modules
 ////////////////////////////////////////////////////////////////////////////////
 
-mod merkle_tree;
+pub(crate) mod merkle_tree;
@@ -1,5 +1,9 @@
+use std::sync::Arc;
+
 use crate::error::WalletKitError;
+use crate::AppId;
 
+use alloy_core::primitives::{keccak256, Address, U256};
 use alloy_core::sol_types::SolValue;
 use ruint_uniffi::Uint256;
 #[cfg(feature = "semaphore")]
@@ -42,20 +46,23 @@ impl ProofContext {
     /// # Arguments
     ///
     /// * `app_id` - The ID of the application requesting proofs.  This can be obtained from the [Developer Portal](https://developer.world.org).
+    ///   Surrounding whitespace is ignored, see [`AppId::parse`].
     /// * `action` - Optional. Custom incognito action being requested.
     /// * `signal` - Optional. The signal is included in the ZKP and is committed to in the proof. When verifying the proof, the
     ///   same signal must be provided to ensure the proof is valid. The signal can be used to prevent replay attacks, MITM or other cases.
     ///   More details available in the [docs](https://docs.world.org/world-id/further-reading/zero-knowledge-proofs).
     /// * `credential_type` - The type of credential being requested.
     ///
-    #[must_use]
+    /// # Errors
+    ///
+    /// - Returns [`WalletKitError::InvalidInput`] if `app_id` is not a well-formed Developer Portal app ID.
     #[uniffi::constructor]
     pub fn new(
         app_id: &str,
         action: Option<String>,
         signal: Option<String>,
         credential_type: CredentialType,
-    ) -> Self {
+    ) -> Result<Self, WalletKitError> {
         Self::new_from_bytes(
             app_id,
             action.map(std::string::String::into_bytes),
@@ -64,6 +71,33 @@ impl ProofContext {
         )
     }
 
+    /// Initializes a `ProofContext` like [`ProofContext::new`], without validating `app_id`.
+    ///
+    /// Only for tests and legacy identifiers which predate the Developer Portal format. The `app_id` is hashed exactly as
+    /// provided, surrounding whitespace included.
+    #[must_use]
+    #[uniffi::constructor]
+    #[expect(
+        clippy::needless_pass_by_value,
+        reason = "UniFFI constructors take owned arguments"
+    )]
+    pub fn new_unchecked(
+        app_id: &str,
+        action: Option<String>,
+        signal: Option<String>,
+        credential_type: CredentialType,
+    ) -> Self {
+        let signal_hash =
+            Uint256::from(hash_to_field(signal.unwrap_or_default().as_bytes()));
+
+        Self::new_from_signal_hash_unchecked(
+            &AppId::new_unchecked(app_id),
+            action.as_deref().map(str::as_bytes),
+            credential_type,
+            &signal_hash,
+        )
+    }
+
     /// Initializes a `Proof::ProofContext` where the `action` is provided as raw bytes. This is useful for advanced cases
     /// where the `action` is an already ABI encoded value for on-chain usage.
     /// See _walletkit-core/tests/solidity.rs_ for an example.
@@ -74,24 +108,30 @@ impl ProofContext {
     ///
     /// See `ProofContext::new` for reference. The `action` and `signal` need to be provided as raw bytes.
     ///
-    #[must_use]
+    /// # Errors
+    ///
+    /// - Returns [`WalletKitError::InvalidInput`] if `app_id` is not a well-formed Developer Portal app ID.
     #[uniffi::constructor]
-    #[allow(clippy::needless_pass_by_value)]
+    #[expect(
+        clippy::needless_pass_by_value,
+        reason = "UniFFI constructors take owned arguments"
+    )]
     pub fn new_from_bytes(
         app_id: &str,
         action: Option<Vec<u8>>,
         signal: Option<Vec<u8>>,
         credential_type: CredentialType,
-    ) -> Self {
+    ) -> Result<Self, WalletKitError> {
+        let app_id = AppId::parse(app_id)?;
         let signal_hash =
             Uint256::from(hash_to_field(signal.unwrap_or_default().as_slice()));
 
-        Self::new_from_signal_hash_unchecked(
-            app_id,
-            action,
+        Ok(Self::new_from_signal_hash_unchecked(
+            &app_id,
+            action.as_deref(),
             credential_type,
             &signal_hash,
-        )
+        ))
     }
 
     /// Initializes a `ProofContext` from an already hashed signal.
@@ -112,27 +152,62 @@ impl ProofContext {
     ///
     /// # Errors
     ///
+    /// - Returns [`WalletKitError::InvalidInput`] if `app_id` is not a well-formed Developer Portal app ID.
     /// - Returns an error if the signal is not a valid number in the field.
     #[uniffi::constructor]
+    #[expect(
+        clippy::needless_pass_by_value,
+        reason = "UniFFI constructors take owned arguments"
+    )]
     pub fn new_from_signal_hash(
         app_id: &str,
         action: Option<Vec<u8>>,
         credential_type: CredentialType,
         signal_hash: &Uint256,
     ) -> Result<Self, WalletKitError> {
+        let app_id = AppId::parse(app_id)?;
         if signal_hash.0 >= MODULUS {
             return Err(WalletKitError::InvalidNumber);
         }
 
         Ok(Self::new_from_signal_hash_unchecked(
-            app_id,
-            action,
+            &app_id,
+            action.as_deref(),
             credential_type,
             signal_hash,
         ))
     }
 
+    /// Returns a copy of this context whose signal binds the proof to a
+    /// transaction, see [`transaction_signal_hash`] for the encoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `to` - The `0x`-prefixed address the transaction is sent to.
+    /// * `value_wei` - The value of the transaction in wei, as a decimal string.
+    /// * `calldata` - The raw transaction calldata (not hex-encoded).
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`WalletKitError::InvalidInput`] if `to` is not an address or
+    ///   `value_wei` is not a decimal `uint256`.
+    pub fn with_transaction_signal(
+        &self,
+        to: &str,
+        value_wei: &str,
+        calldata: &[u8],
+    ) -> Result<Arc<Self>, WalletKitError> {
+        Ok(Arc::new(Self {
+            signal_hash: transaction_signal_hash(to, value_wei, calldata)?,
+            ..self.clone()
+        }))
+    }
+
     /// Get the raw external nullifier for this context.
+    ///
+    /// For contexts built from an `app_id` and `action` this is exactly
+    /// [`compute_external_nullifier`], which RP backends can use to index their
+    /// nullifier databases.
     #[must_use]
     pub const fn get_external_nullifier(&self) -> Uint256 {
         self.external_nullifier
@@ -153,22 +228,26 @@ impl ProofContext {
 
 // This impl block is not exported to foreign bindings.
 impl ProofContext {
+    /// Checks that `app_id` is a well-formed Developer Portal app ID, see
+    /// [`AppId::parse`]. The constructors other than
+    /// [`new_unchecked`](Self::new_unchecked) run the same check.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WalletKitError::InvalidInput`] for the `app_id` attribute with a
+    /// reason describing what is invalid.
+    pub fn validate_app_id(app_id: &str) -> Result<(), WalletKitError> {
+        AppId::parse(app_id).map(|_| ())
+    }
+
     fn new_from_signal_hash_unchecked(
-        app_id: &str,
-        action: Option<Vec<u8>>,
+        app_id: &AppId,
+        action: Option<&[u8]>,
         credential_type: CredentialType,
         signal_hash: &Uint256,
     ) -> Self {
-        let mut pre_image = hash_to_field(app_id.as_bytes()).abi_encode_packed();
-
-        if let Some(action) = action {
-            pre_image.extend_from_slice(&action);
-        }
-
-        let external_nullifier = hash_to_field(&pre_image).into();
-
         Self {
-            external_nullifier,
+            external_nullifier: external_nullifier_from_bytes(app_id.as_str(), action),
             credential_type,
             signal_hash: *signal_hash,
             require_mined_proof: false,
@@ -252,10 +331,106 @@ impl ProofContext {
     }
 }
 
+/// Computes the external nullifier for an `app_id` and optional `action`.
+///
+/// This is the function [`ProofContext::new`] uses, exposed so RP backends can
+/// pre-compute the value that proofs will commit to:
+///
+/// ```text
+/// external_nullifier = hash_to_field(abi.encodePacked(hash_to_field(app_id), action))
+/// hash_to_field(x)   = keccak256(x) >> 8
+/// ```
+///
+/// - The action is encoded as its raw UTF-8 bytes. No Unicode normalization is
+///   applied, so visually identical actions in different normal forms yield
+///   different nullifiers.
+/// - An empty action appends nothing (no padding), so `None` and `Some("")`
+///   produce the same external nullifier.
+/// - There is no length limit on the action.
+#[must_use]
+#[uniffi::export]
+#[expect(
+    clippy::needless_pass_by_value,
+    reason = "foreign bindings pass optional strings by value"
+)]
+pub fn compute_external_nullifier(app_id: &str, action: Option<String>) -> Uint256 {
+    external_nullifier_from_bytes(app_id, action.as_deref().map(str::as_bytes))
+}
+
+/// Computes the external nullifier from an `app_id` and raw action bytes. See
+/// [`compute_external_nullifier`].
+fn external_nullifier_from_bytes(app_id: &str, action: Option<&[u8]>) -> Uint256 {
+    let mut pre_image = hash_to_field(app_id.as_bytes()).abi_encode_packed();
+    if let Some(action) = action {
+        pre_image.extend_from_slice(action);
+    }
+    hash_to_field(&pre_image).into()
+}
+
+/// Computes the signal hash that binds a proof to a transaction.
+///
+/// This is the value the on-chain verifier recomputes from the transaction it
+/// executes and passes as `signalHash` to `verifyProof`:
+///
+/// ```text
+/// signal_hash = abi.encodePacked(to, value, keccak256(calldata)).hashToField()
+/// hashToField(x) = uint256(keccak256(x)) >> 8
+/// ```
+///
+/// `to` is packed as its 20 address bytes, `value` as a 32-byte big-endian
+/// `uint256` and the calldata hash as `bytes32`, i.e. an 84-byte pre-image.
+/// Passing the calldata hash (or a hex string of it) as a plain signal does
+/// not produce this value.
+///
+/// # Errors
+///
+/// - Returns [`WalletKitError::InvalidInput`] if `to` is not an address or
+///   `value_wei` is not a decimal `uint256`.
+#[uniffi::export]
+pub fn transaction_signal_hash(
+    to: &str,
+    value_wei: &str,
+    calldata: &[u8],
+) -> Result<Uint256, WalletKitError> {
+    let to: Address = to.parse().map_err(|_| WalletKitError::InvalidInput {
+        attribute: "to".to_string(),
+        reason: "must be a 20-byte hex address".to_string(),
+    })?;
+    // `from_str_radix` tolerates input such as an empty string, so check the
+    // digits explicitly rather than guessing at what the caller meant
+    let value = Some(value_wei)
+        .filter(|v| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|v| U256::from_str_radix(v, 10).ok())
+        .ok_or_else(|| WalletKitError::InvalidInput {
+            attribute: "value_wei".to_string(),
+            reason: "must be a decimal uint256".to_string(),
+        })?;
+    let pre_image = (to, value, keccak256(calldata)).abi_encode_packed();
+    Ok(hash_to_field(&pre_image).into())
+}
+
+/// Checks that `signal_hash` (as committed to by a proof) binds the proof to
+/// the transaction `to`, `value_wei`, `calldata`. Intended for RP backends
+/// that relay or inspect the transaction before verifying the proof.
+///
+/// # Errors
+///
+/// - Returns [`WalletKitError::InvalidInput`] if the transaction fields are
+///   malformed, see [`transaction_signal_hash`].
+#[uniffi::export]
+pub fn verify_transaction_signal(
+    signal_hash: &Uint256,
+    to: &str,
+    value_wei: &str,
+    calldata: &[u8],
+) -> Result<bool, WalletKitError> {
+    Ok(transaction_signal_hash(to, value_wei, calldata)? == *signal_hash)
+}
+
 /// Represents the complete output of a World ID Proof (i.e. a credential persentation). This output
 /// can be serialized to JSON and can be verified easily with the Developer Portal or Sign up Sequencer.
 ///
-/// For on-chain verification, the `proof` (which is packed) should generally be deserialized into `uint256[8]`.
+/// For on-chain verification, use [`ProofOutput::to_onchain_calldata`] to get the proof as `uint256[8]`.
 ///
 /// More information on: [On-Chain Verification](https://docs.world.org/world-id/id/on-chain)
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, uniffi::Object)]
@@ -281,14 +456,51 @@ pub struct ProofOutput {
 impl ProofOutput {
     /// Converts the entire proof output to a JSON string with standard attribute names.
     ///
+    /// Same output as [`Self::to_portal_json`].
+    ///
     /// # Errors
     /// Will error if serialization fails.
     pub fn to_json(&self) -> Result<String, WalletKitError> {
+        self.to_portal_json()
+    }
+
+    /// Converts the proof output to the JSON accepted by the Developer Portal's
+    /// `/api/v2/verify` endpoint (and the Sign up Sequencer): `merkle_root`,
+    /// `nullifier_hash`, `credential_type` and `proof` as a `0x`-prefixed hex
+    /// string of the ABI-encoded proof (see [`Self::to_abi_encoded`]).
+    ///
+    /// # Errors
+    /// Will error if serialization fails.
+    pub fn to_portal_json(&self) -> Result<String, WalletKitError> {
         serde_json::to_string(self).map_err(|e| WalletKitError::SerializationError {
             error: format!("Failed to serialize proof: {e}"),
         })
     }
 
+    /// Returns the proof as the eight `uint256` values expected by the `proof`
+    /// argument of `verifyProof` on the `WorldIDRouter` / `WorldIDIdentityManager`
+    /// contracts (and Semaphore's `ISemaphoreVerifier`).
+    ///
+    /// The order is `[a.x, a.y, b.x[0], b.x[1], b.y[0], b.y[1], c.x, c.y]`, the
+    /// same order used by the hex string in [`Self::to_portal_json`].
+    #[must_use]
+    pub fn to_onchain_calldata(&self) -> Vec<Uint256> {
+        self.raw_proof
+            .flatten()
+            .into_iter()
+            .map(Into::into)
+            .collect()
+    }
+
+    /// Returns the proof ABI-encoded as a static `uint256[8]` (256 bytes), i.e.
+    /// [`Self::to_onchain_calldata`] as 32-byte big-endian words. This is the
+    /// encoding to splice into `verifyProof` calldata when building the
+    /// transaction by hand.
+    #[must_use]
+    pub fn to_abi_encoded(&self) -> Vec<u8> {
+        self.proof.0.to_vec()
+    }
+
     /// Exposes the nullifier hash to foreign code. Struct fields are not directly exposed to foreign code.
     #[must_use]
     pub const fn get_nullifier_hash(&self) -> Uint256 {
@@ -377,7 +589,8 @@ mod external_nullifier_tests {
             None,
             None,
             CredentialType::Orb,
-        );
+        )
+        .unwrap();
         assert_eq!(
             context.external_nullifier.to_padded_hex_string(),
             "0x0073e4a6b670e81dc619b1f8703aa7491dc5aaadf75409aba0ac2414014c0227"
@@ -389,7 +602,8 @@ mod external_nullifier_tests {
             Some(String::new()),
             None,
             CredentialType::Orb,
-        );
+        )
+        .unwrap();
         assert_eq!(
             context.external_nullifier.to_padded_hex_string(),
             "0x0073e4a6b670e81dc619b1f8703aa7491dc5aaadf75409aba0ac2414014c0227"
@@ -405,7 +619,8 @@ mod external_nullifier_tests {
             Some("test-action-qli8g".to_string()),
             None,
             CredentialType::Orb,
-        );
+        )
+        .unwrap();
         assert_eq!(
             context.external_nullifier.to_padded_hex_string(),
             "0x00d8b157e767dc59faa533120ed0ce34fc51a71937292ea8baed6ee6f4fda866"
@@ -419,7 +634,8 @@ mod external_nullifier_tests {
             Some("test-123123".to_string()),
             None,
             CredentialType::Orb,
-        );
+        )
+        .unwrap();
         assert_eq!(
             context.external_nullifier.0,
             uint!(
@@ -443,7 +659,8 @@ mod external_nullifier_tests {
             Some(custom_action),
             None,
             CredentialType::Orb,
-        );
+        )
+        .unwrap();
         assert_eq!(
             context.external_nullifier.to_padded_hex_string(),
             // expected output obtained from Solidity
@@ -466,7 +683,8 @@ mod external_nullifier_tests {
             Some(custom_action),
             None,
             CredentialType::Orb,
-        );
+        )
+        .unwrap();
         assert_eq!(
             context.external_nullifier.to_padded_hex_string(),
             // expected output obtained from Solidity
@@ -523,6 +741,125 @@ mod external_nullifier_tests {
         );
     }
 
+    #[test]
+    fn test_compute_external_nullifier_matches_context() {
+        let app_id = "app_staging_45068dca85829d2fd90e2dd6f0bff997";
+        let action = "test-action-qli8g";
+        let context = ProofContext::new(
+            app_id,
+            Some(action.to_string()),
+            None,
+            CredentialType::Orb,
+        )
+        .unwrap();
+        let external_nullifier =
+            compute_external_nullifier(app_id, Some(action.to_string()));
+        assert_eq!(external_nullifier, context.get_external_nullifier());
+        assert_eq!(
+            external_nullifier.to_padded_hex_string(),
+            "0x00d8b157e767dc59faa533120ed0ce34fc51a71937292ea8baed6ee6f4fda866"
+        );
+    }
+
+    #[test]
+    fn test_compute_external_nullifier_empty_action() {
+        let app_id = "app_369183bd38f1641b6964ab51d7a20434";
+        let expected =
+            "0x0073e4a6b670e81dc619b1f8703aa7491dc5aaadf75409aba0ac2414014c0227";
+        assert_eq!(
+            compute_external_nullifier(app_id, None).to_padded_hex_string(),
+            expected
+        );
+        assert_eq!(
+            compute_external_nullifier(app_id, Some(String::new()))
+                .to_padded_hex_string(),
+            expected
+        );
+    }
+
+    /// Values published in the World ID docs and already pinned by the tests
+    /// above for [`ProofContext::new`].
+    /// Reference: <https://github.com/worldcoin/world-id-docs/blob/main/src/pages/world-id/try.tsx>
+    #[test]
+    fn test_compute_external_nullifier_reference_vectors() {
+        let vectors = [
+            (
+                "app_staging_45068dca85829d2fd90e2dd6f0bff997",
+                Some("test-action-qli8g"),
+                "0x00d8b157e767dc59faa533120ed0ce34fc51a71937292ea8baed6ee6f4fda866",
+            ),
+            (
+                "app_369183bd38f1641b6964ab51d7a20434",
+                None,
+                "0x0073e4a6b670e81dc619b1f8703aa7491dc5aaadf75409aba0ac2414014c0227",
+            ),
+            (
+                "app_10eb12bd96d8f7202892ff25f094c803",
+                Some("test-123123"),
+                "0x0065ebab05692ff2e0816cc4c3b83216c33eaa4d906c6495add6323fe0e2dc89",
+            ),
+        ];
+        for (app_id, action, expected) in vectors {
+            assert_eq!(
+                compute_external_nullifier(app_id, action.map(str::to_string))
+                    .to_padded_hex_string(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_compute_external_nullifier_long_action() {
+        let app_id = "app_10eb12bd96d8f7202892ff25f094c803";
+        let action = "action-".repeat(1000);
+
+        // the whole action is hashed, not a truncated prefix
+        let mut other = action.clone();
+        other.push('x');
+        assert_ne!(
+            compute_external_nullifier(app_id, Some(action.clone())),
+            compute_external_nullifier(app_id, Some(other))
+        );
+        assert_eq!(
+            compute_external_nullifier(app_id, Some(action.clone())),
+            ProofContext::new_from_bytes(
+                app_id,
+                Some(action.into_bytes()),
+                None,
+                CredentialType::Orb,
+            )
+            .unwrap()
+            .get_external_nullifier()
+        );
+    }
+
+    #[test]
+    fn test_compute_external_nullifier_non_ascii_action() {
+        let app_id = "app_10eb12bd96d8f7202892ff25f094c803";
+        // precomposed "é" (NFC)
+        let nfc_action = "caf\u{e9}-\u{1f30d}-\u{884c}\u{52d5}";
+        // "e" followed by a combining acute accent (NFD)
+        let nfd_action = "cafe\u{301}-\u{1f30d}-\u{884c}\u{52d5}";
+
+        // the action is hashed as its raw UTF-8 bytes
+        let nfc = compute_external_nullifier(app_id, Some(nfc_action.to_string()));
+        assert_eq!(
+            nfc,
+            ProofContext::new_from_bytes(
+                app_id,
+                Some(nfc_action.as_bytes().to_vec()),
+                None,
+                CredentialType::Orb,
+            )
+            .unwrap()
+            .get_external_nullifier()
+        );
+
+        // and is not normalized
+        let nfd = compute_external_nullifier(app_id, Some(nfd_action.to_string()));
+        assert_ne!(nfc, nfd);
+    }
+
     #[cfg(feature = "legacy-nullifiers")]
     #[test]
     fn test_ensure_raw_external_nullifier_is_in_the_field() {
@@ -539,6 +876,85 @@ mod external_nullifier_tests {
     }
 }
 
+#[cfg(test)]
+mod app_id_tests {
+    use super::*;
+
+    fn reason(app_id: &str) -> String {
+        match ProofContext::validate_app_id(app_id) {
+            Err(WalletKitError::InvalidInput { attribute, reason }) => {
+                assert_eq!(attribute, "app_id");
+                reason
+            }
+            other => panic!("expected InvalidInput, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_validate_app_id_valid() {
+        ProofContext::validate_app_id("app_369183bd38f1641b6964ab51d7a20434").unwrap();
+        ProofContext::validate_app_id("app_staging_45068dca85829d2fd90e2dd6f0bff997")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_validate_app_id_wrong_prefix() {
+        assert!(reason("369183bd38f1641b6964ab51d7a20434").contains("must start with"));
+        assert!(
+            reason("ap_369183bd38f1641b6964ab51d7a20434").contains("must start with")
+        );
+    }
+
+    #[test]
+    fn test_validate_app_id_wrong_length() {
+        assert!(reason("app_369183bd38f1641b").contains("found 16"));
+        assert!(reason("app_369183bd38f1641b6964ab51d7a204341").contains("found 33"));
+        assert!(reason("app_").contains("found 0"));
+    }
+
+    #[test]
+    fn test_validate_app_id_non_hex() {
+        assert!(reason("app_369183bd38f1641b6964ab51d7a2043z")
+            .contains("only contain lowercase hexadecimal"));
+        // the Developer Portal hashes app IDs as issued, in lowercase
+        assert!(reason("app_CE4CB73CB75FC3B73B71FFB4DE178410")
+            .contains("only contain lowercase hexadecimal"));
+    }
+
+    #[test]
+    fn test_constructors_validate_app_id() {
+        let app_id = "app_369183bd38f1641b6964ab51d7a20434 ";
+        assert!(matches!(
+            ProofContext::new("app_369183bd 38f1641b6964ab51d7a20434", None, None, CredentialType::Orb),
+            Err(WalletKitError::InvalidInput { attribute, .. }) if attribute == "app_id"
+        ));
+        assert!(
+            ProofContext::new_from_bytes("app_1", None, None, CredentialType::Orb)
+                .is_err()
+        );
+        assert!(ProofContext::new_from_signal_hash(
+            "app_1",
+            None,
+            CredentialType::Orb,
+            &Uint256::from(1u64)
+        )
+        .is_err());
+
+        // surrounding whitespace is not hashed into the external nullifier
+        assert_eq!(
+            ProofContext::new(app_id, None, None, CredentialType::Orb)
+                .unwrap()
+                .external_nullifier,
+            compute_external_nullifier(app_id.trim(), None)
+        );
+        assert_ne!(
+            ProofContext::new_unchecked(app_id, None, None, CredentialType::Orb)
+                .external_nullifier,
+            compute_external_nullifier(app_id.trim(), None)
+        );
+    }
+}
+
 #[cfg(test)]
 mod signal_tests {
     use ruint::aliases::U256;
@@ -550,7 +966,7 @@ mod signal_tests {
         let invalid_signals = [MODULUS, MODULUS + U256::from(1)];
         for signal_hash in invalid_signals {
             let context = ProofContext::new_from_signal_hash(
-                "my_app_id",
+                "app_369183bd38f1641b6964ab51d7a20434",
                 None,
                 CredentialType::Device,
                 &signal_hash.into(),
@@ -566,7 +982,8 @@ mod signal_tests {
             Some("test-action".to_string()),
             None,
             CredentialType::Orb,
-        );
+        )
+        .unwrap();
 
         let external_nullifier = context.get_external_nullifier();
         assert_eq!(external_nullifier, context.external_nullifier);
@@ -584,7 +1001,8 @@ mod signal_tests {
             None,
             Some(signal.clone()),
             CredentialType::Device,
-        );
+        )
+        .unwrap();
 
         let signal_hash = context.get_signal_hash();
         assert_eq!(signal_hash, context.signal_hash);
@@ -595,13 +1013,172 @@ mod signal_tests {
 
     #[test]
     fn test_get_credential_type() {
-        let orb_context = ProofContext::new("app_123", None, None, CredentialType::Orb);
+        let orb_context =
+            ProofContext::new_unchecked("app_123", None, None, CredentialType::Orb);
         assert_eq!(orb_context.get_credential_type(), CredentialType::Orb);
 
         let device_context =
-            ProofContext::new("app_456", None, None, CredentialType::Device);
+            ProofContext::new_unchecked("app_456", None, None, CredentialType::Device);
         assert_eq!(device_context.get_credential_type(), CredentialType::Device);
     }
+
+    /// Expected hashes are `uint256(keccak256(abi.encodePacked(to, value,
+    /// keccak256(calldata)))) >> 8`, computed independently of this crate.
+    #[test]
+    fn test_transaction_signal_hash_vectors() {
+        let transfer_calldata = hex::decode(
+            "a9059cbb000000000000000000000000d8da6bf26964af9d7eed9e03e53415d37aa96045\
+             00000000000000000000000000000000000000000000000000000000000f4240",
+        )
+        .unwrap();
+        let vectors: [(&str, &str, Vec<u8>, &str); 3] = [
+            (
+                "0x0000000000000000000000000000000000000000",
+                "0",
+                vec![],
+                "0x00f9002faae4cf06dcb8237d37b7c227636666831278bacdb8d7f52933b8698a",
+            ),
+            (
+                // checksum casing is not enforced
+                "0x541f3cc5772a64f2ba0a47e83236CcE2F089b188",
+                "1000000000000000000",
+                transfer_calldata,
+                "0x001e8e096273d3bc58a1559fcaa676c11621111f6a5c20abdc49a3eed887ecd7",
+            ),
+            (
+                "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045",
+                "115792089237316195423570985008687907853269984665640564039457584007913129639935",
+                (0u8..100).collect(),
+                "0x002dbd0be727672c1c2e1d372e4694d174ed405544ce7cac3f87d0e8fe3d1f43",
+            ),
+        ];
+        for (to, value_wei, calldata, expected) in vectors {
+            let signal_hash =
+                transaction_signal_hash(to, value_wei, &calldata).unwrap();
+            assert_eq!(signal_hash.to_padded_hex_string(), expected);
+            assert!(
+                verify_transaction_signal(&signal_hash, to, value_wei, &calldata)
+                    .unwrap()
+            );
+
+            let context =
+                ProofContext::new_unchecked("app_123", None, None, CredentialType::Orb)
+                    .with_transaction_signal(to, value_wei, &calldata)
+                    .unwrap();
+            assert_eq!(context.get_signal_hash(), signal_hash);
+        }
+    }
+
+    #[test]
+    fn test_transaction_signal_rejects_other_encodings() {
+        let to = "0x541f3cc5772a64f2ba0a47e83236CcE2F089b188";
+        let calldata = [0xde, 0xad, 0xbe, 0xef];
+        let signal_hash = transaction_signal_hash(to, "1", &calldata).unwrap();
+
+        // the calldata hash used directly as a signal does not verify
+        let naive: Uint256 = hash_to_field(keccak256(calldata).as_slice()).into();
+        assert!(!verify_transaction_signal(&naive, to, "1", &calldata).unwrap());
+        assert!(!verify_transaction_signal(&signal_hash, to, "2", &calldata).unwrap());
+
+        assert!(matches!(
+            transaction_signal_hash("0x1234", "1", &calldata),
+            Err(WalletKitError::InvalidInput { attribute, .. }) if attribute == "to"
+        ));
+        let too_large = format!("{}0", U256::MAX);
+        for value_wei in ["0x1", "-1", "", "1.5", " 1", too_large.as_str()] {
+            assert!(matches!(
+                transaction_signal_hash(to, value_wei, &calldata),
+                Err(WalletKitError::InvalidInput { attribute, .. }) if attribute == "value_wei"
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod encoding_tests {
+    use ruint::aliases::U256;
+    use serde_json::Value;
+
+    use super::*;
+
+    /// Proof whose field elements are distinct and span the full word width.
+    fn fixture_output() -> ProofOutput {
+        let flat = [
+            U256::from(1),
+            U256::from(2),
+            U256::from(3),
+            U256::from(4),
+            U256::from(5),
+            U256::from(6),
+            U256::from(7),
+            MODULUS - U256::from(1),
+        ];
+        let raw_proof = Proof::from_flat(flat);
+        ProofOutput {
+            merkle_root: U256::from(0x1234).into(),
+            nullifier_hash: U256::from(0x5678).into(),
+            raw_proof,
+            proof: PackedProof::from(raw_proof),
+            credential_type: CredentialType::Device,
+        }
+    }
+
+    #[test]
+    fn test_onchain_calldata_known_vector() {
+        let output = fixture_output();
+        let calldata = output
+            .to_onchain_calldata()
+            .iter()
+            .map(Uint256::to_padded_hex_string)
+            .collect::<Vec<_>>();
+        assert_eq!(calldata.len(), 8);
+        assert_eq!(
+            calldata[0],
+            "0x0000000000000000000000000000000000000000000000000000000000000001"
+        );
+        assert_eq!(
+            calldata[2],
+            "0x0000000000000000000000000000000000000000000000000000000000000003"
+        );
+        assert_eq!(
+            calldata[7],
+            "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000000"
+        );
+
+        let encoded = output.to_abi_encoded();
+        assert_eq!(encoded.len(), 256);
+        assert_eq!(
+            hex::encode(&encoded[..64]),
+            "0000000000000000000000000000000000000000000000000000000000000001\
+             0000000000000000000000000000000000000000000000000000000000000002"
+        );
+        assert_eq!(
+            hex::encode(&encoded[224..]),
+            "30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000000"
+        );
+    }
+
+    #[test]
+    fn test_abi_encoding_matches_portal_json() {
+        let output = fixture_output();
+        let encoded = output.to_abi_encoded();
+
+        let decoded = <[U256; 8]>::abi_decode(&encoded).expect("decode uint256[8]");
+        let calldata = output
+            .to_onchain_calldata()
+            .into_iter()
+            .map(Into::<U256>::into)
+            .collect::<Vec<_>>();
+        assert_eq!(decoded.to_vec(), calldata);
+
+        let json: Value =
+            serde_json::from_str(&output.to_portal_json().unwrap()).unwrap();
+        assert_eq!(
+            json["proof"].as_str().unwrap(),
+            format!("0x{}", hex::encode(&encoded))
+        );
+        assert_eq!(output.to_json().unwrap(), output.to_portal_json().unwrap());
+    }
 }
 
 #[cfg(test)]
@@ -632,7 +1209,8 @@ mod proof_tests {
             Some("test-action-89tcf".to_string()),
             None,
             CredentialType::Device,
-        );
+        )
+        .unwrap();
 
         let mut secret = b"not_a_real_secret".to_vec();
 
@@ -674,6 +1252,20 @@ mod proof_tests {
             30
         )
         .unwrap());
+
+        // RP backends can verify against the independently computed value
+        assert!(verify_proof(
+            *zkp.merkle_root,
+            *zkp.nullifier_hash,
+            hash_to_field(&[]),
+            *compute_external_nullifier(
+                "app_staging_45068dca85829d2fd90e2dd6f0bff997",
+                Some("test-action-89tcf".to_string())
+            ),
+            &zkp.raw_proof,
+            30
+        )
+        .unwrap());
     }
 
     #[test]
@@ -683,7 +1275,8 @@ mod proof_tests {
             Some("test-action-89tcf".to_string()),
             None,
             CredentialType::Device,
-        );
+        )
+        .unwrap();
 
         let mut secret = b"not_a_real_secret".to_vec();
         let identity = semaphore_rs::identity::Identity::from_secret(
@@ -80,7 +80,7 @@ impl WorldId {
     ///
     /// # tokio_test::block_on(async {
     ///     let world_id = WorldId::new(b"not_a_real_secret", &Environment::Staging);
-    ///     let context = ProofContext::new("app_ce4cb73cb75fc3b73b71ffb4de178410", Some("my_action".to_string()), None, CredentialType::Device);
+    ///     let context = ProofContext::new("app_ce4cb73cb75fc3b73b71ffb4de178410", Some("my_action".to_string()), None, CredentialType::Device).unwrap();
     ///     let proof = world_id.generate_proof(&context).await.unwrap();
     ///     assert_eq!(proof.nullifier_hash.to_padded_hex_string(), "0x302e253346d2b41a0fd71562ffc6e5ddcbab6d8ea3dd6d68e6a695b5639b1c37")
     /// # })
@@ -164,7 +164,8 @@ mod tests {
     #[tokio::test]
     async fn test_proof_generation() {
         let world_id = WorldId::new(b"not_a_real_secret", &Environment::Staging);
-        let context = ProofContext::new("app_id", None, None, CredentialType::Orb);
+        let context =
+            ProofContext::new_unchecked("app_id", None, None, CredentialType::Orb);
         let nullifier_hash = world_id.generate_nullifier_hash(&context);
         assert_eq!(
             nullifier_hash.to_padded_hex_string(),
@@ -206,7 +207,7 @@ mod tests {
     #[tokio::test]
     async fn test_proof_generation_with_device_credential_and_string_signal() {
         let world_id = WorldId::new(b"not_a_real_secret", &Environment::Staging);
-        let context = ProofContext::new(
+        let context = ProofContext::new_unchecked(
             "app_id",
             None,
             Some("test-signal".to_string()),
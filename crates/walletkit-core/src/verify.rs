@@ -0,0 +1,458 @@
+//! Verification of World ID proofs on the RP side, e.g. by the Rust backend
+//! of a mini-app.
+//!
+//! Every proof of a response is checked with the environment's
+//! `WorldIDVerifier` contract through an `eth_call`. The contract checks the
+//! Groth16 proof against the request's public inputs and the Merkle root
+//! encoded in the proof against the World ID registry. [`RootSource`] lets
+//! the RP additionally pin the root it expects.
+
+use alloy_core::primitives::U256;
+use alloy_core::sol_types::SolCall;
+use ruint_uniffi::Uint256;
+use world_id_core::requests::{ProofType, RequestItem, ResponseItem};
+
+use crate::defaults::world_id_verifier_address;
+use crate::error::WalletKitError;
+use crate::requests::{ProofRequest, ProofResponse};
+use crate::rpc::{eth_call, is_valid_root, CallOutcome};
+use crate::Environment;
+
+alloy_core::sol! {
+    function verify(
+        uint256 nullifier,
+        uint256 action,
+        uint64 rpId,
+        uint256 nonce,
+        uint256 signalHash,
+        uint64 expiresAtMin,
+        uint64 issuerSchemaId,
+        uint256 credentialGenesisIssuedAtMin,
+        uint256[5] calldata zeroKnowledgeProof
+    ) external view;
+
+    function verifySession(
+        uint64 rpId,
+        uint256 nonce,
+        uint256 signalHash,
+        uint64 expiresAtMin,
+        uint64 issuerSchemaId,
+        uint256 credentialGenesisIssuedAtMin,
+        uint256 sessionId,
+        uint256[2] calldata sessionNullifier,
+        uint256[5] calldata zeroKnowledgeProof
+    ) external view;
+}
+
+/// Which Merkle roots of the World ID registry [`verify_proof`] accepts.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum RootSource {
+    /// Any root the registry currently accepts.
+    Registry,
+    /// Only `root`, e.g. one the RP fetched from the registry itself. The
+    /// registry must still accept it.
+    Root {
+        /// The expected root.
+        root: Uint256,
+    },
+}
+
+/// Result of [`verify_proof`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct ProofVerification {
+    /// Whether the response is valid: it matches the request, its root is
+    /// accepted and all its proofs verify.
+    pub valid: bool,
+    /// The Merkle root the proofs were generated against. `None` if the
+    /// response has no proofs.
+    pub root: Option<Uint256>,
+    /// Whether the root is accepted, see [`RootSource`].
+    pub root_accepted: bool,
+    /// The verification of each proof, in the order of the response.
+    pub items: Vec<ProofItemVerification>,
+    /// Why the response as a whole is invalid, e.g. because it carries an
+    /// error or does not match the request. No proof is checked then.
+    pub error: Option<String>,
+}
+
+/// Verification of one proof of a response, see [`ProofVerification`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct ProofItemVerification {
+    /// The RP-defined identifier of the request item.
+    pub identifier: String,
+    /// The issuer schema of the proven credential.
+    pub issuer_schema_id: u64,
+    /// Whether the proof verifies.
+    pub valid: bool,
+    /// Why the proof does not verify, e.g. the verifier's revert reason.
+    pub error: Option<String>,
+}
+
+/// Verifies the proof response `proof_json` to `request`, as produced by
+/// [`ProofResponse::to_json`], at `now`.
+///
+/// The response must match the request, must not be past its `valid_until`
+/// and its root must be accepted as `root_source` says. Each proof is then
+/// checked with the `environment`'s `WorldIDVerifier` through the JSON-RPC
+/// endpoint at `rpc_url`.
+///
+/// An invalid response or proof is reported in the returned
+/// [`ProofVerification`], not as an error.
+///
+/// # Errors
+///
+/// - [`WalletKitError::InvalidInput`] if `proof_json` is malformed.
+/// - [`WalletKitError::NetworkError`] if an RPC call fails.
+#[uniffi::export(async_runtime = "tokio")]
+pub async fn verify_proof(
+    request: &ProofRequest,
+    proof_json: &str,
+    root_source: RootSource,
+    rpc_url: &str,
+    environment: &Environment,
+    now: u64,
+) -> Result<ProofVerification, WalletKitError> {
+    let response = ProofResponse::from_json(proof_json)?;
+    let root = response.merkle_root();
+    let mut verification = ProofVerification {
+        valid: false,
+        root: root.map(Into::into),
+        root_accepted: false,
+        items: Vec::new(),
+        error: None,
+    };
+
+    if let Err(err) = request.0.validate_response(&response.0) {
+        verification.error = Some(err.to_string());
+        return Ok(verification);
+    }
+    if response.is_expired(now) {
+        verification.error = Some("response is past its valid_until".to_string());
+        return Ok(verification);
+    }
+    let Some(root) = root else {
+        verification.error = Some("response has no proofs".to_string());
+        return Ok(verification);
+    };
+
+    verification.root_accepted = match &root_source {
+        RootSource::Registry => is_valid_root(rpc_url, environment, root).await?,
+        RootSource::Root { root: expected } => {
+            root == expected.0 && is_valid_root(rpc_url, environment, root).await?
+        }
+    };
+
+    for item in &response.0.responses {
+        // `validate_response` checked every item has a request item
+        let Some(request_item) = request
+            .0
+            .requests
+            .iter()
+            .find(|request_item| request_item.identifier == item.identifier)
+        else {
+            continue;
+        };
+        let result = if item.proof.as_ethereum_representation()[4] == root {
+            verify_item(request, &response, request_item, item, rpc_url, environment)
+                .await?
+        } else {
+            Err("proof is against a different root than the response".to_string())
+        };
+        verification.items.push(ProofItemVerification {
+            identifier: item.identifier.clone(),
+            issuer_schema_id: item.issuer_schema_id,
+            valid: result.is_ok(),
+            error: result.err(),
+        });
+    }
+
+    verification.valid =
+        verification.root_accepted && verification.items.iter().all(|item| item.valid);
+    Ok(verification)
+}
+
+/// Checks the proof of `item` with the `WorldIDVerifier`, returning the
+/// reason if it is rejected.
+async fn verify_item(
+    request: &ProofRequest,
+    response: &ProofResponse,
+    request_item: &RequestItem,
+    item: &ResponseItem,
+    rpc_url: &str,
+    environment: &Environment,
+) -> Result<Result<(), String>, WalletKitError> {
+    let request = &request.0;
+    let rp_id = request.rp_id.into_inner();
+    let nonce: U256 = request.nonce.into();
+    let signal_hash: U256 = request_item.signal_hash().into();
+    let credential_genesis_issued_at_min =
+        U256::from(request_item.genesis_issued_at_min.unwrap_or_default());
+    let zero_knowledge_proof = item.proof.as_ethereum_representation();
+
+    let calldata = match request.proof_type {
+        ProofType::Uniqueness => {
+            let (Some(nullifier), Some(action)) = (item.nullifier, request.action)
+            else {
+                return Ok(Err(
+                    "uniqueness proof without nullifier or action".to_string()
+                ));
+            };
+            verifyCall {
+                nullifier: nullifier.into(),
+                action: action.into(),
+                rpId: rp_id,
+                nonce,
+                signalHash: signal_hash,
+                expiresAtMin: item.expires_at_min,
+                issuerSchemaId: item.issuer_schema_id,
+                credentialGenesisIssuedAtMin: credential_genesis_issued_at_min,
+                zeroKnowledgeProof: zero_knowledge_proof,
+            }
+            .abi_encode()
+        }
+        ProofType::CreateSession | ProofType::Session => {
+            let (Some(session_nullifier), Some(session_id)) =
+                (item.session_nullifier, response.0.session_id)
+            else {
+                return Ok(Err(
+                    "session proof without session nullifier or session id".to_string(),
+                ));
+            };
+            verifySessionCall {
+                rpId: rp_id,
+                nonce,
+                signalHash: signal_hash,
+                expiresAtMin: item.expires_at_min,
+                issuerSchemaId: item.issuer_schema_id,
+                credentialGenesisIssuedAtMin: credential_genesis_issued_at_min,
+                sessionId: session_id.commitment.into(),
+                sessionNullifier: session_nullifier.as_ethereum_representation(),
+                zeroKnowledgeProof: zero_knowledge_proof,
+            }
+            .abi_encode()
+        }
+    };
+
+    let to = world_id_verifier_address(environment);
+    Ok(match eth_call(rpc_url, to, calldata).await? {
+        CallOutcome::Returned(_) => Ok(()),
+        CallOutcome::Reverted(reason) => Err(reason),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::signers::{local::PrivateKeySigner, SignerSync};
+    use alloy_core::primitives::U160;
+    use taceo_oprf::types::OprfKeyId;
+    use world_id_core::primitives::{
+        rp::RpId, FieldElement, Nullifier, ZeroKnowledgeProof,
+    };
+    use world_id_core::requests::{
+        ProofRequest as CoreProofRequest, ProofResponse as CoreProofResponse,
+        RequestVersion,
+    };
+
+    use super::*;
+
+    const TRUE: &str =
+        "0x0000000000000000000000000000000000000000000000000000000000000001";
+
+    fn request() -> ProofRequest {
+        let signature = PrivateKeySigner::from_bytes(&[1u8; 32].into())
+            .unwrap()
+            .sign_message_sync(b"test")
+            .unwrap();
+        CoreProofRequest {
+            id: "test_request".to_string(),
+            version: RequestVersion::V1,
+            proof_type: ProofType::Uniqueness,
+            created_at: 1_700_000_000,
+            expires_at: 1_700_000_300,
+            rp_id: RpId::new(1),
+            oprf_key_id: OprfKeyId::new(U160::from(1)),
+            session_id: None,
+            action: Some(FieldElement::from(1u64)),
+            signature,
+            nonce: FieldElement::from(2u64),
+            requests: vec![RequestItem {
+                identifier: "credential".to_string(),
+                issuer_schema_id: 1,
+                signal: None,
+                genesis_issued_at_min: None,
+                expires_at_min: None,
+            }],
+            constraints: None,
+        }
+        .into()
+    }
+
+    fn response_json(root: u64) -> String {
+        let proof = ZeroKnowledgeProof::from_ethereum_representation([
+            U256::from(1u64),
+            U256::from(2u64),
+            U256::from(3u64),
+            U256::from(4u64),
+            U256::from(root),
+        ]);
+        ProofResponse::from(CoreProofResponse {
+            id: "test_request".to_string(),
+            version: RequestVersion::V1,
+            session_id: None,
+            error: None,
+            responses: vec![ResponseItem::new_uniqueness(
+                "credential".to_string(),
+                1,
+                proof,
+                Nullifier::from(FieldElement::from(3u64)),
+                1_700_000_000,
+            )],
+        })
+        .to_json()
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_verify_proof_accepts_valid_response() {
+        let mut server = mockito::Server::new_async().await;
+        // `isValidRoot` returns true, `verify` returns nothing
+        let mock = server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": TRUE })
+                    .to_string(),
+            )
+            .expect(2)
+            .create_async()
+            .await;
+
+        let verification = verify_proof(
+            &request(),
+            &response_json(42),
+            RootSource::Registry,
+            &server.url(),
+            &Environment::Staging,
+            1_700_000_100,
+        )
+        .await
+        .expect("verify");
+        mock.assert_async().await;
+        assert!(verification.valid);
+        assert_eq!(verification.root, Some(Uint256::from(U256::from(42u64))));
+        assert_eq!(verification.items.len(), 1);
+        assert!(verification.items[0].valid);
+
+        let verification = verify_proof(
+            &request(),
+            &response_json(42),
+            RootSource::Root {
+                root: U256::from(43u64).into(),
+            },
+            &server.url(),
+            &Environment::Staging,
+            1_700_000_100,
+        )
+        .await
+        .expect("verify");
+        assert!(!verification.valid);
+        assert!(!verification.root_accepted);
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_verify_proof_reports_rejected_proof() {
+        let mut server = mockito::Server::new_async().await;
+        let calldata = crate::rpc::isValidRootCall {
+            root: U256::from(42u64),
+        }
+        .abi_encode();
+        let root_mock = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "params": [{ "data": format!("0x{}", hex::encode(calldata)) }],
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": TRUE })
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+        let verifier = world_id_verifier_address(&Environment::Staging);
+        let verify_mock = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "params": [{ "to": verifier.to_string() }],
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "error": { "code": 3, "message": "execution reverted: ProofInvalid()" },
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let verification = verify_proof(
+            &request(),
+            &response_json(42),
+            RootSource::Registry,
+            &server.url(),
+            &Environment::Staging,
+            1_700_000_100,
+        )
+        .await
+        .expect("verify");
+        root_mock.assert_async().await;
+        verify_mock.assert_async().await;
+        assert!(!verification.valid);
+        assert!(verification.root_accepted);
+        assert!(!verification.items[0].valid);
+        assert!(verification.items[0]
+            .error
+            .as_deref()
+            .is_some_and(|error| error.contains("ProofInvalid")));
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_verify_proof_rejects_mismatched_response() {
+        let mut request = request();
+        request.0.id = "other_request".to_string();
+
+        // no RPC call is needed for a response to another request
+        let verification = verify_proof(
+            &request,
+            &response_json(42),
+            RootSource::Registry,
+            "http://127.0.0.1:1",
+            &Environment::Staging,
+            1_700_000_100,
+        )
+        .await
+        .expect("verify");
+        assert!(!verification.valid);
+        assert!(verification.error.is_some());
+        assert!(verification.items.is_empty());
+
+        assert!(matches!(
+            verify_proof(
+                &request,
+                "{}",
+                RootSource::Registry,
+                "http://127.0.0.1:1",
+                &Environment::Staging,
+                1_700_000_100,
+            )
+            .await,
+            Err(WalletKitError::InvalidInput { .. })
+        ));
+    }
+}
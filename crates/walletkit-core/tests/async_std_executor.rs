@@ -0,0 +1,113 @@
+#![allow(missing_docs, clippy::missing_docs_in_private_items)]
+
+//! Drives the async `Authenticator` API from `async-std` instead of tokio,
+//! against a mock RPC and indexer.
+//!
+//! Run with:
+//!   `cargo test --test async_std_executor --features embed-zkeys`
+
+mod common;
+
+use std::sync::Arc;
+
+use alloy::primitives::address;
+use alloy::signers::{local::PrivateKeySigner, SignerSync};
+use alloy_core::primitives::U160;
+use taceo_oprf::types::OprfKeyId;
+use walletkit_core::requests::ProofRequest;
+use walletkit_core::{Authenticator, Groth16Materials};
+use world_id_core::primitives::{rp::RpId, Config, FieldElement, ServiceEndpoint};
+use world_id_core::requests::{
+    ProofRequest as CoreProofRequest, ProofType, RequestItem, RequestVersion,
+};
+
+fn proof_request(now: u64) -> ProofRequest {
+    let signature = PrivateKeySigner::from_bytes(&[1u8; 32].into())
+        .expect("test signer should be valid")
+        .sign_message_sync(b"test")
+        .expect("test signature should sign");
+    let request = CoreProofRequest {
+        id: "async_std_request".to_string(),
+        version: RequestVersion::V1,
+        proof_type: ProofType::Uniqueness,
+        created_at: now,
+        expires_at: now + 300,
+        rp_id: RpId::new(1),
+        oprf_key_id: OprfKeyId::new(U160::from(1)),
+        session_id: None,
+        action: Some(FieldElement::from(1u64)),
+        signature,
+        nonce: FieldElement::from(2u64),
+        requests: vec![RequestItem {
+            identifier: "credential".to_string(),
+            issuer_schema_id: 1,
+            signal: None,
+            genesis_issued_at_min: None,
+            expires_at_min: None,
+        }],
+        constraints: None,
+    };
+    ProofRequest::from_json(&serde_json::to_string(&request).unwrap())
+        .expect("request should parse")
+}
+
+#[test]
+fn test_authenticator_runs_under_async_std() {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    // the sync mockito server runs on its own thread, outside any tokio runtime
+    let mut mock_server = mockito::Server::new();
+    mock_server
+        .mock("POST", "/")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x0000000000000000000000000000000000000000000000000000000000000001"
+            })
+            .to_string(),
+        )
+        .create();
+    let indexer_mock = mock_server
+        .mock("POST", "/inclusion-proof")
+        .with_status(500)
+        .create();
+
+    let config = Config::new(
+        Some(mock_server.url()),
+        480,
+        address!("0x969947cFED008bFb5e3F32a25A1A2CDdf64d46fe"),
+        ServiceEndpoint::direct(mock_server.url()),
+        ServiceEndpoint::direct(mock_server.url()),
+        vec![],
+        2,
+    )
+    .unwrap();
+    let config = serde_json::to_string(&config).unwrap();
+
+    let store = common::create_test_credential_store();
+    store.init(1, 100).expect("init storage");
+    let materials =
+        Arc::new(Groth16Materials::from_embedded().expect("load groth16 materials"));
+
+    async_std::task::block_on(async {
+        assert!(tokio::runtime::Handle::try_current().is_err());
+
+        let authenticator = Authenticator::init(&[2u8; 32], &config, materials, store)
+            .await
+            .expect("init authenticator");
+        assert_eq!(authenticator.leaf_index(), 1);
+
+        // the indexer is down, so proving stops at the inclusion proof fetch
+        let now = 1_700_000_000;
+        let result = authenticator
+            .generate_proof(&proof_request(now), Some(now))
+            .await;
+        assert!(result.is_err());
+    });
+
+    indexer_mock.assert();
+    drop(mock_server);
+}
@@ -0,0 +1,81 @@
+//! Builds `tests/c_ffi/smoke.c` against the `walletkit_core` shared library and
+//! runs it.
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Directory holding the `libwalletkit_core` artifacts for the current profile.
+fn lib_dir() -> PathBuf {
+    let exe = std::env::current_exe().expect("current exe");
+    // target/<profile>/deps/c_ffi-<hash>
+    exe.parent()
+        .and_then(Path::parent)
+        .expect("target profile dir")
+        .to_path_buf()
+}
+
+/// Builds the `walletkit_core` shared library with the `c-ffi` feature. The
+/// test harness only links the rlib, so the cdylib may otherwise be stale.
+fn build_shared_library() {
+    let mut cargo = Command::new(env!("CARGO"));
+    // same selection as `cargo test --workspace` so dependency features unify
+    // identically and nothing is rebuilt
+    cargo.args([
+        "build",
+        "--workspace",
+        "--lib",
+        "--tests",
+        "--features",
+        "walletkit-core/c-ffi",
+    ]);
+    // variables set by `cargo test` for this binary would otherwise invalidate
+    // build script fingerprints of the outer build
+    for (key, _) in std::env::vars_os() {
+        let key = key.to_string_lossy();
+        if key.starts_with("CARGO_") && key != "CARGO_HOME" && key != "CARGO_TARGET_DIR"
+        {
+            cargo.env_remove(key.as_ref());
+        }
+    }
+    if !cfg!(debug_assertions) {
+        cargo.arg("--release");
+    }
+    let status = cargo.status().expect("run cargo");
+    assert!(status.success(), "failed to build libwalletkit_core");
+}
+
+#[test]
+fn test_c_smoke_program() {
+    build_shared_library();
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let out_dir = tempfile::tempdir().expect("create temp dir");
+    let binary = out_dir.path().join("smoke");
+    let lib_dir = lib_dir();
+
+    let status = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".into()))
+        .arg(manifest_dir.join("tests/c_ffi/smoke.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lwalletkit_core")
+        .arg("-o")
+        .arg(&binary)
+        .status()
+        .expect("run C compiler");
+    assert!(status.success(), "failed to compile smoke.c");
+
+    let storage_root = out_dir.path().join("storage");
+    let output = Command::new(&binary)
+        .arg(&storage_root)
+        .output()
+        .expect("run smoke binary");
+    assert!(
+        output.status.success(),
+        "smoke program failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "ok");
+}
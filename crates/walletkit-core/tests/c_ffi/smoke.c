@@ -0,0 +1,92 @@
+/*
+ * Smoke test for the C FFI surface. Built and run by `tests/c_ffi.rs`.
+ *
+ * Usage: smoke <storage_root>
+ */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "walletkit.h"
+
+#define CHECK(cond)                                                        \
+    do {                                                                   \
+        if (!(cond)) {                                                     \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,         \
+                    __LINE__, #cond);                                      \
+            return 1;                                                      \
+        }                                                                  \
+    } while (0)
+
+/* Identity "keystore": good enough to exercise the callback plumbing. */
+static int32_t copy_input(void *user_data, const uint8_t *ad, size_t ad_len,
+                          const uint8_t *input, size_t input_len,
+                          uint8_t **out, size_t *out_len) {
+    (void)user_data;
+    (void)ad;
+    (void)ad_len;
+    *out = malloc(input_len ? input_len : 1);
+    if (*out == NULL) {
+        return 1;
+    }
+    memcpy(*out, input, input_len);
+    *out_len = input_len;
+    return 0;
+}
+
+static void free_buffer(void *user_data, uint8_t *buf, size_t len) {
+    (void)user_data;
+    (void)len;
+    free(buf);
+}
+
+static int expect_error(WalletKitStatus expected, const char *needle) {
+    CHECK(walletkit_last_error_code() == expected);
+    char *message = walletkit_last_error_message();
+    CHECK(message != NULL);
+    CHECK(strstr(message, needle) != NULL);
+    walletkit_free_string(message);
+    return 0;
+}
+
+int main(int argc, char **argv) {
+    CHECK(argc == 2);
+    const char *storage_root = argv[1];
+    const uint8_t seed[32] = {7};
+    WalletKitKeystore keystore = {NULL, copy_input, copy_input, free_buffer};
+    WalletKitAuthenticator *handle = NULL;
+
+    /* No error recorded before the first call. */
+    CHECK(walletkit_last_error_code() == WALLET_KIT_STATUS_OK);
+    CHECK(walletkit_last_error_message() == NULL);
+
+    /* NULL arguments are rejected without touching the library state. */
+    CHECK(walletkit_authenticator_init(NULL, 0, "{}", storage_root, &keystore,
+                                       &handle) ==
+          WALLET_KIT_STATUS_INVALID_ARGUMENT);
+    CHECK(handle == NULL);
+
+    /* An invalid config is reported as invalid input. */
+    CHECK(walletkit_authenticator_init(seed, sizeof(seed), "not json",
+                                       storage_root, &keystore, &handle) ==
+          WALLET_KIT_STATUS_INVALID_INPUT);
+    CHECK(handle == NULL);
+    if (expect_error(WALLET_KIT_STATUS_INVALID_INPUT, "config")) {
+        return 1;
+    }
+
+    /* Proof generation requires a handle, in both blocking and async form. */
+    CHECK(walletkit_generate_proof_json(NULL, "{}", 0) == NULL);
+    if (expect_error(WALLET_KIT_STATUS_INVALID_ARGUMENT, "handle")) {
+        return 1;
+    }
+    CHECK(walletkit_generate_proof_json_async(NULL, "{}", 0, NULL, NULL) ==
+          WALLET_KIT_STATUS_INVALID_ARGUMENT);
+
+    /* Releasing NULL is a no-op. */
+    walletkit_free_string(NULL);
+    walletkit_authenticator_free(NULL);
+
+    puts("ok");
+    return 0;
+}
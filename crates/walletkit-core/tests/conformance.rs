@@ -0,0 +1,286 @@
+#![allow(missing_docs, clippy::missing_panics_doc, reason = "integration tests")]
+
+//! Conformance tests for the proof pipeline's deterministic stages: external
+//! nullifier, signal hash, nullifier hash and the public-input encoding.
+//!
+//! Every build of `walletkit-core` (native, wasm) must reproduce
+//! `tests/conformance/vectors.json` byte for byte; a build that diverges
+//! produces proofs some verifiers accept and others reject. The test reports
+//! the first stage that differs.
+//!
+//! Run with:
+//!   `cargo test -p walletkit-core --test conformance --features conformance-tests`
+//!
+//! The corpus is generated from a fixed seed. Regenerate the vectors (only
+//! for an intended change of the pipeline) with
+//! `WALLETKIT_UPDATE_CONFORMANCE=1` set.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use walletkit_core::v3::{proof::ProofContext, world_id::WorldId, CredentialType};
+use walletkit_core::Environment;
+
+const CORPUS_SEED: u64 = 0x5eed_2420;
+const RANDOM_CASES: usize = 48;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Vector {
+    label: String,
+    app_id: String,
+    /// Hex-encoded action, `None` for the app's default action.
+    action: Option<String>,
+    /// Hex-encoded signal, `None` for no signal.
+    signal: Option<String>,
+    /// Hex-encoded World ID secret.
+    secret: String,
+    stages: Stages,
+}
+
+/// Intermediate values of the pipeline, in the order they are computed.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Stages {
+    external_nullifier: String,
+    signal_hash: String,
+    nullifier_hash: String,
+    /// `nullifier_hash || external_nullifier || signal_hash`, as 32-byte
+    /// big-endian words in the order verifiers pass them.
+    public_inputs: String,
+}
+
+impl Stages {
+    fn compute(
+        app_id: &str,
+        action: Option<&[u8]>,
+        signal: Option<&[u8]>,
+        secret: &[u8],
+    ) -> Self {
+        let context = ProofContext::new_from_bytes(
+            app_id,
+            action.map(<[u8]>::to_vec),
+            signal.map(<[u8]>::to_vec),
+            CredentialType::Orb,
+        )
+        .expect("conformance app IDs are well-formed");
+        let nullifier_hash = WorldId::new(secret, &Environment::Staging)
+            .generate_nullifier_hash(&context);
+        let public_inputs = [
+            nullifier_hash,
+            context.external_nullifier,
+            context.signal_hash,
+        ]
+        .iter()
+        .flat_map(|input| input.to_be_bytes::<32>())
+        .collect::<Vec<_>>();
+        Self {
+            external_nullifier: hex::encode(
+                context.external_nullifier.to_be_bytes::<32>(),
+            ),
+            signal_hash: hex::encode(context.signal_hash.to_be_bytes::<32>()),
+            nullifier_hash: hex::encode(nullifier_hash.to_be_bytes::<32>()),
+            public_inputs: hex::encode(public_inputs),
+        }
+    }
+
+    fn named(&self) -> [(&'static str, &str); 4] {
+        [
+            ("external_nullifier", &self.external_nullifier),
+            ("signal_hash", &self.signal_hash),
+            ("nullifier_hash", &self.nullifier_hash),
+            ("public_inputs", &self.public_inputs),
+        ]
+    }
+}
+
+impl Vector {
+    fn new(
+        label: impl Into<String>,
+        app_id: impl Into<String>,
+        action: Option<Vec<u8>>,
+        signal: Option<Vec<u8>>,
+        secret: &[u8],
+    ) -> Self {
+        let app_id = app_id.into();
+        let stages =
+            Stages::compute(&app_id, action.as_deref(), signal.as_deref(), secret);
+        Self {
+            label: label.into(),
+            app_id,
+            action: action.map(hex::encode),
+            signal: signal.map(hex::encode),
+            secret: hex::encode(secret),
+            stages,
+        }
+    }
+
+    fn recompute(&self) -> Stages {
+        Stages::compute(
+            &self.app_id,
+            self.action
+                .as_ref()
+                .map(|action| hex::decode(action).expect("hex action"))
+                .as_deref(),
+            self.signal
+                .as_ref()
+                .map(|signal| hex::decode(signal).expect("hex signal"))
+                .as_deref(),
+            &hex::decode(&self.secret).expect("hex secret"),
+        )
+    }
+}
+
+/// `SplitMix64`, so the corpus does not depend on a `rand` version.
+struct Corpus(u64);
+
+impl Corpus {
+    const fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        usize::try_from(self.next_u64() % bound as u64).expect("bounded")
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next_u64().to_le_bytes()[0]).collect()
+    }
+
+    fn app_id(&mut self) -> String {
+        let prefix = if self.below(4) == 0 {
+            "app_staging_"
+        } else {
+            "app_"
+        };
+        format!("{prefix}{}", hex::encode(self.bytes(16)))
+    }
+
+    fn text(&mut self, max_chars: usize) -> String {
+        const CHARS: &[char] = &[
+            'a', 'Z', '0', '-', '_', ' ', 'é', 'ß', '世', '🌍', '\u{200d}', '\0',
+        ];
+        let len = self.below(max_chars + 1);
+        (0..len).map(|_| CHARS[self.below(CHARS.len())]).collect()
+    }
+
+    fn signal(&mut self) -> Option<Vec<u8>> {
+        match self.below(6) {
+            0 => None,
+            1 => Some(Vec::new()),
+            2 => Some(self.text(64).into_bytes()),
+            3 => Some(self.bytes(32)),
+            4 => Some(self.bytes(20)),
+            _ => {
+                const LENGTHS: [usize; 4] = [31, 33, 256, 1024];
+                let len = LENGTHS[self.below(LENGTHS.len())];
+                Some(self.bytes(len))
+            }
+        }
+    }
+}
+
+fn corpus() -> Vec<Vector> {
+    let secret = [0x42; 32];
+    let app_id = "app_10eb12bd96d8f7202892ff25f094c803";
+    let mut vectors = vec![
+        Vector::new("no action, no signal", app_id, None, None, &secret),
+        Vector::new(
+            "empty action and signal",
+            app_id,
+            Some(Vec::new()),
+            Some(Vec::new()),
+            &secret,
+        ),
+        Vector::new(
+            "unicode",
+            app_id,
+            Some("vötè-🌍".as_bytes().to_vec()),
+            Some("👩\u{200d}💻 世界".as_bytes().to_vec()),
+            &secret,
+        ),
+        Vector::new(
+            "long signal",
+            app_id,
+            Some(b"claim".to_vec()),
+            Some(vec![0xab; 4096]),
+            &secret,
+        ),
+        // Signals whose bytes read differently little- and big-endian, as
+        // ABI-encoded addresses and integers from on-chain RPs are.
+        Vector::new(
+            "byte order: address signal",
+            app_id,
+            Some(b"airdrop".to_vec()),
+            Some(
+                hex::decode(
+                    "000000000000000000000000f39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+                )
+                .expect("hex"),
+            ),
+            &secret,
+        ),
+        Vector::new(
+            "byte order: integer signal",
+            app_id,
+            Some(b"airdrop".to_vec()),
+            Some(1u64.to_be_bytes().to_vec()),
+            &secret,
+        ),
+    ];
+
+    let mut corpus = Corpus(CORPUS_SEED);
+    for i in 0..RANDOM_CASES {
+        let app_id = corpus.app_id();
+        let action = match corpus.below(3) {
+            0 => None,
+            1 => Some(corpus.text(40).into_bytes()),
+            _ => {
+                let len = corpus.below(96);
+                Some(corpus.bytes(len))
+            }
+        };
+        let signal = corpus.signal();
+        let secret = corpus.bytes(32);
+        vectors.push(Vector::new(
+            format!("random {i}"),
+            app_id,
+            action,
+            signal,
+            &secret,
+        ));
+    }
+    vectors
+}
+
+fn vectors_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/vectors.json")
+}
+
+#[test]
+fn test_pipeline_matches_conformance_vectors() {
+    let path = vectors_path();
+    if std::env::var_os("WALLETKIT_UPDATE_CONFORMANCE").is_some() {
+        let json = serde_json::to_string_pretty(&corpus()).expect("serialize vectors");
+        std::fs::write(&path, json + "\n").expect("write vectors");
+    }
+
+    let vectors: Vec<Vector> =
+        serde_json::from_slice(&std::fs::read(&path).expect("read vectors"))
+            .expect("parse vectors");
+    assert!(vectors.len() > RANDOM_CASES);
+    for vector in &vectors {
+        let actual = vector.recompute();
+        for ((stage, expected), (_, actual)) in
+            vector.stages.named().into_iter().zip(actual.named())
+        {
+            assert_eq!(
+                expected, actual,
+                "vector {:?} diverges first at stage `{stage}`\ninputs: {vector:?}",
+                vector.label
+            );
+        }
+    }
+}
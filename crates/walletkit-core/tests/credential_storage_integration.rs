@@ -5,8 +5,12 @@
 
 mod common;
 
+use std::sync::Arc;
+
 use rand::rngs::OsRng;
-use walletkit_core::storage::CredentialStore;
+use walletkit_core::storage::{
+    CredentialStore, MigrationReport, RegistryKind, StorageError,
+};
 use walletkit_core::Credential;
 use world_id_core::api_types::AccountInclusionProof;
 use world_id_core::primitives::AuthenticatorPublicKeySet;
@@ -59,13 +63,114 @@ fn test_storage_flow_end_to_end() {
     };
 
     store
-        .merkle_cache_put(&account_inclusion_proof, 100, 10)
+        .merkle_cache_put(
+            RegistryKind::AccountRegistry,
+            &account_inclusion_proof,
+            100,
+            10,
+        )
         .expect("cache put");
     let now = 105;
-    let hit = store.merkle_cache_get(now).expect("cache get");
+    let hit = store
+        .merkle_cache_get(RegistryKind::AccountRegistry, now)
+        .expect("cache get");
     assert_eq!(hit.unwrap().inclusion_proof.leaf_index, 42);
-    let miss = store.merkle_cache_get(111).expect("cache get");
+    let miss = store
+        .merkle_cache_get(RegistryKind::AccountRegistry, 111)
+        .expect("cache get");
     assert!(miss.is_none());
 
     common::cleanup_storage(&root);
 }
+
+#[test]
+fn test_import_from_legacy_store() {
+    let legacy_root = common::temp_root();
+    let legacy_provider = common::InMemoryStorageProvider::new(&legacy_root);
+    let legacy = Arc::new(
+        CredentialStore::from_provider(&legacy_provider).expect("legacy store"),
+    );
+    legacy.init(42, 100).expect("init legacy");
+    for issuer_schema_id in 1..=5u64 {
+        let credential: Credential = CoreCredential::new()
+            .issuer_schema_id(issuer_schema_id)
+            .genesis_issued_at(1_700_000_000)
+            .into();
+        legacy
+            .store_credential(
+                &credential,
+                &CoreFieldElement::random(&mut OsRng).into(),
+                1_800_000_000,
+                None,
+                100,
+            )
+            .expect("store credential");
+    }
+    let expired: Credential = CoreCredential::new()
+        .issuer_schema_id(6)
+        .genesis_issued_at(1_700_000_000)
+        .into();
+    legacy
+        .store_credential(
+            &expired,
+            &CoreFieldElement::from(1u64).into(),
+            150,
+            None,
+            100,
+        )
+        .expect("store expired credential");
+
+    let root = common::temp_root();
+    let provider = common::InMemoryStorageProvider::new(&root);
+    let store = CredentialStore::from_provider(&provider).expect("store");
+    store.init(42, 200).expect("init");
+
+    let expected = MigrationReport {
+        migrated: 5,
+        skipped: 1,
+        errors: vec![],
+    };
+    let dry_run = store
+        .import_from_legacy_store_dry_run(Arc::clone(&legacy), 200)
+        .expect("dry run");
+    assert_eq!(dry_run, expected);
+    assert!(store.list_credentials(None, 200).expect("list").is_empty());
+
+    let report = store
+        .import_from_legacy_store(Arc::clone(&legacy), 200)
+        .expect("import");
+    assert_eq!(report, expected);
+    let mut imported = store
+        .list_credentials(None, 200)
+        .expect("list")
+        .into_iter()
+        .map(|record| record.issuer_schema_id)
+        .collect::<Vec<_>>();
+    imported.sort_unstable();
+    assert_eq!(imported, vec![1, 2, 3, 4, 5]);
+    for issuer_schema_id in 1..=5u64 {
+        let (_, legacy_blinding) = legacy
+            .get_credential(issuer_schema_id, 200)
+            .expect("get legacy credential")
+            .expect("legacy credential exists");
+        let (_, blinding) = store
+            .get_credential(issuer_schema_id, 200)
+            .expect("get credential")
+            .expect("credential was imported");
+        assert_eq!(blinding.to_bytes(), legacy_blinding.to_bytes());
+    }
+
+    // a store for another account refuses the import
+    let other_root = common::temp_root();
+    let other_provider = common::InMemoryStorageProvider::new(&other_root);
+    let other = CredentialStore::from_provider(&other_provider).expect("other store");
+    other.init(7, 200).expect("init other");
+    assert!(matches!(
+        other.import_from_legacy_store(legacy, 200),
+        Err(StorageError::InvalidLeafIndex { .. })
+    ));
+
+    common::cleanup_storage(&legacy_root);
+    common::cleanup_storage(&root);
+    common::cleanup_storage(&other_root);
+}
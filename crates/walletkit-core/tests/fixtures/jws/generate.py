@@ -0,0 +1,105 @@
+"""Generates the JWS-signed proof request fixtures in `fixtures.json`.
+
+Requires the `cryptography` package. Keys are random, so every run produces
+new fixtures: `python3 generate.py > fixtures.json`.
+"""
+
+import base64
+import hashlib
+import hmac
+import json
+
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import ec, ed25519
+from cryptography.hazmat.primitives.asymmetric.utils import decode_dss_signature
+
+NOW = 1725381200
+REQUEST = {
+    "id": "req_abc123",
+    "version": 1,
+    "created_at": 1725381192,
+    "expires_at": 1725381492,
+    "rp_id": "rp_0000000000000001",
+    "oprf_key_id": "0x1",
+    "session_id": None,
+    "action": "0x000000000000000000000000000000000000000000000000000000000000002a",
+    "signature": "0xa1fd06f0d8ceb541f6096fe2e865063eac1ff085c9d2bac2eedcc9ed03804bfc18d956b38c5ac3a8f7e71fde43deff3bda254d369c699f3c7a3f8e6b8477a5f51c",
+    "nonce": "0x0000000000000000000000000000000000000000000000000000000000000001",
+    "proof_requests": [{"identifier": "orb", "issuer_schema_id": 1}],
+}
+
+
+def b64url(data: bytes) -> str:
+    return base64.urlsafe_b64encode(data).rstrip(b"=").decode()
+
+
+def signing_input(alg: str, kid: str, claims: dict) -> str:
+    header = b64url(json.dumps({"alg": alg, "kid": kid, "typ": "JWT"}).encode())
+    payload = b64url(json.dumps({**REQUEST, **claims}).encode())
+    return f"{header}.{payload}"
+
+
+def es256(key: ec.EllipticCurvePrivateKey, kid: str, claims: dict) -> str:
+    data = signing_input("ES256", kid, claims)
+    r, s = decode_dss_signature(key.sign(data.encode(), ec.ECDSA(hashes.SHA256())))
+    return f"{data}.{b64url(r.to_bytes(32, 'big') + s.to_bytes(32, 'big'))}"
+
+
+def eddsa(key: ed25519.Ed25519PrivateKey, kid: str, claims: dict) -> str:
+    data = signing_input("EdDSA", kid, claims)
+    return f"{data}.{b64url(key.sign(data.encode()))}"
+
+
+def hs256(secret: bytes, kid: str, claims: dict) -> str:
+    data = signing_input("HS256", kid, claims)
+    return f"{data}.{b64url(hmac.new(secret, data.encode(), hashlib.sha256).digest())}"
+
+
+es_key = ec.generate_private_key(ec.SECP256R1())
+ed_key = ed25519.Ed25519PrivateKey.generate()
+other_key = ec.generate_private_key(ec.SECP256R1())
+
+es_public = es_key.public_key().public_numbers()
+ed_public = ed_key.public_key().public_bytes(
+    serialization.Encoding.Raw, serialization.PublicFormat.Raw
+)
+es_sec1 = es_key.public_key().public_bytes(
+    serialization.Encoding.X962, serialization.PublicFormat.UncompressedPoint
+)
+
+valid = {"iat": NOW - 8, "exp": NOW + 292}
+print(
+    json.dumps(
+        {
+            "now": NOW,
+            "rp_id": REQUEST["rp_id"],
+            "jwks": {
+                "keys": [
+                    {
+                        "kty": "EC",
+                        "crv": "P-256",
+                        "kid": "es256-1",
+                        "x": b64url(es_public.x.to_bytes(32, "big")),
+                        "y": b64url(es_public.y.to_bytes(32, "big")),
+                    },
+                    {
+                        "kty": "OKP",
+                        "crv": "Ed25519",
+                        "kid": "eddsa-1",
+                        "x": b64url(ed_public),
+                    },
+                ]
+            },
+            "tokens": {
+                "valid_es256": es256(es_key, "es256-1", valid),
+                "valid_eddsa": eddsa(ed_key, "eddsa-1", valid),
+                "expired": es256(es_key, "es256-1", {"iat": NOW - 600, "exp": NOW - 300}),
+                "wrong_key": es256(other_key, "es256-1", valid),
+                # HMAC keyed with the RP's public key, as in the classic
+                # algorithm confusion attack
+                "alg_confusion": hs256(es_sec1, "es256-1", valid),
+            },
+        },
+        indent=2,
+    )
+)
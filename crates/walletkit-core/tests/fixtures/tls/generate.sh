@@ -0,0 +1,38 @@
+#!/usr/bin/env sh
+# Regenerates the certificates for the TLS pinning tests in
+# `src/tls_pinning.rs`: a CA and a leaf for 127.0.0.1 signed by it, valid
+# for 100 years, plus an unrelated self-signed decoy certificate. The last
+# commands print the pins of the leaf and the decoy.
+set -eu
+cd "$(dirname "$0")"
+
+openssl ecparam -name prime256v1 -genkey -noout -out ca.key.pem
+openssl req -x509 -new -key ca.key.pem -sha256 -days 36500 \
+  -subj "/CN=WalletKit Test CA" \
+  -addext "basicConstraints=critical,CA:TRUE" \
+  -addext "keyUsage=critical,keyCertSign" \
+  -out ca.pem
+openssl x509 -in ca.pem -outform DER -out ca.der
+
+openssl ecparam -name prime256v1 -genkey -noout -out server.key.pem
+openssl pkcs8 -topk8 -nocrypt -in server.key.pem -outform DER -out server.key.der
+openssl req -new -key server.key.pem -subj "/CN=127.0.0.1" -out server.csr
+printf '%s\n' \
+  "basicConstraints=critical,CA:FALSE" \
+  "keyUsage=critical,digitalSignature" \
+  "extendedKeyUsage=serverAuth" \
+  "subjectAltName=IP:127.0.0.1" >server.ext
+openssl x509 -req -in server.csr -CA ca.pem -CAkey ca.key.pem -CAcreateserial \
+  -sha256 -days 36500 -extfile server.ext -outform DER -out server.der
+
+# a certificate no chain of the leaf goes through, to append to the chain
+openssl ecparam -name prime256v1 -genkey -noout -out decoy.key.pem
+openssl req -x509 -new -key decoy.key.pem -sha256 -days 36500 \
+  -subj "/CN=WalletKit Test Decoy" -outform DER -out decoy.der
+rm -f ca.key.pem server.key.pem server.csr server.ext ca.pem ca.srl decoy.key.pem
+
+# SHA-256 of the SubjectPublicKeyInfo of the leaf and the decoy
+for cert in server.der decoy.der; do
+  openssl x509 -inform DER -in "$cert" -pubkey -noout |
+    openssl pkey -pubin -outform DER | openssl dgst -sha256 -hex
+done
@@ -150,7 +150,7 @@ async fn e2e_session_proof() -> Result<()> {
         .session_id
         .expect("create-session response should include session_id");
     let cached_seed = store
-        .get_session_seed(session_id.oprf_seed, now)
+        .get_session_seed(authenticator.environment_scope(), session_id.oprf_seed, now)
         .wrap_err("get_session_seed failed")?;
     assert!(
         cached_seed.is_some(),
@@ -189,6 +189,16 @@ async fn e2e_session_proof() -> Result<()> {
         "session proof should not have a uniqueness nullifier"
     );
 
+    // Both proofs disclosed the credential and were counted.
+    let record = store
+        .list_credentials(Some(schema_id), now_secs())
+        .wrap_err("list_credentials failed")?
+        .into_iter()
+        .next()
+        .ok_or_else(|| eyre::eyre!("issued credential missing from listing"))?;
+    assert_eq!(record.use_count, 2);
+    assert!(record.last_used_at.is_some());
+
     // Phase 5: an independent ownership proof must share the session proof's
     // Merkle root — both prove inclusion of the same on-chain account.
     let (credential, blinding_factor) = store
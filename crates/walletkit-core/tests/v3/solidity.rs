@@ -47,7 +47,8 @@ async fn test_advanced_external_nullifier_generation_on_chain() {
         Some(custom_action),
         None,
         CredentialType::Orb,
-    );
+    )
+    .unwrap();
 
     let nullifier = contract
         .generateExternalNullifier("test_text".to_string())
@@ -205,7 +206,8 @@ async fn test_verify_simple_world_id_proof_on_chain() {
         None,
         Some("my_signal".to_string()),
         CredentialType::Orb,
-    );
+    )
+    .unwrap();
 
     let proof = world_id.generate_proof(&proof_context).await.unwrap();
 
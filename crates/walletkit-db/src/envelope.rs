@@ -8,7 +8,7 @@
 //! independent vaults (e.g. credential vault and `OrbPcpStore`) cannot share
 //! intermediate keys.
 
-use secrecy::SecretBox;
+use secrecy::{ExposeSecret, SecretBox};
 use serde::{Deserialize, Serialize};
 use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
@@ -96,13 +96,7 @@ pub fn init_or_open_envelope_key(
 ) -> StoreResult<SecretBox<[u8; 32]>> {
     let _guard = lock.lock()?;
     if let Some(bytes) = blob_store.read(filename.to_string())? {
-        let envelope = KeyEnvelope::deserialize(&bytes)?;
-        let k_intermediate_bytes = Zeroizing::new(
-            keystore
-                .open_sealed(ad.to_vec(), envelope.wrapped_k_intermediate.clone())?,
-        );
-        let k_intermediate = parse_key_32(&k_intermediate_bytes, "intermediate key")?;
-        Ok(SecretBox::init_with(|| k_intermediate))
+        open_envelope(keystore, &bytes, ad)
     } else {
         let mut k_intermediate = Zeroizing::new([0u8; 32]);
         getrandom::fill(k_intermediate.as_mut())
@@ -123,6 +117,87 @@ pub fn init_or_open_envelope_key(
     }
 }
 
+/// Opens the envelope-sealed intermediate key at `filename` without creating
+/// one.
+///
+/// Returns `None` if no envelope exists.
+///
+/// # Errors
+///
+/// Propagates errors from the lock, keystore, blob store, or CBOR codec.
+pub fn open_envelope_key(
+    keystore: &dyn Keystore,
+    blob_store: &dyn AtomicBlobStore,
+    lock: &Lock,
+    filename: &str,
+    ad: &[u8],
+) -> StoreResult<Option<SecretBox<[u8; 32]>>> {
+    let _guard = lock.lock()?;
+    blob_store
+        .read(filename.to_string())?
+        .map(|bytes| open_envelope(keystore, &bytes, ad))
+        .transpose()
+}
+
+/// Re-seals `k_intermediate` under `new_keystore` and replaces the envelope
+/// at `filename`.
+///
+/// Used when the device keystore changes (e.g. after an OS keystore
+/// migration) while the intermediate key is still known. The new envelope is
+/// checked to open under `new_keystore` before it is persisted through
+/// [`AtomicBlobStore::write_atomic`], so a failure at any point leaves the
+/// previous envelope in place. The original `created_at` is kept if the
+/// previous envelope can still be parsed.
+///
+/// # Errors
+///
+/// Propagates errors from the lock, keystore, blob store, or CBOR codec, and
+/// returns [`StoreError::Crypto`] if the new envelope does not open to
+/// `k_intermediate`.
+pub fn rewrap_envelope_key(
+    new_keystore: &dyn Keystore,
+    blob_store: &dyn AtomicBlobStore,
+    lock: &Lock,
+    filename: &str,
+    ad: &[u8],
+    k_intermediate: &SecretBox<[u8; 32]>,
+    now: u64,
+) -> StoreResult<()> {
+    let _guard = lock.lock()?;
+    let created_at = blob_store
+        .read(filename.to_string())?
+        .and_then(|bytes| KeyEnvelope::deserialize(&bytes).ok())
+        .map_or(now, |envelope| envelope.created_at);
+
+    let wrapped = new_keystore.seal(ad, k_intermediate.expose_secret())?;
+    let reopened =
+        Zeroizing::new(new_keystore.open_sealed(ad.to_vec(), wrapped.clone())?);
+    if reopened.as_slice() != k_intermediate.expose_secret() {
+        return Err(StoreError::Crypto(
+            "re-sealed intermediate key does not open under the new keystore"
+                .to_string(),
+        ));
+    }
+
+    let mut envelope = KeyEnvelope::new(wrapped, now);
+    envelope.created_at = created_at;
+    blob_store.write_atomic(filename.to_string(), envelope.serialize()?)
+}
+
+/// Deserializes `bytes` and opens the sealed intermediate key.
+fn open_envelope(
+    keystore: &dyn Keystore,
+    bytes: &[u8],
+    ad: &[u8],
+) -> StoreResult<SecretBox<[u8; 32]>> {
+    let envelope = KeyEnvelope::deserialize(bytes)?;
+    let k_intermediate_bytes = Zeroizing::new(
+        keystore.open_sealed(ad.to_vec(), envelope.wrapped_k_intermediate.clone())?,
+    );
+    let k_intermediate = parse_key_32(&k_intermediate_bytes, "intermediate key")?;
+    Ok(SecretBox::init_with(|| k_intermediate))
+}
+
 fn parse_key_32(bytes: &[u8], label: &str) -> StoreResult<[u8; 32]> {
     if bytes.len() != 32 {
         return Err(StoreError::InvalidEnvelope(format!(
@@ -137,7 +212,9 @@ fn parse_key_32(bytes: &[u8], label: &str) -> StoreResult<[u8; 32]> {
 
 #[cfg(test)]
 mod tests {
-    use super::{init_or_open_envelope_key, KeyEnvelope};
+    use super::{
+        init_or_open_envelope_key, open_envelope_key, rewrap_envelope_key, KeyEnvelope,
+    };
     use crate::{AtomicBlobStore, Keystore, Lock, StoreError, StoreResult};
     use secrecy::ExposeSecret;
     use std::sync::Mutex;
@@ -266,4 +343,50 @@ mod tests {
 
         assert_eq!(key_a.expose_secret(), key_b.expose_secret());
     }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_rewrap_envelope_key() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let lock = Lock::open(&dir.path().join("envelope.lock")).expect("open lock");
+        let old_keystore = XorKeystore { pad: [0xAA; 32] };
+        let new_keystore = XorKeystore { pad: [0x55; 32] };
+        let blob_store = InMemoryBlobs::new();
+
+        assert!(
+            open_envelope_key(&old_keystore, &blob_store, &lock, "k.bin", b"ad")
+                .expect("open missing")
+                .is_none()
+        );
+        let key = init_or_open_envelope_key(
+            &old_keystore,
+            &blob_store,
+            &lock,
+            "k.bin",
+            b"ad",
+            100,
+        )
+        .expect("init");
+
+        rewrap_envelope_key(
+            &new_keystore,
+            &blob_store,
+            &lock,
+            "k.bin",
+            b"ad",
+            &key,
+            200,
+        )
+        .expect("rewrap");
+        let reopened =
+            open_envelope_key(&new_keystore, &blob_store, &lock, "k.bin", b"ad")
+                .expect("open")
+                .expect("present");
+        assert_eq!(reopened.expose_secret(), key.expose_secret());
+
+        let bytes = blob_store.read("k.bin".to_string()).unwrap().unwrap();
+        let envelope = KeyEnvelope::deserialize(&bytes).expect("deserialize");
+        assert_eq!(envelope.created_at, 100);
+        assert_eq!(envelope.updated_at, 200);
+    }
 }
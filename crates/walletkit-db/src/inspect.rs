@@ -0,0 +1,216 @@
+//! Keyless identification of storage files, for support and forensic tooling.
+//!
+//! [`inspect_file`] reads only what is in the clear:
+//!
+//! - **Plaintext `SQLite` databases** (e.g. exports from
+//!   [`cipher::export_plaintext_copy`](crate::cipher::export_plaintext_copy))
+//!   carry the standard 100-byte header, including `PRAGMA application_id`
+//!   (offset 68) and `PRAGMA user_version` (offset 60).
+//! - **Encrypted databases** are recognised by the layout `sqlite3mc`'s
+//!   `chacha20` cipher gives page 1: a 16-byte salt in place of the header
+//!   magic, followed by header bytes 16..24 (page size, file format versions,
+//!   reserved bytes, payload fractions), which stay unencrypted so the page
+//!   size can be read before the key is applied. Everything else, including
+//!   `application_id` and `user_version`, is encrypted.
+//! - **Key envelopes** are plain CBOR; the version and timestamps are
+//!   readable, the wrapped key is sealed.
+//!
+//! Nothing here needs or touches a key.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::envelope::KeyEnvelope;
+
+/// Magic string at the start of every plaintext `SQLite` database.
+const SQLITE_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// Length of the `SQLite` database header.
+const SQLITE_HEADER_LEN: usize = 100;
+
+/// Header bytes 21..24: the maximum and minimum embedded payload fractions
+/// and the leaf payload fraction, which `SQLite` requires to be 64, 32, 32.
+const SQLITE_PAYLOAD_FRACTIONS: [u8; 3] = [64, 32, 32];
+
+/// Key envelopes are a few hundred bytes; anything much larger is not one.
+const MAX_ENVELOPE_LEN: u64 = 4 * 1024;
+
+/// What [`inspect_file`] found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileFormat {
+    /// An unencrypted `SQLite` database.
+    PlaintextDatabase {
+        /// `PRAGMA application_id`; 0 if never set.
+        application_id: u32,
+        /// `PRAGMA user_version`.
+        user_version: u32,
+    },
+    /// A `sqlite3mc`-encrypted database. Its application ID and version are
+    /// encrypted.
+    EncryptedDatabase {
+        /// Page size in bytes.
+        page_size: u32,
+    },
+    /// A key envelope from [`crate::init_or_open_envelope_key`].
+    KeyEnvelope {
+        /// Envelope format version.
+        version: u32,
+        /// Seconds since the UNIX epoch when the envelope was created.
+        created_at: u64,
+        /// Seconds since the UNIX epoch when the envelope was last re-sealed.
+        updated_at: u64,
+    },
+    /// None of the above.
+    Unknown,
+}
+
+/// Identifies the storage file at `path` without any keys.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read.
+pub fn inspect_file(path: &Path) -> io::Result<FileFormat> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut header = Vec::new();
+    (&mut file)
+        .take(SQLITE_HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+
+    if let Some(format) = database_format(&header) {
+        return Ok(format);
+    }
+    if len <= MAX_ENVELOPE_LEN {
+        file.read_to_end(&mut header)?;
+        if let Some(format) = envelope_format(&header) {
+            return Ok(format);
+        }
+    }
+    Ok(FileFormat::Unknown)
+}
+
+/// Identifies in-memory storage file contents, e.g. a plaintext vault backup
+/// received from another device, like [`inspect_file`].
+#[must_use]
+pub fn inspect_bytes(bytes: &[u8]) -> FileFormat {
+    database_format(bytes)
+        .or_else(|| {
+            (bytes.len() as u64 <= MAX_ENVELOPE_LEN)
+                .then(|| envelope_format(bytes))
+                .flatten()
+        })
+        .unwrap_or(FileFormat::Unknown)
+}
+
+/// Reads a CBOR key envelope.
+fn envelope_format(bytes: &[u8]) -> Option<FileFormat> {
+    let envelope = ciborium::de::from_reader::<KeyEnvelope, _>(bytes).ok()?;
+    Some(FileFormat::KeyEnvelope {
+        version: envelope.version,
+        created_at: envelope.created_at,
+        updated_at: envelope.updated_at,
+    })
+}
+
+/// Reads a database header, plaintext or encrypted.
+fn database_format(header: &[u8]) -> Option<FileFormat> {
+    if header.len() < SQLITE_HEADER_LEN || header[21..24] != SQLITE_PAYLOAD_FRACTIONS {
+        return None;
+    }
+    let read_u32 = |offset: usize| {
+        u32::from_be_bytes([
+            header[offset],
+            header[offset + 1],
+            header[offset + 2],
+            header[offset + 3],
+        ])
+    };
+    if header.starts_with(SQLITE_MAGIC) {
+        return Some(FileFormat::PlaintextDatabase {
+            application_id: read_u32(68),
+            user_version: read_u32(60),
+        });
+    }
+    // 1 stands for 65536; other sizes are powers of two from 512
+    let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+        1 => 65536,
+        size if size >= 512 && size.is_power_of_two() => u32::from(size),
+        _ => return None,
+    };
+    Some(FileFormat::EncryptedDatabase { page_size })
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::SecretBox;
+
+    use super::*;
+    use crate::cipher;
+    use crate::test_utils::init_sqlite;
+
+    #[test]
+    fn test_inspect_file() {
+        init_sqlite();
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let encrypted_path = dir.path().join("encrypted.sqlite");
+        let plaintext_path = dir.path().join("plaintext.sqlite");
+        let key = SecretBox::init_with(|| [0x44u8; 32]);
+
+        let conn = cipher::open_encrypted(&encrypted_path, &key, false)
+            .expect("open encrypted");
+        conn.execute_batch(
+            "CREATE TABLE widgets (id INTEGER PRIMARY KEY);
+             PRAGMA application_id = 1234;
+             PRAGMA user_version = 7;",
+        )
+        .expect("create schema");
+        cipher::export_plaintext_copy(&conn, &plaintext_path, &["widgets"])
+            .expect("export");
+        drop(conn);
+
+        assert_eq!(
+            inspect_file(&encrypted_path).expect("inspect encrypted"),
+            FileFormat::EncryptedDatabase { page_size: 4096 }
+        );
+        assert_eq!(
+            inspect_file(&plaintext_path).expect("inspect plaintext"),
+            FileFormat::PlaintextDatabase {
+                application_id: 1234,
+                user_version: 7,
+            }
+        );
+
+        let envelope_path = dir.path().join("keys.bin");
+        let envelope = KeyEnvelope::new(vec![0xAA; 60], 1_700_000_000);
+        std::fs::write(&envelope_path, envelope.serialize().expect("serialize"))
+            .expect("write envelope");
+        assert_eq!(
+            inspect_file(&envelope_path).expect("inspect envelope"),
+            FileFormat::KeyEnvelope {
+                version: 1,
+                created_at: 1_700_000_000,
+                updated_at: 1_700_000_000,
+            }
+        );
+
+        let garbage_path = dir.path().join("garbage.bin");
+        let mut garbage = vec![0u8; 8192];
+        getrandom::fill(&mut garbage).expect("random bytes");
+        std::fs::write(&garbage_path, &garbage).expect("write garbage");
+        assert_eq!(
+            inspect_file(&garbage_path).expect("inspect garbage"),
+            FileFormat::Unknown
+        );
+
+        assert!(inspect_file(&dir.path().join("missing")).is_err());
+
+        let plaintext = std::fs::read(&plaintext_path).expect("read plaintext");
+        assert_eq!(
+            inspect_bytes(&plaintext),
+            inspect_file(&plaintext_path).expect("inspect plaintext")
+        );
+        assert_eq!(inspect_bytes(&garbage), FileFormat::Unknown);
+        assert_eq!(inspect_bytes(&[]), FileFormat::Unknown);
+    }
+}
@@ -9,8 +9,13 @@
 //!   exposing the underlying [`Connection`].
 //! - [`blobs`] — content-addressed blob storage (`ensure_schema`, `put`,
 //!   `get`), [`ContentId`], and [`compute_content_id`].
+//! - [`migration`] — versioned schema migrations tracked in
+//!   `PRAGMA user_version`, with progress reporting.
 //! - [`init_or_open_envelope_key`] — sealed intermediate key persisted via
-//!   [`AtomicBlobStore`].
+//!   [`AtomicBlobStore`]; [`open_envelope_key`] and [`rewrap_envelope_key`]
+//!   open it without creating one and re-seal it under a new keystore.
+//! - [`inspect`] — keyless identification of databases and key envelopes
+//!   for support tooling.
 //! - [`Lock`] / [`LockGuard`] — cross-process exclusive lock (`flock` /
 //!   `LockFileEx` native, no-op on WASM).
 //! - [`Keystore`] / [`AtomicBlobStore`] — plain-Rust trait surface for
@@ -21,6 +26,8 @@
 //! these primitives.
 
 pub mod blobs;
+pub mod inspect;
+pub mod migration;
 
 mod envelope;
 mod error;
@@ -30,7 +37,7 @@ mod traits;
 mod vault;
 
 pub use blobs::{compute_content_id, ContentId};
-pub use envelope::init_or_open_envelope_key;
+pub use envelope::{init_or_open_envelope_key, open_envelope_key, rewrap_envelope_key};
 pub use error::{StoreError, StoreResult};
 pub use lock::{Lock, LockGuard};
 pub use sqlite::{
@@ -0,0 +1,375 @@
+//! Versioned schema migrations tracked in `PRAGMA user_version`.
+//!
+//! Each [`Migration`] whose version is above the database's `user_version` is
+//! applied in order inside its own savepoint, together with the
+//! `user_version` bump. A failing step is rolled back on its own, leaving the
+//! database at the last successfully applied version.
+//!
+//! Databases created before a consumer adopted migrations report
+//! `user_version = 0`, so a consumer's first migration must be idempotent
+//! (`CREATE TABLE IF NOT EXISTS`, ...) with respect to its pre-existing schema.
+//!
+//! A database whose `user_version` is above the last known migration was
+//! written by a newer version of the consumer; it is left untouched and
+//! [`run_migrations`] fails rather than guessing at its schema.
+
+use crate::sqlite::{Connection, DbResult, Error};
+
+/// Generic `SQLite` error code, used for migration misuse.
+const SQLITE_ERROR: i32 = 1;
+
+/// A data migration written in Rust, see [`Migration::data_migration`].
+pub type DataMigration = fn(&Connection) -> DbResult<()>;
+
+/// A single schema migration step.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// Schema version after this step. Versions must be strictly increasing
+    /// and start above `0`.
+    pub version: u32,
+    /// SQL applied by this step. May contain several statements.
+    pub sql: &'static str,
+    /// Human-readable summary, for logs.
+    pub description: &'static str,
+    /// Rust code run after `sql` in the same savepoint, for data migrations
+    /// SQL cannot express. Non-capturing closures coerce to it.
+    pub data_migration: Option<DataMigration>,
+}
+
+/// Progress reported after each applied migration step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MigrationProgress {
+    /// Zero-based index of the step within the migrations being applied.
+    pub migration_index: usize,
+    /// Number of steps being applied in this run.
+    pub total: usize,
+    /// Schema version reached by this step.
+    pub version_applied: u32,
+}
+
+/// Returns the database's `user_version`.
+///
+/// # Errors
+///
+/// Returns `Error` if the pragma cannot be read.
+pub fn schema_version(conn: &Connection) -> DbResult<u32> {
+    let version =
+        conn.query_row("PRAGMA user_version", &[], |row| Ok(row.column_i64(0)))?;
+    u32::try_from(version).map_err(|_| {
+        Error::new(SQLITE_ERROR, format!("invalid schema version {version}"))
+    })
+}
+
+/// Applies every migration newer than the database's `user_version`, calling
+/// `progress` after each step.
+///
+/// Returns the resulting schema version.
+///
+/// # Errors
+///
+/// Returns `Error` if the migrations are not strictly increasing, if the
+/// database is at a newer version than the last migration, or if a step
+/// fails. Steps applied before the failing one stay applied.
+pub fn run_migrations<F>(
+    conn: &Connection,
+    migrations: &[Migration],
+    progress: F,
+) -> DbResult<u32>
+where
+    F: Fn(MigrationProgress),
+{
+    validate(migrations)?;
+    let current = schema_version(conn)?;
+    let latest = migrations.last().map_or(0, |migration| migration.version);
+    if current > latest {
+        return Err(Error::new(
+            SQLITE_ERROR,
+            format!(
+                "database schema version {current} is newer than the latest known version {latest}"
+            ),
+        ));
+    }
+    let pending = migrations
+        .iter()
+        .filter(|migration| migration.version > current)
+        .collect::<Vec<_>>();
+
+    for (migration_index, migration) in pending.iter().enumerate() {
+        apply(conn, migration)?;
+        progress(MigrationProgress {
+            migration_index,
+            total: pending.len(),
+            version_applied: migration.version,
+        });
+    }
+    Ok(pending
+        .last()
+        .map_or(current, |migration| migration.version))
+}
+
+/// Runs [`run_migrations`] on a blocking thread, so large migrations do not
+/// stall the async runtime.
+///
+/// The connection is moved to the blocking thread and handed back together
+/// with the resulting schema version. `progress` is called on that thread.
+///
+/// # Errors
+///
+/// Returns `Error` if a migration fails or the blocking task panics.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn run_migrations_async<F>(
+    conn: Connection,
+    migrations: &'static [Migration],
+    progress: F,
+) -> DbResult<(Connection, u32)>
+where
+    F: Fn(MigrationProgress) + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        let version = run_migrations(&conn, migrations, progress)?;
+        Ok((conn, version))
+    })
+    .await
+    .map_err(|err| Error::new(SQLITE_ERROR, format!("migration task failed: {err}")))?
+}
+
+fn validate(migrations: &[Migration]) -> DbResult<()> {
+    let mut previous = 0;
+    for migration in migrations {
+        if migration.version <= previous {
+            return Err(Error::new(
+                SQLITE_ERROR,
+                format!(
+                    "migration versions must be strictly increasing from 1, got {} after {previous}",
+                    migration.version
+                ),
+            ));
+        }
+        previous = migration.version;
+    }
+    Ok(())
+}
+
+fn apply(conn: &Connection, migration: &Migration) -> DbResult<()> {
+    let savepoint = format!("migration_{}", migration.version);
+    conn.execute_batch(&format!("SAVEPOINT {savepoint}"))?;
+    let result = conn
+        .execute_batch(migration.sql)
+        .and_then(|()| migration.data_migration.map_or(Ok(()), |run| run(conn)))
+        .and_then(|()| {
+            conn.execute_batch(&format!("PRAGMA user_version = {}", migration.version))
+        });
+    match result {
+        Ok(()) => conn.execute_batch(&format!("RELEASE {savepoint}")),
+        Err(err) => {
+            // keep the original error; the rollback only undoes this step
+            let _ = conn.execute_batch(&format!(
+                "ROLLBACK TO {savepoint}; RELEASE {savepoint}"
+            ));
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::test_utils::init_sqlite;
+
+    const MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            sql: "CREATE TABLE items (id INTEGER PRIMARY KEY);",
+            description: "items table",
+            data_migration: None,
+        },
+        Migration {
+            version: 2,
+            sql: "ALTER TABLE items ADD COLUMN name TEXT;",
+            description: "item names",
+            data_migration: None,
+        },
+        Migration {
+            version: 3,
+            sql: "CREATE INDEX idx_items_name ON items (name);",
+            description: "item name index",
+            data_migration: None,
+        },
+    ];
+
+    fn table_exists(conn: &Connection, name: &str) -> bool {
+        conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name = ?1",
+            &[name.into()],
+            |row| Ok(row.column_i64(0) != 0),
+        )
+        .expect("query sqlite_master")
+    }
+
+    #[test]
+    fn test_progress_reported_for_each_step() {
+        init_sqlite();
+        let conn = Connection::open_in_memory().expect("open db");
+        let seen = Mutex::new(Vec::new());
+
+        let version = run_migrations(&conn, MIGRATIONS, |progress| {
+            seen.lock().unwrap().push(progress);
+        })
+        .expect("run migrations");
+
+        assert_eq!(version, 3);
+        assert_eq!(schema_version(&conn).expect("version"), 3);
+        let seen = seen.into_inner().unwrap();
+        assert_eq!(
+            seen,
+            (0..3)
+                .map(|index| MigrationProgress {
+                    migration_index: index,
+                    total: 3,
+                    version_applied: MIGRATIONS[index].version,
+                })
+                .collect::<Vec<_>>()
+        );
+
+        // already migrated: nothing to do
+        let version =
+            run_migrations(&conn, MIGRATIONS, |_| panic!("no steps expected"))
+                .expect("rerun migrations");
+        assert_eq!(version, 3);
+    }
+
+    #[test]
+    fn test_failed_step_rolls_back_only_itself() {
+        init_sqlite();
+        let conn = Connection::open_in_memory().expect("open db");
+        let broken = [
+            MIGRATIONS[0],
+            Migration {
+                version: 2,
+                sql: "CREATE TABLE partial (id INTEGER); SELECT * FROM missing_table;",
+                description: "fails halfway",
+                data_migration: None,
+            },
+        ];
+
+        run_migrations(&conn, &broken, |_| {}).expect_err("second step fails");
+        assert_eq!(schema_version(&conn).expect("version"), 1);
+        assert!(table_exists(&conn, "items"));
+        assert!(!table_exists(&conn, "partial"));
+    }
+
+    #[test]
+    fn test_resumes_partially_migrated_database() {
+        init_sqlite();
+        let conn = Connection::open_in_memory().expect("open db");
+        run_migrations(&conn, &MIGRATIONS[..1], |_| {}).expect("first step");
+        let seen = Mutex::new(Vec::new());
+
+        let version = run_migrations(&conn, MIGRATIONS, |progress| {
+            seen.lock().unwrap().push(progress);
+        })
+        .expect("resume migrations");
+
+        assert_eq!(version, 3);
+        assert_eq!(
+            seen.into_inner().unwrap(),
+            [
+                MigrationProgress {
+                    migration_index: 0,
+                    total: 2,
+                    version_applied: 2,
+                },
+                MigrationProgress {
+                    migration_index: 1,
+                    total: 2,
+                    version_applied: 3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_newer_database() {
+        init_sqlite();
+        let conn = Connection::open_in_memory().expect("open db");
+        run_migrations(&conn, MIGRATIONS, |_| {}).expect("run migrations");
+
+        let error =
+            run_migrations(&conn, &MIGRATIONS[..2], |_| panic!("no steps expected"))
+                .expect_err("database is newer than the migrations");
+        assert!(error.message.contains("newer"), "{error}");
+        assert_eq!(schema_version(&conn).expect("version"), 3);
+    }
+
+    #[test]
+    fn test_data_migration_shares_the_step_savepoint() {
+        init_sqlite();
+        let conn = Connection::open_in_memory().expect("open db");
+        let backfill = Migration {
+            version: 2,
+            sql: "ALTER TABLE items ADD COLUMN name TEXT;",
+            description: "named items",
+            data_migration: Some(|conn| {
+                for id in 1..=3_i64 {
+                    conn.execute(
+                        "INSERT INTO items (id, name) VALUES (?1, ?2)",
+                        &[id.into(), format!("item {id}").as_str().into()],
+                    )?;
+                }
+                Ok(())
+            }),
+        };
+
+        run_migrations(&conn, &[MIGRATIONS[0], backfill], |_| {})
+            .expect("run migrations");
+        let name = conn
+            .query_row("SELECT name FROM items WHERE id = 3", &[], |row| {
+                Ok(row.column_text(0))
+            })
+            .expect("query item");
+        assert_eq!(name, "item 3");
+
+        // a failing data migration rolls back its step's SQL too
+        let broken = Migration {
+            version: 3,
+            sql: "CREATE TABLE partial (id INTEGER);",
+            description: "fails in rust",
+            data_migration: Some(|conn| {
+                conn.execute_batch("SELECT * FROM missing_table;")
+            }),
+        };
+        run_migrations(&conn, &[MIGRATIONS[0], backfill, broken], |_| {})
+            .expect_err("data migration fails");
+        assert_eq!(schema_version(&conn).expect("version"), 2);
+        assert!(!table_exists(&conn, "partial"));
+    }
+
+    #[test]
+    fn test_rejects_unordered_versions() {
+        init_sqlite();
+        let conn = Connection::open_in_memory().expect("open db");
+        let unordered = [MIGRATIONS[1], MIGRATIONS[0]];
+        run_migrations(&conn, &unordered, |_| {}).expect_err("unordered versions");
+        assert_eq!(schema_version(&conn).expect("version"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_async_runner_reports_progress() {
+        init_sqlite();
+        let conn = Connection::open_in_memory().expect("open db");
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+
+        let (conn, version) = run_migrations_async(conn, MIGRATIONS, move |progress| {
+            sink.lock().unwrap().push(progress.version_applied);
+        })
+        .await
+        .expect("run migrations");
+
+        assert_eq!(version, 3);
+        assert_eq!(*seen.lock().unwrap(), [1, 2, 3]);
+        assert!(table_exists(&conn, "items"));
+    }
+}
@@ -38,6 +38,9 @@ use zeroize::Zeroizing;
 
 use super::connection::Connection;
 use super::error::{DbResult, Error};
+use super::statement::StepResult;
+use super::transaction::Transaction;
+use super::value::Value;
 
 /// Opens a database, applies the encryption key, and configures the connection.
 ///
@@ -125,6 +128,9 @@ fn configure_connection(conn: &Connection) -> DbResult<()> {
 /// encryption configuration. Since the destination is unencrypted, the
 /// backup API cannot be used.
 ///
+/// The copy keeps the source's `PRAGMA application_id` and `user_version`, so
+/// [`crate::inspect::inspect_file`] can tell what it is a backup of.
+///
 /// # Errors
 ///
 /// Returns `Error` if the `ATTACH`, copy, or `DETACH` fails.
@@ -133,6 +139,10 @@ pub fn export_plaintext_copy(
     dest_path: &Path,
     tables: &[&str],
 ) -> DbResult<()> {
+    let application_id =
+        conn.query_row("PRAGMA application_id", &[], |row| Ok(row.column_i64(0)))?;
+    let user_version =
+        conn.query_row("PRAGMA user_version", &[], |row| Ok(row.column_i64(0)))?;
     let dest_str = dest_path.to_string_lossy();
     let attach_sql = format!(
         "ATTACH DATABASE '{}' AS backup KEY '';",
@@ -147,6 +157,10 @@ pub fn export_plaintext_copy(
                 "CREATE TABLE backup.{table} AS SELECT * FROM {table};"
             ))?;
         }
+        tx.execute_batch(&format!(
+            "PRAGMA backup.application_id = {application_id};
+             PRAGMA backup.user_version = {user_version};"
+        ))?;
         tx.commit()
     })();
 
@@ -167,11 +181,10 @@ pub fn export_plaintext_copy(
 /// See [`export_plaintext_copy`] for why `ATTACH` + SQL is used instead of
 /// the `sqlite3_backup` API.
 ///
-/// **Schema migration:** The import uses `SELECT *`, so column changes are
-/// handled automatically as long as both sides share the same schema. If a
-/// caller's schema evolves (e.g. new columns with `NOT NULL` constraints),
-/// restoring an older backup into a newer schema will fail. When that happens,
-/// the caller needs version-aware import logic.
+/// **Schema migration:** Only the tables and columns present in the backup are
+/// copied, so restoring an older backup into a schema with added tables, or
+/// added columns that have defaults or are nullable, works. Removed or renamed
+/// columns still need version-aware import logic.
 ///
 /// # Errors
 ///
@@ -217,8 +230,12 @@ pub fn import_plaintext_copy(
         // a retry.
         let tx = conn.transaction()?;
         for table in tables {
+            // a table missing from the backup was added after it was taken
+            let Some(columns) = backup_columns(&tx, table)? else {
+                continue;
+            };
             tx.execute_batch(&format!(
-                "INSERT INTO {table} SELECT * FROM backup.{table};"
+                "INSERT INTO {table} ({columns}) SELECT {columns} FROM backup.{table};"
             ))?;
         }
         tx.commit()
@@ -232,6 +249,19 @@ pub fn import_plaintext_copy(
     Ok(())
 }
 
+/// Returns the comma-separated column list of `table` in the attached backup,
+/// or `None` if the backup has no such table.
+fn backup_columns(tx: &Transaction<'_>, table: &str) -> DbResult<Option<String>> {
+    let mut stmt =
+        tx.prepare("SELECT name FROM pragma_table_info(?1, 'backup') ORDER BY cid")?;
+    stmt.bind_values(&[Value::Text(table.to_string())])?;
+    let mut columns = Vec::new();
+    while let StepResult::Row(row) = stmt.step()? {
+        columns.push(format!("\"{}\"", row.column_text(0)));
+    }
+    Ok((!columns.is_empty()).then(|| columns.join(", ")))
+}
+
 /// Runs `PRAGMA integrity_check` and returns whether the database is healthy.
 ///
 /// # Errors
@@ -244,6 +274,53 @@ pub fn integrity_check(conn: &Connection) -> DbResult<bool> {
     Ok(result.trim() == "ok")
 }
 
+/// Re-encrypts the database open on `conn` under `new_key`.
+///
+/// `PRAGMA rekey` rewrites every page in a single transaction, so after a
+/// crash the database opens under either the old or the new key, never a mix.
+/// `sqlite3mc` cannot rekey in WAL mode, so the journal mode is switched to
+/// `DELETE` for the rekey and back to WAL afterwards; no other connection may
+/// have the database open.
+///
+/// # Errors
+///
+/// Returns `Error` if the journal mode cannot be switched, e.g. because
+/// another connection is open, or the rekey fails.
+pub fn rekey(conn: &Connection, new_key: &SecretBox<[u8; 32]>) -> DbResult<()> {
+    let key_hex = Zeroizing::new(hex::encode(new_key.expose_secret()));
+    let pragma = Zeroizing::new(format!("PRAGMA rekey = \"x'{}'\";", key_hex.as_str()));
+
+    let journal_mode = conn.query_row("PRAGMA journal_mode = DELETE;", &[], |row| {
+        Ok(row.column_text(0))
+    })?;
+    if !journal_mode.eq_ignore_ascii_case("delete") {
+        return Err(Error::new(
+            -1,
+            format!("cannot leave WAL mode to rekey (journal mode {journal_mode})"),
+        ));
+    }
+    let rekeyed = conn.execute_batch_zeroized(&pragma);
+    // restore WAL whether or not the rekey succeeded
+    conn.execute_batch("PRAGMA journal_mode = WAL;")?;
+    rekeyed?;
+    conn.execute_batch("SELECT count(*) FROM sqlite_master;")
+}
+
+/// Makes `sqlite3mc` encrypt databases on the VFS `real_vfs`, e.g. a browser
+/// OPFS VFS registered by the host, and makes that the default VFS.
+///
+/// `sqlite3mc` only wraps the VFS that is the default when it initialises, so
+/// a VFS registered later must be wrapped explicitly before databases on it
+/// are opened with [`open_encrypted`].
+///
+/// # Errors
+///
+/// Returns `Error` if `real_vfs` is not registered.
+#[cfg(target_arch = "wasm32")]
+pub fn use_encrypted_vfs(real_vfs: &str) -> DbResult<()> {
+    super::ffi::make_cipher_vfs_default(real_vfs)
+}
+
 #[cfg(test)]
 mod tests {
     use super::{
@@ -291,6 +368,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_rekey_switches_key() {
+        init_sqlite();
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("rekey.sqlite");
+        let old_key = SecretBox::init_with(|| [0x01u8; 32]);
+        let new_key = SecretBox::init_with(|| [0x02u8; 32]);
+
+        {
+            let conn = open_encrypted(&path, &old_key, false).expect("open");
+            conn.execute_batch(
+                "CREATE TABLE secret (id INTEGER PRIMARY KEY);
+                 INSERT INTO secret (id) VALUES (7);",
+            )
+            .expect("create table");
+            conn.rekey(&new_key).expect("rekey");
+            let journal_mode = conn
+                .query_row("PRAGMA journal_mode;", &[], |row| Ok(row.column_text(0)))
+                .expect("journal mode");
+            assert_eq!(journal_mode, "wal");
+        }
+
+        assert!(open_encrypted(&path, &old_key, false).is_err());
+        let conn = open_encrypted(&path, &new_key, false).expect("open with new key");
+        let id = conn
+            .query_row("SELECT id FROM secret", &[], |row| Ok(row.column_i64(0)))
+            .expect("query");
+        assert_eq!(id, 7);
+    }
+
     #[test]
     fn test_integrity_check() {
         init_sqlite();
@@ -394,4 +501,49 @@ mod tests {
             "expected non-empty-table error, got: {err}"
         );
     }
+
+    #[test]
+    fn test_cipher_import_older_backup_fills_new_column_defaults() {
+        init_sqlite();
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let src_path = dir.path().join("source.sqlite");
+        let dest_path = dir.path().join("backup.plain.sqlite");
+        let restore_path = dir.path().join("restore.sqlite");
+        let key = SecretBox::init_with(|| [0x33u8; 32]);
+
+        {
+            let conn = open_encrypted(&src_path, &key, false).expect("open src");
+            conn.execute_batch(
+                "CREATE TABLE widgets (id INTEGER PRIMARY KEY, val TEXT NOT NULL);",
+            )
+            .expect("create table");
+            conn.execute(
+                "INSERT INTO widgets (id, val) VALUES (?1, ?2)",
+                params![1_i64, "alpha"],
+            )
+            .expect("insert");
+            export_plaintext_copy(&conn, &dest_path, &["widgets"]).expect("export");
+        }
+
+        let conn = open_encrypted(&restore_path, &key, false).expect("open restore");
+        conn.execute_batch(
+            "CREATE TABLE widgets (
+                id INTEGER PRIMARY KEY,
+                val TEXT NOT NULL,
+                revision INTEGER NOT NULL DEFAULT 7
+            );",
+        )
+        .expect("create table");
+        import_plaintext_copy(&conn, &dest_path, &["widgets"]).expect("import");
+
+        let (val, revision) = conn
+            .query_row(
+                "SELECT val, revision FROM widgets WHERE id = 1",
+                &[],
+                |row| Ok((row.column_text(0), row.column_i64(1))),
+            )
+            .expect("query");
+        assert_eq!(val, "alpha");
+        assert_eq!(revision, 7);
+    }
 }
@@ -5,6 +5,8 @@
 
 use std::path::Path;
 
+use secrecy::SecretBox;
+
 use super::error::{DbResult, Error};
 use super::ffi::{self, RawDb};
 use super::statement::{Row, Statement, StepResult};
@@ -148,6 +150,27 @@ impl Connection {
         Transaction::begin(self, true)
     }
 
+    /// Frees as much of the connection's heap memory (mostly the page
+    /// cache) as possible. The cache refills on the next reads.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error` if the pragma fails.
+    pub fn release_memory(&self) -> DbResult<()> {
+        self.db.exec("PRAGMA shrink_memory;")
+    }
+
+    /// Re-encrypts the database under `new_key` in place, without exporting
+    /// and re-importing its data, see [`cipher::rekey`](super::cipher::rekey).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error` if another connection has the database open or the
+    /// rekey fails; the database then keeps its old key.
+    pub fn rekey(&self, new_key: &SecretBox<[u8; 32]>) -> DbResult<()> {
+        super::cipher::rekey(self, new_key)
+    }
+
     /// Returns the rowid of the most recent successful INSERT.
     #[allow(dead_code)]
     #[must_use]
@@ -383,6 +383,27 @@ impl Drop for RawStmt<'_> {
     }
 }
 
+// -- VFS registration ---------------------------------------------------------
+
+/// Registers an encrypting `sqlite3mc` VFS on top of the already registered
+/// VFS `real_vfs` and makes it the default, so databases opened afterwards on
+/// `real_vfs` storage are encrypted.
+#[cfg(target_arch = "wasm32")]
+pub fn make_cipher_vfs_default(real_vfs: &str) -> DbResult<()> {
+    let c_name = to_cstring(real_vfs)?;
+    // Safety: c_name is a valid null-terminated string that outlives the call;
+    // sqlite3mc copies the name.
+    let rc = unsafe { raw::sqlite3mc_vfs_create(c_name.as_ptr(), 1) };
+    if rc == SQLITE_OK as c_int {
+        Ok(())
+    } else {
+        Err(Error::new(
+            rc,
+            format!("failed to register cipher VFS over {real_vfs}"),
+        ))
+    }
+}
+
 // -- Helpers (private) --------------------------------------------------------
 
 fn to_cstring(s: &str) -> DbResult<CString> {
@@ -630,4 +651,10 @@ mod raw {
     pub unsafe fn sqlite3_last_insert_rowid(db: *mut c_void) -> i64 {
         wasm::sqlite3_last_insert_rowid(db.cast())
     }
+    pub unsafe fn sqlite3mc_vfs_create(
+        z_vfs_real: *const c_char,
+        make_default: c_int,
+    ) -> c_int {
+        wasm::sqlite3mc_vfs_create(z_vfs_real.cast(), make_default)
+    }
 }
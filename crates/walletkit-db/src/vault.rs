@@ -54,11 +54,71 @@ impl Vault {
         Ok(Self { conn })
     }
 
+    /// Opens an existing encrypted database read-only, for readers that must
+    /// not wait for a writer.
+    ///
+    /// In WAL mode each read transaction sees a consistent snapshot of the
+    /// last commit, so an open write transaction on another connection
+    /// neither blocks these reads nor leaks into them. No schema callback or
+    /// integrity check runs; open the writer with [`Vault::open`] first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StoreError::Db`] if the file is missing or cannot be opened
+    /// or keyed.
+    pub fn open_read_only(
+        db_path: &Path,
+        key: &SecretBox<[u8; 32]>,
+    ) -> StoreResult<Self> {
+        let conn = cipher::open_encrypted(db_path, key, true)?;
+        Ok(Self { conn })
+    }
+
     /// Borrows the underlying connection.
     #[must_use]
     pub const fn connection(&self) -> &Connection {
         &self.conn
     }
+
+    /// Re-encrypts the database under `new_key`, see [`Connection::rekey`]. Only
+    /// this connection may have the database open.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StoreError::Db`] if the rekey fails; the database then keeps
+    /// its old key.
+    pub fn rekey(&self, new_key: &SecretBox<[u8; 32]>) -> StoreResult<()> {
+        self.conn.rekey(new_key).map_err(StoreError::from)
+    }
+
+    /// Copies the WAL into the database file and truncates it, so the file
+    /// is complete on its own once the connection is closed.
+    ///
+    /// Frames still needed by a reader on another connection stay in the
+    /// WAL; this is not reported as an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StoreError::Db`] if the checkpoint cannot run.
+    pub fn checkpoint(&self) -> StoreResult<()> {
+        self.conn
+            .execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")
+            .map_err(StoreError::from)
+    }
+
+    /// Releases memory the connection holds for reuse: frees its page cache
+    /// and runs a `PASSIVE` WAL checkpoint, which never waits for readers or
+    /// writers. Nothing is lost; later reads repopulate the cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StoreError::Db`] if either pragma fails.
+    pub fn release_memory(&self) -> StoreResult<()> {
+        self.conn.release_memory()?;
+        self.conn
+            .execute_batch("PRAGMA wal_checkpoint(PASSIVE);")
+            .map_err(StoreError::from)
+    }
 }
 
 #[cfg(test)]
@@ -105,4 +165,104 @@ mod tests {
         let err = Vault::open(&db_path, &wrong, |_| Ok(())).expect_err("wrong key");
         assert!(matches!(err, StoreError::Db(_)));
     }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_read_only_vault_reads_last_commit() {
+        init_sqlite();
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let db_path = dir.path().join("vault.sqlite");
+        let key = SecretBox::init_with(|| [0x33u8; 32]);
+        let count = |vault: &Vault| {
+            vault
+                .connection()
+                .query_row("SELECT COUNT(*) FROM items", &[], |row| {
+                    Ok(row.column_i64(0))
+                })
+                .expect("count")
+        };
+
+        let writer = Vault::open(&db_path, &key, |conn| {
+            conn.execute_batch(
+                "CREATE TABLE items (id INTEGER PRIMARY KEY);
+                 INSERT INTO items (id) VALUES (1);",
+            )
+        })
+        .expect("open vault");
+        let reader = Vault::open_read_only(&db_path, &key).expect("open reader");
+
+        let tx = writer.connection().transaction_immediate().expect("begin");
+        tx.execute("INSERT INTO items (id) VALUES (2)", &[])
+            .expect("insert");
+        // the open write transaction neither blocks nor leaks into reads
+        assert_eq!(count(&reader), 1);
+        tx.commit().expect("commit");
+        assert_eq!(count(&reader), 2);
+
+        let err = reader
+            .connection()
+            .execute("INSERT INTO items (id) VALUES (3)", &[])
+            .expect_err("read-only");
+        assert!(err.to_string().contains("readonly"), "{err}");
+        assert!(
+            Vault::open_read_only(&dir.path().join("missing.sqlite"), &key).is_err()
+        );
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_checkpoint_truncates_wal() {
+        init_sqlite();
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let db_path = dir.path().join("vault.sqlite");
+        let wal_path = dir.path().join("vault.sqlite-wal");
+        let key = SecretBox::init_with(|| [0x44u8; 32]);
+
+        let vault = Vault::open(&db_path, &key, |conn| {
+            conn.execute_batch(
+                "CREATE TABLE items (id INTEGER PRIMARY KEY);
+                 INSERT INTO items (id) VALUES (1);",
+            )
+        })
+        .expect("open vault");
+        assert!(std::fs::metadata(&wal_path).expect("wal").len() > 0);
+
+        vault.checkpoint().expect("checkpoint");
+        assert_eq!(std::fs::metadata(&wal_path).expect("wal").len(), 0);
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_release_memory_keeps_data_readable() {
+        init_sqlite();
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let db_path = dir.path().join("vault.sqlite");
+        let key = SecretBox::init_with(|| [0x45u8; 32]);
+
+        let vault = Vault::open(&db_path, &key, |conn| {
+            conn.execute_batch(
+                "CREATE TABLE items (id INTEGER PRIMARY KEY);
+                 INSERT INTO items (id) VALUES (1);",
+            )
+        })
+        .expect("open vault");
+        let count = |vault: &Vault| {
+            vault
+                .connection()
+                .query_row("SELECT COUNT(*) FROM items", &[], |row| {
+                    Ok(row.column_i64(0))
+                })
+                .expect("count")
+        };
+        assert_eq!(count(&vault), 1);
+
+        vault.release_memory().expect("release memory");
+        assert_eq!(count(&vault), 1);
+        vault
+            .connection()
+            .execute("INSERT INTO items (id) VALUES (2)", &[])
+            .expect("insert");
+        vault.release_memory().expect("release memory again");
+        assert_eq!(count(&vault), 2);
+    }
 }
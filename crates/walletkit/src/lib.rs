@@ -5,4 +5,9 @@ walletkit_core::uniffi_reexport_scaffolding!();
 
 pub use walletkit_core::*;
 
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static ALLOC: walletkit_core::alloc_stats::CountingAllocator =
+    walletkit_core::alloc_stats::CountingAllocator;
+
 uniffi::setup_scaffolding!("walletkit");